@@ -5,24 +5,234 @@
 // <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
+#[cfg(feature = "serialization")]
+extern crate serde;
 
 use std::io::{Error, ErrorKind};
-use std::net::{SocketAddr, ToSocketAddrs};
+use std::net::{IpAddr, SocketAddr, ToSocketAddrs};
+use std::ops::RangeInclusive;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::time::Duration;
 use traits::socket::Socket;
-use shared::stats::{StatsCollector, Stats};
-use shared::udp_socket::UdpSocket;
-use super::{Config, ClientStream, Connection, Handler, MessageKind, tick};
+use shared::control::{u32_from_bytes, u32_to_bytes, u64_from_bytes, u64_to_bytes};
+use shared::stats::{ClientStats, PacketDropReason, StatsCollector, Stats};
+use shared::udp_socket::{UdpSocket, canonical_addr, unspecified_addr};
+use shared::network_simulator::SimulatedSocket;
+#[cfg(feature = "encryption")]
+use shared::resumption_ticket::ResumptionTicket;
+#[cfg(feature = "serialization")]
+use self::serde::{Deserialize, Serialize};
+use super::{
+    Config, ClientStream, Connection, ConnectionID, ConnectionState, Handler, HostMigration,
+    MessageHandle, MessageKind, ResumptionToken, tick
+};
+
+/// Commands send to a connected `Client` via its `ClientHandle`s.
+#[derive(Debug)]
+enum Command {
+    Close,
+    Send(MessageKind, Vec<u8>)
+}
+
+/// A cloneable handle which allows other threads, i.e. a UI thread, to
+/// control a connected `Client`.
+///
+/// Commands are applied at the start of the client's next tick and once more
+/// before it sends its packet.
+///
+/// # Examples
+///
+/// ```
+/// use std::thread;
+/// use cobalt::{Client, Config, Handler, MessageKind};
+///
+/// struct GameClient;
+/// impl Handler<Client> for GameClient {}
+///
+/// let mut client = Client::new(Config::default());
+/// let handle = client.handle();
+///
+/// let ui = thread::spawn(move || {
+///     handle.send(MessageKind::Reliable, b"Goodbye".to_vec()).unwrap();
+///     handle.close().unwrap();
+/// });
+///
+/// client.connect(&mut GameClient, "127.0.0.1:7156").unwrap();
+/// ui.join().unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct ClientHandle {
+    commands: Sender<Command>
+}
+
+impl ClientHandle {
+
+    /// Requests the client to close its connection, exiting its tick loop
+    /// like `Client::close`.
+    ///
+    /// Clients which are driven via the synchronous API are closed via
+    /// `Client::close_sync` once the command is applied.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `BrokenPipe` in case the client was dropped.
+    pub fn close(&self) -> Result<(), Error> {
+        self.command(Command::Close)
+    }
+
+    /// Requests the client to send a message of the specified `kind` along
+    /// with its `payload` to the server.
+    ///
+    /// Messages which are requested while the client is not connected are
+    /// sent once it connects, messages which are still pending when the
+    /// client closes are discarded.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `BrokenPipe` in case the client was dropped.
+    pub fn send(&self, kind: MessageKind, payload: Vec<u8>) -> Result<(), Error> {
+        self.command(Command::Send(kind, payload))
+    }
+
+    fn command(&self, command: Command) -> Result<(), Error> {
+        self.commands.send(command).map_err(|_| {
+            Error::new(ErrorKind::BrokenPipe, "")
+        })
+    }
+
+}
+
+/// The minimal state of a client's connection, exported via
+/// `Client::export_session()`, which allows a restarted client process to
+/// resume its session with the server via `Client::resume()`.
+///
+/// Sessions are only available for connections which the server issued a
+/// `ResumptionToken` for, see `Config::resumption_threshold`. Since the
+/// token grants access to the connection, it must be stored as securely as
+/// any other credential when persisting it via `ClientSession::to_bytes`.
+///
+/// # Examples
+///
+/// ```
+/// use cobalt::{ClientSession, ConnectionID, ResumptionToken};
+///
+/// let session = ClientSession {
+///     server_address: "127.0.0.1:7156".parse().unwrap(),
+///     resumption_token: ResumptionToken {
+///         id: ConnectionID(1234),
+///         secret: 0x0102_0304_0506_0708
+///     },
+///     local_seq_number: 12,
+///     remote_seq_number: 34,
+///     tick_number: 5678
+/// };
+///
+/// let bytes = session.to_bytes();
+/// assert_eq!(ClientSession::from_bytes(&bytes), Some(session));
+/// ```
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+#[cfg_attr(feature = "serialization", derive(Serialize, Deserialize))]
+pub struct ClientSession {
+
+    /// The address of the server the session was established with.
+    pub server_address: SocketAddr,
+
+    /// The token which resumes the session's connection, including its id.
+    pub resumption_token: ResumptionToken,
+
+    /// The sequence number of the next packet send to the server.
+    pub local_seq_number: u32,
+
+    /// The sequence number of the last packet received from the server.
+    pub remote_seq_number: u32,
+
+    /// The number of the client's current tick.
+    pub tick_number: u32
+
+}
+
+/// Version of the format produced by `ClientSession::to_bytes`.
+const SESSION_FORMAT_VERSION: u8 = 1;
+
+impl ClientSession {
+
+    /// Serializes the session for persistent storage.
+    ///
+    /// The bytes start with a format version, so sessions which were stored
+    /// by other versions of the library are rejected by
+    /// `ClientSession::from_bytes` instead of being misread.
+    pub fn to_bytes(&self) -> Vec<u8> {
+
+        let token = self.resumption_token;
+        let mut bytes = Vec::with_capacity(37);
+        bytes.push(SESSION_FORMAT_VERSION);
+        bytes.extend_from_slice(&u32_to_bytes(token.id.0));
+        bytes.extend_from_slice(&u64_to_bytes(token.secret));
+        bytes.push(self.local_seq_number as u8);
+        bytes.push(self.remote_seq_number as u8);
+        bytes.extend_from_slice(&u32_to_bytes(self.tick_number));
+        bytes.push((self.server_address.port() >> 8) as u8);
+        bytes.push(self.server_address.port() as u8);
+
+        match self.server_address.ip() {
+            IpAddr::V4(ip) => bytes.extend_from_slice(&ip.octets()),
+            IpAddr::V6(ip) => bytes.extend_from_slice(&ip.octets())
+        }
+
+        bytes
+
+    }
+
+    /// Parses a session which was serialized via `ClientSession::to_bytes`.
+    ///
+    /// Returns `None` in case the bytes are malformed or were serialized in
+    /// a different format version.
+    pub fn from_bytes(bytes: &[u8]) -> Option<ClientSession> {
+
+        if bytes.first() != Some(&SESSION_FORMAT_VERSION) {
+            return None;
+        }
+
+        let bytes = &bytes[1..];
+        let ip = match bytes.len() {
+            24 => {
+                let mut octets = [0; 4];
+                octets.copy_from_slice(&bytes[20..]);
+                IpAddr::from(octets)
+            },
+            36 => {
+                let mut octets = [0; 16];
+                octets.copy_from_slice(&bytes[20..]);
+                IpAddr::from(octets)
+            },
+            _ => return None
+        };
+
+        Some(ClientSession {
+            server_address: SocketAddr::new(ip, (bytes[18] as u16) << 8 | bytes[19] as u16),
+            resumption_token: ResumptionToken {
+                id: ConnectionID(u32_from_bytes(&bytes[0..4])),
+                secret: u64_from_bytes(&bytes[4..12])
+            },
+            local_seq_number: bytes[12] as u32,
+            remote_seq_number: bytes[13] as u32,
+            tick_number: u32_from_bytes(&bytes[14..18])
+        })
+
+    }
+
+}
 
 /// Implementation of a single-server client with handler based event dispatch.
 ///
 /// There are two ways of creating and connection a client instance:
 ///
 /// 1. Blocking mode to be used in it dedicated thread, with asynchronous
-/// `Handler` callbacks, available via the methods **without** the `sync`
-/// postfix.
+///    `Handler` callbacks, available via the methods **without** the `sync`
+///    postfix.
 ///
 /// 2. Non-Blocking mode, with synchronous `Handler` callbacks, available via
-/// the methods **with** the `sync` postfix.
+///    the methods **with** the `sync` postfix.
 #[derive(Debug)]
 pub struct Client {
     closed: bool,
@@ -30,20 +240,61 @@ pub struct Client {
     config: Config,
     peer_address: Option<SocketAddr>,
     local_address: Option<SocketAddr>,
-    statistics: StatsCollector
+    bind_address: Option<SocketAddr>,
+    bind_ports: Option<RangeInclusive<u16>>,
+    server_address: Option<SocketAddr>,
+    server_host: Option<String>,
+    statistics: StatsCollector,
+    connection_stats: ClientStats,
+    unscheduled_bytes_sent: u32,
+    tick_number: u32,
+    resumption_token: Option<ResumptionToken>,
+    session: Option<ClientSession>,
+    #[cfg(feature = "encryption")]
+    resumption_ticket: Option<ResumptionTicket>,
+    #[cfg(feature = "connect_token")]
+    connect_token: Option<Vec<u8>>,
+    host_migration: Option<HostMigration>,
+    command_sender: Sender<Command>,
+    commands: Receiver<Command>
 }
 
 impl Client {
 
     /// Creates a new client with the given configuration.
     pub fn new(config: Config) -> Client {
+        let (command_sender, commands) = channel();
         Client {
             closed: false,
             running: false,
-            config: config,
+            config,
             peer_address: None,
             local_address: None,
-            statistics: StatsCollector::new(config)
+            bind_address: None,
+            bind_ports: None,
+            server_address: None,
+            server_host: None,
+            statistics: StatsCollector::new(config),
+            connection_stats: ClientStats::default(),
+            unscheduled_bytes_sent: 0,
+            tick_number: 0,
+            resumption_token: None,
+            session: None,
+            #[cfg(feature = "encryption")]
+            resumption_ticket: None,
+            #[cfg(feature = "connect_token")]
+            connect_token: None,
+            host_migration: None,
+            command_sender,
+            commands
+        }
+    }
+
+    /// Returns a new handle which allows other threads to control the client
+    /// while it is connected.
+    pub fn handle(&self) -> ClientHandle {
+        ClientHandle {
+            commands: self.command_sender.clone()
         }
     }
 
@@ -57,11 +308,133 @@ impl Client {
         self.local_address.ok_or_else(|| Error::new(ErrorKind::AddrNotAvailable, ""))
     }
 
+    /// Overrides the local address which the client binds its socket to with
+    /// its next `connect` or `connect_sync` call, i.e. in order to pick the
+    /// interface of a multi-homed machine or a port allowed by a firewall.
+    ///
+    /// A port of `0` lets the operating system pick a free port. Setting the
+    /// address to `None` binds to the unspecified address of the server's
+    /// address family.
+    pub fn set_bind_addr(&mut self, addr: Option<SocketAddr>) {
+        self.bind_address = addr;
+    }
+
+    /// Restricts the local ports which the client binds its socket to with
+    /// its next `connect` or `connect_sync` call, overriding the port of the
+    /// bind address.
+    ///
+    /// The ports are tried in order and the first one which is free is used.
+    pub fn set_bind_ports(&mut self, ports: Option<RangeInclusive<u16>>) {
+        self.bind_ports = ports;
+    }
+
     /// Returns statistics (i.e. bandwidth usage) for the last second.
     pub fn stats(&mut self) -> Stats {
         self.statistics.average()
     }
 
+    /// Returns a snapshot of the roundtrip time, packet loss, totals and
+    /// queue depths of the client's connection.
+    ///
+    /// The snapshot is updated after each time packets were received or
+    /// send, so it can also be queried from within the `Handler` callbacks.
+    pub fn connection_stats(&self) -> ClientStats {
+        self.connection_stats
+    }
+
+    /// Returns the number of the current tick, which is send along with data
+    /// packets when `Config::packet_tick_numbers` is enabled.
+    ///
+    /// The number is increased by one after each tick's packets were send.
+    pub fn tick_number(&self) -> u32 {
+        self.tick_number
+    }
+
+    /// Overrides the number of the current tick, i.e. in order to align it
+    /// with the ticks of a game's simulation.
+    pub fn set_tick_number(&mut self, tick: u32) {
+        self.tick_number = tick;
+    }
+
+    /// Returns the resumption token of the client's last lost connection.
+    pub fn resumption_token(&self) -> Option<ResumptionToken> {
+        self.resumption_token
+    }
+
+    /// Overrides the resumption token which will be presented to the server
+    /// by the client's next connection attempt.
+    ///
+    /// Setting the token to `None` will make the client establish a new
+    /// connection instead.
+    pub fn set_resumption_token(&mut self, token: Option<ResumptionToken>) {
+        self.resumption_token = token;
+    }
+
+    /// Returns the session of the client's current or last lost connection,
+    /// in order to persist it across a restart of the client process.
+    ///
+    /// The session is updated after each time packets were received or
+    /// send. Returns `None` in case the server did not issue a resumption
+    /// token for the connection, or in case the connection was closed instead
+    /// of being lost.
+    pub fn export_session(&self) -> Option<ClientSession> {
+        self.session
+    }
+
+    /// Imports a session which was exported via `Client::export_session()`,
+    /// i.e. by a previous instance of the client process.
+    ///
+    /// The session's connection is resumed by the client's next connection
+    /// attempt, which continues the session's packet sequence and tick
+    /// number. `Client::reconnect_sync` connects to the session's server.
+    pub fn resume(&mut self, session: ClientSession) {
+        self.resumption_token = Some(session.resumption_token);
+        self.server_address = Some(session.server_address);
+        self.server_host = None;
+        self.tick_number = session.tick_number;
+        self.session = Some(session);
+    }
+
+    /// Returns the resumption ticket of the client's last connection which
+    /// was not closed programmatically.
+    ///
+    /// > Note: This method is feature-gated and will only be included when the
+    /// > `encryption` feature is enabled.
+    #[cfg(feature = "encryption")]
+    pub fn resumption_ticket(&self) -> Option<ResumptionTicket> {
+        self.resumption_ticket.clone()
+    }
+
+    /// Overrides the resumption ticket which will be presented to the server
+    /// by the client's next connection attempt, i.e. with a ticket that was
+    /// persisted before the application restarted.
+    ///
+    /// Resumption tokens take precedence over tickets.
+    ///
+    /// > Note: This method is feature-gated and will only be included when the
+    /// > `encryption` feature is enabled.
+    #[cfg(feature = "encryption")]
+    pub fn set_resumption_ticket(&mut self, ticket: Option<ResumptionTicket>) {
+        self.resumption_ticket = ticket;
+    }
+
+    /// Sets the sealed connect token issued by a backend, which will be
+    /// presented to the server by the client's connection attempts.
+    ///
+    /// > Note: This method is feature-gated and will only be included when the
+    /// > `connect_token` feature is enabled.
+    #[cfg(feature = "connect_token")]
+    pub fn set_connect_token(&mut self, token: Option<Vec<u8>>) {
+        self.connect_token = token;
+    }
+
+    /// Returns the outcome of the host election which took place when the
+    /// client's last connection to a session host with `host_migration`
+    /// enabled was lost.
+    pub fn host_migration(&self) -> Option<&HostMigration> {
+        self.host_migration.as_ref()
+    }
+
     /// Returns a copy of the client's current configuration.
     pub fn config(&self) -> Config {
         self.config
@@ -86,16 +459,12 @@ impl Client {
     ///
     /// This method starts the tick loop, blocking the calling thread.
     pub fn connect<A: ToSocketAddrs>(
-        &mut self, handler: &mut Handler<Client>, addr: A
+        &mut self, handler: &mut dyn Handler<Client>, addr: A
 
     ) -> Result<(), Error> {
 
-        let socket = try!(UdpSocket::new(
-            "0.0.0.0:0",
-            self.config.packet_max_size
-        ));
-
-        self.connect_from_socket(handler, addr, socket)
+        let mut state = self.connect_sync(handler, addr)?;
+        self.run(handler, &mut state)
 
     }
 
@@ -109,30 +478,45 @@ impl Client {
     ///
     /// This method starts the tick loop, blocking the calling thread.
     pub fn connect_from_socket<S: Socket, A: ToSocketAddrs>(
-        &mut self, handler: &mut Handler<Client>, addr: A, socket: S
+        &mut self, handler: &mut dyn Handler<Client>, addr: A, socket: S
 
     ) -> Result<(), Error> {
 
-        let mut state = try!(
-            self.connect_from_socket_sync(handler, addr, socket)
-        );
-
-        let mut tick_overflow = 0;
-        while self.running {
+        let mut state = self.connect_from_socket_sync(handler, addr, socket)?;
+        self.run(handler, &mut state)
 
-            let tick_start = tick::start();
-            let tick_delay = 1000000000 / self.config.send_rate;
-
-            self.receive_sync(handler, &mut state, tick_delay / 1000000);
-            self.tick_sync(handler, &mut state);
-            self.send_sync(handler, &mut state);
-
-            tick::end(tick_delay, tick_start, &mut tick_overflow, &self.config);
+    }
 
-        }
+    /// Establishes a connection with the server at the specified host name,
+    /// i.e. `"play.example.com:7156"`, by creating a local socket for
+    /// message sending.
+    ///
+    /// The host name is remembered and resolved anew by `Client::reconnect`,
+    /// so servers behind dynamic DNS or failover records are reached at
+    /// their current address.
+    ///
+    /// This method starts the tick loop, blocking the calling thread.
+    pub fn connect_host(
+        &mut self, handler: &mut dyn Handler<Client>, host: &str
 
-        self.close_sync(handler, &mut state)
+    ) -> Result<(), Error> {
+        let mut state = self.connect_host_sync(handler, host)?;
+        self.run(handler, &mut state)
+    }
 
+    /// Establishes a new connection with the server the client was last
+    /// connected to, resolving its host name anew in case the connection
+    /// was established via `Client::connect_host`.
+    ///
+    /// This method starts the tick loop, blocking the calling thread.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `NotConnected` in case the client was never
+    /// connected to a server.
+    pub fn reconnect(&mut self, handler: &mut dyn Handler<Client>) -> Result<(), Error> {
+        let mut state = self.reconnect_sync(handler)?;
+        self.run(handler, &mut state)
     }
 
     /// Asynchronously closes the connection to the server.
@@ -163,16 +547,41 @@ impl Client {
     /// This method returns a `ClientState` instance for this client, which can
     /// be used with other synchronous `Client` methods.
     pub fn connect_sync<A: ToSocketAddrs>(
-        &mut self, handler: &mut Handler<Client>, addr: A
+        &mut self, handler: &mut dyn Handler<Client>, addr: A
 
     ) -> Result<ClientState<UdpSocket>, Error> {
 
-        let socket = try!(UdpSocket::new(
-            "0.0.0.0:0",
-            self.config.packet_max_size
-        ));
+        let mut peer_addrs: Vec<SocketAddr> = Vec::new();
+        for addr in addr.to_socket_addrs()? {
+            let addr = canonical_addr(addr);
+            if !peer_addrs.contains(&addr) {
+                peer_addrs.push(addr);
+            }
+        }
+
+        if peer_addrs.is_empty() {
+            return Err(Error::new(ErrorKind::AddrNotAvailable, ""));
+        }
+
+        // Bind to the address family of the server
+        let socket = self.bind_socket(peer_addrs[0])?;
+        let mut state = self.connect_from_socket_sync(handler, peer_addrs[0], socket)?;
+
+        // Race the connection against the remaining addresses, each of
+        // which is tried after a staggered delay
+        let delay = self.config.connect_attempt_delay * self.config.send_rate / 1000;
+        for (index, addr) in peer_addrs.into_iter().enumerate().skip(1) {
+            if let Ok(socket) = self.bind_socket(addr) {
+                state.attempts.push(ConnectAttempt {
+                    socket,
+                    connection: None,
+                    peer_address: addr,
+                    delay: delay * index as u32
+                });
+            }
+        }
 
-        self.connect_from_socket_sync(handler, addr, socket)
+        Ok(state)
 
     }
 
@@ -187,37 +596,100 @@ impl Client {
     /// This method returns a `ClientState` instance for this client, which can
     /// be used with other synchronous `Client` methods.
     pub fn connect_from_socket_sync<A: ToSocketAddrs, S: Socket>(
-        &mut self, handler: &mut Handler<Client>, addr: A, socket: S
+        &mut self, handler: &mut dyn Handler<Client>, addr: A, socket: S
 
     ) -> Result<ClientState<S>, Error> {
 
-        let peer_addr = try!(addr.to_socket_addrs()).nth(0).unwrap();
-        let local_addr = try!(socket.local_addr());
+        let peer_addr = canonical_addr(addr.to_socket_addrs()?.next().unwrap());
+        let local_addr = socket.local_addr()?;
 
         self.peer_address = Some(peer_addr);
         self.local_address = Some(local_addr);
+        self.server_address = Some(peer_addr);
+        self.server_host = None;
         self.statistics.reset();
+        self.connection_stats = ClientStats::default();
         self.running = true;
         self.closed = false;
+        self.host_migration = None;
 
-        let connection = Connection::new(
-            self.config,
-            local_addr,
-            peer_addr,
-            handler.rate_limiter(&self.config)
-        );
+        let mut connection = self.create_connection(handler, local_addr, peer_addr);
+
+        // Try to resume the last lost connection
+        if let Some(token) = self.resumption_token.take() {
+            connection.resume(token);
+
+            // Continue the packet sequence of the connection's session
+            if let Some(session) = self.session.filter(|s| s.resumption_token == token) {
+                connection.restore_seq_numbers(
+                    session.local_seq_number,
+                    session.remote_seq_number
+                );
+            }
+
+        } else {
+            self.resume_with_ticket(&mut connection);
+        }
+
+        self.session = None;
+        self.update_session(&connection);
 
         handler.connect(self);
 
-        Ok(ClientState::new(socket, connection, peer_addr))
+        Ok(ClientState::new(SimulatedSocket::new(socket, self.config), connection, peer_addr))
+
+    }
+
+    /// Establishes a connection with the server at the specified host name,
+    /// i.e. `"play.example.com:7156"`, by creating a local socket for
+    /// message sending.
+    ///
+    /// The host name is remembered and resolved anew by
+    /// `Client::reconnect_sync`, so servers behind dynamic DNS or failover
+    /// records are reached at their current address.
+    ///
+    /// This method returns a `ClientState` instance for this client, which can
+    /// be used with other synchronous `Client` methods.
+    pub fn connect_host_sync(
+        &mut self, handler: &mut dyn Handler<Client>, host: &str
+
+    ) -> Result<ClientState<UdpSocket>, Error> {
+        let state = self.connect_sync(handler, host)?;
+        self.server_host = Some(host.to_string());
+        Ok(state)
+    }
+
+    /// Establishes a new connection with the server the client was last
+    /// connected to, resolving its host name anew in case the connection
+    /// was established via `Client::connect_host_sync`.
+    ///
+    /// The resumption token of a lost connection is presented to the server
+    /// just like with any other connection attempt.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `NotConnected` in case the client was never
+    /// connected to a server.
+    pub fn reconnect_sync(
+        &mut self, handler: &mut dyn Handler<Client>
 
+    ) -> Result<ClientState<UdpSocket>, Error> {
+        if let Some(host) = self.server_host.clone() {
+            self.connect_host_sync(handler, &host)
+
+        } else if let Some(addr) = self.server_address {
+            self.connect_sync(handler, addr)
+
+        } else {
+            Err(Error::new(ErrorKind::NotConnected, ""))
+        }
     }
 
     /// Receives all currently buffered incoming packet from the underlying
     /// connection.
     pub fn receive_sync<S: Socket>(
         &mut self,
-        handler: &mut Handler<Client>, state: &mut ClientState<S>,
+        handler: &mut dyn Handler<Client>, state: &mut ClientState<S>,
         tick_delay: u32
     ) {
 
@@ -225,23 +697,53 @@ impl Client {
         // address feeding them into our connection object for parsing
         if !self.closed {
             let mut bytes_received = 0;
-            while let Ok((addr, packet)) = state.socket.try_recv() {
-                if addr == state.peer_address {
-                    bytes_received += packet.len();
-                    state.connection.receive_packet(
-                        packet, tick_delay, self, handler
-                    );
+            if state.attempts.is_empty() {
+                bytes_received += self.receive_from(
+                    handler,
+                    &mut state.socket, &mut state.connection, state.peer_address,
+                    tick_delay
+                );
+
+            // Racing connections report their events once one of them was
+            // selected
+            } else {
+                bytes_received += self.receive_from(
+                    &mut AttemptHandler,
+                    &mut state.socket, &mut state.connection, state.peer_address,
+                    tick_delay
+                );
+                for attempt in &mut state.attempts {
+                    if let Some(ref mut connection) = attempt.connection {
+                        bytes_received += self.receive_from(
+                            &mut AttemptHandler,
+                            &mut attempt.socket, connection, attempt.peer_address,
+                            tick_delay
+                        );
+                    }
                 }
+                self.select_attempt(handler, state);
             }
             self.statistics.set_bytes_received(bytes_received as u32);
+            self.connection_stats.bytes_received += bytes_received as u64;
+
+            // Promptly acknowledge any received data
+            self.unscheduled_bytes_sent += state.connection.send_ack_packet(
+                &mut state.socket, &state.peer_address, self, handler
+            );
+            self.rebind_on_send_error(handler, state);
+            self.update_connection_stats(&state.connection);
+            self.update_session(&state.connection);
+
+            handler.after_receive(self);
         }
 
     }
 
     /// Performs exactly on tick of the underlying connection.
     pub fn tick_sync<S: Socket>(
-        &mut self, handler: &mut Handler<Client>, state: &mut ClientState<S>
+        &mut self, handler: &mut dyn Handler<Client>, state: &mut ClientState<S>
     ) {
+        self.apply_commands(handler, state);
         if !self.closed {
             handler.tick_connection(self, &mut state.connection);
         }
@@ -249,24 +751,106 @@ impl Client {
 
     /// Sends exactly on outgoing packet from the underlying connection.
     pub fn send_sync<S: Socket>(
-        &mut self, handler: &mut Handler<Client>, state: &mut ClientState<S>
+        &mut self, handler: &mut dyn Handler<Client>, state: &mut ClientState<S>
     ) {
+        self.apply_commands(handler, state);
         if !self.closed {
-            let bytes_sent = state.connection.send_packet(
-                &mut state.socket, &state.peer_address, self, handler
-            );
-            self.statistics.set_bytes_sent(bytes_sent);
+            handler.before_send(self);
+            state.connection.set_tick_number(self.tick_number);
+            let bytes_sent = if state.attempts.is_empty() {
+                state.connection.send_packet(
+                    &mut state.socket, &state.peer_address, self, handler
+                )
+
+            } else {
+                self.send_attempts(handler, state, true)
+            };
+            self.rebind_on_send_error(handler, state);
+            self.tick_number = self.tick_number.wrapping_add(1);
+            self.statistics.set_bytes_sent(bytes_sent + self.unscheduled_bytes_sent);
+            self.connection_stats.bytes_sent += u64::from(bytes_sent + self.unscheduled_bytes_sent);
+            self.unscheduled_bytes_sent = 0;
             self.statistics.tick();
             state.stats = self.statistics.average();
+            self.update_connection_stats(&state.connection);
+            self.update_session(&state.connection);
+        }
+    }
+
+    /// Immediately sends an additional packet from the underlying connection
+    /// outside of the regular tick schedule, containing all messages queued
+    /// so far, i.e. to not delay latency critical messages until the next
+    /// `send_sync` call.
+    ///
+    /// The packet is subject to the same congestion and bandwidth limits as
+    /// all other packets. Like any other packet, it dismisses received
+    /// messages which have not yet been consumed unless a receive watermark
+    /// was set on the connection.
+    pub fn flush_sync<S: Socket>(
+        &mut self, handler: &mut dyn Handler<Client>, state: &mut ClientState<S>
+    ) {
+        self.apply_commands(handler, state);
+        if !self.closed {
+            state.connection.set_tick_number(self.tick_number);
+            let bytes_sent = if state.attempts.is_empty() {
+                state.connection.send_packet(
+                    &mut state.socket, &state.peer_address, self, handler
+                )
+
+            } else {
+                self.send_attempts(handler, state, false)
+            };
+            self.rebind_on_send_error(handler, state);
+
+            // Accounted for along with the next tick's packet
+            self.unscheduled_bytes_sent += bytes_sent;
+            self.update_connection_stats(&state.connection);
+            self.update_session(&state.connection);
+        }
+    }
+
+    /// Performs a single iteration of the client's tick loop, for driving
+    /// the client from a game's main loop instead of `Client::connect`.
+    ///
+    /// This receives all pending packets, ticks the connection and sends its
+    /// outgoing packet. Once the client was requested to close via
+    /// `Client::close`, i.e. from one of the handler's callbacks, the
+    /// connection is closed via `Client::close_sync` just like the internal
+    /// loop would do.
+    ///
+    /// Returns `false` once the client is no longer connected, after which
+    /// there is no point in calling this again.
+    pub fn run_once<S: Socket>(
+        &mut self, handler: &mut dyn Handler<Client>, state: &mut ClientState<S>
+
+    ) -> bool {
+
+        if self.closed {
+            return false;
+        }
+
+        let tick_delay = 1000 / self.config.send_rate;
+        self.receive_sync(handler, state, tick_delay);
+        self.tick_sync(handler, state);
+        self.send_sync(handler, state);
+
+        if self.running {
+            true
+
+        } else {
+            self.close_sync(handler, state).ok();
+            false
         }
+
     }
 
     /// Closes the connection to the server.
     ///
-    /// This resets the connection and shuts down the underlying socket the
-    /// client was sending and receiving from.
+    /// This notifies the server in case the connection was established,
+    /// resets the connection and shuts down the underlying socket the client
+    /// was sending and receiving from.
     pub fn close_sync<S: Socket>(
-        &mut self, handler: &mut Handler<Client>, state: &mut ClientState<S>
+        &mut self, handler: &mut dyn Handler<Client>, state: &mut ClientState<S>
 
     ) -> Result<(), Error> {
 
@@ -276,9 +860,32 @@ impl Client {
         } else {
 
             self.closed = true;
+            self.running = false;
+
+            // Discard the commands which were not applied before closing
+            while self.commands.try_recv().is_ok() {}
+
+            // Remember the resumption token unless the connection was closed
+            self.resumption_token = match state.connection.state() {
+                ConnectionState::Closing | ConnectionState::Closed => None,
+                _ => state.connection.resumption_token()
+            };
+            self.remember_resumption_ticket(&state.connection);
+
+            // Sessions of closed connections can no longer be resumed
+            if self.resumption_token.is_none() {
+                self.session = None;
+            }
+
+            // Remember the new host of the session in case it was lost
+            if state.connection.state() == ConnectionState::Lost {
+                self.host_migration = state.connection.host_migration();
+            }
 
             handler.close(self);
+            state.send_close_packet();
             state.connection.reset();
+            state.attempts.clear();
 
             self.peer_address = None;
             self.local_address = None;
@@ -294,14 +901,326 @@ impl Client {
         ClientStream::from_client(self)
     }
 
+    // Internal Helpers -------------------------------------------------------
+    fn create_connection(
+        &self, handler: &mut dyn Handler<Client>,
+        local_addr: SocketAddr, peer_addr: SocketAddr
+
+    ) -> Connection {
+
+        let mut connection = Connection::new(
+            self.config,
+            local_addr,
+            peer_addr,
+            handler.rate_limiter(&self.config)
+        );
+        connection.set_id(handler.id_generator(&self.config).generate());
+
+        // Present the connect token issued by the backend
+        #[cfg(feature = "connect_token")]
+        {
+            if let Some(ref token) = self.connect_token {
+                connection.present_connect_token(token.clone());
+            }
+        }
+
+        connection
+
+    }
+
+    fn receive_from<S: Socket>(
+        &mut self,
+        handler: &mut dyn Handler<Client>,
+        socket: &mut S, connection: &mut Connection, peer_address: SocketAddr,
+        tick_delay: u32
+
+    ) -> usize {
+
+        let mut bytes_received = 0;
+        while let Ok((addr, packet)) = socket.try_recv() {
+            if addr == peer_address {
+                bytes_received += packet.len();
+
+                // Fail right away in case the server uses a different
+                // protocol version
+                if let Some(protocol_header) = Connection::protocol_mismatch_from_packet(&self.config, &packet) {
+                    connection.receive_protocol_mismatch(
+                        protocol_header, self, handler
+                    );
+
+                // Fail right away in case the server is full
+                } else if Connection::server_full_from_packet(&self.config, &packet) == Some(connection.id()) {
+                    connection.receive_server_full(self, handler);
+
+                // Handshake cookies issued by the server are echoed
+                // with the next packets
+                } else if let Some(packet) = connection.receive_handshake_cookie(packet) {
+                    match Connection::authenticate_packet(&self.config, packet) {
+                        Some(packet) => connection.receive_packet(
+                            packet, tick_delay, self, handler
+                        ),
+                        None => connection.drop_packet(
+                            PacketDropReason::AuthenticationFailed, self, handler
+                        )
+                    }
+                }
+            }
+        }
+
+        bytes_received
+
+    }
+
+    // Sends the packets of all attempts which already started, counting
+    // down the delays of the remaining ones in case of a regular `tick`
+    fn send_attempts<S: Socket>(
+        &mut self, handler: &mut dyn Handler<Client>, state: &mut ClientState<S>,
+        tick: bool
+
+    ) -> u32 {
+
+        let mut bytes_sent = state.connection.send_packet(
+            &mut state.socket, &state.peer_address, self, &mut AttemptHandler
+        );
+
+        for attempt in &mut state.attempts {
+
+            if tick {
+                if attempt.delay > 0 {
+                    attempt.delay -= 1;
+                    continue;
+                }
+
+                // Connections are only created once their attempt starts, so
+                // their handshake is not timed out early
+                if attempt.connection.is_none() {
+                    let local_addr = attempt.socket.local_addr().unwrap_or_else(|_| {
+                        unspecified_addr(attempt.peer_address)
+                    });
+                    attempt.connection = Some(self.create_connection(
+                        handler, local_addr, attempt.peer_address
+                    ));
+                }
+            }
+
+            if let Some(ref mut connection) = attempt.connection {
+                connection.set_tick_number(self.tick_number);
+                bytes_sent += connection.send_packet(
+                    &mut attempt.socket, &attempt.peer_address, self, &mut AttemptHandler
+                );
+            }
+
+        }
+
+        self.select_attempt(handler, state);
+        bytes_sent
+
+    }
+
+    fn update_connection_stats(&mut self, connection: &Connection) {
+        let state = connection.dump_state();
+        let stats = &mut self.connection_stats;
+        stats.rtt = state.rtt;
+        stats.rtt_jitter = connection.rtt_jitter();
+        stats.packet_loss = state.packet_loss;
+        stats.messages_sent = connection.messages_sent();
+        stats.messages_received = connection.messages_received();
+        stats.packets_unacked = state.packets_unacked;
+        stats.queued_instant = state.queued_instant;
+        stats.queued_reliable = state.queued_reliable;
+        stats.queued_ordered = state.queued_ordered;
+        stats.queued_delayed = state.queued_delayed;
+        stats.received = state.received;
+    }
+
+    fn update_session(&mut self, connection: &Connection) {
+        if let Some(token) = connection.resumption_token() {
+            let (local_seq_number, remote_seq_number) = connection.seq_numbers();
+            self.session = Some(ClientSession {
+                server_address: connection.peer_addr(),
+                resumption_token: token,
+                local_seq_number,
+                remote_seq_number,
+                tick_number: self.tick_number
+            });
+        }
+    }
+
+    // Continues the connection from a freshly bound socket once sending
+    // failed because the local network changed, relying on the server to
+    // migrate the connection to its new address
+    fn rebind_on_send_error<S: Socket>(
+        &mut self, handler: &mut dyn Handler<Client>, state: &mut ClientState<S>
+    ) {
+        if state.connection.take_send_error() == Some(ErrorKind::AddrNotAvailable) {
+            let previous = state.connection.local_addr();
+            let peer_addr = state.connection.peer_addr();
+            let socket = &mut state.socket;
+            if let Ok(addr) = self.bind_local(peer_addr, |addr| socket.rebind(addr)) {
+                state.connection.set_local_addr(addr);
+                self.local_address = Some(addr);
+                handler.connection_rebound(self, &mut state.connection, previous);
+            }
+        }
+    }
+
+    fn select_attempt<S: Socket>(
+        &mut self, handler: &mut dyn Handler<Client>, state: &mut ClientState<S>
+    ) {
+
+        if state.connection.state() != ConnectionState::Connected {
+
+            let connected = state.attempts.iter().position(|attempt| {
+                attempt.state() == Some(ConnectionState::Connected)
+            });
+
+            if let Some(index) = connected {
+                let attempt = state.attempts.swap_remove(index);
+                state.socket = SimulatedSocket::new(attempt.socket, self.config);
+                state.peer_address = attempt.peer_address;
+                state.connection = attempt.connection.unwrap();
+
+            // Only fail once all attempts failed
+            } else {
+                let failed = state.connection.state() == ConnectionState::FailedToConnect
+                    && state.attempts.iter().all(|attempt| {
+                        attempt.state() == Some(ConnectionState::FailedToConnect)
+                    });
+
+                if failed {
+                    state.attempts.clear();
+                    handler.connection_failed(self, &mut state.connection);
+                }
+                return;
+            }
+
+        }
+
+        state.attempts.clear();
+        self.peer_address = Some(state.peer_address);
+        self.local_address = Some(state.connection.local_addr());
+
+        if state.connection.resumed() {
+            handler.connection_resumed(self, &mut state.connection);
+
+        } else {
+            handler.connection(self, &mut state.connection);
+        }
+
+        handler.connection_address_selected(self, &mut state.connection, state.peer_address);
+
+    }
+
+    fn run<S: Socket>(
+        &mut self, handler: &mut dyn Handler<Client>, state: &mut ClientState<S>
+
+    ) -> Result<(), Error> {
+
+        let mut tick_overflow = 0;
+        while self.running {
+
+            let tick_start = tick::start();
+            let tick_delay = 1000000000 / self.config.send_rate;
+
+            self.receive_sync(handler, state, tick_delay / 1000000);
+            self.tick_sync(handler, state);
+            self.send_sync(handler, state);
+
+            tick::end(tick_delay, tick_start, &mut tick_overflow, &self.config);
+
+        }
+
+        if self.closed {
+            Ok(())
+
+        } else {
+            self.close_sync(handler, state)
+        }
+
+    }
+
+    fn bind_socket(&self, peer_addr: SocketAddr) -> Result<UdpSocket, Error> {
+        let max_packet_size = self.config.packet_max_size;
+        self.bind_local(peer_addr, |addr| UdpSocket::new(addr, max_packet_size))
+    }
+
+    // Binds to the configured local address, trying each of the configured
+    // ports in order
+    fn bind_local<T, F>(
+        &self, peer_addr: SocketAddr, mut bind: F
+
+    ) -> Result<T, Error>
+        where F: FnMut(SocketAddr) -> Result<T, Error> {
+
+        let addr = self.bind_address.unwrap_or_else(|| unspecified_addr(peer_addr));
+        if let Some(ref ports) = self.bind_ports {
+
+            let mut error = Error::new(ErrorKind::InvalidInput, "empty port range");
+            for port in ports.clone() {
+                match bind(SocketAddr::new(addr.ip(), port)) {
+                    Ok(socket) => return Ok(socket),
+                    Err(err) => error = err
+                }
+            }
+
+            Err(error)
+
+        } else {
+            bind(addr)
+        }
+
+    }
+
+    fn apply_commands<S: Socket>(
+        &mut self, handler: &mut dyn Handler<Client>, state: &mut ClientState<S>
+    ) {
+        while !self.closed {
+            match self.commands.try_recv() {
+                Ok(Command::Close) => {
+                    self.close_sync(handler, state).ok();
+                },
+                Ok(Command::Send(kind, payload)) => {
+                    state.connection.send(kind, payload);
+                },
+                Err(_) => break
+            }
+        }
+    }
+
+    #[cfg(feature = "encryption")]
+    fn resume_with_ticket(&mut self, connection: &mut Connection) {
+        if let Some(ticket) = self.resumption_ticket.take() {
+            connection.resume_with_ticket(ticket);
+        }
+    }
+
+    #[cfg(not(feature = "encryption"))]
+    fn resume_with_ticket(&mut self, _: &mut Connection) {}
+
+    #[cfg(feature = "encryption")]
+    fn remember_resumption_ticket(&mut self, connection: &Connection) {
+        self.resumption_ticket = match connection.state() {
+            ConnectionState::Closing | ConnectionState::Closed => None,
+            _ => connection.resumption_ticket()
+        };
+    }
+
+    #[cfg(not(feature = "encryption"))]
+    fn remember_resumption_ticket(&mut self, _: &Connection) {}
+
 }
 
 /// A structure used for synchronous calls on a `Client` instance.
+///
+/// Dropping the state while its connection is established sends a closure
+/// packet to the server, so the connection does not have to time out on the
+/// server's end.
 #[derive(Debug)]
 pub struct ClientState<S: Socket> {
-    socket: S,
+    socket: SimulatedSocket<S>,
     connection: Connection,
     peer_address: SocketAddr,
+    attempts: Vec<ConnectAttempt<S>>,
     stats: Stats
 }
 
@@ -312,22 +1231,28 @@ impl <S: Socket>ClientState<S> {
     // bindings of Self when trying to both call a method on it's
     // connection and pass it to that method.
     fn new(
-        socket: S,
+        socket: SimulatedSocket<S>,
         connection: Connection,
         peer_addr: SocketAddr
 
     ) -> ClientState<S> {
         ClientState {
-            socket: socket,
-            connection: connection,
+            socket,
+            connection,
             peer_address: peer_addr,
-            stats: Stats {
-                bytes_sent: 0,
-                bytes_received: 0
-            }
+            attempts: Vec::new(),
+            stats: Stats::default()
         }
     }
 
+    /// Consumes the state, returning this client's underlying socket.
+    ///
+    /// This allows the client to bind a `Server` to the very same address
+    /// after it was elected as the new host of a session.
+    pub fn into_socket(mut self) -> S {
+        self.socket.take().expect("socket is only taken once")
+    }
+
     /// Returns the average roundtrip time for this client's underlying
     /// connection.
     pub fn rtt(&self) -> u32 {
@@ -347,6 +1272,11 @@ impl <S: Socket>ClientState<S> {
         self.stats
     }
 
+    /// Returns the id of this client's underlying connection.
+    pub fn id(&self) -> ConnectionID {
+        self.connection.id()
+    }
+
     /// Returns the socket address for the local end of this client's
     /// underlying connection.
     pub fn local_addr(&self) -> SocketAddr {
@@ -362,12 +1292,49 @@ impl <S: Socket>ClientState<S> {
     /// Overrides the configuration of this client's underlying connection.
     pub fn set_config(&mut self, config: Config) {
         self.connection.set_config(config);
+        self.socket.set_config(config);
     }
 
     /// Sends a message of the specified `kind` along with its `payload` over
     /// this client's underlying connection.
-    pub fn send(&mut self, kind: MessageKind, payload: Vec<u8>) {
-        self.connection.send(kind, payload);
+    pub fn send(&mut self, kind: MessageKind, payload: Vec<u8>) -> Option<MessageHandle> {
+        self.connection.send(kind, payload)
+    }
+
+    /// Sends a message of the specified `kind` along with its `payload` over
+    /// this client's underlying connection, returning an error in case the
+    /// message was rejected.
+    pub fn try_send(
+        &mut self, kind: MessageKind, payload: Vec<u8>
+
+    ) -> Result<MessageHandle, Error> {
+        self.connection.try_send(kind, payload)
+    }
+
+    /// Sends a message of the specified `kind` along with its `payload` over
+    /// this client's underlying connection once the `delay` has elapsed.
+    pub fn send_after(
+        &mut self, kind: MessageKind, payload: Vec<u8>, delay: Duration
+
+    ) -> Result<MessageHandle, Error> {
+        self.connection.send_after(kind, payload, delay)
+    }
+
+    /// Cancels the message referred to by `handle` in case it has not yet
+    /// been send over this client's underlying connection.
+    pub fn cancel(&mut self, handle: MessageHandle) -> bool {
+        self.connection.cancel(handle)
+    }
+
+    /// Requests the server to keep this client's underlying connection alive
+    /// for the specified number of milliseconds, see
+    /// `Connection::request_keep_alive()`.
+    pub fn request_keep_alive(&mut self, duration: u32) -> Result<(), Error> {
+        self.connection.request_keep_alive(duration)
+    }
+
+    pub(crate) fn connection_mut(&mut self) -> &mut Connection {
+        &mut self.connection
     }
 
     /// Resets this client's underlying connection state.
@@ -375,5 +1342,52 @@ impl <S: Socket>ClientState<S> {
         self.connection.reset();
     }
 
+    // Notifies the server that an established connection is closed
+    fn send_close_packet(&mut self) {
+        if self.connection.state() == ConnectionState::Connected {
+            self.connection.close();
+            self.connection.send_packet(
+                &mut self.socket, &self.peer_address, &mut (), &mut CloseHandler
+            );
+        }
+    }
+
+}
+
+/// Attempt to connect to one of the further addresses of a host name, racing
+/// the attempts to its other addresses.
+#[derive(Debug)]
+struct ConnectAttempt<S: Socket> {
+    socket: S,
+    connection: Option<Connection>,
+    peer_address: SocketAddr,
+    delay: u32
+}
+
+impl<S: Socket> ConnectAttempt<S> {
+    fn state(&self) -> Option<ConnectionState> {
+        self.connection.as_ref().map(|connection| connection.state())
+    }
+}
+
+impl<S: Socket> Drop for ClientState<S> {
+    fn drop(&mut self) {
+        self.send_close_packet();
+    }
 }
 
+/// Handler which discards the events of racing connection attempts until one
+/// of them was selected.
+#[derive(Debug)]
+struct AttemptHandler;
+
+impl Handler<Client> for AttemptHandler {}
+
+/// Handler which discards the events of the closure packet send once a
+/// `ClientState` is dropped.
+#[derive(Debug)]
+struct CloseHandler;
+
+impl Handler<()> for CloseHandler {}
+
+// Static Helpers -------------------------------------------------------------
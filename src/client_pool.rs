@@ -0,0 +1,424 @@
+// Copyright (c) 2015-2016 Ivo Wetzel
+
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::cmp;
+use std::thread;
+use std::rc::Rc;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::io::{Error, ErrorKind};
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{channel, Receiver, Sender, TryRecvError};
+use traits::socket::Socket;
+use shared::shared_socket::{Inbox, SharedSocket};
+use shared::udp_socket::UdpSocket;
+use client_stream::StreamHandler;
+use super::{
+    Client, ClientEvent, ClientState, Config, Connection, ConnectionID,
+    ConnectionState, MessageKind, tick
+};
+
+/// Identifier of a single client inside a `ClientPool`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub struct PoolClientID(pub u32);
+
+/// Implementation of a pool of clients which share a fixed number of local
+/// sockets and threads, suitable for load testing a server from a single
+/// process.
+///
+/// Each client has its own connection to the server, but the sockets are
+/// shared between multiple clients, with incoming packets being dispatched by
+/// their connection id. The clients are ticked by the pool's threads at the
+/// configured `send_rate` and the events of all clients are polled via a
+/// single `receive` call.
+///
+/// Clients are removed from the pool once they failed to connect, were lost
+/// or closed.
+///
+/// # Examples
+///
+/// ```no_run
+/// use cobalt::{ClientEvent, ClientPool, Config, MessageKind};
+///
+/// // Hundreds of clients using 4 sockets and 2 threads
+/// let mut pool = ClientPool::new(Config::default(), 4, 2).unwrap();
+/// for _ in 0..500 {
+///     pool.connect("127.0.0.1:7156").unwrap();
+/// }
+///
+/// loop {
+///     while let Ok((id, event)) = pool.receive() {
+///         if event == ClientEvent::Connection {
+///             pool.send(id, MessageKind::Reliable, b"Hello".to_vec()).unwrap();
+///         }
+///     }
+/// }
+/// ```
+#[derive(Debug)]
+pub struct ClientPool {
+    workers: Vec<Worker>,
+    events: Receiver<(PoolClientID, ClientEvent)>,
+    clients: Arc<Mutex<HashSet<PoolClientID>>>,
+    local_addrs: Vec<SocketAddr>,
+    next_id: u32
+}
+
+impl ClientPool {
+
+    /// Creates a new pool with the given configuration which binds the
+    /// specified number of local `sockets` and spreads them across up to
+    /// `threads` threads.
+    ///
+    /// At least one socket and thread are used and the number of threads is
+    /// limited to the number of sockets.
+    pub fn new(config: Config, sockets: usize, threads: usize) -> Result<ClientPool, Error> {
+
+        let sockets = cmp::max(sockets, 1);
+        let threads = cmp::max(cmp::min(threads, sockets), 1);
+
+        // Bind all sockets up front in order to report failures
+        let mut local_addrs = Vec::with_capacity(sockets);
+        let mut assigned: Vec<Vec<UdpSocket>> = (0..threads).map(|_| Vec::new()).collect();
+        for index in 0..sockets {
+            let socket = UdpSocket::new("0.0.0.0:0", config.packet_max_size)?;
+            local_addrs.push(socket.local_addr()?);
+            assigned[index % threads].push(socket);
+        }
+
+        let (event_sender, events) = channel();
+        let clients = Arc::new(Mutex::new(HashSet::new()));
+        let workers = assigned.into_iter().map(|sockets| {
+            Worker::spawn(config, sockets, event_sender.clone(), clients.clone())
+
+        }).collect();
+
+        Ok(ClientPool {
+            workers,
+            events,
+            clients,
+            local_addrs,
+            next_id: 0
+        })
+
+    }
+
+    /// Returns the local addresses of the sockets shared by the pool's
+    /// clients.
+    pub fn local_addrs(&self) -> &[SocketAddr] {
+        &self.local_addrs
+    }
+
+    /// Returns the number of clients in the pool.
+    pub fn len(&self) -> usize {
+        self.clients.lock().unwrap().len()
+    }
+
+    /// Returns whether the pool does not contain any clients.
+    pub fn is_empty(&self) -> bool {
+        self.clients.lock().unwrap().is_empty()
+    }
+
+    /// Adds a new client to the pool which establishes a connection with the
+    /// server at the specified address, returning its id.
+    ///
+    /// Clients are distributed evenly across the pool's sockets.
+    pub fn connect<A: ToSocketAddrs>(&mut self, addr: A) -> Result<PoolClientID, Error> {
+
+        let addr = addr.to_socket_addrs()?.next().ok_or_else(|| {
+            Error::new(ErrorKind::InvalidInput, "")
+        })?;
+
+        let id = PoolClientID(self.next_id);
+        let socket = id.0 as usize % self.local_addrs.len();
+        let threads = self.workers.len();
+        self.clients.lock().unwrap().insert(id);
+        if let Err(err) = self.workers[socket % threads].command(
+            Command::Connect(id, socket / threads, addr)
+        ) {
+            self.clients.lock().unwrap().remove(&id);
+            return Err(err);
+        }
+
+        self.next_id = self.next_id.wrapping_add(1);
+        Ok(id)
+
+    }
+
+    /// Receives the next event of any of the pool's clients.
+    pub fn receive(&mut self) -> Result<(PoolClientID, ClientEvent), TryRecvError> {
+        self.events.try_recv()
+    }
+
+    /// Queues a message of the specified `kind` along with its `payload` to
+    /// be send by the client with the given `id` on its next tick.
+    ///
+    /// Messages which are rejected by the client's connection are dropped.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `NotConnected` in case the client was closed
+    /// or removed from the pool after it failed to connect or was lost.
+    pub fn send(
+        &mut self, id: PoolClientID, kind: MessageKind, payload: Vec<u8>
+
+    ) -> Result<(), Error> {
+        if self.clients.lock().unwrap().contains(&id) {
+            self.worker(id).command(Command::Send(id, kind, payload))
+
+        } else {
+            Err(Error::new(ErrorKind::NotConnected, ""))
+        }
+    }
+
+    /// Closes the connection of the client with the given `id` and removes
+    /// it from the pool.
+    pub fn close(&mut self, id: PoolClientID) -> Result<(), Error> {
+        if self.clients.lock().unwrap().remove(&id) {
+            self.worker(id).command(Command::Close(id))
+
+        } else {
+            Err(Error::new(ErrorKind::NotConnected, ""))
+        }
+    }
+
+    fn worker(&self, id: PoolClientID) -> &Worker {
+        let socket = id.0 as usize % self.local_addrs.len();
+        &self.workers[socket % self.workers.len()]
+    }
+
+}
+
+impl Drop for ClientPool {
+    fn drop(&mut self) {
+        for worker in &self.workers {
+            worker.command(Command::Shutdown).ok();
+        }
+        for worker in &mut self.workers {
+            if let Some(thread) = worker.thread.take() {
+                thread.join().ok();
+            }
+        }
+    }
+}
+
+
+// Workers --------------------------------------------------------------------
+#[derive(Debug)]
+enum Command {
+    Connect(PoolClientID, usize, SocketAddr),
+    Send(PoolClientID, MessageKind, Vec<u8>),
+    Close(PoolClientID),
+    Shutdown
+}
+
+#[derive(Debug)]
+struct Worker {
+    commands: Sender<Command>,
+    thread: Option<thread::JoinHandle<()>>
+}
+
+impl Worker {
+
+    fn spawn(
+        config: Config,
+        sockets: Vec<UdpSocket>,
+        events: Sender<(PoolClientID, ClientEvent)>,
+        pool_clients: Arc<Mutex<HashSet<PoolClientID>>>
+
+    ) -> Worker {
+        let (commands, receiver) = channel();
+        Worker {
+            commands,
+            thread: Some(thread::spawn(move || {
+                WorkerLoop::new(config, sockets, events, pool_clients).run(receiver);
+            }))
+        }
+    }
+
+    fn command(&self, command: Command) -> Result<(), Error> {
+        self.commands.send(command).map_err(|_| {
+            Error::new(ErrorKind::BrokenPipe, "")
+        })
+    }
+
+}
+
+struct WorkerLoop {
+    config: Config,
+    sockets: Vec<Rc<RefCell<UdpSocket>>>,
+    clients: HashMap<PoolClientID, PooledClient>,
+    events: Sender<(PoolClientID, ClientEvent)>,
+    pool_clients: Arc<Mutex<HashSet<PoolClientID>>>
+}
+
+impl WorkerLoop {
+
+    fn new(
+        config: Config,
+        sockets: Vec<UdpSocket>,
+        events: Sender<(PoolClientID, ClientEvent)>,
+        pool_clients: Arc<Mutex<HashSet<PoolClientID>>>
+
+    ) -> WorkerLoop {
+        WorkerLoop {
+            config,
+            sockets: sockets.into_iter().map(|s| Rc::new(RefCell::new(s))).collect(),
+            clients: HashMap::new(),
+            events,
+            pool_clients
+        }
+    }
+
+    fn run(&mut self, commands: Receiver<Command>) {
+
+        let mut tick_overflow = 0;
+        loop {
+
+            let tick_start = tick::start();
+            let tick_delay = 1_000_000_000 / self.config.send_rate;
+
+            // Apply all commands issued by the pool since the last tick
+            loop {
+                match commands.try_recv() {
+                    Ok(Command::Connect(id, socket, addr)) => self.connect(id, socket, addr),
+                    Ok(Command::Send(id, kind, payload)) => {
+                        if let Some(client) = self.clients.get_mut(&id) {
+                            client.state.try_send(kind, payload).ok();
+                        }
+                    },
+                    Ok(Command::Close(id)) => self.close(id),
+                    Ok(Command::Shutdown) | Err(TryRecvError::Disconnected) => {
+                        let ids: Vec<PoolClientID> = self.clients.keys().cloned().collect();
+                        for id in ids {
+                            self.close(id);
+                        }
+                        return;
+                    },
+                    Err(TryRecvError::Empty) => break
+                }
+            }
+
+            self.dispatch();
+
+            let mut dropped = Vec::new();
+            for (id, client) in &mut self.clients {
+                client.client.receive_sync(
+                    &mut client.handler, &mut client.state, tick_delay / 1_000_000
+                );
+                client.client.tick_sync(&mut client.handler, &mut client.state);
+                client.client.send_sync(&mut client.handler, &mut client.state);
+
+                // Collect all failed / lost / closed clients
+                match client.state.connection_mut().state() {
+                    ConnectionState::FailedToConnect | ConnectionState::Lost | ConnectionState::Closed => {
+                        dropped.push(*id);
+                    },
+                    _ => client.forward(*id, &self.events)
+                }
+            }
+
+            for id in dropped {
+                self.close(id);
+            }
+
+            tick::end(tick_delay, tick_start, &mut tick_overflow, &self.config);
+
+        }
+
+    }
+
+    fn connect(&mut self, id: PoolClientID, socket: usize, addr: SocketAddr) {
+
+        let (pool_socket, incoming) = SharedSocket::new(self.sockets[socket].clone());
+
+        let mut client = Client::new(self.config);
+        let mut handler = StreamHandler::new();
+        if let Ok(state) = client.connect_from_socket_sync(&mut handler, addr, pool_socket) {
+            let mut client = PooledClient {
+                client,
+                state,
+                handler,
+                socket,
+                incoming
+            };
+            client.forward(id, &self.events);
+            self.clients.insert(id, client);
+
+        } else {
+            self.pool_clients.lock().unwrap().remove(&id);
+            self.events.send((id, ClientEvent::ConnectionFailed)).ok();
+        }
+
+    }
+
+    fn close(&mut self, id: PoolClientID) {
+        if let Some(mut client) = self.clients.remove(&id) {
+            self.pool_clients.lock().unwrap().remove(&id);
+            client.client.close_sync(&mut client.handler, &mut client.state).ok();
+            client.forward(id, &self.events);
+        }
+    }
+
+    // Routes the packets received on all sockets to the clients they are
+    // addressed to
+    fn dispatch(&mut self) {
+
+        // Connection ids can change over the lifetime of a client
+        let routes: HashMap<(usize, ConnectionID), PoolClientID> = self.clients.iter().map(|(id, c)| {
+            ((c.socket, c.state.id()), *id)
+
+        }).collect();
+
+        for (index, socket) in self.sockets.iter().enumerate() {
+            while let Ok((addr, packet)) = socket.borrow_mut().try_recv() {
+
+                let route = if packet.len() >= 8 {
+                    Connection::id_from_packet(&self.config, &packet).and_then(|conn_id| {
+                        routes.get(&(index, conn_id))
+                    })
+
+                } else {
+                    None
+                };
+
+                // Packets without a known id are passed on to all clients
+                // talking to the sender
+                if let Some(id) = route {
+                    self.clients[id].incoming.borrow_mut().push_back((addr, packet));
+
+                } else {
+                    for client in self.clients.values() {
+                        if client.socket == index && client.state.peer_addr() == addr {
+                            client.incoming.borrow_mut().push_back((addr, packet.clone()));
+                        }
+                    }
+                }
+
+            }
+        }
+
+    }
+
+}
+
+struct PooledClient {
+    client: Client,
+    state: ClientState<SharedSocket<UdpSocket>>,
+    handler: StreamHandler,
+    socket: usize,
+    incoming: Inbox
+}
+
+impl PooledClient {
+    fn forward(&mut self, id: PoolClientID, events: &Sender<(PoolClientID, ClientEvent)>) {
+        while let Some(event) = self.handler.try_recv() {
+            events.send((id, event)).ok();
+        }
+    }
+}
@@ -10,9 +10,11 @@ use std::collections::VecDeque;
 use std::io::{Error, ErrorKind};
 use std::net::{SocketAddr, ToSocketAddrs};
 use std::sync::mpsc::TryRecvError;
+use std::time::Duration;
 use shared::udp_socket::UdpSocket;
 use super::{
-    Config, Client, ClientState, Connection, Handler, MessageKind, Stats
+    ClientStats, Config, Client, ClientState, Connection, Handler, HostMigration,
+    MessageHandle, MessageKind, Stats
 };
 
 
@@ -34,18 +36,40 @@ pub enum ClientEvent {
     /// Event emitted once a connection to the server has been established.
     Connection,
 
+    /// Event emitted once a previously lost connection to the server has been
+    /// resumed.
+    ConnectionResumed,
+
+    /// Event emitted right after `Connection` respectively
+    /// `ConnectionResumed`, with the address that was selected in case the
+    /// server's host name resolved to several addresses.
+    ConnectionAddressSelected(SocketAddr),
+
+    /// Event emitted each time the stream's socket was rebound after sending
+    /// failed, with the previous local address.
+    ConnectionRebound(SocketAddr),
+
     /// Event emitted when a initial connection attempt to a server failed.
     ConnectionFailed,
 
     /// Event emitted when a existing connection to a server is lost.
     ConnectionLost,
 
+    /// Event emitted when a existing connection to the host of a session is
+    /// lost, right before `ConnectionLost`, with the outcome of the host
+    /// election.
+    HostMigration(HostMigration),
+
     /// Event emitted when a connection is closed programmatically.
     ConnectionClosed(bool),
 
     /// Event emitted for each message received from the server.
     Message(Vec<u8>),
 
+    /// Event emitted each time a `Reliable` or `Ordered` message send to the
+    /// server has been acknowledged.
+    MessageDelivered(MessageHandle),
+
     /// Event emitted each time the stream's congestion state changes.
     ConnectionCongestionState(bool),
 
@@ -72,7 +96,7 @@ impl ClientStream {
     pub fn new(config: Config) -> ClientStream {
         ClientStream {
             handler: StreamHandler::new(),
-            config: config,
+            config,
             client: Client::new(config),
             state: None,
             tick_rate: 0,
@@ -86,7 +110,7 @@ impl ClientStream {
         ClientStream {
             handler: StreamHandler::new(),
             config: client.config(),
-            client: client,
+            client,
             state: None,
             tick_rate: 0,
             should_receive: false
@@ -133,6 +157,12 @@ impl ClientStream {
         self.state.as_ref().map_or(0, |s| s.stats().bytes_received)
     }
 
+    /// Returns a snapshot of the roundtrip time, packet loss, totals and
+    /// queue depths of this stream's underlying connection.
+    pub fn connection_stats(&self) -> ClientStats {
+        self.client.connection_stats()
+    }
+
     /// Returns a copy of the stream's current configuration.
     pub fn config(&self) -> Config {
         self.config
@@ -180,24 +210,23 @@ impl ClientStream {
     /// connection.
     pub fn receive(&mut self) -> Result<ClientEvent, TryRecvError> {
 
-        if self.state.is_none() {
-            Err(TryRecvError::Disconnected)
-
-        } else {
+        if let Some(state) = self.state.as_mut() {
 
             if self.should_receive {
 
                 self.should_receive = false;
 
-                let mut state = self.state.as_mut().unwrap();
                 self.client.receive_sync(
-                    &mut self.handler, &mut state, 1000 / self.tick_rate
+                    &mut self.handler, state, 1000 / self.tick_rate
                 );
-                self.client.tick_sync(&mut self.handler, &mut state);
+                self.client.tick_sync(&mut self.handler, state);
 
             }
 
-            self.handler.try_recv().ok_or_else(|| TryRecvError::Empty)
+            self.handler.try_recv().ok_or(TryRecvError::Empty)
+
+        } else {
+            Err(TryRecvError::Disconnected)
 
         }
 
@@ -205,9 +234,53 @@ impl ClientStream {
 
     /// Queues a message of the specified `kind` along with its `payload` to
     /// be send with the next `flush` call.
-    pub fn send(&mut self, kind: MessageKind, payload: Vec<u8>) -> Result<(), Error> {
+    ///
+    /// Returns a handle for cancelling the message before the next `flush`
+    /// call or an error in case the message is rejected by the stream's
+    /// underlying connection.
+    pub fn send(
+        &mut self, kind: MessageKind, payload: Vec<u8>
+
+    ) -> Result<MessageHandle, Error> {
+        if let Some(ref mut state) = self.state {
+            state.try_send(kind, payload)
+
+        } else {
+            Err(Error::new(ErrorKind::NotConnected, ""))
+        }
+    }
+
+    /// Queues a message of the specified `kind` along with its `payload` to
+    /// be send with the first `flush` call after the `delay` has elapsed.
+    pub fn send_after(
+        &mut self, kind: MessageKind, payload: Vec<u8>, delay: Duration
+
+    ) -> Result<MessageHandle, Error> {
+        if let Some(ref mut state) = self.state {
+            state.send_after(kind, payload, delay)
+
+        } else {
+            Err(Error::new(ErrorKind::NotConnected, ""))
+        }
+    }
+
+    /// Cancels a queued message referred to by `handle` in case it has not
+    /// yet been send, returning whether it was removed.
+    pub fn cancel(&mut self, handle: MessageHandle) -> Result<bool, Error> {
+        if let Some(ref mut state) = self.state {
+            Ok(state.cancel(handle))
+
+        } else {
+            Err(Error::new(ErrorKind::NotConnected, ""))
+        }
+    }
+
+    /// Requests the server to keep the stream's underlying connection alive
+    /// for the specified number of milliseconds, i.e. before the application
+    /// goes into the background.
+    pub fn request_keep_alive(&mut self, duration: u32) -> Result<(), Error> {
         if let Some(ref mut state) = self.state {
-            Ok(state.send(kind, payload))
+            state.request_keep_alive(duration)
 
         } else {
             Err(Error::new(ErrorKind::NotConnected, ""))
@@ -216,9 +289,10 @@ impl ClientStream {
 
     /// Sends all queued messages over the stream's underlying connection.
     pub fn flush(&mut self) -> Result<(), Error> {
-        if let Some(mut state) = self.state.as_mut() {
+        if let Some(state) = self.state.as_mut() {
             self.should_receive = true;
-            Ok(self.client.send_sync(&mut self.handler, &mut state))
+            self.client.send_sync(&mut self.handler, state);
+            Ok(())
 
         } else {
             Err(Error::new(ErrorKind::NotConnected, ""))
@@ -227,8 +301,9 @@ impl ClientStream {
 
     /// Resets the stream's underlying connection to the server.
     pub fn reset(&mut self) -> Result<(), Error> {
-        if let Some(mut state) = self.state.as_mut() {
-            Ok(state.reset())
+        if let Some(state) = self.state.as_mut() {
+            state.reset();
+            Ok(())
 
         } else {
             Err(Error::new(ErrorKind::NotConnected, ""))
@@ -251,19 +326,19 @@ impl ClientStream {
 
 
 #[derive(Debug, Default)]
-struct StreamHandler {
+pub struct StreamHandler {
     events: VecDeque<ClientEvent>
 }
 
 impl StreamHandler {
 
-    fn new() -> StreamHandler {
+    pub fn new() -> StreamHandler {
         StreamHandler {
             events: VecDeque::new()
         }
     }
 
-    fn try_recv(&mut self) -> Option<ClientEvent> {
+    pub fn try_recv(&mut self) -> Option<ClientEvent> {
         self.events.pop_front()
     }
 
@@ -299,10 +374,28 @@ impl Handler<Client> for StreamHandler {
         self.events.push_back(ClientEvent::Connection);
     }
 
+    fn connection_resumed(&mut self, _: &mut Client, _: &mut Connection) {
+        self.events.push_back(ClientEvent::ConnectionResumed);
+    }
+
+    fn connection_address_selected(&mut self, _: &mut Client, _: &mut Connection, addr: SocketAddr) {
+        self.events.push_back(ClientEvent::ConnectionAddressSelected(addr));
+    }
+
+    fn connection_rebound(&mut self, _: &mut Client, _: &mut Connection, addr: SocketAddr) {
+        self.events.push_back(ClientEvent::ConnectionRebound(addr));
+    }
+
     fn connection_failed(&mut self, _: &mut Client, _: &mut Connection) {
         self.events.push_back(ClientEvent::ConnectionFailed);
     }
 
+    fn connection_message_delivered(
+        &mut self, _: &mut Client, _: &mut Connection, handle: MessageHandle
+    ) {
+        self.events.push_back(ClientEvent::MessageDelivered(handle));
+    }
+
     fn connection_packet_lost(
         &mut self, _: &mut Client, _: &mut Connection, data: &[u8]
     ) {
@@ -313,6 +406,12 @@ impl Handler<Client> for StreamHandler {
         self.events.push_back(ClientEvent::ConnectionCongestionState(state));
     }
 
+    fn connection_host_migration(
+        &mut self, _: &mut Client, _: &mut Connection, migration: HostMigration
+    ) {
+        self.events.push_back(ClientEvent::HostMigration(migration));
+    }
+
     fn connection_lost(&mut self, _: &mut Client, _: &mut Connection) {
         self.events.push_back(ClientEvent::ConnectionLost);
     }
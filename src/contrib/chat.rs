@@ -0,0 +1,192 @@
+// Copyright (c) 2015-2016 Ivo Wetzel
+
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use std::collections::{HashMap, HashSet};
+use super::super::{Connection, ConnectionID, ConnectionMap, Handler, MessageKind, Server};
+
+/// Server handler implementing a chat room with channels and broadcast
+/// groups.
+///
+/// Clients talk to the handler with UTF-8 encoded text messages:
+///
+/// - `JOIN <channel>` joins the specified channel.
+/// - `LEAVE <channel>` leaves the specified channel.
+/// - `SAY <channel> <text>` sends the text to all other members of the
+///   channel, which receive it as `<channel> <sender id> <text>`.
+///
+/// Messages from connections which are not a member of the channel and any
+/// other messages are ignored.
+///
+/// Broadcast groups are managed by the server alone, allowing it to send
+/// messages to a specific set of connections via `ChatHandler::broadcast()`.
+///
+/// Connections are removed from all channels and groups once they are lost
+/// or closed.
+#[derive(Debug, Default)]
+pub struct ChatHandler {
+    channels: HashMap<String, HashSet<ConnectionID>>,
+    groups: HashMap<String, HashSet<ConnectionID>>,
+    broadcasts: Vec<(String, Vec<u8>)>
+}
+
+impl ChatHandler {
+
+    /// Creates a new handler without any channels or groups.
+    pub fn new() -> ChatHandler {
+        ChatHandler::default()
+    }
+
+    /// Returns the ids of all members of the specified `channel` in ascending
+    /// order.
+    pub fn channel_members(&self, channel: &str) -> Vec<ConnectionID> {
+        sorted_members(self.channels.get(channel))
+    }
+
+    /// Returns the ids of all members of the specified `group` in ascending
+    /// order.
+    pub fn group_members(&self, group: &str) -> Vec<ConnectionID> {
+        sorted_members(self.groups.get(group))
+    }
+
+    /// Adds the connection with the specified `id` to a broadcast `group`.
+    pub fn add_to_group(&mut self, group: &str, id: ConnectionID) {
+        self.groups.entry(group.to_string()).or_default().insert(id);
+    }
+
+    /// Removes the connection with the specified `id` from a broadcast
+    /// `group`.
+    pub fn remove_from_group(&mut self, group: &str, id: ConnectionID) {
+        remove_member(&mut self.groups, group, id);
+    }
+
+    /// Sends `data` to all members of the specified broadcast `group`.
+    ///
+    /// The message is send with the next tick of the server.
+    pub fn broadcast(&mut self, group: &str, data: Vec<u8>) {
+        self.broadcasts.push((group.to_string(), data));
+    }
+
+    fn remove_connection(&mut self, id: ConnectionID) {
+        for members in self.channels.values_mut().chain(self.groups.values_mut()) {
+            members.remove(&id);
+        }
+        self.channels.retain(|_, members| !members.is_empty());
+        self.groups.retain(|_, members| !members.is_empty());
+    }
+
+    fn command(
+        &mut self, id: ConnectionID, text: &str,
+        connections: &mut ConnectionMap
+    ) {
+
+        let mut parts = text.splitn(3, ' ');
+        match (parts.next(), parts.next(), parts.next()) {
+
+            (Some("JOIN"), Some(channel), None) if !channel.is_empty() => {
+                self.channels.entry(channel.to_string()).or_default().insert(id);
+            },
+
+            (Some("LEAVE"), Some(channel), None) => {
+                remove_member(&mut self.channels, channel, id);
+            },
+
+            (Some("SAY"), Some(channel), Some(text)) => {
+                if let Some(members) = self.channels.get(channel) {
+                    if members.contains(&id) {
+                        let data = format!("{} {} {}", channel, id.0, text).into_bytes();
+                        send_to(members, Some(id), &data, connections);
+                    }
+                }
+            },
+
+            _ => {}
+
+        }
+
+    }
+
+}
+
+impl Handler<Server> for ChatHandler {
+
+    fn tick_connections(
+        &mut self, _: &mut Server,
+        connections: &mut ConnectionMap
+    ) {
+
+        // Collect all commands first, since they affect other connections
+        let mut commands = Vec::new();
+        for (id, conn) in connections.iter_mut() {
+            for data in conn.received() {
+                if let Ok(text) = String::from_utf8(data) {
+                    commands.push((*id, text));
+                }
+            }
+        }
+
+        for (id, text) in commands {
+            self.command(id, &text, connections);
+        }
+
+        // Send out all pending group broadcasts
+        for (group, data) in self.broadcasts.drain(..) {
+            if let Some(members) = self.groups.get(&group) {
+                send_to(members, None, &data, connections);
+            }
+        }
+
+    }
+
+    fn connection_lost(&mut self, _: &mut Server, conn: &mut Connection) {
+        self.remove_connection(conn.id());
+    }
+
+    fn connection_closed(&mut self, _: &mut Server, conn: &mut Connection, _: bool) {
+        self.remove_connection(conn.id());
+    }
+
+}
+
+// Static Helpers -------------------------------------------------------------
+fn sorted_members(members: Option<&HashSet<ConnectionID>>) -> Vec<ConnectionID> {
+    let mut ids: Vec<ConnectionID> = members.map_or_else(Vec::new, |members| {
+        members.iter().cloned().collect()
+    });
+    ids.sort_by_key(|id| id.0);
+    ids
+}
+
+fn remove_member(
+    sets: &mut HashMap<String, HashSet<ConnectionID>>,
+    name: &str,
+    id: ConnectionID
+) {
+    let empty = sets.get_mut(name).is_some_and(|members| {
+        members.remove(&id);
+        members.is_empty()
+    });
+
+    if empty {
+        sets.remove(name);
+    }
+}
+
+fn send_to(
+    members: &HashSet<ConnectionID>,
+    sender: Option<ConnectionID>,
+    data: &[u8],
+    connections: &mut ConnectionMap
+) {
+    for id in members {
+        if Some(*id) != sender {
+            if let Some(conn) = connections.get_mut(id) {
+                conn.send(MessageKind::Ordered, data.to_vec());
+            }
+        }
+    }
+}
+
@@ -0,0 +1,55 @@
+// Copyright (c) 2015-2016 Ivo Wetzel
+
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use super::super::{ConnectionMap, Handler, MessageKind, Server};
+
+/// Server handler which sends every received message straight back to the
+/// connection it was received from.
+///
+/// # Examples
+///
+/// ```no_run
+/// use cobalt::{Config, MessageKind, Server};
+/// use cobalt::contrib::EchoHandler;
+///
+/// let mut handler = EchoHandler::new(MessageKind::Reliable);
+/// let mut server = Server::new(Config::default());
+/// server.bind(&mut handler, "127.0.0.1:7156").unwrap();
+/// ```
+#[derive(Debug, Copy, Clone)]
+pub struct EchoHandler {
+    kind: MessageKind
+}
+
+impl EchoHandler {
+
+    /// Creates a new handler which echoes messages back as the specified
+    /// `kind`.
+    pub fn new(kind: MessageKind) -> EchoHandler {
+        EchoHandler {
+            kind
+        }
+    }
+
+}
+
+impl Handler<Server> for EchoHandler {
+
+    fn tick_connections(
+        &mut self, _: &mut Server,
+        connections: &mut ConnectionMap
+    ) {
+        for conn in connections.values_mut() {
+            let messages: Vec<Vec<u8>> = conn.received().collect();
+            for data in messages {
+                conn.send(self.kind, data);
+            }
+        }
+    }
+
+}
+
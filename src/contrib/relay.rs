@@ -0,0 +1,82 @@
+// Copyright (c) 2015-2016 Ivo Wetzel
+
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use super::super::{ConnectionID, ConnectionMap, Handler, MessageKind, Server};
+
+/// Server handler which relays messages between its connections, allowing
+/// clients to exchange data without connecting to each other directly.
+///
+/// Each message starts with the 4 byte, big endian `ConnectionID` of its
+/// recipient, where an id of `0` addresses all other connections. Relayed
+/// messages start with the id of their sender instead. Messages addressed to
+/// unknown connections and messages shorter than 4 bytes are dropped.
+#[derive(Debug, Copy, Clone)]
+pub struct RelayHandler {
+    kind: MessageKind
+}
+
+impl RelayHandler {
+
+    /// Creates a new handler which relays messages as the specified `kind`.
+    pub fn new(kind: MessageKind) -> RelayHandler {
+        RelayHandler {
+            kind
+        }
+    }
+
+}
+
+impl Handler<Server> for RelayHandler {
+
+    fn tick_connections(
+        &mut self, _: &mut Server,
+        connections: &mut ConnectionMap
+    ) {
+
+        // Collect all messages first, so they can be relayed to any connection
+        let mut relayed = Vec::new();
+        for (id, conn) in connections.iter_mut() {
+            for data in conn.received() {
+                if data.len() >= 4 {
+                    relayed.push((*id, id_from_bytes(&data[..4]), data));
+                }
+            }
+        }
+
+        for (sender, recipient, mut data) in relayed {
+
+            data[..4].copy_from_slice(&id_to_bytes(sender));
+
+            if recipient == ConnectionID(0) {
+                for (id, conn) in connections.iter_mut() {
+                    if *id != sender {
+                        conn.send(self.kind, data.clone());
+                    }
+                }
+
+            } else if let Some(conn) = connections.get_mut(&recipient) {
+                conn.send(self.kind, data);
+            }
+
+        }
+
+    }
+
+}
+
+// Static Helpers -------------------------------------------------------------
+fn id_from_bytes(bytes: &[u8]) -> ConnectionID {
+    ConnectionID(
+        (bytes[0] as u32) << 24 | (bytes[1] as u32) << 16 |
+        (bytes[2] as u32) << 8  |  bytes[3] as u32
+    )
+}
+
+fn id_to_bytes(id: ConnectionID) -> [u8; 4] {
+    [(id.0 >> 24) as u8, (id.0 >> 16) as u8, (id.0 >> 8) as u8, id.0 as u8]
+}
+
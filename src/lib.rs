@@ -194,6 +194,35 @@
 //! // }
 //! ```
 //!
+//! Servers can be integrated in the same fashion via a `ServerStream`, whose
+//! events carry the id of the connection they originate from.
+//!
+//! ```
+//! use cobalt::{Config, MessageKind, ServerEvent, ServerStream};
+//!
+//! let mut stream = ServerStream::new(Config::default());
+//! stream.bind("127.0.0.1:0").ok();
+//!
+//! // Inside of the existing event loop
+//! // loop {
+//!
+//!     // Receive incoming events
+//!     while let Ok(event) = stream.receive() {
+//!         match event {
+//!             ServerEvent::Connection(id) => {
+//!                 stream.send(id, MessageKind::Reliable, b"Welcome".to_vec()).ok();
+//!             },
+//!             ServerEvent::Message(id, payload) => println!("Received message from {:?}: {:?}", id, payload),
+//!             _ => {}
+//!         }
+//!     }
+//!
+//!     // Receive and send packets
+//!     stream.flush().ok();
+//!
+//! // }
+//! ```
+//!
 //! ## Low level synchronous integration
 //!
 //! For times when even the stream based abstraction is too much, there's also
@@ -226,6 +255,36 @@
 //! // Send any pending messages via the connection
 //! client.send_sync(&mut handler, &mut state);
 //! ```
+//!
+//! `Client::run_once` combines the three calls above into one iteration of
+//! the client's own tick loop and reports whether the client is still
+//! connected, closing the connection once `Client::close` was called.
+//! In between ticks, `Client::flush_sync` sends latency critical messages
+//! right away instead of waiting for the next `send_sync` call.
+//!
+//! Servers can be driven the same way, leaving the scheduling of each tick to
+//! an existing game loop.
+//!
+//! ```
+//! use cobalt::{Config, Handler, Server};
+//!
+//! struct SyncHandler;
+//! impl Handler<Server> for SyncHandler {}
+//!
+//! let mut handler = SyncHandler;
+//! let mut server = Server::new(Config::default());
+//! let mut state = server.bind_sync(&mut handler, "127.0.0.1:0").unwrap();
+//!
+//! // Receive, tick and send once per frame of the game loop
+//! server.tick_sync(&mut handler, &mut state);
+//!
+//! // Shutdown the server once the game ends
+//! server.shutdown_sync(&mut handler, &mut state).unwrap();
+//! ```
+//!
+//! Alternatively `Server::run_once` performs one iteration of the server's own
+//! tick loop and reports whether the server is still running, shutting it down
+//! once a `ServerHandle` requested it to.
 #![cfg_attr(feature="clippy", feature(plugin))]
 #![cfg_attr(feature="clippy", plugin(clippy))]
 #![deny(
@@ -236,59 +295,168 @@
     unused_import_braces, unused_qualifications
 )]
 mod client;
+mod client_pool;
 mod client_stream;
+mod multi_client;
 mod server;
+mod server_stream;
 mod tick;
 
+/// Ready-made server handlers for common patterns, usable as building blocks
+/// for applications.
+pub mod contrib {
+    mod chat;
+    mod echo;
+    mod relay;
+
+    #[doc(inline)]
+    pub use self::chat::ChatHandler;
+
+    #[doc(inline)]
+    pub use self::echo::EchoHandler;
+
+    #[doc(inline)]
+    pub use self::relay::RelayHandler;
+}
+
 mod shared {
+    #[cfg(feature = "authentication")]
+    pub mod authentication;
     pub mod binary_rate_limiter;
     pub mod config;
+    #[cfg(feature = "connect_token")]
+    pub mod connect_token;
     pub mod connection;
+    pub mod connection_rng;
+    pub mod control;
+    #[cfg(any(feature = "encryption", feature = "authentication"))]
+    pub mod crypto_provider;
+    #[cfg(feature = "dtls")]
+    pub mod dtls_socket;
+    #[cfg(feature = "encryption")]
+    pub mod encryption;
+    pub mod fnv_hasher;
+    #[cfg(feature = "authentication")]
+    pub mod handshake_cookie;
+    pub mod host_migration;
+    #[cfg(feature = "key_exchange")]
+    pub mod key_exchange;
     pub mod message_queue;
+    pub mod multi_socket;
+    pub mod network_simulator;
+    pub mod os_id_generator;
+    pub mod packet;
+    #[cfg(feature = "encryption")]
+    pub mod resumption_ticket;
+    pub mod rtt_histogram;
+    #[cfg(all(feature = "reuse_port", unix))]
+    pub mod sharded_socket;
+    pub mod shared_socket;
+    pub mod state_dump;
     pub mod udp_socket;
     pub mod stats;
+    pub mod workers;
 }
 
 mod traits {
+    #[cfg(any(feature = "encryption", feature = "authentication"))]
+    pub mod crypto_provider;
     pub mod handler;
+    pub mod id_generator;
     pub mod rate_limiter;
     pub mod socket;
 }
 
 #[doc(inline)]
-pub use shared::config::Config;
+pub use shared::config::{
+    AddressChangePolicy, ClosedSendPolicy, Config, ConnectionConfig,
+    EndpointConfig, IdCollisionPolicy, SendOrder
+};
 
 #[doc(inline)]
 pub use shared::connection::{
     Connection,
     ConnectionID,
     ConnectionMap,
-    ConnectionState
+    ConnectionState,
+    ResumptionToken
 };
 
 #[doc(inline)]
-pub use shared::message_queue::MessageKind;
+pub use shared::connection_rng::ConnectionRng;
+
+#[doc(inline)]
+pub use shared::fnv_hasher::{FnvBuildHasher, FnvHasher};
+
+#[cfg(feature = "connect_token")]
+#[doc(inline)]
+pub use shared::connect_token::ConnectToken;
+
+#[doc(inline)]
+pub use shared::host_migration::{HostMigration, Peer};
+
+#[doc(inline)]
+pub use shared::message_queue::{MessageHandle, MessageKind};
+
+#[doc(inline)]
+pub use shared::packet::PacketType;
+
+#[cfg(feature = "encryption")]
+#[doc(inline)]
+pub use shared::resumption_ticket::ResumptionTicket;
 
 #[doc(inline)]
 pub use shared::binary_rate_limiter::BinaryRateLimiter;
 
+#[cfg(any(feature = "encryption", feature = "authentication"))]
+#[doc(inline)]
+pub use shared::crypto_provider::{DefaultCryptoProvider, install_crypto_provider};
+
+#[doc(inline)]
+pub use shared::os_id_generator::OsIdGenerator;
+
+#[doc(inline)]
+pub use shared::multi_socket::MultiSocket;
+
 #[doc(inline)]
 pub use shared::udp_socket::UdpSocket;
 
+#[cfg(feature = "dtls")]
 #[doc(inline)]
-pub use shared::stats::Stats;
+pub use shared::dtls_socket::DtlsSocket;
 
+#[cfg(all(feature = "reuse_port", unix))]
 #[doc(inline)]
-pub use traits::handler::Handler;
+pub use shared::sharded_socket::ShardedSocket;
+
+#[doc(inline)]
+pub use shared::stats::{ClientStats, PacketDropReason, SecurityStats, ServerStats, Stats};
+
+#[doc(inline)]
+pub use shared::state_dump::{ConnectionInfo, ConnectionStateDump, ServerStateDump};
+
+#[doc(inline)]
+pub use traits::handler::{Admission, BanTarget, ConnectionFailure, Handler};
 
 #[doc(inline)]
 pub use traits::rate_limiter::RateLimiter;
 
+#[doc(inline)]
+pub use traits::id_generator::IdGenerator;
+
+#[cfg(any(feature = "encryption", feature = "authentication"))]
+#[doc(inline)]
+pub use traits::crypto_provider::CryptoProvider;
+
+#[cfg(feature = "key_exchange")]
+#[doc(inline)]
+pub use traits::crypto_provider::KeyAgreement;
+
 #[doc(inline)]
 pub use traits::socket::Socket;
 
 #[doc(inline)]
-pub use client::Client;
+pub use client::{Client, ClientHandle, ClientSession};
 
 #[doc(inline)]
 pub use client::ClientState;
@@ -296,19 +464,49 @@ pub use client::ClientState;
 #[doc(inline)]
 pub use client_stream::ClientStream;
 
+#[doc(inline)]
+pub use client_pool::{ClientPool, PoolClientID};
+
 #[doc(inline)]
 pub use client_stream::ClientEvent;
 
 #[doc(inline)]
-pub use server::Server;
+pub use multi_client::MultiClient;
+
+#[doc(inline)]
+pub use server::{Server, ServerHandle, ServerState};
+
+#[doc(inline)]
+pub use server_stream::{ServerEvent, ServerStream};
 
 #[cfg(test)]
 mod tests {
     mod client;
+    mod client_pool;
     mod client_stream;
+    mod compat;
+    mod config;
+    mod contrib;
     mod connection;
+    mod connection_rng;
+    #[cfg(feature = "dtls")]
+    mod dtls_socket;
+    #[cfg(feature = "encryption")]
+    mod encryption;
+    mod fnv_hasher;
+    #[cfg(feature = "authentication")]
+    mod handshake_cookie;
     mod message_queue;
+    mod multi_client;
+    mod multi_socket;
+    #[cfg(feature = "encryption")]
+    mod resumption_ticket;
+    mod rtt_histogram;
     mod server;
+    #[cfg(all(feature = "reuse_port", unix))]
+    mod sharded_socket;
+    mod server_stream;
+    mod workers;
     mod mock;
 }
 
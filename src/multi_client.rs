@@ -0,0 +1,233 @@
+// Copyright (c) 2015-2016 Ivo Wetzel
+
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::rc::Rc;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::io::{Error, ErrorKind};
+use std::net::{SocketAddr, ToSocketAddrs};
+use traits::socket::Socket;
+use shared::shared_socket::{Inbox, SharedSocket};
+use shared::udp_socket::{UdpSocket, canonical_addr};
+use super::{
+    Client, ClientState, Config, Connection, ConnectionID, ConnectionState,
+    Handler, MessageHandle, MessageKind
+};
+
+/// Implementation of a client which maintains connections to several servers
+/// at once over a single local socket, i.e. to a game server and a separate
+/// chat or relay server.
+///
+/// Each connection is driven by its own `Client` instance, which is passed to
+/// the `Handler` callbacks along with the connection, so events can be told
+/// apart by their `ConnectionID`. Incoming packets are dispatched to the
+/// connections by the address of the server they were received from.
+///
+/// Connections are removed once they failed to connect, were lost or closed,
+/// or once `Client::close` was called on their client from one of the
+/// handler's callbacks.
+///
+/// # Examples
+///
+/// ```no_run
+/// use cobalt::{Client, Config, Handler, MessageKind, MultiClient};
+///
+/// struct MultiHandler;
+/// impl Handler<Client> for MultiHandler {}
+///
+/// let mut handler = MultiHandler;
+/// let mut client = MultiClient::bind(Config::default(), "0.0.0.0:0").unwrap();
+/// let game = client.connect_sync(&mut handler, "127.0.0.1:7156").unwrap();
+/// let chat = client.connect_sync(&mut handler, "127.0.0.1:7157").unwrap();
+///
+/// client.send(chat, MessageKind::Reliable, b"Hello".to_vec()).unwrap();
+///
+/// // Receive, tick and send all connections once per frame of the game loop
+/// client.tick_sync(&mut handler);
+///
+/// client.close_sync(&mut handler, game).unwrap();
+/// client.shutdown_sync(&mut handler);
+/// ```
+#[derive(Debug)]
+pub struct MultiClient<S: Socket> {
+    config: Config,
+    socket: Rc<RefCell<S>>,
+    clients: HashMap<ConnectionID, MultiClientEntry<S>>
+}
+
+impl MultiClient<UdpSocket> {
+
+    /// Creates a new client with the given configuration, which binds its
+    /// socket to the specified local address.
+    pub fn bind<A: ToSocketAddrs>(config: Config, addr: A) -> Result<MultiClient<UdpSocket>, Error> {
+        let socket = UdpSocket::new(addr, config.packet_max_size)?;
+        Ok(MultiClient::from_socket(config, socket))
+    }
+
+}
+
+impl<S: Socket> MultiClient<S> {
+
+    /// Creates a new client with the given configuration, which uses the
+    /// specified socket for all of its connections.
+    pub fn from_socket(config: Config, socket: S) -> MultiClient<S> {
+        MultiClient {
+            config,
+            socket: Rc::new(RefCell::new(socket)),
+            clients: HashMap::new()
+        }
+    }
+
+    /// Returns the local address of the client's socket.
+    pub fn local_addr(&self) -> Result<SocketAddr, Error> {
+        self.socket.borrow().local_addr()
+    }
+
+    /// Returns the ids of all of the client's connections, sorted by their
+    /// value.
+    pub fn connections(&self) -> Vec<ConnectionID> {
+        let mut ids: Vec<ConnectionID> = self.clients.keys().cloned().collect();
+        ids.sort_by_key(|id| id.0);
+        ids
+    }
+
+    /// Returns the connection with the specified id, if any.
+    pub fn connection(&mut self, id: ConnectionID) -> Option<&mut Connection> {
+        self.clients.get_mut(&id).map(|entry| entry.state.connection_mut())
+    }
+
+    /// Establishes a connection with the server at the specified address,
+    /// returning the id of the new connection.
+    ///
+    /// Returns an error of kind `AlreadyExists` in case the client is already
+    /// connected to the specified address.
+    pub fn connect_sync<A: ToSocketAddrs>(
+        &mut self, handler: &mut dyn Handler<Client>, addr: A
+
+    ) -> Result<ConnectionID, Error> {
+
+        let peer_addr = canonical_addr(addr.to_socket_addrs()?.next().ok_or_else(|| {
+            Error::new(ErrorKind::AddrNotAvailable, "")
+        })?);
+
+        // Packets are dispatched by the address of their server
+        if self.clients.values().any(|entry| entry.state.peer_addr() == peer_addr) {
+            return Err(Error::new(ErrorKind::AlreadyExists, ""));
+        }
+
+        let (socket, incoming) = SharedSocket::new(self.socket.clone());
+
+        let mut client = Client::new(self.config);
+        let mut state = client.connect_from_socket_sync(handler, peer_addr, socket)?;
+
+        let id = state.id();
+        match self.clients.entry(id) {
+            Entry::Occupied(_) => {
+                client.close_sync(handler, &mut state).ok();
+                Err(Error::new(ErrorKind::AlreadyExists, ""))
+            },
+            Entry::Vacant(entry) => {
+                entry.insert(MultiClientEntry {
+                    client,
+                    state,
+                    incoming
+                });
+                Ok(id)
+            }
+        }
+
+    }
+
+    /// Queues a message of the specified `kind` along with its `payload` to
+    /// be send over the connection with the specified id.
+    pub fn send(
+        &mut self, id: ConnectionID, kind: MessageKind, payload: Vec<u8>
+
+    ) -> Result<MessageHandle, Error> {
+        if let Some(entry) = self.clients.get_mut(&id) {
+            entry.state.try_send(kind, payload)
+
+        } else {
+            Err(Error::new(ErrorKind::NotConnected, ""))
+        }
+    }
+
+    /// Receives all buffered incoming packets, ticks all connections and
+    /// sends their outgoing packets.
+    pub fn tick_sync(&mut self, handler: &mut dyn Handler<Client>) {
+
+        self.dispatch();
+
+        for id in self.connections() {
+
+            let entry = self.clients.get_mut(&id).unwrap();
+            let running = entry.client.run_once(handler, &mut entry.state);
+
+            if !running {
+                self.clients.remove(&id);
+
+            } else {
+                match entry.state.connection_mut().state() {
+                    ConnectionState::FailedToConnect | ConnectionState::Lost | ConnectionState::Closed => {
+                        self.close_sync(handler, id).ok();
+                    },
+                    _ => {}
+                }
+            }
+
+        }
+
+    }
+
+    /// Closes the connection with the specified id.
+    pub fn close_sync(
+        &mut self, handler: &mut dyn Handler<Client>, id: ConnectionID
+
+    ) -> Result<(), Error> {
+        if let Some(mut entry) = self.clients.remove(&id) {
+            entry.client.close_sync(handler, &mut entry.state)
+
+        } else {
+            Err(Error::new(ErrorKind::NotConnected, ""))
+        }
+    }
+
+    /// Closes all of the client's connections.
+    pub fn shutdown_sync(&mut self, handler: &mut dyn Handler<Client>) {
+        for id in self.connections() {
+            self.close_sync(handler, id).ok();
+        }
+    }
+
+    // Routes the packets received on the socket to the connection with the
+    // server they were send from
+    fn dispatch(&mut self) {
+
+        let routes: HashMap<SocketAddr, ConnectionID> = self.clients.iter().map(|(id, entry)| {
+            (entry.state.peer_addr(), *id)
+
+        }).collect();
+
+        while let Ok((addr, packet)) = self.socket.borrow_mut().try_recv() {
+            let addr = canonical_addr(addr);
+            if let Some(id) = routes.get(&addr) {
+                self.clients[id].incoming.borrow_mut().push_back((addr, packet));
+            }
+        }
+
+    }
+
+}
+
+#[derive(Debug)]
+struct MultiClientEntry<S: Socket> {
+    client: Client,
+    state: ClientState<SharedSocket<S>>,
+    incoming: Inbox
+}
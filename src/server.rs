@@ -6,222 +6,2175 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
+extern crate rand;
+
+use std::cmp;
+use std::fmt;
+use std::mem;
 use std::io::{Error, ErrorKind};
-use std::collections::HashMap;
-use std::net::{SocketAddr, ToSocketAddrs};
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, ToSocketAddrs};
+use std::ops::RangeInclusive;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::time::{Duration, Instant};
 use traits::socket::Socket;
+use shared::multi_socket::MultiSocket;
 use shared::udp_socket::UdpSocket;
-use shared::stats::{StatsCollector, Stats};
-use super::{Config, Connection, ConnectionID, Handler, tick};
+use shared::workers::WorkerPool;
+use shared::host_migration::Peer;
+use shared::stats::{
+    ConnectionChurn, PacketDropReason, SecurityStats, ServerStats, StatsCollector, Stats
+};
+use self::rand::{thread_rng, Rng};
+#[cfg(feature = "connect_token")]
+use shared::connect_token::ConnectToken;
+#[cfg(feature = "encryption")]
+use shared::resumption_ticket::ResumptionTicket;
+#[cfg(feature = "authentication")]
+use shared::handshake_cookie;
+use super::{
+    Admission, AddressChangePolicy, BanTarget, Config, Connection, ConnectionFailure,
+    ConnectionID, ConnectionInfo, ConnectionMap, ConnectionState, FnvBuildHasher, Handler,
+    IdCollisionPolicy, IdGenerator, MessageKind, SendOrder, ServerStateDump, tick
+};
+
+/// Commands send to a running `Server` via its `ServerHandle`s.
+#[derive(Debug)]
+enum Command {
+    Shutdown,
+    Kick(ConnectionID, u8),
+    Send(ConnectionID, MessageKind, Vec<u8>),
+    Broadcast(MessageKind, Vec<u8>, Option<ConnectionID>),
+    Multicast(Vec<ConnectionID>, MessageKind, Vec<u8>),
+    BroadcastFiltered(MessageKind, Vec<u8>, BroadcastFilter),
+    SetBandwidthLimit(ConnectionID, u32),
+    SetConfig(Box<Config>)
+}
+
+/// Changes to the bans of a `Server` which have yet to be reported to its
+/// `Handler`.
+#[derive(Debug)]
+enum BanChange {
+    Applied(BanTarget, Option<Duration>),
+    Lifted(BanTarget)
+}
+
+/// Function deciding the payload of a `Server::broadcast_filtered` message
+/// for a single connection.
+type FilterFn = dyn FnMut(&Connection, &[u8]) -> Option<Vec<u8>> + Send;
+
+/// Per connection filter of a `Server::broadcast_filtered` message.
+struct BroadcastFilter(Box<FilterFn>);
+
+impl fmt::Debug for BroadcastFilter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "BroadcastFilter")
+    }
+}
+
+/// A cloneable handle which allows other threads to control a running
+/// `Server`.
+///
+/// Commands are applied at the start of the server's next tick and once more
+/// before it sends its packets.
+///
+/// # Examples
+///
+/// ```
+/// use std::thread;
+/// use cobalt::{Config, Handler, MessageKind, Server};
+///
+/// struct GameServer;
+/// impl Handler<Server> for GameServer {}
+///
+/// let mut server = Server::new(Config::default());
+/// let handle = server.handle();
+///
+/// let control = thread::spawn(move || {
+///     handle.broadcast(MessageKind::Reliable, b"Goodbye".to_vec()).unwrap();
+///     handle.shutdown().unwrap();
+/// });
+///
+/// server.bind(&mut GameServer, "127.0.0.1:0").unwrap();
+/// control.join().unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct ServerHandle {
+    commands: Sender<Command>
+}
+
+impl ServerHandle {
+
+    /// Requests the server to shut down, exiting its tick loop.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `BrokenPipe` in case the server was dropped.
+    pub fn shutdown(&self) -> Result<(), Error> {
+        self.command(Command::Shutdown)
+    }
+
+    /// Requests the server to close the connection with the specified id,
+    /// like `Server::kick`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `BrokenPipe` in case the server was dropped.
+    pub fn kick(&self, id: ConnectionID, reason: u8) -> Result<(), Error> {
+        self.command(Command::Kick(id, reason))
+    }
+
+    /// Requests the server to send a message of the specified `kind` to the
+    /// connection with the specified id.
+    ///
+    /// Messages for unknown connections are discarded.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `BrokenPipe` in case the server was dropped.
+    pub fn send(&self, id: ConnectionID, kind: MessageKind, payload: Vec<u8>) -> Result<(), Error> {
+        self.command(Command::Send(id, kind, payload))
+    }
+
+    /// Requests the server to send a message of the specified `kind` to all
+    /// of its established connections.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `BrokenPipe` in case the server was dropped.
+    pub fn broadcast(&self, kind: MessageKind, payload: Vec<u8>) -> Result<(), Error> {
+        self.command(Command::Broadcast(kind, payload, None))
+    }
+
+    /// Requests the server to send a message of the specified `kind` to all
+    /// of its established connections, except the one with the specified id.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `BrokenPipe` in case the server was dropped.
+    pub fn broadcast_except(
+        &self, except: ConnectionID, kind: MessageKind, payload: Vec<u8>
+
+    ) -> Result<(), Error> {
+        self.command(Command::Broadcast(kind, payload, Some(except)))
+    }
+
+    /// Requests the server to send a message of the specified `kind` to all
+    /// of its established connections, as decided by the `filter`, like
+    /// `Server::broadcast_filtered`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `BrokenPipe` in case the server was dropped.
+    pub fn broadcast_filtered<F>(
+        &self, kind: MessageKind, payload: Vec<u8>, filter: F
+
+    ) -> Result<(), Error>
+        where F: FnMut(&Connection, &[u8]) -> Option<Vec<u8>> + Send + 'static {
+        self.command(Command::BroadcastFiltered(kind, payload, BroadcastFilter(Box::new(filter))))
+    }
+
+    /// Requests the server to cap the outgoing bandwidth of the connection
+    /// with the specified id, like `Server::set_bandwidth_limit`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `BrokenPipe` in case the server was dropped.
+    pub fn set_bandwidth_limit(&self, id: ConnectionID, bytes_per_second: u32) -> Result<(), Error> {
+        self.command(Command::SetBandwidthLimit(id, bytes_per_second))
+    }
+
+    /// Requests the server to replace its configuration along with the one
+    /// of all of its connections, without resetting any of them.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `BrokenPipe` in case the server was dropped.
+    pub fn set_config(&self, config: Config) -> Result<(), Error> {
+        self.command(Command::SetConfig(Box::new(config)))
+    }
+
+    fn command(&self, command: Command) -> Result<(), Error> {
+        self.commands.send(command).map_err(|_| {
+            Error::new(ErrorKind::BrokenPipe, "")
+        })
+    }
+
+}
+
+/// A lost connection which can still be resumed by its client.
+#[derive(Debug)]
+struct ResumableConnection {
+    secret: u64,
+    lost_time: Instant,
+    messages: Vec<(MessageKind, Vec<u8>)>
+}
+
+/// Per second budget of packets from addresses without an established
+/// connection and of newly created connections.
+#[derive(Debug)]
+struct HandshakeLimiter {
+    window_start: Instant,
+    handshakes: u32,
+    packets: HashMap<IpAddr, u32>,
+    rejections: HashSet<IpAddr>
+}
+
+impl HandshakeLimiter {
+
+    fn new() -> HandshakeLimiter {
+        HandshakeLimiter {
+            window_start: Instant::now(),
+            handshakes: 0,
+            packets: HashMap::new(),
+            rejections: HashSet::new()
+        }
+    }
+
+    fn update(&mut self) {
+        if self.window_start.elapsed() >= Duration::from_secs(1) {
+            self.window_start = Instant::now();
+            self.handshakes = 0;
+            self.packets.clear();
+            self.rejections.clear();
+        }
+    }
+
+    fn allow_packet(&mut self, ip: IpAddr, limit: u32) -> bool {
+        if limit == 0 {
+            return true;
+        }
+
+        let count = self.packets.entry(ip).or_insert(0);
+        if *count < limit {
+            *count += 1;
+            true
+
+        } else {
+            false
+        }
+    }
+
+    fn allow_rejection(&mut self, ip: IpAddr) -> bool {
+        self.rejections.insert(ip)
+    }
+
+    fn allow_handshake(&mut self, limit: u32) -> bool {
+        if limit == 0 {
+            true
+
+        } else if self.handshakes < limit {
+            self.handshakes += 1;
+            true
+
+        } else {
+            false
+        }
+    }
+
+}
+
+/// Token bucket per IP address which limits the rate of all incoming packets.
+#[derive(Debug)]
+struct FloodLimiter {
+    buckets: HashMap<IpAddr, (f64, Instant)>,
+    throttled: HashMap<IpAddr, u32>
+}
+
+impl FloodLimiter {
+
+    fn new() -> FloodLimiter {
+        FloodLimiter {
+            buckets: HashMap::new(),
+            throttled: HashMap::new()
+        }
+    }
+
+    fn update(&mut self, rate: u32, burst: u32) {
+        // Forget about buckets which have been refilled completely
+        if rate > 0 {
+            let refill = Duration::from_secs_f64(flood_burst(rate, burst) / rate as f64);
+            self.buckets.retain(|_, &mut (_, last)| last.elapsed() < refill);
+
+        } else {
+            self.buckets.clear();
+        }
+    }
+
+    fn allow_packet(&mut self, ip: IpAddr, rate: u32, burst: u32) -> bool {
+        if rate == 0 {
+            return true;
+        }
+
+        let burst = flood_burst(rate, burst);
+        let now = Instant::now();
+        let bucket = self.buckets.entry(ip).or_insert((burst, now));
+        bucket.0 = (bucket.0 + now.duration_since(bucket.1).as_secs_f64() * rate as f64).min(burst);
+        bucket.1 = now;
+
+        if bucket.0 >= 1.0 {
+            bucket.0 -= 1.0;
+            true
+
+        } else {
+            *self.throttled.entry(ip).or_insert(0) += 1;
+            false
+        }
+    }
+
+    fn take_throttled(&mut self) -> Vec<(IpAddr, u32)> {
+        self.throttled.drain().collect()
+    }
+
+}
 
 /// Implementation of a multi-client server with handler based event dispatch.
+///
+/// There are two ways of binding a server instance:
+///
+/// 1. Blocking mode to be used in it dedicated thread, with asynchronous
+///    `Handler` callbacks, available via the methods **without** the `sync`
+///    postfix.
+///
+/// 2. Non-Blocking mode, with synchronous `Handler` callbacks, available via
+///    the methods **with** the `sync` postfix, i.e. to embed the server into
+///    an existing game loop.
 #[derive(Debug)]
 pub struct Server {
     closed: bool,
     config: Config,
     local_address: Option<SocketAddr>,
-    statistics: StatsCollector
+    statistics: StatsCollector,
+    churn: ConnectionChurn,
+    tick_number: u32,
+    banned_addresses: HashMap<IpAddr, Option<Instant>>,
+    banned_connections: HashMap<ConnectionID, Option<Instant>>,
+    ban_changes: Vec<BanChange>,
+    allowed_addresses: HashSet<IpAddr>,
+    allowed_connections: HashSet<ConnectionID>,
+    security_stats: SecurityStats,
+    totals: ServerStats,
+    groups: HashMap<String, HashSet<ConnectionID>>,
+    relays: HashMap<ConnectionID, ConnectionID>,
+    connection_info: Vec<ConnectionInfo>,
+    command_sender: Sender<Command>,
+    commands: Receiver<Command>,
+    #[cfg(feature = "authentication")]
+    cookie_secret: [u8; 32]
 }
 
 impl Server {
 
     /// Creates a new server with the given configuration.
     pub fn new(config: Config) -> Server {
+        let (command_sender, commands) = channel();
         Server {
             closed: false,
-            config: config,
+            config,
             local_address: None,
-            statistics: StatsCollector::new(config)
+            statistics: StatsCollector::new(config),
+            churn: ConnectionChurn::default(),
+            tick_number: 0,
+            banned_addresses: HashMap::new(),
+            banned_connections: HashMap::new(),
+            ban_changes: Vec::new(),
+            allowed_addresses: HashSet::new(),
+            allowed_connections: HashSet::new(),
+            security_stats: SecurityStats::default(),
+            totals: ServerStats::default(),
+            groups: HashMap::new(),
+            relays: HashMap::new(),
+            connection_info: Vec::new(),
+            command_sender,
+            commands,
+            #[cfg(feature = "authentication")]
+            cookie_secret: handshake_cookie::secret()
         }
     }
 
-    /// Returns the local address that the server is bound to.
-    pub fn local_addr(&self) -> Result<SocketAddr, Error> {
-        self.local_address.ok_or_else(|| Error::new(ErrorKind::AddrNotAvailable, ""))
+    /// Returns a new handle which allows other threads to control the server
+    /// while it is running.
+    pub fn handle(&self) -> ServerHandle {
+        ServerHandle {
+            commands: self.command_sender.clone()
+        }
     }
 
-    /// Returns statistics (i.e. bandwidth usage) for the last second.
-    pub fn stats(&mut self) -> Stats {
-        self.statistics.average()
+    /// Queues a message of the specified `kind` for the connection with the
+    /// specified id, without requiring access to the connection itself.
+    ///
+    /// The message is send along with the connection's next packet, while
+    /// messages for unknown connections are discarded.
+    pub fn send(&self, id: ConnectionID, kind: MessageKind, payload: Vec<u8>) {
+        self.command_sender.send(Command::Send(id, kind, payload)).ok();
     }
 
-    /// Binds the server to the specified local address by creating a socket
-    /// and actively listens for incoming client connections.
+    /// Closes the connection with the specified id, sending the `reason`
+    /// code to its remote and invoking `Handler::connection_kicked` on both
+    /// ends.
     ///
-    /// Clients connecting to the server must use a compatible connection
-    /// / packet configuration in order to be able to connect.
+    /// The connection is closed with the server's next tick.
+    pub fn kick(&self, id: ConnectionID, reason: u8) {
+        self.command_sender.send(Command::Kick(id, reason)).ok();
+    }
+
+    /// Caps the number of bytes per second send to the connection with the
+    /// specified id, i.e. in order to deprioritize spectators. A value of `0`
+    /// removes the cap.
     ///
-    /// The `handler` is a struct that implements the `Handler` trait in order
-    /// to handle events from the server and its connections.
-    pub fn bind<A: ToSocketAddrs>(
-        &mut self, handler: &mut Handler<Server>, addr: A
+    /// Messages which would exceed the cap remain queued on the connection
+    /// until its next packets, see `Connection::set_bandwidth_limit`. The cap
+    /// is applied with the server's next tick.
+    pub fn set_bandwidth_limit(&self, id: ConnectionID, bytes_per_second: u32) {
+        self.command_sender.send(Command::SetBandwidthLimit(id, bytes_per_second)).ok();
+    }
 
-    ) -> Result<(), Error> {
+    /// Queues a message of the specified `kind` for all established
+    /// connections, which is send along with their next packets.
+    pub fn broadcast(&self, kind: MessageKind, payload: Vec<u8>) {
+        self.command_sender.send(Command::Broadcast(kind, payload, None)).ok();
+    }
 
-        let socket = try!(UdpSocket::new(
-            addr,
-            self.config.packet_max_size
-        ));
+    /// Queues a message of the specified `kind` for all established
+    /// connections except the one with the specified id, i.e. in order to
+    /// relay a message to everyone but its sender.
+    pub fn broadcast_except(&self, except: ConnectionID, kind: MessageKind, payload: Vec<u8>) {
+        self.command_sender.send(Command::Broadcast(kind, payload, Some(except))).ok();
+    }
 
-        self.bind_to_socket(handler, socket)
+    /// Queues a message of the specified `kind` for all established
+    /// connections, invoking the `filter` with each of them and the
+    /// `payload` in order to decide what they are send, i.e. for interest
+    /// management.
+    ///
+    /// The filter returns `None` in order to skip a connection, or the
+    /// payload for it, which may be trimmed down to the parts relevant to
+    /// its remote.
+    pub fn broadcast_filtered<F>(&self, kind: MessageKind, payload: Vec<u8>, filter: F)
+        where F: FnMut(&Connection, &[u8]) -> Option<Vec<u8>> + Send + 'static {
+        self.command_sender.send(
+            Command::BroadcastFiltered(kind, payload, BroadcastFilter(Box::new(filter)))
 
+        ).ok();
     }
 
-    /// Binds the server to specified socket and actively listens for incoming
-    /// client connections.
+    /// Adds the connection with the specified id to the named `group`, i.e.
+    /// a lobby or a match, creating the group if necessary.
     ///
-    /// Clients connecting to the server must use a compatible connection
-    /// / packet configuration in order to be able to connect.
+    /// Connections can be members of any number of groups and are removed
+    /// from all of them once they are dropped by the server.
+    pub fn join_group(&mut self, group: &str, id: ConnectionID) {
+        self.groups.entry(group.to_string()).or_default().insert(id);
+    }
+
+    /// Removes the connection with the specified id from the named `group`,
+    /// returning whether it was a member.
     ///
-    /// The `handler` is a struct that implements the `Handler` trait in order
-    /// to handle events from the server and its connections.
-    pub fn bind_to_socket<S: Socket>(
-        &mut self, handler: &mut Handler<Server>, mut socket: S
+    /// Groups without any members are removed.
+    pub fn leave_group(&mut self, group: &str, id: ConnectionID) -> bool {
+        let left = self.groups.get_mut(group).is_some_and(|members| members.remove(&id));
+        if self.groups.get(group).is_some_and(|members| members.is_empty()) {
+            self.groups.remove(group);
+        }
+        left
+    }
 
-    ) -> Result<(), Error> {
+    /// Returns the ids of all connections in the named `group`, sorted by
+    /// their value.
+    pub fn group_members(&self, group: &str) -> Vec<ConnectionID> {
+        let mut members: Vec<ConnectionID> = self.groups.get(group).map(|members| {
+            members.iter().cloned().collect()
 
-        // Store bound socket address
-        let local_addr = try!(socket.local_addr());
-        self.local_address = Some(local_addr);
+        }).unwrap_or_default();
+        members.sort_by_key(|id| id.0);
+        members
+    }
 
-        // Reset stats
-        self.statistics.reset();
+    /// Returns the names of all groups the connection with the specified id
+    /// is a member of, sorted by name.
+    pub fn groups_of(&self, id: ConnectionID) -> Vec<String> {
+        let mut groups: Vec<String> = self.groups.iter().filter(|&(_, members)| {
+            members.contains(&id)
 
-        // List of dropped connections
-        let mut dropped: Vec<ConnectionID> = Vec::new();
+        }).map(|(group, _)| group.clone()).collect();
+        groups.sort();
+        groups
+    }
 
-        // Mappping of connections to their remote sender address
-        let mut addresses: HashMap<ConnectionID, SocketAddr> = HashMap::new();
+    /// Queues a message of the specified `kind` for all established
+    /// connections in the named `group`, which is send along with their next
+    /// packets.
+    pub fn broadcast_group(&self, group: &str, kind: MessageKind, payload: Vec<u8>) {
+        self.command_sender.send(Command::Multicast(self.group_members(group), kind, payload)).ok();
+    }
 
-        // Mapping of the actual connection objects
-        let mut connections: HashMap<ConnectionID, Connection> = HashMap::new();
+    /// Pairs the connections with the specified ids, so that all messages
+    /// received from either of them are forwarded to the other one with the
+    /// same `MessageKind`, i.e. for relaying between clients which can't
+    /// connect to each other directly.
+    ///
+    /// Forwarded messages are not passed on to `Handler::tick_connections`.
+    /// Any previous pairings of the two connections are dissolved, and
+    /// pairings end once either connection is dropped by the server.
+    pub fn pair(&mut self, a: ConnectionID, b: ConnectionID) {
+        self.unpair(a);
+        self.unpair(b);
+        self.relays.insert(a, b);
+        self.relays.insert(b, a);
+    }
 
-        // Invoke handler
-        handler.bind(self);
+    /// Dissolves the pairing of the connection with the specified id,
+    /// returning the id of the connection it was paired with, if any.
+    pub fn unpair(&mut self, id: ConnectionID) -> Option<ConnectionID> {
+        let partner = self.relays.remove(&id)?;
+        self.relays.remove(&partner);
+        Some(partner)
+    }
 
-        // Receive and send until we shut down.
-        let mut tick_overflow = 0;
-        while !self.closed {
+    /// Returns the id of the connection the one with the specified id is
+    /// paired with, if any.
+    pub fn paired(&self, id: ConnectionID) -> Option<ConnectionID> {
+        self.relays.get(&id).cloned()
+    }
 
-            let tick_start = tick::start();
-            let tick_delay = 1000_000_000 / self.config.send_rate;
+    /// Returns a copy of the server's current configuration.
+    pub fn config(&self) -> Config {
+        self.config
+    }
 
-            // Receive all incoming UDP packets to our local address
-            let mut bytes_received = 0;
-            while let Ok((addr, packet)) = socket.try_recv() {
+    /// Replaces the configuration of the server along with the one of all of
+    /// its connections, i.e. in order to tune the `send_rate` or connection
+    /// thresholds at runtime.
+    ///
+    /// The connections are updated with the start of the next tick, without
+    /// resetting any of them.
+    pub fn set_config(&mut self, config: Config) {
+        self.config = config;
+        self.statistics.set_config(config);
+        self.command_sender.send(Command::SetConfig(Box::new(config))).ok();
+    }
 
-                // Try to extract the connection id from the packet
-                if let Some(id) = Connection::id_from_packet(&self.config, &packet) {
+    /// Returns the local address that the server is bound to.
+    pub fn local_addr(&self) -> Result<SocketAddr, Error> {
+        self.local_address.ok_or_else(|| Error::new(ErrorKind::AddrNotAvailable, ""))
+    }
 
-                    // Retrieve or create a connection for the current
-                    // connection id
-                    let connection = connections.entry(id).or_insert_with(|| {
+    /// Returns statistics (i.e. bandwidth usage) for the last second.
+    pub fn stats(&mut self) -> Stats {
+        self.statistics.average()
+    }
 
-                        // Also map the intitial address which is used by
-                        // the connection
-                        addresses.insert(id, addr);
+    /// Returns the ids of all of the specified `connections` which carry the
+    /// `tag`, sorted by their value, i.e. in order to send messages to all
+    /// admins from within `Handler::tick_connections`.
+    ///
+    /// See `Connection::tag` for attaching tags.
+    pub fn connections_with_tag(
+        &self, connections: &ConnectionMap, tag: &str
 
-                        let mut conn = Connection::new(
-                            self.config,
-                            local_addr,
-                            addr,
-                            handler.rate_limiter(&self.config)
-                        );
+    ) -> Vec<ConnectionID> {
+        let mut ids: Vec<ConnectionID> = connections.values().filter(|conn| {
+            conn.has_tag(tag)
 
-                        conn.set_id(id);
-                        conn
+        }).map(|conn| conn.id()).collect();
+        ids.sort_by_key(|id| id.0);
+        ids
+    }
 
-                    });
+    /// Returns read-only information about each of the server's connections
+    /// as of the end of its last tick, ordered by their id.
+    ///
+    /// Unlike the connections passed to `Handler::tick_connections` this is
+    /// available in between ticks, i.e. for serving monitoring endpoints
+    /// from the thread that drives a server via `Server::tick_sync`.
+    pub fn connections(&self) -> impl Iterator<Item = (ConnectionID, &ConnectionInfo)> {
+        self.connection_info.iter().map(|info| (info.id, info))
+    }
 
-                    // Map the current remote address of the connection to
-                    // the latest address that sent a packet for the
-                    // connection id in question. This is done in order to
-                    // work in situations were the remote port of a
-                    // connection is switched around by NAT.
-                    if addr != connection.peer_addr() {
-                        connection.set_peer_addr(addr);
-                        addresses.remove(&id);
-                        addresses.insert(id, addr);
-                    }
+    /// Returns read-only information about the connection with the specified
+    /// id as of the end of the server's last tick, if any.
+    pub fn connection_info(&self, id: ConnectionID) -> Option<&ConnectionInfo> {
+        self.connection_info.binary_search_by_key(&id.0, |info| info.id.0).ok().map(|index| {
+            &self.connection_info[index]
+        })
+    }
 
-                    // Statistics
-                    bytes_received += packet.len();
+    /// Returns the totals of the server since it was bound, along with an
+    /// aggregate over the specified `connections`, i.e. those passed to
+    /// `Handler::tick_connections`.
+    ///
+    /// The average round trip time and packet loss only take connections
+    /// into account which are currently connected.
+    pub fn aggregate_stats(
+        &self, connections: &ConnectionMap
 
-                    // Then feed the packet into the connection object for
-                    // parsing
-                    connection.receive_packet(
-                        packet, tick_delay / 1000_000, self, handler
-                    );
+    ) -> ServerStats {
 
-                }
+        let mut stats = self.totals;
+        let mut rtt = 0u64;
+        let mut packet_loss = 0.0;
 
+        for conn in connections.values() {
+            match conn.state() {
+                ConnectionState::Connected => {
+                    stats.active_connections += 1;
+                    rtt += u64::from(conn.rtt());
+                    packet_loss += conn.packet_loss();
+                },
+                ConnectionState::Connecting => stats.pending_connections += 1,
+                _ => {}
             }
+        }
 
-            self.statistics.set_bytes_received(bytes_received as u32);
+        if stats.active_connections > 0 {
+            stats.average_rtt = (rtt / stats.active_connections as u64) as u32;
+            stats.packet_loss = packet_loss / stats.active_connections as f32;
+        }
 
-            // Invoke handler
-            handler.tick_connections(self, &mut connections);
+        stats
 
-            // Create outgoing packets for all connections
-            let mut bytes_sent = 0;
-            for (id, conn) in &mut connections {
+    }
 
-                // Resolve the last known remote address for this
-                // connection and send the data
-                let addr = addresses.get(id).unwrap();
+    /// Returns the total number of received packets which were dropped for
+    /// each cause, including those dropped by the server's connections.
+    pub fn security_stats(&self) -> SecurityStats {
+        self.security_stats
+    }
 
-                // Then invoke the connection to send a outgoing packet
-                bytes_sent += conn.send_packet(&mut socket, addr, self, handler);
+    /// Returns the number of the current tick, which is send along with data
+    /// packets when `Config::packet_tick_numbers` is enabled.
+    ///
+    /// The number is increased by one after each tick's packets were send.
+    pub fn tick_number(&self) -> u32 {
+        self.tick_number
+    }
 
-                // Collect all lost / closed connections
-                if !conn.open() {
-                    dropped.push(*id);
-                }
+    /// Overrides the number of the current tick, i.e. in order to align it
+    /// with the ticks of a game's simulation.
+    pub fn set_tick_number(&mut self, tick: u32) {
+        self.tick_number = tick;
+    }
 
-            }
+    /// Bans the specified IP address, either for the given `duration` or
+    /// until it gets unbanned.
+    ///
+    /// Packets from banned addresses are dropped before they reach any
+    /// connection, while existing connections to the address are closed
+    /// before the next packets are send, invoking `Handler::connection_banned`
+    /// for each of them.
+    ///
+    /// Temporary bans are lifted automatically once they expire. Both the
+    /// ban and its lifting are reported via `Handler::ban_applied` and
+    /// `Handler::ban_lifted`.
+    pub fn ban_address(&mut self, ip: IpAddr, duration: Option<Duration>) {
+        self.banned_addresses.insert(ip, duration.map(|d| Instant::now() + d));
+        self.ban_changes.push(BanChange::Applied(BanTarget::Address(ip), duration));
+    }
 
-            // Update statistics
-            self.statistics.set_bytes_sent(bytes_sent);
-            self.statistics.tick();
+    /// Lifts the ban of the specified IP address, returning whether it was
+    /// banned.
+    pub fn unban_address(&mut self, ip: IpAddr) -> bool {
+        let banned = self.banned_addresses.remove(&ip).is_some();
+        if banned {
+            self.ban_changes.push(BanChange::Lifted(BanTarget::Address(ip)));
+        }
+        banned
+    }
 
-            // Remove any dropped connections and their address mappings
-            for id in dropped.drain(..) {
-                connections.remove(&id).unwrap().reset();
-                addresses.remove(&id);
-            }
+    /// Returns whether the specified IP address is currently banned.
+    pub fn is_address_banned(&self, ip: IpAddr) -> bool {
+        is_banned(&self.banned_addresses, &ip)
+    }
 
-            tick::end(tick_delay, tick_start, &mut tick_overflow, &self.config);
+    /// Bans the specified `ConnectionID`, either for the given `duration` or
+    /// until it gets unbanned.
+    ///
+    /// Packets for banned ids are dropped before they reach any connection,
+    /// while an existing connection with the id is closed before the next
+    /// packets are send, invoking `Handler::connection_banned` for it.
+    ///
+    /// Temporary bans are lifted automatically once they expire. Both the
+    /// ban and its lifting are reported via `Handler::ban_applied` and
+    /// `Handler::ban_lifted`.
+    pub fn ban_connection(&mut self, id: ConnectionID, duration: Option<Duration>) {
+        self.banned_connections.insert(id, duration.map(|d| Instant::now() + d));
+        self.ban_changes.push(BanChange::Applied(BanTarget::Connection(id), duration));
+    }
 
+    /// Lifts the ban of the specified `ConnectionID`, returning whether it
+    /// was banned.
+    pub fn unban_connection(&mut self, id: ConnectionID) -> bool {
+        let banned = self.banned_connections.remove(&id).is_some();
+        if banned {
+            self.ban_changes.push(BanChange::Lifted(BanTarget::Connection(id)));
         }
+        banned
+    }
 
-        // Invoke handler
-        handler.shutdown(self);
+    /// Returns whether the specified `ConnectionID` is currently banned.
+    pub fn is_connection_banned(&self, id: ConnectionID) -> bool {
+        is_banned(&self.banned_connections, &id)
+    }
 
-        // Reset socket address
-        self.local_address = None;
+    /// Allows the specified IP address to create new connections while
+    /// `Config::whitelist_only` is enabled.
+    pub fn allow_address(&mut self, ip: IpAddr) {
+        self.allowed_addresses.insert(ip);
+    }
 
-        // Reset all connection states
-        for (_, conn) in &mut connections {
-            conn.reset();
-        }
+    /// Removes the specified IP address from the whitelist, returning whether
+    /// it was allowed.
+    ///
+    /// Established connections from the address are kept.
+    pub fn disallow_address(&mut self, ip: IpAddr) -> bool {
+        self.allowed_addresses.remove(&ip)
+    }
 
-        Ok(())
+    /// Allows the specified `ConnectionID` to create a new connection from
+    /// any address while `Config::whitelist_only` is enabled, i.e. for peers
+    /// which were handed out the id by a matchmaking backend.
+    pub fn allow_connection(&mut self, id: ConnectionID) {
+        self.allowed_connections.insert(id);
+    }
 
+    /// Removes the specified `ConnectionID` from the whitelist, returning
+    /// whether it was allowed.
+    ///
+    /// An established connection with the id is kept.
+    pub fn disallow_connection(&mut self, id: ConnectionID) -> bool {
+        self.allowed_connections.remove(&id)
     }
 
-    /// Shuts down the server, closing all active client connections.
+    /// Returns whether a new connection with the specified `ConnectionID`
+    /// would be created for a packet from the specified IP address, based on
+    /// the whitelist.
     ///
-    /// This exits the tick loop, resets all connections and shuts down the
-    /// underlying socket the server was bound to.
-    pub fn shutdown(&mut self) -> Result<(), Error> {
-        if self.closed {
-            Err(Error::new(ErrorKind::NotConnected, ""))
+    /// Always returns `true` in case `Config::whitelist_only` is disabled.
+    pub fn is_whitelisted(&self, ip: IpAddr, id: ConnectionID) -> bool {
+        !self.config.whitelist_only
+            || self.allowed_addresses.contains(&ip)
+            || self.allowed_connections.contains(&id)
+    }
 
-        } else {
-            self.closed = true;
-            Ok(())
+    /// Returns a snapshot of the state of the server and the specified
+    /// `connections`, i.e. those passed to `Handler::tick_connections`.
+    ///
+    /// The snapshot includes the server's configuration and statistics along
+    /// with the public state, statistics and queue depths of each connection.
+    pub fn dump_state(
+        &self, connections: &ConnectionMap
+
+    ) -> ServerStateDump {
+
+        let mut dumps: Vec<_> = connections.values().map(|conn| {
+            conn.dump_state()
+
+        }).collect();
+
+        dumps.sort_by_key(|dump| dump.id.0);
+
+        ServerStateDump {
+            local_addr: self.local_address,
+            config: self.config,
+            stats: self.statistics.average(),
+            banned_addresses: self.banned_addresses.len(),
+            banned_connections: self.banned_connections.len(),
+            connections: dumps
         }
+
     }
 
-}
+    /// Binds the server to the specified local address by creating a socket
+    /// and actively listens for incoming client connections.
+    ///
+    /// Clients connecting to the server must use a compatible connection
+    /// / packet configuration in order to be able to connect.
+    ///
+    /// When binding to port `0`, the operating system picks an available
+    /// port which is reported via `Server::local_addr()` once the
+    /// `Handler::bind` method is invoked.
+    ///
+    /// The `handler` is a struct that implements the `Handler` trait in order
+    /// to handle events from the server and its connections.
+    pub fn bind<A: ToSocketAddrs>(
+        &mut self, handler: &mut dyn Handler<Server>, addr: A
 
+    ) -> Result<(), Error> {
+
+        let socket = UdpSocket::new(
+            addr,
+            self.config.packet_max_size
+        )?;
+
+        self.bind_to_socket(handler, socket)
+
+    }
+
+    /// Binds the server to the first available port within the specified
+    /// range on the given local IP address and actively listens for incoming
+    /// client connections.
+    ///
+    /// The chosen port is reported via `Server::local_addr()` once the
+    /// `Handler::bind` method is invoked. In case none of the ports could be
+    /// bound, the error of the last attempt is returned.
+    ///
+    /// The `handler` is a struct that implements the `Handler` trait in order
+    /// to handle events from the server and its connections.
+    pub fn bind_range<I: Into<IpAddr>>(
+        &mut self,
+        handler: &mut dyn Handler<Server>,
+        ip: I, ports: RangeInclusive<u16>
+
+    ) -> Result<(), Error> {
+
+        let ip = ip.into();
+        let mut error = Error::new(ErrorKind::AddrNotAvailable, "");
+
+        for port in ports {
+            match UdpSocket::new((ip, port), self.config.packet_max_size) {
+                Ok(socket) => return self.bind_to_socket(handler, socket),
+                Err(err) => error = err
+            }
+        }
+
+        Err(error)
+
+    }
+
+    /// Binds the server to all of the specified local addresses by creating
+    /// one socket for each of them and actively listens for incoming client
+    /// connections on all of them.
+    ///
+    /// Replies are send through the socket on which the connection's last
+    /// packet was received, while `Server::local_addr()` reports the address
+    /// of the first socket.
+    ///
+    /// The `handler` is a struct that implements the `Handler` trait in order
+    /// to handle events from the server and its connections.
+    pub fn bind_multiple<A: ToSocketAddrs>(
+        &mut self, handler: &mut dyn Handler<Server>, addrs: &[A]
+
+    ) -> Result<(), Error> {
+        let socket = self.multi_socket(addrs)?;
+        self.bind_to_socket(handler, socket)
+    }
+
+    /// Binds the server to the specified port on both the unspecified IPv6
+    /// and IPv4 addresses and actively listens for incoming client
+    /// connections of either family.
+    ///
+    /// When the operating system supports dual-stack sockets a single IPv6
+    /// socket is used, with IPv4 clients being known by their plain IPv4
+    /// addresses. Otherwise a separate IPv4 socket is bound to the same port.
+    /// When IPv6 is not available at all, only IPv4 is bound.
+    ///
+    /// The `handler` is a struct that implements the `Handler` trait in order
+    /// to handle events from the server and its connections.
+    pub fn bind_dual_stack(
+        &mut self, handler: &mut dyn Handler<Server>, port: u16
+
+    ) -> Result<(), Error> {
+        let socket = self.dual_stack_socket(port)?;
+        self.bind_to_socket(handler, socket)
+    }
+
+    /// Binds the server to specified socket and actively listens for incoming
+    /// client connections.
+    ///
+    /// Clients connecting to the server must use a compatible connection
+    /// / packet configuration in order to be able to connect.
+    ///
+    /// The `handler` is a struct that implements the `Handler` trait in order
+    /// to handle events from the server and its connections.
+    ///
+    /// This method starts the tick loop, blocking the calling thread.
+    pub fn bind_to_socket<S: Socket>(
+        &mut self, handler: &mut dyn Handler<Server>, socket: S
+
+    ) -> Result<(), Error> {
+
+        let mut state = self.bind_to_socket_sync(handler, socket)?;
+
+        // Receive and send until we shut down.
+        let mut tick_overflow = 0;
+        while !self.closed {
+
+            let tick_start = tick::start();
+            let tick_delay = 1_000_000_000 / self.config.send_rate;
+
+            self.tick_sync(handler, &mut state);
+
+            // Keep receiving packets until the next tick is due
+            let config = self.config;
+            tick::end_receiving(tick_delay, tick_start, &mut tick_overflow, &config, &mut |timeout| {
+                state.socket.wait(timeout);
+                self.receive_sync(handler, &mut state);
+            });
+
+        }
+
+        self.shutdown_sync(handler, &mut state)
+
+    }
+
+
+    // Non-Blocking, Synchronous API ------------------------------------------
+
+    /// Binds the server to the specified local address by creating a socket
+    /// and actively listens for incoming client connections.
+    ///
+    /// The `handler` is a struct that implements the `Handler` trait in order
+    /// to handle events from the server and its connections.
+    ///
+    /// This method returns a `ServerState` instance for this server, which
+    /// can be used with other synchronous `Server` methods.
+    pub fn bind_sync<A: ToSocketAddrs>(
+        &mut self, handler: &mut dyn Handler<Server>, addr: A
+
+    ) -> Result<ServerState<UdpSocket>, Error> {
+
+        let socket = UdpSocket::new(
+            addr,
+            self.config.packet_max_size
+        )?;
+
+        self.bind_to_socket_sync(handler, socket)
+
+    }
+
+    /// Binds the server to all of the specified local addresses by creating
+    /// one socket for each of them and actively listens for incoming client
+    /// connections on all of them.
+    ///
+    /// The `handler` is a struct that implements the `Handler` trait in order
+    /// to handle events from the server and its connections.
+    ///
+    /// This method returns a `ServerState` instance for this server, which
+    /// can be used with other synchronous `Server` methods.
+    pub fn bind_multiple_sync<A: ToSocketAddrs>(
+        &mut self, handler: &mut dyn Handler<Server>, addrs: &[A]
+
+    ) -> Result<ServerState<MultiSocket<UdpSocket>>, Error> {
+        let socket = self.multi_socket(addrs)?;
+        self.bind_to_socket_sync(handler, socket)
+    }
+
+    /// Binds the server to the specified port on both the unspecified IPv6
+    /// and IPv4 addresses like `Server::bind_dual_stack()`.
+    ///
+    /// This method returns a `ServerState` instance for this server, which
+    /// can be used with other synchronous `Server` methods.
+    pub fn bind_dual_stack_sync(
+        &mut self, handler: &mut dyn Handler<Server>, port: u16
+
+    ) -> Result<ServerState<MultiSocket<UdpSocket>>, Error> {
+        let socket = self.dual_stack_socket(port)?;
+        self.bind_to_socket_sync(handler, socket)
+    }
+
+    /// Binds the server to specified socket and actively listens for incoming
+    /// client connections.
+    ///
+    /// The `handler` is a struct that implements the `Handler` trait in order
+    /// to handle events from the server and its connections.
+    ///
+    /// This method returns a `ServerState` instance for this server, which
+    /// can be used with other synchronous `Server` methods, leaving the
+    /// scheduling of its ticks to the caller.
+    pub fn bind_to_socket_sync<S: Socket>(
+        &mut self, handler: &mut dyn Handler<Server>, socket: S
+
+    ) -> Result<ServerState<S>, Error> {
+
+        // Store bound socket address
+        let local_addr = socket.local_addr()?;
+        self.local_address = Some(local_addr);
+        self.closed = false;
+
+        // Reset stats
+        self.statistics.reset();
+        self.totals = ServerStats::default();
+
+        let workers = WorkerPool::new(self.worker_threads())?;
+        let state = ServerState::new(
+            socket, local_addr, handler.id_generator(&self.config), workers
+        );
+
+        // Invoke handler
+        handler.bind(self);
+
+        Ok(state)
+
+    }
+
+    /// Performs exactly one tick of the server, receiving all buffered
+    /// incoming packets, invoking the handler for all connections and
+    /// sending one outgoing packet for each of them.
+    ///
+    /// Does nothing once the server was shut down.
+    pub fn tick_sync<S: Socket>(
+        &mut self, handler: &mut dyn Handler<Server>, state: &mut ServerState<S>
+    ) {
+
+        if self.closed || self.local_address.is_none() {
+            return;
+        }
+
+        let tick_start = tick::start();
+        let tick_delay = 1_000_000_000 / self.config.send_rate;
+
+        // Measure how far the previous tick deviated from the send rate
+        if let Some(previous_start) = state.previous_tick_start {
+            self.statistics.set_tick_jitter(
+                tick::jitter(tick_delay, previous_start, tick_start)
+            );
+        }
+        state.previous_tick_start = Some(tick_start);
+
+        // Apply the commands of all handles
+        self.apply_commands(handler, &mut state.connections);
+        if self.closed {
+            return;
+        }
+
+        // Forget about lost connections which can no longer be resumed
+        let resumption_threshold = Duration::from_millis(
+            self.config.resumption_threshold as u64
+        );
+        state.resumable.retain(|_, r| r.lost_time.elapsed() <= resumption_threshold);
+
+        // Lift expired bans and report all changes to the bans
+        self.expire_bans();
+        for change in mem::take(&mut self.ban_changes) {
+            match change {
+                BanChange::Applied(target, duration) => handler.ban_applied(self, target, duration),
+                BanChange::Lifted(target) => handler.ban_lifted(self, target)
+            }
+        }
+
+        // Follow changes to the configured number of worker threads
+        self.update_workers(state);
+
+        // Receive all packets which arrived since the last tick
+        self.receive(handler, state);
+
+        let ServerState {
+            ref mut socket,
+            ref mut addresses,
+            ref mut connections,
+            ref mut resumable,
+            ref mut pending,
+            ref mut flood_limiter,
+            ref mut peers,
+            ref mut session_state,
+            ref mut send_offset,
+            ref mut received_bytes,
+            ref mut stats_report_time,
+            ref workers,
+            ..
+        } = *state;
+
+        // Update receive statistics
+        self.statistics.set_bytes_received(mem::take(received_bytes) as u32);
+
+        // List of dropped connections
+        let mut dropped: Vec<ConnectionID> = Vec::new();
+
+        // Remove connections which did not complete their handshake in time
+        let timeout = Duration::from_millis(u64::from(self.config.pending_connection_timeout));
+        let mut expired = Vec::new();
+        pending.retain(|id, created| {
+            let connecting = connections.get(id).is_some_and(|conn| !conn.acknowledged());
+            if !connecting {
+                false
+
+            } else if timeout > Duration::ZERO && created.elapsed() > timeout {
+                expired.push(*id);
+                false
+
+            } else {
+                true
+            }
+        });
+
+        for id in expired {
+            let mut conn = connections.remove(&id).unwrap();
+            handler.connection_failed(self, &mut conn);
+            self.fail_attempt(handler, conn.peer_addr(), ConnectionFailure::HandshakeTimeout);
+            addresses.remove(&id);
+            self.release_connection(id);
+            self.totals.dropped_connections += 1;
+        }
+
+        // Report addresses whose packets were dropped by the flood limit
+        for (ip, dropped) in flood_limiter.take_throttled() {
+            handler.source_throttled(self, ip, dropped);
+        }
+
+        // Promptly acknowledge any received data
+        let mut bytes_sent = 0;
+        for (id, conn) in connections.iter_mut() {
+            let addr = addresses.get(id).unwrap();
+            let bytes = conn.send_ack_packet(socket, addr, self, handler);
+            if bytes > 0 {
+                bytes_sent += bytes;
+                self.totals.packets_sent += 1;
+            }
+        }
+
+        // Forward the messages of paired connections to their partners
+        for (from, to) in &self.relays {
+            let messages: Vec<(MessageKind, Vec<u8>)> = match connections.get_mut(from) {
+                Some(conn) => conn.received_with_kinds().collect(),
+                None => continue
+            };
+            if let Some(conn) = connections.get_mut(to) {
+                for (kind, payload) in messages {
+                    conn.send(kind, payload);
+                }
+            }
+        }
+
+        // Invoke handler
+        handler.after_receive(self);
+        handler.tick_connections(self, connections);
+        handler.before_send(self);
+
+        // Queue the messages which were send by id during the callbacks
+        self.apply_commands(handler, connections);
+
+        // Share peers and session state for host migration
+        if self.config.host_migration {
+
+            let mut current: Vec<Peer> = connections.values().filter(|conn| {
+                conn.state() == ConnectionState::Connected
+
+            }).map(|conn| Peer {
+                id: conn.id(),
+                addr: addresses[&conn.id()]
+
+            }).collect();
+
+            current.sort_by_key(|peer| peer.id.0);
+
+            let changed = current != *peers;
+            let state = handler.session_state(self);
+            let state_changed = state != *session_state;
+            for conn in connections.values_mut() {
+                if conn.state() == ConnectionState::Connected {
+
+                    if changed {
+                        conn.share_peers(&current).ok();
+                    }
+
+                    // Newly connected peers still need the current state
+                    let joined = !peers.iter().any(|peer| peer.id == conn.id());
+                    if let Some(ref data) = state {
+                        if state_changed || joined {
+                            conn.share_session_state(data).ok();
+                        }
+                    }
+
+                }
+            }
+
+            *peers = current;
+            *session_state = state;
+
+        }
+
+        // Close connections to banned addresses or with banned ids
+        for conn in connections.values_mut() {
+            let banned = self.is_address_banned(conn.peer_addr().ip())
+                || self.is_connection_banned(conn.id());
+
+            if banned && conn.state().can_transition_to(ConnectionState::Closing) {
+                handler.connection_banned(self, conn);
+                conn.close();
+            }
+        }
+
+        // Determine the order in which connections send their packets
+        let mut order: Vec<ConnectionID> = connections.keys().cloned().collect();
+        match self.config.send_order {
+            SendOrder::Fixed => {},
+            SendOrder::Rotate => {
+                order.sort_by_key(|id| id.0);
+                if !order.is_empty() {
+                    let len = order.len();
+                    order.rotate_left(*send_offset % len);
+                }
+                *send_offset = send_offset.wrapping_add(1);
+            },
+            SendOrder::Shuffle => thread_rng().shuffle(&mut order)
+        }
+
+        // Create outgoing packets for all connections, sharing the outgoing
+        // bandwidth budget between them
+        let mut budget = self.tick_bandwidth_budget();
+        if workers.threads() > 1 {
+
+            // Assemble the packets on the workers and send them in order
+            // afterwards
+            let share = budget.map(|budget| budget / cmp::max(order.len(), 1));
+            let jobs: Vec<_> = order.iter().map(|id| {
+                let mut conn = connections.remove(id).unwrap();
+                conn.set_tick_number(self.tick_number);
+                if let Some(share) = share {
+                    conn.set_send_budget(share);
+                }
+                (conn, addresses[id])
+
+            }).collect();
+
+            for (mut conn, mut result) in workers.send(jobs) {
+
+                result.replay(self, &mut conn, handler);
+
+                // Errors are recorded on the connection, like for packets
+                // which are send directly
+                for (addr, packet) in result.packets.drain(..) {
+                    if let Err(err) = socket.send_to(&packet, addr) {
+                        conn.set_send_error(err.kind());
+                    }
+                }
+
+                if result.bytes > 0 {
+                    bytes_sent += result.bytes;
+                    self.totals.packets_sent += 1;
+                }
+
+                if !conn.open() {
+                    dropped.push(result.id);
+                }
+
+                connections.insert(result.id, conn);
+
+            }
+
+        } else {
+            for (index, id) in order.iter().enumerate() {
+
+                let conn = connections.get_mut(id).unwrap();
+                conn.set_tick_number(self.tick_number);
+
+                // Bytes left unused by previous connections are passed on
+                if let Some(remaining) = budget {
+                    conn.set_send_budget(remaining / (order.len() - index));
+                }
+
+                // Resolve the last known remote address for this
+                // connection and send the data
+                let addr = addresses.get(id).unwrap();
+
+                // Then invoke the connection to send a outgoing packet
+                let bytes = conn.send_packet(socket, addr, self, handler);
+                if let Some(ref mut remaining) = budget {
+                    *remaining = remaining.saturating_sub(bytes as usize);
+                }
+
+                if bytes > 0 {
+                    bytes_sent += bytes;
+                    self.totals.packets_sent += 1;
+                }
+
+                // Collect all lost / closed connections
+                if !conn.open() {
+                    dropped.push(*id);
+                }
+
+            }
+        }
+
+        self.tick_number = self.tick_number.wrapping_add(1);
+
+        // Update statistics
+        for id in &dropped {
+            if connections[id].state() == ConnectionState::Lost {
+                self.churn.timeouts += 1;
+
+            } else {
+                self.churn.disconnects += 1;
+            }
+        }
+
+        self.statistics.set_bytes_sent(bytes_sent);
+        self.statistics.set_connection_churn(mem::take(&mut self.churn));
+        self.statistics.tick();
+
+        // Report the statistics once per second
+        if stats_report_time.elapsed() >= Duration::from_secs(1) {
+            *stats_report_time = Instant::now();
+            let stats = self.statistics.average();
+            handler.stats_tick(self, stats);
+        }
+        self.totals.bytes_sent += u64::from(bytes_sent);
+        self.totals.dropped_connections += dropped.len() as u64;
+
+        // Remove any dropped connections and their address mappings
+        for id in dropped.drain(..) {
+
+            let mut conn = connections.remove(&id).unwrap();
+
+            // Keep lost connections around for later resumption
+            if conn.state() == ConnectionState::Lost {
+                if let Some(token) = conn.resumption_token() {
+
+                    // Optionally retain any unacknowledged messages
+                    let messages = if self.config.resumption_retain_messages {
+                        conn.take_pending_messages()
+
+                    } else {
+                        Vec::new()
+                    };
+
+                    resumable.insert(id, ResumableConnection {
+                        secret: token.secret,
+                        lost_time: Instant::now(),
+                        messages
+                    });
+
+                }
+            }
+
+            conn.reset();
+            addresses.remove(&id);
+            self.release_connection(id);
+
+        }
+
+        // Take a snapshot of the remaining connections for access in
+        // between ticks
+        self.connection_info = connections.values().map(|conn| conn.dump_state()).collect();
+        self.connection_info.sort_by_key(|info| info.id.0);
+
+    }
+
+    /// Performs a single iteration of the server's tick loop, for driving
+    /// the server from an external scheduler like a game engine's fixed
+    /// update or a timer, instead of `Server::bind`.
+    ///
+    /// This receives all pending packets, ticks all connections and sends
+    /// their outgoing packets. Once the server was requested to shut down,
+    /// i.e. via a `ServerHandle`, it is shut down via `Server::shutdown_sync`
+    /// just like the internal loop would do.
+    ///
+    /// Returns `false` once the server is no longer running, after which
+    /// there is no point in calling this again.
+    pub fn run_once<S: Socket>(
+        &mut self, handler: &mut dyn Handler<Server>, state: &mut ServerState<S>
+
+    ) -> bool {
+
+        self.tick_sync(handler, state);
+
+        if self.closed {
+            self.shutdown_sync(handler, state).ok();
+            false
+
+        } else {
+            self.local_address.is_some()
+        }
+
+    }
+
+    /// Receives and parses all buffered incoming packets without performing
+    /// a tick, invoking the handler for any resulting connection events
+    /// right away.
+    ///
+    /// The received messages are passed on with the next `Server::tick_sync`
+    /// call, which also remains the only place packets are send from. Calling
+    /// this in between ticks keeps packets from waiting in the socket for up
+    /// to a full tick, which `Server::bind` does automatically.
+    ///
+    /// Does nothing once the server was shut down.
+    pub fn receive_sync<S: Socket>(
+        &mut self, handler: &mut dyn Handler<Server>, state: &mut ServerState<S>
+    ) {
+        if !self.closed && self.local_address.is_some() {
+            self.receive(handler, state);
+        }
+    }
+
+    /// Shuts down the server, resetting all of its connections.
+    ///
+    /// This invokes `Handler::shutdown` and releases the local address of the
+    /// server, while the socket is dropped along with the `state`.
+    pub fn shutdown_sync<S: Socket>(
+        &mut self, handler: &mut dyn Handler<Server>, state: &mut ServerState<S>
+
+    ) -> Result<(), Error> {
+
+        if self.local_address.is_none() {
+            Err(Error::new(ErrorKind::NotConnected, ""))
+
+        } else {
+
+            self.closed = true;
+
+            // Invoke handler
+            handler.shutdown(self);
+
+            // Reset socket address
+            self.local_address = None;
+
+            // Reset all connection states
+            for conn in state.connections.values_mut() {
+                conn.reset();
+            }
+            self.groups.clear();
+            self.relays.clear();
+            self.connection_info.clear();
+
+            Ok(())
+
+        }
+
+    }
+
+    /// Shuts down the server, closing all active client connections.
+    ///
+    /// This exits the tick loop, resets all connections and shuts down the
+    /// underlying socket the server was bound to.
+    pub fn shutdown(&mut self) -> Result<(), Error> {
+        if self.closed {
+            Err(Error::new(ErrorKind::NotConnected, ""))
+
+        } else {
+            self.closed = true;
+            Ok(())
+        }
+    }
+
+    // Internal Helpers -------------------------------------------------------
+    fn apply_commands(
+        &mut self, handler: &mut dyn Handler<Server>,
+        connections: &mut ConnectionMap
+    ) {
+        while let Ok(command) = self.commands.try_recv() {
+            match command {
+                Command::Shutdown => self.closed = true,
+                Command::Kick(id, reason) => if let Some(conn) = connections.get_mut(&id) {
+                    if conn.state().can_transition_to(ConnectionState::Closing) {
+                        conn.close_with_reason(reason);
+                        handler.connection_kicked(self, conn, reason);
+                    }
+                },
+                Command::Send(id, kind, payload) => if let Some(conn) = connections.get_mut(&id) {
+                    conn.send(kind, payload);
+                },
+                Command::Multicast(ids, kind, payload) => for id in ids {
+                    if let Some(conn) = connections.get_mut(&id) {
+                        if conn.state() == ConnectionState::Connected {
+                            conn.send(kind, payload.clone());
+                        }
+                    }
+                },
+                Command::Broadcast(kind, payload, except) => for conn in connections.values_mut() {
+                    if conn.state() == ConnectionState::Connected && Some(conn.id()) != except {
+                        conn.send(kind, payload.clone());
+                    }
+                },
+                Command::BroadcastFiltered(kind, payload, mut filter) => for conn in connections.values_mut() {
+                    if conn.state() == ConnectionState::Connected {
+                        if let Some(payload) = (filter.0)(conn, &payload) {
+                            conn.send(kind, payload);
+                        }
+                    }
+                },
+                Command::SetBandwidthLimit(id, bytes_per_second) => if let Some(conn) = connections.get_mut(&id) {
+                    conn.set_bandwidth_limit(bytes_per_second);
+                },
+                Command::SetConfig(config) => {
+                    self.config = *config;
+                    self.statistics.set_config(*config);
+                    for conn in connections.values_mut() {
+                        conn.set_config(*config);
+                    }
+                }
+            }
+        }
+    }
+
+    fn worker_threads(&self) -> usize {
+        if cfg!(feature = "packet_handler_compress") {
+            1
+
+        } else {
+            self.config.worker_threads as usize
+        }
+    }
+
+    fn update_workers<S: Socket>(&self, state: &mut ServerState<S>) {
+        let threads = self.worker_threads();
+        if cmp::max(threads, 1) != cmp::max(state.workers.threads(), 1) {
+            // Keep the previous workers in case no threads can be spawned
+            if let Ok(workers) = WorkerPool::new(threads) {
+                state.workers = workers;
+            }
+        }
+    }
+
+    fn expire_bans(&mut self) {
+
+        let now = Instant::now();
+        let mut lifted = Vec::new();
+
+        self.banned_addresses.retain(|ip, until| {
+            let active = ban_active(*until, now);
+            if !active {
+                lifted.push(BanChange::Lifted(BanTarget::Address(*ip)));
+            }
+            active
+        });
+
+        self.banned_connections.retain(|id, until| {
+            let active = ban_active(*until, now);
+            if !active {
+                lifted.push(BanChange::Lifted(BanTarget::Connection(*id)));
+            }
+            active
+        });
+
+        self.ban_changes.append(&mut lifted);
+
+    }
+
+    fn tick_bandwidth_budget(&self) -> Option<usize> {
+        if self.config.max_outgoing_bandwidth == 0 {
+            None
+
+        } else {
+            Some((self.config.max_outgoing_bandwidth / self.config.send_rate) as usize)
+        }
+    }
+
+    fn connection_received(
+        &self,
+        connection: &mut Connection,
+        addresses: &mut HashMap<ConnectionID, SocketAddr, FnvBuildHasher>
+    ) {
+
+        // Issue resumption tokens for established connections
+        if self.config.resumption_threshold > 0
+            && connection.state() == ConnectionState::Connected
+            && connection.resumption_token().is_none() {
+            connection.issue_resumption_token();
+        }
+
+        // Issue resumption tickets for established connections
+        #[cfg(feature = "encryption")]
+        {
+            if let Some(key) = self.config.resumption_ticket_key {
+                if connection.state() == ConnectionState::Connected
+                    && connection.resumption_ticket().is_none() {
+                    connection.issue_resumption_ticket(key, Duration::from_secs(
+                        self.config.resumption_ticket_lifetime as u64
+                    ));
+                }
+            }
+        }
+
+        // Issue migration tokens for established connections
+        if self.validates_migrations()
+            && connection.state() == ConnectionState::Connected
+            && !connection.has_migration_token() {
+            connection.issue_migration_token();
+        }
+
+        // Follow validated migrations
+        let id = connection.id();
+        if addresses.get(&id) != Some(&connection.peer_addr()) {
+            addresses.insert(id, connection.peer_addr());
+        }
+
+    }
+
+    fn receive<S: Socket>(
+        &mut self, handler: &mut dyn Handler<Server>, state: &mut ServerState<S>
+    ) {
+
+        let ServerState {
+            ref mut socket,
+            local_addr,
+            ref mut addresses,
+            ref mut connections,
+            ref mut resumable,
+            ref mut pending,
+            ref mut limiter,
+            ref mut flood_limiter,
+            ref mut id_generator,
+            ref mut received_bytes,
+            ref workers,
+            ..
+        } = *state;
+
+        let tick_delay = 1_000_000_000 / self.config.send_rate;
+
+        // Start a new budget window every second
+        limiter.update();
+        flood_limiter.update(self.config.flood_packet_rate, self.config.flood_packet_burst);
+
+        // Receive all incoming UDP packets to our local address
+        let threads = workers.threads();
+        let mut received: Vec<(ConnectionID, Vec<u8>)> = Vec::new();
+        let mut bytes_received = 0;
+        while let Ok((addr, packet)) = socket.try_recv() {
+
+            // Drop packets from banned addresses
+            if self.is_address_banned(addr.ip()) {
+                self.drop_packet(handler, addr, PacketDropReason::Banned);
+                continue;
+            }
+
+            // Drop packets from addresses which exceed their packet rate
+            if !flood_limiter.allow_packet(
+                addr.ip(),
+                self.config.flood_packet_rate,
+                self.config.flood_packet_burst
+            ) {
+                self.drop_packet(handler, addr, PacketDropReason::RateLimited);
+                continue;
+            }
+
+            // Answer server info queries without creating a connection
+            if let Some(challenge) = Connection::server_info_query_from_packet(&self.config, &packet) {
+                if limiter.allow_rejection(addr.ip()) {
+                    if let Some(info) = handler.server_info(self) {
+                        if let Some(response) = Connection::server_info_packet(&self.config, challenge, &info) {
+                            socket.send_to(&response, addr).ok();
+                        }
+                    }
+                }
+                continue;
+            }
+
+            // Notify senders of mismatched protocol versions
+            if self.config.protocol_mismatch_response {
+                if let Some(response) = Connection::protocol_mismatch_packet(&self.config, &packet) {
+                    if limiter.allow_rejection(addr.ip()) {
+                        socket.send_to(&response, addr).ok();
+                    }
+                    self.drop_packet(handler, addr, PacketDropReason::ProtocolMismatch);
+                    self.fail_attempt(handler, addr, ConnectionFailure::ProtocolMismatch);
+                    continue;
+                }
+            }
+
+            // Discard forged packets before they reach any connection
+            let packet_size = packet.len();
+            let packet = match Connection::authenticate_packet(&self.config, packet) {
+                Some(packet) => packet,
+                None => {
+                    self.drop_packet(handler, addr, PacketDropReason::AuthenticationFailed);
+                    continue;
+                }
+            };
+
+            // Try to extract the connection id from the packet
+            if let Some(id) = Connection::id_from_packet(&self.config, &packet) {
+
+                // Drop packets for banned connection ids
+                if self.is_connection_banned(id) {
+                    self.drop_packet(handler, addr, PacketDropReason::Banned);
+                    continue;
+                }
+
+                // Notify about packets for established connections which
+                // arrive from a different address
+                let reconnect = match connections.get_mut(&id) {
+                    Some(conn) if addr != conn.peer_addr() => {
+                        handler.connection_address_conflict(self, conn, addr);
+                        self.config.address_change_policy == AddressChangePolicy::Reconnect
+                    },
+                    _ => false
+                };
+
+                // Close the existing connection in favor of a new one
+                if reconnect {
+                    let mut conn = connections.remove(&id).unwrap();
+                    handler.connection_closed(self, &mut conn, false);
+                    conn.reset();
+                    addresses.remove(&id);
+                    pending.remove(&id);
+                    self.release_connection(id);
+                    self.churn.disconnects += 1;
+                    self.totals.dropped_connections += 1;
+                }
+
+                // Limit the packets from addresses without an
+                // established connection
+                let known = connections.get(&id).is_some_and(|conn| conn.peer_addr() == addr);
+                if !known && !limiter.allow_packet(
+                    addr.ip(), self.config.unknown_address_packet_limit
+                ) {
+                    self.drop_packet(handler, addr, PacketDropReason::RateLimited);
+                    continue;
+                }
+
+                // Drop packets which would create a new connection for
+                // sources which are not whitelisted
+                if !connections.contains_key(&id) && !self.is_whitelisted(addr.ip(), id) {
+                    self.fail_attempt(handler, addr, ConnectionFailure::Denied);
+                    continue;
+                }
+
+                // Drop packets which would create a new connection
+                // without being padded to the minimum handshake size
+                if !connections.contains_key(&id)
+                    && packet_size < self.config.handshake_min_size {
+                    continue;
+                }
+
+                // Answer packets which would create a new connection with
+                // a signed cookie, until the remote echoes it back
+                #[cfg(feature = "authentication")]
+                {
+                    if self.config.handshake_cookies && !connections.contains_key(&id)
+                        && !Connection::handshake_cookie_from_packet(&self.config, &packet).is_some_and(|cookie| {
+                            handshake_cookie::verify(&self.cookie_secret, addr, id, &cookie)
+                        }) {
+
+                        let cookie = handshake_cookie::issue(&self.cookie_secret, addr, id);
+                        socket.send_to(
+                            &Connection::handshake_cookie_packet(&self.config, id, &cookie),
+                            addr
+                        ).ok();
+                        continue;
+                    }
+                }
+
+                // Drop packets which would create a new connection
+                // without presenting a valid connect token
+                #[cfg(feature = "connect_token")]
+                let connect_token = if connections.contains_key(&id) || resumable.contains_key(&id) {
+                    None
+
+                } else if let Some(key) = self.config.connect_token_key {
+                    match Connection::connect_token_from_packet(&self.config, &packet).and_then(|data| {
+                        ConnectToken::verify(key, id, &data)
+                    }) {
+                        Some(token) => Some(token),
+                        None => continue
+                    }
+
+                } else {
+                    None
+                };
+
+                // Verify the resumption ticket presented by packets which
+                // would create a new connection
+                #[cfg(feature = "encryption")]
+                let resumption_ticket = if connections.contains_key(&id) {
+                    None
+
+                } else {
+                    self.config.resumption_ticket_key.and_then(|key| {
+                        Connection::resumption_ticket_from_packet(&self.config, &packet).and_then(|data| {
+                            ResumptionTicket::verify(key, id, &data)
+                        })
+                    })
+                };
+
+                // Limit the number of connections per IP address
+                if !connections.contains_key(&id) && self.config.max_connections_per_ip > 0 {
+                    let count = addresses.values().filter(|a| a.ip() == addr.ip()).count();
+                    if count >= self.config.max_connections_per_ip as usize {
+                        handler.connection_limit_exceeded(self, addr, id);
+                        self.fail_attempt(handler, addr, ConnectionFailure::Denied);
+                        continue;
+                    }
+                }
+
+                // Deny new connections once the server is full
+                if !connections.contains_key(&id) && self.config.max_connections > 0
+                    && connections.len() >= self.config.max_connections as usize {
+                    handler.server_full(self, addr, id);
+                    if limiter.allow_rejection(addr.ip()) {
+                        socket.send_to(&Connection::server_full_packet(&self.config, id), addr).ok();
+                    }
+                    self.fail_attempt(handler, addr, ConnectionFailure::ServerFull);
+                    continue;
+                }
+
+                // Limit the number of connections which have yet to
+                // complete their handshake
+                if !connections.contains_key(&id) && self.config.max_pending_connections > 0
+                    && pending.len() >= self.config.max_pending_connections as usize {
+                    self.drop_packet(handler, addr, PacketDropReason::RateLimited);
+                    continue;
+                }
+
+                // Drop packets which would create a new connection with an
+                // id which the generator does not accept
+                if !connections.contains_key(&id) && !id_generator.validate(id) {
+                    self.fail_attempt(handler, addr, ConnectionFailure::Denied);
+                    continue;
+                }
+
+                // Let the handler decide on new connections
+                if !connections.contains_key(&id)
+                    && handler.connection_request(self, addr, id, &packet) == Admission::Reject {
+                    self.fail_attempt(handler, addr, ConnectionFailure::Denied);
+                    continue;
+                }
+
+                // Limit the number of new connections across all
+                // addresses
+                if !connections.contains_key(&id) && !limiter.allow_handshake(
+                    self.config.handshake_limit
+                ) {
+                    self.drop_packet(handler, addr, PacketDropReason::RateLimited);
+                    continue;
+                }
+
+                // Retrieve or create a connection for the current
+                // connection id
+                if !connections.contains_key(&id) {
+                    self.churn.connects += 1;
+                    socket.register_connection(id);
+                }
+
+                let connection = connections.entry(id).or_insert_with(|| {
+
+                    // Also map the intitial address which is used by
+                    // the connection
+                    addresses.insert(id, addr);
+                    pending.insert(id, Instant::now());
+
+                    let mut conn = Connection::new(
+                        self.config,
+                        local_addr,
+                        addr,
+                        handler.rate_limiter(&self.config)
+                    );
+
+                    conn.set_id(id);
+                    conn.require_address_validation();
+
+                    // Keep the nonces of both ends apart
+                    #[cfg(feature = "encryption")]
+                    {
+                        conn.set_accepted();
+                    }
+
+                    #[cfg(feature = "connect_token")]
+                    {
+                        if let Some(token) = connect_token {
+                            conn.set_connect_token(token);
+                        }
+                    }
+
+                    // Restore the session of a valid resumption ticket
+                    #[cfg(feature = "encryption")]
+                    {
+                        if let Some(ticket) = resumption_ticket {
+                            conn.accept_resumption_ticket(ticket);
+                        }
+                    }
+
+                    // Allow recently lost connections to be resumed
+                    if let Some(r) = resumable.remove(&id) {
+                        conn.allow_resumption(r.secret, r.messages);
+                    }
+
+                    conn
+
+                });
+
+                // Map the current remote address of the connection to
+                // the latest address that sent a packet for the
+                // connection id in question. This is done in order to
+                // work in situations were the remote port of a
+                // connection is switched around by NAT.
+                //
+                // With migration validation enabled, the address is only
+                // updated once the remote proved its identity.
+                if addr != connection.peer_addr() {
+                    if self.validates_migrations() {
+                        connection.validate_peer_addr(addr);
+
+                    } else {
+                        connection.set_peer_addr(addr);
+                        addresses.remove(&id);
+                        addresses.insert(id, addr);
+                    }
+                }
+
+                // Statistics
+                bytes_received += packet.len();
+                self.totals.packets_received += 1;
+
+                // Leave the parsing to the workers once all packets have
+                // been received
+                if threads > 1 {
+                    received.push((id, packet));
+                    continue;
+                }
+
+                // Then feed the packet into the connection object for
+                // parsing
+                let dropped = connection.security_stats();
+                connection.receive_packet(
+                    packet, tick_delay / 1_000_000, self, handler
+                );
+                self.security_stats += connection.security_stats() - dropped;
+                self.connection_received(connection, addresses);
+                self.resolve_id_collision(handler, connection);
+
+            } else {
+                self.drop_packet(handler, addr, PacketDropReason::ProtocolMismatch);
+            }
+
+        }
+
+        // Parse the received packets of each connection on the workers
+        if !received.is_empty() {
+
+            // Batch the packets of each connection in order of their arrival
+            let mut batches: HashMap<ConnectionID, (usize, Vec<Vec<u8>>), FnvBuildHasher> = HashMap::default();
+            for (id, packet) in received {
+                let rank = batches.len();
+                batches.entry(id).or_insert_with(|| (rank, Vec::new())).1.push(packet);
+            }
+
+            let mut jobs: Vec<_> = batches.into_iter().filter_map(|(id, (rank, packets))| {
+                connections.remove(&id).map(|conn| (rank, conn, packets))
+
+            }).collect();
+
+            jobs.sort_by_key(|job| job.0);
+
+            let jobs = jobs.into_iter().map(|(_, conn, packets)| (conn, packets)).collect();
+            for (mut conn, mut result) in workers.receive(jobs, tick_delay / 1_000_000) {
+                result.replay(self, &mut conn, handler);
+                self.security_stats += result.dropped;
+                self.connection_received(&mut conn, addresses);
+                self.resolve_id_collision(handler, &mut conn);
+                connections.insert(result.id, conn);
+            }
+
+        }
+
+
+        *received_bytes += bytes_received;
+        self.totals.bytes_received += bytes_received as u64;
+
+    }
+
+    fn resolve_id_collision(
+        &mut self, handler: &mut dyn Handler<Server>, connection: &mut Connection
+    ) {
+        if let Some(addr) = connection.take_failed_migration() {
+
+            let decided = handler.connection_id_collision(self, connection, addr);
+            let policy = if self.config.id_collision_policy == IdCollisionPolicy::Handler {
+                decided
+
+            } else {
+                self.config.id_collision_policy
+            };
+
+            connection.cancel_peer_addr_validation();
+            if policy == IdCollisionPolicy::CloseBoth
+                && connection.state().can_transition_to(ConnectionState::Closing) {
+                connection.close();
+            }
+
+        }
+    }
+
+    fn release_connection(&mut self, id: ConnectionID) {
+        self.groups.retain(|_, members| {
+            members.remove(&id);
+            !members.is_empty()
+        });
+        self.unpair(id);
+    }
+
+    fn fail_attempt(
+        &mut self, handler: &mut dyn Handler<Server>,
+        addr: SocketAddr, failure: ConnectionFailure
+    ) {
+        if failure == ConnectionFailure::HandshakeTimeout {
+            self.churn.timeouts += 1;
+
+        } else {
+            self.churn.rejections += 1;
+        }
+        handler.connection_attempt_failed(self, addr, failure);
+    }
+
+    fn drop_packet(
+        &mut self, handler: &mut dyn Handler<Server>,
+        addr: SocketAddr, reason: PacketDropReason
+    ) {
+        self.security_stats.record(reason);
+        handler.packet_dropped(self, addr, reason);
+    }
+
+    fn multi_socket<A: ToSocketAddrs>(
+        &self, addrs: &[A]
+
+    ) -> Result<MultiSocket<UdpSocket>, Error> {
+        let sockets = addrs.iter().map(|addr| {
+            UdpSocket::new(addr, self.config.packet_max_size)
+
+        }).collect::<Result<Vec<UdpSocket>, Error>>()?;
+        MultiSocket::new(sockets)
+    }
+
+    fn dual_stack_socket(&self, port: u16) -> Result<MultiSocket<UdpSocket>, Error> {
+
+        let size = self.config.packet_max_size;
+        let v6 = match UdpSocket::new((Ipv6Addr::UNSPECIFIED, port), size) {
+            Ok(socket) => socket,
+            Err(_) => return MultiSocket::new(vec![
+                UdpSocket::new((Ipv4Addr::UNSPECIFIED, port), size)?
+            ])
+        };
+
+        // A dual-stack socket already occupies the IPv4 port
+        let port = v6.local_addr()?.port();
+        match UdpSocket::new((Ipv4Addr::UNSPECIFIED, port), size) {
+            Ok(v4) => MultiSocket::new(vec![v6, v4]),
+            Err(ref err) if err.kind() == ErrorKind::AddrInUse => MultiSocket::new(vec![v6]),
+            Err(err) => Err(err)
+        }
+
+    }
+
+    fn validates_migrations(&self) -> bool {
+        self.config.migration_validation
+            || self.config.address_change_policy == AddressChangePolicy::Challenge
+    }
+
+}
+
+// Static Helpers -------------------------------------------------------------
+fn flood_burst(rate: u32, burst: u32) -> f64 {
+    if burst == 0 {
+        rate as f64
+
+    } else {
+        burst as f64
+    }
+}
+
+fn is_banned<K: Eq + Hash>(bans: &HashMap<K, Option<Instant>>, key: &K) -> bool {
+    bans.get(key).is_some_and(|until| ban_active(*until, Instant::now()))
+}
+
+fn ban_active(until: Option<Instant>, now: Instant) -> bool {
+    match until {
+        Some(until) => until > now,
+        None => true
+    }
+}
+
+/// A structure used for synchronous calls on a `Server` instance.
+#[derive(Debug)]
+pub struct ServerState<S: Socket> {
+
+    /// The socket the server is bound to
+    socket: S,
+
+    /// The local address of the socket
+    local_addr: SocketAddr,
+
+    /// Mappping of connections to their remote sender address
+    addresses: HashMap<ConnectionID, SocketAddr, FnvBuildHasher>,
+
+    /// Mapping of the actual connection objects
+    connections: ConnectionMap,
+
+    /// Mapping of lost connections which can still be resumed
+    resumable: HashMap<ConnectionID, ResumableConnection, FnvBuildHasher>,
+
+    /// Creation times of connections whose remote has yet to acknowledge
+    /// any of their packets
+    pending: HashMap<ConnectionID, Instant, FnvBuildHasher>,
+
+    /// Budget for packets from unknown addresses and new connections
+    limiter: HandshakeLimiter,
+
+    /// Token buckets for all incoming packets per address
+    flood_limiter: FloodLimiter,
+
+    /// Validator for the ids of new connections
+    id_generator: Box<dyn IdGenerator>,
+
+    /// List of peers last shared with all connections
+    peers: Vec<Peer>,
+
+    /// Session state last shared with all connections
+    session_state: Option<Vec<u8>>,
+
+    /// Offset of the first connection to send a packet when rotating
+    send_offset: usize,
+
+    /// Start of the previous tick
+    previous_tick_start: Option<Instant>,
+
+    /// Time at which the statistics were last passed to the handler
+    stats_report_time: Instant,
+
+    /// Number of bytes received since the previous tick
+    received_bytes: usize,
+
+    /// Worker threads which process the connections in parallel
+    workers: WorkerPool
+
+}
+
+impl <S: Socket>ServerState<S> {
+
+    fn new(
+        socket: S, local_addr: SocketAddr, id_generator: Box<dyn IdGenerator>,
+        workers: WorkerPool
+
+    ) -> ServerState<S> {
+        ServerState {
+            socket,
+            local_addr,
+            addresses: HashMap::default(),
+            connections: ConnectionMap::default(),
+            resumable: HashMap::default(),
+            pending: HashMap::default(),
+            limiter: HandshakeLimiter::new(),
+            flood_limiter: FloodLimiter::new(),
+            id_generator,
+            peers: Vec::new(),
+            session_state: None,
+            send_offset: 0,
+            previous_tick_start: None,
+            stats_report_time: Instant::now(),
+            received_bytes: 0,
+            workers
+        }
+    }
+
+    /// Returns the connections of the server, i.e. those passed to
+    /// `Handler::tick_connections`.
+    pub fn connections(&self) -> &ConnectionMap {
+        &self.connections
+    }
+
+    /// Returns the connections of the server for modification, i.e. to
+    /// queue messages outside of the handler.
+    pub fn connections_mut(&mut self) -> &mut ConnectionMap {
+        &mut self.connections
+    }
+
+    /// Returns the server's underlying socket.
+    pub fn socket(&self) -> &S {
+        &self.socket
+    }
+
+    /// Consumes the state, returning the server's underlying socket.
+    pub fn into_socket(self) -> S {
+        self.socket
+    }
+
+}
@@ -0,0 +1,355 @@
+// Copyright (c) 2015-2016 Ivo Wetzel
+
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::collections::VecDeque;
+use std::io::{Error, ErrorKind};
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::sync::mpsc::TryRecvError;
+use shared::udp_socket::UdpSocket;
+use super::{
+    Config, Connection, ConnectionFailure, ConnectionID, ConnectionMap, Handler, MessageHandle,
+    MessageKind, Server, ServerHandle, ServerState, ServerStats, Stats
+};
+
+
+/// Enum of stream related server events.
+#[derive(Debug, PartialEq)]
+pub enum ServerEvent {
+
+    /// Event emitted once the stream's underlying server has been bound to
+    /// its local address.
+    Bind,
+
+    /// Event emitted each time the stream's underlying handler's
+    /// `tick_connections` method would have been invoked.
+    Tick,
+
+    /// Event emitted once the stream's underlying server has been shut down.
+    Shutdown,
+
+    /// Event emitted once a connection from a client has been established.
+    Connection(ConnectionID),
+
+    /// Event emitted once a previously lost connection has been resumed by
+    /// its client.
+    ConnectionResumed(ConnectionID),
+
+    /// Event emitted when a connection from a client failed to establish.
+    ConnectionFailed(ConnectionID),
+
+    /// Event emitted when an attempt of a remote address to establish a new
+    /// connection failed.
+    ConnectionAttemptFailed(SocketAddr, ConnectionFailure),
+
+    /// Event emitted when a existing connection to a client is lost.
+    ConnectionLost(ConnectionID),
+
+    /// Event emitted when a connection is closed with a reason code, right
+    /// before `ConnectionClosed`.
+    ConnectionKicked(ConnectionID, u8),
+
+    /// Event emitted when a connection is closed programmatically.
+    ConnectionClosed(ConnectionID, bool),
+
+    /// Event emitted for each message received from a client.
+    Message(ConnectionID, Vec<u8>),
+
+    /// Event emitted each time a `Reliable` or `Ordered` message send to a
+    /// client has been acknowledged.
+    MessageDelivered(ConnectionID, MessageHandle),
+
+    /// Event emitted each time the congestion state of a connection changes.
+    ConnectionCongestionState(ConnectionID, bool),
+
+    /// Event emitted each time a packet send to a client is lost.
+    PacketLost(ConnectionID, Vec<u8>)
+
+}
+
+/// Implementation of a stream based `Server` interface suitable for event
+/// polling.
+///
+/// Each call to `ServerStream::flush()` performs exactly one tick of the
+/// underlying server, the events of which are then drained via
+/// `ServerStream::receive()`.
+#[derive(Debug)]
+pub struct ServerStream {
+    handler: StreamHandler,
+    server: Server,
+    state: Option<ServerState<UdpSocket>>
+}
+
+impl ServerStream {
+
+    /// Creates a new server stream with the given configuration.
+    pub fn new(config: Config) -> ServerStream {
+        ServerStream::from_server(Server::new(config))
+    }
+
+    /// Consumes the passed in `Server` instance converting it into a
+    /// `ServerStream`.
+    pub fn from_server(server: Server) -> ServerStream {
+        ServerStream {
+            handler: StreamHandler::new(),
+            server,
+            state: None
+        }
+    }
+
+    // Getter
+
+    /// Returns the local address that the stream is bound to.
+    pub fn local_addr(&self) -> Result<SocketAddr, Error> {
+        self.server.local_addr()
+    }
+
+    /// Returns statistics (i.e. bandwidth usage) for the last second.
+    pub fn stats(&mut self) -> Stats {
+        self.server.stats()
+    }
+
+    /// Returns the totals of the stream's underlying server along with an
+    /// aggregate over its current connections.
+    pub fn aggregate_stats(&self) -> ServerStats {
+        match self.state {
+            Some(ref state) => self.server.aggregate_stats(state.connections()),
+            None => self.server.aggregate_stats(&ConnectionMap::default())
+        }
+    }
+
+    /// Returns the ids of all of the stream's connections which carry the
+    /// specified `tag`, sorted by their value.
+    pub fn connections_with_tag(&self, tag: &str) -> Vec<ConnectionID> {
+        match self.state {
+            Some(ref state) => self.server.connections_with_tag(state.connections(), tag),
+            None => Vec::new()
+        }
+    }
+
+    /// Returns a copy of the stream's current configuration.
+    pub fn config(&self) -> Config {
+        self.server.config()
+    }
+
+    /// Returns the connection with the specified id, if any.
+    pub fn connection(&mut self, id: ConnectionID) -> Option<&mut Connection> {
+        self.state.as_mut().and_then(|state| state.connections_mut().get_mut(&id))
+    }
+
+    /// Returns a new handle which allows other threads to control the
+    /// stream's underlying server.
+    pub fn handle(&self) -> ServerHandle {
+        self.server.handle()
+    }
+
+    // Setter
+
+    /// Overrides the stream's current configuration with the one provided,
+    /// applying it to all connections with the next `flush` call.
+    pub fn set_config(&mut self, config: Config) {
+        self.server.set_config(config);
+    }
+
+    // Methods
+
+    /// Binds the stream to the specified local address.
+    pub fn bind<A: ToSocketAddrs>(&mut self, addr: A) -> Result<(), Error> {
+
+        if self.state.is_none() {
+
+            // Clear any previous stream events
+            self.handler.clear();
+
+            self.state = Some(self.server.bind_sync(&mut self.handler, addr)?);
+
+            Ok(())
+
+        } else {
+            Err(Error::new(ErrorKind::AlreadyExists, ""))
+        }
+
+    }
+
+    /// Receives the next event from the stream's underlying server.
+    pub fn receive(&mut self) -> Result<ServerEvent, TryRecvError> {
+        if let Some(event) = self.handler.try_recv() {
+            Ok(event)
+
+        } else if self.state.is_some() {
+            Err(TryRecvError::Empty)
+
+        } else {
+            Err(TryRecvError::Disconnected)
+        }
+    }
+
+    /// Queues a message of the specified `kind` along with its `payload` for
+    /// the connection with the specified id, to be send with the next `flush`
+    /// call.
+    pub fn send(
+        &mut self, id: ConnectionID, kind: MessageKind, payload: Vec<u8>
+
+    ) -> Result<(), Error> {
+        if self.state.is_some() {
+            self.server.send(id, kind, payload);
+            Ok(())
+
+        } else {
+            Err(Error::new(ErrorKind::NotConnected, ""))
+        }
+    }
+
+    /// Queues a message of the specified `kind` along with its `payload` for
+    /// all established connections, to be send with the next `flush` call.
+    pub fn broadcast(&mut self, kind: MessageKind, payload: Vec<u8>) -> Result<(), Error> {
+        if self.state.is_some() {
+            self.server.broadcast(kind, payload);
+            Ok(())
+
+        } else {
+            Err(Error::new(ErrorKind::NotConnected, ""))
+        }
+    }
+
+    /// Closes the connection with the specified id with the next `flush`
+    /// call, sending the `reason` code along to the client.
+    pub fn kick(&mut self, id: ConnectionID, reason: u8) -> Result<(), Error> {
+        if self.state.is_some() {
+            self.server.kick(id, reason);
+            Ok(())
+
+        } else {
+            Err(Error::new(ErrorKind::NotConnected, ""))
+        }
+    }
+
+    /// Performs one tick of the stream's underlying server, queuing the
+    /// events for all received packets and sending all queued messages.
+    pub fn flush(&mut self) -> Result<(), Error> {
+        if let Some(state) = self.state.as_mut() {
+            self.server.tick_sync(&mut self.handler, state);
+            Ok(())
+
+        } else {
+            Err(Error::new(ErrorKind::NotConnected, ""))
+        }
+    }
+
+    /// Shuts down the stream's underlying server, resetting all of its
+    /// connections.
+    pub fn shutdown(&mut self) -> Result<(), Error> {
+        if let Some(mut state) = self.state.take() {
+            self.server.shutdown_sync(&mut self.handler, &mut state)
+
+        } else {
+            Err(Error::new(ErrorKind::NotConnected, ""))
+        }
+    }
+
+}
+
+
+#[derive(Debug, Default)]
+struct StreamHandler {
+    events: VecDeque<ServerEvent>
+}
+
+impl StreamHandler {
+
+    fn new() -> StreamHandler {
+        StreamHandler {
+            events: VecDeque::new()
+        }
+    }
+
+    fn try_recv(&mut self) -> Option<ServerEvent> {
+        self.events.pop_front()
+    }
+
+    fn clear(&mut self) {
+        self.events.clear();
+    }
+
+}
+
+impl Handler<Server> for StreamHandler {
+
+    fn bind(&mut self, _: &mut Server) {
+        self.events.push_back(ServerEvent::Bind);
+    }
+
+    fn tick_connections(
+        &mut self, _: &mut Server, connections: &mut ConnectionMap
+    ) {
+
+        // Keep the messages of each connection in order of their ids
+        let mut ids: Vec<ConnectionID> = connections.keys().cloned().collect();
+        ids.sort_by_key(|id| id.0);
+
+        for id in ids {
+            let conn = connections.get_mut(&id).unwrap();
+            for msg in conn.received() {
+                self.events.push_back(ServerEvent::Message(id, msg));
+            }
+        }
+
+        self.events.push_back(ServerEvent::Tick);
+
+    }
+
+    fn shutdown(&mut self, _: &mut Server) {
+        self.events.push_back(ServerEvent::Shutdown);
+    }
+
+    fn connection(&mut self, _: &mut Server, conn: &mut Connection) {
+        self.events.push_back(ServerEvent::Connection(conn.id()));
+    }
+
+    fn connection_resumed(&mut self, _: &mut Server, conn: &mut Connection) {
+        self.events.push_back(ServerEvent::ConnectionResumed(conn.id()));
+    }
+
+    fn connection_failed(&mut self, _: &mut Server, conn: &mut Connection) {
+        self.events.push_back(ServerEvent::ConnectionFailed(conn.id()));
+    }
+
+    fn connection_attempt_failed(
+        &mut self, _: &mut Server, addr: SocketAddr, failure: ConnectionFailure
+    ) {
+        self.events.push_back(ServerEvent::ConnectionAttemptFailed(addr, failure));
+    }
+
+    fn connection_message_delivered(
+        &mut self, _: &mut Server, conn: &mut Connection, handle: MessageHandle
+    ) {
+        self.events.push_back(ServerEvent::MessageDelivered(conn.id(), handle));
+    }
+
+    fn connection_packet_lost(
+        &mut self, _: &mut Server, conn: &mut Connection, data: &[u8]
+    ) {
+        self.events.push_back(ServerEvent::PacketLost(conn.id(), data.to_vec()));
+    }
+
+    fn connection_congestion_state(&mut self, _: &mut Server, conn: &mut Connection, state: bool) {
+        self.events.push_back(ServerEvent::ConnectionCongestionState(conn.id(), state));
+    }
+
+    fn connection_lost(&mut self, _: &mut Server, conn: &mut Connection) {
+        self.events.push_back(ServerEvent::ConnectionLost(conn.id()));
+    }
+
+    fn connection_kicked(&mut self, _: &mut Server, conn: &mut Connection, reason: u8) {
+        self.events.push_back(ServerEvent::ConnectionKicked(conn.id(), reason));
+    }
+
+    fn connection_closed(&mut self, _: &mut Server, conn: &mut Connection, by_remote: bool) {
+        self.events.push_back(ServerEvent::ConnectionClosed(conn.id(), by_remote));
+    }
+
+}
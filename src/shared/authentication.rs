@@ -0,0 +1,101 @@
+// Copyright (c) 2015-2016 Ivo Wetzel
+
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use super::crypto_provider::crypto_provider;
+
+/// Number of bytes added to each packet by its authentication tag.
+pub const AUTHENTICATION_OVERHEAD: usize = 16;
+
+/// Number of bytes added to a packet by its signature.
+pub const SIGNATURE_OVERHEAD: usize = 64;
+
+/// Appends a tag to the `packet` which authenticates its full contents with
+/// the message authentication code of the installed `CryptoProvider` using
+/// the specified `key`.
+pub fn sign(key: &[u8; 32], packet: &mut Vec<u8>) {
+    let tag = tag(key, packet);
+    packet.extend_from_slice(&tag);
+}
+
+/// Verifies the tag at the end of the `packet` with the specified `key`,
+/// stripping it off in case it is valid.
+///
+/// Returns `false` for packets which failed to authenticate.
+pub fn verify(key: &[u8; 32], packet: &mut Vec<u8>) -> bool {
+
+    if packet.len() < AUTHENTICATION_OVERHEAD {
+        return false;
+    }
+
+    let length = packet.len() - AUTHENTICATION_OVERHEAD;
+    if verify_tag(key, &packet[..length], &packet[length..]) {
+        packet.truncate(length);
+        true
+
+    } else {
+        false
+    }
+
+}
+
+/// Returns the tag which authenticates the `data` with the message
+/// authentication code of the installed `CryptoProvider` using the specified
+/// `key`.
+pub fn tag(key: &[u8; 32], data: &[u8]) -> [u8; AUTHENTICATION_OVERHEAD] {
+    let mac = crypto_provider().mac(key, data);
+    let mut tag = [0; AUTHENTICATION_OVERHEAD];
+    tag.copy_from_slice(&mac[..AUTHENTICATION_OVERHEAD]);
+    tag
+}
+
+/// Verifies the `tag` of the `data` with the specified `key`.
+pub fn verify_tag(key: &[u8; 32], data: &[u8], tag: &[u8]) -> bool {
+
+    // Compare in constant time to not leak the position of the first mismatch
+    let expected = self::tag(key, data);
+    let diff = expected.iter().zip(tag).fold(0, |diff, (a, b)| {
+        diff | (a ^ b)
+    });
+
+    diff == 0 && tag.len() == AUTHENTICATION_OVERHEAD
+
+}
+
+/// Appends a signature to the `packet` which covers its full contents using
+/// the private signing `key`.
+pub fn sign_with(key: &[u8; 32], packet: &mut Vec<u8>) {
+    let signature = crypto_provider().sign(key, packet);
+    packet.extend_from_slice(&signature);
+}
+
+/// Verifies the signature at the end of the `packet` with the specified
+/// `public_key`, stripping it off in case it is valid.
+///
+/// Returns `false` for packets which failed to verify.
+pub fn verify_signed(public_key: &[u8; 32], packet: &mut Vec<u8>) -> bool {
+
+    if packet.len() < SIGNATURE_OVERHEAD {
+        return false;
+    }
+
+    let length = packet.len() - SIGNATURE_OVERHEAD;
+    let mut signature = [0; SIGNATURE_OVERHEAD];
+    signature.copy_from_slice(&packet[length..]);
+    if crypto_provider().verify(public_key, &packet[..length], &signature) {
+        packet.truncate(length);
+        true
+
+    } else {
+        false
+    }
+
+}
+
+/// Returns the public key which belongs to the private signing `key`.
+pub fn signing_public_key(key: &[u8; 32]) -> [u8; 32] {
+    crypto_provider().signing_public_key(key)
+}
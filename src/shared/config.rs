@@ -5,9 +5,127 @@
 // <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
+#[cfg(feature = "serialization")]
+extern crate serde;
+#[cfg(feature = "serialization")]
+extern crate serde_json;
+#[cfg(feature = "serialization")]
+extern crate toml;
+
+use std::io::{Error, ErrorKind};
+
+#[cfg(feature = "serialization")]
+use std::fs;
+#[cfg(feature = "serialization")]
+use std::path::Path;
+#[cfg(feature = "serialization")]
+use self::serde::{Deserialize, Serialize};
+#[cfg(feature = "authentication")]
+use super::connection::HANDSHAKE_COOKIE_SIZE;
+
+/// Smallest `packet_max_size` accepted by `Config::validate`.
+const MIN_PACKET_SIZE: usize = 64;
+
+/// Largest `packet_max_size` accepted by `Config::validate`, which is the
+/// maximum payload of a UDP datagram.
+const MAX_PACKET_SIZE: usize = 65507;
+
+/// Enum defining how messages are handled which are send over a connection
+/// that is closing, closed or otherwise no longer open.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serialization", derive(Serialize, Deserialize))]
+pub enum ClosedSendPolicy {
+
+    /// Messages are rejected with an error of the kind
+    /// `ErrorKind::NotConnected`.
+    Error,
+
+    /// Messages are accepted but dropped right away.
+    Drop,
+
+    /// Messages are kept in the connection's queues, so they can still be
+    /// delivered in case the connection gets resumed.
+    Buffer
+
+}
+
+/// Enum defining the order in which a server sends the packets of its
+/// connections during each tick.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serialization", derive(Serialize, Deserialize))]
+pub enum SendOrder {
+
+    /// Packets are send in the iteration order of the server's internal
+    /// connection map, which usually stays the same from tick to tick.
+    Fixed,
+
+    /// Packets are send in the order of the connection ids, with the first
+    /// connection moving by one position each tick.
+    Rotate,
+
+    /// Packets are send in a random order which is chosen anew each tick.
+    Shuffle
+
+}
+
+/// Enum defining how a server handles packets for an established
+/// `ConnectionID` which arrive from a different address than the one used by
+/// the connection.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serialization", derive(Serialize, Deserialize))]
+pub enum AddressChangePolicy {
+
+    /// The connection follows the new address, which requires the remote to
+    /// prove its identity first in case `migration_validation` is enabled.
+    Migrate,
+
+    /// The connection only follows the new address once the remote proved
+    /// its identity, regardless of `migration_validation`.
+    Challenge,
+
+    /// The packet is treated as a new connection attempt, which replaces the
+    /// existing connection.
+    Reconnect
+
+}
+
+/// Enum defining how a server handles a peer which sends packets carrying the
+/// `ConnectionID` of an established connection from a different address, but
+/// fails to prove that it owns the connection's migration token.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serialization", derive(Serialize, Deserialize))]
+pub enum IdCollisionPolicy {
+
+    /// The existing connection is kept and the address of the new peer is no
+    /// longer challenged.
+    RejectNew,
+
+    /// The existing connection is closed as well, since its packets can no
+    /// longer be told apart from the ones of the new peer.
+    CloseBoth,
+
+    /// The policy returned by `Handler::connection_id_collision` is applied.
+    Handler
+
+}
 
 /// Structure defining connection and message configuration options.
+///
+/// The options can be split into the `ConnectionConfig` which applies to each
+/// individual connection and the `EndpointConfig` which applies to the
+/// `Server` or `Client` as a whole via `Config::connection` and
+/// `Config::endpoint`, and combined again via `Config::from_parts`.
+///
+/// With the `serialization` feature enabled, configurations can be
+/// (de-)serialized via `serde` and loaded from TOML or JSON files with
+/// `Config::from_file`. Fields which are missing from a file keep their
+/// default value.
 #[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(
+    feature = "serialization",
+    derive(Serialize, Deserialize),
+    serde(default, deny_unknown_fields)
+)]
 pub struct Config {
 
     /// Number of packets send per second. Default is `30`.
@@ -21,18 +139,429 @@ pub struct Config {
     /// `[1, 2, 3, 4]`.
     pub protocol_header: [u8; 4],
 
+    /// Whether packets carry a type byte following their header, which
+    /// explicitly marks data, acknowledgment only, closure and keep alive
+    /// packets.
+    ///
+    /// Both ends of a connection must use the same setting. Default is
+    /// `false`, which keeps packets compatible with previous versions.
+    pub packet_types: bool,
+
+    /// Whether a server responds to packets with a different
+    /// `protocol_header` with a small packet naming its own protocol header,
+    /// instead of ignoring them.
+    ///
+    /// This allows clients of mismatched versions to fail right away instead
+    /// of running into the `connection_init_threshold`. Responses are limited
+    /// to one per IP address and second and are never bigger than the packet
+    /// they respond to.
+    ///
+    /// Default is `false`.
+    pub protocol_mismatch_response: bool,
+
+    /// Whether data packets carry the sender's tick number, which is made
+    /// available along with each received message via
+    /// `Connection::received_with_tick_numbers`.
+    ///
+    /// The presence of the tick number is flagged in the packet's type byte,
+    /// so remotes parse such packets regardless of their own setting. Requires
+    /// `packet_types` to be enabled.
+    ///
+    /// Default is `false`.
+    pub packet_tick_numbers: bool,
+
+    /// Pre-shared key from which each connection derives its own key for
+    /// encrypting and authenticating all of its packets with
+    /// ChaCha20-Poly1305. Packets which fail to authenticate are dropped.
+    ///
+    /// Both ends of a connection must use the same key. Default is `None`.
+    ///
+    /// > Note: This field is feature-gated and will only be included when the
+    /// > `encryption` feature is enabled.
+    #[cfg(feature = "encryption")]
+    pub encryption_key: Option<[u8; 32]>,
+
+    /// Number of milliseconds after which an encrypted connection rotates its
+    /// packet key, `0` disables time based rotation.
+    ///
+    /// Each new key is derived from the previous one and announced in-band.
+    /// Since the derivation is one-way, a compromised key does not expose
+    /// the packets of earlier epochs, but all later keys can be derived from
+    /// it, so the packets of every following epoch are exposed as well. Both
+    /// ends of a connection must support key rotation. Default is `0`.
+    ///
+    /// > Note: This field is feature-gated and will only be included when the
+    /// > `encryption` feature is enabled.
+    #[cfg(feature = "encryption")]
+    pub key_rotation_interval: u32,
+
+    /// Number of packets after which an encrypted connection rotates its
+    /// packet key, `0` disables packet based rotation.
+    ///
+    /// Default is `0`.
+    ///
+    /// > Note: This field is feature-gated and will only be included when the
+    /// > `encryption` feature is enabled.
+    #[cfg(feature = "encryption")]
+    pub key_rotation_packets: u32,
+
+    /// Pre-shared key with which all packets are authenticated by appending
+    /// a HMAC-SHA256 tag over their header and payload, without encrypting
+    /// them.
+    ///
+    /// The `Server` and `Client` discard packets which fail to authenticate
+    /// before they reach any connection. Both ends of a connection must use
+    /// the same key. Default is `None`.
+    ///
+    /// > Note: This field is feature-gated and will only be included when the
+    /// > `authentication` feature is enabled.
+    #[cfg(feature = "authentication")]
+    pub authentication_key: Option<[u8; 32]>,
+
+    /// Whether a `Server` answers the first packet from an unknown address
+    /// with a signed cookie and only creates a connection once the remote
+    /// echoes the cookie back.
+    ///
+    /// Cookies are verified without keeping any state, so no memory is
+    /// committed to addresses which cannot receive packets, at the cost of an
+    /// additional round trip for every new connection. Clients always echo
+    /// the cookies they receive. Since cookies are larger than an empty
+    /// packet, `Config::validate` requires `handshake_min_size` to be at
+    /// least `32` bytes, so spoofed packets are not answered with larger
+    /// cookies.
+    ///
+    /// Default is `false`.
+    ///
+    /// > Note: This field is feature-gated and will only be included when the
+    /// > `authentication` feature is enabled.
+    #[cfg(feature = "authentication")]
+    pub handshake_cookies: bool,
+
+    /// Private Ed25519 key with which a `Server` signs its responses to
+    /// unconnected server info queries, so server browsers can verify that
+    /// the info was not spoofed or tampered with by a third party.
+    ///
+    /// Only the matching public key, as returned from
+    /// `Connection::server_info_public_key`, needs to be distributed to the
+    /// server browsers, which set it as their `server_info_public_key`.
+    /// Default is `None`.
+    ///
+    /// > Note: This field is feature-gated and will only be included when the
+    /// > `authentication` feature is enabled.
+    #[cfg(feature = "authentication")]
+    pub server_info_signing_key: Option<[u8; 32]>,
+
+    /// Public Ed25519 key with which the responses to server info queries
+    /// are verified.
+    ///
+    /// When set, `Connection::server_info_from_packet` only accepts responses
+    /// which were signed with the matching `server_info_signing_key`.
+    /// Default is `None`.
+    ///
+    /// > Note: This field is feature-gated and will only be included when the
+    /// > `authentication` feature is enabled.
+    #[cfg(feature = "authentication")]
+    pub server_info_public_key: Option<[u8; 32]>,
+
+    /// Whether connections perform an ephemeral X25519 key exchange once
+    /// established, from which a secret shared by both ends is derived and
+    /// made available via `Connection::session_key`.
+    ///
+    /// Both ends of a connection must use the same setting. Default is
+    /// `false`.
+    ///
+    /// > Note: This field is feature-gated and will only be included when the
+    /// > `key_exchange` feature is enabled.
+    #[cfg(feature = "key_exchange")]
+    pub key_exchange: bool,
+
+    /// Whether packets are encrypted and authenticated with a key derived
+    /// from the key exchange, without requiring any pre-shared key.
+    ///
+    /// Each end starts encrypting its packets once the remote has
+    /// acknowledged its public key and only accepts encrypted packets once
+    /// the remote has started to encrypt them as well. Requires
+    /// `key_exchange` to be enabled.
+    ///
+    /// Since the exchange is not authenticated, this protects against
+    /// eavesdropping but not against an active attacker intercepting the
+    /// handshake.
+    ///
+    /// Both ends of a connection must use the same setting. Default is
+    /// `false`.
+    ///
+    /// > Note: This field is feature-gated and will only be included when the
+    /// > `key_exchange` feature is enabled.
+    #[cfg(feature = "key_exchange")]
+    pub key_exchange_encryption: bool,
+
+    /// Private key shared with a backend which issues `ConnectToken`s.
+    ///
+    /// When set, a `Server` only creates connections for packets which
+    /// present a valid, unexpired token that was issued for their
+    /// `ConnectionID`. All other packets are dropped before any connection
+    /// state is allocated. Default is `None`.
+    ///
+    /// > Note: This field is feature-gated and will only be included when the
+    /// > `connect_token` feature is enabled.
+    #[cfg(feature = "connect_token")]
+    pub connect_token_key: Option<[u8; 32]>,
+
+    /// Whether closure packets from the remote are only accepted once they
+    /// were authenticated via `encryption_key`, `authentication_key` or
+    /// `key_exchange_encryption`.
+    ///
+    /// Closure packets carry no secret on their own, so anyone who learns a
+    /// `ConnectionID` could otherwise close the connection. Unauthenticated
+    /// closure packets are ignored, in which case the connection is only
+    /// dropped once the `connection_drop_threshold` is exceeded.
+    ///
+    /// Default is `false`.
+    pub authenticated_close: bool,
+
     /// Maximum roundtrip-time in milliseconds before a packet is considered
     /// lost. Default is `1000`.
     pub packet_drop_threshold: u32,
 
+    /// Maximum time in milliseconds a packet may remain unacknowledged before
+    /// it is considered lost and its reliable messages are re-send, even if
+    /// the remote has stopped sending any acknowledgements.
+    ///
+    /// The timeout is extended by twice the connection's current roundtrip
+    /// time. A value of `0` disables the time based re-transmission.
+    ///
+    /// Default is `0`.
+    pub retransmit_timeout_ms: u32,
+
+    /// Whether a minimal, acknowledgment only packet should be send right
+    /// after receiving packets containing data, instead of waiting for the
+    /// next tick to acknowledge them.
+    ///
+    /// This keeps the measured roundtrip time accurate at low send rates.
+    /// Default is `false`.
+    pub immediate_acks: bool,
+
+    /// Maximum number of received packets whose acknowledgment may be
+    /// coalesced into a single acknowledgment only packet when
+    /// `immediate_acks` is enabled. A value of `0` disables the limit.
+    ///
+    /// An acknowledgment only packet is send as soon as either this or the
+    /// `ack_coalesce_ms` limit is reached, with both limits disabled every
+    /// received packet containing data is acknowledged right away. A regular
+    /// packet send in the meantime acknowledges all data received so far.
+    ///
+    /// Default is `0`.
+    pub ack_coalesce_packets: u32,
+
+    /// Maximum time in milliseconds the acknowledgment of received packets
+    /// may be delayed when `immediate_acks` is enabled, before an
+    /// acknowledgment only packet must be send. A value of `0` disables the
+    /// limit.
+    ///
+    /// The limit is checked after receiving packets and whenever a tick's
+    /// regular packet is skipped by the rate limiter, so acknowledgments may
+    /// be delayed by up to one receive interval longer. See
+    /// `ack_coalesce_packets` for how both limits interact.
+    ///
+    /// Default is `0`.
+    pub ack_coalesce_ms: u32,
+
     /// Maximum time in milliseconds until the first packet must be received
     /// before a connection attempt fails. Default is `100`.
     pub connection_init_threshold: u32,
 
+    /// Minimum time in milliseconds between any two packets send while a
+    /// connection is being established, i.e. in order to not flood lossy
+    /// links with retransmitted handshakes.
+    ///
+    /// Must be less than the `connection_init_threshold`, since the attempt
+    /// would fail before the handshake is ever retransmitted otherwise.
+    /// Default is `0`, which sends a packet with every tick.
+    pub handshake_retry_interval: u32,
+
+    /// Maximum number of packets send while a connection is being
+    /// established, before the connection attempt fails in case the last one
+    /// went unanswered for another `handshake_retry_interval`.
+    ///
+    /// The attempt fails once the `connection_init_threshold` is exceeded
+    /// regardless. Default is `0`, which disables the limit.
+    pub handshake_max_attempts: u32,
+
     /// Maximum time in milliseconds between any two packets before the
     /// connection gets dropped. Default is `1000`.
     pub connection_drop_threshold: u32,
 
+    /// Maximum time in milliseconds a connection may go without receiving
+    /// any application messages from its remote before it is closed, i.e. in
+    /// order to reclaim the slots of idle players.
+    ///
+    /// Unlike the `connection_drop_threshold` this ignores packets which only
+    /// carry acknowledgements or internal control messages. Idle connections
+    /// are closed with the `Connection::IDLE_TIMEOUT_REASON` code. A value of
+    /// `0` disables the timeout.
+    ///
+    /// Default is `0`.
+    pub idle_timeout: u32,
+
+    /// Maximum number of packets per second a server accepts from any single
+    /// IP address without an established connection, which includes both
+    /// connection attempts and packets from migrating remotes.
+    ///
+    /// Further packets are dropped before any connection state is allocated.
+    /// A value of `0` disables the limit.
+    ///
+    /// Default is `0`.
+    pub unknown_address_packet_limit: u32,
+
+    /// Number of packets per second a server accepts from any single IP
+    /// address, regardless of whether they belong to an established
+    /// connection.
+    ///
+    /// Each address has a bucket of `flood_packet_burst` tokens which refills
+    /// at this rate, with every packet taking one token. Packets arriving at
+    /// an empty bucket are dropped before they are parsed and reported via
+    /// `Handler::source_throttled`. A value of `0` disables the limit.
+    ///
+    /// Default is `0`.
+    pub flood_packet_rate: u32,
+
+    /// Maximum number of packets a server accepts from any single IP address
+    /// in a single burst when `flood_packet_rate` is enabled.
+    ///
+    /// A value of `0` uses the `flood_packet_rate` as the burst size.
+    ///
+    /// Default is `0`.
+    pub flood_packet_burst: u32,
+
+    /// Maximum number of new connections a server creates per second across
+    /// all addresses.
+    ///
+    /// Packets which would create further connections are dropped. A value
+    /// of `0` disables the limit.
+    ///
+    /// Default is `0`.
+    pub handshake_limit: u32,
+
+    /// Whether a server only creates connections for IP addresses and
+    /// `ConnectionID`s which were explicitly allowed via
+    /// `Server::allow_address` and `Server::allow_connection`.
+    ///
+    /// Packets from all other sources which would create a new connection
+    /// are dropped, while established connections are kept regardless.
+    ///
+    /// Default is `false`.
+    pub whitelist_only: bool,
+
+    /// Maximum number of connections a server keeps for any single IP
+    /// address.
+    ///
+    /// Packets which would create further connections are dropped and
+    /// reported via `Handler::connection_limit_exceeded`. A value of `0`
+    /// disables the limit.
+    ///
+    /// Default is `0`.
+    pub max_connections_per_ip: u32,
+
+    /// Maximum number of connections a server keeps in total.
+    ///
+    /// Packets which would create further connections are answered with a
+    /// denial, which fails the connection attempt of the client, and are
+    /// reported via `Handler::server_full`. A value of `0` disables the
+    /// limit.
+    ///
+    /// Default is `0`.
+    pub max_connections: u32,
+
+    /// Maximum number of milliseconds a server keeps connections whose
+    /// remote has yet to acknowledge any of their packets, i.e. half-open
+    /// connections from spoofed addresses.
+    ///
+    /// Expired connections are removed and reported via
+    /// `Handler::connection_failed`. A value of `0` keeps them until the
+    /// `connection_drop_threshold` is exceeded.
+    ///
+    /// Default is `0`.
+    pub pending_connection_timeout: u32,
+
+    /// Maximum number of connections a server keeps whose remote has yet to
+    /// acknowledge any of their packets.
+    ///
+    /// Packets which would create further connections are dropped and
+    /// reported as `PacketDropReason::RateLimited`, so half-open connections
+    /// can't take up the whole connection table. A value of `0` disables the
+    /// limit.
+    ///
+    /// Default is `0`.
+    pub max_pending_connections: u32,
+
+    /// Number of worker threads across which a server shards the parsing of
+    /// received packets and the assembly of outgoing packets of its
+    /// connections each tick.
+    ///
+    /// All `Handler` callbacks are still invoked on the server's thread,
+    /// after the workers of the respective phase have finished. The threads
+    /// are spawned once the server is bound and reused for every tick.
+    /// Values of `0` and `1` process all connections on the server's thread.
+    ///
+    /// > Note: Connections are always processed on the server's thread when
+    /// > the `packet_handler_compress` feature is enabled, since the
+    /// > compression methods of the handler must be invoked in place.
+    ///
+    /// Default is `0`.
+    pub worker_threads: u32,
+
+    /// Maximum number of bytes per second a server sends across all of its
+    /// connections.
+    ///
+    /// Each tick's share of the budget is split evenly between the
+    /// connections, with the bytes left unused by one connection being
+    /// passed on to the ones sending after it. Messages which exceed a
+    /// connection's share remain queued for later ticks, so the packets get
+    /// smaller instead of saturating the uplink. Packet headers and
+    /// acknowledgements are always send. A value of `0` disables the limit.
+    ///
+    /// Default is `0`.
+    pub max_outgoing_bandwidth: u32,
+
+    /// Minimum size in bytes of packets which create a new connection on a
+    /// server.
+    ///
+    /// Connections pad their packets to this size until they are
+    /// established, while servers drop any smaller packets for unknown
+    /// connections. This prevents small, spoofed packets from causing larger
+    /// responses. A value of `0` disables the padding.
+    ///
+    /// Both ends of a connection must use the same setting. Default is `0`.
+    pub handshake_min_size: usize,
+
+    /// Maximum multiple of the bytes received from a not yet validated
+    /// address, which a server sends to it.
+    ///
+    /// An address is validated once the remote acknowledges a packet which
+    /// was send to it. Until then, packets exceeding the limit are dropped
+    /// as if they were lost. A value of `0` disables the limit.
+    ///
+    /// Default is `0`.
+    pub amplification_factor: u32,
+
+    /// Whether early data which a remote attached to its connection request
+    /// via `Connection::send_early_data()` is accepted.
+    ///
+    /// Accepted early data is received like any other reliable message,
+    /// allowing small exchanges like a ping or a quick query to complete
+    /// within a single round trip. Rejected early data is send again by the
+    /// remote once the connection has been established.
+    ///
+    /// Early data is not protected against replays: anyone who captured a
+    /// connection request can send it again, causing its message to be
+    /// received once more by a new connection. It also arrives before the
+    /// remote was authenticated and before any keys were exchanged, so it
+    /// should only be accepted for requests which are safe to process more
+    /// than once.
+    ///
+    /// Default is `false`.
+    pub early_data: bool,
+
     /// The percent of available packet bytes to use when serializing
     /// `MessageKind::Instant` into a packet via a `MessageQueue`.
     pub message_quota_instant: f32,
@@ -45,6 +574,52 @@ pub struct Config {
     /// `MessageKind::Ordered` into a packet via a `MessageQueue`.
     pub message_quota_ordered: f32,
 
+    /// Maximum number of messages which may be waiting to be send in each of
+    /// the queues for `MessageKind::Instant`, `MessageKind::Reliable` and
+    /// `MessageKind::Ordered` messages.
+    ///
+    /// Further messages are rejected until the queue drains. A value of `0`
+    /// disables the limit.
+    ///
+    /// Default is `0`.
+    pub message_queue_limit: usize,
+
+    /// Maximum number of messages which are held back while a connection is
+    /// still being established, instead of being send along with its
+    /// handshake packets.
+    ///
+    /// Held back messages are send in order once the connection has been
+    /// established. Further messages are rejected with an error of the kind
+    /// `ErrorKind::WouldBlock` until then. A value of `0` sends all messages
+    /// right away.
+    ///
+    /// Default is `0`.
+    pub connecting_message_limit: usize,
+
+    /// Maximum number of milliseconds for which a connection is kept alive
+    /// without receiving any packets, once its remote requested to be kept
+    /// alive via `Connection::request_keep_alive()`, i.e. while a mobile
+    /// client is in the background.
+    ///
+    /// Longer requests are granted this maximum instead. A value of `0`
+    /// declines all requests.
+    ///
+    /// Default is `0`.
+    pub max_keep_alive_extension: u32,
+
+    /// Determines how messages are handled which are send over a connection
+    /// that is closing, closed, lost or failed to connect.
+    ///
+    /// Default is `ClosedSendPolicy::Buffer`.
+    pub closed_send_policy: ClosedSendPolicy,
+
+    /// Determines the order in which a server sends the packets of its
+    /// connections during each tick, spreading the latency at the tail of
+    /// each burst of packets across all connections.
+    ///
+    /// Default is `SendOrder::Fixed`.
+    pub send_order: SendOrder,
+
     /// Whether to keep track of ticks which exceed their maximum running time
     /// and speed up successive ticks in order to keep the desired target
     /// `send_rate` stable.
@@ -79,7 +654,177 @@ pub struct Config {
     /// Values smaller than `0.0` or bigger than `1.0` will have no effect.
     ///
     /// Default is `1.0`.
-    pub tick_overflow_recovery_rate: f32
+    pub tick_overflow_recovery_rate: f32,
+
+    /// Time in milliseconds at the end of each tick's sleep time which is
+    /// spend yielding to other threads in a loop instead of sleeping.
+    ///
+    /// The granularity of the operating system's sleep timer can be as
+    /// coarse as `15.6` milliseconds, which is too imprecise for higher
+    /// `send_rate`s. Sleeping for most of the interval and spinning the
+    /// remainder keeps the tick rate precise at the cost of additional CPU
+    /// usage.
+    ///
+    /// Default is `0`, which sleeps for the whole interval.
+    pub tick_spin_threshold: u32,
+
+    /// Maximum time in milliseconds after a connection was lost during which
+    /// its client may still resume it by presenting the resumption token it
+    /// was issued by the server.
+    ///
+    /// Resumed connections keep their previous `ConnectionID` and are
+    /// considered as established with their very first packet.
+    ///
+    /// A value of `0` disables the issuing of resumption tokens.
+    ///
+    /// The token's secret is send as part of a regular message, so unless the
+    /// packets are encrypted, anyone observing the traffic can use it to take
+    /// over the connection once it was lost. Resumption tokens should
+    /// therefore only be issued in combination with `encryption_key` or the
+    /// encryption of the key exchange.
+    ///
+    /// Default is `0`.
+    pub resumption_threshold: u32,
+
+    /// Private key with which a `Server` seals the `ResumptionTicket`s it
+    /// issues to its established connections.
+    ///
+    /// Clients presenting a valid ticket while connecting resume their
+    /// previous session, including its `ConnectionID` and the session key of
+    /// the key exchange, without the server keeping any state for it. Unlike
+    /// resumption tokens, tickets survive a restart of the client. Default is
+    /// `None`.
+    ///
+    /// > Note: This field is feature-gated and will only be included when the
+    /// > `encryption` feature is enabled.
+    #[cfg(feature = "encryption")]
+    pub resumption_ticket_key: Option<[u8; 32]>,
+
+    /// Number of seconds for which the `ResumptionTicket`s issued by a
+    /// `Server` remain valid.
+    ///
+    /// Default is `3600`.
+    ///
+    /// > Note: This field is feature-gated and will only be included when the
+    /// > `encryption` feature is enabled.
+    #[cfg(feature = "encryption")]
+    pub resumption_ticket_lifetime: u32,
+
+    /// Whether reliable and ordered messages which have not yet been
+    /// acknowledged by the remote of a lost connection are retained and
+    /// re-send once the connection gets resumed within the
+    /// `resumption_threshold`.
+    ///
+    /// Retained messages might be delivered more than once in case their
+    /// acknowledgement was lost along with the connection.
+    ///
+    /// Default is `false`.
+    pub resumption_retain_messages: bool,
+
+    /// Whether a connection must validate a change of its remote address
+    /// before sending any further packets to it.
+    ///
+    /// When enabled, a challenge is send to the new address which the remote
+    /// has to answer with a proof based on a migration token it was issued
+    /// once the connection was established. Until then, packets keep being
+    /// send to the previous address.
+    ///
+    /// Remotes which have not been issued a migration token are unable to
+    /// migrate to a different address.
+    ///
+    /// The proof is a message authentication code, which requires the
+    /// `authentication` feature. Migration tokens are only issued once the
+    /// connection's packets are encrypted or authenticated via
+    /// `authentication_key`. Without encryption their secret is send in plain
+    /// text, leaving the authenticated packets as the only protection.
+    ///
+    /// Default is `false`.
+    pub migration_validation: bool,
+
+    /// Determines how a server handles packets for an established
+    /// `ConnectionID` arriving from a different address, which happens when
+    /// either the remote migrated or a different peer reuses its id.
+    ///
+    /// Default is `AddressChangePolicy::Migrate`.
+    pub address_change_policy: AddressChangePolicy,
+
+    /// Determines how a server handles a peer which reuses the id of an
+    /// established connection from a different address, as detected by the
+    /// migration validation of `migration_validation` or
+    /// `AddressChangePolicy::Challenge`.
+    ///
+    /// `Handler::connection_id_collision` is invoked for each collision
+    /// regardless of the policy.
+    ///
+    /// Default is `IdCollisionPolicy::RejectNew`.
+    pub id_collision_policy: IdCollisionPolicy,
+
+    /// Time in milliseconds a client waits before it attempts to connect to
+    /// the next address of a host name which resolves to several addresses,
+    /// i.e. both an IPv6 and an IPv4 address.
+    ///
+    /// The attempts race each other and the first one to complete its
+    /// handshake is kept, while all others are dropped. A value of `0`
+    /// starts all attempts at once.
+    ///
+    /// Default is `250`.
+    pub connect_attempt_delay: u32,
+
+    /// Time in milliseconds by which a client delays each packet it sends
+    /// or receives, in order to test an application under bad network
+    /// conditions.
+    ///
+    /// Default is `0`.
+    ///
+    /// > Note: This field is feature-gated and will only be included when the
+    /// > `network_simulator` feature is enabled.
+    #[cfg(feature = "network_simulator")]
+    pub simulated_latency: u32,
+
+    /// Maximum time in milliseconds by which a client further delays each
+    /// packet it sends or receives, on top of the `simulated_latency`. Since
+    /// the delay is picked at random for each packet, packets may arrive out
+    /// of order.
+    ///
+    /// Default is `0`.
+    ///
+    /// > Note: This field is feature-gated and will only be included when the
+    /// > `network_simulator` feature is enabled.
+    #[cfg(feature = "network_simulator")]
+    pub simulated_jitter: u32,
+
+    /// Percentage of the packets a client sends or receives which it drops,
+    /// in order to test an application under bad network conditions.
+    ///
+    /// Default is `0.0`.
+    ///
+    /// > Note: This field is feature-gated and will only be included when the
+    /// > `network_simulator` feature is enabled.
+    #[cfg(feature = "network_simulator")]
+    pub simulated_packet_loss: f32,
+
+    /// Percentage of the packets a client sends or receives which it
+    /// duplicates, in order to test an application under bad network
+    /// conditions.
+    ///
+    /// Default is `0.0`.
+    ///
+    /// > Note: This field is feature-gated and will only be included when the
+    /// > `network_simulator` feature is enabled.
+    #[cfg(feature = "network_simulator")]
+    pub simulated_packet_duplication: f32,
+
+    /// Whether a server shares the list of all connected peers and its
+    /// session state with each of its connections, allowing the peers to
+    /// elect a new host among themselves once the connection to the server
+    /// is lost.
+    ///
+    /// Since the peers need to be able to reach the new host, the address of
+    /// every connection is exposed to all other connections, so this should
+    /// only be enabled for sessions between mutually trusted players.
+    ///
+    /// Default is `false`.
+    pub host_migration: bool
 
 }
 
@@ -89,17 +834,772 @@ impl Default for Config {
         Config {
             send_rate: 30,
             protocol_header: [1, 2, 3, 4],
+            packet_types: false,
+            packet_tick_numbers: false,
+            protocol_mismatch_response: false,
+            #[cfg(feature = "encryption")]
+            encryption_key: None,
+            #[cfg(feature = "encryption")]
+            key_rotation_interval: 0,
+            #[cfg(feature = "encryption")]
+            key_rotation_packets: 0,
+            #[cfg(feature = "authentication")]
+            authentication_key: None,
+            #[cfg(feature = "authentication")]
+            handshake_cookies: false,
+            #[cfg(feature = "authentication")]
+            server_info_signing_key: None,
+            #[cfg(feature = "authentication")]
+            server_info_public_key: None,
+            #[cfg(feature = "key_exchange")]
+            key_exchange: false,
+            #[cfg(feature = "key_exchange")]
+            key_exchange_encryption: false,
+            #[cfg(feature = "connect_token")]
+            connect_token_key: None,
             packet_max_size: 1400,
+            authenticated_close: false,
             packet_drop_threshold: 1000,
+            retransmit_timeout_ms: 0,
+            immediate_acks: false,
+            ack_coalesce_packets: 0,
+            ack_coalesce_ms: 0,
             connection_init_threshold: 100,
+            handshake_retry_interval: 0,
+            handshake_max_attempts: 0,
             connection_drop_threshold: 1000,
+            idle_timeout: 0,
+            unknown_address_packet_limit: 0,
+            flood_packet_rate: 0,
+            flood_packet_burst: 0,
+            handshake_limit: 0,
+            whitelist_only: false,
+            max_connections_per_ip: 0,
+            max_connections: 0,
+            pending_connection_timeout: 0,
+            max_pending_connections: 0,
+            worker_threads: 0,
+            max_outgoing_bandwidth: 0,
+            handshake_min_size: 0,
+            amplification_factor: 0,
+            early_data: false,
             message_quota_instant: 60.0,
             message_quota_reliable: 20.0,
             message_quota_ordered: 20.0,
+            message_queue_limit: 0,
+            connecting_message_limit: 0,
+            max_keep_alive_extension: 0,
+            closed_send_policy: ClosedSendPolicy::Buffer,
+            send_order: SendOrder::Fixed,
             tick_overflow_recovery: true,
-            tick_overflow_recovery_rate: 1.0
+            tick_overflow_recovery_rate: 1.0,
+            tick_spin_threshold: 0,
+            resumption_threshold: 0,
+            #[cfg(feature = "encryption")]
+            resumption_ticket_key: None,
+            #[cfg(feature = "encryption")]
+            resumption_ticket_lifetime: 3600,
+            resumption_retain_messages: false,
+            migration_validation: false,
+            address_change_policy: AddressChangePolicy::Migrate,
+            id_collision_policy: IdCollisionPolicy::RejectNew,
+            connect_attempt_delay: 250,
+            #[cfg(feature = "network_simulator")]
+            simulated_latency: 0,
+            #[cfg(feature = "network_simulator")]
+            simulated_jitter: 0,
+            #[cfg(feature = "network_simulator")]
+            simulated_packet_loss: 0.0,
+            #[cfg(feature = "network_simulator")]
+            simulated_packet_duplication: 0.0,
+            host_migration: false
+        }
+    }
+
+}
+
+impl Config {
+
+    /// Combines separate connection and endpoint settings into a single
+    /// configuration.
+    pub fn from_parts(connection: ConnectionConfig, endpoint: EndpointConfig) -> Config {
+        Config {
+            packet_max_size: connection.packet_max_size,
+            protocol_header: connection.protocol_header,
+            packet_types: connection.packet_types,
+            packet_tick_numbers: connection.packet_tick_numbers,
+            #[cfg(feature = "encryption")]
+            encryption_key: connection.encryption_key,
+            #[cfg(feature = "encryption")]
+            key_rotation_interval: connection.key_rotation_interval,
+            #[cfg(feature = "encryption")]
+            key_rotation_packets: connection.key_rotation_packets,
+            #[cfg(feature = "authentication")]
+            authentication_key: connection.authentication_key,
+            #[cfg(feature = "key_exchange")]
+            key_exchange: connection.key_exchange,
+            #[cfg(feature = "key_exchange")]
+            key_exchange_encryption: connection.key_exchange_encryption,
+            authenticated_close: connection.authenticated_close,
+            packet_drop_threshold: connection.packet_drop_threshold,
+            retransmit_timeout_ms: connection.retransmit_timeout_ms,
+            immediate_acks: connection.immediate_acks,
+            ack_coalesce_packets: connection.ack_coalesce_packets,
+            ack_coalesce_ms: connection.ack_coalesce_ms,
+            connection_init_threshold: connection.connection_init_threshold,
+            handshake_retry_interval: connection.handshake_retry_interval,
+            handshake_max_attempts: connection.handshake_max_attempts,
+            connection_drop_threshold: connection.connection_drop_threshold,
+            idle_timeout: connection.idle_timeout,
+            handshake_min_size: connection.handshake_min_size,
+            amplification_factor: connection.amplification_factor,
+            early_data: connection.early_data,
+            message_quota_instant: connection.message_quota_instant,
+            message_quota_reliable: connection.message_quota_reliable,
+            message_quota_ordered: connection.message_quota_ordered,
+            message_queue_limit: connection.message_queue_limit,
+            connecting_message_limit: connection.connecting_message_limit,
+            max_keep_alive_extension: connection.max_keep_alive_extension,
+            closed_send_policy: connection.closed_send_policy,
+            migration_validation: connection.migration_validation,
+            send_rate: endpoint.send_rate,
+            protocol_mismatch_response: endpoint.protocol_mismatch_response,
+            #[cfg(feature = "authentication")]
+            handshake_cookies: endpoint.handshake_cookies,
+            #[cfg(feature = "authentication")]
+            server_info_signing_key: endpoint.server_info_signing_key,
+            #[cfg(feature = "authentication")]
+            server_info_public_key: endpoint.server_info_public_key,
+            #[cfg(feature = "connect_token")]
+            connect_token_key: endpoint.connect_token_key,
+            unknown_address_packet_limit: endpoint.unknown_address_packet_limit,
+            flood_packet_rate: endpoint.flood_packet_rate,
+            flood_packet_burst: endpoint.flood_packet_burst,
+            handshake_limit: endpoint.handshake_limit,
+            whitelist_only: endpoint.whitelist_only,
+            max_connections_per_ip: endpoint.max_connections_per_ip,
+            max_connections: endpoint.max_connections,
+            pending_connection_timeout: endpoint.pending_connection_timeout,
+            max_pending_connections: endpoint.max_pending_connections,
+            worker_threads: endpoint.worker_threads,
+            max_outgoing_bandwidth: endpoint.max_outgoing_bandwidth,
+            send_order: endpoint.send_order,
+            tick_overflow_recovery: endpoint.tick_overflow_recovery,
+            tick_overflow_recovery_rate: endpoint.tick_overflow_recovery_rate,
+            tick_spin_threshold: endpoint.tick_spin_threshold,
+            resumption_threshold: endpoint.resumption_threshold,
+            #[cfg(feature = "encryption")]
+            resumption_ticket_key: endpoint.resumption_ticket_key,
+            #[cfg(feature = "encryption")]
+            resumption_ticket_lifetime: endpoint.resumption_ticket_lifetime,
+            resumption_retain_messages: endpoint.resumption_retain_messages,
+            address_change_policy: endpoint.address_change_policy,
+            id_collision_policy: endpoint.id_collision_policy,
+            connect_attempt_delay: endpoint.connect_attempt_delay,
+            #[cfg(feature = "network_simulator")]
+            simulated_latency: endpoint.simulated_latency,
+            #[cfg(feature = "network_simulator")]
+            simulated_jitter: endpoint.simulated_jitter,
+            #[cfg(feature = "network_simulator")]
+            simulated_packet_loss: endpoint.simulated_packet_loss,
+            #[cfg(feature = "network_simulator")]
+            simulated_packet_duplication: endpoint.simulated_packet_duplication,
+            host_migration: endpoint.host_migration
+        }
+    }
+
+    /// Returns the settings which control each individual connection.
+    pub fn connection(&self) -> ConnectionConfig {
+        ConnectionConfig {
+            packet_max_size: self.packet_max_size,
+            protocol_header: self.protocol_header,
+            packet_types: self.packet_types,
+            packet_tick_numbers: self.packet_tick_numbers,
+            #[cfg(feature = "encryption")]
+            encryption_key: self.encryption_key,
+            #[cfg(feature = "encryption")]
+            key_rotation_interval: self.key_rotation_interval,
+            #[cfg(feature = "encryption")]
+            key_rotation_packets: self.key_rotation_packets,
+            #[cfg(feature = "authentication")]
+            authentication_key: self.authentication_key,
+            #[cfg(feature = "key_exchange")]
+            key_exchange: self.key_exchange,
+            #[cfg(feature = "key_exchange")]
+            key_exchange_encryption: self.key_exchange_encryption,
+            authenticated_close: self.authenticated_close,
+            packet_drop_threshold: self.packet_drop_threshold,
+            retransmit_timeout_ms: self.retransmit_timeout_ms,
+            immediate_acks: self.immediate_acks,
+            ack_coalesce_packets: self.ack_coalesce_packets,
+            ack_coalesce_ms: self.ack_coalesce_ms,
+            connection_init_threshold: self.connection_init_threshold,
+            handshake_retry_interval: self.handshake_retry_interval,
+            handshake_max_attempts: self.handshake_max_attempts,
+            connection_drop_threshold: self.connection_drop_threshold,
+            idle_timeout: self.idle_timeout,
+            handshake_min_size: self.handshake_min_size,
+            amplification_factor: self.amplification_factor,
+            early_data: self.early_data,
+            message_quota_instant: self.message_quota_instant,
+            message_quota_reliable: self.message_quota_reliable,
+            message_quota_ordered: self.message_quota_ordered,
+            message_queue_limit: self.message_queue_limit,
+            connecting_message_limit: self.connecting_message_limit,
+            max_keep_alive_extension: self.max_keep_alive_extension,
+            closed_send_policy: self.closed_send_policy,
+            migration_validation: self.migration_validation
+        }
+    }
+
+    /// Returns the settings which control the server or client as a whole.
+    pub fn endpoint(&self) -> EndpointConfig {
+        EndpointConfig {
+            send_rate: self.send_rate,
+            protocol_mismatch_response: self.protocol_mismatch_response,
+            #[cfg(feature = "authentication")]
+            handshake_cookies: self.handshake_cookies,
+            #[cfg(feature = "authentication")]
+            server_info_signing_key: self.server_info_signing_key,
+            #[cfg(feature = "authentication")]
+            server_info_public_key: self.server_info_public_key,
+            #[cfg(feature = "connect_token")]
+            connect_token_key: self.connect_token_key,
+            unknown_address_packet_limit: self.unknown_address_packet_limit,
+            flood_packet_rate: self.flood_packet_rate,
+            flood_packet_burst: self.flood_packet_burst,
+            handshake_limit: self.handshake_limit,
+            whitelist_only: self.whitelist_only,
+            max_connections_per_ip: self.max_connections_per_ip,
+            max_connections: self.max_connections,
+            pending_connection_timeout: self.pending_connection_timeout,
+            max_pending_connections: self.max_pending_connections,
+            worker_threads: self.worker_threads,
+            max_outgoing_bandwidth: self.max_outgoing_bandwidth,
+            send_order: self.send_order,
+            tick_overflow_recovery: self.tick_overflow_recovery,
+            tick_overflow_recovery_rate: self.tick_overflow_recovery_rate,
+            tick_spin_threshold: self.tick_spin_threshold,
+            resumption_threshold: self.resumption_threshold,
+            #[cfg(feature = "encryption")]
+            resumption_ticket_key: self.resumption_ticket_key,
+            #[cfg(feature = "encryption")]
+            resumption_ticket_lifetime: self.resumption_ticket_lifetime,
+            resumption_retain_messages: self.resumption_retain_messages,
+            address_change_policy: self.address_change_policy,
+            id_collision_policy: self.id_collision_policy,
+            connect_attempt_delay: self.connect_attempt_delay,
+            #[cfg(feature = "network_simulator")]
+            simulated_latency: self.simulated_latency,
+            #[cfg(feature = "network_simulator")]
+            simulated_jitter: self.simulated_jitter,
+            #[cfg(feature = "network_simulator")]
+            simulated_packet_loss: self.simulated_packet_loss,
+            #[cfg(feature = "network_simulator")]
+            simulated_packet_duplication: self.simulated_packet_duplication,
+            host_migration: self.host_migration
+        }
+    }
+
+    /// Loads a configuration from the TOML or JSON file at the specified
+    /// `path`, based on the file's extension.
+    ///
+    /// Fields missing from the file keep their default value, while unknown
+    /// fields are rejected. The loaded configuration is checked via
+    /// `Config::validate` before it is returned.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of the kind `ErrorKind::InvalidData` in case the
+    /// file cannot be parsed or contains invalid values, with its message
+    /// naming the offending field.
+    ///
+    /// > Note: This method is feature-gated and will only be included when
+    /// > the `serialization` feature is enabled.
+    #[cfg(feature = "serialization")]
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Config, Error> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)?;
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => Config::from_toml(&text),
+            Some("json") => Config::from_json(&text),
+            _ => Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Config file must have a .toml or .json extension"
+            ))
+        }
+    }
+
+    /// Parses and validates a configuration from a TOML string.
+    ///
+    /// > Note: This method is feature-gated and will only be included when
+    /// > the `serialization` feature is enabled.
+    #[cfg(feature = "serialization")]
+    pub fn from_toml(text: &str) -> Result<Config, Error> {
+        let config: Config = toml::from_str(text).map_err(|err| {
+            Error::new(ErrorKind::InvalidData, err.to_string())
+        })?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Parses and validates a configuration from a JSON string.
+    ///
+    /// > Note: This method is feature-gated and will only be included when
+    /// > the `serialization` feature is enabled.
+    #[cfg(feature = "serialization")]
+    pub fn from_json(text: &str) -> Result<Config, Error> {
+        let config: Config = serde_json::from_str(text).map_err(|err| {
+            Error::new(ErrorKind::InvalidData, err.to_string())
+        })?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Checks the configuration for values which would prevent connections
+    /// from working.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of the kind `ErrorKind::InvalidData` for the first
+    /// invalid field, with its message starting with the field's name.
+    pub fn validate(&self) -> Result<(), Error> {
+
+        if self.send_rate == 0 {
+            return Err(invalid_field("send_rate", "must be greater than 0"));
+        }
+
+        if self.packet_max_size < MIN_PACKET_SIZE || self.packet_max_size > MAX_PACKET_SIZE {
+            return Err(invalid_field("packet_max_size", &format!(
+                "must be between {} and {}", MIN_PACKET_SIZE, MAX_PACKET_SIZE
+            )));
+        }
+
+        if self.handshake_min_size > self.packet_max_size {
+            return Err(invalid_field(
+                "handshake_min_size", "must not exceed packet_max_size"
+            ));
+        }
+
+        if self.packet_tick_numbers && !self.packet_types {
+            return Err(invalid_field(
+                "packet_tick_numbers", "requires packet_types to be enabled"
+            ));
+        }
+
+        if self.connection_drop_threshold == 0 {
+            return Err(invalid_field(
+                "connection_drop_threshold", "must be greater than 0"
+            ));
+        }
+
+        if self.handshake_retry_interval >= self.connection_init_threshold
+            && self.handshake_retry_interval > 0 {
+            return Err(invalid_field(
+                "handshake_retry_interval", "must be less than connection_init_threshold"
+            ));
+        }
+
+        for &(name, quota) in &[
+            ("message_quota_instant", self.message_quota_instant),
+            ("message_quota_reliable", self.message_quota_reliable),
+            ("message_quota_ordered", self.message_quota_ordered),
+            #[cfg(feature = "network_simulator")]
+            ("simulated_packet_loss", self.simulated_packet_loss),
+            #[cfg(feature = "network_simulator")]
+            ("simulated_packet_duplication", self.simulated_packet_duplication)
+        ] {
+            if !(0.0..=100.0).contains(&quota) {
+                return Err(invalid_field(name, "must be between 0 and 100"));
+            }
         }
+
+        if !(0.0..=1.0).contains(&self.tick_overflow_recovery_rate) {
+            return Err(invalid_field(
+                "tick_overflow_recovery_rate", "must be between 0.0 and 1.0"
+            ));
+        }
+
+        self.validate_migration_validation()?;
+        self.validate_handshake_cookies()?;
+        self.validate_key_exchange()
+
+    }
+
+    #[cfg(feature = "authentication")]
+    fn validate_migration_validation(&self) -> Result<(), Error> {
+        if self.validates_migrations()
+            && self.authentication_key.is_none()
+            && !self.encrypts_packets() {
+            Err(invalid_field(
+                "migration_validation",
+                "requires encryption or an authentication_key"
+            ))
+
+        } else {
+            Ok(())
+        }
+    }
+
+    #[cfg(not(feature = "authentication"))]
+    fn validate_migration_validation(&self) -> Result<(), Error> {
+        if self.validates_migrations() {
+            Err(invalid_field(
+                "migration_validation",
+                "requires the authentication feature"
+            ))
+
+        } else {
+            Ok(())
+        }
+    }
+
+    #[cfg(feature = "authentication")]
+    fn validate_handshake_cookies(&self) -> Result<(), Error> {
+        if self.handshake_cookies && self.handshake_min_size < HANDSHAKE_COOKIE_SIZE {
+            Err(invalid_field("handshake_min_size", &format!(
+                "must be at least {} with handshake_cookies enabled", HANDSHAKE_COOKIE_SIZE
+            )))
+
+        } else {
+            Ok(())
+        }
+    }
+
+    #[cfg(not(feature = "authentication"))]
+    fn validate_handshake_cookies(&self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn validates_migrations(&self) -> bool {
+        self.migration_validation
+            || self.address_change_policy == AddressChangePolicy::Challenge
+    }
+
+    #[cfg(all(feature = "authentication", feature = "key_exchange"))]
+    fn encrypts_packets(&self) -> bool {
+        self.encryption_key.is_some() || self.key_exchange_encryption
+    }
+
+    #[cfg(all(feature = "authentication", feature = "encryption", not(feature = "key_exchange")))]
+    fn encrypts_packets(&self) -> bool {
+        self.encryption_key.is_some()
     }
 
+    #[cfg(all(feature = "authentication", not(feature = "encryption")))]
+    fn encrypts_packets(&self) -> bool {
+        false
+    }
+
+    #[cfg(feature = "key_exchange")]
+    fn validate_key_exchange(&self) -> Result<(), Error> {
+        if self.key_exchange_encryption && !self.key_exchange {
+            Err(invalid_field(
+                "key_exchange_encryption", "requires key_exchange to be enabled"
+            ))
+
+        } else {
+            Ok(())
+        }
+    }
+
+    #[cfg(not(feature = "key_exchange"))]
+    fn validate_key_exchange(&self) -> Result<(), Error> {
+        Ok(())
+    }
+
+}
+
+/// Structure defining the subset of `Config` options which control the
+/// behavior of each individual `Connection`, like its packet sizes, timing
+/// thresholds and message quotas.
+///
+/// See `Config` for a detailed description of each field.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(
+    feature = "serialization",
+    derive(Serialize, Deserialize),
+    serde(default, deny_unknown_fields)
+)]
+pub struct ConnectionConfig {
+
+    /// Maximum bytes that can be received / send in one packet.
+    pub packet_max_size: usize,
+
+    /// 32-Bit Protocol ID used to identify UDP related packets.
+    pub protocol_header: [u8; 4],
+
+    /// Whether packets carry a type byte following their header.
+    pub packet_types: bool,
+
+    /// Whether data packets carry the sender's tick number.
+    pub packet_tick_numbers: bool,
+
+    /// Pre-shared key from which the packet keys are derived.
+    #[cfg(feature = "encryption")]
+    pub encryption_key: Option<[u8; 32]>,
+
+    /// Number of milliseconds after which the packet key is rotated.
+    #[cfg(feature = "encryption")]
+    pub key_rotation_interval: u32,
+
+    /// Number of packets after which the packet key is rotated.
+    #[cfg(feature = "encryption")]
+    pub key_rotation_packets: u32,
+
+    /// Pre-shared key with which all packets are authenticated.
+    #[cfg(feature = "authentication")]
+    pub authentication_key: Option<[u8; 32]>,
+
+    /// Whether an ephemeral X25519 key exchange is performed.
+    #[cfg(feature = "key_exchange")]
+    pub key_exchange: bool,
+
+    /// Whether packets are encrypted with the exchanged key.
+    #[cfg(feature = "key_exchange")]
+    pub key_exchange_encryption: bool,
+
+    /// Whether only authenticated closure packets are accepted.
+    pub authenticated_close: bool,
+
+    /// Maximum roundtrip-time in milliseconds before a packet is considered
+    /// lost.
+    pub packet_drop_threshold: u32,
+
+    /// Maximum time in milliseconds a packet may remain unacknowledged.
+    pub retransmit_timeout_ms: u32,
+
+    /// Whether received data is acknowledged right away.
+    pub immediate_acks: bool,
+
+    /// Maximum number of packets acknowledged by a single ack only packet.
+    pub ack_coalesce_packets: u32,
+
+    /// Maximum time in milliseconds an acknowledgment may be delayed.
+    pub ack_coalesce_ms: u32,
+
+    /// Maximum time in milliseconds until the first packet must be received.
+    pub connection_init_threshold: u32,
+
+    /// Minimum time in milliseconds between packets send while connecting.
+    pub handshake_retry_interval: u32,
+
+    /// Maximum number of packets send while connecting.
+    pub handshake_max_attempts: u32,
+
+    /// Maximum time in milliseconds between any two packets.
+    pub connection_drop_threshold: u32,
+
+    /// Maximum time in milliseconds between any two received messages.
+    pub idle_timeout: u32,
+
+    /// Minimum size in bytes of packets sent while connecting.
+    pub handshake_min_size: usize,
+
+    /// Maximum multiple of the received bytes sent to unvalidated addresses.
+    pub amplification_factor: u32,
+
+    /// Whether early data attached to connection requests is accepted.
+    pub early_data: bool,
+
+    /// Percent of packet bytes used for `MessageKind::Instant`.
+    pub message_quota_instant: f32,
+
+    /// Percent of packet bytes used for `MessageKind::Reliable`.
+    pub message_quota_reliable: f32,
+
+    /// Percent of packet bytes used for `MessageKind::Ordered`.
+    pub message_quota_ordered: f32,
+
+    /// Maximum number of messages waiting in each send queue.
+    pub message_queue_limit: usize,
+
+    /// Maximum number of messages held back until the connection is
+    /// established.
+    pub connecting_message_limit: usize,
+
+    /// Maximum milliseconds a connection is kept alive on request of its
+    /// remote.
+    pub max_keep_alive_extension: u32,
+
+    /// How messages send over a connection which is no longer open are handled.
+    pub closed_send_policy: ClosedSendPolicy,
+
+    /// Whether address changes must be validated before packets follow them.
+    pub migration_validation: bool
+
+}
+
+impl Default for ConnectionConfig {
+
+    fn default() -> ConnectionConfig {
+        Config::default().connection()
+    }
+
+}
+
+impl From<Config> for ConnectionConfig {
+
+    fn from(config: Config) -> ConnectionConfig {
+        config.connection()
+    }
+
+}
+
+/// Structure defining the subset of `Config` options which control a `Server`
+/// or `Client` as a whole, like its send rate, tick timing and the admission
+/// of new connections.
+///
+/// See `Config` for a detailed description of each field.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(
+    feature = "serialization",
+    derive(Serialize, Deserialize),
+    serde(default, deny_unknown_fields)
+)]
+pub struct EndpointConfig {
+
+    /// Number of packets send per second.
+    pub send_rate: u32,
+
+    /// Whether packets with a different protocol header are answered.
+    pub protocol_mismatch_response: bool,
+
+    /// Whether new connections must echo a signed cookie first.
+    #[cfg(feature = "authentication")]
+    pub handshake_cookies: bool,
+
+    /// Private key with which server info responses are signed.
+    #[cfg(feature = "authentication")]
+    pub server_info_signing_key: Option<[u8; 32]>,
+
+    /// Public key with which server info responses are verified.
+    #[cfg(feature = "authentication")]
+    pub server_info_public_key: Option<[u8; 32]>,
+
+    /// Private key with which `ConnectToken`s are verified.
+    #[cfg(feature = "connect_token")]
+    pub connect_token_key: Option<[u8; 32]>,
+
+    /// Maximum packets per second accepted from an unknown IP address.
+    pub unknown_address_packet_limit: u32,
+
+    /// Number of packets per second accepted from any single IP address.
+    pub flood_packet_rate: u32,
+
+    /// Maximum number of packets accepted from any single IP address in a
+    /// single burst.
+    pub flood_packet_burst: u32,
+
+    /// Maximum number of new connections created per second.
+    pub handshake_limit: u32,
+
+    /// Whether only explicitly allowed sources may create connections.
+    pub whitelist_only: bool,
+
+    /// Maximum number of connections kept for any single IP address.
+    pub max_connections_per_ip: u32,
+
+    /// Maximum number of connections kept in total.
+    pub max_connections: u32,
+
+    /// Maximum time in milliseconds unacknowledged connections are kept.
+    pub pending_connection_timeout: u32,
+
+    /// Maximum number of unacknowledged connections kept.
+    pub max_pending_connections: u32,
+
+    /// Number of worker threads across which connections are processed.
+    pub worker_threads: u32,
+
+    /// Maximum number of bytes per second send across all connections.
+    pub max_outgoing_bandwidth: u32,
+
+    /// Order in which the packets of all connections are send each tick.
+    pub send_order: SendOrder,
+
+    /// Whether ticks exceeding their running time are caught up with.
+    pub tick_overflow_recovery: bool,
+
+    /// How much of each tick's sleep time may be used to catch up.
+    pub tick_overflow_recovery_rate: f32,
+
+    /// Time in milliseconds at the end of each tick spend spinning.
+    pub tick_spin_threshold: u32,
+
+    /// Maximum time in milliseconds during which lost connections may be
+    /// resumed.
+    pub resumption_threshold: u32,
+
+    /// Private key with which resumption tickets are sealed.
+    #[cfg(feature = "encryption")]
+    pub resumption_ticket_key: Option<[u8; 32]>,
+
+    /// Number of seconds for which resumption tickets remain valid.
+    #[cfg(feature = "encryption")]
+    pub resumption_ticket_lifetime: u32,
+
+    /// Whether unacknowledged messages are retained for resumption.
+    pub resumption_retain_messages: bool,
+
+    /// How packets for known ids from different addresses are handled.
+    pub address_change_policy: AddressChangePolicy,
+
+    /// How peers reusing the ids of established connections are handled.
+    pub id_collision_policy: IdCollisionPolicy,
+
+    /// Time in milliseconds between attempts to connect to the resolved
+    /// addresses of a host name.
+    pub connect_attempt_delay: u32,
+
+    /// Simulated delay in milliseconds of each packet of a client.
+    #[cfg(feature = "network_simulator")]
+    pub simulated_latency: u32,
+
+    /// Simulated maximum extra delay in milliseconds of each packet of a
+    /// client.
+    #[cfg(feature = "network_simulator")]
+    pub simulated_jitter: u32,
+
+    /// Simulated percentage of the packets of a client which are dropped.
+    #[cfg(feature = "network_simulator")]
+    pub simulated_packet_loss: f32,
+
+    /// Simulated percentage of the packets of a client which are duplicated.
+    #[cfg(feature = "network_simulator")]
+    pub simulated_packet_duplication: f32,
+
+    /// Whether peers are shared with each connection for host migration.
+    pub host_migration: bool
+
+}
+
+impl Default for EndpointConfig {
+
+    fn default() -> EndpointConfig {
+        Config::default().endpoint()
+    }
+
+}
+
+impl From<Config> for EndpointConfig {
+
+    fn from(config: Config) -> EndpointConfig {
+        config.endpoint()
+    }
+
+}
+
+impl From<(ConnectionConfig, EndpointConfig)> for Config {
+
+    fn from((connection, endpoint): (ConnectionConfig, EndpointConfig)) -> Config {
+        Config::from_parts(connection, endpoint)
+    }
+
+}
+
+// Static Helpers -------------------------------------------------------------
+fn invalid_field(name: &str, reason: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, format!("{}: {}", name, reason))
 }
 
@@ -0,0 +1,133 @@
+// Copyright (c) 2015-2016 Ivo Wetzel
+
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use super::encryption::PacketCipher;
+use super::super::ConnectionID;
+
+/// A short-lived token issued by a backend, which allows a client to connect
+/// to a `Server` configured with the same `Config::connect_token_key`.
+///
+/// Tokens are sealed with the private key shared by the backend and the
+/// server, so clients can neither read nor forge them. The id of the
+/// connection the token was issued for is the only part which is kept in
+/// plain text, allowing clients to connect with it.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+/// use cobalt::{ConnectionID, ConnectToken};
+///
+/// let key = [7; 32];
+/// let token = ConnectToken::new(
+///     ConnectionID(1234), Duration::from_secs(30), b"user".to_vec()
+/// );
+///
+/// let sealed = token.seal(key);
+/// assert_eq!(ConnectToken::verify(key, ConnectionID(1234), &sealed), Some(token));
+/// assert_eq!(ConnectToken::verify(key, ConnectionID(5678), &sealed), None);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConnectToken {
+
+    /// The id of the connection the token was issued for.
+    pub id: ConnectionID,
+
+    /// The time the token expires at, in seconds since the unix epoch.
+    pub expires: u64,
+
+    /// Arbitrary data attached by the backend, i.e. the id of the user the
+    /// token was issued to.
+    pub user_data: Vec<u8>
+
+}
+
+impl ConnectToken {
+
+    /// Creates a new token for the connection with the specified `id`, which
+    /// is `valid_for` the given duration.
+    pub fn new(id: ConnectionID, valid_for: Duration, user_data: Vec<u8>) -> ConnectToken {
+        ConnectToken {
+            id,
+            expires: (unix_time() + valid_for).as_secs(),
+            user_data
+        }
+    }
+
+    /// Returns whether the token has expired.
+    pub fn expired(&self) -> bool {
+        unix_time().as_secs() >= self.expires
+    }
+
+    /// Seals the token with the specified private `key`, returning its
+    /// binary representation which is handed to the client.
+    pub fn seal(&self, key: [u8; 32]) -> Vec<u8> {
+
+        let mut body = Vec::with_capacity(8 + self.user_data.len());
+        for i in 0..8 {
+            body.push((self.expires >> (56 - i * 8)) as u8);
+        }
+        body.extend_from_slice(&self.user_data);
+
+        PacketCipher::new(key).encrypt(&id_to_bytes(self.id), &body)
+
+    }
+
+    /// Returns the id of the connection a sealed token was issued for.
+    pub fn id_from_sealed(data: &[u8]) -> Option<ConnectionID> {
+        if data.len() >= 4 {
+            Some(ConnectionID(
+                (data[0] as u32) << 24 | (data[1] as u32) << 16 |
+                (data[2] as u32) << 8  |  data[3] as u32
+            ))
+
+        } else {
+            None
+        }
+    }
+
+    /// Opens a sealed token with the specified private `key`, returning it
+    /// only in case it was issued for the connection with the specified `id`
+    /// and has not yet expired.
+    pub fn verify(key: [u8; 32], id: ConnectionID, data: &[u8]) -> Option<ConnectToken> {
+
+        if ConnectToken::id_from_sealed(data) != Some(id) {
+            return None;
+        }
+
+        let opened = PacketCipher::new(key).decrypt(&data[..4], &data[4..])?;
+        if opened.len() < 12 {
+            return None;
+        }
+
+        let token = ConnectToken {
+            id,
+            expires: opened[4..12].iter().fold(0, |value, b| value << 8 | *b as u64),
+            user_data: opened[12..].to_vec()
+        };
+
+        if token.expired() {
+            None
+
+        } else {
+            Some(token)
+        }
+
+    }
+
+}
+
+// Static Helpers -------------------------------------------------------------
+fn unix_time() -> Duration {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default()
+}
+
+fn id_to_bytes(id: ConnectionID) -> [u8; 4] {
+    [(id.0 >> 24) as u8, (id.0 >> 16) as u8, (id.0 >> 8) as u8, id.0 as u8]
+}
+
@@ -6,15 +6,52 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 extern crate rand;
+#[cfg(feature = "serialization")]
+extern crate serde;
 
 use std::cmp;
+use std::mem;
+use std::io::{Error, ErrorKind};
 use std::net::SocketAddr;
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
+use std::collections::hash_map::RandomState;
 use std::collections::VecDeque;
 use std::time::{Duration, Instant};
-use super::message_queue::{MessageQueue, MessageIterator};
+use self::rand::{OsRng, Rng};
+use super::control::Control;
+use super::host_migration::{self, HostMigration, Peer};
+use super::message_queue::{
+    KindedMessageIterator, MessageQueue, MessageHandle, MessageIterator,
+    TickedMessageIterator, TimedMessageIterator, MESSAGE_HEADER_BYTES
+};
+use super::packet::{PacketType, TICK_NUMBER_BYTES, TICK_NUMBER_FLAG};
+use super::connection_rng::ConnectionRng;
+use super::rtt_histogram::RttHistogram;
+use super::state_dump::ConnectionStateDump;
+use super::stats::{PacketDropReason, SecurityStats};
+#[cfg(feature = "authentication")]
+use super::authentication::{self, AUTHENTICATION_OVERHEAD};
+#[cfg(feature = "authentication")]
+use super::handshake_cookie::COOKIE_BYTES;
+#[cfg(feature = "encryption")]
+use super::encryption::{
+    PacketCipher, ReplayWindow, ACCEPTED_NONCE_BIT, ENCRYPTION_OVERHEAD, RESUMPTION_NONCE_BYTES,
+    random_bytes, resumption_key
+};
+#[cfg(feature = "key_exchange")]
+use super::key_exchange::KeyExchange;
+#[cfg(feature = "connect_token")]
+use super::connect_token::ConnectToken;
+#[cfg(feature = "encryption")]
+use super::resumption_ticket::ResumptionTicket;
 use super::super::traits::socket::Socket;
-use super::super::{Config, MessageKind, Handler, RateLimiter};
+use super::super::{
+    ClosedSendPolicy, Config, IdGenerator, MessageKind, Handler, OsIdGenerator,
+    RateLimiter
+};
+
+#[cfg(feature = "serialization")]
+use self::serde::{Deserialize, Serialize};
 
 /// Maximum number of acknowledgement bits available in the packet header.
 const MAX_ACK_BITS: u32 = 32;
@@ -25,12 +62,59 @@ const MAX_SEQ_NUMBER: u32 = 256;
 /// Number of bytes used by a packet header.
 const PACKET_HEADER_SIZE: usize = 14;
 
-/// Special packet data used to notify of programmtic connection closure.
+/// Special packet data used to notify of programmtic connection closure when
+/// packets do not carry a type.
 const CLOSURE_PACKET_DATA: [u8; 6] = [
     0, 128, // Most distant sequence numbers
     85, 85, 85, 85 // ack bitfield with every second bit set
 ];
 
+/// Data following the remote's protocol header in packets notifying it of a
+/// protocol mismatch.
+const PROTOCOL_MISMATCH_MARKER: [u8; 4] = [255, 255, 255, 255];
+
+/// Size of packets notifying the remote of a protocol mismatch.
+const PROTOCOL_MISMATCH_SIZE: usize = 12;
+
+/// Marker which identifies packets denying a connection since the server is
+/// full.
+const SERVER_FULL_MARKER: [u8; 4] = [255, 255, 255, 252];
+
+/// Size of packets denying a connection since the server is full.
+const SERVER_FULL_SIZE: usize = 12;
+
+/// Marker which identifies server info queries.
+const SERVER_INFO_MARKER: [u8; 4] = [255, 255, 255, 253];
+
+/// Marker which identifies responses to server info queries.
+const SERVER_INFO_RESPONSE_MARKER: [u8; 4] = [255, 255, 255, 251];
+
+/// Size of server info queries, which are padded with zeros so their
+/// responses can be larger without amplifying spoofed queries.
+const SERVER_INFO_QUERY_SIZE: usize = 128;
+
+/// Size of the protocol header, marker and challenge of server info queries
+/// and responses.
+const SERVER_INFO_CHALLENGE_SIZE: usize = 16;
+
+/// Size of the fixed part of server info responses.
+const SERVER_INFO_HEADER_SIZE: usize = 17;
+
+/// Maximum size of server info responses, which limits their amplification
+/// to three times the size of a query.
+const SERVER_INFO_RESPONSE_MAX_SIZE: usize = SERVER_INFO_QUERY_SIZE * 3;
+
+/// Size of the signature appended to signed server info responses.
+const SERVER_INFO_SIGNATURE_SIZE: usize = 64;
+
+/// Marker which identifies packets carrying a handshake cookie.
+#[cfg(feature = "authentication")]
+const HANDSHAKE_COOKIE_MARKER: [u8; 4] = [255, 255, 255, 254];
+
+/// Size of packets carrying a handshake cookie.
+#[cfg(feature = "authentication")]
+pub const HANDSHAKE_COOKIE_SIZE: usize = 12 + COOKIE_BYTES;
+
 /// Enum indicating the state of a `SentPacketAck`.
 #[derive(Debug, PartialEq)]
 enum PacketState {
@@ -45,11 +129,13 @@ struct SentPacketAck {
     seq: u32,
     time: Instant,
     state: PacketState,
-    packet: Option<Vec<u8>>
+    packet: Option<Vec<u8>>,
+    handles: Vec<Option<MessageHandle>>
 }
 
 /// Enum indicating the state of a connection.
 #[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serialization", derive(Serialize, Deserialize))]
 pub enum ConnectionState {
 
     /// The connection has been opened but has yet to receive the first
@@ -76,6 +162,37 @@ pub enum ConnectionState {
 
 }
 
+impl ConnectionState {
+
+    /// Returns whether a connection may move from its current state into the
+    /// `next` state.
+    ///
+    /// `Lost`, `Closed` and `FailedToConnect` are final states, only a
+    /// `Connection::reset()` will return such a connection into the
+    /// `Connecting` state.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cobalt::ConnectionState;
+    ///
+    /// assert!(ConnectionState::Connecting.can_transition_to(ConnectionState::Connected));
+    /// assert!(!ConnectionState::Closed.can_transition_to(ConnectionState::Connected));
+    /// ```
+    pub fn can_transition_to(self, next: ConnectionState) -> bool {
+        matches!((self, next),
+            (ConnectionState::Connecting, ConnectionState::Connected) |
+            (ConnectionState::Connecting, ConnectionState::FailedToConnect) |
+            (ConnectionState::Connecting, ConnectionState::Closing) |
+            (ConnectionState::Connected, ConnectionState::Lost) |
+            (ConnectionState::Connected, ConnectionState::Closing) |
+            (ConnectionState::Connected, ConnectionState::Closed) |
+            (ConnectionState::Closing, ConnectionState::Closed)
+        )
+    }
+
+}
+
 /// Representation of a random ID for connection identification purposes.
 ///
 /// Used to uniquely\* identify the reliable connections. The ID is send with
@@ -84,16 +201,44 @@ pub enum ConnectionState {
 /// where NAT re-assigns local UDP ports which would cause purely address based
 /// packet identification mechanisms to break down.
 ///
+/// Client connections draw their ID from the `IdGenerator` returned by their
+/// handler, which uses the random number generator of the operating system
+/// by default, so IDs of live connections cannot be predicted.
+///
 /// > \* Since the ID is random integer, there is of course a always a chance
 /// > for two connections to end up with the same ID, in that case - due to
-/// conflicting ack sequences and message data - both connections will get
-/// dropped shortly.
+/// > conflicting ack sequences and message data - both connections will get
+/// > dropped shortly.
 #[derive(Debug, PartialEq, Eq, Hash, Copy, Clone)]
+#[cfg_attr(feature = "serialization", derive(Serialize, Deserialize))]
 pub struct ConnectionID(pub u32);
 
+/// Token issued by a server which allows a client to resume its connection
+/// after it was lost.
+///
+/// A client presenting a valid token within the configured
+/// `resumption_threshold` keeps its previous `ConnectionID` and is considered
+/// as connected with its very first packet, which means that any messages
+/// send along with it are delivered right away.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+#[cfg_attr(feature = "serialization", derive(Serialize, Deserialize))]
+pub struct ResumptionToken {
+
+    /// The id of the connection which can be resumed.
+    pub id: ConnectionID,
+
+    /// The random secret which must be presented in order to resume the
+    /// connection.
+    pub secret: u64
+
+}
 
 /// Type alias for connection mappings.
-pub type ConnectionMap = HashMap<ConnectionID, Connection>;
+///
+/// Uses the standard library's default hasher unless another one is
+/// specified, i.e. `ConnectionMap<FnvBuildHasher>` for maps which are
+/// maintained outside of the server.
+pub type ConnectionMap<S = RandomState> = HashMap<ConnectionID, Connection, S>;
 
 /// Implementation of a reliable, virtual connection logic.
 #[derive(Debug)]
@@ -120,12 +265,41 @@ pub struct Connection {
     /// The current, local sequence number
     local_seq_number: u32,
 
+    /// The protocol header used by the remote in case it rejected the
+    /// connection due to a protocol mismatch
+    remote_protocol_header: Option<[u8; 4]>,
+
+    /// Whether the remote rejected the connection since it was full
+    server_full: bool,
+
+    /// The reason code send along with the closure packets of the connection
+    close_reason: Option<u8>,
+
+    /// The reason code the remote closed the connection with
+    remote_close_reason: Option<u8>,
+
+    /// The local tick number which is send along with data packets
+    tick_number: u32,
+
     /// Exponentially smoothed moving average of the roundtrip time
     smoothed_rtt: f32,
 
+    /// Exponentially smoothed moving average of the roundtrip time's
+    /// deviation from its average
+    rtt_jitter: f32,
+
+    /// Histogram of all roundtrip time samples
+    rtt_histogram: RttHistogram,
+
+    /// Pseudo random number generator seeded from the connection id
+    rng: ConnectionRng,
+
     /// Last time a packet was received
     last_receive_time: Instant,
 
+    /// Last time a application message was received
+    last_message_time: Instant,
+
     /// Queue of recently received packets used for ack bitfield construction
     recv_ack_queue: VecDeque<u32>,
 
@@ -135,6 +309,13 @@ pub struct Connection {
     /// Number of all packets sent over the connection
     sent_packets: u32,
 
+    /// Number of packets sent while the connection was being established
+    handshake_attempts: u32,
+
+    /// Last time a packet was sent while the connection was being
+    /// established
+    last_handshake_time: Option<Instant>,
+
     /// Number of all packets received over the connection
     recv_packets: u32,
 
@@ -148,12 +329,204 @@ pub struct Connection {
     message_queue: MessageQueue,
 
     /// The rate limiter used to handle and avoid network congestion
-    rate_limiter: Box<RateLimiter>
+    rate_limiter: Box<dyn RateLimiter>,
+
+    /// The resumption token that was issued to or by this connection
+    resumption_token: Option<ResumptionToken>,
+
+    /// Whether the resumption token was issued by this end of the connection
+    resumption_issuer: bool,
+
+    /// Resumption secret presented to the remote while connecting
+    resumption_request: Option<u64>,
+
+    /// Early data attached to the connection request, retained in case the
+    /// remote rejects it
+    early_data: Option<Vec<u8>>,
+
+    /// Resumption secret accepted from the remote while connecting
+    resumption_expected: Option<u64>,
+
+    /// Messages which are re-send once the remote resumes the connection
+    resumption_messages: Vec<(MessageKind, Vec<u8>)>,
+
+    /// Whether the connection resumed a previously lost one
+    resumed: bool,
+
+    /// Number of unconsumed received bytes above which the connection is
+    /// under receive pressure
+    receive_watermark: usize,
+
+    /// Whether the unconsumed received data exceeds the receive watermark
+    receive_pressure: bool,
+
+    /// Maximum number of bytes per second send over the connection
+    bandwidth_limit: u32,
+
+    /// Application defined tags of the connection
+    tags: BTreeSet<String>,
+
+    /// Number of bytes which may currently be send under the bandwidth limit
+    /// along with the time it was last refilled
+    bandwidth_allowance: (f64, Instant),
+
+    /// Maximum number of bytes of the next packet, as allotted by the
+    /// outgoing bandwidth budget of a server
+    send_budget: Option<usize>,
+
+    /// Sequence number of the last receive pressure advertisement
+    receive_pressure_seq: u8,
+
+    /// Receive pressure and sequence number last advertised by the remote
+    remote_receive_pressure: (u8, bool),
+
+    /// Time until which the connection is kept alive without receiving any
+    /// packets, as negotiated with its remote
+    keep_alive_until: Option<Instant>,
+
+    /// The migration secret that was issued to or by this connection
+    migration_secret: Option<[u8; 32]>,
+
+    /// Address the remote is attempting to migrate to along with the nonce
+    /// of the challenge send to it
+    migration_candidate: Option<(SocketAddr, u64)>,
+
+    /// Migration candidate which answered its challenge with an invalid
+    /// proof
+    failed_migration: Option<SocketAddr>,
+
+    /// Kind of the last error the socket reported when sending a packet
+    send_error: Option<ErrorKind>,
+
+    /// Peers of the session hosted by the remote
+    peers: Vec<Peer>,
+
+    /// Latest session state shared by the remote
+    session_state: Option<Vec<u8>>,
+
+    /// Number of received packets with data still awaiting their
+    /// acknowledgment
+    ack_pending: u32,
+
+    /// Time the oldest packet awaiting its acknowledgment was received
+    ack_pending_time: Instant,
+
+    /// Whether the remote address must be validated before sending more
+    /// than `Config::amplification_factor` times the bytes received from it
+    address_validation: bool,
+    acknowledged: bool,
+
+    /// Bytes received from and send to the remote address while it has not
+    /// yet been validated
+    unvalidated_bytes: Option<(usize, usize)>,
+
+    /// The cipher used to encrypt and authenticate all packets
+    #[cfg(feature = "encryption")]
+    cipher: Option<PacketCipher>,
+
+    /// The number of times the packet key was rotated
+    #[cfg(feature = "encryption")]
+    key_epoch: u32,
+
+    /// The cipher in use before the packet key was first exchanged or
+    /// rotated, which is restored when the connection is reset
+    #[cfg(feature = "encryption")]
+    unrotated_cipher: Option<PacketCipher>,
+
+    /// The cipher of the next key epoch until the remote starts to encrypt
+    /// its packets with it
+    #[cfg(feature = "encryption")]
+    rotated_cipher: Option<PacketCipher>,
+
+    /// Handle of the message announcing the next key epoch until it was
+    /// acknowledged by the remote
+    #[cfg(feature = "encryption")]
+    key_rotation_handle: Option<MessageHandle>,
+
+    /// Time at which the current packet key came into use
+    #[cfg(feature = "encryption")]
+    key_rotation_time: Instant,
+
+    /// Number of packets send with the current packet key
+    #[cfg(feature = "encryption")]
+    key_rotation_packets: u32,
+
+    /// Counter of the last nonce used to encrypt a packet
+    #[cfg(feature = "encryption")]
+    nonce_counter: u64,
+
+    /// Whether the connection was accepted from the remote rather than
+    /// initiated by it, which sets the `ACCEPTED_NONCE_BIT` of its nonces
+    #[cfg(feature = "encryption")]
+    accepted: bool,
+
+    /// Window over the nonce counters of the packets received from the
+    /// remote
+    #[cfg(feature = "encryption")]
+    replay_window: ReplayWindow,
+
+    /// Number of authenticated packets which were dropped as replays
+    #[cfg(feature = "encryption")]
+    replayed_packets: u32,
+
+    /// Number of received packets which were dropped by cause
+    security_stats: SecurityStats,
+
+    /// The pending ephemeral key exchange with the remote
+    #[cfg(feature = "key_exchange")]
+    key_exchange: Option<KeyExchange>,
+
+    /// The secret derived from the completed key exchange
+    #[cfg(feature = "key_exchange")]
+    session_key: Option<[u8; 32]>,
+
+    /// Handle of the message carrying the local public key or resumption
+    /// nonce until it was acknowledged by the remote
+    #[cfg(feature = "encryption")]
+    key_exchange_handle: Option<MessageHandle>,
+
+    /// The cipher derived from the key exchange or the resumed session until
+    /// the remote starts to encrypt its packets with it
+    #[cfg(feature = "encryption")]
+    exchange_cipher: Option<PacketCipher>,
+
+    /// The secret of the session restored from a resumption ticket and the
+    /// local nonce, until the nonce of the remote arrives
+    #[cfg(feature = "encryption")]
+    resumption_secret: Option<([u8; 32], [u8; RESUMPTION_NONCE_BYTES])>,
+
+    /// The handshake cookie issued by the remote, which is echoed while
+    /// connecting
+    #[cfg(feature = "authentication")]
+    handshake_cookie: Option<Vec<u8>>,
+
+    /// The resumption ticket that was issued to or by this connection
+    #[cfg(feature = "encryption")]
+    resumption_ticket: Option<ResumptionTicket>,
+
+    /// The sealed resumption ticket presented to the remote while connecting
+    #[cfg(feature = "encryption")]
+    resumption_ticket_request: Option<Vec<u8>>,
+
+    /// The sealed connect token presented to the remote while connecting
+    #[cfg(feature = "connect_token")]
+    connect_token_request: Option<Vec<u8>>,
+
+    /// The connect token presented by the remote
+    #[cfg(feature = "connect_token")]
+    connect_token: Option<ConnectToken>
 
 }
 
 impl Connection {
 
+    /// The reason code with which connections are closed once they exceed
+    /// the configured `idle_timeout`.
+    ///
+    /// Applications should not use this code for their own closures, so
+    /// remotes can tell idle kicks apart.
+    pub const IDLE_TIMEOUT_REASON: u8 = 255;
+
     /// Creates a new Virtual Connection over the given `SocketAddr`.
     ///
     /// # Examples
@@ -175,28 +548,114 @@ impl Connection {
         config: Config,
         local_addr: SocketAddr,
         peer_addr: SocketAddr,
-        rate_limiter: Box<RateLimiter>
+        rate_limiter: Box<dyn RateLimiter>
 
     ) -> Connection {
-        Connection {
-            config: config,
-            random_id: ConnectionID(rand::random()),
+        let random_id = OsIdGenerator::new().generate();
+        let mut conn = Connection {
+            config,
+            random_id,
             state: ConnectionState::Connecting,
             local_address: local_addr,
             peer_address: peer_addr,
             local_seq_number: 0,
             remote_seq_number: 0,
+            tick_number: 0,
+            remote_protocol_header: None,
+            server_full: false,
+            close_reason: None,
+            remote_close_reason: None,
             smoothed_rtt: 0.0,
+            rtt_jitter: 0.0,
+            rtt_histogram: RttHistogram::new(),
+            rng: ConnectionRng::new(random_id),
             last_receive_time: Instant::now(),
+            last_message_time: Instant::now(),
             recv_ack_queue: VecDeque::new(),
             sent_ack_queue: Vec::new(),
             sent_packets: 0,
+            handshake_attempts: 0,
+            last_handshake_time: None,
             recv_packets: 0,
             acked_packets: 0,
             lost_packets: 0,
             message_queue: MessageQueue::new(config),
-            rate_limiter: rate_limiter
-        }
+            rate_limiter,
+            resumption_token: None,
+            resumption_issuer: false,
+            resumption_request: None,
+            early_data: None,
+            resumption_expected: None,
+            resumption_messages: Vec::new(),
+            resumed: false,
+            receive_watermark: 0,
+            receive_pressure: false,
+            bandwidth_limit: 0,
+            bandwidth_allowance: (0.0, Instant::now()),
+            send_budget: None,
+            tags: BTreeSet::new(),
+            receive_pressure_seq: 0,
+            remote_receive_pressure: (0, false),
+            keep_alive_until: None,
+            migration_secret: None,
+            migration_candidate: None,
+            failed_migration: None,
+            send_error: None,
+            peers: Vec::new(),
+            session_state: None,
+            ack_pending: 0,
+            ack_pending_time: Instant::now(),
+            address_validation: false,
+            acknowledged: false,
+            unvalidated_bytes: None,
+            #[cfg(feature = "encryption")]
+            cipher: config.encryption_key.map(|key| {
+                PacketCipher::derive(key, random_id)
+            }),
+            #[cfg(feature = "encryption")]
+            key_epoch: 0,
+            #[cfg(feature = "encryption")]
+            unrotated_cipher: None,
+            #[cfg(feature = "encryption")]
+            rotated_cipher: None,
+            #[cfg(feature = "encryption")]
+            key_rotation_handle: None,
+            #[cfg(feature = "encryption")]
+            key_rotation_time: Instant::now(),
+            #[cfg(feature = "encryption")]
+            key_rotation_packets: 0,
+            #[cfg(feature = "encryption")]
+            nonce_counter: 0,
+            #[cfg(feature = "encryption")]
+            accepted: false,
+            #[cfg(feature = "encryption")]
+            replay_window: ReplayWindow::new(),
+            #[cfg(feature = "encryption")]
+            replayed_packets: 0,
+            security_stats: SecurityStats::default(),
+            #[cfg(feature = "key_exchange")]
+            key_exchange: None,
+            #[cfg(feature = "key_exchange")]
+            session_key: None,
+            #[cfg(feature = "encryption")]
+            key_exchange_handle: None,
+            #[cfg(feature = "encryption")]
+            exchange_cipher: None,
+            #[cfg(feature = "encryption")]
+            resumption_secret: None,
+            #[cfg(feature = "authentication")]
+            handshake_cookie: None,
+            #[cfg(feature = "encryption")]
+            resumption_ticket: None,
+            #[cfg(feature = "encryption")]
+            resumption_ticket_request: None,
+            #[cfg(feature = "connect_token")]
+            connect_token_request: None,
+            #[cfg(feature = "connect_token")]
+            connect_token: None
+        };
+        conn.reset_key_exchange();
+        conn
     }
 
     /// Extracts a `ConnectionID` from packet with a valid protocol header.
@@ -224,7 +683,7 @@ impl Connection {
     /// assert!(conn_id == Some(ConnectionID(16909060)));
     /// ```
     pub fn id_from_packet(config: &Config, packet: &[u8]) -> Option<ConnectionID> {
-        if &packet[0..4] == &config.protocol_header {
+        if packet[0..4] == config.protocol_header {
             Some(ConnectionID(
                 (packet[4] as u32) << 24 | (packet[5] as u32) << 16 |
                 (packet[6] as u32) << 8  |  packet[7] as u32
@@ -235,15 +694,289 @@ impl Connection {
         }
     }
 
+    /// Creates a packet notifying the sender of a packet with a different
+    /// protocol header of the mismatch.
+    ///
+    /// Returns `None` for packets which use the configured protocol header
+    /// or are too short to be a packet of any version.
+    pub fn protocol_mismatch_packet(config: &Config, packet: &[u8]) -> Option<Vec<u8>> {
+        if packet.len() < PACKET_HEADER_SIZE || packet[0..4] == config.protocol_header {
+            None
+
+        } else {
+            let mut response = Vec::with_capacity(PROTOCOL_MISMATCH_SIZE);
+            response.extend_from_slice(&packet[0..4]);
+            response.extend_from_slice(&PROTOCOL_MISMATCH_MARKER);
+            response.extend_from_slice(&config.protocol_header);
+            Some(response)
+        }
+    }
+
+    /// Extracts the protocol header of the remote from a packet notifying of
+    /// a protocol mismatch.
+    pub fn protocol_mismatch_from_packet(config: &Config, packet: &[u8]) -> Option<[u8; 4]> {
+        if packet.len() == PROTOCOL_MISMATCH_SIZE
+            && packet[0..4] == config.protocol_header
+            && packet[4..8] == PROTOCOL_MISMATCH_MARKER {
+
+            let mut protocol_header = [0; 4];
+            protocol_header.copy_from_slice(&packet[8..12]);
+            Some(protocol_header)
+
+        } else {
+            None
+        }
+    }
+
+    /// Creates a packet denying the connection `id` since the server is
+    /// full.
+    pub fn server_full_packet(config: &Config, id: ConnectionID) -> Vec<u8> {
+        let mut packet = Vec::with_capacity(SERVER_FULL_SIZE);
+        packet.extend_from_slice(&config.protocol_header);
+        packet.extend_from_slice(&SERVER_FULL_MARKER);
+        packet.push((id.0 >> 24) as u8);
+        packet.push((id.0 >> 16) as u8);
+        packet.push((id.0 >> 8) as u8);
+        packet.push(id.0 as u8);
+        packet
+    }
+
+    /// Extracts the id of the connection which is denied by a packet since
+    /// the server is full.
+    ///
+    /// Returns `None` for all other packets.
+    pub fn server_full_from_packet(config: &Config, packet: &[u8]) -> Option<ConnectionID> {
+        if packet.len() == SERVER_FULL_SIZE
+            && packet[0..4] == config.protocol_header
+            && packet[4..8] == SERVER_FULL_MARKER {
+
+            Some(ConnectionID(
+                (packet[8] as u32) << 24 | (packet[9] as u32) << 16 |
+                (packet[10] as u32) << 8  |  packet[11] as u32
+            ))
+
+        } else {
+            None
+        }
+    }
+
+    /// Creates a query for the info of a server, which is answered without
+    /// establishing a connection.
+    ///
+    /// The `challenge` is echoed by the response, so only responses to the
+    /// latest query are accepted. Queries are padded to a fixed size of `128`
+    /// bytes, limiting their responses to three times that size.
+    pub fn server_info_query_packet(config: &Config, challenge: u64) -> Vec<u8> {
+        let mut packet = Vec::with_capacity(SERVER_INFO_QUERY_SIZE);
+        packet.extend_from_slice(&config.protocol_header);
+        packet.extend_from_slice(&SERVER_INFO_MARKER);
+        for i in 0..8 {
+            packet.push((challenge >> (56 - i * 8)) as u8);
+        }
+        packet.resize(SERVER_INFO_QUERY_SIZE, 0);
+        packet
+    }
+
+    /// Extracts the challenge of a server info query.
+    ///
+    /// Returns `None` in case the packet is not a query or is not padded to
+    /// the full query size.
+    pub fn server_info_query_from_packet(config: &Config, packet: &[u8]) -> Option<u64> {
+        if packet.len() == SERVER_INFO_QUERY_SIZE
+            && packet[SERVER_INFO_CHALLENGE_SIZE..].iter().all(|b| *b == 0) {
+            Connection::server_info_challenge(config, SERVER_INFO_MARKER, packet)
+
+        } else {
+            None
+        }
+    }
+
+    /// Creates the response to the server info query with the specified
+    /// `challenge`, which carries the specified `info`.
+    ///
+    /// The response is signed in case `Config::server_info_signing_key` is
+    /// set.
+    ///
+    /// Returns `None` in case the response would exceed `384` bytes, i.e.
+    /// three times the size of the query, which leaves room for `367` bytes
+    /// of info or `303` bytes when signed.
+    pub fn server_info_packet(config: &Config, challenge: u64, info: &[u8]) -> Option<Vec<u8>> {
+
+        let size = SERVER_INFO_HEADER_SIZE + info.len() + if Connection::server_info_signed(config) {
+            SERVER_INFO_SIGNATURE_SIZE
+
+        } else {
+            0
+        };
+
+        if size > SERVER_INFO_RESPONSE_MAX_SIZE {
+            return None;
+        }
+
+        let mut packet = Connection::server_info_query_packet(config, challenge);
+        packet.truncate(SERVER_INFO_CHALLENGE_SIZE);
+        packet[4..8].copy_from_slice(&SERVER_INFO_RESPONSE_MARKER);
+        packet.push(Connection::server_info_signed(config) as u8);
+        packet.extend_from_slice(info);
+        Connection::sign_server_info(config, &mut packet);
+        Some(packet)
+
+    }
+
+    /// Extracts the info from the response to the server info query with the
+    /// specified `challenge`.
+    ///
+    /// Returns `None` in case the packet is not a response to the query. With
+    /// `Config::server_info_public_key` set, `None` is also returned for
+    /// responses which are unsigned or fail to verify; otherwise signatures
+    /// are stripped without being verified.
+    pub fn server_info_from_packet(config: &Config, challenge: u64, packet: &[u8]) -> Option<Vec<u8>> {
+
+        if packet.len() < SERVER_INFO_HEADER_SIZE
+            || Connection::server_info_challenge(config, SERVER_INFO_RESPONSE_MARKER, packet) != Some(challenge) {
+            return None;
+        }
+
+        let mut info = packet.to_vec();
+        let signed = match info[SERVER_INFO_CHALLENGE_SIZE] {
+            0 => false,
+            1 => true,
+            _ => return None
+        };
+
+        if !Connection::verify_server_info(config, signed, &mut info) {
+            return None;
+        }
+
+        Some(info.split_off(SERVER_INFO_HEADER_SIZE))
+
+    }
+
+    /// Returns the public key which verifies the server info responses
+    /// signed with the private `signing_key`, for use as
+    /// `Config::server_info_public_key`.
+    ///
+    /// > Note: This method is feature-gated and will only be included when
+    /// > the `authentication` feature is enabled.
+    #[cfg(feature = "authentication")]
+    pub fn server_info_public_key(signing_key: &[u8; 32]) -> [u8; 32] {
+        authentication::signing_public_key(signing_key)
+    }
+
+    /// Verifies the authentication tag of a packet received from the remote,
+    /// returning the packet without its tag.
+    ///
+    /// Returns `None` in case the packet failed to authenticate. Packets are
+    /// returned as is when `Config::authentication_key` is not set.
+    ///
+    /// > Note: This method is feature-gated and will only be included when the
+    /// > `authentication` feature is enabled.
+    #[cfg(feature = "authentication")]
+    pub fn authenticate_packet(config: &Config, mut packet: Vec<u8>) -> Option<Vec<u8>> {
+        match config.authentication_key {
+            Some(key) => if authentication::verify(&key, &mut packet) {
+                Some(packet)
+
+            } else {
+                None
+            },
+            None => Some(packet)
+        }
+    }
+
+    #[cfg(not(feature = "authentication"))]
+    pub(crate) fn authenticate_packet(_: &Config, packet: Vec<u8>) -> Option<Vec<u8>> {
+        Some(packet)
+    }
+
+    /// Extracts the sealed connect token presented with a packet.
+    ///
+    /// Returns `None` in case the packet does not contain a token.
+    ///
+    /// > Note: This method is feature-gated and will only be included when the
+    /// > `connect_token` feature is enabled.
+    #[cfg(feature = "connect_token")]
+    pub fn connect_token_from_packet(config: &Config, packet: &[u8]) -> Option<Vec<u8>> {
+        Connection::control_from_packet(config, packet).into_iter().filter_map(|control| {
+            match control {
+                Control::ConnectToken { data } => Some(data),
+                _ => None
+            }
+
+        }).next()
+    }
+
+    /// Extracts the sealed resumption ticket presented with a packet.
+    ///
+    /// Returns `None` in case the packet does not contain a ticket.
+    ///
+    /// > Note: This method is feature-gated and will only be included when the
+    /// > `encryption` feature is enabled.
+    #[cfg(feature = "encryption")]
+    pub fn resumption_ticket_from_packet(config: &Config, packet: &[u8]) -> Option<Vec<u8>> {
+        Connection::control_from_packet(config, packet).into_iter().filter_map(|control| {
+            match control {
+                Control::ResumeTicket { data, .. } => Some(data),
+                _ => None
+            }
+
+        }).next()
+    }
+
+    /// Creates a packet which carries the handshake `cookie` a server issued
+    /// for the connection `id`, in response to its connection request.
+    ///
+    /// > Note: This method is feature-gated and will only be included when the
+    /// > `authentication` feature is enabled.
+    #[cfg(feature = "authentication")]
+    pub fn handshake_cookie_packet(config: &Config, id: ConnectionID, cookie: &[u8]) -> Vec<u8> {
+        let mut packet = Vec::with_capacity(HANDSHAKE_COOKIE_SIZE);
+        packet.extend_from_slice(&config.protocol_header);
+        packet.extend_from_slice(&HANDSHAKE_COOKIE_MARKER);
+        packet.push((id.0 >> 24) as u8);
+        packet.push((id.0 >> 16) as u8);
+        packet.push((id.0 >> 8) as u8);
+        packet.push(id.0 as u8);
+        packet.extend_from_slice(cookie);
+        packet
+    }
+
+    /// Extracts the handshake cookie echoed with a packet.
+    ///
+    /// Returns `None` in case the packet does not contain a cookie.
+    ///
+    /// > Note: This method is feature-gated and will only be included when the
+    /// > `authentication` feature is enabled.
+    #[cfg(feature = "authentication")]
+    pub fn handshake_cookie_from_packet(config: &Config, packet: &[u8]) -> Option<Vec<u8>> {
+        Connection::control_from_packet(config, packet).into_iter().filter_map(|control| {
+            match control {
+                Control::HandshakeCookie { data } => Some(data),
+                _ => None
+            }
+
+        }).next()
+    }
+
     /// Returns whether the connection is currently accepting any incoming
     /// packets.
     pub fn open(&self) -> bool {
-        match self.state {
+        matches!(
+            self.state,
             ConnectionState::Closing |
             ConnectionState::Connecting |
-            ConnectionState::Connected => true,
-            _ => false
-        }
+            ConnectionState::Connected
+        )
+    }
+
+    /// Returns the connection's pseudo random number generator, which is
+    /// seeded from its id.
+    ///
+    /// Both ends of a connection produce the same sequence of values as long
+    /// as they draw the same number of values from it. The generator starts
+    /// over whenever the id of the connection changes or it is reset.
+    pub fn rng(&mut self) -> &mut ConnectionRng {
+        &mut self.rng
     }
 
     /// Returns whether the connection is currently congested and should be
@@ -260,6 +993,58 @@ impl Connection {
     /// Overrides the id of the connection.
     pub fn set_id(&mut self, id: ConnectionID) {
         self.random_id = id;
+        self.rng = ConnectionRng::new(id);
+
+        // Derive the key for the new id
+        #[cfg(feature = "encryption")]
+        {
+            self.reset_key_rotation();
+            if let Some(key) = self.config.encryption_key {
+                self.cipher = Some(PacketCipher::derive(key, id));
+            }
+        }
+
+    }
+
+    /// Returns the protocol header used by the remote in case it rejected
+    /// the connection attempt due to a protocol mismatch.
+    pub fn remote_protocol_header(&self) -> Option<[u8; 4]> {
+        self.remote_protocol_header
+    }
+
+    /// Fails a connection attempt which the remote rejected due to a
+    /// mismatch with its `protocol_header`, which is passed in as well.
+    ///
+    /// Does nothing unless the connection is still connecting.
+    pub fn receive_protocol_mismatch<O>(
+        &mut self, protocol_header: [u8; 4],
+        owner: &mut O, handler: &mut dyn Handler<O>
+    ) {
+        if self.state == ConnectionState::Connecting {
+            self.remote_protocol_header = Some(protocol_header);
+            self.set_state(ConnectionState::FailedToConnect);
+            handler.connection_failed(owner, self);
+        }
+    }
+
+    /// Returns whether the remote rejected the connection attempt since it
+    /// already reached its `Config::max_connections`.
+    pub fn server_full(&self) -> bool {
+        self.server_full
+    }
+
+    /// Fails a connection attempt which the remote rejected since it was
+    /// full.
+    ///
+    /// Does nothing unless the connection is still connecting.
+    pub fn receive_server_full<O>(
+        &mut self, owner: &mut O, handler: &mut dyn Handler<O>
+    ) {
+        if self.state == ConnectionState::Connecting {
+            self.server_full = true;
+            self.set_state(ConnectionState::FailedToConnect);
+            handler.connection_failed(owner, self);
+        }
     }
 
     /// Returns the current state of the connection.
@@ -272,6 +1057,48 @@ impl Connection {
         self.smoothed_rtt.ceil() as u32
     }
 
+    /// Returns the roundtrip time in milliseconds below or at which the
+    /// percentage `p` of all roundtrip times measured over the connection
+    /// fall.
+    ///
+    /// Unlike the smoothed average returned by `rtt()`, this exposes the
+    /// tail latency of the connection, i.e. `rtt_percentile(99.0)`. Returns
+    /// `0` until the first packet was acknowledged.
+    pub fn rtt_percentile(&self, p: f32) -> u32 {
+        self.rtt_histogram.percentile(p)
+    }
+
+    /// Returns the average deviation of the roundtrip times measured over
+    /// the connection from their average in milliseconds.
+    pub fn rtt_jitter(&self) -> u32 {
+        self.rtt_jitter.ceil() as u32
+    }
+
+    /// Returns a snapshot of the connection's public state, statistics and
+    /// queue depths.
+    pub fn dump_state(&self) -> ConnectionStateDump {
+        ConnectionStateDump {
+            id: self.id(),
+            state: self.state,
+            local_addr: self.local_address,
+            peer_addr: self.peer_address,
+            rtt: self.rtt(),
+            rtt_p99: self.rtt_percentile(99.0),
+            packet_loss: self.packet_loss(),
+            congested: self.congested(),
+            packets_sent: self.sent_packets,
+            packets_received: self.recv_packets,
+            packets_acked: self.acked_packets,
+            packets_lost: self.lost_packets,
+            packets_unacked: self.sent_ack_queue.len(),
+            queued_instant: self.message_queue.queued(MessageKind::Instant),
+            queued_reliable: self.message_queue.queued(MessageKind::Reliable),
+            queued_ordered: self.message_queue.queued(MessageKind::Ordered),
+            queued_delayed: self.message_queue.delayed(),
+            received: self.message_queue.received_len()
+        }
+    }
+
     /// Returns the percent of packets that were sent and never acknowledged
     /// over the total number of packets that have been send across the
     /// connection.
@@ -279,64 +1106,853 @@ impl Connection {
         100.0 / cmp::max(self.sent_packets, 1) as f32 * self.lost_packets as f32
     }
 
+    /// Returns the number of messages which were send over the connection,
+    /// excluding re-transmissions.
+    pub fn messages_sent(&self) -> u32 {
+        self.message_queue.sent_messages()
+    }
+
+    /// Returns the number of messages which were received over the
+    /// connection, excluding duplicates.
+    pub fn messages_received(&self) -> u32 {
+        self.message_queue.received_messages()
+    }
+
     /// Returns the socket address for the local end of this connection.
     pub fn local_addr(&self) -> SocketAddr {
         self.local_address
     }
 
+    pub(crate) fn set_local_addr(&mut self, addr: SocketAddr) {
+        self.local_address = addr;
+    }
+
+    /// Returns and clears the kind of the last error the socket reported
+    /// when sending one of the connection's packets, i.e. since the local
+    /// network interface went away.
+    pub fn take_send_error(&mut self) -> Option<ErrorKind> {
+        self.send_error.take()
+    }
+
+    pub(crate) fn set_send_error(&mut self, kind: ErrorKind) {
+        self.send_error = Some(kind);
+    }
+
     /// Returns the socket address for the remote end of this connection.
     pub fn peer_addr(&self) -> SocketAddr {
         self.peer_address
     }
 
     /// Sets the socket address of the remote peer of this connection.
+    ///
+    /// The new address needs to be validated again in case the connection
+    /// requires address validation.
     pub fn set_peer_addr(&mut self, peer_addr: SocketAddr) {
+        if peer_addr != self.peer_address && self.address_validation {
+            self.unvalidated_bytes = Some((0, 0));
+        }
         self.peer_address = peer_addr;
     }
 
+    /// Requires the remote address to be validated before the connection
+    /// sends more than `Config::amplification_factor` times the bytes it
+    /// received from it.
+    ///
+    /// The address is validated once the remote acknowledges any packet.
+    /// Packets exceeding the limit are dropped as if they were lost.
+    pub fn require_address_validation(&mut self) {
+        if self.config.amplification_factor > 0 {
+            self.address_validation = true;
+            self.unvalidated_bytes = Some((0, 0));
+        }
+    }
+
+    /// Returns whether the remote has acknowledged any of the packets send
+    /// over the connection, proving that it actually receives them.
+    pub fn acknowledged(&self) -> bool {
+        self.acknowledged
+    }
+
+    /// Returns a copy of the connection's current configuration.
+    pub fn config(&self) -> Config {
+        self.config
+    }
+
     /// Overrides the connection's existing configuration.
     pub fn set_config(&mut self, config: Config) {
         self.config = config;
         self.message_queue.set_config(config);
     }
 
-    /// Sends a message of the specified `kind` along with its `payload` over
-    /// the connection.
+    /// Overrides the key used to encrypt and authenticate all packets of the
+    /// connection, instead of the one derived from `Config::encryption_key`.
     ///
-    /// How exactly the message is send and whether it is guaranteed to be
-    /// delivered eventually is determined by its `MessageKind`.
-    pub fn send(&mut self, kind: MessageKind, payload: Vec<u8>) {
-        self.message_queue.send(kind, payload);
+    /// > Note: This method is feature-gated and will only be included when the
+    /// > `encryption` feature is enabled.
+    #[cfg(feature = "encryption")]
+    pub fn set_encryption_key(&mut self, key: [u8; 32]) {
+        self.reset_key_rotation();
+        self.cipher = Some(PacketCipher::new(key));
     }
 
-    /// Returns a consuming iterator over all messages received over this
-    /// connections.
-    pub fn received(&mut self) -> MessageIterator {
-        self.message_queue.received()
+    /// Marks the connection as accepted by a server rather than initiated by
+    /// a client.
+    ///
+    /// Both ends of a connection encrypt their packets with the same key, so
+    /// the nonces of the accepting end are kept apart from the ones of the
+    /// initiating end. Each end drops packets carrying its own direction,
+    /// i.e. its own packets reflected back to it by an attacker.
+    ///
+    /// > Note: This method is feature-gated and will only be included when the
+    /// > `encryption` feature is enabled.
+    #[cfg(feature = "encryption")]
+    pub fn set_accepted(&mut self) {
+        self.accepted = true;
     }
 
-    /// Receives a incoming UDP packet.
-    pub fn receive_packet<O>(
-        &mut self,
-        packet: Vec<u8>, tick_delay: u32,
-        owner: &mut O, handler: &mut Handler<O>
-    ) {
+    /// Returns the number of times the packet key of the connection was
+    /// rotated since it was established.
+    ///
+    /// > Note: This method is feature-gated and will only be included when the
+    /// > `encryption` feature is enabled.
+    #[cfg(feature = "encryption")]
+    pub fn key_epoch(&self) -> u32 {
+        self.key_epoch
+    }
 
-        // Ignore any packets shorter then the header length
-        if packet.len() < PACKET_HEADER_SIZE {
-            return;
+    /// Starts rotating the packet key of the connection, announcing the next
+    /// key epoch to the remote.
+    ///
+    /// The connection keeps using the current key until the remote has
+    /// acknowledged the announcement. Does nothing if the connection is not
+    /// encrypted or a rotation is already pending.
+    ///
+    /// > Note: This method is feature-gated and will only be included when the
+    /// > `encryption` feature is enabled.
+    #[cfg(feature = "encryption")]
+    pub fn rotate_key(&mut self) {
+        if self.rotated_cipher.is_none() && !self.key_exchange_pending() {
+            let epoch = self.key_epoch.wrapping_add(1);
+            if let Some(ref cipher) = self.cipher {
+                self.rotated_cipher = Some(cipher.rotate(epoch));
+                self.key_rotation_handle = Some(self.message_queue.send_control_tracked(
+                    Control::KeyRotation { epoch }.encode()
+                ));
+            }
         }
+    }
 
-        // Update connection state
-        if !self.update_receive_state(&packet, owner, handler) {
-            return;
-        }
+    /// Returns the number of authenticated packets which were dropped because
+    /// their nonce had either been received before or fell too far behind
+    /// the nonces of the most recent packets.
+    ///
+    /// > Note: This method is feature-gated and will only be included when the
+    /// > `encryption` feature is enabled.
+    #[cfg(feature = "encryption")]
+    pub fn replayed_packets(&self) -> u32 {
+        self.replayed_packets
+    }
+
+    /// Returns the number of received packets which were dropped by the
+    /// connection for each cause, i.e. for failing to authenticate.
+    ///
+    /// For clients this also includes the packets which were dropped before
+    /// they reached the connection.
+    pub fn security_stats(&self) -> SecurityStats {
+        self.security_stats
+    }
+
+    /// Returns whether the packets of the connection are encrypted.
+    ///
+    /// > Note: This method is feature-gated and will only be included when the
+    /// > `encryption` feature is enabled.
+    #[cfg(feature = "encryption")]
+    pub fn encrypted(&self) -> bool {
+        self.send_cipher().is_some()
+    }
+
+    /// Returns the secret derived from the ephemeral key exchange with the
+    /// remote, which both ends of the connection share.
+    ///
+    /// Returns `None` if `Config::key_exchange` is disabled or the exchange
+    /// has not completed yet.
+    ///
+    /// > Note: This method is feature-gated and will only be included when the
+    /// > `key_exchange` feature is enabled.
+    #[cfg(feature = "key_exchange")]
+    pub fn session_key(&self) -> Option<[u8; 32]> {
+        self.session_key
+    }
+
+    /// Returns the resumption token which was issued for this connection, if
+    /// any.
+    pub fn resumption_token(&self) -> Option<ResumptionToken> {
+        self.resumption_token
+    }
+
+    /// Returns whether the connection resumed a previously lost connection
+    /// instead of establishing a new one.
+    pub fn resumed(&self) -> bool {
+        self.resumed
+    }
+
+    /// Issues a new resumption token for this connection and sends it to the
+    /// remote, replacing any previously issued token.
+    ///
+    /// The token is send in plain text unless the connection's packets are
+    /// encrypted, see `Config::resumption_threshold`.
+    pub fn issue_resumption_token(&mut self) -> ResumptionToken {
+
+        let token = ResumptionToken {
+            id: self.random_id,
+            secret: rand::random()
+        };
+
+        self.resumption_token = Some(token);
+        self.resumption_issuer = true;
+        self.message_queue.send_control(MessageKind::Reliable, Control::ResumptionToken {
+            secret: token.secret,
+            resumed: self.resumed
+
+        }.encode());
+
+        token
+
+    }
+
+    /// Presents the sealed connect `token` issued by a backend to the remote
+    /// while connecting, taking over the id the token was issued for.
+    ///
+    /// > Note: This method is feature-gated and will only be included when the
+    /// > `connect_token` feature is enabled.
+    #[cfg(feature = "connect_token")]
+    pub fn present_connect_token(&mut self, token: Vec<u8>) {
+        if let Some(id) = ConnectToken::id_from_sealed(&token) {
+            self.set_id(id);
+        }
+        self.connect_token_request = Some(token);
+    }
+
+    /// Returns the resumption ticket which was issued for this connection,
+    /// if any.
+    ///
+    /// > Note: This method is feature-gated and will only be included when the
+    /// > `encryption` feature is enabled.
+    #[cfg(feature = "encryption")]
+    pub fn resumption_ticket(&self) -> Option<ResumptionTicket> {
+        self.resumption_ticket.clone()
+    }
+
+    /// Issues a new resumption ticket for this connection, sealed with the
+    /// private `key` and `valid_for` the given duration, and sends it to the
+    /// remote.
+    ///
+    /// Returns `None` without issuing a ticket while the key exchange with
+    /// the remote has not yet completed.
+    ///
+    /// > Note: This method is feature-gated and will only be included when the
+    /// > `encryption` feature is enabled.
+    #[cfg(feature = "encryption")]
+    pub fn issue_resumption_ticket(
+        &mut self, key: [u8; 32], valid_for: Duration
+
+    ) -> Option<ResumptionTicket> {
+
+        if !self.session_established() {
+            return None;
+        }
+
+        let ticket = ResumptionTicket::issue(
+            key, self.random_id, valid_for, self.established_session_key()
+        );
+
+        self.resumption_ticket = Some(ticket.clone());
+        self.message_queue.send_control(MessageKind::Reliable, Control::ResumptionTicket {
+            resumed: self.resumed,
+            data: ticket.data.clone()
+
+        }.encode());
+
+        Some(ticket)
+
+    }
+
+    /// Attempts to resume the session of the `ticket` by taking over its id
+    /// and session key, presenting the ticket to the remote while connecting.
+    ///
+    /// The packets of the resumed session are encrypted with a fresh key
+    /// derived from the session key, or the `Config::encryption_key` for
+    /// tickets without one, and nonces contributed by both ends.
+    ///
+    /// In case the remote rejects the ticket, a new session is established
+    /// instead.
+    ///
+    /// > Note: This method is feature-gated and will only be included when the
+    /// > `encryption` feature is enabled.
+    #[cfg(feature = "encryption")]
+    pub fn resume_with_ticket(&mut self, ticket: ResumptionTicket) {
+        self.set_id(ticket.id);
+        self.start_resumption(ticket.session_key);
+        self.resumption_ticket_request = Some(ticket.data.clone());
+        self.resumption_ticket = Some(ticket);
+    }
+
+    /// Restores the session of a `ticket` which the remote presented while
+    /// connecting and which was verified via `ResumptionTicket::verify`.
+    ///
+    /// The key of the resumed session is derived once the remote's nonce
+    /// arrives along with the ticket, see `Connection::resume_with_ticket`.
+    ///
+    /// > Note: This method is feature-gated and will only be included when the
+    /// > `encryption` feature is enabled.
+    #[cfg(feature = "encryption")]
+    pub fn accept_resumption_ticket(&mut self, ticket: ResumptionTicket) {
+        if self.state == ConnectionState::Connecting {
+            self.start_resumption(ticket.session_key);
+            self.resumed = true;
+        }
+    }
+
+    /// Returns the verified connect token which was presented by the remote,
+    /// if any.
+    ///
+    /// > Note: This method is feature-gated and will only be included when the
+    /// > `connect_token` feature is enabled.
+    #[cfg(feature = "connect_token")]
+    pub fn connect_token(&self) -> Option<&ConnectToken> {
+        self.connect_token.as_ref()
+    }
+
+    /// Stores the verified connect `token` which was presented by the
+    /// remote.
+    ///
+    /// > Note: This method is feature-gated and will only be included when the
+    /// > `connect_token` feature is enabled.
+    #[cfg(feature = "connect_token")]
+    pub fn set_connect_token(&mut self, token: ConnectToken) {
+        self.connect_token = Some(token);
+    }
+
+    /// Stores the handshake cookie in case the `packet` was send by the
+    /// remote in response to the connection request, so the cookie is echoed
+    /// with all further packets until the connection is established.
+    ///
+    /// Returns the packet in case it does not carry a cookie.
+    ///
+    /// > Note: This method is feature-gated and will only be included when the
+    /// > `authentication` feature is enabled.
+    #[cfg(feature = "authentication")]
+    pub fn receive_handshake_cookie(&mut self, packet: Vec<u8>) -> Option<Vec<u8>> {
+        if self.state == ConnectionState::Connecting
+            && packet.len() == HANDSHAKE_COOKIE_SIZE
+            && packet[0..4] == self.config.protocol_header
+            && packet[4..8] == HANDSHAKE_COOKIE_MARKER
+            && packet[8..12] == [
+                (self.random_id.0 >> 24) as u8,
+                (self.random_id.0 >> 16) as u8,
+                (self.random_id.0 >> 8) as u8,
+                self.random_id.0 as u8
+            ] {
+
+            self.handshake_cookie = Some(packet[12..].to_vec());
+            None
+
+        } else {
+            Some(packet)
+        }
+    }
+
+    #[cfg(not(feature = "authentication"))]
+    pub(crate) fn receive_handshake_cookie(&mut self, packet: Vec<u8>) -> Option<Vec<u8>> {
+        Some(packet)
+    }
+
+    /// Attempts to resume a previously lost connection by taking over its id
+    /// and presenting the `token` to the remote while connecting.
+    ///
+    /// Messages which are send before the connection is established will be
+    /// delivered as soon as the remote accepts the resumption.
+    pub fn resume(&mut self, token: ResumptionToken) {
+        self.random_id = token.id;
+        self.rng = ConnectionRng::new(token.id);
+        self.resumption_token = Some(token);
+        self.resumption_issuer = false;
+        self.resumption_request = Some(token.secret);
+    }
+
+    /// Returns the sequence number of the next packet send over the
+    /// connection along with the one of the last packet received from the
+    /// remote.
+    pub fn seq_numbers(&self) -> (u32, u32) {
+        (self.local_seq_number, self.remote_seq_number)
+    }
+
+    /// Continues the packet sequence of a previous connection, i.e. when
+    /// resuming it from another process, so the remote does not drop the
+    /// first packets as duplicates in case it still considers the previous
+    /// connection as established.
+    pub fn restore_seq_numbers(&mut self, local: u32, remote: u32) {
+        self.local_seq_number = local % MAX_SEQ_NUMBER;
+        self.remote_seq_number = remote % MAX_SEQ_NUMBER;
+    }
+
+    /// Allows the remote to resume a previously lost connection by presenting
+    /// the specified `secret` with its first packet.
+    ///
+    /// The `messages` are send over the connection once it has been resumed
+    /// and dropped otherwise.
+    pub fn allow_resumption(
+        &mut self, secret: u64, messages: Vec<(MessageKind, Vec<u8>)>
+    ) {
+        self.resumption_expected = Some(secret);
+        self.resumption_messages = messages;
+    }
+
+    /// Removes and returns all reliable and ordered messages which have not
+    /// yet been acknowledged by the remote.
+    ///
+    /// This includes the messages from all packets which are still pending
+    /// acknowledgement.
+    pub fn take_pending_messages(&mut self) -> Vec<(MessageKind, Vec<u8>)> {
+
+        // Treat all pending packets as lost, newest first so the oldest
+        // messages end up at the front of the queues
+        let header_size = self.header_size();
+        for ack in self.sent_ack_queue.iter_mut().rev() {
+            if ack.state == PacketState::Unknown {
+                if let Some(packet) = ack.packet.take() {
+                    let (_, body) = split_tick_number(&packet, header_size);
+                    self.message_queue.lost_packet(body, &ack.handles);
+                }
+            }
+        }
+
+        self.sent_ack_queue.clear();
+        self.message_queue.take_pending()
+
+    }
+
+    /// Issues a new migration token for this connection and sends it to the
+    /// remote, allowing it to prove its identity after an address change.
+    ///
+    /// The token is only issued once the connection's packets are encrypted
+    /// or authenticated. With authentication alone its secret is send in
+    /// plain text, so observers of the traffic which also know the
+    /// `Config::authentication_key` are able to answer the challenges.
+    pub fn issue_migration_token(&mut self) {
+
+        if !self.send_encrypted() && !self.receive_signed() {
+            return;
+        }
+
+        let mut secret = [0; 32];
+        if let Ok(mut rng) = OsRng::new() {
+            rng.fill_bytes(&mut secret);
+            self.migration_secret = Some(secret);
+            self.message_queue.send_control(
+                MessageKind::Reliable,
+                Control::MigrationToken { secret }.encode()
+            );
+        }
+
+    }
+
+    /// Returns whether a migration token was issued to or by this connection.
+    pub fn has_migration_token(&self) -> bool {
+        self.migration_secret.is_some()
+    }
+
+    /// Starts the validation of a migration of the remote to the specified
+    /// `addr`.
+    ///
+    /// A copy of each outgoing packet is send to the address along with a
+    /// challenge until the remote answers it, at which point the connection's
+    /// peer address is updated. Without an issued migration token the
+    /// migration is never validated.
+    pub fn validate_peer_addr(&mut self, addr: SocketAddr) {
+        let pending = match self.migration_candidate {
+            Some((candidate, _)) => candidate == addr,
+            None => false
+        };
+        if !pending {
+            self.migration_candidate = Some((addr, rand::random()));
+        }
+    }
+
+    /// Abandons the validation of a migration started via
+    /// `Connection::validate_peer_addr`, so no further challenges are send.
+    pub fn cancel_peer_addr_validation(&mut self) {
+        self.migration_candidate = None;
+    }
+
+    /// Returns and clears the address of the last migration candidate which
+    /// answered its challenge with an invalid proof, i.e. a different peer
+    /// which reuses the id of the connection.
+    pub fn take_failed_migration(&mut self) -> Option<SocketAddr> {
+        self.failed_migration.take()
+    }
+
+    /// Returns the peers of the session hosted by the remote, as shared by a
+    /// `Server` with `host_migration` enabled.
+    pub fn peers(&self) -> &[Peer] {
+        &self.peers
+    }
+
+    /// Returns the latest session state shared by the remote, if any.
+    pub fn session_state(&self) -> Option<&[u8]> {
+        self.session_state.as_ref().map(|data| &data[..])
+    }
+
+    /// Shares the list of `peers` taking part in the session with the remote.
+    ///
+    /// Returns an error of the kind `ErrorKind::InvalidInput` in case the
+    /// list does not fit into a single packet.
+    pub fn share_peers(&mut self, peers: &[Peer]) -> Result<(), Error> {
+        self.send_control_checked(Control::Peers {
+            peers: peers.to_vec()
+        })
+    }
+
+    /// Shares the session state `data` with the remote.
+    ///
+    /// Returns an error of the kind `ErrorKind::InvalidInput` in case the
+    /// state does not fit into a single packet.
+    pub fn share_session_state(&mut self, data: &[u8]) -> Result<(), Error> {
+        self.send_control_checked(Control::SessionState {
+            data: data.to_vec()
+        })
+    }
+
+    /// Elects the new host out of the peers shared by the remote, in case the
+    /// connection to it was lost.
+    ///
+    /// Returns `None` if the remote did not share any peers.
+    pub fn host_migration(&self) -> Option<HostMigration> {
+        host_migration::elect_host(&self.peers).map(|host| HostMigration {
+            host,
+            local: host.id == self.random_id,
+            session_state: self.session_state.clone()
+        })
+    }
+
+    /// Returns the number of unconsumed received bytes above which the
+    /// connection is under receive pressure.
+    pub fn receive_watermark(&self) -> usize {
+        self.receive_watermark
+    }
+
+    /// Sets the number of unconsumed received `bytes` above which the
+    /// connection is under receive pressure, allowing slow consumers to push
+    /// back on fast senders. A value of `0` disables the watermark.
+    ///
+    /// While a watermark is set, received messages which are not consumed
+    /// during a tick are retained instead of being dismissed once the next
+    /// packet is send. Changes of the pressure are advertised to the remote
+    /// and reported via `Handler::connection_receive_pressure()`.
+    pub fn set_receive_watermark(&mut self, bytes: usize) {
+        self.receive_watermark = bytes;
+    }
+
+    /// Returns whether the unconsumed data received over the connection
+    /// exceeds its receive watermark.
+    pub fn receive_pressure(&self) -> bool {
+        self.receive_pressure
+    }
+
+    /// Returns whether the remote last advertised to be under receive
+    /// pressure, in which case it should be send less data.
+    pub fn remote_receive_pressure(&self) -> bool {
+        self.remote_receive_pressure.1
+    }
+
+    /// Requests the remote to keep the connection alive for the specified
+    /// number of milliseconds even though no packets are received, i.e.
+    /// before a mobile client goes into the background.
+    ///
+    /// The remote grants at most its configured `max_keep_alive_extension`,
+    /// both ends then keep the connection alive for the granted duration
+    /// and report it via `Handler::connection_keep_alive()`.
+    ///
+    /// Returns an error of the kind `ErrorKind::NotConnected` in case the
+    /// connection is not established.
+    pub fn request_keep_alive(&mut self, duration: u32) -> Result<(), Error> {
+        if self.state == ConnectionState::Connected {
+            self.send_control_checked(Control::KeepAliveRequest { duration })
+
+        } else {
+            Err(Error::new(ErrorKind::NotConnected, ""))
+        }
+    }
+
+    /// Returns the number of milliseconds for which the connection is still
+    /// kept alive without receiving any packets, as granted via
+    /// `Connection::request_keep_alive()`.
+    pub fn keep_alive_remaining(&self) -> u32 {
+        self.keep_alive_until.map_or(0, |until| {
+            let now = Instant::now();
+            if until > now {
+                dur_as_ms(until - now)
+
+            } else {
+                0
+            }
+        })
+    }
+
+    /// Returns the maximum number of bytes per second send over the
+    /// connection, `0` if unlimited.
+    pub fn bandwidth_limit(&self) -> u32 {
+        self.bandwidth_limit
+    }
+
+    /// Caps the number of bytes per second send over the connection, i.e. in
+    /// order to deprioritize spectators. A value of `0` removes the cap.
+    ///
+    /// The cap is enforced while packets are assembled: messages which would
+    /// exceed it remain queued until enough bandwidth became available again,
+    /// with bursts of up to one second's worth of bytes.
+    pub fn set_bandwidth_limit(&mut self, bytes_per_second: u32) {
+        self.bandwidth_limit = bytes_per_second;
+        self.bandwidth_allowance = (self.max_bandwidth_allowance(), Instant::now());
+    }
+
+    /// Attaches the specified `tag` to the connection, i.e. in order to mark
+    /// admins or observers, returning whether it was not yet attached.
+    ///
+    /// Tags are purely local and never send to the remote. Enum based tags
+    /// can be used by converting them into their names.
+    pub fn tag(&mut self, tag: &str) -> bool {
+        self.tags.insert(tag.to_string())
+    }
+
+    /// Removes the specified `tag` from the connection, returning whether it
+    /// was attached.
+    pub fn untag(&mut self, tag: &str) -> bool {
+        self.tags.remove(tag)
+    }
+
+    /// Returns whether the specified `tag` is attached to the connection.
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.contains(tag)
+    }
+
+    /// Returns all tags attached to the connection, sorted by name.
+    pub fn tags(&self) -> Vec<String> {
+        self.tags.iter().cloned().collect()
+    }
+
+    /// Returns the maximum size of a single message payload that can be send
+    /// over the connection.
+    pub fn max_message_size(&self) -> usize {
+        self.config.packet_max_size.saturating_sub(
+            self.header_size() + self.tick_number_size() + self.packet_overhead()
+                + MESSAGE_HEADER_BYTES
+        )
+    }
+
+    /// Sends a message of the specified `kind` along with its `payload` over
+    /// the connection.
+    ///
+    /// How exactly the message is send and whether it is guaranteed to be
+    /// delivered eventually is determined by its `MessageKind`.
+    ///
+    /// Returns a handle for cancelling the message or `None` in case the
+    /// message was rejected by `Connection::try_send()` and dropped.
+    ///
+    /// Once a `Reliable` or `Ordered` message has been acknowledged by the
+    /// remote, `Handler::connection_message_delivered()` is invoked with its
+    /// handle.
+    pub fn send(&mut self, kind: MessageKind, payload: Vec<u8>) -> Option<MessageHandle> {
+        self.try_send(kind, payload).ok()
+    }
+
+    /// Sends a message of the specified `kind` along with its `payload` over
+    /// the connection, returning an error in case the message was rejected.
+    ///
+    /// Payloads exceeding `Connection::max_message_size()` result in an error
+    /// of the kind `ErrorKind::InvalidInput`, as they would never fit into a
+    /// packet. Messages exceeding the configured `message_queue_limit` result
+    /// in an error of the kind `ErrorKind::WouldBlock`.
+    ///
+    /// Once the connection is no longer open or is closing, messages are
+    /// handled according to the configured `closed_send_policy`. While the
+    /// connection is still being established, messages exceeding the
+    /// configured `connecting_message_limit` result in an error of the kind
+    /// `ErrorKind::WouldBlock`.
+    pub fn try_send(
+        &mut self, kind: MessageKind, payload: Vec<u8>
+
+    ) -> Result<MessageHandle, Error> {
+        if payload.len() > self.max_message_size() {
+            Err(Error::new(ErrorKind::InvalidInput, ""))
+
+        } else if let Some(result) = self.closed_send() {
+            result
+
+        } else if self.holds_messages()
+               && self.held_messages() >= self.config.connecting_message_limit {
+            Err(Error::new(ErrorKind::WouldBlock, ""))
+
+        } else {
+            self.message_queue.try_send(kind, payload)
+        }
+    }
+
+    /// Attaches a single `MessageKind::Reliable` message to the connection
+    /// request, so a remote with `Config::early_data` enabled receives it
+    /// along with the connection itself.
+    ///
+    /// Remotes which do not accept early data reject it, in which case the
+    /// message is send again as a regular one once the connection has been
+    /// established. The delivery of early data is not reported to the
+    /// handler.
+    ///
+    /// Returns an error of the kind `ErrorKind::AlreadyExists` in case the
+    /// connection request was already send or already carries early data.
+    /// Payloads which do not fit into a single message result in an error of
+    /// the kind `ErrorKind::InvalidInput`.
+    pub fn send_early_data(&mut self, payload: Vec<u8>) -> Result<(), Error> {
+        if payload.len() >= self.max_message_size() {
+            Err(Error::new(ErrorKind::InvalidInput, ""))
+
+        } else if self.state != ConnectionState::Connecting
+               || self.sent_packets > 0 || self.early_data.is_some() {
+
+            Err(Error::new(ErrorKind::AlreadyExists, ""))
+
+        } else {
+            self.message_queue.send_control(
+                MessageKind::Reliable,
+                Control::EarlyData { data: payload.clone() }.encode()
+            );
+            self.early_data = Some(payload);
+            Ok(())
+        }
+    }
+
+    /// Sends a message of the specified `kind` along with its `payload` over
+    /// the connection once the `delay` has elapsed.
+    ///
+    /// The message is rejected in the same way as with
+    /// `Connection::try_send()`, except for the `message_queue_limit` which
+    /// does not apply to delayed messages.
+    pub fn send_after(
+        &mut self, kind: MessageKind, payload: Vec<u8>, delay: Duration
+
+    ) -> Result<MessageHandle, Error> {
+        if payload.len() > self.max_message_size() {
+            Err(Error::new(ErrorKind::InvalidInput, ""))
+
+        } else if let Some(result) = self.closed_send() {
+            result
+
+        } else {
+            self.message_queue.send_after(kind, payload, delay)
+        }
+    }
+
+    /// Cancels the message referred to by `handle` in case it has not yet
+    /// been send, returning whether it was removed from the connection's
+    /// queues.
+    pub fn cancel(&mut self, handle: MessageHandle) -> bool {
+        self.message_queue.cancel(handle)
+    }
+
+    /// Returns a consuming iterator over all messages received over this
+    /// connections.
+    pub fn received(&mut self) -> MessageIterator<'_> {
+        self.message_queue.received()
+    }
+
+    /// Returns a consuming iterator over all messages received over this
+    /// connection along with the tick number of the remote at the time the
+    /// message was send.
+    ///
+    /// The tick number is `None` for messages from packets without one, see
+    /// `Config::packet_tick_numbers`.
+    pub fn received_with_tick_numbers(&mut self) -> TickedMessageIterator<'_> {
+        self.message_queue.received_with_tick_numbers()
+    }
+
+    /// Returns a consuming iterator over all messages received over this
+    /// connection along with the time at which the packet carrying them
+    /// arrived and its sequence number, i.e. for interpolation and lag
+    /// compensation.
+    ///
+    /// Ordered messages which arrived out of order report the arrival of
+    /// their own packet rather than the one which completed their order.
+    /// Since the arrival is recorded for every packet before its messages
+    /// are parsed, it is always present for messages received over a
+    /// connection.
+    pub fn received_with_arrival(&mut self) -> TimedMessageIterator<'_> {
+        self.message_queue.received_with_arrival()
+    }
+
+    /// Returns a consuming iterator over all messages received over this
+    /// connection along with the kinds they were send as, i.e. in order to
+    /// forward them unchanged.
+    pub fn received_with_kinds(&mut self) -> KindedMessageIterator<'_> {
+        self.message_queue.received_with_kinds()
+    }
+
+    /// Returns the local tick number which is send along with data packets.
+    pub fn tick_number(&self) -> u32 {
+        self.tick_number
+    }
+
+    /// Sets the local tick number which is send along with data packets.
+    ///
+    /// Servers and clients set the tick number of all of their connections
+    /// before each tick's packets are send.
+    pub fn set_tick_number(&mut self, tick: u32) {
+        self.tick_number = tick;
+    }
+
+    /// Receives a incoming UDP packet.
+    pub fn receive_packet<O>(
+        &mut self,
+        packet: Vec<u8>, tick_delay: u32,
+        owner: &mut O, handler: &mut dyn Handler<O>
+    ) {
+
+        // Ignore any packets shorter then the header length
+        let header_size = self.header_size();
+        if packet.len() < header_size {
+            return;
+        }
+
+        // Bytes received from an unvalidated address raise its send limit
+        if let Some((ref mut received, _)) = self.unvalidated_bytes {
+            *received += packet.len();
+        }
+
+        // Ignore any packets which fail to decrypt and authenticate
+        let packet = match self.decrypt_packet(packet) {
+            Ok(packet) => packet,
+            Err(reason) => {
+                self.drop_packet(reason, owner, handler);
+                return;
+            }
+        };
+
+        // Ignore any packets of unknown or unsupported types
+        let packet_type = match self.packet_type(&packet) {
+            Some(PacketType::Fragment) | Some(PacketType::Extension) | None => return,
+            Some(packet_type) => packet_type
+        };
+
+        // Update connection state
+        let connecting = self.state == ConnectionState::Connecting;
+        if !self.update_receive_state(&packet, packet_type, owner, handler) {
+            return;
+        }
 
         // Update time used for disconnect detection
         self.last_receive_time = Instant::now();
 
         // Read remote sequence number
         self.remote_seq_number = packet[8] as u32;
+        self.message_queue.set_arrival(self.last_receive_time, self.remote_seq_number);
 
         // Get latest acknowledge sequence number
         let ack_seq_number = packet[9] as u32;
@@ -347,26 +1963,83 @@ impl Connection {
                      | (packet[12] as u32) << 8
                      |  packet[13] as u32;
 
-        // Check recently send packets for their acknowledgment
-        for i in 0..self.sent_ack_queue.len() {
+        // Push packet data into message queue
+        let has_data = if cfg!(feature = "packet_handler_compress") {
 
-            if let Some(lost_packet) = {
+            // Optional packet decompression
+            let mut decompressed = packet[..header_size].to_vec();
+            decompressed.extend(handler.connection_packet_decompress(
+                owner, self,
+                &packet[header_size..]
+            ));
 
-                let ack = self.sent_ack_queue.get_mut(i).unwrap();
-                let last_receive_since_ack = if self.last_receive_time > ack.time {
-                    dur_as_ms(self.last_receive_time - ack.time)
-                } else {
-                    0
-                };
+            self.receive_body(&decompressed, header_size)
 
-                // Calculate the roundtrip time from acknowledged packets
+        } else {
+            self.receive_body(&packet, header_size)
+        };
+
+        // Handle internal control messages
+        let ack_only = self.receive_control(connecting, owner, handler)
+                    || packet_type == PacketType::AckOnly;
+
+        // Acknowledge packets containing data without waiting for the next
+        // tick
+        if self.config.immediate_acks && !ack_only && has_data {
+            if self.ack_pending == 0 {
+                self.ack_pending_time = Instant::now();
+            }
+            self.ack_pending += 1;
+        }
+
+        // Acknowledgments from immediate ack packets were not delayed by the
+        // remote's tick
+        let tick_delay = if ack_only {
+            0
+
+        } else {
+            tick_delay
+        };
+
+        // Invoke handler once the connection has been established
+        if connecting {
+            if self.resumed {
+                handler.connection_resumed(owner, self);
+
+            } else {
+                handler.connection(owner, self);
+            }
+        }
+
+        // Check whether the received data exceeds the watermark
+        self.update_receive_pressure(owner, handler);
+
+        // Check recently send packets for their acknowledgment
+        let mut delivered = Vec::new();
+        for i in 0..self.sent_ack_queue.len() {
+
+            if let Some(lost_packet) = {
+
+                let ack = self.sent_ack_queue.get_mut(i).unwrap();
+                let last_receive_since_ack = if self.last_receive_time > ack.time {
+                    dur_as_ms(self.last_receive_time - ack.time)
+                } else {
+                    0
+                };
+
+                // Calculate the roundtrip time from acknowledged packets
                 if seq_was_acked(ack.seq, ack_seq_number, bitfield) {
                     self.acked_packets = self.acked_packets.wrapping_add(1);
-                    self.smoothed_rtt = moving_average(
-                        self.smoothed_rtt,
-                        (cmp::max(last_receive_since_ack, tick_delay) - tick_delay) as f32
+                    let rtt = cmp::max(last_receive_since_ack, tick_delay) - tick_delay;
+                    self.rtt_jitter = moving_average(
+                        self.rtt_jitter, (rtt as f32 - self.smoothed_rtt).abs()
                     );
+                    self.smoothed_rtt = moving_average(self.smoothed_rtt, rtt as f32);
+                    self.rtt_histogram.record(rtt);
                     ack.state = PacketState::Acked;
+                    self.unvalidated_bytes = None;
+                    self.acknowledged = true;
+                    delivered.extend(ack.handles.drain(..).flatten());
                     None
 
                 // Extract data from lost packets
@@ -375,344 +2048,1758 @@ impl Connection {
 
                     self.lost_packets = self.lost_packets.wrapping_add(1);
                     ack.state = PacketState::Lost;
-                    ack.packet.take()
+                    ack.packet.take().map(|p| (p, mem::take(&mut ack.handles)))
 
                 // Keep all pending packets
                 } else {
                     None
                 }
 
-            } {
+            } {
+                self.packet_lost(lost_packet, owner, handler);
+            }
+
+        }
+
+        // Remove all acknowledged and lost packets from the sent ack queue
+        self.sent_ack_queue.retain(|p| p.state == PacketState::Unknown);
+
+        // Notify about messages which were delivered to the remote
+        for handle in delivered {
+            if !self.key_exchange_delivered(handle) && !self.key_rotation_delivered(handle) {
+                handler.connection_message_delivered(owner, self, handle);
+            }
+        }
+
+        // Insert packet into receive acknowledgment queue
+        self.recv_ack_queue.push_front(self.remote_seq_number);
+
+        // Don't keep more entries than we can actually acknowledge per packet
+        if self.recv_ack_queue.len() > MAX_ACK_BITS as usize {
+            self.recv_ack_queue.pop_back();
+        }
+
+        // Update packet statistics
+        self.recv_packets = self.recv_packets.wrapping_add(1);
+
+    }
+
+    /// Send a new outgoing UDP packet.
+    pub fn send_packet<O, S: Socket>(
+        &mut self,
+        socket: &mut S, addr: &SocketAddr,
+        owner: &mut O, handler: &mut dyn Handler<O>
+
+    ) -> u32 {
+
+        // Update connection state
+        if !self.update_send_state(owner, handler) {
+            return 0;
+        }
+
+        // Retransmit handshakes at the configured interval
+        if self.state == ConnectionState::Connecting && !self.handshake_due() {
+            return 0;
+        }
+
+        // Generate the key pair once the first packet is send
+        self.start_key_exchange();
+
+        let congested = self.rate_limiter.congested();
+        let rtt = self.rtt();
+        let packet_loss = self.packet_loss();
+
+        // Update congestion state
+        self.rate_limiter.update(rtt, packet_loss);
+
+        // Check if the state changed and invoke handler
+        if congested != self.rate_limiter.congested() {
+            handler.connection_congestion_state(owner, self, !congested);
+        }
+
+        // Check if we should be sending packets, if not skip this packet but
+        // still acknowledge received data whose coalescing limit was reached
+        if !self.rate_limiter.should_send() {
+            return self.send_ack_packet(socket, addr, owner, handler);
+        }
+
+        // Take write buffer out and insert a fresh, empty one in its place
+        let header_size = self.header_size();
+        let mut packet = Vec::<u8>::with_capacity(header_size);
+        let mut handles = Vec::new();
+        let mut challenge = None;
+
+        // Send closing packets if required
+        if self.state == ConnectionState::Closing {
+            self.write_header(&mut packet, self.local_seq_number, PacketType::Close);
+            if let Some(reason) = self.close_reason {
+                packet.push(reason);
+            }
+
+        } else {
+
+            self.write_header(&mut packet, self.local_seq_number, PacketType::Data);
+            self.write_tick_number(&mut packet);
+            let body_start = packet.len();
+
+            // The packet acknowledges all data received so far
+            self.ack_pending = 0;
+
+            // Check whether the consumed data fell back below the watermark
+            self.update_receive_pressure(owner, handler);
+
+            // Present resumption secret until the connection is established
+            if let Some(secret) = self.resumption_request {
+                self.message_queue.send_control(
+                    MessageKind::Instant,
+                    Control::Resume { secret }.encode()
+                );
+            }
+
+            // Present the connect token until the connection is established
+            self.send_connect_token();
+
+            // Present the resumption ticket until the connection is
+            // established
+            self.send_resumption_ticket();
+
+            // Echo the handshake cookie until the connection is established
+            self.send_handshake_cookie();
+
+            // Rotate the packet key once it was used for long enough
+            self.update_key_rotation();
+
+            // Re-send messages from packets which timed out
+            self.retransmit_timed_out(owner, handler);
+
+            // Prepare a challenge for the address the remote is attempting
+            // to migrate to
+            challenge = self.migration_candidate.map(|(candidate, nonce)| {
+                (candidate, Control::MigrationChallenge { nonce }.encode())
+            });
+
+            // Write messages from queue into the packet, limited by the
+            // remaining bandwidth allowance
+            let available = cmp::min(
+                self.config.packet_max_size - body_start - self.packet_overhead(),
+                self.bandwidth_available(body_start + self.packet_overhead())
+            );
+            let available = match self.send_budget.take() {
+                Some(budget) => cmp::min(
+                    available,
+                    budget.saturating_sub(body_start + self.packet_overhead())
+                ),
+                None => available
+            };
+            let available = match challenge {
+                Some((_, ref control)) => available.saturating_sub(
+                    MESSAGE_HEADER_BYTES + control.len()
+                ),
+                None => available
+            };
+
+            // Hold back messages until the connection is established
+            let held = self.holds_messages();
+            self.message_queue.hold(held);
+            handles = self.message_queue.send_packet(&mut packet, available);
+
+            // Pad packets to the minimum handshake size until the
+            // connection is established
+            if self.state == ConnectionState::Connecting && self.config.handshake_min_size > 0 {
+                let size = cmp::min(
+                    self.config.handshake_min_size,
+                    self.config.packet_max_size
+                );
+                MessageQueue::write_padding(
+                    &mut packet,
+                    size.saturating_sub(self.packet_overhead())
+                );
+            }
+
+            // Mark packets without any messages as keep alive packets
+            if self.config.packet_types && packet.len() == body_start {
+                packet[PACKET_HEADER_SIZE] &= !0x0F;
+                packet[PACKET_HEADER_SIZE] |= PacketType::KeepAlive as u8;
+            }
+
+        }
+
+        // Send packet to socket
+        let mut bytes_sent = self.send_to_socket(
+            socket, addr, &packet, owner, handler
+        );
+
+
+        // Challenge the address the remote is attempting to migrate to with a
+        // copy of the packet, so the challenge shares its sequence number
+        if let Some((candidate, control)) = challenge {
+
+            let mut challenge = packet.clone();
+            if self.config.packet_types {
+                challenge[PACKET_HEADER_SIZE] &= !0x0F;
+                challenge[PACKET_HEADER_SIZE] |= PacketType::Data as u8;
+            }
+
+            MessageQueue::write_control(
+                &mut challenge, MessageKind::Instant, control
+            );
+
+            bytes_sent += self.send_to_socket(
+                socket, &candidate, &challenge, owner, handler
+            );
+
+        }
+
+        // Insert packet into send acknowledgment queue (but avoid dupes)
+        if self.send_ack_required(self.local_seq_number) {
+            self.sent_ack_queue.push(SentPacketAck {
+                seq: self.local_seq_number,
+                time: Instant::now(),
+                state: PacketState::Unknown,
+                packet: Some(packet),
+                handles
+            });
+        }
+
+        // Increase local sequence number and wrap around
+        self.local_seq_number += 1;
+
+        if self.local_seq_number == MAX_SEQ_NUMBER {
+            self.local_seq_number = 0;
+        }
+
+        // Update packet statistics
+        self.sent_packets = self.sent_packets.wrapping_add(1);
+        if self.state == ConnectionState::Connecting {
+            self.handshake_attempts = self.handshake_attempts.saturating_add(1);
+            self.last_handshake_time = Some(Instant::now());
+        }
+        self.bandwidth_allowance.0 -= bytes_sent as f64;
+
+        // Dismiss any pending, received messages unless they are retained for
+        // consumers which are watched by a receive watermark
+        if self.receive_watermark == 0 {
+            self.message_queue.dismiss();
+        }
+
+        // Return number of bytes sent over the socket
+        bytes_sent as u32
+
+    }
+
+    /// Sends a minimal packet which only acknowledges the data received
+    /// since the last packet was send.
+    ///
+    /// Does nothing unless `Config::immediate_acks` is enabled and either
+    /// `Config::ack_coalesce_packets` or `Config::ack_coalesce_ms` has been
+    /// reached for the data awaiting its acknowledgment. With neither limit
+    /// set, the data is acknowledged right away.
+    pub fn send_ack_packet<O, S: Socket>(
+        &mut self,
+        socket: &mut S, addr: &SocketAddr,
+        owner: &mut O, handler: &mut dyn Handler<O>
+
+    ) -> u32 {
+
+        if self.ack_pending == 0 || self.state != ConnectionState::Connected {
+            return 0;
+        }
+
+        // Coalesce acknowledgments until one of the limits is reached
+        if !self.ack_due() {
+            return 0;
+        }
+
+        // Use a sequence number of its own so the packet is never dropped as
+        // a duplicate, it is however not tracked for acknowledgment
+        let mut packet = Vec::<u8>::with_capacity(self.header_size());
+        self.write_header(&mut packet, self.local_seq_number, PacketType::AckOnly);
+
+        // Mark the packet so the remote does not correct the roundtrip time
+        // for our tick delay
+        if !self.config.packet_types {
+            MessageQueue::write_control(
+                &mut packet, MessageKind::Instant, Control::Ack.encode()
+            );
+        }
+
+        let bytes_sent = self.send_to_socket(socket, addr, &packet, owner, handler);
+
+        self.local_seq_number += 1;
+
+        if self.local_seq_number == MAX_SEQ_NUMBER {
+            self.local_seq_number = 0;
+        }
+
+        self.ack_pending = 0;
+
+        bytes_sent as u32
+
+    }
+
+    /// Resets the connection for re-use with another address.
+    pub fn reset(&mut self) {
+        self.state = ConnectionState::Connecting;
+        self.acknowledged = false;
+        self.local_seq_number = 0;
+        self.remote_seq_number = 0;
+        self.remote_protocol_header = None;
+        self.server_full = false;
+        self.close_reason = None;
+        self.remote_close_reason = None;
+        self.smoothed_rtt = 0.0;
+        self.rtt_jitter = 0.0;
+        self.rtt_histogram.clear();
+        self.rng = ConnectionRng::new(self.random_id);
+        self.last_receive_time = Instant::now();
+        self.last_message_time = Instant::now();
+        self.recv_ack_queue.clear();
+        self.sent_ack_queue.clear();
+        self.sent_packets = 0;
+        self.handshake_attempts = 0;
+        self.last_handshake_time = None;
+        self.recv_packets = 0;
+        self.acked_packets = 0;
+        self.lost_packets = 0;
+        self.message_queue.reset();
+        self.rate_limiter.reset();
+        self.resumption_token = None;
+        self.resumption_issuer = false;
+        self.resumption_request = None;
+        self.early_data = None;
+        self.resumption_expected = None;
+        self.resumption_messages.clear();
+        self.resumed = false;
+        self.receive_pressure = false;
+        self.bandwidth_allowance = (self.max_bandwidth_allowance(), Instant::now());
+        self.send_budget = None;
+        self.receive_pressure_seq = 0;
+        self.remote_receive_pressure = (0, false);
+        self.keep_alive_until = None;
+        self.migration_secret = None;
+        self.migration_candidate = None;
+        self.failed_migration = None;
+        self.send_error = None;
+        self.peers.clear();
+        self.session_state = None;
+        self.ack_pending = 0;
+        self.reset_key_rotation();
+        self.reset_replay_window();
+        self.reset_key_exchange();
+        self.reset_connect_token();
+        self.reset_resumption_ticket();
+        self.reset_handshake_cookie();
+    }
+
+    /// Closes the connection, no further packets will be received or send.
+    ///
+    /// Has no effect on connections which are already closing or no longer
+    /// open.
+    pub fn close(&mut self) {
+        if self.state.can_transition_to(ConnectionState::Closing) {
+            self.config.connection_drop_threshold = 20;
+            self.set_state(ConnectionState::Closing);
+        }
+    }
+
+    /// Closes the connection like `Connection::close()`, but sends the
+    /// specified `reason` code along with the closure packets, which is then
+    /// reported via `Handler::connection_kicked` by the remote.
+    pub fn close_with_reason(&mut self, reason: u8) {
+        if self.state.can_transition_to(ConnectionState::Closing) {
+            self.close_reason = Some(reason);
+            self.close();
+        }
+    }
+
+    /// Returns the reason code the remote closed the connection with via
+    /// `Connection::close_with_reason()`, if any.
+    pub fn remote_close_reason(&self) -> Option<u8> {
+        self.remote_close_reason
+    }
+
+
+    // Internal State Handling ------------------------------------------------
+
+    fn update_receive_state<T>(
+        &mut self,
+        packet: &[u8], packet_type: PacketType,
+        owner: &mut T, handler: &mut dyn Handler<T>
+
+    ) -> bool {
+
+        // Ignore any packets which do not match the desired protocol header
+        packet[0..4] == self.config.protocol_header && match self.state {
+
+            ConnectionState::Lost |
+            ConnectionState::Closed |
+            ConnectionState::FailedToConnect => false,
+
+            ConnectionState::Closing => true,
+
+            ConnectionState::Connecting => {
+
+                // Once we receive the first valid packet we consider the
+                // connection as established
+                self.set_state(ConnectionState::Connected);
+
+                // Reset Packet Loss upon connection
+                self.lost_packets = 0;
+
+                true
+
+            },
+
+            ConnectionState::Connected => {
+
+                // Check for closure packet from remote, ignoring closures
+                // which could have been forged by a third party
+                if packet_type == PacketType::Close {
+                    if !self.config.authenticated_close || self.receive_authenticated() {
+                        self.set_state(ConnectionState::Closed);
+                        self.remote_close_reason = packet.get(self.header_size()).cloned();
+                        if let Some(reason) = self.remote_close_reason {
+                            handler.connection_kicked(owner, self, reason);
+                        }
+                        handler.connection_closed(owner, self, true);
+                    }
+                    false
+
+                } else {
+                    // Check if the packet sequence number is more recent,
+                    // otherwise drop it as a duplicate
+                    seq_is_more_recent(
+                        packet[8] as u32, self.remote_seq_number
+                    )
+                }
+
+            }
+
+        }
+
+    }
+
+    fn update_send_state<T>(
+        &mut self, owner: &mut T, handler: &mut dyn Handler<T>
+
+    ) -> bool {
+
+        // Calculate time since last received packet
+        let inactive_time = dur_as_ms(self.last_receive_time.elapsed());
+
+        match self.state {
+
+            ConnectionState::Lost |
+            ConnectionState::Closed |
+            ConnectionState::FailedToConnect => false,
+
+            ConnectionState::Connecting => {
+
+                // Quickly detect initial connection failures
+                if inactive_time > self.config.connection_init_threshold
+                    || self.handshake_exhausted() {
+                    self.set_state(ConnectionState::FailedToConnect);
+                    handler.connection_failed(owner, self);
+                    false
+
+                } else {
+                    true
+                }
+
+            },
+
+            ConnectionState::Connected => {
+
+                // Detect connection timeouts, unless the connection is kept
+                // alive on request of the remote
+                if inactive_time > self.config.connection_drop_threshold
+                    && self.keep_alive_remaining() == 0 {
+                    self.set_state(ConnectionState::Lost);
+
+                    // Elect a new host for the session
+                    if let Some(migration) = self.host_migration() {
+                        handler.connection_host_migration(owner, self, migration);
+                    }
+
+                    handler.connection_lost(owner, self);
+                    false
+
+                // Close connections which went idle for too long
+                } else if self.config.idle_timeout > 0
+                    && dur_as_ms(self.last_message_time.elapsed()) > self.config.idle_timeout {
+                    self.close_with_reason(Connection::IDLE_TIMEOUT_REASON);
+                    handler.connection_kicked(owner, self, Connection::IDLE_TIMEOUT_REASON);
+                    true
+
+                } else {
+                    true
+                }
+
+            },
+
+            ConnectionState::Closing => {
+
+                // Detect connection closure
+                if inactive_time > self.config.connection_drop_threshold {
+                    self.set_state(ConnectionState::Closed);
+                    handler.connection_closed(owner, self, false);
+                    false
+
+                } else {
+                    true
+                }
+
+            }
+
+        }
+
+    }
+
+    fn retransmit_timed_out<O>(
+        &mut self, owner: &mut O, handler: &mut dyn Handler<O>
+    ) {
+
+        if self.config.retransmit_timeout_ms == 0 {
+            return;
+        }
+
+        let timeout = self.config.retransmit_timeout_ms + self.rtt() * 2;
+        let now = Instant::now();
+        for i in 0..self.sent_ack_queue.len() {
+
+            if let Some(lost_packet) = {
+
+                let ack = self.sent_ack_queue.get_mut(i).unwrap();
+                if ack.state == PacketState::Unknown
+                    && dur_as_ms(now - ack.time) > timeout {
+
+                    self.lost_packets = self.lost_packets.wrapping_add(1);
+                    ack.state = PacketState::Lost;
+                    ack.packet.take().map(|p| (p, mem::take(&mut ack.handles)))
+
+                } else {
+                    None
+                }
+
+            } {
+                self.packet_lost(lost_packet, owner, handler);
+            }
+
+        }
+
+        // Remove all timed out packets from the sent ack queue
+        self.sent_ack_queue.retain(|p| p.state == PacketState::Unknown);
+
+    }
+
+    fn packet_lost<O>(
+        &mut self,
+        (packet, handles): (Vec<u8>, Vec<Option<MessageHandle>>),
+        owner: &mut O, handler: &mut dyn Handler<O>
+    ) {
+
+        // Push messages from lost packets into the queue
+        let (_, body) = split_tick_number(&packet, self.header_size());
+        self.message_queue.lost_packet(body, &handles);
+
+        // Optional packet lost notification
+        if cfg!(feature = "packet_handler_lost") {
+            handler.connection_packet_lost(owner, self, body);
+        }
+
+    }
+
+    fn closed_send(&mut self) -> Option<Result<MessageHandle, Error>> {
+
+        if self.open() && self.state != ConnectionState::Closing {
+            return None;
+        }
+
+        match self.config.closed_send_policy {
+            ClosedSendPolicy::Error => Some(Err(Error::new(ErrorKind::NotConnected, ""))),
+            ClosedSendPolicy::Drop => Some(Ok(self.message_queue.discard())),
+            ClosedSendPolicy::Buffer => None
+        }
+
+    }
+
+    fn handshake_due(&self) -> bool {
+        match self.last_handshake_time {
+            Some(time) => dur_as_ms(time.elapsed()) >= self.config.handshake_retry_interval,
+            None => true
+        }
+    }
+
+    fn handshake_exhausted(&self) -> bool {
+        self.config.handshake_max_attempts > 0
+            && self.handshake_attempts >= self.config.handshake_max_attempts
+            && self.handshake_due()
+    }
+
+    fn keep_alive(&mut self, duration: u32) {
+        self.keep_alive_until = if duration > 0 {
+            Some(Instant::now() + Duration::from_millis(u64::from(duration)))
+
+        } else {
+            None
+        };
+    }
+
+    fn holds_messages(&self) -> bool {
+        self.state == ConnectionState::Connecting && self.config.connecting_message_limit > 0
+    }
+
+    fn held_messages(&self) -> usize {
+        self.message_queue.queued(MessageKind::Instant)
+            + self.message_queue.queued(MessageKind::Reliable)
+            + self.message_queue.queued(MessageKind::Ordered)
+    }
+
+    fn send_control_checked(&mut self, control: Control) -> Result<(), Error> {
+
+        let data = control.encode();
+        if data.len() > self.max_message_size() {
+            Err(Error::new(ErrorKind::InvalidInput, ""))
+
+        } else {
+            self.message_queue.send_control(MessageKind::Reliable, data);
+            Ok(())
+        }
+
+    }
+
+    fn send_to_socket<S: Socket, O>(
+        &mut self,
+        socket: &mut S, addr: &SocketAddr, packet: &[u8],
+        owner: &mut O, handler: &mut dyn Handler<O>
+
+    ) -> usize {
+
+        // Optional packet compression
+        let compressed = if cfg!(feature = "packet_handler_compress") {
+            Some(handler.connection_packet_compress(
+                owner, self,
+                packet[..self.header_size()].to_vec(),
+                &packet[self.header_size()..]
+            ))
+
+        } else {
+            None
+        };
+
+        let packet = compressed.as_ref().map_or(packet, |p| &p[..]);
+
+        // Optional packet encryption
+        let encrypted = self.encrypt_packet(packet);
+        let packet = encrypted.as_ref().map_or(packet, |p| &p[..]);
+
+        // Optional packet authentication
+        let signed = self.sign_packet(packet);
+        let packet = signed.as_ref().map_or(packet, |p| &p[..]);
+
+        // Drop packets exceeding the limit of an unvalidated address
+        if *addr == self.peer_address {
+            if let Some((received, ref mut sent)) = self.unvalidated_bytes {
+                let limit = received * self.config.amplification_factor as usize;
+                if *sent + packet.len() > limit {
+                    return 0;
+                }
+                *sent += packet.len();
+            }
+        }
+
+        // Errors are reported to the owner, which may be able to recover
+        // from them, i.e. by rebinding its socket
+        if let Err(err) = socket.send_to(packet, *addr) {
+            self.send_error = Some(err.kind());
+            return 0;
+        }
+
+        // Number of all bytes sent
+        packet.len()
+
+    }
+
+    fn receive_control<O>(
+        &mut self,
+        connecting: bool,
+        owner: &mut O, handler: &mut dyn Handler<O>
+
+    ) -> bool {
+
+        let mut ack_only = false;
+        let messages: Vec<Vec<u8>> = self.message_queue.received_control().collect();
+        for data in messages {
+            match Control::decode(&data) {
+
+                // Store tokens issued by the remote
+                Some(Control::ResumptionToken { secret, resumed }) if !self.resumption_issuer => {
+
+                    self.resumption_token = Some(ResumptionToken {
+                        id: self.random_id,
+                        secret
+                    });
+
+                    if connecting {
+                        self.resumed = resumed && self.resumption_request.is_some();
+                    }
+
+                },
+
+                // Verify resumption attempts from the remote
+                Some(Control::Resume { secret }) if connecting && self.resumption_expected == Some(secret) => {
+
+                    self.resumed = true;
+
+                    // Re-send any messages retained from the lost connection
+                    for (kind, data) in self.resumption_messages.drain(..) {
+                        self.message_queue.send(kind, data);
+                    }
+
+                },
+
+                // Store migration tokens issued by the remote
+                Some(Control::MigrationToken { secret }) => {
+                    self.migration_secret = Some(secret);
+                },
+
+                // Prove our identity to the remote after an address change
+                Some(Control::MigrationChallenge { nonce }) => {
+                    let proof = self.migration_secret.and_then(|secret| {
+                        migration_proof(&secret, nonce, self.random_id)
+                    });
+                    if let Some(proof) = proof {
+                        self.message_queue.send_control(
+                            MessageKind::Instant,
+                            Control::MigrationResponse { proof }.encode()
+                        );
+                    }
+                },
+
+                // Store the session information shared by the host
+                Some(Control::Peers { peers }) => {
+                    self.peers = peers;
+                },
+
+                Some(Control::SessionState { data }) => {
+                    self.session_state = Some(data);
+                },
+
+                Some(Control::Ack) => {
+                    ack_only = true;
+                },
+
+                // Store resumption tickets issued by the remote
+                Some(Control::ResumptionTicket { resumed, data }) => {
+                    self.receive_resumption_ticket(resumed, data, connecting);
+                },
+
+                // Derive the key of a resumed session from the nonce of the
+                // client, or the one of the server
+                Some(Control::ResumeTicket { nonce, .. }) => {
+                    self.complete_resumption(nonce, true);
+                },
+
+                Some(Control::ResumeNonce { nonce }) => {
+                    self.complete_resumption(nonce, false);
+                },
+
+                // Derive the session key from the remote's public key
+                Some(Control::KeyExchange { public }) => {
+                    self.complete_key_exchange(public);
+                },
+
+                // Derive the key of the epoch announced by the remote
+                Some(Control::KeyRotation { epoch }) => {
+                    self.receive_key_rotation(epoch);
+                },
+
+                // Accept or reject the early data of the remote
+                Some(Control::EarlyData { data }) => {
+                    if self.config.early_data {
+                        self.message_queue.receive_reliable(data);
+
+                    } else {
+                        self.message_queue.send_control(
+                            MessageKind::Reliable,
+                            Control::EarlyDataRejected.encode()
+                        );
+                    }
+                },
+
+                // Store the receive pressure of the remote, ignoring outdated
+                // advertisements
+                Some(Control::ReceivePressure { seq, pressure }) if seq.wrapping_sub(self.remote_receive_pressure.0) as i8 > 0 => {
+                    self.remote_receive_pressure = (seq, pressure);
+                },
+
+                // Keep the connection alive on request of the remote, within
+                // the configured bounds
+                Some(Control::KeepAliveRequest { duration }) => {
+                    let granted = cmp::min(duration, self.config.max_keep_alive_extension);
+                    self.keep_alive(granted);
+                    self.message_queue.send_control(
+                        MessageKind::Reliable,
+                        Control::KeepAliveGrant { duration: granted }.encode()
+                    );
+                    handler.connection_keep_alive(owner, self, granted);
+                },
+
+                // Keep the connection alive for as long as the remote does
+                Some(Control::KeepAliveGrant { duration }) => {
+                    self.keep_alive(duration);
+                    handler.connection_keep_alive(owner, self, duration);
+                },
+
+                // Re-send rejected early data as a regular message
+                Some(Control::EarlyDataRejected) => {
+                    if let Some(data) = self.early_data.take() {
+                        self.message_queue.send(MessageKind::Reliable, data);
+                    }
+                },
+
+                // Verify the proof for a pending migration
+                Some(Control::MigrationResponse { proof }) => {
+                    if let (Some(secret), Some((addr, nonce))) = (self.migration_secret, self.migration_candidate) {
+                        if verify_migration_proof(&secret, nonce, self.random_id, &proof) {
+                            let previous = self.peer_address;
+                            self.peer_address = addr;
+                            self.migration_candidate = None;
+                            handler.connection_migrated(owner, self, previous);
+
+                        } else {
+                            self.failed_migration = Some(addr);
+                        }
+                    }
+                },
+
+                _ => {}
+
+            }
+        }
+
+        // Resumption can only happen with the first packet
+        if self.state != ConnectionState::Connecting {
+            self.resumption_request = None;
+            self.resumption_expected = None;
+            self.resumption_messages.clear();
+            self.reject_resumption_ticket();
+        }
+
+        ack_only
+
+    }
+
+    // Internal Helpers -------------------------------------------------------
+    fn update_receive_pressure<O>(&mut self, owner: &mut O, handler: &mut dyn Handler<O>) {
+        let pressure = self.receive_watermark > 0
+            && self.message_queue.received_bytes() > self.receive_watermark;
+
+        if pressure != self.receive_pressure {
+            self.receive_pressure = pressure;
+            self.receive_pressure_seq = self.receive_pressure_seq.wrapping_add(1);
+            self.message_queue.send_control(MessageKind::Reliable, Control::ReceivePressure {
+                seq: self.receive_pressure_seq,
+                pressure
+
+            }.encode());
+            handler.connection_receive_pressure(owner, self, pressure);
+        }
+    }
+
+    fn set_state(&mut self, state: ConnectionState) {
+        debug_assert!(
+            self.state.can_transition_to(state),
+            "Invalid connection state transition from {:?} to {:?}",
+            self.state, state
+        );
+        self.state = state;
+    }
+
+    fn header_size(&self) -> usize {
+        if self.config.packet_types {
+            PACKET_HEADER_SIZE + 1
+
+        } else {
+            PACKET_HEADER_SIZE
+        }
+    }
+
+    fn tick_number_size(&self) -> usize {
+        if self.config.packet_types && self.config.packet_tick_numbers {
+            TICK_NUMBER_BYTES
+
+        } else {
+            0
+        }
+    }
+
+    fn write_tick_number(&self, packet: &mut Vec<u8>) {
+        if self.tick_number_size() > 0 {
+            packet[PACKET_HEADER_SIZE] |= TICK_NUMBER_FLAG;
+            packet.push((self.tick_number >> 24) as u8);
+            packet.push((self.tick_number >> 16) as u8);
+            packet.push((self.tick_number >> 8) as u8);
+            packet.push(self.tick_number as u8);
+        }
+    }
+
+    fn receive_body(&mut self, packet: &[u8], header_size: usize) -> bool {
+        let (tick, body) = split_tick_number(packet, header_size);
+        let received = self.message_queue.received_len();
+        self.message_queue.receive_packet(body, tick);
+        if self.message_queue.received_len() > received {
+            self.last_message_time = Instant::now();
+        }
+        !body.is_empty()
+    }
+
+    fn max_bandwidth_allowance(&self) -> f64 {
+        cmp::max(self.bandwidth_limit as usize, self.config.packet_max_size) as f64
+    }
+
+    fn bandwidth_available(&mut self, overhead: usize) -> usize {
+
+        if self.bandwidth_limit == 0 {
+            return usize::MAX;
+        }
+
+        // Refill the allowance for the time passed since the last packet
+        let (allowance, time) = self.bandwidth_allowance;
+        let now = Instant::now();
+        let refill = (now - time).as_secs_f64() * self.bandwidth_limit as f64;
+        self.bandwidth_allowance = (
+            (allowance + refill).min(self.max_bandwidth_allowance()),
+            now
+        );
+
+        (self.bandwidth_allowance.0.max(0.0) as usize).saturating_sub(overhead)
+
+    }
+
+    fn packet_overhead(&self) -> usize {
+        self.encryption_overhead() + self.authentication_overhead()
+    }
+
+    #[cfg(feature = "authentication")]
+    fn authentication_overhead(&self) -> usize {
+        if self.config.authentication_key.is_some() {
+            AUTHENTICATION_OVERHEAD
+
+        } else {
+            0
+        }
+    }
+
+    #[cfg(not(feature = "authentication"))]
+    fn authentication_overhead(&self) -> usize {
+        0
+    }
+
+    #[cfg(feature = "authentication")]
+    fn sign_packet(&self, packet: &[u8]) -> Option<Vec<u8>> {
+        self.config.authentication_key.map(|key| {
+            let mut signed = packet.to_vec();
+            authentication::sign(&key, &mut signed);
+            signed
+        })
+    }
+
+    #[cfg(not(feature = "authentication"))]
+    fn sign_packet(&self, _: &[u8]) -> Option<Vec<u8>> {
+        None
+    }
+
+    #[cfg(feature = "encryption")]
+    fn encryption_overhead(&self) -> usize {
+        if self.cipher.is_some() || self.exchange_encryption() {
+            ENCRYPTION_OVERHEAD
+
+        } else {
+            0
+        }
+    }
+
+    #[cfg(not(feature = "encryption"))]
+    fn encryption_overhead(&self) -> usize {
+        0
+    }
+
+    #[cfg(feature = "encryption")]
+    fn encrypt_packet(&mut self, packet: &[u8]) -> Option<Vec<u8>> {
+        let header_size = self.header_size();
+        let counter = self.nonce_counter.wrapping_add(1);
+        let direction = if self.accepted { ACCEPTED_NONCE_BIT } else { 0 };
+        let encrypted = self.send_cipher().map(|cipher| {
+            cipher.encrypt_counted(
+                &packet[..header_size], &packet[header_size..], counter | direction
+            )
+        });
+        if encrypted.is_some() {
+            self.nonce_counter = counter;
+        }
+        encrypted
+    }
+
+    #[cfg(not(feature = "encryption"))]
+    fn encrypt_packet(&mut self, _: &[u8]) -> Option<Vec<u8>> {
+        None
+    }
+
+    fn ack_due(&self) -> bool {
+        let packets = self.config.ack_coalesce_packets;
+        let ms = self.config.ack_coalesce_ms;
+        (packets == 0 && ms == 0)
+            || (packets > 0 && self.ack_pending >= packets)
+            || (ms > 0 && dur_as_ms(self.ack_pending_time.elapsed()) >= ms)
+    }
+
+    #[cfg(feature = "encryption")]
+    fn send_encrypted(&self) -> bool {
+        self.send_cipher().is_some()
+    }
+
+    #[cfg(not(feature = "encryption"))]
+    fn send_encrypted(&self) -> bool {
+        false
+    }
+
+    fn receive_authenticated(&self) -> bool {
+        self.receive_encrypted() || self.receive_signed()
+    }
+
+    #[cfg(feature = "encryption")]
+    fn receive_encrypted(&self) -> bool {
+        self.cipher.is_some()
+    }
+
+    #[cfg(not(feature = "encryption"))]
+    fn receive_encrypted(&self) -> bool {
+        false
+    }
+
+    #[cfg(feature = "authentication")]
+    fn receive_signed(&self) -> bool {
+        self.config.authentication_key.is_some()
+    }
+
+    #[cfg(not(feature = "authentication"))]
+    fn receive_signed(&self) -> bool {
+        false
+    }
+
+    fn server_info_challenge(config: &Config, marker: [u8; 4], packet: &[u8]) -> Option<u64> {
+        if packet.len() >= SERVER_INFO_CHALLENGE_SIZE
+            && packet[0..4] == config.protocol_header
+            && packet[4..8] == marker {
+
+            Some(packet[8..16].iter().fold(0, |challenge, byte| {
+                challenge << 8 | *byte as u64
+            }))
+
+        } else {
+            None
+        }
+    }
+
+    #[cfg(feature = "authentication")]
+    fn server_info_signed(config: &Config) -> bool {
+        config.server_info_signing_key.is_some()
+    }
+
+    #[cfg(not(feature = "authentication"))]
+    fn server_info_signed(_: &Config) -> bool {
+        false
+    }
+
+    #[cfg(feature = "authentication")]
+    fn sign_server_info(config: &Config, packet: &mut Vec<u8>) {
+        if let Some(key) = config.server_info_signing_key {
+            authentication::sign_with(&key, packet);
+        }
+    }
+
+    #[cfg(not(feature = "authentication"))]
+    fn sign_server_info(_: &Config, _: &mut Vec<u8>) {}
+
+    #[cfg(feature = "authentication")]
+    fn verify_server_info(config: &Config, signed: bool, packet: &mut Vec<u8>) -> bool {
+        match config.server_info_public_key {
+            Some(key) => signed && authentication::verify_signed(&key, packet),
+            None => Connection::strip_server_info_signature(signed, packet)
+        }
+    }
+
+    #[cfg(not(feature = "authentication"))]
+    fn verify_server_info(_: &Config, signed: bool, packet: &mut Vec<u8>) -> bool {
+        Connection::strip_server_info_signature(signed, packet)
+    }
+
+    fn strip_server_info_signature(signed: bool, packet: &mut Vec<u8>) -> bool {
+        if !signed {
+            true
+
+        } else if packet.len() >= SERVER_INFO_HEADER_SIZE + SERVER_INFO_SIGNATURE_SIZE {
+            let length = packet.len() - SERVER_INFO_SIGNATURE_SIZE;
+            packet.truncate(length);
+            true
+
+        } else {
+            false
+        }
+    }
+
+    /// Limits the size of the next packet to the specified number of bytes,
+    /// headers included, in order to distribute a server's outgoing
+    /// bandwidth budget across its connections.
+    ///
+    /// Messages are still limited by `Config::packet_max_size` and the
+    /// connection's own bandwidth limit.
+    pub(crate) fn set_send_budget(&mut self, bytes: usize) {
+        self.send_budget = Some(bytes);
+    }
+
+    /// Counts a received packet which was dropped for the specified `reason`
+    /// and notifies the handler about it.
+    pub(crate) fn drop_packet<O>(
+        &mut self, reason: PacketDropReason,
+        owner: &mut O, handler: &mut dyn Handler<O>
+    ) {
+        self.security_stats.record(reason);
+        handler.packet_dropped(owner, self.peer_address, reason);
+    }
+
+    #[cfg(feature = "encryption")]
+    fn decrypt_packet(&mut self, packet: Vec<u8>) -> Result<Vec<u8>, PacketDropReason> {
+        let header_size = self.header_size();
+        let decrypted = if let Some(decrypted) = self.decrypt_exchanged(&packet, header_size) {
+            decrypted
+
+        } else if let Some(decrypted) = self.decrypt_rotated(&packet, header_size) {
+            decrypted
+
+        } else {
+            match self.cipher {
+                Some(ref cipher) => {
+                    cipher.decrypt(&packet[..header_size], &packet[header_size..]).ok_or(
+                        PacketDropReason::AuthenticationFailed
+                    )?
+                },
+                None => return Ok(packet)
+            }
+        };
+
+        // Drop authenticated packets which were send in our own direction,
+        // i.e. reflected back at us, or whose nonce was already received
+        let counter = PacketCipher::nonce_counter(&packet[header_size..]);
+        let reflected = (counter & ACCEPTED_NONCE_BIT != 0) == self.accepted;
+        if !reflected && self.replay_window.accept(counter & !ACCEPTED_NONCE_BIT) {
+            Ok(decrypted)
+
+        } else {
+            self.replayed_packets = self.replayed_packets.wrapping_add(1);
+            Err(PacketDropReason::Replayed)
+        }
+    }
+
+    #[cfg(not(feature = "encryption"))]
+    fn decrypt_packet(&mut self, packet: Vec<u8>) -> Result<Vec<u8>, PacketDropReason> {
+        Ok(packet)
+    }
+
+    #[cfg(feature = "key_exchange")]
+    fn reset_key_exchange(&mut self) {
+
+        self.session_key = None;
+        self.exchange_cipher = None;
+        self.key_exchange_handle = None;
+        self.key_exchange = None;
+        self.resumption_secret = None;
+
+        // Drop any cipher derived from a previous exchange
+        if self.config.key_exchange && self.config.key_exchange_encryption {
+            let id = self.random_id;
+            self.cipher = self.config.encryption_key.map(|key| {
+                PacketCipher::derive(key, id)
+            });
+        }
+
+    }
+
+    #[cfg(all(feature = "encryption", not(feature = "key_exchange")))]
+    fn reset_key_exchange(&mut self) {
+        self.exchange_cipher = None;
+        self.key_exchange_handle = None;
+        self.resumption_secret = None;
+    }
+
+    #[cfg(not(feature = "encryption"))]
+    fn reset_key_exchange(&mut self) {}
+
+    // Generates the ephemeral key pair only once the handshake starts, so
+    // connections which never send anything do not pay for it
+    #[cfg(feature = "key_exchange")]
+    fn start_key_exchange(&mut self) {
+        if self.config.key_exchange && self.key_exchange.is_none() && self.session_key.is_none()
+            && self.resumption_secret.is_none() {
+            let exchange = KeyExchange::new();
+            self.key_exchange_handle = Some(self.message_queue.send_control_tracked(
+                Control::KeyExchange {
+                    public: exchange.public_key()
+
+                }.encode()
+            ));
+            self.key_exchange = Some(exchange);
+        }
+    }
+
+    #[cfg(not(feature = "key_exchange"))]
+    fn start_key_exchange(&mut self) {}
 
-                // Push messages from lost packets into the queue
-                self.message_queue.lost_packet(&lost_packet[PACKET_HEADER_SIZE..]);
+    #[cfg(feature = "key_exchange")]
+    fn complete_key_exchange(&mut self, public: [u8; 32]) {
 
-                // Optional packet lost notification
-                if cfg!(feature = "packet_handler_lost") {
-                    handler.connection_packet_lost(
-                        owner, self, &lost_packet[PACKET_HEADER_SIZE..]
-                    );
-                }
+        if !self.config.key_exchange {
+            return;
+        }
 
+        // The remote started a new exchange instead of resuming the session
+        // of a ticket, or before the local end started its own
+        self.discard_restored_session();
+
+        if let Some(key) = self.key_exchange.as_mut().and_then(|exchange| {
+            exchange.complete(public)
+        }) {
+            self.session_key = Some(key);
+            if self.config.key_exchange_encryption {
+                self.exchange_cipher = Some(PacketCipher::derive(key, self.random_id));
             }
+        }
+    }
+
+    #[cfg(not(feature = "key_exchange"))]
+    fn complete_key_exchange(&mut self, _: [u8; 32]) {}
 
+    #[cfg(feature = "encryption")]
+    fn key_exchange_delivered(&mut self, handle: MessageHandle) -> bool {
+        if self.key_exchange_handle == Some(handle) {
+            // The remote now knows the local public key or nonce and is
+            // therefore able to decrypt packets using the exchanged key
+            self.key_exchange_handle = None;
+            true
+
+        } else {
+            false
         }
+    }
 
-        // Push packet data into message queue
-        if cfg!(feature = "packet_handler_compress") {
+    #[cfg(feature = "connect_token")]
+    fn send_connect_token(&mut self) {
+        if self.state == ConnectionState::Connecting {
+            if let Some(ref data) = self.connect_token_request {
+                self.message_queue.send_control(
+                    MessageKind::Instant,
+                    Control::ConnectToken {
+                        data: data.clone()
+
+                    }.encode()
+                );
+            }
+        }
+    }
 
-            // Optional packet decompression
-            let packet = handler.connection_packet_decompress(
-                owner, self,
-                &packet[PACKET_HEADER_SIZE..]
-            );
+    #[cfg(not(feature = "connect_token"))]
+    fn send_connect_token(&mut self) {}
 
-            self.message_queue.receive_packet(&packet[..]);
+    #[cfg(feature = "connect_token")]
+    fn reset_connect_token(&mut self) {
+        self.connect_token_request = None;
+        self.connect_token = None;
+    }
 
-        } else {
-            self.message_queue.receive_packet(&packet[PACKET_HEADER_SIZE..]);
+    #[cfg(not(feature = "connect_token"))]
+    fn reset_connect_token(&mut self) {}
+
+    #[cfg(feature = "encryption")]
+    fn send_resumption_ticket(&mut self) {
+        if self.state == ConnectionState::Connecting {
+            if let Some(ref data) = self.resumption_ticket_request {
+                let nonce = self.resumption_secret.map(|(_, nonce)| nonce).unwrap_or_default();
+                self.message_queue.send_control(
+                    MessageKind::Instant,
+                    Control::ResumeTicket {
+                        nonce,
+                        data: data.clone()
+
+                    }.encode()
+                );
+            }
         }
+    }
 
-        // Remove all acknowledged and lost packets from the sent ack queue
-        self.sent_ack_queue.retain(|p| p.state == PacketState::Unknown);
+    #[cfg(not(feature = "encryption"))]
+    fn send_resumption_ticket(&mut self) {}
 
-        // Insert packet into receive acknowledgment queue
-        self.recv_ack_queue.push_front(self.remote_seq_number);
+    #[cfg(feature = "encryption")]
+    fn receive_resumption_ticket(&mut self, resumed: bool, data: Vec<u8>, connecting: bool) {
+        if connecting {
+            self.resumed = resumed && self.resumption_ticket_request.is_some();
+        }
 
-        // Don't keep more entries than we can actually acknowledge per packet
-        if self.recv_ack_queue.len() > MAX_ACK_BITS as usize {
-            self.recv_ack_queue.pop_back();
+        // Only keep tickets which were issued for our id
+        if ResumptionTicket::id_from_sealed(&data) == Some(self.random_id) {
+            self.resumption_ticket = Some(ResumptionTicket {
+                id: self.random_id,
+                session_key: self.established_session_key(),
+                data
+            });
         }
+    }
 
-        // Update packet statistics
-        self.recv_packets = self.recv_packets.wrapping_add(1);
+    #[cfg(not(feature = "encryption"))]
+    fn receive_resumption_ticket(&mut self, _: bool, _: Vec<u8>, _: bool) {}
+
+    #[cfg(feature = "encryption")]
+    fn reject_resumption_ticket(&mut self) {
+        // Establish a new session in case the remote did not accept the
+        // session of our ticket
+        if let Some(data) = self.resumption_ticket_request.take() {
+            if !self.resumed {
+                if self.resumption_ticket.as_ref().is_some_and(|t| t.data == data) {
+                    self.resumption_ticket = None;
+                }
+                self.discard_restored_session();
+            }
+        }
+    }
 
+    #[cfg(not(feature = "encryption"))]
+    fn reject_resumption_ticket(&mut self) {}
+
+    #[cfg(feature = "encryption")]
+    fn reset_resumption_ticket(&mut self) {
+        self.resumption_ticket = None;
+        self.resumption_ticket_request = None;
     }
 
-    /// Send a new outgoing UDP packet.
-    pub fn send_packet<O, S: Socket>(
-        &mut self,
-        socket: &mut S, addr: &SocketAddr,
-        owner: &mut O, handler: &mut Handler<O>
+    #[cfg(not(feature = "encryption"))]
+    fn reset_resumption_ticket(&mut self) {}
+
+    // Restores the session of a ticket, with a fresh nonce for deriving the
+    // key of the resumed session
+    #[cfg(feature = "encryption")]
+    fn start_resumption(&mut self, session_key: Option<[u8; 32]>) {
+        self.restore_session(session_key);
+        self.resumption_secret = session_key.or(self.config.encryption_key).map(|secret| {
+            let mut nonce = [0; RESUMPTION_NONCE_BYTES];
+            random_bytes(&mut nonce);
+            (secret, nonce)
+        });
+    }
 
-    ) -> u32 {
+    #[cfg(feature = "encryption")]
+    fn complete_resumption(&mut self, remote_nonce: [u8; RESUMPTION_NONCE_BYTES], from_client: bool) {
 
-        // Update connection state
-        if !self.update_send_state(owner, handler) {
-            return 0;
+        // Clients send their nonce along with the ticket and servers answer
+        // with theirs
+        if from_client != self.accepted {
+            return;
         }
 
-        let congested = self.rate_limiter.congested();
-        let rtt = self.rtt();
-        let packet_loss = self.packet_loss();
+        let (secret, nonce) = match self.resumption_secret.take() {
+            Some(pending) => pending,
+            None => return
+        };
 
-        // Update congestion state
-        self.rate_limiter.update(rtt, packet_loss);
+        // Neither the packets nor the nonces of the previous session are
+        // valid under the fresh key
+        let key = if self.accepted {
+            resumption_key(&secret, &remote_nonce, &nonce)
 
-        // Check if the state changed and invoke handler
-        if congested != self.rate_limiter.congested() {
-            handler.connection_congestion_state(owner, self, !congested);
+        } else {
+            resumption_key(&secret, &nonce, &remote_nonce)
+        };
+
+        #[cfg(feature = "key_exchange")]
+        {
+            if self.config.key_exchange {
+                self.session_key = Some(key);
+            }
         }
 
-        // Check if we should be sending packets, if not skip this packet
-        if !self.rate_limiter.should_send() {
-            return 0;
+        if self.cipher.is_some() || self.exchange_encryption() {
+            self.exchange_cipher = Some(PacketCipher::derive(key, self.random_id));
         }
 
-        // Take write buffer out and insert a fresh, empty one in its place
-        let mut packet = Vec::<u8>::with_capacity(PACKET_HEADER_SIZE);
+        // Only switch to the fresh key once the client knows our nonce
+        if self.accepted {
+            self.key_exchange_handle = Some(self.message_queue.send_control_tracked(
+                Control::ResumeNonce { nonce }.encode()
+            ));
+        }
 
-        // Set packet protocol header
-        packet.push(self.config.protocol_header[0]);
-        packet.push(self.config.protocol_header[1]);
-        packet.push(self.config.protocol_header[2]);
-        packet.push(self.config.protocol_header[3]);
+    }
 
-        // Set connection ID
-        packet.push((self.random_id.0 >> 24) as u8);
-        packet.push((self.random_id.0 >> 16) as u8);
-        packet.push((self.random_id.0 >> 8) as u8);
-        packet.push(self.random_id.0 as u8);
+    #[cfg(not(feature = "encryption"))]
+    fn complete_resumption(&mut self, _: [u8; 16], _: bool) {}
 
-        // Send closing packets if required
-        if self.state == ConnectionState::Closing {
-            packet.extend_from_slice(&CLOSURE_PACKET_DATA);
+    #[cfg(feature = "key_exchange")]
+    fn restore_session(&mut self, session_key: Option<[u8; 32]>) {
+        if let (true, Some(_)) = (self.config.key_exchange, session_key) {
 
-        } else {
+            // Skip the exchange which was started for a new session
+            if let Some(handle) = self.key_exchange_handle.take() {
+                self.message_queue.cancel(handle);
+            }
 
-            // Set local sequence number
-            packet.push(self.local_seq_number as u8);
+            self.key_exchange = None;
 
-            // Set packet ack number
-            packet.push(self.remote_seq_number as u8);
+        }
+    }
 
-            // Construct ack bitfield from most recently received packets
-            let mut bitfield: u32 = 0;
-            for seq in &self.recv_ack_queue {
+    #[cfg(all(feature = "encryption", not(feature = "key_exchange")))]
+    fn restore_session(&mut self, _: Option<[u8; 32]>) {}
 
-                // Ignore the remote sequence as it already gets set in the header
-                if *seq != self.remote_seq_number {
+    #[cfg(feature = "key_exchange")]
+    fn discard_restored_session(&mut self) {
+        if self.key_exchange.is_none() {
+            self.reset_key_exchange();
+            self.start_key_exchange();
 
-                    // Calculate bitfield index
-                    let bit = seq_bit_index(*seq, self.remote_seq_number);
+        } else {
+            self.resumption_secret = None;
+        }
+    }
 
-                    // Set ack bit
-                    if bit < MAX_ACK_BITS {
-                        bitfield |= (1 << bit) as u32;
-                    }
+    #[cfg(all(feature = "encryption", not(feature = "key_exchange")))]
+    fn discard_restored_session(&mut self) {
+        self.reset_key_exchange();
+    }
 
-                }
+    #[cfg(feature = "key_exchange")]
+    fn session_established(&self) -> bool {
+        !self.config.key_exchange || self.session_key.is_some()
+    }
 
-            }
+    #[cfg(all(feature = "encryption", not(feature = "key_exchange")))]
+    fn session_established(&self) -> bool {
+        true
+    }
 
-            // Set ack bitfield
-            packet.push((bitfield >> 24) as u8);
-            packet.push((bitfield >> 16) as u8);
-            packet.push((bitfield >> 8) as u8);
-            packet.push(bitfield as u8);
+    #[cfg(feature = "key_exchange")]
+    fn established_session_key(&self) -> Option<[u8; 32]> {
+        self.session_key
+    }
 
-            // Write messages from queue into the packet
-            self.message_queue.send_packet(
-                &mut packet, self.config.packet_max_size - PACKET_HEADER_SIZE
-            );
+    #[cfg(all(feature = "encryption", not(feature = "key_exchange")))]
+    fn established_session_key(&self) -> Option<[u8; 32]> {
+        None
+    }
 
+    #[cfg(feature = "authentication")]
+    fn send_handshake_cookie(&mut self) {
+        if self.state == ConnectionState::Connecting {
+            if let Some(ref data) = self.handshake_cookie {
+                self.message_queue.send_control(
+                    MessageKind::Instant,
+                    Control::HandshakeCookie {
+                        data: data.clone()
+
+                    }.encode()
+                );
+            }
         }
+    }
 
-        // Send packet to socket
-        let bytes_sent = if cfg!(feature = "packet_handler_compress") {
+    #[cfg(not(feature = "authentication"))]
+    fn send_handshake_cookie(&mut self) {}
 
-            // Optional packet compression
-            let packet = handler.connection_packet_compress(
-                owner, self,
-                packet[..PACKET_HEADER_SIZE].to_vec(),
-                &packet[PACKET_HEADER_SIZE..]
-            );
+    #[cfg(feature = "authentication")]
+    fn reset_handshake_cookie(&mut self) {
+        self.handshake_cookie = None;
+    }
 
-            socket.send_to(
-                &packet[..], *addr
+    #[cfg(not(feature = "authentication"))]
+    fn reset_handshake_cookie(&mut self) {}
 
-            ).expect(&format!("Failed to send compressed packet to {:?}", addr));
+    #[cfg(any(feature = "encryption", feature = "authentication"))]
+    fn control_from_packet(config: &Config, packet: &[u8]) -> Vec<Control> {
 
-            // Number of all bytes sent
-            packet.len()
+        let header_size = if config.packet_types {
+            PACKET_HEADER_SIZE + 1
 
         } else {
-            socket.send_to(
-                &packet[..], *addr
+            PACKET_HEADER_SIZE
+        };
 
-            ).expect(&format!("Failed to send packet to {:?}", addr));
+        if packet.len() < header_size {
+            return Vec::new();
+        }
 
-            // Number of all bytes sent
-            packet.len()
+        // Packets of encrypted connections must be decrypted first
+        let decrypted = match Connection::decrypt_first_packet(config, packet, header_size) {
+            Some(decrypted) => decrypted,
+            None => return Vec::new()
         };
 
+        let (_, body) = split_tick_number(&decrypted, header_size);
+        MessageQueue::control_from_packet(body).iter().filter_map(|data| {
+            Control::decode(data)
 
-        // Insert packet into send acknowledgment queue (but avoid dupes)
-        if self.send_ack_required(self.local_seq_number) {
-            self.sent_ack_queue.push(SentPacketAck {
-                seq: self.local_seq_number,
-                time: Instant::now(),
-                state: PacketState::Unknown,
-                packet: Some(packet)
-            });
-        }
+        }).collect()
 
-        // Increase local sequence number and wrap around
-        self.local_seq_number += 1;
+    }
 
-        if self.local_seq_number == MAX_SEQ_NUMBER {
-            self.local_seq_number = 0;
+    #[cfg(feature = "encryption")]
+    fn decrypt_first_packet(config: &Config, packet: &[u8], header_size: usize) -> Option<Vec<u8>> {
+        match config.encryption_key {
+            Some(key) => {
+                let id = Connection::id_from_packet(config, packet)?;
+                let body = &packet[header_size..];
+                PacketCipher::derive(key, id).decrypt(&packet[..header_size], body).filter(|_| {
+                    // Only accept packets of the initiating end
+                    PacketCipher::nonce_counter(body) & ACCEPTED_NONCE_BIT == 0
+                })
+            },
+            None => Some(packet.to_vec())
         }
+    }
 
-        // Update packet statistics
-        self.sent_packets = self.sent_packets.wrapping_add(1);
+    #[cfg(all(feature = "authentication", not(feature = "encryption")))]
+    fn decrypt_first_packet(_: &Config, packet: &[u8], _: usize) -> Option<Vec<u8>> {
+        Some(packet.to_vec())
+    }
 
-        // Dismiss any pending, received messages
-        self.message_queue.dismiss();
+    #[cfg(not(feature = "encryption"))]
+    fn key_exchange_delivered(&mut self, _: MessageHandle) -> bool {
+        false
+    }
 
-        // Return number of bytes sent over the socket
-        bytes_sent as u32
+    #[cfg(feature = "key_exchange")]
+    fn exchange_encryption(&self) -> bool {
+        self.config.key_exchange && self.config.key_exchange_encryption
+    }
 
+    #[cfg(all(feature = "encryption", not(feature = "key_exchange")))]
+    fn exchange_encryption(&self) -> bool {
+        false
     }
 
-    /// Resets the connection for re-use with another address.
-    pub fn reset(&mut self) {
-        self.state = ConnectionState::Connecting;
-        self.local_seq_number = 0;
-        self.remote_seq_number = 0;
-        self.smoothed_rtt = 0.0;
-        self.last_receive_time = Instant::now();
-        self.recv_ack_queue.clear();
-        self.sent_ack_queue.clear();
-        self.sent_packets = 0;
-        self.recv_packets = 0;
-        self.acked_packets = 0;
-        self.lost_packets = 0;
-        self.message_queue.reset();
-        self.rate_limiter.reset();
+    #[cfg(feature = "encryption")]
+    fn send_cipher(&self) -> Option<&PacketCipher> {
+        match self.exchange_cipher {
+            Some(ref cipher) if self.key_exchange_handle.is_none()
+                && self.resumption_ticket_request.is_none() => Some(cipher),
+            _ => self.key_cipher()
+        }
     }
 
-    /// Closes the connection, no further packets will be received or send.
-    pub fn close(&mut self) {
-        self.config.connection_drop_threshold = 20;
-        self.state = ConnectionState::Closing;
+    #[cfg(feature = "encryption")]
+    fn key_exchange_pending(&self) -> bool {
+        self.exchange_cipher.is_some()
     }
 
+    #[cfg(feature = "encryption")]
+    fn key_cipher(&self) -> Option<&PacketCipher> {
+        match self.rotated_cipher {
+            Some(ref cipher) if self.key_rotation_handle.is_none() => Some(cipher),
+            _ => self.cipher.as_ref()
+        }
+    }
 
-    // Internal State Handling ------------------------------------------------
+    #[cfg(feature = "encryption")]
+    fn update_key_rotation(&mut self) {
+        if self.state == ConnectionState::Connected && self.cipher.is_some() {
 
-    fn update_receive_state<T>(
-        &mut self, packet: &[u8], owner: &mut T, handler: &mut Handler<T>
+            self.key_rotation_packets = self.key_rotation_packets.saturating_add(1);
 
-    ) -> bool {
+            let interval = self.config.key_rotation_interval;
+            let packets = self.config.key_rotation_packets;
+            let elapsed = self.key_rotation_time.elapsed();
+            if (interval > 0 && elapsed >= Duration::from_millis(interval as u64))
+                || (packets > 0 && self.key_rotation_packets >= packets) {
+                self.rotate_key();
+            }
 
-        // Ignore any packets which do not match the desired protocol header
-        &packet[0..4] == &self.config.protocol_header && match self.state {
+        }
+    }
 
-            ConnectionState::Lost |
-            ConnectionState::Closed |
-            ConnectionState::FailedToConnect => false,
+    #[cfg(not(feature = "encryption"))]
+    fn update_key_rotation(&mut self) {}
 
-            ConnectionState::Closing => true,
+    #[cfg(feature = "encryption")]
+    fn receive_key_rotation(&mut self, epoch: u32) {
+        if epoch == self.key_epoch.wrapping_add(1) && self.rotated_cipher.is_none() {
+            // The remote keeps using the current key until the announcement
+            // was acknowledged, so the new key can be used right away
+            self.rotated_cipher = self.cipher.as_ref().map(|cipher| cipher.rotate(epoch));
+        }
+    }
 
-            ConnectionState::Connecting => {
+    #[cfg(not(feature = "encryption"))]
+    fn receive_key_rotation(&mut self, _: u32) {}
 
-                // Once we receive the first valid packet we consider the
-                // connection as established
-                self.state = ConnectionState::Connected;
+    #[cfg(feature = "encryption")]
+    fn key_rotation_delivered(&mut self, handle: MessageHandle) -> bool {
+        if self.key_rotation_handle == Some(handle) {
+            // The remote now knows about the next epoch and is therefore
+            // able to decrypt packets using the rotated key
+            self.key_rotation_handle = None;
+            true
 
-                // Reset Packet Loss upon connection
-                self.lost_packets = 0;
+        } else {
+            false
+        }
+    }
 
-                handler.connection(owner, self);
+    #[cfg(not(feature = "encryption"))]
+    fn key_rotation_delivered(&mut self, _: MessageHandle) -> bool {
+        false
+    }
 
-                true
+    #[cfg(feature = "encryption")]
+    fn decrypt_rotated(&mut self, packet: &[u8], header_size: usize) -> Option<Vec<u8>> {
+
+        let decrypted = self.rotated_cipher.as_ref().and_then(|cipher| {
+            cipher.decrypt(&packet[..header_size], &packet[header_size..])
+        });
+
+        // Once the remote encrypts its packets with the rotated key, the
+        // previous key is no longer accepted
+        if decrypted.is_some() {
+            let previous = self.cipher.take();
+            self.unrotated_cipher = self.unrotated_cipher.take().or(previous);
+            self.cipher = self.rotated_cipher.take();
+            self.key_epoch = self.key_epoch.wrapping_add(1);
+            self.key_rotation_handle = None;
+            self.key_rotation_time = Instant::now();
+            self.key_rotation_packets = 0;
+        }
 
-            },
+        decrypted
 
-            ConnectionState::Connected => {
+    }
 
-                // Check for closure packet from remote
-                if &packet[8..14] == &CLOSURE_PACKET_DATA {
-                    self.state = ConnectionState::Closed;
-                    handler.connection_closed(owner, self, true);
-                    false
+    #[cfg(feature = "encryption")]
+    fn reset_replay_window(&mut self) {
+        // Start at a random counter, so the nonces of a connection which
+        // re-uses the key of a previous one are not re-used either
+        self.nonce_counter = rand::random::<u64>() >> 2;
+        self.replay_window = ReplayWindow::new();
+        self.replayed_packets = 0;
+    }
 
-                } else {
-                    // Check if the packet sequence number is more recent,
-                    // otherwise drop it as a duplicate
-                    seq_is_more_recent(
-                        packet[8] as u32, self.remote_seq_number
-                    )
-                }
+    #[cfg(not(feature = "encryption"))]
+    fn reset_replay_window(&mut self) {}
 
-            }
+    #[cfg(feature = "encryption")]
+    fn reset_key_rotation(&mut self) {
+        if let Some(cipher) = self.unrotated_cipher.take() {
+            self.cipher = Some(cipher);
+        }
+        self.key_epoch = 0;
+        self.rotated_cipher = None;
+        self.key_rotation_handle = None;
+        self.key_rotation_time = Instant::now();
+        self.key_rotation_packets = 0;
+    }
+
+    #[cfg(not(feature = "encryption"))]
+    fn reset_key_rotation(&mut self) {}
+
+    #[cfg(feature = "encryption")]
+    fn decrypt_exchanged(&mut self, packet: &[u8], header_size: usize) -> Option<Vec<u8>> {
+
+        let decrypted = self.exchange_cipher.as_ref().and_then(|cipher| {
+            cipher.decrypt(&packet[..header_size], &packet[header_size..])
+        });
 
+        // Once the remote encrypts its packets with the exchanged key, it
+        // has completed the exchange and only encrypted packets are accepted
+        if decrypted.is_some() {
+            self.reset_key_rotation();
+            self.unrotated_cipher = self.cipher.take();
+            self.cipher = self.exchange_cipher.take();
         }
 
+        decrypted
+
     }
 
-    fn update_send_state<T>(
-        &mut self, owner: &mut T, handler: &mut Handler<T>
+    fn packet_type(&self, packet: &[u8]) -> Option<PacketType> {
+        if self.config.packet_types {
+            PacketType::from_byte(packet[PACKET_HEADER_SIZE])
 
-    ) -> bool {
+        } else if packet[8..PACKET_HEADER_SIZE] == CLOSURE_PACKET_DATA {
+            Some(PacketType::Close)
 
-        // Calculate time since last received packet
-        let inactive_time = dur_as_ms(self.last_receive_time.elapsed());
+        } else {
+            Some(PacketType::Data)
+        }
+    }
 
-        match self.state {
+    fn write_header(&self, packet: &mut Vec<u8>, seq: u32, packet_type: PacketType) {
 
-            ConnectionState::Lost |
-            ConnectionState::Closed |
-            ConnectionState::FailedToConnect => false,
+        // Set packet protocol header
+        packet.extend_from_slice(&self.config.protocol_header);
 
-            ConnectionState::Connecting => {
+        // Set connection ID
+        packet.push((self.random_id.0 >> 24) as u8);
+        packet.push((self.random_id.0 >> 16) as u8);
+        packet.push((self.random_id.0 >> 8) as u8);
+        packet.push(self.random_id.0 as u8);
 
-                // Quickly detect initial connection failures
-                if inactive_time > self.config.connection_init_threshold {
-                    self.state = ConnectionState::FailedToConnect;
-                    handler.connection_failed(owner, self);
-                    false
+        // Without packet types closure is signaled via special packet data
+        if !self.config.packet_types && packet_type == PacketType::Close {
+            packet.extend_from_slice(&CLOSURE_PACKET_DATA);
+            return;
+        }
 
-                } else {
-                    true
-                }
+        // Set local sequence number
+        packet.push(seq as u8);
 
-            },
+        // Set packet ack number
+        packet.push(self.remote_seq_number as u8);
 
-            ConnectionState::Connected => {
+        // Set ack bitfield
+        let bitfield = self.ack_bitfield();
+        packet.push((bitfield >> 24) as u8);
+        packet.push((bitfield >> 16) as u8);
+        packet.push((bitfield >> 8) as u8);
+        packet.push(bitfield as u8);
 
-                // Detect connection timeouts
-                if inactive_time > self.config.connection_drop_threshold {
-                    self.state = ConnectionState::Lost;
-                    handler.connection_lost(owner, self);
-                    false
+        // Set packet type
+        if self.config.packet_types {
+            packet.push(packet_type as u8);
+        }
 
-                } else {
-                    true
-                }
+    }
 
-            },
+    fn ack_bitfield(&self) -> u32 {
 
-            ConnectionState::Closing => {
+        // Construct ack bitfield from most recently received packets
+        let mut bitfield: u32 = 0;
+        for seq in &self.recv_ack_queue {
 
-                // Detect connection closure
-                if inactive_time > self.config.connection_drop_threshold {
-                    self.state = ConnectionState::Closed;
-                    handler.connection_closed(owner, self, false);
-                    false
+            // Ignore the remote sequence as it already gets set in the header
+            if *seq != self.remote_seq_number {
 
-                } else {
-                    true
+                // Calculate bitfield index
+                let bit = seq_bit_index(*seq, self.remote_seq_number);
+
+                // Set ack bit
+                if bit < MAX_ACK_BITS {
+                    bitfield |= (1 << bit) as u32;
                 }
 
             }
 
         }
 
+        bitfield
+
     }
 
-    // Internal Helpers -------------------------------------------------------
     fn send_ack_required(&self, seq: u32) -> bool {
         !self.sent_ack_queue.iter().any(|p| p.seq == seq)
     }
@@ -720,6 +3807,22 @@ impl Connection {
 }
 
 // Static Helpers -------------------------------------------------------------
+fn split_tick_number(packet: &[u8], header_size: usize) -> (Option<u32>, &[u8]) {
+    let body = &packet[header_size..];
+    let flagged = header_size > PACKET_HEADER_SIZE
+        && packet[PACKET_HEADER_SIZE] & TICK_NUMBER_FLAG == TICK_NUMBER_FLAG;
+
+    if flagged && body.len() >= TICK_NUMBER_BYTES {
+        let tick = (body[0] as u32) << 24 | (body[1] as u32) << 16
+                 | (body[2] as u32) << 8  |  body[3] as u32;
+
+        (Some(tick), &body[TICK_NUMBER_BYTES..])
+
+    } else {
+        (None, body)
+    }
+}
+
 fn moving_average(a: f32, b: f32) -> f32 {
     (a - (a - b) * 0.10).max(0.0)
 }
@@ -749,6 +3852,40 @@ fn seq_was_acked(seq: u32, ack: u32, bitfield: u32) -> bool {
 }
 
 fn dur_as_ms(dur: Duration) -> u32 {
-    (dur.as_secs() as u32 * 1000) + (dur.subsec_nanos() / 1000_000)
+    (dur.as_secs() as u32 * 1000) + dur.subsec_millis()
+}
+
+
+#[cfg(feature = "authentication")]
+fn migration_proof(secret: &[u8; 32], nonce: u64, id: ConnectionID) -> Option<[u8; 16]> {
+    Some(authentication::tag(secret, &migration_challenge(nonce, id)))
+}
+
+#[cfg(not(feature = "authentication"))]
+fn migration_proof(_: &[u8; 32], _: u64, _: ConnectionID) -> Option<[u8; 16]> {
+    None
+}
+
+#[cfg(feature = "authentication")]
+fn verify_migration_proof(secret: &[u8; 32], nonce: u64, id: ConnectionID, proof: &[u8; 16]) -> bool {
+    authentication::verify_tag(secret, &migration_challenge(nonce, id), proof)
+}
+
+#[cfg(not(feature = "authentication"))]
+fn verify_migration_proof(_: &[u8; 32], _: u64, _: ConnectionID, _: &[u8; 16]) -> bool {
+    false
+}
+
+#[cfg(feature = "authentication")]
+fn migration_challenge(nonce: u64, id: ConnectionID) -> [u8; 12] {
+    let mut data = [0; 12];
+    for (i, byte) in data[..8].iter_mut().enumerate() {
+        *byte = (nonce >> (56 - i * 8)) as u8;
+    }
+    data[8] = (id.0 >> 24) as u8;
+    data[9] = (id.0 >> 16) as u8;
+    data[10] = (id.0 >> 8) as u8;
+    data[11] = id.0 as u8;
+    data
 }
 
@@ -0,0 +1,71 @@
+// Copyright (c) 2015-2016 Ivo Wetzel
+
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use super::super::ConnectionID;
+
+/// Constant mixed into every seed so generators of different purposes do not
+/// share sequences.
+const SEED_SALT: u64 = 0x636f_6261_6c74_726e;
+
+/// Deterministic pseudo random number generator of a single connection.
+///
+/// The generator is seeded from the connection's id which is agreed upon
+/// during the handshake, so both ends of a connection produce the very same
+/// sequence as long as they draw the same number of values.
+///
+/// The sequence is based on SplitMix64 and is stable across versions. It is
+/// **not** suitable for any security related purposes.
+#[derive(Debug, Copy, Clone)]
+pub struct ConnectionRng {
+    state: u64
+}
+
+impl ConnectionRng {
+
+    /// Creates a new generator which is seeded from the specified connection
+    /// `id`.
+    pub fn new(id: ConnectionID) -> ConnectionRng {
+        ConnectionRng {
+            state: id.0 as u64 ^ SEED_SALT
+        }
+    }
+
+    /// Returns the next pseudo random `u64`.
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Returns the next pseudo random `u32`.
+    pub fn next_u32(&mut self) -> u32 {
+        (self.next_u64() >> 32) as u32
+    }
+
+    /// Returns the next pseudo random `f32` in the range of `0.0` to `1.0`,
+    /// excluding `1.0`.
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u32() >> 8) as f32 / (1u32 << 24) as f32
+    }
+
+    /// Returns the next pseudo random `u32` in the range of `low` to `high`,
+    /// excluding `high`.
+    ///
+    /// Returns `low` without drawing a value in case the range is empty.
+    pub fn range(&mut self, low: u32, high: u32) -> u32 {
+        if high <= low {
+            low
+
+        } else {
+            let span = (high - low) as u64;
+            low + ((self.next_u32() as u64 * span) >> 32) as u32
+        }
+    }
+
+}
@@ -0,0 +1,389 @@
+// Copyright (c) 2015-2016 Ivo Wetzel
+
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use super::host_migration::Peer;
+use super::super::ConnectionID;
+
+/// Enum of internal control messages exchanged between the two ends of a
+/// connection.
+#[derive(Debug, PartialEq)]
+pub enum Control {
+
+    /// Issues a token to the remote which allows it to resume the connection
+    /// later on, also indicating whether the connection itself was resumed.
+    ResumptionToken {
+        secret: u64,
+        resumed: bool
+    },
+
+    /// Requests the resumption of a previously lost connection.
+    Resume {
+        secret: u64
+    },
+
+    /// Issues a token to the remote which allows it to prove its identity
+    /// when migrating to a different address.
+    MigrationToken {
+        secret: [u8; 32]
+    },
+
+    /// Challenges the remote to prove its identity at a new address.
+    MigrationChallenge {
+        nonce: u64
+    },
+
+    /// Answers a migration challenge with a proof derived from the migration
+    /// token.
+    MigrationResponse {
+        proof: [u8; 16]
+    },
+
+    /// Shares the list of all peers taking part in the session with the
+    /// remote.
+    Peers {
+        peers: Vec<Peer>
+    },
+
+    /// Shares the host's latest session state with the remote.
+    SessionState {
+        data: Vec<u8>
+    },
+
+    /// Marks a packet which was send only to promptly acknowledge received
+    /// data.
+    Ack,
+
+    /// Shares the public key of an ephemeral key exchange with the remote.
+    KeyExchange {
+        public: [u8; 32]
+    },
+
+    /// Presents a connect token issued by a backend to the server.
+    ConnectToken {
+        data: Vec<u8>
+    },
+
+    /// Announces the rotation of the packet key to the specified epoch.
+    KeyRotation {
+        epoch: u32
+    },
+
+    /// Carries a single message attached to the connection request.
+    EarlyData {
+        data: Vec<u8>
+    },
+
+    /// Rejects the early data attached to the connection request.
+    EarlyDataRejected,
+
+    /// Advertises whether the unconsumed received data exceeds the receive
+    /// watermark, with a sequence number to discard outdated advertisements.
+    ReceivePressure {
+        seq: u8,
+        pressure: bool
+    },
+
+    /// Echoes the handshake cookie issued by the server.
+    HandshakeCookie {
+        data: Vec<u8>
+    },
+
+    /// Issues a sealed resumption ticket to the remote, also indicating
+    /// whether the connection itself was resumed from a ticket.
+    ResumptionTicket {
+        resumed: bool,
+        data: Vec<u8>
+    },
+
+    /// Presents a sealed resumption ticket to the server, along with the
+    /// client's nonce for the key of the resumed session.
+    ResumeTicket {
+        nonce: [u8; 16],
+        data: Vec<u8>
+    },
+
+    /// Accepts a resumption ticket with the server's nonce for the key of
+    /// the resumed session.
+    ResumeNonce {
+        nonce: [u8; 16]
+    },
+
+    /// Requests the remote to keep the connection alive for the specified
+    /// number of milliseconds without receiving any packets.
+    KeepAliveRequest {
+        duration: u32
+    },
+
+    /// Answers a keep alive request with the number of milliseconds granted
+    /// by the remote.
+    KeepAliveGrant {
+        duration: u32
+    }
+
+}
+
+impl Control {
+
+    /// Serializes the control message into its binary representation.
+    pub fn encode(&self) -> Vec<u8> {
+        match *self {
+            Control::ResumptionToken { secret, resumed } => {
+                let mut data = vec![0];
+                data.extend_from_slice(&u64_to_bytes(secret));
+                data.push(resumed as u8);
+                data
+            },
+            Control::Resume { secret } => with_u64(1, secret),
+            Control::MigrationToken { ref secret } => {
+                let mut data = vec![2];
+                data.extend_from_slice(secret);
+                data
+            },
+            Control::MigrationChallenge { nonce } => with_u64(3, nonce),
+            Control::MigrationResponse { ref proof } => {
+                let mut data = vec![4];
+                data.extend_from_slice(proof);
+                data
+            },
+            Control::Peers { ref peers } => {
+                let mut data = vec![5];
+                for peer in peers {
+                    data.push((peer.id.0 >> 24) as u8);
+                    data.push((peer.id.0 >> 16) as u8);
+                    data.push((peer.id.0 >> 8) as u8);
+                    data.push(peer.id.0 as u8);
+                    match peer.addr.ip() {
+                        IpAddr::V4(ip) => {
+                            data.push(4);
+                            data.extend_from_slice(&ip.octets());
+                        },
+                        IpAddr::V6(ip) => {
+                            data.push(6);
+                            data.extend_from_slice(&ip.octets());
+                        }
+                    }
+                    data.push((peer.addr.port() >> 8) as u8);
+                    data.push(peer.addr.port() as u8);
+                }
+                data
+            },
+            Control::SessionState { ref data } => {
+                let mut encoded = vec![6];
+                encoded.extend_from_slice(data);
+                encoded
+            },
+            Control::Ack => vec![7],
+            Control::KeyExchange { ref public } => {
+                let mut data = vec![8];
+                data.extend_from_slice(public);
+                data
+            },
+            Control::ConnectToken { ref data } => {
+                let mut encoded = vec![9];
+                encoded.extend_from_slice(data);
+                encoded
+            },
+            Control::KeyRotation { epoch } => with_u32(10, epoch),
+            Control::EarlyData { ref data } => {
+                let mut encoded = vec![11];
+                encoded.extend_from_slice(data);
+                encoded
+            },
+            Control::EarlyDataRejected => vec![12],
+            Control::ReceivePressure { seq, pressure } => vec![13, seq, pressure as u8],
+            Control::HandshakeCookie { ref data } => {
+                let mut encoded = vec![14];
+                encoded.extend_from_slice(data);
+                encoded
+            },
+            Control::ResumptionTicket { resumed, ref data } => {
+                let mut encoded = vec![15, resumed as u8];
+                encoded.extend_from_slice(data);
+                encoded
+            },
+            Control::ResumeTicket { ref nonce, ref data } => {
+                let mut encoded = vec![16];
+                encoded.extend_from_slice(nonce);
+                encoded.extend_from_slice(data);
+                encoded
+            },
+            Control::KeepAliveRequest { duration } => with_u32(17, duration),
+            Control::KeepAliveGrant { duration } => with_u32(18, duration),
+            Control::ResumeNonce { ref nonce } => {
+                let mut data = vec![19];
+                data.extend_from_slice(nonce);
+                data
+            }
+        }
+    }
+
+    /// Parses a control message from its binary representation, returning
+    /// `None` for unknown or malformed messages.
+    pub fn decode(data: &[u8]) -> Option<Control> {
+        match data.first() {
+            Some(&0) if data.len() == 10 => Some(Control::ResumptionToken {
+                secret: u64_from_bytes(&data[1..9]),
+                resumed: data[9] == 1
+            }),
+            Some(&1) if data.len() == 9 => Some(Control::Resume {
+                secret: u64_from_bytes(&data[1..9])
+            }),
+            Some(&2) if data.len() == 33 => {
+                let mut secret = [0; 32];
+                secret.copy_from_slice(&data[1..]);
+                Some(Control::MigrationToken { secret })
+            },
+            Some(&3) if data.len() == 9 => Some(Control::MigrationChallenge {
+                nonce: u64_from_bytes(&data[1..9])
+            }),
+            Some(&4) if data.len() == 17 => {
+                let mut proof = [0; 16];
+                proof.copy_from_slice(&data[1..]);
+                Some(Control::MigrationResponse { proof })
+            },
+            Some(&5) => peers_from_bytes(&data[1..]).map(|peers| {
+                Control::Peers { peers }
+            }),
+            Some(&6) => Some(Control::SessionState {
+                data: data[1..].to_vec()
+            }),
+            Some(&7) if data.len() == 1 => Some(Control::Ack),
+            Some(&8) if data.len() == 33 => {
+                let mut public = [0; 32];
+                public.copy_from_slice(&data[1..]);
+                Some(Control::KeyExchange { public })
+            },
+            Some(&9) => Some(Control::ConnectToken {
+                data: data[1..].to_vec()
+            }),
+            Some(&10) if data.len() == 5 => Some(Control::KeyRotation {
+                epoch: u32_from_bytes(&data[1..5])
+            }),
+            Some(&11) => Some(Control::EarlyData {
+                data: data[1..].to_vec()
+            }),
+            Some(&12) if data.len() == 1 => Some(Control::EarlyDataRejected),
+            Some(&13) if data.len() == 3 => Some(Control::ReceivePressure {
+                seq: data[1],
+                pressure: data[2] == 1
+            }),
+            Some(&14) => Some(Control::HandshakeCookie {
+                data: data[1..].to_vec()
+            }),
+            Some(&15) if data.len() >= 2 => Some(Control::ResumptionTicket {
+                resumed: data[1] == 1,
+                data: data[2..].to_vec()
+            }),
+            Some(&16) if data.len() >= 17 => {
+                let mut nonce = [0; 16];
+                nonce.copy_from_slice(&data[1..17]);
+                Some(Control::ResumeTicket {
+                    nonce,
+                    data: data[17..].to_vec()
+                })
+            },
+            Some(&17) if data.len() == 5 => Some(Control::KeepAliveRequest {
+                duration: u32_from_bytes(&data[1..5])
+            }),
+            Some(&18) if data.len() == 5 => Some(Control::KeepAliveGrant {
+                duration: u32_from_bytes(&data[1..5])
+            }),
+            Some(&19) if data.len() == 17 => {
+                let mut nonce = [0; 16];
+                nonce.copy_from_slice(&data[1..]);
+                Some(Control::ResumeNonce { nonce })
+            },
+            _ => None
+        }
+    }
+
+}
+
+// Static Helpers -------------------------------------------------------------
+fn with_u64(tag: u8, value: u64) -> Vec<u8> {
+    let mut data = vec![tag];
+    data.extend_from_slice(&u64_to_bytes(value));
+    data
+}
+
+fn with_u32(tag: u8, value: u32) -> Vec<u8> {
+    let mut data = vec![tag];
+    data.extend_from_slice(&u32_to_bytes(value));
+    data
+}
+
+fn peers_from_bytes(mut data: &[u8]) -> Option<Vec<Peer>> {
+
+    let mut peers = Vec::new();
+    while !data.is_empty() {
+
+        if data.len() < 5 {
+            return None;
+        }
+
+        let id = ConnectionID(
+            (data[0] as u32) << 24 | (data[1] as u32) << 16 |
+            (data[2] as u32) << 8  |  data[3] as u32
+        );
+
+        let (ip, rest) = match data[4] {
+            4 if data.len() >= 11 => {
+                let mut octets = [0; 4];
+                octets.copy_from_slice(&data[5..9]);
+                (IpAddr::V4(Ipv4Addr::from(octets)), &data[9..])
+            },
+            6 if data.len() >= 23 => {
+                let mut octets = [0; 16];
+                octets.copy_from_slice(&data[5..21]);
+                (IpAddr::V6(Ipv6Addr::from(octets)), &data[21..])
+            },
+            _ => return None
+        };
+
+        let port = (rest[0] as u16) << 8 | rest[1] as u16;
+        peers.push(Peer {
+            id,
+            addr: SocketAddr::new(ip, port)
+        });
+
+        data = &rest[2..];
+
+    }
+
+    Some(peers)
+
+}
+
+/// Encodes a `u64` in big endian byte order.
+pub fn u64_to_bytes(value: u64) -> [u8; 8] {
+    [
+        (value >> 56) as u8, (value >> 48) as u8,
+        (value >> 40) as u8, (value >> 32) as u8,
+        (value >> 24) as u8, (value >> 16) as u8,
+        (value >> 8) as u8, value as u8
+    ]
+}
+
+/// Decodes a `u64` from big endian `bytes`.
+pub fn u64_from_bytes(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0, |value, b| value << 8 | *b as u64)
+}
+
+/// Encodes a `u32` in big endian byte order.
+pub fn u32_to_bytes(value: u32) -> [u8; 4] {
+    [
+        (value >> 24) as u8, (value >> 16) as u8,
+        (value >> 8) as u8, value as u8
+    ]
+}
+
+/// Decodes a `u32` from big endian `bytes`.
+pub fn u32_from_bytes(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0, |value, b| value << 8 | *b as u32)
+}
+
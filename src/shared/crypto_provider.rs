@@ -0,0 +1,153 @@
+// Copyright (c) 2015-2016 Ivo Wetzel
+
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+#[cfg(feature = "encryption")]
+extern crate chacha20poly1305;
+#[cfg(feature = "authentication")]
+extern crate ed25519_dalek;
+#[cfg(feature = "authentication")]
+extern crate hmac;
+#[cfg(feature = "authentication")]
+extern crate sha2;
+#[cfg(feature = "key_exchange")]
+extern crate x25519_dalek;
+
+use std::io::{Error, ErrorKind};
+use std::sync::OnceLock;
+
+#[cfg(feature = "encryption")]
+use self::chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+#[cfg(feature = "encryption")]
+use self::chacha20poly1305::aead::{Aead, KeyInit, Payload};
+#[cfg(feature = "authentication")]
+use self::ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+#[cfg(feature = "authentication")]
+use self::hmac::{Hmac, Mac};
+#[cfg(feature = "authentication")]
+use self::sha2::Sha256;
+#[cfg(feature = "key_exchange")]
+use self::x25519_dalek::{EphemeralSecret, PublicKey};
+
+use super::super::CryptoProvider;
+#[cfg(feature = "key_exchange")]
+use super::super::KeyAgreement;
+
+static PROVIDER: OnceLock<Box<dyn CryptoProvider>> = OnceLock::new();
+
+/// Installs the process wide `provider` for all cryptographic operations.
+///
+/// The provider must be installed before the first packet is encrypted,
+/// signed or key exchanged, after which the `DefaultCryptoProvider` is in use
+/// for the remaining lifetime of the process.
+///
+/// # Errors
+///
+/// Returns an error of kind `AlreadyExists` in case a provider is already in
+/// use.
+///
+/// > Note: This function is feature-gated and will only be included when
+/// > either the `encryption` or the `authentication` feature is enabled.
+pub fn install_crypto_provider(provider: Box<dyn CryptoProvider>) -> Result<(), Error> {
+    PROVIDER.set(provider).map_err(|_| {
+        Error::new(ErrorKind::AlreadyExists, "Crypto provider already in use.")
+    })
+}
+
+/// Returns the provider in use, installing the `DefaultCryptoProvider` in
+/// case none was installed yet.
+pub fn crypto_provider() -> &'static dyn CryptoProvider {
+    &**PROVIDER.get_or_init(|| Box::new(DefaultCryptoProvider))
+}
+
+/// Implementation of a crypto provider using ChaCha20-Poly1305 for
+/// encryption, HMAC-SHA256 for authentication, Ed25519 for signatures and
+/// X25519 for key exchange.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct DefaultCryptoProvider;
+
+impl CryptoProvider for DefaultCryptoProvider {
+
+    #[cfg(feature = "encryption")]
+    fn seal(&self, key: &[u8; 32], nonce: &[u8; 12], aad: &[u8], data: &[u8]) -> Vec<u8> {
+        ChaCha20Poly1305::new(Key::from_slice(key)).encrypt(Nonce::from_slice(nonce), Payload {
+            msg: data,
+            aad
+        }).expect("Failed to encrypt data")
+    }
+
+    #[cfg(feature = "encryption")]
+    fn open(&self, key: &[u8; 32], nonce: &[u8; 12], aad: &[u8], sealed: &[u8]) -> Option<Vec<u8>> {
+        ChaCha20Poly1305::new(Key::from_slice(key)).decrypt(Nonce::from_slice(nonce), Payload {
+            msg: sealed,
+            aad
+        }).ok()
+    }
+
+    #[cfg(feature = "authentication")]
+    fn mac(&self, key: &[u8; 32], data: &[u8]) -> [u8; 32] {
+        let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(key).expect("Invalid key length");
+        mac.update(data);
+        let mut tag = [0; 32];
+        tag.copy_from_slice(&mac.finalize().into_bytes());
+        tag
+    }
+
+    #[cfg(feature = "authentication")]
+    fn signing_public_key(&self, key: &[u8; 32]) -> [u8; 32] {
+        SigningKey::from_bytes(key).verifying_key().to_bytes()
+    }
+
+    #[cfg(feature = "authentication")]
+    fn sign(&self, key: &[u8; 32], data: &[u8]) -> [u8; 64] {
+        SigningKey::from_bytes(key).sign(data).to_bytes()
+    }
+
+    #[cfg(feature = "authentication")]
+    fn verify(&self, public_key: &[u8; 32], data: &[u8], signature: &[u8; 64]) -> bool {
+        match VerifyingKey::from_bytes(public_key) {
+            Ok(key) => key.verify(data, &Signature::from_bytes(signature)).is_ok(),
+            Err(_) => false
+        }
+    }
+
+    #[cfg(feature = "key_exchange")]
+    fn key_agreement(&self) -> Box<dyn KeyAgreement> {
+        let secret = EphemeralSecret::random();
+        let public = PublicKey::from(&secret);
+        Box::new(X25519Agreement {
+            secret,
+            public
+        })
+    }
+
+}
+
+#[cfg(feature = "key_exchange")]
+struct X25519Agreement {
+    secret: EphemeralSecret,
+    public: PublicKey
+}
+
+#[cfg(feature = "key_exchange")]
+impl KeyAgreement for X25519Agreement {
+
+    fn public_key(&self) -> [u8; 32] {
+        self.public.to_bytes()
+    }
+
+    fn agree(self: Box<Self>, remote: [u8; 32]) -> Option<[u8; 32]> {
+        let shared = self.secret.diffie_hellman(&PublicKey::from(remote));
+        if shared.was_contributory() {
+            Some(shared.to_bytes())
+
+        } else {
+            None
+        }
+    }
+
+}
+
@@ -0,0 +1,354 @@
+// Copyright (c) 2015-2016 Ivo Wetzel
+
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+extern crate openssl;
+
+use std::net;
+use std::fmt;
+use std::io::{Error, ErrorKind, Read, Write};
+use std::collections::{HashMap, VecDeque};
+use std::sync::mpsc::TryRecvError;
+use std::time::{Duration, Instant};
+use self::openssl::error::ErrorStack;
+use self::openssl::ssl::{
+    ErrorCode, Ssl, SslAcceptor, SslConnector, SslStream
+};
+use super::super::traits::socket::Socket;
+
+/// Maximum size of the datagrams used for the DTLS handshake, larger
+/// handshake messages are fragmented.
+const HANDSHAKE_MTU: u32 = 1200;
+
+/// Maximum size of a decrypted DTLS record.
+const MAX_RECORD_SIZE: usize = 16384;
+
+/// Number of milliseconds after which incomplete handshakes are abandoned.
+const HANDSHAKE_TIMEOUT: u64 = 10000;
+
+/// Socket adapter which wraps another socket with DTLS.
+///
+/// Every remote address gets its own DTLS session whose handshake is driven
+/// by the datagrams exchanged with it, certificates and cipher suites are
+/// configured through the passed `SslAcceptor` or `SslConnector`.
+///
+/// Packets send before the handshake with an address has completed are
+/// dropped and left to cobalt's reliability layer. Every DTLS record adds up
+/// to 64 bytes on top of the packet, so the underlying socket must be able to
+/// receive packets of at least `Config::packet_max_size` plus this overhead.
+///
+/// Since sessions exist before an address has proven that it can receive
+/// packets, servers should enable DTLS cookie exchange on their acceptor.
+///
+/// > Note: This struct is feature-gated and will only be included when the
+/// > `dtls` feature is enabled.
+pub struct DtlsSocket<S: Socket> {
+    socket: S,
+    role: Role,
+    sessions: HashMap<net::SocketAddr, Session>,
+    received: VecDeque<(net::SocketAddr, Vec<u8>)>,
+    buffer: Vec<u8>
+}
+
+impl<S: Socket> DtlsSocket<S> {
+
+    /// Creates a new socket which accepts DTLS sessions from any remote
+    /// address that sends packets to the underlying `socket`.
+    pub fn server(socket: S, acceptor: SslAcceptor) -> Self {
+        DtlsSocket::new(socket, Role::Server(acceptor))
+    }
+
+    /// Creates a new socket which establishes DTLS sessions with the
+    /// addresses it sends packets to, verifying their certificates against
+    /// the specified `domain`.
+    ///
+    /// Packets from addresses which were never send to are ignored.
+    pub fn client(socket: S, connector: SslConnector, domain: &str) -> Self {
+        DtlsSocket::new(socket, Role::Client(connector, domain.to_string()))
+    }
+
+    /// Returns whether the DTLS handshake with the specified address has
+    /// completed.
+    pub fn established(&self, addr: net::SocketAddr) -> bool {
+        self.sessions.get(&addr).is_some_and(|s| s.established)
+    }
+
+    /// Returns a reference to the underlying socket.
+    pub fn get_ref(&self) -> &S {
+        &self.socket
+    }
+
+    fn new(socket: S, role: Role) -> Self {
+        DtlsSocket {
+            socket,
+            role,
+            sessions: HashMap::new(),
+            received: VecDeque::new(),
+            buffer: vec![0; MAX_RECORD_SIZE]
+        }
+    }
+
+    fn receive_datagram(&mut self, addr: net::SocketAddr, datagram: Vec<u8>) {
+
+        // Only servers accept sessions from unknown addresses
+        if !self.sessions.contains_key(&addr) {
+            if let Role::Server(_) = self.role {
+                match Session::new(&self.role) {
+                    Ok(session) => {
+                        self.sessions.insert(addr, session);
+                    },
+                    Err(_) => return
+                }
+
+            } else {
+                return;
+            }
+        }
+
+        let alive = {
+            let session = self.sessions.get_mut(&addr).unwrap();
+            session.stream.get_mut().incoming.push_back(datagram);
+
+            let alive = session.handshake() && (!session.established || {
+                session.read(&mut self.buffer, addr, &mut self.received)
+            });
+
+            session.flush(&mut self.socket, addr).ok();
+            alive
+        };
+
+        if !alive {
+            self.sessions.remove(&addr);
+        }
+
+    }
+
+    fn update_handshakes(&mut self) {
+
+        let timeout = Duration::from_millis(HANDSHAKE_TIMEOUT);
+        let socket = &mut self.socket;
+
+        // Retransmit lost handshake messages and abandon stale handshakes
+        self.sessions.retain(|addr, session| {
+            if session.established {
+                true
+
+            } else if session.started.elapsed() > timeout {
+                false
+
+            } else {
+                let alive = session.handshake();
+                session.flush(socket, *addr).ok();
+                alive
+            }
+        });
+
+    }
+
+}
+
+impl<S: Socket> Socket for DtlsSocket<S> {
+
+    /// Attempts to return a decrypted, incoming packet without blocking.
+    fn try_recv(&mut self) -> Result<(net::SocketAddr, Vec<u8>), TryRecvError> {
+        loop {
+            if let Some(packet) = self.received.pop_front() {
+                return Ok(packet);
+            }
+
+            match self.socket.try_recv() {
+                Ok((addr, datagram)) => self.receive_datagram(addr, datagram),
+                Err(err) => {
+                    self.update_handshakes();
+                    return Err(err);
+                }
+            }
+        }
+    }
+
+    /// Encrypts and sends data to the given address, starting a new session
+    /// if required. On success, returns the number of bytes written, which is
+    /// `0` in case the handshake with the address has not yet completed.
+    fn send_to(
+        &mut self, data: &[u8], addr: net::SocketAddr)
+
+    -> Result<usize, Error> {
+
+        // Clients initiate new sessions
+        if !self.sessions.contains_key(&addr) {
+            if let Role::Client(_, _) = self.role {
+                let session = Session::new(&self.role).map_err(Error::other)?;
+                self.sessions.insert(addr, session);
+
+            } else {
+                return Ok(0);
+            }
+        }
+
+        let (alive, written) = {
+            let session = self.sessions.get_mut(&addr).unwrap();
+            let (alive, written) = if session.established {
+                match session.stream.ssl_write(data) {
+                    Ok(written) => (true, written),
+                    Err(_) => (false, 0)
+                }
+
+            } else {
+                (session.handshake(), 0)
+            };
+
+            session.flush(&mut self.socket, addr)?;
+            (alive, written)
+        };
+
+        if !alive {
+            self.sessions.remove(&addr);
+        }
+
+        Ok(written)
+
+    }
+
+    /// Returns the address of the underlying socket.
+    fn local_addr(&self) -> Result<net::SocketAddr, Error> {
+        self.socket.local_addr()
+    }
+
+    /// Blocks until the underlying socket can receive a datagram or the
+    /// `timeout` elapsed, unless decrypted packets are already available.
+    fn wait(&mut self, timeout: Duration) {
+        if self.received.is_empty() {
+            self.socket.wait(timeout);
+        }
+    }
+
+}
+
+impl<S: Socket + fmt::Debug> fmt::Debug for DtlsSocket<S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "DtlsSocket({:?})", self.socket)
+    }
+}
+
+
+// Sessions -------------------------------------------------------------------
+enum Role {
+    Server(SslAcceptor),
+    Client(SslConnector, String)
+}
+
+struct Session {
+    stream: SslStream<Datagrams>,
+    established: bool,
+    started: Instant
+}
+
+impl Session {
+
+    fn new(role: &Role) -> Result<Session, ErrorStack> {
+
+        let mut ssl = match *role {
+            Role::Server(ref acceptor) => {
+                let mut ssl = Ssl::new(acceptor.context())?;
+                ssl.set_accept_state();
+                ssl
+            },
+            Role::Client(ref connector, ref domain) => {
+                let mut ssl = connector.configure()?.into_ssl(domain)?;
+                ssl.set_connect_state();
+                ssl
+            }
+        };
+
+        ssl.set_mtu(HANDSHAKE_MTU)?;
+
+        Ok(Session {
+            stream: SslStream::new(ssl, Datagrams::default())?,
+            established: false,
+            started: Instant::now()
+        })
+
+    }
+
+    // Advances the handshake, returns false if it failed
+    fn handshake(&mut self) -> bool {
+        if self.established {
+            true
+
+        } else {
+            match self.stream.do_handshake() {
+                Ok(()) => {
+                    self.established = true;
+                    true
+                },
+                Err(ref err) if err.code() == ErrorCode::WANT_READ => true,
+                Err(_) => false
+            }
+        }
+    }
+
+    // Decrypts all pending records, returns false if the session was closed
+    fn read(
+        &mut self,
+        buffer: &mut [u8],
+        addr: net::SocketAddr,
+        received: &mut VecDeque<(net::SocketAddr, Vec<u8>)>
+
+    ) -> bool {
+        loop {
+            match self.stream.ssl_read(buffer) {
+                Ok(len) => received.push_back((addr, buffer[..len].to_vec())),
+                Err(ref err) if err.code() == ErrorCode::WANT_READ => return true,
+                Err(_) => return false
+            }
+        }
+    }
+
+    fn flush<S: Socket>(
+        &mut self, socket: &mut S, addr: net::SocketAddr
+
+    ) -> Result<(), Error> {
+        for datagram in self.stream.get_mut().outgoing.drain(..) {
+            socket.send_to(&datagram, addr)?;
+        }
+        Ok(())
+    }
+
+}
+
+// In-memory transport which keeps the boundaries of the datagrams passed
+// between the DTLS session and the socket
+#[derive(Default)]
+struct Datagrams {
+    incoming: VecDeque<Vec<u8>>,
+    outgoing: Vec<Vec<u8>>
+}
+
+impl Read for Datagrams {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        if let Some(datagram) = self.incoming.pop_front() {
+            let len = datagram.len().min(buf.len());
+            buf[..len].copy_from_slice(&datagram[..len]);
+            Ok(len)
+
+        } else {
+            Err(Error::new(ErrorKind::WouldBlock, ""))
+        }
+    }
+}
+
+impl Write for Datagrams {
+
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Error> {
+        self.outgoing.push(buf.to_vec());
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+
+}
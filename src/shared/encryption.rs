@@ -0,0 +1,251 @@
+// Copyright (c) 2015-2016 Ivo Wetzel
+
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+extern crate hkdf;
+extern crate rand;
+extern crate sha2;
+
+use std::fmt;
+use self::hkdf::Hkdf;
+use self::rand::{OsRng, Rng};
+use self::sha2::Sha256;
+use super::super::{ConnectionID, CryptoProvider};
+use super::crypto_provider::crypto_provider;
+
+/// Number of bytes used by the random nonce prepended to each packet body.
+const NONCE_BYTES: usize = 12;
+
+/// Number of bytes used by the authentication tag appended to each packet
+/// body.
+const TAG_BYTES: usize = 16;
+
+/// Number of bytes added to each packet by its encryption.
+pub const ENCRYPTION_OVERHEAD: usize = NONCE_BYTES + TAG_BYTES;
+
+/// Bit of the nonce counters which is set by the accepting end of a
+/// connection.
+///
+/// Both ends of a connection encrypt their packets with the same key, so
+/// reserving a bit for the direction of a packet ensures that the ends never
+/// use the same nonce, and allows each end to reject reflections of its own
+/// packets.
+pub const ACCEPTED_NONCE_BIT: u64 = 1 << 63;
+
+/// Number of nonce counters below the highest received one which are tracked
+/// by a `ReplayWindow`.
+const REPLAY_WINDOW_SIZE: u64 = 64;
+
+/// Cipher used for encrypting and authenticating the packets of a single
+/// connection with the AEAD of the installed `CryptoProvider`.
+///
+/// Packet bodies are encrypted with a nonce which is send in front of the
+/// encrypted data, while the packet header is only authenticated. Nonces of
+/// connection packets end with a counter, which allows the remote to reject
+/// replayed packets.
+pub struct PacketCipher {
+    provider: &'static dyn CryptoProvider,
+    key: [u8; 32]
+}
+
+impl PacketCipher {
+
+    /// Creates a new cipher using the specified `key`.
+    pub fn new(key: [u8; 32]) -> PacketCipher {
+        PacketCipher::with_provider(crypto_provider(), key)
+    }
+
+    /// Creates a new cipher using the specified `key` with the specified
+    /// `provider` instead of the installed one.
+    pub fn with_provider(provider: &'static dyn CryptoProvider, key: [u8; 32]) -> PacketCipher {
+        PacketCipher {
+            provider,
+            key
+        }
+    }
+
+    /// Creates a new cipher using a key which is derived from the specified
+    /// pre-shared `key` and the `id` of a connection via HKDF-SHA256, so no
+    /// two connections end up sharing the same key.
+    pub fn derive(key: [u8; 32], id: ConnectionID) -> PacketCipher {
+        let info = [
+            (id.0 >> 24) as u8,
+            (id.0 >> 16) as u8,
+            (id.0 >> 8) as u8,
+            id.0 as u8
+        ];
+        PacketCipher::with_provider(
+            crypto_provider(),
+            derive_key(&key, b"cobalt connection key", &[&info])
+        )
+    }
+
+    /// Derives the cipher for the specified key `epoch` from the key of this
+    /// cipher via HKDF-SHA256, so both ends of a connection rotate to the
+    /// same key.
+    pub fn rotate(&self, epoch: u32) -> PacketCipher {
+        let info = [
+            (epoch >> 24) as u8,
+            (epoch >> 16) as u8,
+            (epoch >> 8) as u8,
+            epoch as u8
+        ];
+        PacketCipher::with_provider(
+            self.provider,
+            derive_key(&self.key, b"cobalt key rotation", &[&info])
+        )
+    }
+
+    /// Encrypts the packet `body` with a nonce drawn from the random number
+    /// generator of the operating system, authenticating the packet `header`
+    /// along with it.
+    pub fn encrypt(&self, header: &[u8], body: &[u8]) -> Vec<u8> {
+        let mut nonce = [0; NONCE_BYTES];
+        random_bytes(&mut nonce);
+        self.seal(nonce, header, body)
+    }
+
+    /// Encrypts the packet `body` like `PacketCipher::encrypt()`, but with a
+    /// nonce which ends with the specified `counter`.
+    pub fn encrypt_counted(&self, header: &[u8], body: &[u8], counter: u64) -> Vec<u8> {
+        let mut nonce = [0; NONCE_BYTES];
+        random_bytes(&mut nonce[..4]);
+        for (i, byte) in nonce[4..].iter_mut().enumerate() {
+            *byte = (counter >> (56 - i * 8)) as u8;
+        }
+        self.seal(nonce, header, body)
+    }
+
+    /// Returns the counter from the nonce of an encrypted packet `body`.
+    pub fn nonce_counter(body: &[u8]) -> u64 {
+        body[4..NONCE_BYTES].iter().fold(0, |counter, byte| {
+            counter << 8 | *byte as u64
+        })
+    }
+
+    fn seal(&self, nonce: [u8; NONCE_BYTES], header: &[u8], body: &[u8]) -> Vec<u8> {
+
+        let encrypted = self.provider.seal(&self.key, &nonce, header, body);
+
+        let mut packet = Vec::with_capacity(header.len() + ENCRYPTION_OVERHEAD + body.len());
+        packet.extend_from_slice(header);
+        packet.extend_from_slice(&nonce);
+        packet.extend_from_slice(&encrypted);
+        packet
+
+    }
+
+    /// Decrypts the packet `body`, returning `None` in case either the body
+    /// or the packet `header` failed to authenticate.
+    pub fn decrypt(&self, header: &[u8], body: &[u8]) -> Option<Vec<u8>> {
+
+        if body.len() < ENCRYPTION_OVERHEAD {
+            return None;
+        }
+
+        let mut nonce = [0; NONCE_BYTES];
+        nonce.copy_from_slice(&body[..NONCE_BYTES]);
+
+        self.provider.open(&self.key, &nonce, header, &body[NONCE_BYTES..]).map(|decrypted| {
+            let mut packet = Vec::with_capacity(header.len() + decrypted.len());
+            packet.extend_from_slice(header);
+            packet.extend_from_slice(&decrypted);
+            packet
+        })
+
+    }
+
+}
+
+impl fmt::Debug for PacketCipher {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "PacketCipher")
+    }
+}
+
+/// Number of bytes of the nonces both ends contribute to the key of a
+/// resumed session.
+pub const RESUMPTION_NONCE_BYTES: usize = 16;
+
+/// Derives the key of a resumed session from the `secret` of the previous
+/// session and the fresh nonces of the initiating and the accepting end via
+/// HKDF-SHA256, so no two resumptions of a session share the same key.
+pub fn resumption_key(
+    secret: &[u8; 32],
+    initiating: &[u8; RESUMPTION_NONCE_BYTES],
+    accepting: &[u8; RESUMPTION_NONCE_BYTES]
+
+) -> [u8; 32] {
+    derive_key(secret, b"cobalt session resumption", &[initiating, accepting])
+}
+
+/// Derives a 32 byte key from the input keying material `key` via
+/// HKDF-SHA256, binding it to the specified `label` and `info`.
+pub fn derive_key(key: &[u8], label: &[u8], info: &[&[u8]]) -> [u8; 32] {
+    let mut parts = vec![label];
+    parts.extend_from_slice(info);
+
+    let mut derived = [0; 32];
+    Hkdf::<Sha256>::new(None, key).expand_multi_info(&parts, &mut derived).expect(
+        "Invalid key length"
+    );
+    derived
+}
+
+/// Fills `bytes` from the random number generator of the operating system.
+pub fn random_bytes(bytes: &mut [u8]) {
+    OsRng::new().expect("Failed to access the OS random number generator").fill_bytes(bytes);
+}
+
+
+/// Sliding window over the nonce counters of the packets received from a
+/// remote, which rejects every counter that was either received before or
+/// has fallen behind the window.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct ReplayWindow {
+    highest: u64,
+    received: u64
+}
+
+impl ReplayWindow {
+
+    /// Creates a new, empty window.
+    pub fn new() -> ReplayWindow {
+        ReplayWindow::default()
+    }
+
+    /// Marks the `counter` as received, returning `false` in case it is a
+    /// replay or too old to tell.
+    ///
+    /// Counters start at `1`, so `0` is never accepted.
+    pub fn accept(&mut self, counter: u64) -> bool {
+        if counter == 0 {
+            false
+
+        } else if counter > self.highest {
+            let shift = counter - self.highest;
+            self.received = if shift < REPLAY_WINDOW_SIZE {
+                self.received << shift | 1
+
+            } else {
+                1
+            };
+            self.highest = counter;
+            true
+
+        } else {
+            let offset = self.highest - counter;
+            if offset >= REPLAY_WINDOW_SIZE || self.received & (1 << offset) != 0 {
+                false
+
+            } else {
+                self.received |= 1 << offset;
+                true
+            }
+        }
+    }
+
+}
@@ -0,0 +1,96 @@
+// Copyright (c) 2015-2016 Ivo Wetzel
+
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+extern crate rand;
+
+use std::net::{IpAddr, SocketAddr};
+use std::time::{SystemTime, UNIX_EPOCH};
+use self::rand::{OsRng, Rng};
+use super::authentication::{self, AUTHENTICATION_OVERHEAD};
+use super::super::ConnectionID;
+
+/// Number of bytes used by a handshake cookie.
+pub const COOKIE_BYTES: usize = 4 + AUTHENTICATION_OVERHEAD;
+
+/// Number of seconds for which a handshake cookie is accepted after it was
+/// issued.
+const COOKIE_LIFETIME: u32 = 10;
+
+/// Returns a fresh secret for signing cookies, drawn from the random number
+/// generator of the operating system.
+pub fn secret() -> [u8; 32] {
+    let mut secret = [0; 32];
+    OsRng::new().expect("Failed to access the OS random number generator").fill_bytes(&mut secret);
+    secret
+}
+
+/// Issues a cookie for the connection `id` to the remote at `addr`, signed
+/// with the `secret` of the server.
+///
+/// The cookie consists of the time it was issued at followed by a
+/// HMAC-SHA256 tag over the time, address and id, so the server does not
+/// need to remember it.
+pub fn issue(secret: &[u8; 32], addr: SocketAddr, id: ConnectionID) -> Vec<u8> {
+    let issued = now();
+    let mut data = signed_data(addr, id, issued);
+    authentication::sign(secret, &mut data);
+
+    let mut cookie = Vec::with_capacity(COOKIE_BYTES);
+    cookie.extend_from_slice(&data[..4]);
+    cookie.extend_from_slice(&data[data.len() - AUTHENTICATION_OVERHEAD..]);
+    cookie
+}
+
+/// Verifies a `cookie` which the remote at `addr` echoed for the connection
+/// `id`, returning `false` in case it is forged, was issued for another
+/// address or connection, or has expired.
+pub fn verify(secret: &[u8; 32], addr: SocketAddr, id: ConnectionID, cookie: &[u8]) -> bool {
+
+    if cookie.len() != COOKIE_BYTES {
+        return false;
+    }
+
+    let issued = (cookie[0] as u32) << 24 | (cookie[1] as u32) << 16 |
+                 (cookie[2] as u32) << 8  |  cookie[3] as u32;
+
+    if now().wrapping_sub(issued) > COOKIE_LIFETIME {
+        return false;
+    }
+
+    let mut data = signed_data(addr, id, issued);
+    data.extend_from_slice(&cookie[4..]);
+    authentication::verify(secret, &mut data)
+
+}
+
+fn signed_data(addr: SocketAddr, id: ConnectionID, issued: u32) -> Vec<u8> {
+
+    let mut data = vec![
+        (issued >> 24) as u8,
+        (issued >> 16) as u8,
+        (issued >> 8) as u8,
+        issued as u8,
+        (id.0 >> 24) as u8,
+        (id.0 >> 16) as u8,
+        (id.0 >> 8) as u8,
+        id.0 as u8,
+        (addr.port() >> 8) as u8,
+        addr.port() as u8
+    ];
+
+    match addr.ip() {
+        IpAddr::V4(ip) => data.extend_from_slice(&ip.octets()),
+        IpAddr::V6(ip) => data.extend_from_slice(&ip.octets())
+    }
+
+    data
+
+}
+
+fn now() -> u32 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs() as u32)
+}
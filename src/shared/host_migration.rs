@@ -0,0 +1,54 @@
+// Copyright (c) 2015-2016 Ivo Wetzel
+
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use std::net::SocketAddr;
+use super::super::ConnectionID;
+
+/// A peer taking part in a session which is hosted by a `Server` with
+/// `host_migration` enabled.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Peer {
+
+    /// The id of the peer's connection to the host.
+    pub id: ConnectionID,
+
+    /// The address of the peer as seen by the host.
+    pub addr: SocketAddr
+
+}
+
+/// Outcome of a host election after the connection to the current host of a
+/// session was lost.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HostMigration {
+
+    /// The peer which was elected as the new host.
+    pub host: Peer,
+
+    /// Whether the local end of the lost connection was elected as the new
+    /// host.
+    ///
+    /// The new host is expected to bind a `Server` to the socket which was
+    /// used for the lost connection, as its address is the one known to all
+    /// other peers, while the remaining peers connect to the new host's
+    /// address.
+    pub local: bool,
+
+    /// The latest session state that was shared by the previous host, if
+    /// any.
+    pub session_state: Option<Vec<u8>>
+
+}
+
+/// Elects the new host out of the specified `peers`.
+///
+/// The peer with the lowest `ConnectionID` wins, so all peers which share the
+/// same list arrive at the same result without any further communication.
+pub fn elect_host(peers: &[Peer]) -> Option<Peer> {
+    peers.iter().min_by_key(|peer| peer.id.0).cloned()
+}
+
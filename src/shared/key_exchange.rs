@@ -0,0 +1,66 @@
+// Copyright (c) 2015-2016 Ivo Wetzel
+
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use std::fmt;
+use super::super::KeyAgreement;
+use super::crypto_provider::crypto_provider;
+use super::encryption::derive_key;
+
+/// Ephemeral key exchange deriving a secret which is shared between the two
+/// ends of a connection from the key agreement of the installed
+/// `CryptoProvider`.
+pub struct KeyExchange {
+    agreement: Option<Box<dyn KeyAgreement>>,
+    public: [u8; 32]
+}
+
+impl KeyExchange {
+
+    /// Creates a new key exchange with a fresh, random ephemeral secret.
+    pub fn new() -> KeyExchange {
+        let agreement = crypto_provider().key_agreement();
+        let public = agreement.public_key();
+        KeyExchange {
+            agreement: Some(agreement),
+            public
+        }
+    }
+
+    /// Returns the public key which is send to the remote.
+    pub fn public_key(&self) -> [u8; 32] {
+        self.public
+    }
+
+    /// Completes the exchange with the public key of the remote, returning
+    /// the derived session key.
+    ///
+    /// Returns `None` in case the exchange was already completed or the
+    /// remote's public key is invalid.
+    pub fn complete(&mut self, remote: [u8; 32]) -> Option<[u8; 32]> {
+
+        let shared = self.agreement.take()?.agree(remote)?;
+
+        // Bind the key to both public keys in an order both ends agree on
+        let (first, second) = if self.public < remote {
+            (self.public, remote)
+
+        } else {
+            (remote, self.public)
+        };
+
+        Some(derive_key(&shared, b"cobalt session key", &[&first, &second]))
+
+    }
+
+}
+
+impl fmt::Debug for KeyExchange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "KeyExchange")
+    }
+}
+
@@ -6,6 +6,8 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 use std::cmp;
+use std::io::{Error, ErrorKind};
+use std::time::{Duration, Instant};
 use std::collections::{BinaryHeap, HashSet, VecDeque};
 use super::super::Config;
 
@@ -13,7 +15,13 @@ use super::super::Config;
 const MAX_ORDER_ID: u16 = 4096;
 
 /// Number of bytes used in a single message header.
-const MESSAGE_HEADER_BYTES: usize = 4;
+pub const MESSAGE_HEADER_BYTES: usize = 4;
+
+/// Bit flag set on the kind of internal control messages.
+///
+/// Remote queues which are not aware of control messages will parse these as
+/// `MessageKind::Invalid` and ignore them.
+const CONTROL_MESSAGE_FLAG: u8 = 0x08;
 
 /// Enum for specification of a message handling algorithm.
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
@@ -41,13 +49,23 @@ pub enum MessageKind {
     Invalid = 3
 }
 
+/// Handle to a message which was pushed into a `MessageQueue`, allowing it to
+/// be cancelled while it is still waiting to be send.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct MessageHandle(u32);
+
 /// Structure for handling messages inside a `MessageQueue` with support for
 /// insertion into a binary min heap for order checking on received messages.
 #[derive(Debug, Eq, PartialEq)]
 struct Message {
+    id: u32,
     kind: MessageKind,
+    control: bool,
+    sent: bool,
     order: u16,
     size: u16,
+    tick: Option<u32>,
+    arrival: Option<(Instant, u32)>,
     data: Vec<u8>
 }
 
@@ -65,6 +83,16 @@ impl PartialOrd for Message {
     }
 }
 
+/// Structure for messages which only become eligible for sending once their
+/// delay has elapsed.
+#[derive(Debug)]
+struct DelayedMessage {
+    due: Instant,
+    id: u32,
+    kind: MessageKind,
+    data: Vec<u8>
+}
+
 /// Consuming iterator over the received messages of a `MessageQueue`.
 #[derive(Debug)]
 pub struct MessageIterator<'a> {
@@ -84,6 +112,58 @@ impl<'a> Iterator for MessageIterator<'a> {
 
 }
 
+/// Consuming iterator over the received messages of a `MessageQueue` along
+/// with the tick numbers of the packets which carried them.
+#[derive(Debug)]
+pub struct TickedMessageIterator<'a> {
+    messages: &'a mut VecDeque<Message>
+}
+
+impl<'a> Iterator for TickedMessageIterator<'a> {
+
+    type Item = (Option<u32>, Vec<u8>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.messages.pop_front().map(|m| (m.tick, m.data))
+    }
+
+}
+
+/// Consuming iterator over the received messages of a `MessageQueue` along
+/// with the times at which they arrived and the sequence numbers of the
+/// packets which carried them.
+#[derive(Debug)]
+pub struct TimedMessageIterator<'a> {
+    messages: &'a mut VecDeque<Message>
+}
+
+impl<'a> Iterator for TimedMessageIterator<'a> {
+
+    type Item = (Option<(Instant, u32)>, Vec<u8>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.messages.pop_front().map(|m| (m.arrival, m.data))
+    }
+
+}
+
+/// Consuming iterator over the received messages of a `MessageQueue` along
+/// with the kinds they were send as.
+#[derive(Debug)]
+pub struct KindedMessageIterator<'a> {
+    messages: &'a mut VecDeque<Message>
+}
+
+impl<'a> Iterator for KindedMessageIterator<'a> {
+
+    type Item = (MessageKind, Vec<u8>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.messages.pop_front().map(|m| (m.kind, m.data))
+    }
+
+}
+
 /// Implementation of a queue that manages the sending and receiving of both
 /// reliable and unreliable message types and also supports optional in order
 /// transmission.
@@ -101,6 +181,9 @@ pub struct MessageQueue {
     /// kind `MessageKind::Ordered`
     remote_order_id: u16,
 
+    /// The id which gets attached to the next message pushed into the queue
+    next_message_id: u32,
+
     /// Queue of outgoing messages of the kind `MessageKind::Instant`
     i_queue: VecDeque<Message>,
 
@@ -110,14 +193,38 @@ pub struct MessageQueue {
     /// Queue of outgoing messages of the kind `MessageKind::Ordered`
     o_queue: VecDeque<Message>,
 
+    /// Queue of outgoing internal control messages
+    c_queue: VecDeque<Message>,
+
+    /// Queue of outgoing messages waiting for their delay to elapse, sorted
+    /// by their due time
+    delayed_queue: VecDeque<DelayedMessage>,
+
     /// Ordered queue of incoming messages
     recv_queue: VecDeque<Message>,
 
+    /// Queue of incoming internal control messages
+    recv_control_queue: VecDeque<Message>,
+
     /// Binary Min-Heap to manage incomging, out of order messages
     o_recv_heap: BinaryHeap<Message>,
 
     /// Set for avoiding duplication of out of order messages
-    o_recv_set: HashSet<u16>
+    o_recv_set: HashSet<u16>,
+
+    /// Whether messages other than internal control messages are held back
+    /// instead of being written into packets
+    held: bool,
+
+    /// Number of messages which were written into packets for the first time
+    sent_messages: u32,
+
+    /// Number of messages which were pushed into the receive queue
+    received_messages: u32,
+
+    /// Arrival time and sequence number of the packet which is currently
+    /// being received
+    recv_arrival: Option<(Instant, u32)>
 
 }
 
@@ -126,15 +233,23 @@ impl MessageQueue {
     /// Creates a new queue for sending and receiving messages.
     pub fn new(config: Config) -> MessageQueue {
         MessageQueue {
-            config: config,
+            config,
             local_order_id: 0,
             remote_order_id: 0,
+            next_message_id: 1,
             i_queue: VecDeque::new(),
             r_queue: VecDeque::new(),
             o_queue: VecDeque::new(),
+            c_queue: VecDeque::new(),
+            delayed_queue: VecDeque::new(),
             recv_queue: VecDeque::new(),
+            recv_control_queue: VecDeque::new(),
             o_recv_heap: BinaryHeap::new(),
-            o_recv_set: HashSet::new()
+            o_recv_set: HashSet::new(),
+            held: false,
+            sent_messages: 0,
+            received_messages: 0,
+            recv_arrival: None
         }
     }
 
@@ -143,27 +258,198 @@ impl MessageQueue {
         self.config = config;
     }
 
+    /// Sets whether all messages except for internal control messages are
+    /// held back by `MessageQueue::send_packet()`, keeping their order.
+    pub fn hold(&mut self, held: bool) {
+        self.held = held;
+    }
+
     /// Returns a consuming iterator over all received messages in the queue.
-    pub fn received(&mut self) -> MessageIterator {
+    pub fn received(&mut self) -> MessageIterator<'_> {
         MessageIterator { messages: &mut self.recv_queue }
     }
 
+    /// Returns a consuming iterator over all received messages in the queue
+    /// along with the tick numbers of the packets which carried them.
+    pub fn received_with_tick_numbers(&mut self) -> TickedMessageIterator<'_> {
+        TickedMessageIterator { messages: &mut self.recv_queue }
+    }
+
+    /// Returns a consuming iterator over all received messages in the queue
+    /// along with the times at which they arrived and the sequence numbers
+    /// of the packets which carried them.
+    ///
+    /// Messages which were received before any arrival was recorded via
+    /// `MessageQueue::set_arrival` report `None`.
+    pub fn received_with_arrival(&mut self) -> TimedMessageIterator<'_> {
+        TimedMessageIterator { messages: &mut self.recv_queue }
+    }
+
+    /// Returns a consuming iterator over all received messages in the queue
+    /// along with the kinds they were send as.
+    pub fn received_with_kinds(&mut self) -> KindedMessageIterator<'_> {
+        KindedMessageIterator { messages: &mut self.recv_queue }
+    }
+
+    /// Returns a consuming iterator over all received control messages in the
+    /// queue.
+    pub fn received_control(&mut self) -> MessageIterator<'_> {
+        MessageIterator { messages: &mut self.recv_control_queue }
+    }
+
     /// Clears the queue of received messages, dismissing any messages which
     /// have not been fetched via `MessageQueue::received()`.
     pub fn dismiss(&mut self) {
         self.recv_queue.clear();
     }
 
+    /// Returns the number of messages of the specified `kind` which are
+    /// waiting to be send, excluding delayed messages.
+    pub fn queued(&self, kind: MessageKind) -> usize {
+        match kind {
+            MessageKind::Instant => self.i_queue.len(),
+            MessageKind::Reliable => self.r_queue.len(),
+            MessageKind::Ordered => self.o_queue.len(),
+            MessageKind::Invalid => 0
+        }
+    }
+
+    /// Returns the number of messages which are waiting for their delay to
+    /// elapse.
+    pub fn delayed(&self) -> usize {
+        self.delayed_queue.len()
+    }
+
+    /// Returns the number of payload bytes of all received messages which
+    /// have not yet been consumed, excluding out of order messages.
+    pub fn received_bytes(&self) -> usize {
+        self.recv_queue.iter().map(|m| m.data.len()).sum()
+    }
+
+    /// Returns the number of received messages which have not yet been
+    /// consumed, excluding out of order messages.
+    pub fn received_len(&self) -> usize {
+        self.recv_queue.len()
+    }
+
+    /// Returns the number of messages which were written into packets,
+    /// excluding re-transmissions and internal control messages.
+    pub fn sent_messages(&self) -> u32 {
+        self.sent_messages
+    }
+
+    /// Returns the number of messages which were received, excluding
+    /// duplicates and internal control messages.
+    pub fn received_messages(&self) -> u32 {
+        self.received_messages
+    }
+
     /// Pushes a message of the specified `kind` along with its `data` into the
     /// queue. The message will eventually get serialized via
     /// `MessageQueue::send_packet()`.
-    pub fn send(&mut self, kind: MessageKind, data: Vec<u8>) {
+    ///
+    /// Returns a handle for cancelling the message or `None` in case the
+    /// message was rejected by `MessageQueue::try_send()` and dropped.
+    pub fn send(&mut self, kind: MessageKind, data: Vec<u8>) -> Option<MessageHandle> {
+        self.try_send(kind, data).ok()
+    }
+
+    /// Pushes a message of the specified `kind` along with its `data` into the
+    /// queue, returning an error in case the message was rejected.
+    ///
+    /// Messages of the kind `MessageKind::Invalid` and messages exceeding the
+    /// maximum message size result in an error of the kind
+    /// `ErrorKind::InvalidInput`. In case the queue for the message's kind
+    /// has reached its `message_queue_limit` an error of the kind
+    /// `ErrorKind::WouldBlock` is returned.
+    pub fn try_send(
+        &mut self, kind: MessageKind, data: Vec<u8>
+
+    ) -> Result<MessageHandle, Error> {
+
+        if kind == MessageKind::Invalid || data.len() > u16::MAX as usize {
+            return Err(Error::new(ErrorKind::InvalidInput, ""));
+        }
+
+        let queued = self.queued(kind);
+        if self.config.message_queue_limit > 0
+            && queued >= self.config.message_queue_limit {
+            return Err(Error::new(ErrorKind::WouldBlock, ""));
+        }
+
+        let id = self.next_id();
+        self.push_message(id, kind, data);
+        Ok(MessageHandle(id))
+
+    }
+
+    /// Pushes a message of the specified `kind` along with its `data` into the
+    /// queue, which only becomes eligible for sending once the `delay` has
+    /// elapsed.
+    ///
+    /// Ordered messages are ordered by the time they become eligible for
+    /// sending. Delayed messages are not subject to the `message_queue_limit`
+    /// and can be cancelled until they have been send.
+    pub fn send_after(
+        &mut self, kind: MessageKind, data: Vec<u8>, delay: Duration
+
+    ) -> Result<MessageHandle, Error> {
+
+        if kind == MessageKind::Invalid || data.len() > u16::MAX as usize {
+            return Err(Error::new(ErrorKind::InvalidInput, ""));
+        }
+
+        let id = self.next_id();
+        let due = Instant::now() + delay;
+
+        // Keep the queue sorted by due time
+        let index = self.delayed_queue.iter().position(|m| m.due > due).unwrap_or(
+            self.delayed_queue.len()
+        );
+
+        self.delayed_queue.insert(index, DelayedMessage {
+            due,
+            id,
+            kind,
+            data
+        });
+
+        Ok(MessageHandle(id))
+
+    }
+
+    /// Returns a handle for a message which is dropped right away instead of
+    /// being pushed into the queue.
+    pub fn discard(&mut self) -> MessageHandle {
+        MessageHandle(self.next_id())
+    }
+
+    fn next_id(&mut self) -> u32 {
+
+        // Message id 0 is reserved for messages which cannot be cancelled
+        let id = self.next_message_id;
+        self.next_message_id = self.next_message_id.wrapping_add(1);
+
+        if self.next_message_id == 0 {
+            self.next_message_id = 1;
+        }
+
+        id
+
+    }
+
+    fn push_message(&mut self, id: u32, kind: MessageKind, data: Vec<u8>) {
 
         let message = Message {
-            kind: kind,
+            id,
+            kind,
+            control: false,
+            sent: false,
             order: self.local_order_id,
             size: data.len() as u16,
-            data: data
+            tick: None,
+            arrival: None,
+            data
         };
 
         match kind {
@@ -184,6 +470,137 @@ impl MessageQueue {
 
     }
 
+    /// Removes the message referred to by `handle` from the queue, returning
+    /// whether it was still waiting to be send.
+    ///
+    /// Messages which have already been send cannot be cancelled, not even
+    /// when they are going to be re-send because their packet was lost.
+    pub fn cancel(&mut self, handle: MessageHandle) -> bool {
+
+        if handle.0 == 0 {
+            false
+
+        } else if let Some(index) = self.delayed_queue.iter().position(|m| m.id == handle.0) {
+            self.delayed_queue.remove(index);
+            true
+
+        } else if remove_message(&mut self.i_queue, handle.0).is_some()
+               || remove_message(&mut self.r_queue, handle.0).is_some()
+               || remove_message(&mut self.c_queue, handle.0).is_some() {
+            true
+
+        } else if let Some(order) = remove_message(&mut self.o_queue, handle.0) {
+
+            // Close the gap in the order ids by moving all later messages,
+            // none of which have been send yet, one id forward
+            for m in &mut self.o_queue {
+                if !m.sent && order_is_more_recent(m.order, order) {
+                    m.order = if m.order == 0 {
+                        MAX_ORDER_ID - 1
+                    } else {
+                        m.order - 1
+                    };
+                }
+            }
+
+            self.local_order_id = if self.local_order_id == 0 {
+                MAX_ORDER_ID - 1
+            } else {
+                self.local_order_id - 1
+            };
+
+            true
+
+        } else {
+            false
+        }
+
+    }
+
+    /// Pushes a internal control message of the specified `kind` along with
+    /// its `data` into the queue.
+    ///
+    /// Control messages are always serialized before any other messages and
+    /// are not subject to any quotas. Messages of the kind
+    /// `MessageKind::Ordered` are handled like `MessageKind::Reliable` ones.
+    pub fn send_control(&mut self, kind: MessageKind, data: Vec<u8>) {
+
+        let kind = match kind {
+            MessageKind::Ordered => MessageKind::Reliable,
+            kind => kind
+        };
+
+        if kind != MessageKind::Invalid {
+            self.c_queue.push_back(Message {
+                id: 0,
+                kind,
+                control: true,
+                sent: false,
+                order: 0,
+                size: data.len() as u16,
+                tick: None,
+                arrival: None,
+                data
+            });
+        }
+
+    }
+
+    /// Pushes a reliable internal control message into the queue, returning a
+    /// handle which is reported once the message was acknowledged by the
+    /// remote.
+    #[cfg(feature = "encryption")]
+    pub fn send_control_tracked(&mut self, data: Vec<u8>) -> MessageHandle {
+        let id = self.next_id();
+        self.c_queue.push_back(Message {
+            id,
+            kind: MessageKind::Reliable,
+            control: true,
+            sent: false,
+            order: 0,
+            size: data.len() as u16,
+            tick: None,
+            arrival: None,
+            data
+        });
+        MessageHandle(id)
+    }
+
+    /// Serializes a single internal control message of the specified `kind`
+    /// along with its `data` directly into the `packet`, bypassing all
+    /// queues.
+    pub fn write_control(packet: &mut Vec<u8>, kind: MessageKind, data: Vec<u8>) {
+        let mut queue = VecDeque::new();
+        queue.push_back(Message {
+            id: 0,
+            kind,
+            control: true,
+            sent: false,
+            order: 0,
+            size: data.len() as u16,
+            tick: None,
+            arrival: None,
+            data
+        });
+        write_message(&mut queue, packet, usize::MAX, &mut 0, &mut Vec::new(), &mut 0);
+    }
+
+    /// Grows the `packet` to `size` bytes by appending a message of the kind
+    /// `MessageKind::Invalid`, which is ignored by the receiving end.
+    pub fn write_padding(packet: &mut Vec<u8>, size: usize) {
+        if packet.len() + MESSAGE_HEADER_BYTES <= size {
+            let padding = cmp::min(size - packet.len() - MESSAGE_HEADER_BYTES, u16::MAX as usize);
+            packet.push(MessageKind::Invalid as u8);
+            packet.push(0);
+            packet.push((padding >> 8) as u8);
+            packet.push(padding as u8);
+            packet.resize(packet.len() + padding, 0);
+
+        } else if packet.len() < size {
+            packet.resize(size, 0);
+        }
+    }
+
     /// Serializes a number of internally queued messages into the
     /// `available` space within the `packet`.
     ///
@@ -203,26 +620,53 @@ impl MessageQueue {
     /// Then, after the other quotas have been taken into account, we'll try to
     /// fit more instant messages into the remaining available space within the
     /// packet.
-    pub fn send_packet(&mut self, packet: &mut Vec<u8>, available: usize) {
+    ///
+    /// Returns the handles of all messages which were written into the
+    /// packet in their serialized order, with `None` in place of all
+    /// messages which are neither `MessageKind::Reliable` nor
+    /// `MessageKind::Ordered`.
+    pub fn send_packet(
+        &mut self, packet: &mut Vec<u8>, available: usize
+
+    ) -> Vec<Option<MessageHandle>> {
+
+        // Release delayed messages which became eligible for sending
+        let now = Instant::now();
+        while self.delayed_queue.front().is_some_and(|m| m.due <= now) {
+            let m = self.delayed_queue.pop_front().unwrap();
+            self.push_message(m.id, m.kind, m.data);
+        }
 
-        // First we are trying to fill the packet by using the set quotas
+        // Control messages always take precedence over all other messages
         let mut written = 0;
+        let mut handles = Vec::new();
+        let mut sent = self.sent_messages;
+        write_messages(
+            &mut self.c_queue, packet, available,
+            &mut written, &mut handles, &mut sent
+        );
+
+        if self.held {
+            return handles;
+        }
+
+        // Then we are trying to fill the packet by using the set quotas
         write_messages(
             &mut self.i_queue, packet,
             (available as f32 / 100.0 * self.config.message_quota_instant) as usize,
-            &mut written
+            &mut written, &mut handles, &mut sent
         );
 
         write_messages(
             &mut self.r_queue, packet,
             (available as f32 / 100.0 * self.config.message_quota_reliable) as usize,
-            &mut written
+            &mut written, &mut handles, &mut sent
         );
 
         write_messages(
             &mut self.o_queue, packet,
             (available as f32 / 100.0 * self.config.message_quota_ordered) as usize,
-            &mut written
+            &mut written, &mut handles, &mut sent
         );
 
         // After that, we try to fill the remaining packet space by trying to
@@ -230,19 +674,44 @@ impl MessageQueue {
         let mut more = true;
         while more {
             more = false;
-            more |= write_message(&mut self.i_queue, packet, available, &mut written);
-            more |= write_message(&mut self.r_queue, packet, available, &mut written);
-            more |= write_message(&mut self.o_queue, packet, available, &mut written);
+            more |= write_message(
+                &mut self.i_queue, packet, available, &mut written, &mut handles, &mut sent
+            );
+            more |= write_message(
+                &mut self.r_queue, packet, available, &mut written, &mut handles, &mut sent
+            );
+            more |= write_message(
+                &mut self.o_queue, packet, available, &mut written, &mut handles, &mut sent
+            );
         }
 
+        self.sent_messages = sent;
+        handles
+
+    }
+
+    /// Records the `time` at which the packet that is received next arrived
+    /// along with its sequence number `seq`, which are attached to all of the
+    /// messages received from then on.
+    pub fn set_arrival(&mut self, time: Instant, seq: u32) {
+        self.recv_arrival = Some((time, seq));
     }
 
     /// Parses the contents of a packet into messages, appending all valid
     /// messages into the internal receive queue.
-    pub fn receive_packet(&mut self, packet: &[u8]) {
-        for m in messages_from_packet(packet) {
+    ///
+    /// The remote `tick` number the packet was send at is attached to all of
+    /// its messages.
+    pub fn receive_packet(&mut self, packet: &[u8], tick: Option<u32>) {
+        for mut m in messages_from_packet(packet) {
+            m.tick = tick;
+            m.arrival = self.recv_arrival;
             match m.kind {
+                MessageKind::Instant | MessageKind::Reliable if m.control => {
+                    self.recv_control_queue.push_back(m);
+                },
                 MessageKind::Instant | MessageKind::Reliable => {
+                    self.received_messages = self.received_messages.wrapping_add(1);
                     self.recv_queue.push_back(m);
                 },
                 MessageKind::Ordered => self.receive_ordered_message(m),
@@ -251,22 +720,78 @@ impl MessageQueue {
         }
     }
 
+    /// Pushes a single `MessageKind::Reliable` message with the specified
+    /// `data` into the internal receive queue, as if it had been parsed from
+    /// a packet.
+    pub fn receive_reliable(&mut self, data: Vec<u8>) {
+        self.received_messages = self.received_messages.wrapping_add(1);
+        self.recv_queue.push_back(Message {
+            id: 0,
+            kind: MessageKind::Reliable,
+            control: false,
+            sent: false,
+            order: 0,
+            size: data.len() as u16,
+            tick: None,
+            arrival: self.recv_arrival,
+            data
+        });
+    }
+
+    /// Parses the contents of a packet, returning the data of all internal
+    /// control messages without pushing them into any queue.
+    #[cfg(any(feature = "encryption", feature = "authentication"))]
+    pub fn control_from_packet(packet: &[u8]) -> Vec<Vec<u8>> {
+        messages_from_packet(packet).into_iter().filter(|m| m.control).map(|m| {
+            m.data
+
+        }).collect()
+    }
+
     /// Parses the contents of a lost packet into messages, dropping all
     /// messages of the type `MessageKind::Instant` and prepending all
     /// remaining valid messages into the internal send queues for
     /// re-transmission.
-    pub fn lost_packet(&mut self, packet: &[u8]) {
-        for m in messages_from_packet(packet) {
+    ///
+    /// The `handles` returned when the packet was serialized are restored on
+    /// the re-queued messages.
+    pub fn lost_packet(&mut self, packet: &[u8], handles: &[Option<MessageHandle>]) {
+        for (i, mut m) in messages_from_packet(packet).into_iter().enumerate() {
+            m.id = handles.get(i).and_then(|h| *h).map_or(0, |h| h.0);
             match m.kind {
                 MessageKind::Instant | MessageKind::Invalid => {
                     // ignore lost instant / invalid messages
                 },
+                MessageKind::Reliable if m.control => self.c_queue.push_front(m),
                 MessageKind::Reliable => self.r_queue.push_front(m),
                 MessageKind::Ordered => self.o_queue.push_front(m)
             }
         }
     }
 
+    /// Removes all reliable and ordered messages which are still waiting to be
+    /// send from the queue and returns them.
+    ///
+    /// Ordered messages are returned in their original order.
+    pub fn take_pending(&mut self) -> Vec<(MessageKind, Vec<u8>)> {
+
+        let mut pending: Vec<(MessageKind, Vec<u8>)> = self.r_queue.drain(..).map(|m| {
+            (m.kind, m.data)
+
+        }).collect();
+
+        // Sort ordered messages by their distance to the next local order id
+        let local_order_id = self.local_order_id;
+        let mut ordered: Vec<Message> = self.o_queue.drain(..).collect();
+        ordered.sort_by_key(|m| {
+            (m.order + MAX_ORDER_ID - local_order_id) % MAX_ORDER_ID
+        });
+
+        pending.extend(ordered.into_iter().map(|m| (m.kind, m.data)));
+        pending
+
+    }
+
     /// Resets the queue, clearing all its internal structures and order ids.
     pub fn reset(&mut self) {
         self.local_order_id = 0;
@@ -274,9 +799,15 @@ impl MessageQueue {
         self.i_queue.clear();
         self.r_queue.clear();
         self.o_queue.clear();
+        self.c_queue.clear();
+        self.delayed_queue.clear();
         self.recv_queue.clear();
+        self.recv_control_queue.clear();
         self.o_recv_heap.clear();
         self.o_recv_set.clear();
+        self.sent_messages = 0;
+        self.received_messages = 0;
+        self.recv_arrival = None;
     }
 
     // Internal Message Handling ----------------------------------------------
@@ -287,6 +818,7 @@ impl MessageQueue {
         if m.order == self.remote_order_id {
 
             // Received the message in order
+            self.received_messages = self.received_messages.wrapping_add(1);
             self.recv_queue.push_back(m);
 
             self.remote_order_id += 1;
@@ -317,6 +849,7 @@ impl MessageQueue {
 
                     // Remove it from the heap and push it into the recv queue
                     let msg = self.o_recv_heap.pop().unwrap();
+                    self.received_messages = self.received_messages.wrapping_add(1);
                     self.recv_queue.push_back(msg);
 
                     self.remote_order_id += 1;
@@ -346,6 +879,12 @@ impl MessageQueue {
 }
 
 // Static Helpers -------------------------------------------------------------
+fn remove_message(queue: &mut VecDeque<Message>, id: u32) -> Option<u16> {
+    queue.iter().position(|m| m.id == id && !m.sent).and_then(|index| {
+        queue.remove(index).map(|m| m.order)
+    })
+}
+
 fn order_is_more_recent(a: u16, b: u16) -> bool {
     (a > b) && (a - b <= MAX_ORDER_ID / 2)
     || (b > a) && (b - a > MAX_ORDER_ID / 2)
@@ -371,16 +910,25 @@ fn messages_from_packet(packet: &[u8]) -> Vec<Message> {
         // Read available data
         messages.push(Message {
 
+            // Messages parsed from packets can no longer be cancelled
+            id: 0,
+            sent: true,
+
             // Lower 4 bits of byte 0 are the MessageKind
             kind: match packet[index] & 0x0F {
-                0 => MessageKind::Instant,
-                1 => MessageKind::Reliable,
+                0 | 8 => MessageKind::Instant,
+                1 | 9 => MessageKind::Reliable,
                 2 => MessageKind::Ordered,
                 _ => MessageKind::Invalid
             },
 
+            // With the 4th bit marking internal control messages
+            control: packet[index] & CONTROL_MESSAGE_FLAG == CONTROL_MESSAGE_FLAG,
+
             order: order_high | order_low,
-            size: size,
+            size,
+            tick: None,
+            arrival: None,
             data: packet[
                 index + MESSAGE_HEADER_BYTES..cmp::min(
                     index + MESSAGE_HEADER_BYTES + size as usize,
@@ -402,10 +950,12 @@ fn write_messages(
     queue: &mut VecDeque<Message>,
     packet: &mut Vec<u8>,
     available: usize,
-    written: &mut usize
+    written: &mut usize,
+    handles: &mut Vec<Option<MessageHandle>>,
+    sent: &mut u32
 ) {
     let mut used = 0;
-    while write_message(queue, packet, available, &mut used) {}
+    while write_message(queue, packet, available, &mut used, handles, sent) {}
     *written += used;
 }
 
@@ -413,7 +963,9 @@ fn write_message(
     queue: &mut VecDeque<Message>,
     packet: &mut Vec<u8>,
     available: usize,
-    written: &mut usize
+    written: &mut usize,
+    handles: &mut Vec<Option<MessageHandle>>,
+    sent: &mut u32
 
 ) -> bool {
 
@@ -433,14 +985,34 @@ fn write_message(
         // Remove and serialize the message into the packet
         } else {
             let message = queue.pop_front().unwrap();
+            let control = if message.control {
+                CONTROL_MESSAGE_FLAG
+            } else {
+                0
+            };
             packet.push(
-                ((message.order & 0x0F00) >> 4) as u8 | (message.kind as u8)
+                ((message.order & 0x0F00) >> 4) as u8 | (message.kind as u8) | control
             );
             packet.push(message.order as u8);
             packet.push((message.size >> 8) as u8);
             packet.push(message.size as u8);
             packet.extend_from_slice(&message.data[..]);
             *written += required;
+
+            // Messages parsed from lost packets were already counted
+            if !message.sent && !message.control {
+                *sent = sent.wrapping_add(1);
+            }
+
+            // Keep track of reliable and ordered messages for delivery
+            // notifications
+            handles.push(match message.kind {
+                MessageKind::Reliable | MessageKind::Ordered if message.id != 0 => {
+                    Some(MessageHandle(message.id))
+                },
+                _ => None
+            });
+
             true
         }
 
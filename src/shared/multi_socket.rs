@@ -0,0 +1,103 @@
+// Copyright (c) 2015-2016 Ivo Wetzel
+
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use std::net;
+use std::collections::HashMap;
+use std::io::{Error, ErrorKind};
+use std::sync::mpsc::TryRecvError;
+use super::super::traits::socket::Socket;
+
+/// Implementation of a socket which multiplexes several underlying sockets,
+/// i.e. in order to bind a server to both a LAN and a public interface.
+///
+/// The socket tracks on which of its sockets each remote address was last
+/// seen and sends all replies to that address through the same socket.
+/// Packets for remotes which have yet to be seen are send through the first
+/// socket.
+#[derive(Debug)]
+pub struct MultiSocket<S: Socket> {
+    sockets: Vec<S>,
+    routes: HashMap<net::SocketAddr, usize>,
+    next: usize
+}
+
+impl<S: Socket> MultiSocket<S> {
+
+    /// Creates a new socket multiplexing all of the specified `sockets`.
+    ///
+    /// Returns an error of kind `InvalidInput` in case no sockets were
+    /// specified.
+    pub fn new(sockets: Vec<S>) -> Result<Self, Error> {
+        if sockets.is_empty() {
+            Err(Error::new(ErrorKind::InvalidInput, "no sockets to multiplex"))
+
+        } else {
+            Ok(MultiSocket {
+                sockets,
+                routes: HashMap::new(),
+                next: 0
+            })
+        }
+    }
+
+    /// Returns the local addresses of all underlying sockets.
+    pub fn local_addrs(&self) -> Result<Vec<net::SocketAddr>, Error> {
+        self.sockets.iter().map(|socket| socket.local_addr()).collect()
+    }
+
+    /// Returns the local address of the socket on which packets from the
+    /// specified remote address were last received, if any.
+    pub fn local_addr_for(&self, addr: net::SocketAddr) -> Option<net::SocketAddr> {
+        self.routes.get(&addr).and_then(|index| {
+            self.sockets[*index].local_addr().ok()
+        })
+    }
+
+    /// Stops tracking the socket of the specified remote address, returning
+    /// whether it was tracked.
+    pub fn forget(&mut self, addr: net::SocketAddr) -> bool {
+        self.routes.remove(&addr).is_some()
+    }
+
+}
+
+impl<S: Socket> Socket for MultiSocket<S> {
+
+    /// Attempts to return a incoming packet from any of the underlying
+    /// sockets without blocking, taking turns between them.
+    fn try_recv(&mut self) -> Result<(net::SocketAddr, Vec<u8>), TryRecvError> {
+
+        let count = self.sockets.len();
+        for offset in 0..count {
+            let index = (self.next + offset) % count;
+            if let Ok((addr, packet)) = self.sockets[index].try_recv() {
+                self.next = (index + 1) % count;
+                self.routes.insert(addr, index);
+                return Ok((addr, packet));
+            }
+        }
+
+        Err(TryRecvError::Empty)
+
+    }
+
+    /// Sends data to the given address through the socket on which it was
+    /// last seen.
+    fn send_to(
+        &mut self, data: &[u8], addr: net::SocketAddr)
+
+    -> Result<usize, Error> {
+        let index = self.routes.get(&addr).cloned().unwrap_or(0);
+        self.sockets[index].send_to(data, addr)
+    }
+
+    /// Returns the local address of the first underlying socket.
+    fn local_addr(&self) -> Result<net::SocketAddr, Error> {
+        self.sockets[0].local_addr()
+    }
+
+}
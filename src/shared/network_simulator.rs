@@ -0,0 +1,297 @@
+// Copyright (c) 2015-2016 Ivo Wetzel
+
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+#[cfg(feature = "network_simulator")]
+extern crate rand;
+
+use std::net;
+use std::io::{Error, ErrorKind};
+use std::sync::mpsc::TryRecvError;
+#[cfg(feature = "network_simulator")]
+use std::time::{Duration, Instant};
+use super::super::{Config, ConnectionID};
+#[cfg(feature = "network_simulator")]
+use super::super::ConnectionRng;
+use super::super::traits::socket::Socket;
+
+/// A packet which is held back until it is due.
+#[cfg(feature = "network_simulator")]
+#[derive(Debug)]
+struct DelayedPacket {
+    due: Instant,
+    addr: net::SocketAddr,
+    data: Vec<u8>
+}
+
+/// Socket which passes all packets through to an underlying socket while
+/// simulating bad network conditions, as configured via
+/// `Config::simulated_latency`, `Config::simulated_jitter`,
+/// `Config::simulated_packet_loss` and `Config::simulated_packet_duplication`.
+///
+/// Packets are passed through right away while no simulation is configured.
+///
+/// Once the underlying socket was taken via `SimulatedSocket::take()`, no
+/// further packets are send or received.
+#[cfg(feature = "network_simulator")]
+#[derive(Debug)]
+pub struct SimulatedSocket<S: Socket> {
+    socket: Option<S>,
+    config: Config,
+    rng: ConnectionRng,
+    incoming: Vec<DelayedPacket>,
+    outgoing: Vec<DelayedPacket>
+}
+
+#[cfg(feature = "network_simulator")]
+impl<S: Socket> SimulatedSocket<S> {
+
+    /// Creates a new socket which simulates the network conditions of the
+    /// specified configuration on top of `socket`.
+    pub fn new(socket: S, config: Config) -> SimulatedSocket<S> {
+        SimulatedSocket {
+            socket: Some(socket),
+            config,
+            rng: ConnectionRng::new(ConnectionID(rand::random())),
+            incoming: Vec::new(),
+            outgoing: Vec::new()
+        }
+    }
+
+    /// Overrides the simulated network conditions with the ones of the
+    /// specified configuration.
+    pub fn set_config(&mut self, config: Config) {
+        self.config = config;
+    }
+
+    /// Takes the underlying socket, sending all outgoing packets which are
+    /// still held back right away and discarding the incoming ones.
+    pub fn take(&mut self) -> Option<S> {
+        self.outgoing.sort_by_key(|packet| packet.due);
+        for packet in self.outgoing.drain(..) {
+            send_to(&mut self.socket, &packet.data, packet.addr).ok();
+        }
+        self.incoming.clear();
+        self.socket.take()
+    }
+
+    fn simulated(&self) -> bool {
+        self.config.simulated_latency > 0
+            || self.config.simulated_jitter > 0
+            || self.config.simulated_packet_loss > 0.0
+            || self.config.simulated_packet_duplication > 0.0
+    }
+
+    // Returns the times at which the copies of a packet are due, which are
+    // none for lost packets and two for duplicated ones
+    fn schedule(&mut self) -> Vec<Instant> {
+        let mut due = Vec::new();
+        if self.rng.next_f32() * 100.0 >= self.config.simulated_packet_loss {
+            due.push(self.delay());
+            if self.rng.next_f32() * 100.0 < self.config.simulated_packet_duplication {
+                due.push(self.delay());
+            }
+        }
+        due
+    }
+
+    fn delay(&mut self) -> Instant {
+        let jitter = self.rng.range(0, self.config.simulated_jitter.saturating_add(1));
+        let delay = self.config.simulated_latency.saturating_add(jitter);
+        Instant::now() + Duration::from_millis(u64::from(delay))
+    }
+
+    // Sends all outgoing packets which are due, reporting the last error
+    fn flush(&mut self) -> Result<(), Error> {
+
+        let now = Instant::now();
+        let mut result = Ok(());
+        let mut index = 0;
+        while index < self.outgoing.len() {
+            if self.outgoing[index].due <= now {
+                let packet = self.outgoing.remove(index);
+                if let Err(err) = send_to(&mut self.socket, &packet.data, packet.addr) {
+                    result = Err(err);
+                }
+
+            } else {
+                index += 1;
+            }
+        }
+
+        result
+
+    }
+
+}
+
+#[cfg(feature = "network_simulator")]
+impl<S: Socket> Socket for SimulatedSocket<S> {
+
+    fn try_recv(&mut self) -> Result<(net::SocketAddr, Vec<u8>), TryRecvError> {
+
+        let passthrough = !self.simulated()
+            && self.incoming.is_empty() && self.outgoing.is_empty();
+
+        let socket = match self.socket {
+            Some(ref mut socket) => socket,
+            None => return Err(TryRecvError::Disconnected)
+        };
+
+        if passthrough {
+            return socket.try_recv();
+        }
+
+        let mut received = Vec::new();
+        while let Ok(packet) = socket.try_recv() {
+            received.push(packet);
+        }
+
+        self.flush().ok();
+
+        for (addr, data) in received {
+            for due in self.schedule() {
+                self.incoming.push(DelayedPacket {
+                    due,
+                    addr,
+                    data: data.clone()
+                });
+            }
+        }
+
+        // Jitter may reorder packets
+        let now = Instant::now();
+        let next = self.incoming.iter().enumerate().filter(|&(_, packet)| {
+            packet.due <= now
+
+        }).min_by_key(|&(_, packet)| packet.due).map(|(index, _)| index);
+
+        match next {
+            Some(index) => {
+                let packet = self.incoming.remove(index);
+                Ok((packet.addr, packet.data))
+            },
+            None => Err(TryRecvError::Empty)
+        }
+
+    }
+
+    fn send_to(&mut self, data: &[u8], addr: net::SocketAddr) -> Result<usize, Error> {
+
+        if !self.simulated() && self.outgoing.is_empty() {
+            return send_to(&mut self.socket, data, addr);
+        }
+
+        for due in self.schedule() {
+            self.outgoing.push(DelayedPacket {
+                due,
+                addr,
+                data: data.to_vec()
+            });
+        }
+
+        self.flush().map(|_| data.len())
+
+    }
+
+    fn local_addr(&self) -> Result<net::SocketAddr, Error> {
+        match self.socket {
+            Some(ref socket) => socket.local_addr(),
+            None => Err(Error::new(ErrorKind::NotConnected, ""))
+        }
+    }
+
+    fn rebind(&mut self, addr: net::SocketAddr) -> Result<net::SocketAddr, Error> {
+        match self.socket {
+            Some(ref mut socket) => socket.rebind(addr),
+            None => Err(Error::new(ErrorKind::NotConnected, ""))
+        }
+    }
+
+    fn register_connection(&mut self, id: ConnectionID) {
+        if let Some(ref mut socket) = self.socket {
+            socket.register_connection(id);
+        }
+    }
+
+}
+
+/// Socket which passes all packets through to an underlying socket.
+///
+/// The simulation of bad network conditions is only included when the
+/// `network_simulator` feature is enabled.
+#[cfg(not(feature = "network_simulator"))]
+#[derive(Debug)]
+pub struct SimulatedSocket<S: Socket> {
+    socket: Option<S>
+}
+
+#[cfg(not(feature = "network_simulator"))]
+impl<S: Socket> SimulatedSocket<S> {
+
+    /// Creates a new socket which passes all packets through to `socket`.
+    pub fn new(socket: S, _: Config) -> SimulatedSocket<S> {
+        SimulatedSocket {
+            socket: Some(socket)
+        }
+    }
+
+    /// Does nothing, as no network conditions are simulated.
+    pub fn set_config(&mut self, _: Config) {}
+
+    /// Takes the underlying socket.
+    pub fn take(&mut self) -> Option<S> {
+        self.socket.take()
+    }
+
+}
+
+#[cfg(not(feature = "network_simulator"))]
+impl<S: Socket> Socket for SimulatedSocket<S> {
+
+    fn try_recv(&mut self) -> Result<(net::SocketAddr, Vec<u8>), TryRecvError> {
+        match self.socket {
+            Some(ref mut socket) => socket.try_recv(),
+            None => Err(TryRecvError::Disconnected)
+        }
+    }
+
+    fn send_to(&mut self, data: &[u8], addr: net::SocketAddr) -> Result<usize, Error> {
+        send_to(&mut self.socket, data, addr)
+    }
+
+    fn local_addr(&self) -> Result<net::SocketAddr, Error> {
+        match self.socket {
+            Some(ref socket) => socket.local_addr(),
+            None => Err(Error::new(ErrorKind::NotConnected, ""))
+        }
+    }
+
+    fn rebind(&mut self, addr: net::SocketAddr) -> Result<net::SocketAddr, Error> {
+        match self.socket {
+            Some(ref mut socket) => socket.rebind(addr),
+            None => Err(Error::new(ErrorKind::NotConnected, ""))
+        }
+    }
+
+    fn register_connection(&mut self, id: ConnectionID) {
+        if let Some(ref mut socket) = self.socket {
+            socket.register_connection(id);
+        }
+    }
+
+}
+
+fn send_to<S: Socket>(
+    socket: &mut Option<S>, data: &[u8], addr: net::SocketAddr
+
+) -> Result<usize, Error> {
+    match *socket {
+        Some(ref mut socket) => socket.send_to(data, addr),
+        None => Err(Error::new(ErrorKind::NotConnected, ""))
+    }
+}
+
@@ -0,0 +1,47 @@
+// Copyright (c) 2015-2016 Ivo Wetzel
+
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+extern crate rand;
+
+use std::fmt;
+use self::rand::{OsRng, Rng};
+use super::super::{ConnectionID, IdGenerator};
+
+/// Implementation of a connection id generator which draws the ids from the
+/// random number generator of the operating system.
+pub struct OsIdGenerator {
+    rng: OsRng
+}
+
+impl OsIdGenerator {
+
+    /// Creates a new boxed generator.
+    ///
+    /// # Panics
+    ///
+    /// Panics in case the random number generator of the operating system is
+    /// not available.
+    pub fn new() -> Box<OsIdGenerator> {
+        Box::new(OsIdGenerator {
+            rng: OsRng::new().expect("Failed to access the OS random number generator")
+        })
+    }
+
+}
+
+impl IdGenerator for OsIdGenerator {
+    fn generate(&mut self) -> ConnectionID {
+        ConnectionID(self.rng.next_u32())
+    }
+}
+
+impl fmt::Debug for OsIdGenerator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "OsIdGenerator")
+    }
+}
+
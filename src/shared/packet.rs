@@ -0,0 +1,65 @@
+// Copyright (c) 2015-2016 Ivo Wetzel
+
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+/// Flag set on the type byte of packets whose body starts with the sender's
+/// tick number.
+pub const TICK_NUMBER_FLAG: u8 = 0x10;
+
+/// Number of bytes used by the tick number of flagged packets.
+pub const TICK_NUMBER_BYTES: usize = 4;
+
+/// Enum of packet types which are carried in the packet header when
+/// `Config::packet_types` is enabled.
+///
+/// The lower four bits of the type byte hold the type, while the upper four
+/// bits are reserved for flags.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum PacketType {
+
+    /// Packet containing messages.
+    Data = 0,
+
+    /// Packet send only to promptly acknowledge received data.
+    AckOnly = 1,
+
+    /// Packet notifying the remote of the connection's closure.
+    Close = 2,
+
+    /// Packet without any messages which only keeps the connection alive.
+    KeepAlive = 3,
+
+    /// Packet containing a fragment of a larger message.
+    ///
+    /// Reserved for future use, such packets are currently ignored.
+    Fragment = 4,
+
+    /// Packet with an extended header.
+    ///
+    /// Reserved for future use, such packets are currently ignored.
+    Extension = 5
+
+}
+
+impl PacketType {
+
+    /// Parses the packet type from its binary representation, returning
+    /// `None` for unknown types.
+    pub fn from_byte(byte: u8) -> Option<PacketType> {
+        match byte & 0x0F {
+            0 => Some(PacketType::Data),
+            1 => Some(PacketType::AckOnly),
+            2 => Some(PacketType::Close),
+            3 => Some(PacketType::KeepAlive),
+            4 => Some(PacketType::Fragment),
+            5 => Some(PacketType::Extension),
+            _ => None
+        }
+    }
+
+}
+
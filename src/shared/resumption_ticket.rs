@@ -0,0 +1,193 @@
+// Copyright (c) 2015-2016 Ivo Wetzel
+
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use super::encryption::PacketCipher;
+use super::super::ConnectionID;
+
+/// Version of the format produced by `ResumptionTicket::to_bytes`.
+const TICKET_FORMAT_VERSION: u8 = 1;
+
+/// An encrypted ticket issued by a `Server` configured with a
+/// `Config::resumption_ticket_key`, which allows a client to re-establish its
+/// session with a single round trip, i.e. after a crash or a network change.
+///
+/// The session state is sealed into the ticket with the server's key, so the
+/// server does not need to keep any state for it. Resumed connections keep
+/// their previous `ConnectionID` and skip the key exchange, switching to a
+/// fresh key which is derived from the ticket's secret and nonces exchanged
+/// by both ends.
+///
+/// Since the ticket contains the session key, it must be stored as securely
+/// as the key itself when persisting it via `ResumptionTicket::to_bytes`.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+/// use cobalt::{ConnectionID, ResumptionTicket};
+///
+/// let key = [7; 32];
+/// let ticket = ResumptionTicket::issue(
+///     key, ConnectionID(1234), Duration::from_secs(60), Some([1; 32])
+/// );
+///
+/// let bytes = ticket.to_bytes();
+/// assert_eq!(ResumptionTicket::from_bytes(&bytes), Some(ticket.clone()));
+/// assert_eq!(ResumptionTicket::verify(key, ConnectionID(1234), &ticket.data), Some(ticket));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResumptionTicket {
+
+    /// The id of the connection the ticket was issued for.
+    pub id: ConnectionID,
+
+    /// The session key which was derived from the key exchange, if any.
+    pub session_key: Option<[u8; 32]>,
+
+    /// The sealed ticket which is presented to the server.
+    pub data: Vec<u8>
+
+}
+
+impl ResumptionTicket {
+
+    /// Issues a ticket for the connection with the specified `id` and
+    /// `session_key`, sealed with the private `key` of the server and
+    /// `valid_for` the given duration.
+    pub fn issue(
+        key: [u8; 32],
+        id: ConnectionID,
+        valid_for: Duration,
+        session_key: Option<[u8; 32]>
+
+    ) -> ResumptionTicket {
+
+        let expires = (unix_time() + valid_for).as_secs();
+        let mut body = Vec::with_capacity(41);
+        for i in 0..8 {
+            body.push((expires >> (56 - i * 8)) as u8);
+        }
+
+        if let Some(session_key) = session_key {
+            body.push(1);
+            body.extend_from_slice(&session_key);
+
+        } else {
+            body.push(0);
+        }
+
+        ResumptionTicket {
+            id,
+            session_key,
+            data: PacketCipher::new(key).encrypt(&id_to_bytes(id), &body)
+        }
+
+    }
+
+    /// Returns the id of the connection a sealed ticket was issued for.
+    pub fn id_from_sealed(data: &[u8]) -> Option<ConnectionID> {
+        if data.len() >= 4 {
+            Some(ConnectionID(
+                (data[0] as u32) << 24 | (data[1] as u32) << 16 |
+                (data[2] as u32) << 8  |  data[3] as u32
+            ))
+
+        } else {
+            None
+        }
+    }
+
+    /// Opens a sealed ticket with the specified private `key`, returning it
+    /// only in case it was issued for the connection with the specified `id`
+    /// and has not yet expired.
+    pub fn verify(key: [u8; 32], id: ConnectionID, data: &[u8]) -> Option<ResumptionTicket> {
+
+        if ResumptionTicket::id_from_sealed(data) != Some(id) {
+            return None;
+        }
+
+        let opened = PacketCipher::new(key).decrypt(&data[..4], &data[4..])?;
+        if opened.len() < 13 {
+            return None;
+        }
+
+        let expires = opened[4..12].iter().fold(0, |value, b| value << 8 | *b as u64);
+        if unix_time().as_secs() >= expires {
+            return None;
+        }
+
+        Some(ResumptionTicket {
+            id,
+            session_key: session_key_from_bytes(&opened[12..])?,
+            data: data.to_vec()
+        })
+
+    }
+
+    /// Serializes the ticket for persistent storage.
+    ///
+    /// The serialized bytes start with a format version so that tickets
+    /// persisted by older versions can be detected and discarded.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![TICKET_FORMAT_VERSION];
+        bytes.extend_from_slice(&id_to_bytes(self.id));
+        if let Some(session_key) = self.session_key {
+            bytes.push(1);
+            bytes.extend_from_slice(&session_key);
+
+        } else {
+            bytes.push(0);
+        }
+        bytes.extend_from_slice(&self.data);
+        bytes
+    }
+
+    /// Parses a ticket which was serialized via `ResumptionTicket::to_bytes`.
+    ///
+    /// Returns `None` for malformed bytes or bytes which were serialized with
+    /// a different format version.
+    pub fn from_bytes(bytes: &[u8]) -> Option<ResumptionTicket> {
+
+        if bytes.first() != Some(&TICKET_FORMAT_VERSION) || bytes.len() < 6 {
+            return None;
+        }
+
+        let bytes = &bytes[1..];
+
+        let session_key = session_key_from_bytes(&bytes[4..])?;
+        let offset = if session_key.is_some() { 37 } else { 5 };
+        Some(ResumptionTicket {
+            id: ResumptionTicket::id_from_sealed(bytes)?,
+            session_key,
+            data: bytes[offset..].to_vec()
+        })
+
+    }
+
+}
+
+// Static Helpers -------------------------------------------------------------
+fn session_key_from_bytes(data: &[u8]) -> Option<Option<[u8; 32]>> {
+    match data.first() {
+        Some(&0) => Some(None),
+        Some(&1) if data.len() >= 33 => {
+            let mut key = [0; 32];
+            key.copy_from_slice(&data[1..33]);
+            Some(Some(key))
+        },
+        _ => None
+    }
+}
+
+fn unix_time() -> Duration {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default()
+}
+
+fn id_to_bytes(id: ConnectionID) -> [u8; 4] {
+    [(id.0 >> 24) as u8, (id.0 >> 16) as u8, (id.0 >> 8) as u8, id.0 as u8]
+}
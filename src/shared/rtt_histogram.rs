@@ -0,0 +1,119 @@
+// Copyright (c) 2015-2016 Ivo Wetzel
+
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use std::cmp;
+
+/// Number of bits used for the linear sub-buckets within each power of two.
+const SUB_BUCKET_BITS: u32 = 5;
+
+/// Number of linear sub-buckets within each power of two.
+const SUB_BUCKETS: usize = 1 << SUB_BUCKET_BITS;
+
+/// Largest recordable sample, bigger samples are recorded as this value.
+const MAX_SAMPLE: u32 = u16::MAX as u32;
+
+/// Total number of buckets required to cover all samples up to
+/// `MAX_SAMPLE`.
+const BUCKETS: usize = (16 - SUB_BUCKET_BITS as usize + 1) * SUB_BUCKETS;
+
+/// Histogram of roundtrip time samples in milliseconds.
+///
+/// Samples below `64` are counted exactly, while bigger samples are counted
+/// in buckets which grow with each power of two, keeping the relative error
+/// below `1 / 32`.
+#[derive(Debug, Clone)]
+pub struct RttHistogram {
+    buckets: Vec<u32>,
+    total: u32,
+    max: u32
+}
+
+impl RttHistogram {
+
+    /// Creates a new, empty histogram.
+    pub fn new() -> RttHistogram {
+        RttHistogram {
+            buckets: vec![0; BUCKETS],
+            total: 0,
+            max: 0
+        }
+    }
+
+    /// Records a single `sample` in milliseconds.
+    pub fn record(&mut self, sample: u32) {
+        let sample = cmp::min(sample, MAX_SAMPLE);
+        let count = &mut self.buckets[bucket_index(sample)];
+        *count = count.saturating_add(1);
+        self.total = self.total.saturating_add(1);
+        self.max = cmp::max(self.max, sample);
+    }
+
+    /// Returns the sample in milliseconds below or at which the percentage
+    /// `p` of all recorded samples fall, or `0` in case no samples have been
+    /// recorded.
+    ///
+    /// `p` is clamped to the range of `0.0` to `100.0`.
+    pub fn percentile(&self, p: f32) -> u32 {
+
+        if self.total == 0 {
+            return 0;
+        }
+
+        let p = p.clamp(0.0, 100.0) as f64;
+        let rank = cmp::max((p / 100.0 * self.total as f64).ceil() as u32, 1);
+
+        let mut seen = 0;
+        for (index, count) in self.buckets.iter().enumerate() {
+            seen += *count;
+            if seen >= rank {
+                return cmp::min(bucket_max(index), self.max);
+            }
+        }
+
+        self.max
+
+    }
+
+    /// Removes all recorded samples.
+    pub fn clear(&mut self) {
+        for count in &mut self.buckets {
+            *count = 0;
+        }
+        self.total = 0;
+        self.max = 0;
+    }
+
+}
+
+impl Default for RttHistogram {
+    fn default() -> RttHistogram {
+        RttHistogram::new()
+    }
+}
+
+// Static Helpers -------------------------------------------------------------
+fn bucket_index(sample: u32) -> usize {
+    if (sample as usize) < SUB_BUCKETS * 2 {
+        sample as usize
+
+    } else {
+        let shift = 31 - sample.leading_zeros() - SUB_BUCKET_BITS;
+        SUB_BUCKETS * shift as usize + (sample >> shift) as usize
+    }
+}
+
+fn bucket_max(index: usize) -> u32 {
+    if index < SUB_BUCKETS * 2 {
+        index as u32
+
+    } else {
+        let shift = (index / SUB_BUCKETS - 1) as u32;
+        let sub = (index % SUB_BUCKETS + SUB_BUCKETS) as u32;
+        ((sub + 1) << shift) - 1
+    }
+}
+
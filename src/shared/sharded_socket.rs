@@ -0,0 +1,249 @@
+// Copyright (c) 2015-2016 Ivo Wetzel
+
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+extern crate socket2;
+
+use std::net;
+use std::fmt;
+use std::io::{Error, ErrorKind};
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{channel, Receiver, Sender, TryRecvError};
+use std::time::{Duration, Instant};
+use self::socket2::{Domain, Protocol, Type};
+use super::connection::{Connection, ConnectionID};
+use super::fnv_hasher::FnvBuildHasher;
+use super::udp_socket::UdpSocket;
+use super::super::traits::socket::Socket;
+use super::super::Config;
+
+/// One shard of a group of UDP sockets which are bound to the same address
+/// via `SO_REUSEPORT`, so the kernel balances the incoming packets across
+/// them.
+///
+/// Each shard is meant to drive its own `Server` on a dedicated thread. The
+/// kernel picks the shard by the remote address of each packet, so the
+/// shards share a directory of the connection ids they serve: packets which
+/// reach a different shard than the one serving their connection, i.e. after
+/// the remote migrated to a new address, are forwarded to the serving shard.
+///
+/// Connection ids are only entered into the directory once the `Server` of a
+/// shard accepted the connection, see `Socket::register_connection`, so
+/// packets with unknown ids are handled by the shard which received them.
+///
+/// Connection ids which did not receive any packets for longer than the
+/// configured `connection_drop_threshold` and `resumption_threshold` are
+/// released from the directory, so the next shard to accept them takes them
+/// over.
+///
+/// > Note: This struct is feature-gated and will only be included when the
+/// > `reuse_port` feature is enabled and the target is a unix platform.
+pub struct ShardedSocket {
+    socket: UdpSocket,
+    shard: usize,
+    shards: Vec<Sender<(net::SocketAddr, Vec<u8>)>>,
+    forwarded: Receiver<(net::SocketAddr, Vec<u8>)>,
+    directory: Arc<ConnectionDirectory>,
+    config: Config
+}
+
+impl ShardedSocket {
+
+    /// Binds the specified number of `shards` to the same local address.
+    ///
+    /// When binding to port `0`, all shards share the port picked by the
+    /// operating system for the first one.
+    pub fn bind<A: net::ToSocketAddrs>(
+        addr: A, shards: usize, config: Config
+
+    ) -> Result<Vec<ShardedSocket>, Error> {
+
+        let mut addr = addr.to_socket_addrs()?.next().ok_or_else(|| {
+            Error::new(ErrorKind::InvalidInput, "No address to bind to")
+        })?;
+
+        let mut sockets = Vec::with_capacity(shards);
+        for _ in 0..shards {
+            let socket = reuse_port_socket(addr)?;
+            addr = socket.local_addr()?;
+            sockets.push(UdpSocket::from_std(socket, config.packet_max_size)?);
+        }
+
+        let (senders, receivers): (Vec<_>, Vec<_>) = (0..shards).map(|_| channel()).unzip();
+        let directory = Arc::new(ConnectionDirectory::new(
+            Duration::from_millis(
+                config.connection_drop_threshold as u64 + config.resumption_threshold as u64
+            )
+        ));
+
+        Ok(sockets.into_iter().zip(receivers).enumerate().map(|(shard, (socket, forwarded))| {
+            ShardedSocket {
+                socket,
+                shard,
+                shards: senders.clone(),
+                forwarded,
+                directory: directory.clone(),
+                config
+            }
+
+        }).collect())
+
+    }
+
+    /// Returns the index of this shard within its group.
+    pub fn shard(&self) -> usize {
+        self.shard
+    }
+
+    /// Returns the index of the shard serving the specified connection id,
+    /// if any.
+    pub fn owner(&self, id: ConnectionID) -> Option<usize> {
+        self.directory.owner(id)
+    }
+
+}
+
+impl Socket for ShardedSocket {
+
+    /// Attempts to return a incoming packet for a connection served by this
+    /// shard without blocking, forwarding the packets of connections served
+    /// by other shards.
+    fn try_recv(&mut self) -> Result<(net::SocketAddr, Vec<u8>), TryRecvError> {
+
+        if let Ok(packet) = self.forwarded.try_recv() {
+            return Ok(packet);
+        }
+
+        loop {
+
+            let (addr, packet) = self.socket.try_recv()?;
+            let id = if packet.len() >= 8 {
+                Connection::id_from_packet(&self.config, &packet)
+
+            } else {
+                None
+            };
+
+            let shard = id.and_then(|id| self.directory.route(id)).unwrap_or(self.shard);
+
+            if shard == self.shard {
+                return Ok((addr, packet));
+            }
+
+            self.shards[shard].send((addr, packet)).ok();
+
+        }
+
+    }
+
+    /// Send data on the socket to the given address. On success, returns the
+    /// number of bytes written.
+    fn send_to(
+        &mut self, data: &[u8], addr: net::SocketAddr)
+
+    -> Result<usize, Error> {
+        self.socket.send_to(data, addr)
+    }
+
+    /// Returns the address shared by all shards.
+    fn local_addr(&self) -> Result<net::SocketAddr, Error> {
+        self.socket.local_addr()
+    }
+
+    /// Enters the connection id into the directory, so packets which reach
+    /// other shards are forwarded to this one.
+    fn register_connection(&mut self, id: ConnectionID) {
+        self.directory.register(id, self.shard);
+    }
+
+}
+
+impl fmt::Debug for ShardedSocket {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "ShardedSocket({}, {:?})", self.shard, self.socket)
+    }
+}
+
+/// Directory of the shards serving each connection id.
+///
+/// The directory is only locked for writing when connections are registered
+/// and while pruning idle ids, so routing packets merely takes a read lock.
+struct ConnectionDirectory {
+    owners: RwLock<HashMap<ConnectionID, (usize, AtomicU64), FnvBuildHasher>>,
+    idle_threshold: u64,
+    epoch: Instant,
+    last_prune: AtomicU64
+}
+
+impl ConnectionDirectory {
+
+    fn new(idle_threshold: Duration) -> ConnectionDirectory {
+        ConnectionDirectory {
+            owners: RwLock::new(HashMap::default()),
+            idle_threshold: idle_threshold.as_millis() as u64,
+            epoch: Instant::now(),
+            last_prune: AtomicU64::new(0)
+        }
+    }
+
+    fn owner(&self, id: ConnectionID) -> Option<usize> {
+        self.owners.read().unwrap().get(&id).map(|&(shard, _)| shard)
+    }
+
+    /// Returns the shard serving the connection `id`, in case any shard
+    /// registered it and it was not idle since.
+    fn route(&self, id: ConnectionID) -> Option<usize> {
+
+        let now = self.now();
+        if now.saturating_sub(self.last_prune.load(Ordering::Relaxed)) > self.idle_threshold {
+            self.last_prune.store(now, Ordering::Relaxed);
+            self.owners.write().unwrap().retain(|_, &mut (_, ref seen)| {
+                now.saturating_sub(seen.load(Ordering::Relaxed)) <= self.idle_threshold
+            });
+        }
+
+        let owners = self.owners.read().unwrap();
+        let &(shard, ref seen) = owners.get(&id)?;
+        if now.saturating_sub(seen.load(Ordering::Relaxed)) > self.idle_threshold {
+            None
+
+        } else {
+            seen.store(now, Ordering::Relaxed);
+            Some(shard)
+        }
+
+    }
+
+    /// Makes the specified `shard` serve the connection `id`, unless another
+    /// shard is still serving it.
+    fn register(&self, id: ConnectionID, shard: usize) {
+        let now = self.now();
+        let mut owners = self.owners.write().unwrap();
+        let owner = owners.entry(id).or_insert_with(|| (shard, AtomicU64::new(now)));
+        if now.saturating_sub(owner.1.load(Ordering::Relaxed)) > self.idle_threshold {
+            owner.0 = shard;
+        }
+        owner.1.store(now, Ordering::Relaxed);
+    }
+
+    fn now(&self) -> u64 {
+        self.epoch.elapsed().as_millis() as u64
+    }
+
+}
+
+fn reuse_port_socket(addr: net::SocketAddr) -> Result<net::UdpSocket, Error> {
+    let socket = socket2::Socket::new(
+        Domain::for_address(addr),
+        Type::DGRAM,
+        Some(Protocol::UDP)
+    )?;
+    socket.set_reuse_port(true)?;
+    socket.bind(&addr.into())?;
+    Ok(socket.into())
+}
@@ -0,0 +1,57 @@
+// Copyright (c) 2015-2016 Ivo Wetzel
+
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use std::rc::Rc;
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::io::Error;
+use std::net::SocketAddr;
+use std::sync::mpsc::TryRecvError;
+use super::super::traits::socket::Socket;
+
+/// Packets dispatched to a single connection of a socket shared by several
+/// connections.
+pub type Inbox = Rc<RefCell<VecDeque<(SocketAddr, Vec<u8>)>>>;
+
+/// Socket of a single connection, which receives the packets dispatched to
+/// its inbox and sends via the shared socket.
+#[derive(Debug)]
+pub struct SharedSocket<S: Socket> {
+    socket: Rc<RefCell<S>>,
+    incoming: Inbox
+}
+
+impl<S: Socket> SharedSocket<S> {
+
+    /// Creates a new socket sending via the shared `socket`, along with the
+    /// inbox into which its received packets are dispatched.
+    pub fn new(socket: Rc<RefCell<S>>) -> (SharedSocket<S>, Inbox) {
+        let incoming = Rc::new(RefCell::new(VecDeque::new()));
+        (SharedSocket {
+            socket,
+            incoming: incoming.clone()
+
+        }, incoming)
+    }
+
+}
+
+impl<S: Socket> Socket for SharedSocket<S> {
+
+    fn try_recv(&mut self) -> Result<(SocketAddr, Vec<u8>), TryRecvError> {
+        self.incoming.borrow_mut().pop_front().ok_or(TryRecvError::Empty)
+    }
+
+    fn send_to(&mut self, data: &[u8], addr: SocketAddr) -> Result<usize, Error> {
+        self.socket.borrow_mut().send_to(data, addr)
+    }
+
+    fn local_addr(&self) -> Result<SocketAddr, Error> {
+        self.socket.borrow().local_addr()
+    }
+
+}
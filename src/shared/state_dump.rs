@@ -0,0 +1,112 @@
+// Copyright (c) 2015-2016 Ivo Wetzel
+
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+#[cfg(feature = "serialization")]
+extern crate serde;
+
+use std::net::SocketAddr;
+use super::super::{Config, ConnectionID, ConnectionState, Stats};
+
+#[cfg(feature = "serialization")]
+use self::serde::{Deserialize, Serialize};
+
+/// A snapshot of the public state of a single connection, taken via
+/// `Connection::dump_state()`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serialization", derive(Serialize, Deserialize))]
+pub struct ConnectionStateDump {
+
+    /// The id of the connection.
+    pub id: ConnectionID,
+
+    /// The state of the connection.
+    pub state: ConnectionState,
+
+    /// The local address of the connection.
+    pub local_addr: SocketAddr,
+
+    /// The address of the connection's remote.
+    pub peer_addr: SocketAddr,
+
+    /// The smoothed roundtrip time in milliseconds.
+    pub rtt: u32,
+
+    /// The 99th percentile of all roundtrip times in milliseconds.
+    pub rtt_p99: u32,
+
+    /// The percent of packets which were lost.
+    pub packet_loss: f32,
+
+    /// Whether the connection is currently congested.
+    pub congested: bool,
+
+    /// Number of packets send over the connection.
+    pub packets_sent: u32,
+
+    /// Number of packets received over the connection.
+    pub packets_received: u32,
+
+    /// Number of send packets which were acknowledged.
+    pub packets_acked: u32,
+
+    /// Number of send packets which were lost.
+    pub packets_lost: u32,
+
+    /// Number of send packets which are still awaiting their
+    /// acknowledgment.
+    pub packets_unacked: usize,
+
+    /// Number of `MessageKind::Instant` messages waiting to be send.
+    pub queued_instant: usize,
+
+    /// Number of `MessageKind::Reliable` messages waiting to be send.
+    pub queued_reliable: usize,
+
+    /// Number of `MessageKind::Ordered` messages waiting to be send.
+    pub queued_ordered: usize,
+
+    /// Number of messages waiting for their delay to elapse.
+    pub queued_delayed: usize,
+
+    /// Number of received messages which have not yet been consumed.
+    pub received: usize
+
+}
+
+/// Read-only information about a connection, as returned by
+/// `Server::connections()` in between ticks.
+pub type ConnectionInfo = ConnectionStateDump;
+
+/// A snapshot of the state of a server and all of its connections, taken via
+/// `Server::dump_state()`.
+///
+/// With the `serialization` feature enabled, dumps can be serialized via
+/// `serde` in order to attach them to bug reports.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serialization", derive(Serialize, Deserialize))]
+pub struct ServerStateDump {
+
+    /// The local address the server is bound to.
+    pub local_addr: Option<SocketAddr>,
+
+    /// The configuration of the server.
+    pub config: Config,
+
+    /// The server's statistics for the last second.
+    pub stats: Stats,
+
+    /// Number of currently banned IP addresses.
+    pub banned_addresses: usize,
+
+    /// Number of currently banned connection ids.
+    pub banned_connections: usize,
+
+    /// The state of all connections, ordered by their id.
+    pub connections: Vec<ConnectionStateDump>
+
+}
+
@@ -5,32 +5,276 @@
 // <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
+#[cfg(feature = "serialization")]
+extern crate serde;
+
+use std::cmp;
+use std::ops::{AddAssign, Sub};
 use super::super::Config;
 
+#[cfg(feature = "serialization")]
+use self::serde::{Deserialize, Serialize};
+
 /// A structure containing stats data average of the course of one second.
-#[derive(Debug, PartialEq, Copy, Clone)]
+#[derive(Debug, Default, PartialEq, Copy, Clone)]
+#[cfg_attr(feature = "serialization", derive(Serialize, Deserialize))]
 pub struct Stats {
 
     /// Average number of bytes received over the last second.
     pub bytes_sent: u32,
 
     /// Average number of bytes received over the last second.
-    pub bytes_received: u32
+    pub bytes_received: u32,
+
+    /// Average deviation in microseconds of the actual tick duration from
+    /// the one required by the `send_rate` over the last second.
+    pub(crate) tick_jitter: u32,
+
+    /// Number of connections a server created over the last second.
+    pub(crate) connects: u32,
+
+    /// Number of connections a server dropped after they were closed over
+    /// the last second.
+    pub(crate) disconnects: u32,
+
+    /// Number of connections a server dropped after they were lost or never
+    /// completed their handshake over the last second.
+    pub(crate) timeouts: u32,
+
+    /// Number of connection attempts a server refused over the last second.
+    pub(crate) rejections: u32
 
 }
 
 impl Stats {
+
+    /// Returns the average deviation in microseconds of the actual tick
+    /// duration from the one required by the `send_rate` over the last
+    /// second.
+    pub fn tick_jitter(&self) -> u32 {
+        self.tick_jitter
+    }
+
+    /// Returns the number of connections a server created over the last
+    /// second.
+    pub fn connects(&self) -> u32 {
+        self.connects
+    }
+
+    /// Returns the number of connections a server dropped after they were
+    /// closed over the last second.
+    pub fn disconnects(&self) -> u32 {
+        self.disconnects
+    }
+
+    /// Returns the number of connections a server dropped after they were
+    /// lost or never completed their handshake over the last second.
+    pub fn timeouts(&self) -> u32 {
+        self.timeouts
+    }
+
+    /// Returns the number of connection attempts a server refused over the
+    /// last second.
+    pub fn rejections(&self) -> u32 {
+        self.rejections
+    }
+
     fn reset(&mut self) {
-        self.bytes_sent = 0;
-        self.bytes_received = 0;
+        *self = Stats::default();
     }
+
+}
+
+/// Number of connection changes of a server during a single tick.
+#[derive(Debug, Default, Copy, Clone)]
+pub struct ConnectionChurn {
+
+    /// Number of created connections.
+    pub connects: u32,
+
+    /// Number of closed connections which were dropped.
+    pub disconnects: u32,
+
+    /// Number of lost or expired connections which were dropped.
+    pub timeouts: u32,
+
+    /// Number of refused connection attempts.
+    pub rejections: u32
+
 }
 
-impl Default for Stats {
-    fn default() -> Stats {
-        Stats {
-            bytes_sent: 0,
-            bytes_received: 0
+/// A structure containing the running totals of a server along with an
+/// aggregate over the connections it currently holds.
+#[derive(Debug, Default, PartialEq, Copy, Clone)]
+#[cfg_attr(feature = "serialization", derive(Serialize, Deserialize))]
+pub struct ServerStats {
+
+    /// Total number of bytes sent since the server was bound.
+    pub bytes_sent: u64,
+
+    /// Total number of bytes received since the server was bound.
+    pub bytes_received: u64,
+
+    /// Total number of packets sent since the server was bound.
+    pub packets_sent: u64,
+
+    /// Total number of packets received since the server was bound.
+    pub packets_received: u64,
+
+    /// Number of connections which are currently connected.
+    pub active_connections: usize,
+
+    /// Number of connections which have yet to receive their first packet.
+    pub pending_connections: usize,
+
+    /// Total number of connections which were dropped since the server was
+    /// bound.
+    pub dropped_connections: u64,
+
+    /// Average round trip time in milliseconds across all active
+    /// connections.
+    pub average_rtt: u32,
+
+    /// Average packet loss in percent across all active connections.
+    pub packet_loss: f32
+
+}
+
+/// A snapshot of the statistics and queue depths of a client's connection,
+/// taken via `Client::connection_stats()`.
+#[derive(Debug, Default, PartialEq, Copy, Clone)]
+#[cfg_attr(feature = "serialization", derive(Serialize, Deserialize))]
+pub struct ClientStats {
+
+    /// The smoothed roundtrip time in milliseconds.
+    pub rtt: u32,
+
+    /// The average deviation of the roundtrip time from its average in
+    /// milliseconds.
+    pub rtt_jitter: u32,
+
+    /// The percent of packets which were lost.
+    pub packet_loss: f32,
+
+    /// Total number of bytes sent since the client connected.
+    pub bytes_sent: u64,
+
+    /// Total number of bytes received since the client connected.
+    pub bytes_received: u64,
+
+    /// Number of messages send over the connection, excluding
+    /// re-transmissions.
+    pub messages_sent: u32,
+
+    /// Number of messages received over the connection, excluding
+    /// duplicates.
+    pub messages_received: u32,
+
+    /// Number of send packets which are still awaiting their
+    /// acknowledgment.
+    pub packets_unacked: usize,
+
+    /// Number of `MessageKind::Instant` messages waiting to be send.
+    pub queued_instant: usize,
+
+    /// Number of `MessageKind::Reliable` messages waiting to be send.
+    pub queued_reliable: usize,
+
+    /// Number of `MessageKind::Ordered` messages waiting to be send.
+    pub queued_ordered: usize,
+
+    /// Number of messages waiting for their delay to elapse.
+    pub queued_delayed: usize,
+
+    /// Number of received messages which have not yet been consumed.
+    pub received: usize
+
+}
+
+/// Causes for which received packets are dropped before they reach a
+/// connection's messages.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serialization", derive(Serialize, Deserialize))]
+pub enum PacketDropReason {
+    /// The packet carried a different protocol header.
+    ProtocolMismatch,
+    /// The packet failed to decrypt or carried an invalid authentication tag.
+    AuthenticationFailed,
+    /// The packet's nonce was already received or fell too far behind.
+    Replayed,
+    /// The packet's source exceeded one of the configured packet limits.
+    RateLimited,
+    /// The packet's address or connection id is banned.
+    Banned
+}
+
+/// A structure containing the total number of received packets which were
+/// dropped for each `PacketDropReason`.
+#[derive(Debug, Default, PartialEq, Eq, Copy, Clone)]
+#[cfg_attr(feature = "serialization", derive(Serialize, Deserialize))]
+pub struct SecurityStats {
+
+    /// Number of packets dropped for a different protocol header.
+    pub protocol_mismatch: u64,
+
+    /// Number of packets dropped for failing to authenticate.
+    pub authentication_failed: u64,
+
+    /// Number of packets dropped as replays.
+    pub replayed: u64,
+
+    /// Number of packets dropped by packet limits.
+    pub rate_limited: u64,
+
+    /// Number of packets dropped for banned addresses or connection ids.
+    pub banned: u64
+
+}
+
+impl SecurityStats {
+
+    /// Counts a packet dropped for the specified `reason`.
+    pub fn record(&mut self, reason: PacketDropReason) {
+        let count = match reason {
+            PacketDropReason::ProtocolMismatch => &mut self.protocol_mismatch,
+            PacketDropReason::AuthenticationFailed => &mut self.authentication_failed,
+            PacketDropReason::Replayed => &mut self.replayed,
+            PacketDropReason::RateLimited => &mut self.rate_limited,
+            PacketDropReason::Banned => &mut self.banned
+        };
+        *count = count.wrapping_add(1);
+    }
+
+    /// Returns the total number of dropped packets.
+    pub fn total(&self) -> u64 {
+        self.protocol_mismatch
+            .wrapping_add(self.authentication_failed)
+            .wrapping_add(self.replayed)
+            .wrapping_add(self.rate_limited)
+            .wrapping_add(self.banned)
+    }
+
+}
+
+impl AddAssign for SecurityStats {
+    fn add_assign(&mut self, other: SecurityStats) {
+        self.protocol_mismatch = self.protocol_mismatch.wrapping_add(other.protocol_mismatch);
+        self.authentication_failed = self.authentication_failed.wrapping_add(other.authentication_failed);
+        self.replayed = self.replayed.wrapping_add(other.replayed);
+        self.rate_limited = self.rate_limited.wrapping_add(other.rate_limited);
+        self.banned = self.banned.wrapping_add(other.banned);
+    }
+}
+
+impl Sub for SecurityStats {
+    type Output = SecurityStats;
+    fn sub(self, other: SecurityStats) -> SecurityStats {
+        SecurityStats {
+            protocol_mismatch: self.protocol_mismatch.wrapping_sub(other.protocol_mismatch),
+            authentication_failed: self.authentication_failed.wrapping_sub(other.authentication_failed),
+            replayed: self.replayed.wrapping_sub(other.replayed),
+            rate_limited: self.rate_limited.wrapping_sub(other.rate_limited),
+            banned: self.banned.wrapping_sub(other.banned)
         }
     }
 }
@@ -51,6 +295,9 @@ pub struct StatsCollector {
     /// Internal stat buckets for O(1) average calculation
     buckets: Vec<Stats>,
 
+    /// Internal sum of the tick jitter over the last second
+    tick_jitter: u64,
+
     /// Internal stat average for the current tick
     averages: Stats
 
@@ -63,11 +310,12 @@ impl StatsCollector {
     pub fn new(config: Config) -> StatsCollector {
         StatsCollector {
             tick: 0,
-            config: config,
+            config,
             buckets: (0..config.send_rate + 1).map(|_| {
                 Stats::default()
 
             }).collect::<Vec<Stats>>(),
+            tick_jitter: 0,
             averages: Stats::default()
         }
     }
@@ -78,7 +326,8 @@ impl StatsCollector {
         self.buckets = (0..config.send_rate + 1).map(|_| {
             Stats::default()
 
-        }).collect::<Vec<Stats>>()
+        }).collect::<Vec<Stats>>();
+        self.tick_jitter = 0;
     }
 
     /// Sets the number of bytes sent for the current tick.
@@ -97,6 +346,35 @@ impl StatsCollector {
         self.buckets[self.tick as usize].bytes_received = bytes;
     }
 
+    /// Sets the tick jitter in microseconds for the current tick.
+    pub fn set_tick_jitter(&mut self, jitter: u32) {
+        let old_index = (self.tick as i32 + 1) % (self.config.send_rate + 1) as i32;
+        let old_jitter = self.buckets[old_index as usize].tick_jitter;
+        self.tick_jitter = self.tick_jitter.saturating_sub(u64::from(old_jitter)) + u64::from(jitter);
+        self.averages.tick_jitter = cmp::min(
+            self.tick_jitter / u64::from(self.config.send_rate),
+            u64::from(u32::MAX)
+
+        ) as u32;
+        self.buckets[self.tick as usize].tick_jitter = jitter;
+    }
+
+    /// Sets the connection changes for the current tick.
+    pub fn set_connection_churn(&mut self, churn: ConnectionChurn) {
+        let old_index = (self.tick as i32 + 1) % (self.config.send_rate + 1) as i32;
+        let old = self.buckets[old_index as usize];
+        self.averages.connects = (self.averages.connects - old.connects) + churn.connects;
+        self.averages.disconnects = (self.averages.disconnects - old.disconnects) + churn.disconnects;
+        self.averages.timeouts = (self.averages.timeouts - old.timeouts) + churn.timeouts;
+        self.averages.rejections = (self.averages.rejections - old.rejections) + churn.rejections;
+
+        let bucket = &mut self.buckets[self.tick as usize];
+        bucket.connects = churn.connects;
+        bucket.disconnects = churn.disconnects;
+        bucket.timeouts = churn.timeouts;
+        bucket.rejections = churn.rejections;
+    }
+
     /// Steps the internal tick value used for average calculation.
     pub fn tick(&mut self) {
         self.tick = (self.tick + 1) % (self.config.send_rate + 1);
@@ -113,6 +391,7 @@ impl StatsCollector {
         for d in &mut self.buckets {
             d.reset();
         }
+        self.tick_jitter = 0;
     }
 
 }
@@ -5,17 +5,29 @@
 // <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
+use std::cmp;
 use std::net;
 use std::fmt;
-use std::iter;
+use std::thread;
 use std::io::Error;
 use std::sync::mpsc::TryRecvError;
+use std::time::Duration;
 use super::super::traits::socket::Socket;
 
+/// Maximum time by which the read timeout of a socket may overshoot, as the
+/// operating system rounds it to the granularity of its scheduler.
+const READ_TIMEOUT_SLACK: Duration = Duration::from_millis(10);
+
 /// Non-blocking abstraction over a UDP socket.
+///
+/// IPv6 sockets report packets from IPv4 clients of a dual-stack socket with
+/// their plain IPv4 address instead of the IPv4-mapped IPv6 one, and accept
+/// plain IPv4 addresses when sending, so each remote is only ever known by a
+/// single address.
 pub struct UdpSocket {
     socket: net::UdpSocket,
-    buffer: Vec<u8>
+    buffer: Vec<u8>,
+    ipv6: bool
 }
 
 impl UdpSocket {
@@ -25,19 +37,29 @@ impl UdpSocket {
         address: T, max_packet_size: usize
 
     ) -> Result<Self, Error> {
+        UdpSocket::from_std(net::UdpSocket::bind(address)?, max_packet_size)
+    }
 
-        // Create the send socket
-        let socket = try!(net::UdpSocket::bind(address));
+    /// Creates a new UDP socket from an already bound standard library
+    /// socket, switching it into non-blocking mode.
+    ///
+    /// This allows for sockets which were configured with options not
+    /// exposed by the standard library. For servers sharded across several
+    /// sockets bound with `SO_REUSEPORT` see `ShardedSocket`.
+    pub fn from_std(socket: net::UdpSocket, max_packet_size: usize) -> Result<Self, Error> {
 
         // Switch into non-blocking mode
-        try!(socket.set_nonblocking(true));
+        socket.set_nonblocking(true)?;
 
         // Allocate receival buffer
-        let buffer: Vec<u8> = iter::repeat(0).take(max_packet_size).collect();
+        let buffer: Vec<u8> = vec![0; max_packet_size];
+
+        let ipv6 = socket.local_addr()?.is_ipv6();
 
         Ok(UdpSocket {
-            socket: socket,
-            buffer: buffer
+            socket,
+            buffer,
+            ipv6
         })
 
     }
@@ -50,7 +72,7 @@ impl Socket for UdpSocket {
     fn try_recv(&mut self) -> Result<(net::SocketAddr, Vec<u8>), TryRecvError> {
 
         if let Ok((len, src)) = self.socket.recv_from(&mut self.buffer) {
-            Ok((src, self.buffer[..len].to_vec()))
+            Ok((canonical_addr(src), self.buffer[..len].to_vec()))
 
         } else {
             Err(TryRecvError::Empty)
@@ -63,7 +85,13 @@ impl Socket for UdpSocket {
         &mut self, data: &[u8], addr: net::SocketAddr)
 
     -> Result<usize, Error> {
-        self.socket.send_to(data, addr)
+        match addr {
+            net::SocketAddr::V4(v4) if self.ipv6 => {
+                let mapped = net::SocketAddrV6::new(v4.ip().to_ipv6_mapped(), v4.port(), 0, 0);
+                self.socket.send_to(data, mapped)
+            },
+            _ => self.socket.send_to(data, addr)
+        }
     }
 
     /// Returns the socket address of the underlying `net::UdpSocket`.
@@ -71,6 +99,33 @@ impl Socket for UdpSocket {
         self.socket.local_addr()
     }
 
+    /// Blocks until a packet can be received or the `timeout` elapsed by
+    /// peeking at the underlying `net::UdpSocket` in blocking mode.
+    ///
+    /// Since read timeouts may overshoot by several milliseconds, the last
+    /// `READ_TIMEOUT_SLACK` of the `timeout` are waited for in short sleeps
+    /// instead.
+    fn wait(&mut self, timeout: Duration) {
+        if timeout <= READ_TIMEOUT_SLACK {
+            thread::sleep(cmp::min(timeout, Duration::from_millis(1)));
+
+        } else if self.socket.set_nonblocking(false).is_ok() {
+            if self.socket.set_read_timeout(Some(timeout - READ_TIMEOUT_SLACK)).is_ok() {
+                self.socket.peek_from(&mut [0; 1]).ok();
+            }
+            self.socket.set_nonblocking(true).ok();
+        }
+    }
+
+    /// Replaces the underlying `net::UdpSocket` with one bound to the
+    /// specified local address.
+    fn rebind(&mut self, addr: net::SocketAddr) -> Result<net::SocketAddr, Error> {
+        let socket = net::UdpSocket::bind(addr)?;
+        socket.set_nonblocking(true)?;
+        self.socket = socket;
+        self.socket.local_addr()
+    }
+
 }
 
 impl fmt::Debug for UdpSocket {
@@ -79,3 +134,23 @@ impl fmt::Debug for UdpSocket {
     }
 }
 
+/// Returns the plain IPv4 address for IPv4-mapped IPv6 addresses and the
+/// unchanged address otherwise.
+pub fn canonical_addr(addr: net::SocketAddr) -> net::SocketAddr {
+    match addr {
+        net::SocketAddr::V6(v6) => match v6.ip().to_ipv4_mapped() {
+            Some(ip) => net::SocketAddr::new(net::IpAddr::V4(ip), v6.port()),
+            None => addr
+        },
+        _ => addr
+    }
+}
+
+/// Returns the unspecified local address of the same family as the
+/// specified remote `addr`, i.e. for binding a socket that can reach it.
+pub fn unspecified_addr(addr: net::SocketAddr) -> net::SocketAddr {
+    match addr {
+        net::SocketAddr::V4(_) => net::SocketAddr::new(net::Ipv4Addr::UNSPECIFIED.into(), 0),
+        net::SocketAddr::V6(_) => net::SocketAddr::new(net::Ipv6Addr::UNSPECIFIED.into(), 0)
+    }
+}
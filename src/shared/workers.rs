@@ -0,0 +1,397 @@
+// Copyright (c) 2015-2016 Ivo Wetzel
+
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use std::cmp;
+use std::mem;
+use std::net::SocketAddr;
+use std::io::Error;
+use std::sync::mpsc::{channel, Receiver, Sender, TryRecvError};
+use std::thread::{self, JoinHandle};
+#[cfg(test)]
+use std::thread::ThreadId;
+use super::super::{
+    Connection, ConnectionID, Handler, HostMigration, MessageHandle,
+    PacketDropReason, SecurityStats, Server, Socket
+};
+
+/// A handler callback of a connection which was recorded on a worker thread.
+#[derive(Debug)]
+enum DeferredEvent {
+    Connection,
+    Resumed,
+    Migrated(SocketAddr),
+    Failed,
+    CongestionState(bool),
+    ReceivePressure(bool),
+    KeepAlive(u32),
+    HostMigration(HostMigration),
+    Lost,
+    Kicked(u8),
+    Closed(bool),
+    MessageDelivered(MessageHandle),
+    PacketLost(Vec<u8>),
+    PacketDropped(SocketAddr, PacketDropReason)
+}
+
+/// Handler which records the callbacks of a connection instead of invoking
+/// them, so they can be replayed on the server's thread.
+#[derive(Debug, Default)]
+struct DeferredHandler {
+    events: Vec<DeferredEvent>
+}
+
+impl Handler<()> for DeferredHandler {
+
+    fn packet_dropped(&mut self, _: &mut (), addr: SocketAddr, reason: PacketDropReason) {
+        self.events.push(DeferredEvent::PacketDropped(addr, reason));
+    }
+
+    fn connection(&mut self, _: &mut (), _: &mut Connection) {
+        self.events.push(DeferredEvent::Connection);
+    }
+
+    fn connection_resumed(&mut self, _: &mut (), _: &mut Connection) {
+        self.events.push(DeferredEvent::Resumed);
+    }
+
+    fn connection_migrated(&mut self, _: &mut (), _: &mut Connection, addr: SocketAddr) {
+        self.events.push(DeferredEvent::Migrated(addr));
+    }
+
+    fn connection_failed(&mut self, _: &mut (), _: &mut Connection) {
+        self.events.push(DeferredEvent::Failed);
+    }
+
+    fn connection_congestion_state(&mut self, _: &mut (), _: &mut Connection, state: bool) {
+        self.events.push(DeferredEvent::CongestionState(state));
+    }
+
+    fn connection_receive_pressure(&mut self, _: &mut (), _: &mut Connection, state: bool) {
+        self.events.push(DeferredEvent::ReceivePressure(state));
+    }
+
+    fn connection_keep_alive(&mut self, _: &mut (), _: &mut Connection, duration: u32) {
+        self.events.push(DeferredEvent::KeepAlive(duration));
+    }
+
+    fn connection_host_migration(
+        &mut self, _: &mut (), _: &mut Connection, migration: HostMigration
+    ) {
+        self.events.push(DeferredEvent::HostMigration(migration));
+    }
+
+    fn connection_lost(&mut self, _: &mut (), _: &mut Connection) {
+        self.events.push(DeferredEvent::Lost);
+    }
+
+    fn connection_kicked(&mut self, _: &mut (), _: &mut Connection, reason: u8) {
+        self.events.push(DeferredEvent::Kicked(reason));
+    }
+
+    fn connection_closed(&mut self, _: &mut (), _: &mut Connection, by_remote: bool) {
+        self.events.push(DeferredEvent::Closed(by_remote));
+    }
+
+    fn connection_message_delivered(
+        &mut self, _: &mut (), _: &mut Connection, handle: MessageHandle
+    ) {
+        self.events.push(DeferredEvent::MessageDelivered(handle));
+    }
+
+    fn connection_packet_lost(
+        &mut self, _: &mut (), _: &mut Connection, data: &[u8]
+    ) {
+        self.events.push(DeferredEvent::PacketLost(data.to_vec()));
+    }
+
+}
+
+/// Socket which collects the packets send by a connection on a worker
+/// thread, so they can be send on the server's thread.
+#[derive(Debug)]
+struct BufferedSocket {
+    local_addr: SocketAddr,
+    packets: Vec<(SocketAddr, Vec<u8>)>
+}
+
+impl Socket for BufferedSocket {
+
+    fn try_recv(&mut self) -> Result<(SocketAddr, Vec<u8>), TryRecvError> {
+        Err(TryRecvError::Empty)
+    }
+
+    fn send_to(&mut self, data: &[u8], addr: SocketAddr) -> Result<usize, Error> {
+        self.packets.push((addr, data.to_vec()));
+        Ok(data.len())
+    }
+
+    fn local_addr(&self) -> Result<SocketAddr, Error> {
+        Ok(self.local_addr)
+    }
+
+}
+
+/// Outcome of processing a single connection on a worker thread.
+#[derive(Debug)]
+pub struct WorkResult {
+
+    /// The id of the processed connection.
+    pub id: ConnectionID,
+
+    /// The packets which were send by the connection, in order.
+    pub packets: Vec<(SocketAddr, Vec<u8>)>,
+
+    /// The number of bytes which were send by the connection.
+    pub bytes: u32,
+
+    /// The received packets which were dropped by the connection.
+    pub dropped: SecurityStats,
+
+    events: Vec<DeferredEvent>
+
+}
+
+impl WorkResult {
+
+    /// Invokes the handler callbacks which were recorded for the connection.
+    pub fn replay(
+        &mut self,
+        server: &mut Server, conn: &mut Connection,
+        handler: &mut dyn Handler<Server>
+    ) {
+        for event in self.events.drain(..) {
+            match event {
+                DeferredEvent::Connection => handler.connection(server, conn),
+                DeferredEvent::Resumed => handler.connection_resumed(server, conn),
+                DeferredEvent::Migrated(addr) => handler.connection_migrated(server, conn, addr),
+                DeferredEvent::Failed => handler.connection_failed(server, conn),
+                DeferredEvent::CongestionState(state) => {
+                    handler.connection_congestion_state(server, conn, state)
+                },
+                DeferredEvent::ReceivePressure(state) => {
+                    handler.connection_receive_pressure(server, conn, state)
+                },
+                DeferredEvent::KeepAlive(duration) => {
+                    handler.connection_keep_alive(server, conn, duration)
+                },
+                DeferredEvent::HostMigration(migration) => {
+                    handler.connection_host_migration(server, conn, migration)
+                },
+                DeferredEvent::Lost => handler.connection_lost(server, conn),
+                DeferredEvent::Kicked(reason) => handler.connection_kicked(server, conn, reason),
+                DeferredEvent::Closed(by_remote) => handler.connection_closed(server, conn, by_remote),
+                DeferredEvent::MessageDelivered(handle) => {
+                    handler.connection_message_delivered(server, conn, handle)
+                },
+                DeferredEvent::PacketLost(data) => handler.connection_packet_lost(server, conn, &data),
+                DeferredEvent::PacketDropped(addr, reason) => handler.packet_dropped(server, addr, reason)
+            }
+        }
+    }
+
+}
+
+/// Work which is performed for a single connection on a worker thread.
+#[derive(Debug)]
+enum Job {
+    Receive(Vec<Vec<u8>>, u32),
+    Send(SocketAddr)
+}
+
+/// A persistent worker thread along with the channels which hand it shards
+/// of connections and return them once processed.
+#[derive(Debug)]
+struct Worker {
+    jobs: Sender<Vec<(Connection, Job)>>,
+    results: Receiver<Vec<(Connection, WorkResult)>>,
+    thread: JoinHandle<()>
+}
+
+/// Pool of worker threads which are spawned once when a server is bound and
+/// reused for every tick.
+///
+/// Connections are moved to the workers for the duration of a phase and
+/// returned along with their results.
+#[derive(Debug)]
+pub struct WorkerPool {
+    workers: Vec<Worker>
+}
+
+impl WorkerPool {
+
+    /// Spawns the specified number of worker threads.
+    ///
+    /// No threads are spawned for values of `0` and `1`, in which case all
+    /// connections are processed on the calling thread.
+    pub fn new(threads: usize) -> Result<WorkerPool, Error> {
+
+        let mut workers = Vec::new();
+        if threads > 1 {
+            for _ in 0..threads {
+                workers.push(WorkerPool::spawn()?);
+            }
+        }
+
+        Ok(WorkerPool {
+            workers
+        })
+
+    }
+
+    /// Returns the number of worker threads in the pool.
+    pub fn threads(&self) -> usize {
+        self.workers.len()
+    }
+
+    /// Returns the ids of the worker threads in the pool.
+    #[cfg(test)]
+    pub fn thread_ids(&self) -> Vec<ThreadId> {
+        self.workers.iter().map(|worker| worker.thread.thread().id()).collect()
+    }
+
+    /// Feeds the received packets into their connections, sharded across
+    /// the worker threads.
+    ///
+    /// Connections are returned along with their results in the order of
+    /// the `jobs`.
+    pub fn receive(
+        &self, jobs: Vec<(Connection, Vec<Vec<u8>>)>, tick_delay: u32
+
+    ) -> Vec<(Connection, WorkResult)> {
+        self.run(jobs.into_iter().map(|(conn, packets)| {
+            (conn, Job::Receive(packets, tick_delay))
+
+        }).collect())
+    }
+
+    /// Assembles the outgoing packet of each connection for the specified
+    /// remote address, sharded across the worker threads.
+    ///
+    /// Connections are returned along with their results in the order of
+    /// the `jobs`.
+    pub fn send(
+        &self, jobs: Vec<(Connection, SocketAddr)>
+
+    ) -> Vec<(Connection, WorkResult)> {
+        self.run(jobs.into_iter().map(|(conn, addr)| {
+            (conn, Job::Send(addr))
+
+        }).collect())
+    }
+
+    fn spawn() -> Result<Worker, Error> {
+
+        let (jobs, job_receiver) = channel::<Vec<(Connection, Job)>>();
+        let (result_sender, results) = channel();
+
+        let thread = thread::Builder::new().name("cobalt-worker".to_string()).spawn(move || {
+            for shard in job_receiver.iter() {
+                let processed: Vec<_> = shard.into_iter().map(|(mut conn, job)| {
+                    let result = process(&mut conn, job);
+                    (conn, result)
+
+                }).collect();
+
+                if result_sender.send(processed).is_err() {
+                    break;
+                }
+            }
+        })?;
+
+        Ok(Worker {
+            jobs,
+            results,
+            thread
+        })
+
+    }
+
+    fn run(&self, mut jobs: Vec<(Connection, Job)>) -> Vec<(Connection, WorkResult)> {
+
+        // Process the jobs in place without any workers
+        if self.workers.is_empty() {
+            return jobs.into_iter().map(|(mut conn, job)| {
+                let result = process(&mut conn, job);
+                (conn, result)
+
+            }).collect();
+        }
+
+        // Split the jobs into one contiguous shard per thread
+        let size = cmp::max(jobs.len().div_ceil(self.workers.len()), 1);
+        let mut shards = Vec::new();
+        while jobs.len() > size {
+            let rest = jobs.split_off(size);
+            shards.push(jobs);
+            jobs = rest;
+        }
+        shards.push(jobs);
+
+        for (worker, shard) in self.workers.iter().zip(shards.iter_mut()) {
+            worker.jobs.send(mem::take(shard)).expect("Connection worker thread panicked");
+        }
+
+        self.workers.iter().take(shards.len()).flat_map(|worker| {
+            worker.results.recv().expect("Connection worker thread panicked")
+
+        }).collect()
+
+    }
+
+}
+
+impl Drop for WorkerPool {
+    fn drop(&mut self) {
+        // Closing the job channels ends the workers
+        for Worker { jobs, thread, .. } in self.workers.drain(..) {
+            drop(jobs);
+            thread.join().ok();
+        }
+    }
+}
+
+fn process(conn: &mut Connection, job: Job) -> WorkResult {
+
+    let mut handler = DeferredHandler::default();
+    match job {
+        Job::Receive(packets, tick_delay) => {
+
+            let stats = conn.security_stats();
+            for packet in packets {
+                conn.receive_packet(packet, tick_delay, &mut (), &mut handler);
+            }
+
+            WorkResult {
+                id: conn.id(),
+                packets: Vec::new(),
+                bytes: 0,
+                dropped: conn.security_stats() - stats,
+                events: handler.events
+            }
+
+        },
+        Job::Send(addr) => {
+
+            let mut socket = BufferedSocket {
+                local_addr: conn.local_addr(),
+                packets: Vec::new()
+            };
+
+            let bytes = conn.send_packet(&mut socket, &addr, &mut (), &mut handler);
+
+            WorkResult {
+                id: conn.id(),
+                packets: socket.packets,
+                bytes,
+                dropped: SecurityStats::default(),
+                events: handler.events
+            }
+
+        }
+    }
+
+}
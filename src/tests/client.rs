@@ -5,15 +5,29 @@
 // <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
-use std::net::SocketAddr;
+use std::io::ErrorKind;
+use std::net::{self, IpAddr, SocketAddr};
+use std::thread;
+use std::time::Duration;
 
 use super::mock::{
+    MockSocket,
+    MockAddressSelectedClientHandler,
+    MockRebindClientHandler,
+    MockUnreachableSocket,
+    MockIdGeneratorClientHandler,
     MockTickDelayClientHandler,
     MockSyncClientHandler,
     MockClientStatsHandler,
+    MockTickPhaseHandler,
     MockTickRecorder
 };
-use super::super::{Client, Config, MessageKind, Stats};
+use super::super::{
+    Client, ClientSession, ClientStats, Config, ConnectionID, IdGenerator, MessageKind,
+    OsIdGenerator, ResumptionToken, Socket,
+    Stats,
+    UdpSocket
+};
 
 #[test]
 fn test_client_tick_delay_no_overflow() {
@@ -90,7 +104,8 @@ fn test_client_sync() {
     assert_eq!(state.packet_loss(), 0.0);
     assert_eq!(state.stats(), Stats {
         bytes_sent: 0,
-        bytes_received: 0
+        bytes_received: 0,
+        .. Stats::default()
     });
 
     let peer_addr: SocketAddr = "127.0.0.1:12345".parse().unwrap();
@@ -103,7 +118,8 @@ fn test_client_sync() {
     client.send_sync(&mut handler, &mut state);
     assert_eq!(state.stats(), Stats {
         bytes_sent: 14,
-        bytes_received: 0
+        bytes_received: 0,
+        .. Stats::default()
     });
 
     client.receive_sync(&mut handler, &mut state, 0);
@@ -113,14 +129,16 @@ fn test_client_sync() {
     client.send_sync(&mut handler, &mut state);
     assert_eq!(state.stats(), Stats {
         bytes_sent: 28,
-        bytes_received: 0
+        bytes_received: 0,
+        .. Stats::default()
     });
 
     state.send(MessageKind::Instant, b"Hello World".to_vec());
     client.send_sync(&mut handler, &mut state);
     assert_eq!(state.stats(), Stats {
         bytes_sent: 57,
-        bytes_received: 0
+        bytes_received: 0,
+        .. Stats::default()
     });
 
     state.reset();
@@ -130,6 +148,698 @@ fn test_client_sync() {
 
 }
 
+#[test]
+fn test_client_run_once() {
+
+    let mut handler = MockSyncClientHandler {
+        connect_count: 0,
+        tick_count: 0,
+        close_count: 0
+    };
+
+    let mut client = Client::new(Config::default());
+    let mut state = client.connect_sync(&mut handler, "127.0.0.1:12345").unwrap();
+
+    assert!(client.run_once(&mut handler, &mut state));
+    assert!(client.run_once(&mut handler, &mut state));
+    assert_eq!(handler.tick_count, 2);
+    assert_eq!(state.stats().bytes_sent, 28);
+
+    // Closing the client ends the loop with the next iteration
+    client.close().unwrap();
+    assert!(!client.run_once(&mut handler, &mut state));
+    assert_eq!(handler.tick_count, 3);
+    assert_eq!(handler.close_count, 1);
+
+    assert!(!client.run_once(&mut handler, &mut state));
+    assert_eq!(handler.tick_count, 3);
+    assert_eq!(handler.close_count, 1);
+
+}
+
+#[test]
+fn test_client_handle() {
+
+    let mut handler = MockSyncClientHandler {
+        connect_count: 0,
+        tick_count: 0,
+        close_count: 0
+    };
+
+    let mut client = Client::new(Config::default());
+    let handle = client.handle();
+    let mut state = client.connect_sync(&mut handler, "127.0.0.1:12345").unwrap();
+
+    // Commands are queued from other threads and applied with the next tick
+    let sender = handle.clone();
+    thread::spawn(move || {
+        sender.send(MessageKind::Instant, b"Hello World".to_vec()).unwrap();
+
+    }).join().unwrap();
+
+    assert!(client.run_once(&mut handler, &mut state));
+    assert_eq!(state.stats().bytes_sent, 29);
+
+    handle.close().unwrap();
+    assert!(!client.run_once(&mut handler, &mut state));
+    assert_eq!(handler.close_count, 1);
+
+    // Handles also close clients which are driven via tick_sync / send_sync
+    let mut state = client.connect_sync(&mut handler, "127.0.0.1:12345").unwrap();
+    handle.close().unwrap();
+    client.tick_sync(&mut handler, &mut state);
+    assert_eq!(handler.close_count, 2);
+    assert_eq!(handler.tick_count, 1);
+
+    // Messages which are still pending once the client closes are discarded
+    let mut state = client.connect_sync(&mut handler, "127.0.0.1:12345").unwrap();
+    handle.send(MessageKind::Instant, b"Hello World".to_vec()).unwrap();
+    client.close_sync(&mut handler, &mut state).unwrap();
+
+    let mut state = client.connect_sync(&mut handler, "127.0.0.1:12345").unwrap();
+    client.send_sync(&mut handler, &mut state);
+    assert_eq!(client.connection_stats().messages_sent, 0);
+    client.close_sync(&mut handler, &mut state).unwrap();
+
+    // Handles outliving their client report a broken pipe
+    drop(client);
+    assert_eq!(handle.close().unwrap_err().kind(), ErrorKind::BrokenPipe);
+
+}
+
+#[test]
+fn test_client_bind_addr() {
+
+    let mut handler = MockSyncClientHandler {
+        connect_count: 0,
+        tick_count: 0,
+        close_count: 0
+    };
+
+    let mut client = Client::new(Config::default());
+    client.set_bind_addr(Some("127.0.0.1:0".parse().unwrap()));
+
+    let mut state = client.connect_sync(&mut handler, "127.0.0.1:12345").unwrap();
+    assert_eq!(state.local_addr().ip(), "127.0.0.1".parse::<IpAddr>().unwrap());
+    client.close_sync(&mut handler, &mut state).unwrap();
+
+    // Ports which are in use are skipped
+    let taken = net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    let taken_port = taken.local_addr().unwrap().port();
+    client.set_bind_ports(Some(taken_port..=taken_port));
+    assert!(client.connect_sync(&mut handler, "127.0.0.1:12345").is_err());
+
+    let free_port = net::UdpSocket::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+    client.set_bind_ports(Some(free_port..=free_port));
+
+    let mut state = client.connect_sync(&mut handler, "127.0.0.1:12345").unwrap();
+    assert_eq!(state.local_addr().port(), free_port);
+    client.close_sync(&mut handler, &mut state).unwrap();
+
+}
+
+#[test]
+fn test_client_reconnect() {
+
+    let mut handler = MockSyncClientHandler {
+        connect_count: 0,
+        tick_count: 0,
+        close_count: 0
+    };
+
+    // Clients which never connected have no server to reconnect to
+    let mut client = Client::new(Config::default());
+    assert_eq!(
+        client.reconnect_sync(&mut handler).unwrap_err().kind(),
+        ErrorKind::NotConnected
+    );
+
+    // Host names are resolved anew with each reconnect
+    let mut state = client.connect_host_sync(&mut handler, "localhost:12345").unwrap();
+    client.close_sync(&mut handler, &mut state).unwrap();
+
+    let mut state = client.reconnect_sync(&mut handler).unwrap();
+    assert!(state.peer_addr().ip().is_loopback());
+    assert_eq!(state.peer_addr().port(), 12345);
+    client.close_sync(&mut handler, &mut state).unwrap();
+
+    // Addresses are reused as is
+    let mut state = client.connect_sync(&mut handler, "127.0.0.1:12346").unwrap();
+    client.close_sync(&mut handler, &mut state).unwrap();
+
+    let mut state = client.reconnect_sync(&mut handler).unwrap();
+    assert_eq!(state.peer_addr(), "127.0.0.1:12346".parse().unwrap());
+    client.close_sync(&mut handler, &mut state).unwrap();
+
+    assert_eq!(handler.connect_count, 4);
+    assert_eq!(handler.close_count, 4);
+
+}
+
+#[test]
+fn test_client_connect_attempts() {
+
+    let config = Config {
+        send_rate: 10,
+        connect_attempt_delay: 100,
+        .. Config::default()
+    };
+
+    let mut handler = MockAddressSelectedClientHandler {
+        connections: 0,
+        failures: 0,
+        selected: Vec::new()
+    };
+
+    // Two servers, only the second of which responds
+    let first = net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    let second = net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    second.set_read_timeout(Some(Duration::from_millis(500))).unwrap();
+    let addrs = [first.local_addr().unwrap(), second.local_addr().unwrap()];
+
+    let mut client = Client::new(config);
+    let mut state = client.connect_sync(&mut handler, &addrs[..]).unwrap();
+    assert_eq!(state.peer_addr(), addrs[0]);
+
+    // Attempts to further addresses are staggered
+    client.run_once(&mut handler, &mut state);
+    first.set_nonblocking(true).unwrap();
+    let mut buffer = [0; 256];
+    assert!(first.recv_from(&mut buffer).is_ok());
+
+    second.set_nonblocking(true).unwrap();
+    assert!(second.recv_from(&mut buffer).is_err());
+
+    // Flushing neither counts down nor starts pending attempts
+    for _ in 0..3 {
+        client.flush_sync(&mut handler, &mut state);
+    }
+    assert!(second.recv_from(&mut buffer).is_err());
+    second.set_nonblocking(false).unwrap();
+
+    client.run_once(&mut handler, &mut state);
+    let (_, from) = second.recv_from(&mut buffer).unwrap();
+
+    // The first attempt to complete its handshake is selected
+    second.send_to(&[
+        1, 2, 3, 4,
+        buffer[4], buffer[5], buffer[6], buffer[7],
+        0,
+        0,
+        0, 0, 0, 0
+
+    ], from).unwrap();
+
+    for _ in 0..50 {
+        client.run_once(&mut handler, &mut state);
+        if !handler.selected.is_empty() {
+            break;
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+
+    assert_eq!(handler.connections, 1);
+    assert_eq!(handler.selected, vec![addrs[1]]);
+    assert_eq!(state.peer_addr(), addrs[1]);
+    assert_eq!(client.peer_addr().unwrap(), addrs[1]);
+
+    client.close_sync(&mut handler, &mut state).unwrap();
+
+    // Failures are only reported once all attempts failed
+    let mut client = Client::new(Config {
+        connect_attempt_delay: 0,
+        connection_init_threshold: 10,
+        .. Config::default()
+    });
+
+    let mut state = client.connect_sync(&mut handler, &addrs[..]).unwrap();
+    for _ in 0..10 {
+        client.run_once(&mut handler, &mut state);
+        thread::sleep(Duration::from_millis(10));
+    }
+
+    assert_eq!(handler.failures, 1);
+    assert_eq!(handler.selected.len(), 1);
+
+}
+
+#[test]
+fn test_client_rebind() {
+
+    let socket = MockUnreachableSocket {
+        addr: "127.0.0.1:2000".parse().unwrap(),
+        error: Some(ErrorKind::WouldBlock),
+        sent: 0
+    };
+
+    let mut handler = MockRebindClientHandler {
+        rebound: Vec::new()
+    };
+
+    let mut client = Client::new(Config::default());
+    client.set_bind_addr(Some("127.0.0.1:0".parse().unwrap()));
+    let mut state = client.connect_from_socket_sync(
+        &mut handler, "127.0.0.1:12345", socket
+
+    ).unwrap();
+
+    // Transient errors do not rebind the socket
+    client.send_sync(&mut handler, &mut state);
+    assert!(handler.rebound.is_empty());
+    assert_eq!(state.local_addr(), "127.0.0.1:2000".parse().unwrap());
+
+    // Failed sends due to a lost address rebind the socket to the configured
+    // bind address and keep the connection
+    let mut socket = state.into_socket();
+    socket.error = Some(ErrorKind::AddrNotAvailable);
+    let mut state = client.connect_from_socket_sync(
+        &mut handler, "127.0.0.1:12345", socket
+
+    ).unwrap();
+
+    client.send_sync(&mut handler, &mut state);
+    assert_eq!(handler.rebound, vec![(
+        "127.0.0.1:2000".parse().unwrap(),
+        "127.0.0.1:2001".parse().unwrap()
+    )]);
+    assert_eq!(state.local_addr(), "127.0.0.1:2001".parse().unwrap());
+    assert_eq!(client.local_addr().unwrap(), "127.0.0.1:2001".parse().unwrap());
+
+    client.send_sync(&mut handler, &mut state);
+    assert_eq!(handler.rebound.len(), 1);
+
+    // Rebinding tries the configured bind ports
+    let mut socket = state.into_socket();
+    assert_eq!(socket.sent, 1);
+    socket.error = Some(ErrorKind::AddrNotAvailable);
+    client.set_bind_ports(Some(3000..=3001));
+    let mut state = client.connect_from_socket_sync(
+        &mut handler, "127.0.0.1:12345", socket
+
+    ).unwrap();
+
+    client.send_sync(&mut handler, &mut state);
+    assert_eq!(state.local_addr(), "127.0.0.1:3000".parse().unwrap());
+
+    // UDP sockets bind the specified address
+    let mut socket = UdpSocket::new("127.0.0.1:0", 64).unwrap();
+    let previous = socket.local_addr().unwrap();
+    let addr = socket.rebind("127.0.0.1:0".parse().unwrap()).unwrap();
+    assert_ne!(addr.port(), previous.port());
+    assert_eq!(addr.ip(), previous.ip());
+    assert_eq!(socket.local_addr().unwrap(), addr);
+
+}
+
+#[test]
+#[cfg(feature = "network_simulator")]
+fn test_client_network_simulation() {
+
+    let mut handler = MockSyncClientHandler {
+        connect_count: 0,
+        tick_count: 0,
+        close_count: 0
+    };
+
+    // Dropped packets
+    let socket = MockSocket::from_address("127.0.0.1:0");
+    let mut socket_handle = socket.handle();
+    let mut client = Client::new(Config {
+        simulated_packet_loss: 100.0,
+        .. Config::default()
+    });
+
+    let mut state = client.connect_from_socket_sync(&mut handler, "127.0.0.1:12345", socket).unwrap();
+    client.send_sync(&mut handler, &mut state);
+    assert!(socket_handle.sent().is_empty());
+
+    // Duplicated packets
+    client.set_config(Config {
+        simulated_packet_duplication: 100.0,
+        .. Config::default()
+
+    }, &mut state);
+    client.send_sync(&mut handler, &mut state);
+    assert_eq!(socket_handle.sent().len(), 2);
+
+    // Delayed packets in both directions
+    client.set_config(Config {
+        simulated_latency: 20,
+        .. Config::default()
+
+    }, &mut state);
+    socket_handle.receive(vec![("127.0.0.1:12345", vec![
+        1, 2, 3, 4,
+        0, 0, 0, 0,
+        0,
+        0,
+        0, 0, 0, 0
+    ])]);
+
+    client.receive_sync(&mut handler, &mut state, 0);
+    client.send_sync(&mut handler, &mut state);
+    assert!(socket_handle.sent().is_empty());
+    assert_eq!(state.stats().bytes_received, 0);
+
+    thread::sleep(Duration::from_millis(30));
+    client.receive_sync(&mut handler, &mut state, 0);
+    assert_eq!(socket_handle.sent().len(), 1);
+
+    client.send_sync(&mut handler, &mut state);
+    assert_eq!(state.stats().bytes_received, 14);
+
+    // Packets which are still held back are sent once the socket is taken
+    assert!(socket_handle.sent().is_empty());
+    let _ = state.into_socket();
+    assert_eq!(socket_handle.sent().len(), 1);
+
+}
+
+#[test]
+fn test_client_connection_stats() {
+
+    let mut handler = MockSyncClientHandler {
+        connect_count: 0,
+        tick_count: 0,
+        close_count: 0
+    };
+
+    let socket = MockSocket::from_address("127.0.0.1:0");
+    let mut socket_handle = socket.handle();
+    let mut client = Client::new(Config::default());
+    let mut state = client.connect_from_socket_sync(&mut handler, "127.0.0.1:12345", socket).unwrap();
+    assert_eq!(client.connection_stats(), ClientStats::default());
+
+    // Sent messages and queue depths
+    state.send(MessageKind::Instant, b"Foo".to_vec());
+    state.send(MessageKind::Reliable, b"Bar".to_vec());
+    client.send_sync(&mut handler, &mut state);
+    assert_eq!(socket_handle.sent().len(), 1);
+
+    state.send(MessageKind::Ordered, b"Baz".to_vec());
+    client.receive_sync(&mut handler, &mut state, 0);
+    assert_eq!(client.connection_stats(), ClientStats {
+        bytes_sent: 28,
+        messages_sent: 2,
+        packets_unacked: 1,
+        queued_ordered: 1,
+        .. ClientStats::default()
+    });
+
+    // Received messages
+    socket_handle.receive(vec![("127.0.0.1:12345", vec![
+        1, 2, 3, 4,
+        0, 0, 0, 0,
+        0,
+        0,
+        0, 0, 0, 0,
+        1, 0, 0, 3, 70, 111, 111
+    ])]);
+
+    client.receive_sync(&mut handler, &mut state, 0);
+    client.send_sync(&mut handler, &mut state);
+
+    let stats = client.connection_stats();
+    assert_eq!(stats.bytes_received, 21);
+    assert_eq!(stats.messages_sent, 3);
+    assert_eq!(stats.messages_received, 1);
+    assert_eq!(stats.queued_ordered, 0);
+
+    client.close_sync(&mut handler, &mut state).unwrap();
+
+}
+
+#[test]
+fn test_client_flush() {
+
+    let mut handler = MockSyncClientHandler {
+        connect_count: 0,
+        tick_count: 0,
+        close_count: 0
+    };
+
+    let socket = MockSocket::from_address("127.0.0.1:0");
+    let mut socket_handle = socket.handle();
+    let mut client = Client::new(Config::default());
+    let mut state = client.connect_from_socket_sync(&mut handler, "127.0.0.1:12345", socket).unwrap();
+
+    client.send_sync(&mut handler, &mut state);
+    assert_eq!(socket_handle.sent().len(), 1);
+
+    // Queued messages are send right away
+    state.send(MessageKind::Instant, b"Fire".to_vec());
+    client.flush_sync(&mut handler, &mut state);
+
+    let sent = socket_handle.sent();
+    assert_eq!(sent.len(), 1);
+    assert_eq!(sent[0].1.len(), 22);
+    assert_eq!(client.connection_stats().messages_sent, 1);
+
+    // Flushed bytes are accounted for with the next tick
+    client.send_sync(&mut handler, &mut state);
+    assert_eq!(state.stats().bytes_sent, 14 + 22 + 14);
+    assert_eq!(client.connection_stats().bytes_sent, 14 + 22 + 14);
+
+    client.close_sync(&mut handler, &mut state).unwrap();
+
+}
+
+#[test]
+fn test_client_session() {
+
+    let mut handler = MockSyncClientHandler {
+        connect_count: 0,
+        tick_count: 0,
+        close_count: 0
+    };
+
+    let socket = MockSocket::from_address("127.0.0.1:0");
+    let mut socket_handle = socket.handle();
+    let mut client = Client::new(Config::default());
+    let mut state = client.connect_from_socket_sync(&mut handler, "127.0.0.1:12345", socket).unwrap();
+    client.send_sync(&mut handler, &mut state);
+    assert_eq!(client.export_session(), None);
+
+    // Server issues a resumption token
+    socket_handle.receive(vec![("127.0.0.1:12345", vec![
+        1, 2, 3, 4,
+        0, 0, 0, 0,
+        5,
+        0,
+        0, 0, 0, 0,
+        9, 0, 0, 10, 0, 1, 2, 3, 4, 5, 6, 7, 8, 0
+    ])]);
+
+    client.receive_sync(&mut handler, &mut state, 0);
+    client.send_sync(&mut handler, &mut state);
+    socket_handle.sent();
+
+    let session = client.export_session().unwrap();
+    assert_eq!(session, ClientSession {
+        server_address: "127.0.0.1:12345".parse().unwrap(),
+        resumption_token: ResumptionToken {
+            id: state.id(),
+            secret: 0x0102_0304_0506_0708
+        },
+        local_seq_number: 2,
+        remote_seq_number: 5,
+        tick_number: 2
+    });
+    assert_eq!(ClientSession::from_bytes(&session.to_bytes()), Some(session));
+    drop(state);
+
+    // A new client process resumes the session
+    let socket = MockSocket::from_address("127.0.0.1:0");
+    let mut socket_handle = socket.handle();
+    let mut client = Client::new(Config::default());
+    client.resume(session);
+    assert_eq!(client.resumption_token(), Some(session.resumption_token));
+    assert_eq!(client.tick_number(), 2);
+
+    let mut state = client.connect_from_socket_sync(&mut handler, session.server_address, socket).unwrap();
+    assert_eq!(state.id(), session.resumption_token.id);
+    assert_eq!(client.export_session(), Some(session));
+
+    client.send_sync(&mut handler, &mut state);
+
+    let id = session.resumption_token.id.0;
+    socket_handle.assert_sent(vec![("127.0.0.1:12345", vec![
+        1, 2, 3, 4,
+        (id >> 24) as u8, (id >> 16) as u8, (id >> 8) as u8, id as u8,
+        2,
+        5,
+        0, 0, 0, 0,
+        8, 0, 0, 9, 1, 1, 2, 3, 4, 5, 6, 7, 8
+    ])]);
+
+    // Sessions also round trip with IPv6 server addresses
+    let session = ClientSession {
+        server_address: "[::1]:7156".parse().unwrap(),
+        .. session
+    };
+    assert_eq!(session.to_bytes().len(), 37);
+    assert_eq!(ClientSession::from_bytes(&session.to_bytes()), Some(session));
+    assert_eq!(ClientSession::from_bytes(&[0; 20]), None);
+    assert_eq!(ClientSession::from_bytes(&[]), None);
+
+    // Sessions serialized in other format versions are rejected
+    let mut bytes = session.to_bytes();
+    bytes[0] = 0;
+    assert_eq!(ClientSession::from_bytes(&bytes), None);
+
+}
+
+#[test]
+fn test_client_close_on_drop() {
+
+    let mut handler = MockSyncClientHandler {
+        connect_count: 0,
+        tick_count: 0,
+        close_count: 0
+    };
+
+    let socket = MockSocket::from_address("127.0.0.1:0");
+    let mut socket_handle = socket.handle();
+    let mut client = Client::new(Config::default());
+    let state = client.connect_from_socket_sync(&mut handler, "127.0.0.1:12345", socket).unwrap();
+
+    // Connections which were never established are left alone
+    drop(state);
+    assert!(socket_handle.sent().is_empty());
+
+    let socket = MockSocket::from_address("127.0.0.1:0");
+    let mut socket_handle = socket.handle();
+    let mut state = client.connect_from_socket_sync(&mut handler, "127.0.0.1:12345", socket).unwrap();
+
+    socket_handle.receive(vec![("127.0.0.1:12345", vec![
+        1, 2, 3, 4,
+        0, 0, 0, 0,
+        0,
+        0,
+        0, 0, 0, 0
+    ])]);
+
+    client.receive_sync(&mut handler, &mut state, 0);
+    client.send_sync(&mut handler, &mut state);
+    assert_eq!(socket_handle.sent().len(), 1);
+
+    // Established connections notify the server
+    drop(state);
+    let sent = socket_handle.sent();
+    assert_eq!(sent.len(), 1);
+    assert_eq!(sent[0].1[8..].to_vec(), vec![0, 128, 85, 85, 85, 85]);
+
+    // As do explicitly closed connections
+    let socket = MockSocket::from_address("127.0.0.1:0");
+    let mut socket_handle = socket.handle();
+    let mut state = client.connect_from_socket_sync(&mut handler, "127.0.0.1:12345", socket).unwrap();
+
+    socket_handle.receive(vec![("127.0.0.1:12345", vec![
+        1, 2, 3, 4,
+        0, 0, 0, 0,
+        0,
+        0,
+        0, 0, 0, 0
+    ])]);
+
+    client.receive_sync(&mut handler, &mut state, 0);
+    client.close_sync(&mut handler, &mut state).unwrap();
+
+    let sent = socket_handle.sent();
+    assert_eq!(sent.len(), 1);
+    assert_eq!(sent[0].1[8..].to_vec(), vec![0, 128, 85, 85, 85, 85]);
+
+    // Without sending again once dropped
+    drop(state);
+    assert!(socket_handle.sent().is_empty());
+
+}
+
+#[test]
+fn test_client_sync_tick_phases() {
+
+    let mut handler = MockTickPhaseHandler {
+        phases: Vec::new()
+    };
+
+    let mut client = Client::new(Config::default());
+    let mut state = client.connect_sync(&mut handler, "127.0.0.1:12345").unwrap();
+
+    client.receive_sync(&mut handler, &mut state, 0);
+    client.tick_sync(&mut handler, &mut state);
+    client.send_sync(&mut handler, &mut state);
+
+    assert_eq!(handler.phases, vec!["after_receive", "tick", "before_send"]);
+
+    client.close_sync(&mut handler, &mut state).unwrap();
+
+}
+
+#[test]
+fn test_client_sync_ipv6() {
+
+    let mut handler = MockTickPhaseHandler {
+        phases: Vec::new()
+    };
+
+    // The local socket is bound to the address family of the server
+    let mut client = Client::new(Config::default());
+    let mut state = client.connect_sync(&mut handler, "[::1]:12345").unwrap();
+    assert!(client.local_addr().unwrap().is_ipv6());
+    assert_eq!(client.peer_addr().unwrap(), "[::1]:12345".parse::<SocketAddr>().unwrap());
+    client.close_sync(&mut handler, &mut state).unwrap();
+
+    // IPv4-mapped addresses are known by their plain IPv4 address
+    let mut client = Client::new(Config::default());
+    let mut state = client.connect_sync(&mut handler, "[::ffff:127.0.0.1]:12345").unwrap();
+    assert_eq!(client.peer_addr().unwrap(), "127.0.0.1:12345".parse::<SocketAddr>().unwrap());
+    client.close_sync(&mut handler, &mut state).unwrap();
+
+}
+
+#[test]
+fn test_client_sync_immediate_acks() {
+
+    let config = Config {
+        immediate_acks: true,
+        .. Config::default()
+    };
+
+    let mut handler = MockTickPhaseHandler {
+        phases: Vec::new()
+    };
+
+    let socket = MockSocket::from_address("127.0.0.1:0");
+    let mut socket_handle = socket.handle();
+
+    let mut client = Client::new(config);
+    let mut state = client.connect_from_socket_sync(
+        &mut handler, "127.0.0.1:12345", socket
+
+    ).unwrap();
+
+    socket_handle.receive(vec![("127.0.0.1:12345", [
+        1, 2, 3, 4,
+        0, 0, 0, 0,
+        0,
+        0,
+        0, 0, 0, 0,
+
+        0, 0, 0, 3, 70, 111, 111
+
+    ].to_vec())]);
+
+    // Received data is acknowledged without waiting for the next send
+    client.receive_sync(&mut handler, &mut state, 0);
+
+    let sent = socket_handle.sent();
+    assert_eq!(sent.len(), 1);
+    assert_eq!(sent[0].1[14..].to_vec(), vec![8, 0, 0, 1, 7]);
+
+    client.close_sync(&mut handler, &mut state).unwrap();
+
+}
+
 #[test]
 fn test_client_sync_set_config() {
 
@@ -174,8 +884,40 @@ fn test_client_stats() {
 
     assert_eq!(client.stats(), Stats {
         bytes_sent: 580,
-        bytes_received: 0
+        bytes_received: 0,
+        .. Stats::default()
     });
 
 }
 
+
+#[test]
+fn test_client_id_generator() {
+
+    let socket = MockSocket::from_address("127.0.0.1:0");
+    let mut socket_handle = socket.handle();
+
+    let mut handler = MockIdGeneratorClientHandler;
+    let mut client = Client::new(Config::default());
+    let mut state = client.connect_from_socket_sync(
+        &mut handler, "127.0.0.1:12345", socket
+
+    ).unwrap();
+
+    // The connection uses the id provided by the handler's generator
+    assert_eq!(state.id(), ConnectionID(0x01020304));
+    client.send_sync(&mut handler, &mut state);
+
+    let sent = socket_handle.sent();
+    assert_eq!(sent[0].1[4..8].to_vec(), vec![1, 2, 3, 4]);
+
+    client.close_sync(&mut handler, &mut state).unwrap();
+
+}
+
+#[test]
+fn test_os_id_generator() {
+    let mut generator = OsIdGenerator::new();
+    let ids: Vec<ConnectionID> = (0..4).map(|_| generator.generate()).collect();
+    assert!(ids.windows(2).any(|w| w[0] != w[1]));
+}
@@ -0,0 +1,145 @@
+// Copyright (c) 2015-2016 Ivo Wetzel
+
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use std::thread;
+use std::io::ErrorKind;
+use std::sync::mpsc::{channel, Receiver};
+use std::collections::HashSet;
+use std::time::{Duration, Instant};
+use super::super::{
+    ClientEvent, ClientPool, Config, ConnectionMap, Handler,
+    MessageKind, PoolClientID, Server, Socket, UdpSocket
+};
+use super::super::contrib::EchoHandler;
+
+struct StoppableEchoHandler {
+    echo: EchoHandler,
+    stop: Receiver<()>
+}
+
+impl Handler<Server> for StoppableEchoHandler {
+    fn tick_connections(
+        &mut self, server: &mut Server,
+        connections: &mut ConnectionMap
+    ) {
+        self.echo.tick_connections(server, connections);
+        if self.stop.try_recv().is_ok() {
+            server.shutdown().unwrap();
+        }
+    }
+}
+
+fn poll<F: FnMut(PoolClientID, ClientEvent) -> bool>(pool: &mut ClientPool, mut done: F) {
+    let deadline = Instant::now() + Duration::from_secs(3);
+    while Instant::now() < deadline {
+        while let Ok((id, event)) = pool.receive() {
+            if done(id, event) {
+                return;
+            }
+        }
+        thread::sleep(Duration::from_millis(5));
+    }
+    panic!("Timed out waiting for pool events");
+}
+
+#[test]
+fn test_client_pool() {
+
+    let config = Config {
+        send_rate: 100,
+        .. Config::default()
+    };
+
+    // Echo server
+    let socket = UdpSocket::new("127.0.0.1:0", config.packet_max_size).unwrap();
+    let server_addr = socket.local_addr().unwrap();
+    let (stop, stopped) = channel();
+    let server_thread = thread::spawn(move || {
+        let mut handler = StoppableEchoHandler {
+            echo: EchoHandler::new(MessageKind::Reliable),
+            stop: stopped
+        };
+        Server::new(config).bind_to_socket(&mut handler, socket).unwrap();
+    });
+
+    // Clients sharing two sockets
+    let mut pool = ClientPool::new(config, 2, 4).unwrap();
+    assert_eq!(pool.local_addrs().len(), 2);
+
+    let ids: Vec<PoolClientID> = (0..6).map(|_| {
+        pool.connect(server_addr).unwrap()
+
+    }).collect();
+    assert_eq!(pool.len(), 6);
+
+    let mut connected = HashSet::new();
+    poll(&mut pool, |id, event| {
+        if event == ClientEvent::Connection {
+            connected.insert(id);
+        }
+        connected.len() == ids.len()
+    });
+
+    // Every client receives its own echoed message
+    for id in &ids {
+        pool.send(*id, MessageKind::Reliable, vec![id.0 as u8]).unwrap();
+    }
+
+    let mut echoed = HashSet::new();
+    poll(&mut pool, |id, event| {
+        if let ClientEvent::Message(data) = event {
+            assert_eq!(data, vec![id.0 as u8]);
+            echoed.insert(id);
+        }
+        echoed.len() == ids.len()
+    });
+
+    // Closed clients are removed from the pool
+    pool.close(ids[0]).unwrap();
+    assert_eq!(pool.len(), 5);
+    assert!(pool.close(ids[0]).is_err());
+    assert!(pool.send(ids[0], MessageKind::Instant, Vec::new()).is_err());
+
+    poll(&mut pool, |id, event| {
+        id == ids[0] && event == ClientEvent::Close
+    });
+
+    drop(pool);
+    stop.send(()).unwrap();
+    server_thread.join().unwrap();
+
+}
+
+#[test]
+fn test_client_pool_failed() {
+
+    let config = Config {
+        send_rate: 100,
+        connection_init_threshold: 100,
+        .. Config::default()
+    };
+
+    // Nobody is listening on the server's address
+    let server_addr = UdpSocket::new("127.0.0.1:0", config.packet_max_size).unwrap().local_addr().unwrap();
+
+    let mut pool = ClientPool::new(config, 1, 1).unwrap();
+    let id = pool.connect(server_addr).unwrap();
+    assert_eq!(pool.len(), 1);
+
+    poll(&mut pool, |failed, event| {
+        failed == id && event == ClientEvent::ConnectionFailed
+    });
+
+    // Failed clients are removed from the pool
+    assert!(pool.is_empty());
+    assert_eq!(
+        pool.send(id, MessageKind::Instant, Vec::new()).unwrap_err().kind(),
+        ErrorKind::NotConnected
+    );
+    assert!(pool.close(id).is_err());
+
+}
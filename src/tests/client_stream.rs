@@ -32,7 +32,8 @@ fn test_client_stream() {
     assert_eq!(stream.packet_loss(), 0.0);
     assert_eq!(stream.stats(), Stats {
         bytes_sent: 0,
-        bytes_received: 0
+        bytes_received: 0,
+        .. Stats::default()
     });
 
     assert_eq!(stream.bytes_sent(), 0);
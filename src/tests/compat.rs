@@ -0,0 +1,157 @@
+// Copyright (c) 2015-2016 Ivo Wetzel
+
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use super::mock::{create_connection, create_socket, MockOwner};
+use super::super::{Connection, Handler, MessageKind};
+
+// Golden Captures ------------------------------------------------------------
+//
+// Packets as they were send by peers running previous versions of the crate,
+// along with the handler events they are expected to produce. Changing any of
+// these means breaking older clients in the field.
+//
+// The packets were recorded from a 0.12.0 build sending through a capturing
+// `Socket`, the events by feeding them into a 0.12.0 `Connection`. For the
+// out of order capture, the packet carrying "A" was lost and its message
+// resent after the one carrying "B".
+struct Capture {
+    name: &'static str,
+    packets: &'static [&'static [u8]],
+    events: &'static [&'static str]
+}
+
+const CAPTURES: &[Capture] = &[
+    Capture {
+        name: "0.12 handshake",
+        packets: &[
+            &[1, 2, 3, 4, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0]
+        ],
+        events: &["connection"]
+    },
+    Capture {
+        name: "0.12 messages",
+        packets: &[
+            &[
+                1, 2, 3, 4, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0,
+                0, 0, 0, 3, 70, 111, 111,
+                1, 0, 0, 3, 66, 97, 114,
+                2, 0, 0, 3, 66, 97, 122
+            ]
+        ],
+        events: &["connection", "message Foo", "message Bar", "message Baz"]
+    },
+    Capture {
+        name: "0.12 out of order ordered messages",
+        packets: &[
+            &[
+                1, 2, 3, 4, 0, 0, 0, 1, 1, 0, 0, 0, 0, 0,
+                2, 1, 0, 1, 66
+            ],
+            &[
+                1, 2, 3, 4, 0, 0, 0, 1, 2, 0, 0, 0, 0, 0,
+                2, 0, 0, 1, 65
+            ]
+        ],
+        events: &["connection", "message A", "message B"]
+    },
+    Capture {
+        name: "0.12 closure",
+        packets: &[
+            &[1, 2, 3, 4, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0],
+            &[1, 2, 3, 4, 0, 0, 0, 1, 0, 128, 85, 85, 85, 85]
+        ],
+        events: &["connection", "closed by remote"]
+    }
+];
+
+// Event Recording ------------------------------------------------------------
+struct RecordingHandler {
+    events: Vec<String>
+}
+
+impl Handler<MockOwner> for RecordingHandler {
+
+    fn connection(&mut self, _: &mut MockOwner, _: &mut Connection) {
+        self.events.push("connection".to_string());
+    }
+
+    fn connection_failed(&mut self, _: &mut MockOwner, _: &mut Connection) {
+        self.events.push("failed".to_string());
+    }
+
+    fn connection_lost(&mut self, _: &mut MockOwner, _: &mut Connection) {
+        self.events.push("lost".to_string());
+    }
+
+    fn connection_closed(&mut self, _: &mut MockOwner, _: &mut Connection, by_remote: bool) {
+        self.events.push(if by_remote {
+            "closed by remote".to_string()
+
+        } else {
+            "closed".to_string()
+        });
+    }
+
+}
+
+fn replay(capture: &Capture) -> Vec<String> {
+
+    let (mut conn, mut owner, _) = create_connection(None);
+    let mut handler = RecordingHandler {
+        events: Vec::new()
+    };
+
+    for packet in capture.packets {
+        conn.receive_packet(packet.to_vec(), 0, &mut owner, &mut handler);
+        for data in conn.received() {
+            handler.events.push(
+                format!("message {}", String::from_utf8_lossy(&data))
+            );
+        }
+    }
+
+    handler.events
+
+}
+
+#[test]
+fn test_golden_captures() {
+    for capture in CAPTURES {
+        assert_eq!(replay(capture), capture.events, "Capture: {}", capture.name);
+    }
+}
+
+#[test]
+fn test_golden_send() {
+
+    // Packets send with the default configuration must remain readable by
+    // peers running previous versions of the crate
+    let (mut conn, mut socket, mut socket_handle, mut owner, mut handler) = create_socket(None);
+    let address = conn.peer_addr();
+
+    conn.send(MessageKind::Instant, b"Foo".to_vec());
+    conn.send(MessageKind::Reliable, b"Bar".to_vec());
+    conn.send(MessageKind::Ordered, b"Baz".to_vec());
+    conn.send_packet(&mut socket, &address, &mut owner, &mut handler);
+
+    socket_handle.assert_sent(vec![("255.1.1.2:5678", [
+        1, 2, 3, 4,
+        (conn.id().0 >> 24) as u8,
+        (conn.id().0 >> 16) as u8,
+        (conn.id().0 >> 8) as u8,
+         conn.id().0 as u8,
+        0, 0,
+        0, 0, 0, 0,
+
+        0, 0, 0, 3, 70, 111, 111,
+        1, 0, 0, 3, 66, 97, 114,
+        2, 0, 0, 3, 66, 97, 122
+
+    ].to_vec())]);
+
+}
+
@@ -0,0 +1,255 @@
+// Copyright (c) 2015-2016 Ivo Wetzel
+
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use std::io::ErrorKind;
+use super::super::{Config, ConnectionConfig, EndpointConfig};
+
+#[cfg(feature = "serialization")]
+use std::{env, fs};
+#[cfg(feature = "serialization")]
+use super::super::{ClosedSendPolicy, SendOrder};
+
+#[test]
+fn test_config_validate() {
+
+    assert!(Config::default().validate().is_ok());
+
+    let err = Config {
+        send_rate: 0,
+        .. Config::default()
+
+    }.validate().unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidData);
+    assert_eq!(err.to_string(), "send_rate: must be greater than 0");
+
+    let err = Config {
+        packet_max_size: 32,
+        .. Config::default()
+
+    }.validate().unwrap_err();
+    assert_eq!(err.to_string(), "packet_max_size: must be between 64 and 65507");
+
+    let err = Config {
+        message_quota_reliable: -1.0,
+        .. Config::default()
+
+    }.validate().unwrap_err();
+    assert_eq!(err.to_string(), "message_quota_reliable: must be between 0 and 100");
+
+    let err = Config {
+        packet_tick_numbers: true,
+        .. Config::default()
+
+    }.validate().unwrap_err();
+    assert_eq!(err.to_string(), "packet_tick_numbers: requires packet_types to be enabled");
+
+    let err = Config {
+        connection_init_threshold: 100,
+        handshake_retry_interval: 100,
+        .. Config::default()
+
+    }.validate().unwrap_err();
+    assert_eq!(err.to_string(), "handshake_retry_interval: must be less than connection_init_threshold");
+
+    let err = Config {
+        tick_overflow_recovery_rate: 1.5,
+        .. Config::default()
+
+    }.validate().unwrap_err();
+    assert_eq!(err.to_string(), "tick_overflow_recovery_rate: must be between 0.0 and 1.0");
+
+}
+
+#[test]
+fn test_config_parts() {
+
+    let config = Config {
+        send_rate: 60,
+        packet_max_size: 1200,
+        connection_drop_threshold: 2000,
+        handshake_limit: 5,
+        .. Config::default()
+    };
+
+    let connection = config.connection();
+    assert_eq!(connection.packet_max_size, 1200);
+    assert_eq!(connection.connection_drop_threshold, 2000);
+
+    let endpoint = config.endpoint();
+    assert_eq!(endpoint.send_rate, 60);
+    assert_eq!(endpoint.handshake_limit, 5);
+
+    // Parts combine back into the original configuration
+    assert_eq!(Config::from_parts(connection, endpoint), config);
+    assert_eq!(Config::from((connection, endpoint)), config);
+
+    // Defaults of the parts match the combined defaults
+    assert_eq!(
+        Config::from_parts(ConnectionConfig::default(), EndpointConfig::default()),
+        Config::default()
+    );
+
+}
+
+#[test]
+#[cfg(feature = "network_simulator")]
+fn test_config_validate_network_simulator() {
+
+    let err = Config {
+        simulated_packet_loss: 150.0,
+        .. Config::default()
+
+    }.validate().unwrap_err();
+    assert_eq!(err.to_string(), "simulated_packet_loss: must be between 0 and 100");
+
+    let err = Config {
+        simulated_packet_duplication: -1.0,
+        .. Config::default()
+
+    }.validate().unwrap_err();
+    assert_eq!(err.to_string(), "simulated_packet_duplication: must be between 0 and 100");
+
+}
+
+#[test]
+#[cfg(feature = "key_exchange")]
+fn test_config_validate_key_exchange() {
+
+    let err = Config {
+        key_exchange_encryption: true,
+        .. Config::default()
+
+    }.validate().unwrap_err();
+    assert_eq!(err.to_string(), "key_exchange_encryption: requires key_exchange to be enabled");
+
+    assert!(Config {
+        key_exchange: true,
+        key_exchange_encryption: true,
+        .. Config::default()
+
+    }.validate().is_ok());
+
+}
+
+#[test]
+#[cfg(feature = "authentication")]
+fn test_config_validate_migration_validation() {
+
+    let err = Config {
+        migration_validation: true,
+        .. Config::default()
+
+    }.validate().unwrap_err();
+    assert_eq!(err.to_string(), "migration_validation: requires encryption or an authentication_key");
+
+    assert!(Config {
+        migration_validation: true,
+        authentication_key: Some([9; 32]),
+        .. Config::default()
+
+    }.validate().is_ok());
+
+}
+
+#[test]
+#[cfg(feature = "authentication")]
+fn test_config_validate_handshake_cookies() {
+
+    let err = Config {
+        handshake_cookies: true,
+        handshake_min_size: 31,
+        .. Config::default()
+
+    }.validate().unwrap_err();
+    assert_eq!(err.to_string(), "handshake_min_size: must be at least 32 with handshake_cookies enabled");
+
+    assert!(Config {
+        handshake_cookies: true,
+        handshake_min_size: 32,
+        .. Config::default()
+
+    }.validate().is_ok());
+
+}
+
+#[test]
+#[cfg(feature = "serialization")]
+fn test_config_from_toml() {
+
+    let config = Config::from_toml(r#"
+        send_rate = 60
+        packet_max_size = 1200
+        protocol_header = [5, 6, 7, 8]
+        closed_send_policy = "Drop"
+        send_order = "Shuffle"
+    "#).unwrap();
+
+    assert_eq!(config, Config {
+        send_rate: 60,
+        packet_max_size: 1200,
+        protocol_header: [5, 6, 7, 8],
+        closed_send_policy: ClosedSendPolicy::Drop,
+        send_order: SendOrder::Shuffle,
+        .. Config::default()
+    });
+
+    // Unknown fields
+    let err = Config::from_toml("send_rat = 60").unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidData);
+    assert!(err.to_string().contains("send_rat"));
+
+    // Invalid values
+    let err = Config::from_toml("send_rate = 0").unwrap_err();
+    assert_eq!(err.to_string(), "send_rate: must be greater than 0");
+
+}
+
+#[test]
+#[cfg(feature = "serialization")]
+fn test_config_from_json() {
+
+    let config = Config::from_json(r#"{
+        "send_rate": 20,
+        "immediate_acks": true
+    }"#).unwrap();
+
+    assert_eq!(config, Config {
+        send_rate: 20,
+        immediate_acks: true,
+        .. Config::default()
+    });
+
+    let err = Config::from_json(r#"{ "packet_max_size": 16 }"#).unwrap_err();
+    assert_eq!(err.to_string(), "packet_max_size: must be between 64 and 65507");
+
+}
+
+#[test]
+#[cfg(feature = "serialization")]
+fn test_config_from_file() {
+
+    let dir = env::temp_dir();
+    let toml = dir.join(format!("cobalt-config-{}.toml", std::process::id()));
+    let json = dir.join(format!("cobalt-config-{}.json", std::process::id()));
+    let other = dir.join(format!("cobalt-config-{}.ini", std::process::id()));
+
+    fs::write(&toml, "send_rate = 45\n").unwrap();
+    fs::write(&json, "{ \"send_rate\": 50 }").unwrap();
+    fs::write(&other, "send_rate = 55\n").unwrap();
+
+    assert_eq!(Config::from_file(&toml).unwrap().send_rate, 45);
+    assert_eq!(Config::from_file(&json).unwrap().send_rate, 50);
+    assert_eq!(Config::from_file(&other).unwrap_err().kind(), ErrorKind::InvalidInput);
+
+    fs::remove_file(&toml).unwrap();
+    fs::remove_file(&json).unwrap();
+    fs::remove_file(&other).unwrap();
+
+    // Missing files
+    assert_eq!(Config::from_file(&toml).unwrap_err().kind(), ErrorKind::NotFound);
+
+}
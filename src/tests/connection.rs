@@ -6,18 +6,28 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 use std::net;
-use std::iter;
+use std::io::ErrorKind;
 use std::thread;
 use std::time::Duration;
 
-use super::mock::{create_connection, create_socket, MockOwner};
-use super::super::{Connection, ConnectionState, Config, MessageKind, Handler};
+use super::mock::{create_connection, create_socket, MockOwner, MockSocket};
+use super::super::{
+    ClosedSendPolicy, HostMigration, Peer,
+    Connection, ConnectionID, ConnectionState, Config, MessageHandle,
+    MessageKind, Handler, RateLimiter, ResumptionToken
+};
+
+#[cfg(feature = "connect_token")]
+use super::super::ConnectToken;
+
+#[cfg(feature = "encryption")]
+use super::super::ResumptionTicket;
 
 #[test]
 fn test_create() {
     let (conn, _, _) = create_connection(None);
-    assert_eq!(conn.open(), true);
-    assert_eq!(conn.congested(), false);
+    assert!(conn.open());
+    assert!(!conn.congested());
     assert!(conn.state() == ConnectionState::Connecting);
     assert_eq!(conn.rtt(), 0);
     assert_eq!(conn.packet_loss(), 0.0);
@@ -36,6 +46,25 @@ fn test_set_tick_rate() {
     });
 }
 
+#[test]
+fn test_tags() {
+
+    let (mut conn, _, _) = create_connection(None);
+    assert!(conn.tags().is_empty());
+
+    assert!(conn.tag("observer"));
+    assert!(conn.tag("admin"));
+    assert!(!conn.tag("admin"));
+    assert!(conn.has_tag("admin"));
+    assert_eq!(conn.tags(), vec!["admin".to_string(), "observer".to_string()]);
+
+    assert!(conn.untag("admin"));
+    assert!(!conn.untag("admin"));
+    assert!(!conn.has_tag("admin"));
+    assert_eq!(conn.tags(), vec!["observer".to_string()]);
+
+}
+
 #[test]
 fn test_close_local() {
 
@@ -44,7 +73,7 @@ fn test_close_local() {
 
     // Initiate closure
     conn.close();
-    assert_eq!(conn.open(), true);
+    assert!(conn.open());
     assert!(conn.state() == ConnectionState::Closing);
 
     // Connection should now be sending closing packets
@@ -68,7 +97,7 @@ fn test_close_local() {
     conn.send_packet(&mut socket, &address, &mut owner, &mut handler);
     socket_handle.assert_sent_none();
 
-    assert_eq!(conn.open(), false);
+    assert!(!conn.open());
     assert!(conn.state() == ConnectionState::Closed);
 
 }
@@ -99,93 +128,210 @@ fn test_close_remote() {
 
     ].to_vec(), 0, &mut owner, &mut handler);
 
-    assert_eq!(conn.open(), false);
+    assert!(!conn.open());
     assert!(conn.state() == ConnectionState::Closed);
 
 }
 
 #[test]
-fn test_reset() {
-    let (mut conn, _, _) = create_connection(None);
-    conn.close();
-    conn.reset();
-    assert_eq!(conn.open(), true);
-    assert!(conn.state() == ConnectionState::Connecting);
+fn test_close_with_reason() {
+
+    let (mut conn, mut socket, mut socket_handle, mut owner, mut handler) = create_socket(None);
+    let (mut remote, _, _) = create_connection(None);
+    remote.receive_packet([
+        1, 2, 3, 4,
+        0, 0, 0, 0,
+        0, 0,
+        0, 0, 0, 0
+
+    ].to_vec(), 0, &mut owner, &mut handler);
+
+    // The reason is send along with the closure packets
+    conn.close_with_reason(7);
+    assert!(conn.state() == ConnectionState::Closing);
+
+    let address = conn.peer_addr();
+    conn.send_packet(&mut socket, &address, &mut owner, &mut handler);
+    let packet = socket_handle.sent().remove(0).1;
+    assert_eq!(&packet[8..], &[0, 128, 85, 85, 85, 85, 7]);
+
+    remote.receive_packet(packet, 0, &mut owner, &mut handler);
+    assert!(remote.state() == ConnectionState::Closed);
+    assert_eq!(remote.remote_close_reason(), Some(7));
+
+    remote.reset();
+    assert_eq!(remote.remote_close_reason(), None);
+
 }
 
 #[test]
-fn test_send_sequence_wrap_around() {
+fn test_idle_timeout() {
 
-    let (mut conn, mut socket, mut socket_handle, mut owner, mut handler) = create_socket(None);
+    let (mut conn, mut socket, mut socket_handle, mut owner, mut handler) = create_socket(Some(Config {
+        idle_timeout: 50,
+        .. Config::default()
+    }));
     let address = conn.peer_addr();
 
-    for i in 0..256 {
+    conn.receive_packet([
+        1, 2, 3, 4,
+        0, 0, 0, 0,
+        0, 0,
+        0, 0, 0, 0
 
-        conn.send_packet(&mut socket, &address, &mut owner, &mut handler);
+    ].to_vec(), 0, &mut owner, &mut handler);
 
-        socket_handle.assert_sent(vec![("255.1.1.2:5678", [
-            // protocol id
-            1, 2, 3, 4,
+    // Received messages reset the idle timeout
+    thread::sleep(Duration::from_millis(30));
+    conn.receive_packet([
+        1, 2, 3, 4,
+        0, 0, 0, 0,
+        1, 0,
+        0, 0, 0, 0,
 
-            // connection id
-            (conn.id().0 >> 24) as u8,
-            (conn.id().0 >> 16) as u8,
-            (conn.id().0 >> 8) as u8,
-             conn.id().0 as u8,
+        0, 0, 0, 3, 66, 97, 114
 
-            i as u8, // local sequence number
-            0, // remote sequence number
-            0, 0, 0, 0  // ack bitfield
+    ].to_vec(), 0, &mut owner, &mut handler);
 
-        ].to_vec())]);
+    thread::sleep(Duration::from_millis(30));
+    conn.send_packet(&mut socket, &address, &mut owner, &mut handler);
+    assert!(conn.state() == ConnectionState::Connected);
+
+    // Packets without any messages do not
+    conn.receive_packet([
+        1, 2, 3, 4,
+        0, 0, 0, 0,
+        2, 0,
+        0, 0, 0, 0
+
+    ].to_vec(), 0, &mut owner, &mut handler);
+
+    thread::sleep(Duration::from_millis(30));
+    socket_handle.sent();
+    conn.send_packet(&mut socket, &address, &mut owner, &mut handler);
+    assert!(conn.state() == ConnectionState::Closing);
+
+    // Idle connections are closed with their own reason code
+    let packet = socket_handle.sent().remove(0).1;
+    assert_eq!(&packet[8..], &[0, 128, 85, 85, 85, 85, Connection::IDLE_TIMEOUT_REASON]);
+
+}
+
+#[test]
+fn test_bandwidth_limit() {
+
+    let (mut conn, mut socket, mut socket_handle, mut owner, mut handler) = create_socket(None);
+    let address = conn.peer_addr();
+    assert_eq!(conn.bandwidth_limit(), 0);
+
+    conn.receive_packet([
+        1, 2, 3, 4,
+        0, 0, 0, 0,
+        0, 0,
+        0, 0, 0, 0
+
+    ].to_vec(), 0, &mut owner, &mut handler);
+
+    conn.set_bandwidth_limit(1000);
+    assert_eq!(conn.bandwidth_limit(), 1000);
 
+    for _ in 0..4 {
+        conn.send(MessageKind::Instant, vec![0; 500]);
     }
 
-    // Should now wrap around
+    // The first packets may burst up to one second's worth of bytes
     conn.send_packet(&mut socket, &address, &mut owner, &mut handler);
-    socket_handle.assert_sent(vec![("255.1.1.2:5678", [
-        // protocol id
+    conn.send_packet(&mut socket, &address, &mut owner, &mut handler);
+    let sizes: Vec<usize> = socket_handle.sent().iter().map(|p| p.1.len()).collect();
+    assert_eq!(sizes, vec![14 + 1008, 14]);
+
+    // Further messages are send once the allowance was refilled
+    thread::sleep(Duration::from_millis(600));
+    conn.send_packet(&mut socket, &address, &mut owner, &mut handler);
+    let sizes: Vec<usize> = socket_handle.sent().iter().map(|p| p.1.len()).collect();
+    assert_eq!(sizes, vec![14 + 504]);
+
+    // Removing the limit sends all remaining messages
+    conn.set_bandwidth_limit(0);
+    conn.send_packet(&mut socket, &address, &mut owner, &mut handler);
+    let sizes: Vec<usize> = socket_handle.sent().iter().map(|p| p.1.len()).collect();
+    assert_eq!(sizes, vec![14 + 504]);
+
+}
+
+#[test]
+fn test_close_remote_unauthenticated() {
+
+    let (mut conn, mut owner, mut handler) = create_connection(Some(Config {
+        authenticated_close: true,
+        .. Config::default()
+    }));
+
+    conn.receive_packet([
         1, 2, 3, 4,
+        0, 0, 0, 0,
+        0,
+        0,
+        0, 0, 0, 0
 
-        // connection id
-        (conn.id().0 >> 24) as u8,
-        (conn.id().0 >> 16) as u8,
-        (conn.id().0 >> 8) as u8,
-         conn.id().0 as u8,
+    ].to_vec(), 0, &mut owner, &mut handler);
 
-        0, // local sequence number
-        0, // remote sequence number
-        0, 0, 0, 0  // ack bitfield
+    assert!(conn.state() == ConnectionState::Connected);
 
-    ].to_vec())]);
+    // Closure packets which were not authenticated are ignored
+    conn.receive_packet([
+        1, 2, 3, 4,
+        0, 0, 0, 0,
+        0, 128, 85, 85, 85, 85
+
+    ].to_vec(), 0, &mut owner, &mut handler);
+
+    assert!(conn.open());
+    assert!(conn.state() == ConnectionState::Connected);
 
 }
 
+#[cfg(feature = "encryption")]
 #[test]
-fn test_send_and_receive_packet() {
+fn test_close_remote_authenticated() {
+
+    let config = Config {
+        encryption_key: Some([7; 32]),
+        authenticated_close: true,
+        .. Config::default()
+    };
+
+    let (mut conn, mut socket, mut socket_handle, mut owner, mut handler) = create_socket(Some(config));
+    let (mut remote, _, _) = create_connection(Some(config));
+    remote.set_id(conn.id());
+    remote.set_accepted();
 
-    let (mut conn, mut socket, mut socket_handle, mut owner, mut handler) = create_socket(None);
     let address = conn.peer_addr();
+    conn.send_packet(&mut socket, &address, &mut owner, &mut handler);
+    remote.receive_packet(socket_handle.sent().remove(0).1, 0, &mut owner, &mut handler);
+    assert!(remote.state() == ConnectionState::Connected);
 
-    // Test Initial Packet
+    // Encrypted closure packets are authenticated
+    conn.close();
     conn.send_packet(&mut socket, &address, &mut owner, &mut handler);
-    socket_handle.assert_sent(vec![("255.1.1.2:5678", [
-        // protocol id
-        1, 2, 3, 4,
+    remote.receive_packet(socket_handle.sent().remove(0).1, 0, &mut owner, &mut handler);
+    assert!(remote.state() == ConnectionState::Closed);
 
-        // connection id
-        (conn.id().0 >> 24) as u8,
-        (conn.id().0 >> 16) as u8,
-        (conn.id().0 >> 8) as u8,
-         conn.id().0 as u8,
+}
 
-        0, // local sequence number
-        0, // remote sequence number
-        0, 0, 0, 0  // ack bitfield
+#[test]
+fn test_packet_types() {
 
-    ].to_vec())]);
+    let config = Config {
+        packet_types: true,
+        immediate_acks: true,
+        .. Config::default()
+    };
 
-    // Test sending of written data
+    let (mut conn, mut socket, mut socket_handle, mut owner, mut handler) = create_socket(Some(config));
+    let address = conn.peer_addr();
+
+    // Packets without any messages are keep alive packets
     conn.send_packet(&mut socket, &address, &mut owner, &mut handler);
     socket_handle.assert_sent(vec![("255.1.1.2:5678", [
         1, 2, 3, 4,
@@ -193,13 +339,13 @@ fn test_send_and_receive_packet() {
         (conn.id().0 >> 16) as u8,
         (conn.id().0 >> 8) as u8,
          conn.id().0 as u8,
-        1, // local sequence number
-        0,
-        0, 0, 0, 0
+        0, 0,
+        0, 0, 0, 0,
+        3 // keep alive
 
     ].to_vec())]);
 
-    // Write buffer should get cleared
+    conn.send(MessageKind::Instant, b"Foo".to_vec());
     conn.send_packet(&mut socket, &address, &mut owner, &mut handler);
     socket_handle.assert_sent(vec![("255.1.1.2:5678", [
         1, 2, 3, 4,
@@ -207,400 +353,3110 @@ fn test_send_and_receive_packet() {
         (conn.id().0 >> 16) as u8,
         (conn.id().0 >> 8) as u8,
          conn.id().0 as u8,
+        1, 0,
+        0, 0, 0, 0,
+        0, // data
 
-        2, // local sequence number
-        0,
-        0, 0, 0, 0
+        0, 0, 0, 3, 70, 111, 111
 
     ].to_vec())]);
 
-    // Test receiving of a packet with acknowledgements for two older packets
+    // Receive a data packet
     conn.receive_packet([
         1, 2, 3, 4,
-        0, 0, 0, 0, // ConnectionID is ignored by receive_packet)
-        17, // local sequence number
-        2, // remote sequence number we confirm
-        0, 0, 0, 3, // confirm the first two packets
+        0, 0, 0, 0,
+        0, 0,
+        0, 0, 0, 0,
+        0, // data
+
+        0, 0, 0, 3, 66, 97, 114
 
     ].to_vec(), 0, &mut owner, &mut handler);
 
-    // Receive additional packet
-    conn.receive_packet([
+    assert!(conn.state() == ConnectionState::Connected);
+    assert_eq!(conn.received().collect::<Vec<Vec<u8>>>(), vec![b"Bar".to_vec()]);
+
+    // Acknowledgment only packets are marked by their type
+    conn.send_ack_packet(&mut socket, &address, &mut owner, &mut handler);
+    socket_handle.assert_sent(vec![("255.1.1.2:5678", [
         1, 2, 3, 4,
-        0, 0, 0, 0, // ConnectionID is ignored by receive_packet)
-        18, // local sequence number
-        3, // remote sequence number we confirm
-        0, 0, 0, 0
+        (conn.id().0 >> 24) as u8,
+        (conn.id().0 >> 16) as u8,
+        (conn.id().0 >> 8) as u8,
+         conn.id().0 as u8,
+        2, 0,
+        0, 0, 0, 0,
+        1 // ack only
 
-    ].to_vec(), 0, &mut owner, &mut handler);
+    ].to_vec())]);
 
+    // Packets of unsupported types are ignored
     conn.receive_packet([
         1, 2, 3, 4,
-        0, 0, 0, 0, // ConnectionID is ignored by receive_packet)
-        19, // local sequence number
-        4, // remote sequence number we confirm
-        0, 0, 0, 0
+        0, 0, 0, 0,
+        1, 0,
+        0, 0, 0, 0,
+        4, // fragment
+
+        0, 0, 0, 3, 66, 97, 122
 
     ].to_vec(), 0, &mut owner, &mut handler);
 
+    assert_eq!(conn.received().count(), 0);
+
+    // Receive closure packet
     conn.receive_packet([
         1, 2, 3, 4,
-        0, 0, 0, 0, // ConnectionID is ignored by receive_packet)
-        27, // local sequence number
-        4, // remote sequence number we confirm
-        0, 0, 0, 0
+        0, 0, 0, 0,
+        1, 0,
+        0, 0, 0, 0,
+        2 // close
 
     ].to_vec(), 0, &mut owner, &mut handler);
 
-    // Test Receive Ack Bitfield
+    assert!(!conn.open());
+    assert!(conn.state() == ConnectionState::Closed);
+
+}
+
+#[test]
+fn test_packet_tick_numbers() {
+
+    let config = Config {
+        packet_types: true,
+        packet_tick_numbers: true,
+        .. Config::default()
+    };
+
+    let (mut conn, mut socket, mut socket_handle, mut owner, mut handler) = create_socket(Some(config));
+    let address = conn.peer_addr();
+    conn.set_tick_number(0x0102_0304);
+    assert_eq!(conn.tick_number(), 0x0102_0304);
+
+    // Keep alive packets carry the tick number as well
     conn.send_packet(&mut socket, &address, &mut owner, &mut handler);
     socket_handle.assert_sent(vec![("255.1.1.2:5678", [
         1, 2, 3, 4,
-        (conn.id().0 >> 24) as u8,
-        (conn.id().0 >> 16) as u8,
-        (conn.id().0 >> 8) as u8,
-         conn.id().0 as u8,
-
-        3, // local sequence number
-        27, // remove sequence number set by receive_packet)
+        0, 0, 0, 0,
+        0, 0,
+        0, 0, 0, 0,
+        0x13, // keep alive with tick number
 
-        // Ack bitfield
-        0, 0, 3, 128 // 0000_0000 0000_0000 0000_0011 1000_0000
+        1, 2, 3, 4
 
     ].to_vec())]);
 
-}
+    conn.send(MessageKind::Reliable, b"Foo".to_vec());
+    conn.send_packet(&mut socket, &address, &mut owner, &mut handler);
+    socket_handle.assert_sent(vec![("255.1.1.2:5678", [
+        1, 2, 3, 4,
+        0, 0, 0, 0,
+        1, 0,
+        0, 0, 0, 0,
+        0x10, // data with tick number
 
-#[test]
-fn test_send_and_receive_messages() {
+        1, 2, 3, 4,
+        1, 0, 0, 3, 70, 111, 111
 
-    let (mut conn, mut socket, mut socket_handle, mut owner, mut handler) = create_socket(None);
-    let address = conn.peer_addr();
+    ].to_vec())]);
 
-    // Test Message Sending
-    conn.send(MessageKind::Instant, b"Foo".to_vec());
-    conn.send(MessageKind::Instant, b"Bar".to_vec());
-    conn.send(MessageKind::Reliable, b"Test".to_vec());
-    conn.send(MessageKind::Ordered, b"Hello".to_vec());
-    conn.send(MessageKind::Ordered, b"World".to_vec());
+    // Messages of pending packets are recovered without the tick number
+    assert_eq!(conn.take_pending_messages(), vec![
+        (MessageKind::Reliable, b"Foo".to_vec())
+    ]);
 
-    conn.send_packet(&mut socket, &address, &mut owner, &mut handler);
-    socket_handle.assert_sent(vec![
-        ("255.1.1.2:5678", [
-            1, 2, 3, 4,
-            (conn.id().0 >> 24) as u8,
-            (conn.id().0 >> 16) as u8,
-            (conn.id().0 >> 8) as u8,
-             conn.id().0 as u8,
-            0,
-            0,
-            0, 0, 0, 0,
-
-            // Foo
-            0, 0, 0, 3, 70, 111, 111,
-
-            // Bar
-            0, 0, 0, 3, 66, 97, 114,
-
-            // Test
-            1, 0, 0, 4, 84, 101, 115, 116,
-
-            // Hello
-            2, 0, 0, 5, 72, 101, 108, 108, 111,
-
-            // World
-            2, 1, 0, 5, 87, 111, 114, 108, 100
-
-        ].to_vec())
-    ]);
-
-    // Test Message Receiving
+    // Received messages carry the remote's tick number
     conn.receive_packet([
         1, 2, 3, 4,
         0, 0, 0, 0,
-        0,
-        0,
+        0, 0,
         0, 0, 0, 0,
+        0x10,
 
-        // Foo
-        0, 0, 0, 3, 70, 111, 111,
-
-        // Bar
-        0, 0, 0, 3, 66, 97, 114,
-
-        // Test
-        1, 0, 0, 4, 84, 101, 115, 116,
-
-        // We actually test inverse receiving order here!
-
-        // World
-        2, 1, 0, 5, 87, 111, 114, 108, 100,
-
-        // Hello
-        2, 0, 0, 5, 72, 101, 108, 108, 111
+        0, 0, 0, 7,
+        0, 0, 0, 3, 66, 97, 114
 
     ].to_vec(), 0, &mut owner, &mut handler);
 
-    // Get received messages
-    let messages: Vec<Vec<u8>> = conn.received().collect();
-
-    assert_eq!(messages, vec![
-        b"Foo".to_vec(),
-        b"Bar".to_vec(),
-        b"Test".to_vec(),
-        b"Hello".to_vec(),
-        b"World".to_vec()
-    ]);
-
-    // Test Received dismissing
+    // Packets without the flag are understood as well
     conn.receive_packet([
         1, 2, 3, 4,
         0, 0, 0, 0,
-        1,
-        1,
+        1, 0,
         0, 0, 0, 0,
+        0,
 
-        // Foo
-        0, 0, 0, 3, 70, 111, 111
+        0, 0, 0, 3, 66, 97, 122
 
     ].to_vec(), 0, &mut owner, &mut handler);
 
-    // send_packet should dismiss any received messages which have not been fetched
-    conn.send_packet(&mut socket, &address, &mut owner, &mut handler);
-    socket_handle.assert_sent(vec![
-        ("255.1.1.2:5678", [
-            1, 2, 3, 4,
-            (conn.id().0 >> 24) as u8,
-            (conn.id().0 >> 16) as u8,
-            (conn.id().0 >> 8) as u8,
-             conn.id().0 as u8,
-            1,
-            1,
-            0, 0, 0, 1
-
-        ].to_vec())
+    assert_eq!(conn.received_with_tick_numbers().collect::<Vec<_>>(), vec![
+        (Some(7), b"Bar".to_vec()),
+        (None, b"Baz".to_vec())
     ]);
 
-    let messages: Vec<Vec<u8>> = conn.received().collect();
-    assert_eq!(messages.len(), 0);
-
 }
 
 #[test]
-fn test_receive_invalid_packets() {
+fn test_packet_types_close_local() {
 
-    let (mut conn, mut owner, mut handler) = create_connection(None);
+    let config = Config {
+        packet_types: true,
+        .. Config::default()
+    };
 
-    // Empty packet
-    conn.receive_packet([].to_vec(), 0, &mut owner, &mut handler);
+    let (mut conn, mut socket, mut socket_handle, mut owner, mut handler) = create_socket(Some(config));
+    let address = conn.peer_addr();
 
-    // Garbage packet
-    conn.receive_packet([
-        1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14
+    conn.close();
 
-    ].to_vec(), 0, &mut owner, &mut handler);
+    // Closing packets are marked by their type instead of special data
+    conn.send_packet(&mut socket, &address, &mut owner, &mut handler);
+    socket_handle.assert_sent(vec![("255.1.1.2:5678", [
+        1, 2, 3, 4,
+        (conn.id().0 >> 24) as u8,
+        (conn.id().0 >> 16) as u8,
+        (conn.id().0 >> 8) as u8,
+         conn.id().0 as u8,
+        0, 0,
+        0, 0, 0, 0,
+        2 // close
+
+    ].to_vec())]);
 
 }
 
 #[test]
-fn test_rtt() {
+fn test_handshake_padding() {
 
-    let (mut conn, mut socket, mut socket_handle, mut owner, mut handler) = create_socket(None);
-    let address = conn.peer_addr();
+    let config = Config {
+        handshake_min_size: 32,
+        .. Config::default()
+    };
 
-    assert_eq!(conn.rtt(), 0);
+    let (mut conn, mut socket, mut socket_handle, mut owner, mut handler) = create_socket(Some(config));
+    let address = conn.peer_addr();
 
-    // First packet
+    // Packets are padded until the connection is established
+    conn.send(MessageKind::Instant, b"Foo".to_vec());
     conn.send_packet(&mut socket, &address, &mut owner, &mut handler);
-    socket_handle.assert_sent(vec![
-        ("255.1.1.2:5678", [
-            1, 2, 3, 4,
-            (conn.id().0 >> 24) as u8,
-            (conn.id().0 >> 16) as u8,
-            (conn.id().0 >> 8) as u8,
-             conn.id().0 as u8,
-            0,
-            0,
-            0, 0, 0, 0
-        ].to_vec())
-    ]);
-
-    thread::sleep(Duration::from_millis(500));
-    conn.receive_packet([
+    socket_handle.assert_sent(vec![("255.1.1.2:5678", [
         1, 2, 3, 4,
         0, 0, 0, 0,
-        0,
-        0, // confirm the packet above
         0, 0,
-        0, 0
+        0, 0, 0, 0,
 
-    ].to_vec(), 0, &mut owner, &mut handler);
+        0, 0, 0, 3, 70, 111, 111,
+        3, 0, 0, 7, 0, 0, 0, 0, 0, 0, 0
 
-    // Expect RTT value to have moved by 10% of the overall roundtrip time
-    assert!(conn.rtt() >= 40);
+    ].to_vec())]);
 
-    // Second packet
-    conn.send_packet(&mut socket, &address, &mut owner, &mut handler);
-    socket_handle.assert_sent(vec![
-        ("255.1.1.2:5678", [
-            1, 2, 3, 4,
-            (conn.id().0 >> 24) as u8,
-            (conn.id().0 >> 16) as u8,
-            (conn.id().0 >> 8) as u8,
-             conn.id().0 as u8,
-            1,
-            0,
-            0, 0, 0, 0
-        ].to_vec())
-    ]);
     conn.receive_packet([
         1, 2, 3, 4,
         0, 0, 0, 0,
-        1,
-        1, // confirm the packet above
         0, 0,
-        0, 0
+        0, 0, 0, 0
 
     ].to_vec(), 0, &mut owner, &mut handler);
+    assert_eq!(conn.state(), ConnectionState::Connected);
 
-    // Third packet
     conn.send_packet(&mut socket, &address, &mut owner, &mut handler);
-    socket_handle.assert_sent(vec![
-        ("255.1.1.2:5678", [
-            1, 2, 3, 4,
-            (conn.id().0 >> 24) as u8,
-            (conn.id().0 >> 16) as u8,
-            (conn.id().0 >> 8) as u8,
-             conn.id().0 as u8,
-            2,
-            1,
-            0, 0, 0, 1
-        ].to_vec())
-    ]);
-    conn.receive_packet([
+    socket_handle.assert_sent(vec![("255.1.1.2:5678", [
         1, 2, 3, 4,
         0, 0, 0, 0,
-        2,
-        2, // confirm the packet above
-        0, 0,
-        0, 0
+        1, 0,
+        0, 0, 0, 0
 
-    ].to_vec(), 0, &mut owner, &mut handler);
+    ].to_vec())]);
+
+}
+
+#[test]
+fn test_handshake_retries() {
+
+    let config = Config {
+        connection_init_threshold: 1000,
+        handshake_retry_interval: 30,
+        handshake_max_attempts: 2,
+        .. Config::default()
+    };
+
+    let (mut conn, mut socket, mut socket_handle, mut owner, mut handler) = create_socket(Some(config));
+    let address = conn.peer_addr();
 
-    // Fourth packet
     conn.send_packet(&mut socket, &address, &mut owner, &mut handler);
-    socket_handle.assert_sent(vec![
-        ("255.1.1.2:5678", [
-            1, 2, 3, 4,
-            (conn.id().0 >> 24) as u8,
-            (conn.id().0 >> 16) as u8,
-            (conn.id().0 >> 8) as u8,
-             conn.id().0 as u8,
-            3,
-            2,
-            0, 0, 0, 3
-        ].to_vec())
-    ]);
-    conn.receive_packet([
-        1, 2, 3, 4,
-        0, 0, 0, 0,
-        3,
-        3, // confirm the packet above
-        0, 0,
-        0, 0
+    assert_eq!(socket_handle.sent().len(), 1);
 
-    ].to_vec(), 0, &mut owner, &mut handler);
+    // Handshakes are retransmitted at the configured interval
+    conn.send_packet(&mut socket, &address, &mut owner, &mut handler);
+    assert!(socket_handle.sent().is_empty());
 
-    // Fifth packet
+    thread::sleep(Duration::from_millis(40));
     conn.send_packet(&mut socket, &address, &mut owner, &mut handler);
-    socket_handle.assert_sent(vec![
-        ("255.1.1.2:5678", [
-            1, 2, 3, 4,
-            (conn.id().0 >> 24) as u8,
-            (conn.id().0 >> 16) as u8,
-            (conn.id().0 >> 8) as u8,
-             conn.id().0 as u8,
-            4,
-            3,
-            0, 0, 0, 7
-        ].to_vec())
-    ]);
-    conn.receive_packet([
-        1, 2, 3, 4,
-        0, 0, 0, 0,
-        4,
-        4, // confirm the packet above
-        0, 0,
-        0, 0
+    assert_eq!(socket_handle.sent().len(), 1);
 
-    ].to_vec(), 0, &mut owner, &mut handler);
+    // The attempt fails once the last handshake went unanswered
+    conn.send_packet(&mut socket, &address, &mut owner, &mut handler);
+    assert_eq!(conn.state(), ConnectionState::Connecting);
 
-    // Sixth packet
+    thread::sleep(Duration::from_millis(40));
     conn.send_packet(&mut socket, &address, &mut owner, &mut handler);
-    socket_handle.assert_sent(vec![
-        ("255.1.1.2:5678", [
-            1, 2, 3, 4,
-            (conn.id().0 >> 24) as u8,
-            (conn.id().0 >> 16) as u8,
-            (conn.id().0 >> 8) as u8,
-             conn.id().0 as u8,
-            5,
-            4,
-            0, 0, 0, 15
-        ].to_vec())
-    ]);
+    assert!(socket_handle.sent().is_empty());
+    assert_eq!(conn.state(), ConnectionState::FailedToConnect);
 
+    // Established connections send with every tick
+    conn.reset();
+    conn.send_packet(&mut socket, &address, &mut owner, &mut handler);
     conn.receive_packet([
         1, 2, 3, 4,
         0, 0, 0, 0,
-        5,
-        5, // confirm the packet above
         0, 0,
-        0, 0
+        0, 0, 0, 0
 
     ].to_vec(), 0, &mut owner, &mut handler);
+    assert_eq!(conn.state(), ConnectionState::Connected);
 
-    // Expect RTT to have reduced by 10%
-    assert!(conn.rtt() <= 40);
+    conn.send_packet(&mut socket, &address, &mut owner, &mut handler);
+    conn.send_packet(&mut socket, &address, &mut owner, &mut handler);
+    assert_eq!(socket_handle.sent().len(), 3);
 
 }
 
 #[test]
-fn test_rtt_tick_correction() {
+fn test_amplification_limit() {
 
-    let (mut conn, mut socket, mut socket_handle, mut owner, mut handler) = create_socket(None);
+    let config = Config {
+        amplification_factor: 2,
+        .. Config::default()
+    };
+
+    let (mut conn, mut socket, mut socket_handle, mut owner, mut handler) = create_socket(Some(config));
     let address = conn.peer_addr();
+    conn.require_address_validation();
 
-    assert_eq!(conn.rtt(), 0);
+    conn.receive_packet([
+        1, 2, 3, 4,
+        0, 0, 0, 0,
+        0, 0,
+        0, 0, 0, 0
 
-    // First packet
+    ].to_vec(), 0, &mut owner, &mut handler);
+
+    // Packets exceeding twice the received bytes are dropped
+    conn.send(MessageKind::Instant, b"Hello World".to_vec());
     conn.send_packet(&mut socket, &address, &mut owner, &mut handler);
-    socket_handle.assert_sent(vec![
-        ("255.1.1.2:5678", [
-            1, 2, 3, 4,
-            (conn.id().0 >> 24) as u8,
-            (conn.id().0 >> 16) as u8,
-            (conn.id().0 >> 8) as u8,
-             conn.id().0 as u8,
-            0,
-            0,
-            0, 0, 0, 0
-        ].to_vec())
+    socket_handle.assert_sent_none();
+
+    conn.send_packet(&mut socket, &address, &mut owner, &mut handler);
+    socket_handle.assert_sent(vec![("255.1.1.2:5678", [
+        1, 2, 3, 4,
+        0, 0, 0, 0,
+        1, 0,
+        0, 0, 0, 0
+
+    ].to_vec())]);
+
+    // The remote acknowledging a packet validates its address
+    conn.receive_packet([
+        1, 2, 3, 4,
+        0, 0, 0, 0,
+        1, 1,
+        0, 0, 0, 0
+
+    ].to_vec(), 0, &mut owner, &mut handler);
+
+    conn.send(MessageKind::Instant, b"Hello World".to_vec());
+    conn.send_packet(&mut socket, &address, &mut owner, &mut handler);
+    socket_handle.assert_sent(vec![("255.1.1.2:5678", [
+        1, 2, 3, 4,
+        0, 0, 0, 0,
+        2, 1,
+        0, 0, 0, 1,
+
+        0, 0, 0, 11, 72, 101, 108, 108, 111, 32, 87, 111, 114, 108, 100
+
+    ].to_vec())]);
+
+}
+
+#[test]
+fn test_dump_state() {
+
+    let (mut conn, mut socket, _, mut owner, mut handler) = create_socket(None);
+    let address = conn.peer_addr();
+
+    conn.send(MessageKind::Instant, b"Foo".to_vec());
+    conn.send(MessageKind::Reliable, b"Bar".to_vec());
+    conn.send(MessageKind::Ordered, b"Baz".to_vec());
+    conn.send_after(MessageKind::Ordered, b"Later".to_vec(), Duration::from_secs(60)).unwrap();
+
+    let dump = conn.dump_state();
+    assert_eq!(dump.id, conn.id());
+    assert_eq!(dump.state, ConnectionState::Connecting);
+    assert_eq!(dump.peer_addr, address);
+    assert_eq!(dump.queued_instant, 1);
+    assert_eq!(dump.queued_reliable, 1);
+    assert_eq!(dump.queued_ordered, 1);
+    assert_eq!(dump.queued_delayed, 1);
+    assert_eq!(dump.packets_sent, 0);
+
+    conn.send_packet(&mut socket, &address, &mut owner, &mut handler);
+    conn.receive_packet([
+        1, 2, 3, 4,
+        0, 0, 0, 0,
+        0, 0,
+        0, 0, 0, 0,
+
+        0, 0, 0, 3, 70, 111, 111
+
+    ].to_vec(), 0, &mut owner, &mut handler);
+
+    let dump = conn.dump_state();
+    assert_eq!(dump.state, ConnectionState::Connected);
+    assert_eq!(dump.queued_instant, 0);
+    assert_eq!(dump.queued_reliable, 0);
+    assert_eq!(dump.queued_ordered, 0);
+    assert_eq!(dump.packets_sent, 1);
+    assert_eq!(dump.packets_received, 1);
+    assert_eq!(dump.packets_acked, 1);
+    assert_eq!(dump.packets_unacked, 0);
+    assert_eq!(dump.received, 1);
+
+}
+
+#[test]
+fn test_reset() {
+    let (mut conn, _, _) = create_connection(None);
+    conn.close();
+    conn.reset();
+    assert!(conn.open());
+    assert!(conn.state() == ConnectionState::Connecting);
+}
+
+#[test]
+fn test_state_transitions() {
+
+    let states = [
+        ConnectionState::Connecting,
+        ConnectionState::Connected,
+        ConnectionState::FailedToConnect,
+        ConnectionState::Lost,
+        ConnectionState::Closing,
+        ConnectionState::Closed
+    ];
+
+    let allowed = [
+        (ConnectionState::Connecting, ConnectionState::Connected),
+        (ConnectionState::Connecting, ConnectionState::FailedToConnect),
+        (ConnectionState::Connecting, ConnectionState::Closing),
+        (ConnectionState::Connected, ConnectionState::Lost),
+        (ConnectionState::Connected, ConnectionState::Closing),
+        (ConnectionState::Connected, ConnectionState::Closed),
+        (ConnectionState::Closing, ConnectionState::Closed)
+    ];
+
+    for from in &states {
+        for to in &states {
+            assert_eq!(
+                from.can_transition_to(*to),
+                allowed.contains(&(*from, *to)),
+                "Transition from {:?} to {:?}", from, to
+            );
+        }
+    }
+
+}
+
+#[test]
+fn test_protocol_mismatch() {
+
+    let config = Config::default();
+
+    // Packets with the configured protocol header are not rejected
+    let packet = [1, 2, 3, 4, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0];
+    assert_eq!(Connection::protocol_mismatch_packet(&config, &packet), None);
+
+    // Packets which are too short are not rejected either
+    assert_eq!(Connection::protocol_mismatch_packet(&config, &[5, 6, 7, 8]), None);
+
+    // Mismatched packets are answered with both protocol headers
+    let packet = [5, 6, 7, 8, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0];
+    let response = Connection::protocol_mismatch_packet(&config, &packet).unwrap();
+    assert_eq!(response, vec![
+        5, 6, 7, 8,
+        255, 255, 255, 255,
+        1, 2, 3, 4
     ]);
 
-    thread::sleep(Duration::from_millis(500));
+    // The response is parsed by the sender of the original packet
+    let remote = Config {
+        protocol_header: [5, 6, 7, 8],
+        .. Config::default()
+    };
+    assert_eq!(Connection::protocol_mismatch_from_packet(&remote, &response), Some([1, 2, 3, 4]));
+    assert_eq!(Connection::protocol_mismatch_from_packet(&config, &response), None);
+    assert_eq!(Connection::protocol_mismatch_from_packet(&remote, &packet), None);
+
+    // Connection attempts fail right away
+    let (mut conn, mut owner, mut handler) = create_connection(None);
+    conn.receive_protocol_mismatch([1, 2, 3, 4], &mut owner, &mut handler);
+    assert!(conn.state() == ConnectionState::FailedToConnect);
+    assert_eq!(conn.remote_protocol_header(), Some([1, 2, 3, 4]));
+
+    // Established connections are not affected
+    let (mut conn, mut owner, mut handler) = create_connection(None);
     conn.receive_packet([
         1, 2, 3, 4,
         0, 0, 0, 0,
-        0,
-        0, // confirm the packet above
         0, 0,
-        0, 0
+        0, 0, 0, 0
 
-    ].to_vec(), 500, &mut owner, &mut handler);
+    ].to_vec(), 0, &mut owner, &mut handler);
+    assert!(conn.state() == ConnectionState::Connected);
 
-    // Expect RTT value to have been corrected by passed in tick delay
-    assert!(conn.rtt() <= 10);
+    conn.receive_protocol_mismatch([1, 2, 3, 4], &mut owner, &mut handler);
+    assert!(conn.state() == ConnectionState::Connected);
+    assert_eq!(conn.remote_protocol_header(), None);
+
+}
+
+#[test]
+fn test_server_full() {
+
+    let config = Config::default();
+    let packet = Connection::server_full_packet(&config, ConnectionID(0x0102_0304));
+    assert_eq!(Connection::server_full_from_packet(&config, &packet), Some(ConnectionID(0x0102_0304)));
+    assert_eq!(Connection::server_full_from_packet(&config, &[1, 2, 3, 4, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0]), None);
+
+    // Connection attempts fail right away
+    let (mut conn, mut owner, mut handler) = create_connection(None);
+    conn.receive_server_full(&mut owner, &mut handler);
+    assert!(conn.state() == ConnectionState::FailedToConnect);
+    assert!(conn.server_full());
+
+    conn.reset();
+    assert!(!conn.server_full());
+
+}
+
+#[test]
+fn test_server_info() {
+
+    let config = Config::default();
+
+    let query = Connection::server_info_query_packet(&config, 0x0102_0304_0506_0708);
+    assert_eq!(query.len(), 128);
+    assert_eq!(query[..16].to_vec(), vec![
+        1, 2, 3, 4,
+        255, 255, 255, 253,
+        1, 2, 3, 4, 5, 6, 7, 8
+    ]);
+    assert!(query[16..].iter().all(|b| *b == 0));
+    assert_eq!(Connection::server_info_query_from_packet(&config, &query), Some(0x0102_0304_0506_0708));
+
+    // Regular packets are not queries
+    let packet = [1, 2, 3, 4, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0];
+    assert_eq!(Connection::server_info_query_from_packet(&config, &packet), None);
+
+    // Queries must be fully padded
+    assert_eq!(Connection::server_info_query_from_packet(&config, &query[..16]), None);
+
+    let mut padded = query.clone();
+    padded[127] = 1;
+    assert_eq!(Connection::server_info_query_from_packet(&config, &padded), None);
+
+    // Responses are only accepted for the challenge of the query
+    let response = Connection::server_info_packet(&config, 0x0102_0304_0506_0708, b"Info").unwrap();
+    assert_eq!(response.len(), 21);
+    assert_eq!(Connection::server_info_from_packet(&config, 0x0102_0304_0506_0708, &response), Some(b"Info".to_vec()));
+    assert_eq!(Connection::server_info_from_packet(&config, 1, &response), None);
+    assert_eq!(Connection::server_info_from_packet(&config, 0x0102_0304_0506_0708, &query), None);
+
+    // Responses are limited to three times the size of the query
+    assert_eq!(Connection::server_info_packet(&config, 1, &[1; 367]).unwrap().len(), 384);
+    assert_eq!(Connection::server_info_packet(&config, 1, &[1; 368]), None);
+
+}
+
+#[cfg(feature = "authentication")]
+#[test]
+fn test_server_info_signed() {
+
+    let config = Config {
+        server_info_signing_key: Some([3; 32]),
+        .. Config::default()
+    };
+
+    let verifier = Config {
+        server_info_public_key: Some(Connection::server_info_public_key(&[3; 32])),
+        .. Config::default()
+    };
+
+    let response = Connection::server_info_packet(&config, 1, b"Info").unwrap();
+    assert_eq!(response.len(), 85);
+    assert_eq!(Connection::server_info_from_packet(&verifier, 1, &response), Some(b"Info".to_vec()));
+
+    // Queriers without the public key strip the signature
+    assert_eq!(Connection::server_info_from_packet(&Config::default(), 1, &response), Some(b"Info".to_vec()));
+
+    // Tampered responses fail to verify
+    let mut tampered = response.clone();
+    tampered[17] = b'X';
+    assert_eq!(Connection::server_info_from_packet(&verifier, 1, &tampered), None);
+
+    // Unsigned or differently signed responses are rejected
+    let unsigned = Connection::server_info_packet(&Config::default(), 1, b"Info").unwrap();
+    assert_eq!(Connection::server_info_from_packet(&verifier, 1, &unsigned), None);
+
+    let other = Config {
+        server_info_signing_key: Some([4; 32]),
+        .. Config::default()
+    };
+    let response = Connection::server_info_packet(&other, 1, b"Info").unwrap();
+    assert_eq!(Connection::server_info_from_packet(&verifier, 1, &response), None);
+
+    // The signature counts towards the size limit
+    assert!(Connection::server_info_packet(&config, 1, &[1; 303]).is_some());
+    assert_eq!(Connection::server_info_packet(&config, 1, &[1; 304]), None);
+
+}
+
+#[test]
+fn test_rng() {
+
+    let (mut conn, _, _) = create_connection(None);
+    let (mut remote, _, _) = create_connection(None);
+    remote.set_id(conn.id());
+
+    // Both ends of a connection share the same sequence
+    let sequence: Vec<u32> = (0..4).map(|_| conn.rng().next_u32()).collect();
+    let remote_sequence: Vec<u32> = (0..4).map(|_| remote.rng().next_u32()).collect();
+    assert_eq!(sequence, remote_sequence);
+
+    // Resetting starts the sequence over
+    conn.reset();
+    let reset_sequence: Vec<u32> = (0..4).map(|_| conn.rng().next_u32()).collect();
+    assert_eq!(sequence, reset_sequence);
+
+    // Changing the id reseeds the generator
+    conn.set_id(ConnectionID(conn.id().0.wrapping_add(1)));
+    let other_sequence: Vec<u32> = (0..4).map(|_| conn.rng().next_u32()).collect();
+    assert_ne!(sequence, other_sequence);
+
+}
+
+#[test]
+fn test_close_final_state() {
+
+    let (mut conn, mut socket, mut socket_handle, mut owner, mut handler) = create_socket(None);
+    let address = conn.peer_addr();
+
+    // Fail to connect
+    thread::sleep(Duration::from_millis(110));
+    conn.send_packet(&mut socket, &address, &mut owner, &mut handler);
+    socket_handle.assert_sent_none();
+    assert!(conn.state() == ConnectionState::FailedToConnect);
+
+    // Closing has no effect on connections in a final state
+    conn.close();
+    assert!(conn.state() == ConnectionState::FailedToConnect);
+    assert!(!conn.open());
+
+}
+
+#[test]
+fn test_resumption_token() {
+
+    let (mut conn, mut socket, mut socket_handle, mut owner, mut handler) = create_socket(None);
+    let address = conn.peer_addr();
+
+    // Establish connection
+    conn.receive_packet([
+        1, 2, 3, 4,
+        0, 0, 0, 0,
+        0, 0,
+        0, 0, 0, 0
+
+    ].to_vec(), 0, &mut owner, &mut handler);
+
+    let token = conn.issue_resumption_token();
+    assert_eq!(token.id, conn.id());
+    assert_eq!(conn.resumption_token(), Some(token));
+
+    // Token should be send as a reliable control message
+    let s = token.secret;
+    let packet = [
+        1, 2, 3, 4,
+        (conn.id().0 >> 24) as u8,
+        (conn.id().0 >> 16) as u8,
+        (conn.id().0 >> 8) as u8,
+         conn.id().0 as u8,
+        0, 0,
+        0, 0, 0, 0,
+
+        // Resumption token
+        9, 0, 0, 10, 0,
+        (s >> 56) as u8, (s >> 48) as u8, (s >> 40) as u8, (s >> 32) as u8,
+        (s >> 24) as u8, (s >> 16) as u8, (s >> 8) as u8, s as u8,
+        0
+
+    ].to_vec();
+
+    conn.send_packet(&mut socket, &address, &mut owner, &mut handler);
+    socket_handle.assert_sent(vec![("255.1.1.2:5678", packet.clone())]);
+
+    // Remote should store the token under its own id
+    let (mut remote, mut owner, mut handler) = create_connection(None);
+    assert_eq!(remote.resumption_token(), None);
+
+    remote.receive_packet(packet, 0, &mut owner, &mut handler);
+    assert_eq!(remote.resumption_token(), Some(ResumptionToken {
+        id: remote.id(),
+        secret: s
+    }));
+    assert!(!remote.resumed());
+
+}
+
+#[test]
+fn test_resume() {
+
+    struct ResumptionHandler {
+        connection_calls: u32,
+        connection_resumed_calls: u32
+    }
+
+    impl Handler<MockOwner> for ResumptionHandler {
+
+        fn connection(&mut self, _: &mut MockOwner, _: &mut Connection) {
+            self.connection_calls += 1;
+        }
+
+        fn connection_resumed(&mut self, _: &mut MockOwner, _: &mut Connection) {
+            self.connection_resumed_calls += 1;
+        }
+
+    }
+
+    let (mut conn, mut socket, mut socket_handle, mut owner, mut handler) = create_socket(None);
+    let address = conn.peer_addr();
+
+    conn.resume(ResumptionToken {
+        id: ConnectionID(1),
+        secret: 0x0102_0304_0506_0708
+    });
+    assert_eq!(conn.id(), ConnectionID(1));
+
+    // Resumption secret should be presented along with the first messages
+    conn.send(MessageKind::Reliable, b"Foo".to_vec());
+    conn.send_packet(&mut socket, &address, &mut owner, &mut handler);
+
+    let packet = [
+        1, 2, 3, 4,
+        0, 0, 0, 1,
+        0, 0,
+        0, 0, 0, 0,
+
+        // Resume
+        8, 0, 0, 9, 1, 1, 2, 3, 4, 5, 6, 7, 8,
+
+        // Foo
+        1, 0, 0, 3, 70, 111, 111
+
+    ].to_vec();
+    socket_handle.assert_sent(vec![("255.1.1.2:5678", packet.clone())]);
+
+    // Remote accepts the resumption and delivers the message right away
+    let (mut remote, mut owner, _) = create_connection(None);
+    let mut handler = ResumptionHandler {
+        connection_calls: 0,
+        connection_resumed_calls: 0
+    };
+
+    remote.allow_resumption(0x0102_0304_0506_0708, Vec::new());
+    remote.receive_packet(packet.clone(), 0, &mut owner, &mut handler);
+    assert!(remote.resumed());
+    assert_eq!(handler.connection_calls, 0);
+    assert_eq!(handler.connection_resumed_calls, 1);
+    assert_eq!(remote.received().collect::<Vec<Vec<u8>>>(), vec![b"Foo".to_vec()]);
+
+    // Remote rejects the resumption with a mismatching secret
+    let (mut remote, mut owner, _) = create_connection(None);
+    remote.allow_resumption(0x0807_0605_0403_0201, Vec::new());
+    remote.receive_packet(packet, 0, &mut owner, &mut handler);
+    assert!(!remote.resumed());
+    assert_eq!(handler.connection_calls, 1);
+    assert_eq!(handler.connection_resumed_calls, 1);
+
+    // Confirmation from the remote resumes the local connection
+    conn.receive_packet([
+        1, 2, 3, 4,
+        0, 0, 0, 1,
+        0, 0,
+        0, 0, 0, 0,
+
+        // Resumption token
+        9, 0, 0, 10, 0, 8, 7, 6, 5, 4, 3, 2, 1, 1
+
+    ].to_vec(), 0, &mut owner, &mut handler);
+
+    assert!(conn.resumed());
+    assert_eq!(conn.resumption_token(), Some(ResumptionToken {
+        id: ConnectionID(1),
+        secret: 0x0807_0605_0403_0201
+    }));
+    assert_eq!(handler.connection_calls, 1);
+    assert_eq!(handler.connection_resumed_calls, 2);
+
+    // Further packets no longer present the resumption secret
+    conn.send_packet(&mut socket, &address, &mut owner, &mut handler);
+    socket_handle.assert_sent(vec![("255.1.1.2:5678", [
+        1, 2, 3, 4,
+        0, 0, 0, 1,
+        1, 0,
+        0, 0, 0, 0
+
+    ].to_vec())]);
+
+}
+
+#[test]
+fn test_resume_retained_messages() {
+
+    let (mut conn, mut socket, _, mut owner, mut handler) = create_socket(None);
+    let address = conn.peer_addr();
+
+    // Messages from pending packets and the queues should be retained
+    conn.send(MessageKind::Ordered, b"A".to_vec());
+    conn.send(MessageKind::Ordered, b"B".to_vec());
+    conn.send_packet(&mut socket, &address, &mut owner, &mut handler);
+
+    conn.send(MessageKind::Reliable, b"C".to_vec());
+    conn.send(MessageKind::Ordered, b"D".to_vec());
+    conn.send(MessageKind::Instant, b"E".to_vec());
+
+    let messages = conn.take_pending_messages();
+    assert_eq!(messages, vec![
+        (MessageKind::Reliable, b"C".to_vec()),
+        (MessageKind::Ordered, b"A".to_vec()),
+        (MessageKind::Ordered, b"B".to_vec()),
+        (MessageKind::Ordered, b"D".to_vec())
+    ]);
+
+    // Nothing is retained twice
+    assert!(conn.take_pending_messages().is_empty());
+
+    // The resumed connection re-sends the retained messages with fresh
+    // order ids
+    let (mut remote, mut socket, mut socket_handle, mut owner, mut handler) = create_socket(None);
+    remote.set_id(ConnectionID(1));
+    remote.allow_resumption(0x0102_0304_0506_0708, messages);
+    remote.receive_packet([
+        1, 2, 3, 4,
+        0, 0, 0, 1,
+        0, 0,
+        0, 0, 0, 0,
+
+        // Resume
+        8, 0, 0, 9, 1, 1, 2, 3, 4, 5, 6, 7, 8
+
+    ].to_vec(), 0, &mut owner, &mut handler);
+    assert!(remote.resumed());
+
+    remote.send_packet(&mut socket, &address, &mut owner, &mut handler);
+    socket_handle.assert_sent(vec![("255.1.1.2:5678", [
+        1, 2, 3, 4,
+        0, 0, 0, 1,
+        0, 0,
+        0, 0, 0, 0,
+
+        1, 0, 0, 1, 67,
+        2, 0, 0, 1, 65,
+        2, 1, 0, 1, 66,
+        2, 2, 0, 1, 68
+
+    ].to_vec())]);
+
+}
+
+#[test]
+fn test_receive_watermark() {
+
+    struct PressureHandler {
+        pressure: Vec<bool>
+    }
+
+    impl Handler<MockOwner> for PressureHandler {
+        fn connection_receive_pressure(&mut self, _: &mut MockOwner, _: &mut Connection, pressure: bool) {
+            self.pressure.push(pressure);
+        }
+    }
+
+    let (mut conn, mut socket, mut socket_handle, mut owner, _) = create_socket(None);
+    let mut handler = PressureHandler {
+        pressure: Vec::new()
+    };
+
+    let address = conn.peer_addr();
+    conn.set_id(ConnectionID(1));
+    conn.set_receive_watermark(4);
+    assert_eq!(conn.receive_watermark(), 4);
+
+    // Data up to the watermark causes no pressure
+    conn.receive_packet([
+        1, 2, 3, 4,
+        0, 0, 0, 1,
+        0, 0,
+        0, 0, 0, 0,
+        0, 0, 0, 3, 70, 111, 111
+
+    ].to_vec(), 0, &mut owner, &mut handler);
+    assert!(!conn.receive_pressure());
+    assert!(handler.pressure.is_empty());
+
+    // Unconsumed data exceeding the watermark is reported and advertised
+    conn.receive_packet([
+        1, 2, 3, 4,
+        0, 0, 0, 1,
+        1, 0,
+        0, 0, 0, 0,
+        0, 0, 0, 3, 66, 97, 114
+
+    ].to_vec(), 0, &mut owner, &mut handler);
+    assert!(conn.receive_pressure());
+    assert_eq!(handler.pressure, vec![true]);
+
+    conn.send_packet(&mut socket, &address, &mut owner, &mut handler);
+    let pressure = [
+        1, 2, 3, 4,
+        0, 0, 0, 1,
+        0, 1,
+        0, 0, 0, 1,
+
+        // Receive pressure
+        9, 0, 0, 3, 13, 1, 1
+
+    ].to_vec();
+    socket_handle.assert_sent(vec![("255.1.1.2:5678", pressure.clone())]);
+
+    // Consuming the data releases the pressure with the next packet
+    assert_eq!(conn.received().count(), 2);
+    conn.send_packet(&mut socket, &address, &mut owner, &mut handler);
+    assert!(!conn.receive_pressure());
+    assert_eq!(handler.pressure, vec![true, false]);
+
+    let released = [
+        1, 2, 3, 4,
+        0, 0, 0, 1,
+        1, 1,
+        0, 0, 0, 1,
+
+        // Receive pressure
+        9, 0, 0, 3, 13, 2, 0
+
+    ].to_vec();
+    socket_handle.assert_sent(vec![("255.1.1.2:5678", released.clone())]);
+
+    // The remote keeps track of the latest advertisement
+    let (mut remote, mut owner, mut handler) = create_connection(None);
+    assert!(!remote.remote_receive_pressure());
+    remote.receive_packet(pressure.clone(), 0, &mut owner, &mut handler);
+    assert!(remote.remote_receive_pressure());
+    remote.receive_packet(released, 0, &mut owner, &mut handler);
+    assert!(!remote.remote_receive_pressure());
+
+    // Outdated advertisements are ignored
+    let mut outdated = pressure;
+    outdated[8] = 2;
+    remote.receive_packet(outdated, 0, &mut owner, &mut handler);
+    assert!(!remote.remote_receive_pressure());
+
+}
+
+#[test]
+fn test_keep_alive() {
+
+    struct KeepAliveHandler {
+        granted: Vec<u32>
+    }
+
+    impl Handler<MockOwner> for KeepAliveHandler {
+        fn connection_keep_alive(&mut self, _: &mut MockOwner, _: &mut Connection, duration: u32) {
+            self.granted.push(duration);
+        }
+    }
+
+    let (mut conn, mut socket, mut socket_handle, mut owner, _) = create_socket(None);
+    let mut handler = KeepAliveHandler {
+        granted: Vec::new()
+    };
+
+    let address = conn.peer_addr();
+    conn.set_id(ConnectionID(1));
+    assert_eq!(conn.request_keep_alive(30000).unwrap_err().kind(), ErrorKind::NotConnected);
+
+    conn.receive_packet([
+        1, 2, 3, 4,
+        0, 0, 0, 1,
+        0, 0,
+        0, 0, 0, 0
+
+    ].to_vec(), 0, &mut owner, &mut handler);
+
+    conn.request_keep_alive(30000).unwrap();
+    conn.send_packet(&mut socket, &address, &mut owner, &mut handler);
+
+    let request = [
+        1, 2, 3, 4,
+        0, 0, 0, 1,
+        0, 0,
+        0, 0, 0, 0,
+
+        // Keep alive request
+        9, 0, 0, 5, 17, 0, 0, 117, 48
+
+    ].to_vec();
+    socket_handle.assert_sent(vec![("255.1.1.2:5678", request.clone())]);
+
+    // The remote grants at most its configured maximum
+    let (mut remote, mut remote_socket, mut remote_socket_handle, _, _) = create_socket(Some(Config {
+        max_keep_alive_extension: 10000,
+        connection_drop_threshold: 50,
+        .. Config::default()
+    }));
+    let mut remote_handler = KeepAliveHandler {
+        granted: Vec::new()
+    };
+
+    remote.set_id(ConnectionID(1));
+    remote.receive_packet(request.clone(), 0, &mut owner, &mut remote_handler);
+    assert_eq!(remote_handler.granted, vec![10000]);
+    assert!(remote.keep_alive_remaining() > 9000);
+
+    remote.send_packet(&mut remote_socket, &address, &mut owner, &mut remote_handler);
+    let grant = [
+        1, 2, 3, 4,
+        0, 0, 0, 1,
+        0, 0,
+        0, 0, 0, 0,
+
+        // Keep alive grant
+        9, 0, 0, 5, 18, 0, 0, 39, 16
+
+    ].to_vec();
+    remote_socket_handle.assert_sent(vec![("255.1.1.2:5678", grant.clone())]);
+
+    // Follows the packet which established the connection
+    let mut grant = grant;
+    grant[8] = 1;
+    conn.receive_packet(grant, 0, &mut owner, &mut handler);
+    assert_eq!(handler.granted, vec![10000]);
+    assert!(conn.keep_alive_remaining() > 9000);
+
+    // Connections are kept alive beyond their drop threshold
+    thread::sleep(Duration::from_millis(60));
+    remote.send_packet(&mut remote_socket, &address, &mut owner, &mut remote_handler);
+    assert_eq!(remote.state(), ConnectionState::Connected);
+
+    // Remotes without a maximum decline all requests
+    let (mut remote, _, _, _, _) = create_socket(None);
+    let mut remote_handler = KeepAliveHandler {
+        granted: Vec::new()
+    };
+
+    remote.set_id(ConnectionID(1));
+    remote.receive_packet(request, 0, &mut owner, &mut remote_handler);
+    assert_eq!(remote_handler.granted, vec![0]);
+    assert_eq!(remote.keep_alive_remaining(), 0);
+
+}
+
+#[cfg(feature = "authentication")]
+#[test]
+fn test_handshake_cookie() {
+
+    let (mut conn, mut socket, mut socket_handle, mut owner, mut handler) = create_socket(None);
+    let address = conn.peer_addr();
+    conn.set_id(ConnectionID(1));
+
+    let mut cookie = vec![
+        1, 2, 3, 4,
+        255, 255, 255, 254,
+        0, 0, 0, 1
+    ];
+    cookie.extend_from_slice(&[7; 20]);
+
+    // Cookies issued for other connections are treated as regular packets
+    let mut other = cookie.clone();
+    other[11] = 2;
+    assert_eq!(conn.receive_handshake_cookie(other.clone()), Some(other));
+
+    // Cookies are echoed until the connection is established
+    assert_eq!(conn.receive_handshake_cookie(cookie.clone()), None);
+    conn.send_packet(&mut socket, &address, &mut owner, &mut handler);
+
+    let mut echo = vec![
+        1, 2, 3, 4,
+        0, 0, 0, 1,
+        0, 0,
+        0, 0, 0, 0,
+        8, 0, 0, 21, 14
+    ];
+    echo.extend_from_slice(&[7; 20]);
+    socket_handle.assert_sent(vec![("255.1.1.2:5678", echo)]);
+
+    conn.receive_packet([
+        1, 2, 3, 4,
+        0, 0, 0, 1,
+        0, 0,
+        0, 0, 0, 0
+
+    ].to_vec(), 0, &mut owner, &mut handler);
+    assert_eq!(conn.receive_handshake_cookie(cookie.clone()), Some(cookie));
+
+    conn.send_packet(&mut socket, &address, &mut owner, &mut handler);
+    socket_handle.assert_sent(vec![("255.1.1.2:5678", [
+        1, 2, 3, 4,
+        0, 0, 0, 1,
+        1, 0,
+        0, 0, 0, 0
+
+    ].to_vec())]);
+
+}
+
+#[test]
+fn test_early_data() {
+
+    let (mut conn, mut socket, mut socket_handle, mut owner, mut handler) = create_socket(None);
+    let address = conn.peer_addr();
+    conn.set_id(ConnectionID(1));
+
+    // Early data must fit into a single message
+    let payload = vec![0; conn.max_message_size()];
+    assert_eq!(conn.send_early_data(payload).unwrap_err().kind(), ErrorKind::InvalidInput);
+
+    // Only a single message can be attached to the connection request
+    conn.send_early_data(b"Ping".to_vec()).unwrap();
+    assert_eq!(conn.send_early_data(b"Ping".to_vec()).unwrap_err().kind(), ErrorKind::AlreadyExists);
+
+    conn.send_packet(&mut socket, &address, &mut owner, &mut handler);
+    let packet = [
+        1, 2, 3, 4,
+        0, 0, 0, 1,
+        0, 0,
+        0, 0, 0, 0,
+
+        // Early data
+        9, 0, 0, 5, 11, 80, 105, 110, 103
+
+    ].to_vec();
+    socket_handle.assert_sent(vec![("255.1.1.2:5678", packet.clone())]);
+
+    // Remotes which accept early data receive it right away
+    let (mut remote, mut owner, mut handler) = create_connection(Some(Config {
+        early_data: true,
+        .. Config::default()
+    }));
+    remote.receive_packet(packet.clone(), 0, &mut owner, &mut handler);
+    assert_eq!(remote.received().collect::<Vec<Vec<u8>>>(), vec![b"Ping".to_vec()]);
+
+    // Other remotes reject it
+    let (mut remote, mut remote_socket, mut remote_handle, mut owner, mut handler) = create_socket(None);
+    remote.set_id(ConnectionID(1));
+    remote.receive_packet(packet, 0, &mut owner, &mut handler);
+    assert!(remote.received().next().is_none());
+
+    remote.send_packet(&mut remote_socket, &address, &mut owner, &mut handler);
+    let packet = [
+        1, 2, 3, 4,
+        0, 0, 0, 1,
+        0, 0,
+        0, 0, 0, 0,
+
+        // Rejection
+        9, 0, 0, 1, 12
+
+    ].to_vec();
+    remote_handle.assert_sent(vec![("255.1.1.2:5678", packet.clone())]);
+
+    // Rejected early data is send again as a regular message
+    conn.receive_packet(packet, 0, &mut owner, &mut handler);
+    assert_eq!(conn.state(), ConnectionState::Connected);
+
+    conn.send_packet(&mut socket, &address, &mut owner, &mut handler);
+    socket_handle.assert_sent(vec![("255.1.1.2:5678", [
+        1, 2, 3, 4,
+        0, 0, 0, 1,
+        1, 0,
+        0, 0, 0, 0,
+
+        // Ping
+        1, 0, 0, 4, 80, 105, 110, 103
+
+    ].to_vec())]);
+
+    // Early data can no longer be attached once connected
+    assert_eq!(conn.send_early_data(b"Ping".to_vec()).unwrap_err().kind(), ErrorKind::AlreadyExists);
+
+}
+
+#[test]
+fn test_resume_rejected_drops_retained_messages() {
+
+    let (mut remote, mut socket, mut socket_handle, mut owner, mut handler) = create_socket(None);
+    let address = remote.peer_addr();
+
+    remote.set_id(ConnectionID(1));
+    remote.allow_resumption(0x0102_0304_0506_0708, vec![
+        (MessageKind::Reliable, b"C".to_vec())
+    ]);
+
+    // A new connection without a matching secret gets nothing re-send
+    remote.receive_packet([
+        1, 2, 3, 4,
+        0, 0, 0, 1,
+        0, 0,
+        0, 0, 0, 0
+
+    ].to_vec(), 0, &mut owner, &mut handler);
+    assert!(!remote.resumed());
+
+    remote.send_packet(&mut socket, &address, &mut owner, &mut handler);
+    socket_handle.assert_sent(vec![("255.1.1.2:5678", [
+        1, 2, 3, 4,
+        0, 0, 0, 1,
+        0, 0,
+        0, 0, 0, 0
+
+    ].to_vec())]);
+
+}
+
+#[test]
+fn test_migration_validation_unauthenticated() {
+
+    // Migration tokens are never send over unauthenticated connections
+    let (mut conn, _, _) = create_connection(None);
+    conn.issue_migration_token();
+    assert!(!conn.has_migration_token());
+
+}
+
+#[cfg(feature = "authentication")]
+#[test]
+fn test_migration_validation() {
+
+    struct MigrationHandler {
+        migrated_from: Vec<net::SocketAddr>
+    }
+
+    impl Handler<MockOwner> for MigrationHandler {
+        fn connection_migrated(
+            &mut self, _: &mut MockOwner, _: &mut Connection, previous: net::SocketAddr
+        ) {
+            self.migrated_from.push(previous);
+        }
+    }
+
+    let config = Config {
+        authentication_key: Some([9; 32]),
+        .. Config::default()
+    };
+
+    let (mut conn, mut socket, mut socket_handle, mut owner, _) = create_socket(Some(config));
+    let (mut remote, mut remote_socket, mut remote_socket_handle, _, _) = create_socket(Some(config));
+    let mut handler = MigrationHandler {
+        migrated_from: Vec::new()
+    };
+    remote.set_id(conn.id());
+
+    let address = conn.peer_addr();
+    let migrated: net::SocketAddr = "255.1.1.3:5678".parse().unwrap();
+    let authenticate = |packet| Connection::authenticate_packet(&config, packet).unwrap();
+
+    // Issue a migration token to the remote
+    conn.issue_migration_token();
+    assert!(conn.has_migration_token());
+    conn.send_packet(&mut socket, &address, &mut owner, &mut handler);
+
+    let packet = socket_handle.sent().remove(0).1;
+    remote.receive_packet(authenticate(packet), 0, &mut owner, &mut handler);
+    assert!(remote.has_migration_token());
+
+    remote.send_packet(&mut remote_socket, &address, &mut owner, &mut handler);
+    let packet = remote_socket_handle.sent().remove(0).1;
+    conn.receive_packet(authenticate(packet), 0, &mut owner, &mut handler);
+
+    // Packet which never arrives
+    remote.send_packet(&mut remote_socket, &address, &mut owner, &mut handler);
+    remote_socket_handle.sent();
+
+    // A copy of the regular packet carrying the challenge is send to the new
+    // address, using the same sequence number
+    conn.validate_peer_addr(migrated);
+    conn.send_packet(&mut socket, &address, &mut owner, &mut handler);
+
+    let mut sent = socket_handle.sent();
+    assert_eq!(sent.len(), 2);
+    assert_eq!(sent[0].0, address);
+    assert_eq!(sent[1].0, migrated);
+    assert_eq!(sent[0].1[..14], sent[1].1[..14]);
+    assert_eq!(sent[1].1[8], 1);
+    assert_eq!(&sent[1].1[14..19], &[8, 0, 0, 9, 3]);
+    assert_eq!(conn.peer_addr(), address);
+    assert_eq!(conn.seq_numbers().0, 2);
+
+    // A forged response does not migrate the connection
+    conn.receive_packet([
+        1, 2, 3, 4,
+        0, 0, 0, 0,
+        1, 0,
+        0, 0, 0, 0,
+
+        8, 0, 0, 17, 4, 1, 2, 3, 4, 5, 6, 7, 8, 1, 2, 3, 4, 5, 6, 7, 8
+
+    ].to_vec(), 0, &mut owner, &mut handler);
+    assert_eq!(conn.peer_addr(), address);
+    assert!(handler.migrated_from.is_empty());
+    assert_eq!(conn.take_failed_migration(), Some(migrated));
+    assert_eq!(conn.take_failed_migration(), None);
+
+    // The remote answers the challenge with a valid proof
+    remote.receive_packet(authenticate(sent.remove(1).1), 0, &mut owner, &mut handler);
+    remote.send_packet(&mut remote_socket, &migrated, &mut owner, &mut handler);
+
+    let response = remote_socket_handle.sent().remove(0).1;
+    assert_eq!(response[8], 2);
+    assert_eq!(&response[14..19], &[8, 0, 0, 17, 4]);
+
+    conn.receive_packet(authenticate(response), 0, &mut owner, &mut handler);
+    assert_eq!(conn.peer_addr(), migrated);
+    assert_eq!(handler.migrated_from, vec![address]);
+
+    // No further challenges are send
+    conn.send_packet(&mut socket, &migrated, &mut owner, &mut handler);
+    assert_eq!(socket_handle.sent().len(), 1);
+
+}
+
+#[test]
+fn test_try_send_max_message_size() {
+
+    let config = Config {
+        packet_max_size: 32,
+        .. Config::default()
+    };
+
+    let (mut conn, mut socket, mut socket_handle, mut owner, mut handler) = create_socket(Some(config));
+    let address = conn.peer_addr();
+    assert_eq!(conn.max_message_size(), 14);
+
+    // Messages which would never fit into a packet are rejected
+    assert_eq!(
+        conn.try_send(MessageKind::Reliable, vec![0; 15]).unwrap_err().kind(),
+        ErrorKind::InvalidInput
+    );
+
+    // And silently dropped when using send()
+    conn.send(MessageKind::Reliable, vec![0; 15]);
+
+    assert!(conn.try_send(MessageKind::Reliable, vec![1; 14]).is_ok());
+    conn.send_packet(&mut socket, &address, &mut owner, &mut handler);
+    socket_handle.assert_sent(vec![("255.1.1.2:5678", [
+        1, 2, 3, 4,
+        (conn.id().0 >> 24) as u8,
+        (conn.id().0 >> 16) as u8,
+        (conn.id().0 >> 8) as u8,
+         conn.id().0 as u8,
+        0, 0,
+        0, 0, 0, 0,
+
+        1, 0, 0, 14, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1
+
+    ].to_vec())]);
+
+}
+
+#[test]
+fn test_host_migration() {
+
+    struct HostMigrationHandler {
+        migrations: Vec<HostMigration>
+    }
+
+    impl Handler<MockOwner> for HostMigrationHandler {
+        fn connection_host_migration(
+            &mut self, _: &mut MockOwner, _: &mut Connection, migration: HostMigration
+        ) {
+            self.migrations.push(migration);
+        }
+    }
+
+    let config = Config {
+        connection_drop_threshold: 10,
+        .. Config::default()
+    };
+
+    let (mut host, mut socket, mut socket_handle, mut owner, _) = create_socket(None);
+    let (mut conn, mut conn_socket, _, _, _) = create_socket(Some(config));
+    let mut handler = HostMigrationHandler {
+        migrations: Vec::new()
+    };
+
+    let address = host.peer_addr();
+    let peers = vec![Peer {
+        id: ConnectionID(5),
+        addr: "127.0.0.1:5000".parse().unwrap()
+    }, Peer {
+        id: ConnectionID(2),
+        addr: "[::1]:2000".parse().unwrap()
+    }];
+
+    // Host shares the session information
+    host.share_peers(&peers).unwrap();
+    host.share_session_state(b"State").unwrap();
+    assert!(host.share_session_state(&[0; 2000]).is_err());
+    host.send_packet(&mut socket, &address, &mut owner, &mut handler);
+
+    assert!(conn.host_migration().is_none());
+    conn.set_id(ConnectionID(5));
+    conn.receive_packet(socket_handle.sent().remove(0).1, 0, &mut owner, &mut handler);
+    assert_eq!(conn.peers(), &peers[..]);
+    assert_eq!(conn.session_state(), Some(&b"State"[..]));
+
+    // The peer with the lowest id is elected
+    assert_eq!(conn.host_migration(), Some(HostMigration {
+        host: peers[1],
+        local: false,
+        session_state: Some(b"State".to_vec())
+    }));
+
+    // Election is reported once the connection to the host is lost
+    thread::sleep(Duration::from_millis(20));
+    conn.send_packet(&mut conn_socket, &address, &mut owner, &mut handler);
+    assert_eq!(conn.state(), ConnectionState::Lost);
+    assert_eq!(handler.migrations.len(), 1);
+    assert_eq!(handler.migrations[0].host, peers[1]);
+
+    // The elected peer itself is told to become the new host
+    conn.set_id(ConnectionID(2));
+    assert!(conn.host_migration().unwrap().local);
+
+}
+
+#[test]
+fn test_closed_send_policy() {
+
+    // Reject messages
+    let (mut conn, _, _) = create_connection(Some(Config {
+        closed_send_policy: ClosedSendPolicy::Error,
+        .. Config::default()
+    }));
+
+    conn.close();
+    assert_eq!(
+        conn.try_send(MessageKind::Reliable, b"Foo".to_vec()).unwrap_err().kind(),
+        ErrorKind::NotConnected
+    );
+    assert!(conn.send(MessageKind::Reliable, b"Foo".to_vec()).is_none());
+
+    // Drop messages
+    let (mut conn, _, _) = create_connection(Some(Config {
+        closed_send_policy: ClosedSendPolicy::Drop,
+        .. Config::default()
+    }));
+
+    conn.close();
+    let handle = conn.try_send(MessageKind::Reliable, b"Foo".to_vec()).unwrap();
+    assert!(!conn.cancel(handle));
+    assert!(conn.take_pending_messages().is_empty());
+
+    // Buffer messages
+    let (mut conn, _, _) = create_connection(None);
+
+    conn.close();
+    conn.try_send(MessageKind::Reliable, b"Foo".to_vec()).unwrap();
+    assert_eq!(conn.take_pending_messages(), vec![
+        (MessageKind::Reliable, b"Foo".to_vec())
+    ]);
+
+}
+
+#[test]
+fn test_send_sequence_wrap_around() {
+
+    let (mut conn, mut socket, mut socket_handle, mut owner, mut handler) = create_socket(None);
+    let address = conn.peer_addr();
+
+    for i in 0..256 {
+
+        conn.send_packet(&mut socket, &address, &mut owner, &mut handler);
+
+        socket_handle.assert_sent(vec![("255.1.1.2:5678", [
+            // protocol id
+            1, 2, 3, 4,
+
+            // connection id
+            (conn.id().0 >> 24) as u8,
+            (conn.id().0 >> 16) as u8,
+            (conn.id().0 >> 8) as u8,
+             conn.id().0 as u8,
+
+            i as u8, // local sequence number
+            0, // remote sequence number
+            0, 0, 0, 0  // ack bitfield
+
+        ].to_vec())]);
+
+    }
+
+    // Should now wrap around
+    conn.send_packet(&mut socket, &address, &mut owner, &mut handler);
+    socket_handle.assert_sent(vec![("255.1.1.2:5678", [
+        // protocol id
+        1, 2, 3, 4,
+
+        // connection id
+        (conn.id().0 >> 24) as u8,
+        (conn.id().0 >> 16) as u8,
+        (conn.id().0 >> 8) as u8,
+         conn.id().0 as u8,
+
+        0, // local sequence number
+        0, // remote sequence number
+        0, 0, 0, 0  // ack bitfield
+
+    ].to_vec())]);
+
+}
+
+#[test]
+fn test_send_and_receive_packet() {
+
+    let (mut conn, mut socket, mut socket_handle, mut owner, mut handler) = create_socket(None);
+    let address = conn.peer_addr();
+
+    // Test Initial Packet
+    conn.send_packet(&mut socket, &address, &mut owner, &mut handler);
+    socket_handle.assert_sent(vec![("255.1.1.2:5678", [
+        // protocol id
+        1, 2, 3, 4,
+
+        // connection id
+        (conn.id().0 >> 24) as u8,
+        (conn.id().0 >> 16) as u8,
+        (conn.id().0 >> 8) as u8,
+         conn.id().0 as u8,
+
+        0, // local sequence number
+        0, // remote sequence number
+        0, 0, 0, 0  // ack bitfield
+
+    ].to_vec())]);
+
+    // Test sending of written data
+    conn.send_packet(&mut socket, &address, &mut owner, &mut handler);
+    socket_handle.assert_sent(vec![("255.1.1.2:5678", [
+        1, 2, 3, 4,
+        (conn.id().0 >> 24) as u8,
+        (conn.id().0 >> 16) as u8,
+        (conn.id().0 >> 8) as u8,
+         conn.id().0 as u8,
+        1, // local sequence number
+        0,
+        0, 0, 0, 0
+
+    ].to_vec())]);
+
+    // Write buffer should get cleared
+    conn.send_packet(&mut socket, &address, &mut owner, &mut handler);
+    socket_handle.assert_sent(vec![("255.1.1.2:5678", [
+        1, 2, 3, 4,
+        (conn.id().0 >> 24) as u8,
+        (conn.id().0 >> 16) as u8,
+        (conn.id().0 >> 8) as u8,
+         conn.id().0 as u8,
+
+        2, // local sequence number
+        0,
+        0, 0, 0, 0
+
+    ].to_vec())]);
+
+    // Test receiving of a packet with acknowledgements for two older packets
+    conn.receive_packet([
+        1, 2, 3, 4,
+        0, 0, 0, 0, // ConnectionID is ignored by receive_packet)
+        17, // local sequence number
+        2, // remote sequence number we confirm
+        0, 0, 0, 3, // confirm the first two packets
+
+    ].to_vec(), 0, &mut owner, &mut handler);
+
+    // Receive additional packet
+    conn.receive_packet([
+        1, 2, 3, 4,
+        0, 0, 0, 0, // ConnectionID is ignored by receive_packet)
+        18, // local sequence number
+        3, // remote sequence number we confirm
+        0, 0, 0, 0
+
+    ].to_vec(), 0, &mut owner, &mut handler);
+
+    conn.receive_packet([
+        1, 2, 3, 4,
+        0, 0, 0, 0, // ConnectionID is ignored by receive_packet)
+        19, // local sequence number
+        4, // remote sequence number we confirm
+        0, 0, 0, 0
+
+    ].to_vec(), 0, &mut owner, &mut handler);
+
+    conn.receive_packet([
+        1, 2, 3, 4,
+        0, 0, 0, 0, // ConnectionID is ignored by receive_packet)
+        27, // local sequence number
+        4, // remote sequence number we confirm
+        0, 0, 0, 0
+
+    ].to_vec(), 0, &mut owner, &mut handler);
+
+    // Test Receive Ack Bitfield
+    conn.send_packet(&mut socket, &address, &mut owner, &mut handler);
+    socket_handle.assert_sent(vec![("255.1.1.2:5678", [
+        1, 2, 3, 4,
+        (conn.id().0 >> 24) as u8,
+        (conn.id().0 >> 16) as u8,
+        (conn.id().0 >> 8) as u8,
+         conn.id().0 as u8,
+
+        3, // local sequence number
+        27, // remove sequence number set by receive_packet)
+
+        // Ack bitfield
+        0, 0, 3, 128 // 0000_0000 0000_0000 0000_0011 1000_0000
+
+    ].to_vec())]);
+
+}
+
+#[test]
+fn test_send_and_receive_messages() {
+
+    let (mut conn, mut socket, mut socket_handle, mut owner, mut handler) = create_socket(None);
+    let address = conn.peer_addr();
+
+    // Test Message Sending
+    conn.send(MessageKind::Instant, b"Foo".to_vec());
+    conn.send(MessageKind::Instant, b"Bar".to_vec());
+    conn.send(MessageKind::Reliable, b"Test".to_vec());
+    conn.send(MessageKind::Ordered, b"Hello".to_vec());
+    conn.send(MessageKind::Ordered, b"World".to_vec());
+
+    conn.send_packet(&mut socket, &address, &mut owner, &mut handler);
+    socket_handle.assert_sent(vec![
+        ("255.1.1.2:5678", [
+            1, 2, 3, 4,
+            (conn.id().0 >> 24) as u8,
+            (conn.id().0 >> 16) as u8,
+            (conn.id().0 >> 8) as u8,
+             conn.id().0 as u8,
+            0,
+            0,
+            0, 0, 0, 0,
+
+            // Foo
+            0, 0, 0, 3, 70, 111, 111,
+
+            // Bar
+            0, 0, 0, 3, 66, 97, 114,
+
+            // Test
+            1, 0, 0, 4, 84, 101, 115, 116,
+
+            // Hello
+            2, 0, 0, 5, 72, 101, 108, 108, 111,
+
+            // World
+            2, 1, 0, 5, 87, 111, 114, 108, 100
+
+        ].to_vec())
+    ]);
+
+    // Test Message Receiving
+    conn.receive_packet([
+        1, 2, 3, 4,
+        0, 0, 0, 0,
+        0,
+        0,
+        0, 0, 0, 0,
+
+        // Foo
+        0, 0, 0, 3, 70, 111, 111,
+
+        // Bar
+        0, 0, 0, 3, 66, 97, 114,
+
+        // Test
+        1, 0, 0, 4, 84, 101, 115, 116,
+
+        // We actually test inverse receiving order here!
+
+        // World
+        2, 1, 0, 5, 87, 111, 114, 108, 100,
+
+        // Hello
+        2, 0, 0, 5, 72, 101, 108, 108, 111
+
+    ].to_vec(), 0, &mut owner, &mut handler);
+
+    // Get received messages
+    let messages: Vec<Vec<u8>> = conn.received().collect();
+
+    assert_eq!(messages, vec![
+        b"Foo".to_vec(),
+        b"Bar".to_vec(),
+        b"Test".to_vec(),
+        b"Hello".to_vec(),
+        b"World".to_vec()
+    ]);
+
+    // Test Received dismissing
+    conn.receive_packet([
+        1, 2, 3, 4,
+        0, 0, 0, 0,
+        1,
+        1,
+        0, 0, 0, 0,
+
+        // Foo
+        0, 0, 0, 3, 70, 111, 111
+
+    ].to_vec(), 0, &mut owner, &mut handler);
+
+    // send_packet should dismiss any received messages which have not been fetched
+    conn.send_packet(&mut socket, &address, &mut owner, &mut handler);
+    socket_handle.assert_sent(vec![
+        ("255.1.1.2:5678", [
+            1, 2, 3, 4,
+            (conn.id().0 >> 24) as u8,
+            (conn.id().0 >> 16) as u8,
+            (conn.id().0 >> 8) as u8,
+             conn.id().0 as u8,
+            1,
+            1,
+            0, 0, 0, 1
+
+        ].to_vec())
+    ]);
+
+    let messages: Vec<Vec<u8>> = conn.received().collect();
+    assert_eq!(messages.len(), 0);
+
+}
+
+#[test]
+fn test_receive_messages_with_arrival() {
+
+    let (mut conn, _, _, mut owner, mut handler) = create_socket(None);
+
+    conn.receive_packet([
+        1, 2, 3, 4,
+        0, 0, 0, 0,
+        0,
+        0,
+        0, 0, 0, 0,
+
+        // World
+        2, 1, 0, 5, 87, 111, 114, 108, 100
+
+    ].to_vec(), 0, &mut owner, &mut handler);
+
+    thread::sleep(Duration::from_millis(10));
+
+    conn.receive_packet([
+        1, 2, 3, 4,
+        0, 0, 0, 0,
+        1,
+        0,
+        0, 0, 0, 0,
+
+        // Hello
+        2, 0, 0, 5, 72, 101, 108, 108, 111,
+
+        // Foo
+        0, 0, 0, 3, 70, 111, 111
+
+    ].to_vec(), 0, &mut owner, &mut handler);
+
+    let messages: Vec<_> = conn.received_with_arrival().collect();
+    assert_eq!(messages.len(), 3);
+
+    // Messages report the arrival and sequence number of their own packet
+    let (hello_arrival, ref hello) = messages[0];
+    let (world_arrival, ref world) = messages[1];
+    let (foo_arrival, ref foo) = messages[2];
+
+    let (hello_time, hello_seq) = hello_arrival.unwrap();
+    let (world_time, world_seq) = world_arrival.unwrap();
+    let (foo_time, foo_seq) = foo_arrival.unwrap();
+
+    assert_eq!(hello, b"Hello");
+    assert_eq!(world, b"World");
+    assert_eq!(foo, b"Foo");
+
+    assert_eq!(hello_seq, 1);
+    assert_eq!(world_seq, 0);
+    assert_eq!(foo_seq, 1);
+
+    assert_eq!(hello_time, foo_time);
+    assert!(hello_time - world_time >= Duration::from_millis(10));
+
+}
+
+#[test]
+fn test_send_messages_while_connecting() {
+
+    let config = Config {
+        connecting_message_limit: 2,
+        .. Config::default()
+    };
+
+    let (mut conn, mut socket, mut socket_handle, mut owner, mut handler) = create_socket(Some(config));
+    let address = conn.peer_addr();
+
+    conn.send(MessageKind::Instant, b"Foo".to_vec());
+    conn.send(MessageKind::Ordered, b"Bar".to_vec());
+    assert_eq!(
+        conn.try_send(MessageKind::Reliable, b"Baz".to_vec()).unwrap_err().kind(),
+        ErrorKind::WouldBlock
+    );
+
+    // Messages are held back until the connection is established
+    conn.send_packet(&mut socket, &address, &mut owner, &mut handler);
+    socket_handle.assert_sent(vec![
+        ("255.1.1.2:5678", [
+            1, 2, 3, 4,
+            (conn.id().0 >> 24) as u8,
+            (conn.id().0 >> 16) as u8,
+            (conn.id().0 >> 8) as u8,
+             conn.id().0 as u8,
+            0,
+            0,
+            0, 0, 0, 0
+
+        ].to_vec())
+    ]);
+
+    conn.receive_packet([
+        1, 2, 3, 4,
+        0, 0, 0, 0,
+        0,
+        0,
+        0, 0, 0, 0
+
+    ].to_vec(), 0, &mut owner, &mut handler);
+
+    assert_eq!(conn.state(), ConnectionState::Connected);
+    conn.send(MessageKind::Reliable, b"Baz".to_vec());
+
+    conn.send_packet(&mut socket, &address, &mut owner, &mut handler);
+    socket_handle.assert_sent(vec![
+        ("255.1.1.2:5678", [
+            1, 2, 3, 4,
+            (conn.id().0 >> 24) as u8,
+            (conn.id().0 >> 16) as u8,
+            (conn.id().0 >> 8) as u8,
+             conn.id().0 as u8,
+            1,
+            0,
+            0, 0, 0, 0,
+
+            // Foo
+            0, 0, 0, 3, 70, 111, 111,
+
+            // Baz
+            1, 1, 0, 3, 66, 97, 122,
+
+            // Bar
+            2, 0, 0, 3, 66, 97, 114
+
+        ].to_vec())
+    ]);
+
+}
+
+#[test]
+fn test_receive_invalid_packets() {
+
+    let (mut conn, mut owner, mut handler) = create_connection(None);
+
+    // Empty packet
+    conn.receive_packet([].to_vec(), 0, &mut owner, &mut handler);
+
+    // Garbage packet
+    conn.receive_packet([
+        1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14
+
+    ].to_vec(), 0, &mut owner, &mut handler);
+
+}
+
+#[test]
+fn test_rtt() {
+
+    let (mut conn, mut socket, mut socket_handle, mut owner, mut handler) = create_socket(None);
+    let address = conn.peer_addr();
+
+    assert_eq!(conn.rtt(), 0);
+
+    // First packet
+    conn.send_packet(&mut socket, &address, &mut owner, &mut handler);
+    socket_handle.assert_sent(vec![
+        ("255.1.1.2:5678", [
+            1, 2, 3, 4,
+            (conn.id().0 >> 24) as u8,
+            (conn.id().0 >> 16) as u8,
+            (conn.id().0 >> 8) as u8,
+             conn.id().0 as u8,
+            0,
+            0,
+            0, 0, 0, 0
+        ].to_vec())
+    ]);
+
+    thread::sleep(Duration::from_millis(500));
+    conn.receive_packet([
+        1, 2, 3, 4,
+        0, 0, 0, 0,
+        0,
+        0, // confirm the packet above
+        0, 0,
+        0, 0
+
+    ].to_vec(), 0, &mut owner, &mut handler);
+
+    // Expect RTT value to have moved by 10% of the overall roundtrip time
+    assert!(conn.rtt() >= 40);
+
+    // Second packet
+    conn.send_packet(&mut socket, &address, &mut owner, &mut handler);
+    socket_handle.assert_sent(vec![
+        ("255.1.1.2:5678", [
+            1, 2, 3, 4,
+            (conn.id().0 >> 24) as u8,
+            (conn.id().0 >> 16) as u8,
+            (conn.id().0 >> 8) as u8,
+             conn.id().0 as u8,
+            1,
+            0,
+            0, 0, 0, 0
+        ].to_vec())
+    ]);
+    conn.receive_packet([
+        1, 2, 3, 4,
+        0, 0, 0, 0,
+        1,
+        1, // confirm the packet above
+        0, 0,
+        0, 0
+
+    ].to_vec(), 0, &mut owner, &mut handler);
+
+    // Third packet
+    conn.send_packet(&mut socket, &address, &mut owner, &mut handler);
+    socket_handle.assert_sent(vec![
+        ("255.1.1.2:5678", [
+            1, 2, 3, 4,
+            (conn.id().0 >> 24) as u8,
+            (conn.id().0 >> 16) as u8,
+            (conn.id().0 >> 8) as u8,
+             conn.id().0 as u8,
+            2,
+            1,
+            0, 0, 0, 1
+        ].to_vec())
+    ]);
+    conn.receive_packet([
+        1, 2, 3, 4,
+        0, 0, 0, 0,
+        2,
+        2, // confirm the packet above
+        0, 0,
+        0, 0
+
+    ].to_vec(), 0, &mut owner, &mut handler);
+
+    // Fourth packet
+    conn.send_packet(&mut socket, &address, &mut owner, &mut handler);
+    socket_handle.assert_sent(vec![
+        ("255.1.1.2:5678", [
+            1, 2, 3, 4,
+            (conn.id().0 >> 24) as u8,
+            (conn.id().0 >> 16) as u8,
+            (conn.id().0 >> 8) as u8,
+             conn.id().0 as u8,
+            3,
+            2,
+            0, 0, 0, 3
+        ].to_vec())
+    ]);
+    conn.receive_packet([
+        1, 2, 3, 4,
+        0, 0, 0, 0,
+        3,
+        3, // confirm the packet above
+        0, 0,
+        0, 0
+
+    ].to_vec(), 0, &mut owner, &mut handler);
+
+    // Fifth packet
+    conn.send_packet(&mut socket, &address, &mut owner, &mut handler);
+    socket_handle.assert_sent(vec![
+        ("255.1.1.2:5678", [
+            1, 2, 3, 4,
+            (conn.id().0 >> 24) as u8,
+            (conn.id().0 >> 16) as u8,
+            (conn.id().0 >> 8) as u8,
+             conn.id().0 as u8,
+            4,
+            3,
+            0, 0, 0, 7
+        ].to_vec())
+    ]);
+    conn.receive_packet([
+        1, 2, 3, 4,
+        0, 0, 0, 0,
+        4,
+        4, // confirm the packet above
+        0, 0,
+        0, 0
+
+    ].to_vec(), 0, &mut owner, &mut handler);
+
+    // Sixth packet
+    conn.send_packet(&mut socket, &address, &mut owner, &mut handler);
+    socket_handle.assert_sent(vec![
+        ("255.1.1.2:5678", [
+            1, 2, 3, 4,
+            (conn.id().0 >> 24) as u8,
+            (conn.id().0 >> 16) as u8,
+            (conn.id().0 >> 8) as u8,
+             conn.id().0 as u8,
+            5,
+            4,
+            0, 0, 0, 15
+        ].to_vec())
+    ]);
+
+    conn.receive_packet([
+        1, 2, 3, 4,
+        0, 0, 0, 0,
+        5,
+        5, // confirm the packet above
+        0, 0,
+        0, 0
+
+    ].to_vec(), 0, &mut owner, &mut handler);
+
+    // Expect RTT to have reduced by 10%
+    assert!(conn.rtt() <= 40);
+
+}
+
+#[test]
+fn test_rtt_percentile() {
+
+    let (mut conn, mut socket, _, mut owner, mut handler) = create_socket(None);
+    let address = conn.peer_addr();
+
+    assert_eq!(conn.rtt_percentile(99.0), 0);
+
+    conn.send_packet(&mut socket, &address, &mut owner, &mut handler);
+    thread::sleep(Duration::from_millis(100));
+    conn.receive_packet([
+        1, 2, 3, 4,
+        0, 0, 0, 0,
+        0,
+        0, // confirm the packet above
+        0, 0,
+        0, 0
+
+    ].to_vec(), 0, &mut owner, &mut handler);
+
+    // Percentiles report the actual sample instead of the smoothed average
+    assert!(conn.rtt() < 100);
+    assert!(conn.rtt_percentile(99.0) >= 100);
+
+    conn.reset();
+    assert_eq!(conn.rtt_percentile(99.0), 0);
+
+}
+
+#[test]
+fn test_rtt_tick_correction() {
+
+    let (mut conn, mut socket, mut socket_handle, mut owner, mut handler) = create_socket(None);
+    let address = conn.peer_addr();
+
+    assert_eq!(conn.rtt(), 0);
+
+    // First packet
+    conn.send_packet(&mut socket, &address, &mut owner, &mut handler);
+    socket_handle.assert_sent(vec![
+        ("255.1.1.2:5678", [
+            1, 2, 3, 4,
+            (conn.id().0 >> 24) as u8,
+            (conn.id().0 >> 16) as u8,
+            (conn.id().0 >> 8) as u8,
+             conn.id().0 as u8,
+            0,
+            0,
+            0, 0, 0, 0
+        ].to_vec())
+    ]);
+
+    thread::sleep(Duration::from_millis(500));
+    conn.receive_packet([
+        1, 2, 3, 4,
+        0, 0, 0, 0,
+        0,
+        0, // confirm the packet above
+        0, 0,
+        0, 0
+
+    ].to_vec(), 500, &mut owner, &mut handler);
+
+    // Expect RTT value to have been corrected by passed in tick delay
+    assert!(conn.rtt() <= 10);
+
+}
+
+#[test]
+fn test_retransmit_timeout() {
+
+    let config = Config {
+        retransmit_timeout_ms: 10,
+        .. Config::default()
+    };
+
+    let (mut conn, mut socket, mut socket_handle, mut owner, mut handler) = create_socket(Some(config));
+    let address = conn.peer_addr();
+
+    conn.send(MessageKind::Instant, b"Instant".to_vec());
+    conn.send(MessageKind::Reliable, b"Reliable".to_vec());
+    conn.send_packet(&mut socket, &address, &mut owner, &mut handler);
+    socket_handle.assert_sent(vec![("255.1.1.2:5678", [
+        1, 2, 3, 4,
+        (conn.id().0 >> 24) as u8,
+        (conn.id().0 >> 16) as u8,
+        (conn.id().0 >> 8) as u8,
+         conn.id().0 as u8,
+        0, 0,
+        0, 0, 0, 0,
+
+        0, 0, 0, 7, 73, 110, 115, 116, 97, 110, 116,
+        1, 0, 0, 8, 82, 101, 108, 105, 97, 98, 108, 101
+
+    ].to_vec())]);
+
+    // Wait for the packet to time out without receiving any acknowledgement
+    thread::sleep(Duration::from_millis(20));
+
+    // The reliable message should be re-send with the next packet
+    conn.send_packet(&mut socket, &address, &mut owner, &mut handler);
+    socket_handle.assert_sent(vec![("255.1.1.2:5678", [
+        1, 2, 3, 4,
+        (conn.id().0 >> 24) as u8,
+        (conn.id().0 >> 16) as u8,
+        (conn.id().0 >> 8) as u8,
+         conn.id().0 as u8,
+        1, 0,
+        0, 0, 0, 0,
+
+        1, 0, 0, 8, 82, 101, 108, 105, 97, 98, 108, 101
+
+    ].to_vec())]);
+
+    assert_eq!(conn.packet_loss(), 50.0);
+
+    // But not again before it timed out once more
+    conn.send_packet(&mut socket, &address, &mut owner, &mut handler);
+    socket_handle.assert_sent(vec![("255.1.1.2:5678", [
+        1, 2, 3, 4,
+        (conn.id().0 >> 24) as u8,
+        (conn.id().0 >> 16) as u8,
+        (conn.id().0 >> 8) as u8,
+         conn.id().0 as u8,
+        2, 0,
+        0, 0, 0, 0
+
+    ].to_vec())]);
+
+}
+
+#[test]
+fn test_immediate_acks() {
+
+    let config = Config {
+        immediate_acks: true,
+        .. Config::default()
+    };
+
+    let (mut conn, mut socket, mut socket_handle, mut owner, mut handler) = create_socket(Some(config));
+    let address = conn.peer_addr();
+
+    // Nothing to acknowledge yet
+    conn.send_ack_packet(&mut socket, &address, &mut owner, &mut handler);
+    socket_handle.assert_sent_none();
+
+    // Receive a packet containing data
+    conn.receive_packet([
+        1, 2, 3, 4,
+        0, 0, 0, 0,
+        0,
+        0,
+        0, 0, 0, 0,
+
+        0, 0, 0, 3, 70, 111, 111
+
+    ].to_vec(), 0, &mut owner, &mut handler);
+
+    // Acknowledge it right away
+    conn.send_ack_packet(&mut socket, &address, &mut owner, &mut handler);
+    socket_handle.assert_sent(vec![("255.1.1.2:5678", [
+        1, 2, 3, 4,
+        (conn.id().0 >> 24) as u8,
+        (conn.id().0 >> 16) as u8,
+        (conn.id().0 >> 8) as u8,
+         conn.id().0 as u8,
+        0, 0,
+        0, 0, 0, 0,
+
+        8, 0, 0, 1, 7
+
+    ].to_vec())]);
+
+    // But only once
+    conn.send_ack_packet(&mut socket, &address, &mut owner, &mut handler);
+    socket_handle.assert_sent_none();
+
+    // Empty packets do not require immediate acknowledgment
+    conn.receive_packet([
+        1, 2, 3, 4,
+        0, 0, 0, 0,
+        1,
+        0,
+        0, 0, 0, 0
+
+    ].to_vec(), 0, &mut owner, &mut handler);
+
+    conn.send_ack_packet(&mut socket, &address, &mut owner, &mut handler);
+    socket_handle.assert_sent_none();
+
+    // Regular packets acknowledge all previously received data
+    conn.receive_packet([
+        1, 2, 3, 4,
+        0, 0, 0, 0,
+        2,
+        0,
+        0, 0, 0, 0,
+
+        0, 0, 0, 3, 66, 97, 114
+
+    ].to_vec(), 0, &mut owner, &mut handler);
+
+    conn.send_packet(&mut socket, &address, &mut owner, &mut handler);
+    socket_handle.assert_sent(vec![("255.1.1.2:5678", [
+        1, 2, 3, 4,
+        (conn.id().0 >> 24) as u8,
+        (conn.id().0 >> 16) as u8,
+        (conn.id().0 >> 8) as u8,
+         conn.id().0 as u8,
+        1, 2,
+        0, 0, 0, 3
+
+    ].to_vec())]);
+
+    conn.send_ack_packet(&mut socket, &address, &mut owner, &mut handler);
+    socket_handle.assert_sent_none();
+
+}
+
+#[test]
+fn test_immediate_ack_coalescing() {
+
+    let config = Config {
+        immediate_acks: true,
+        ack_coalesce_packets: 2,
+        ack_coalesce_ms: 20,
+        .. Config::default()
+    };
+
+    let (mut conn, mut socket, mut socket_handle, mut owner, mut handler) = create_socket(Some(config));
+    let address = conn.peer_addr();
+
+    conn.receive_packet([
+        1, 2, 3, 4,
+        0, 0, 0, 0,
+        0,
+        0,
+        0, 0, 0, 0,
+
+        0, 0, 0, 3, 70, 111, 111
+
+    ].to_vec(), 0, &mut owner, &mut handler);
+
+    // Coalesce the acknowledgment of the first packet
+    conn.send_ack_packet(&mut socket, &address, &mut owner, &mut handler);
+    socket_handle.assert_sent_none();
+
+    conn.receive_packet([
+        1, 2, 3, 4,
+        0, 0, 0, 0,
+        1,
+        0,
+        0, 0, 0, 0,
+
+        0, 0, 0, 3, 66, 97, 114
+
+    ].to_vec(), 0, &mut owner, &mut handler);
+
+    // Acknowledge both packets once the packet limit is reached
+    conn.send_ack_packet(&mut socket, &address, &mut owner, &mut handler);
+    socket_handle.assert_sent(vec![("255.1.1.2:5678", [
+        1, 2, 3, 4,
+        (conn.id().0 >> 24) as u8,
+        (conn.id().0 >> 16) as u8,
+        (conn.id().0 >> 8) as u8,
+         conn.id().0 as u8,
+        0, 1,
+        0, 0, 0, 1,
+
+        8, 0, 0, 1, 7
+
+    ].to_vec())]);
+
+    conn.receive_packet([
+        1, 2, 3, 4,
+        0, 0, 0, 0,
+        2,
+        0,
+        0, 0, 0, 0,
+
+        0, 0, 0, 3, 66, 97, 122
+
+    ].to_vec(), 0, &mut owner, &mut handler);
+
+    conn.send_ack_packet(&mut socket, &address, &mut owner, &mut handler);
+    socket_handle.assert_sent_none();
+
+    // Acknowledge the packet once the time limit is exceeded
+    thread::sleep(Duration::from_millis(30));
+    conn.send_ack_packet(&mut socket, &address, &mut owner, &mut handler);
+    socket_handle.assert_sent(vec![("255.1.1.2:5678", [
+        1, 2, 3, 4,
+        (conn.id().0 >> 24) as u8,
+        (conn.id().0 >> 16) as u8,
+        (conn.id().0 >> 8) as u8,
+         conn.id().0 as u8,
+        1, 2,
+        0, 0, 0, 3,
+
+        8, 0, 0, 1, 7
+
+    ].to_vec())]);
+
+}
+
+#[test]
+fn test_immediate_ack_coalescing_time() {
+
+    // The time limit applies without a packet limit
+    let config = Config {
+        immediate_acks: true,
+        ack_coalesce_ms: 20,
+        .. Config::default()
+    };
+
+    let (mut conn, mut socket, mut socket_handle, mut owner, mut handler) = create_socket(Some(config));
+    let address = conn.peer_addr();
+
+    for seq in 0..3 {
+        conn.receive_packet([
+            1, 2, 3, 4,
+            0, 0, 0, 0,
+            seq,
+            0,
+            0, 0, 0, 0,
+
+            0, 0, 0, 3, 70, 111, 111
+
+        ].to_vec(), 0, &mut owner, &mut handler);
+
+        conn.send_ack_packet(&mut socket, &address, &mut owner, &mut handler);
+        socket_handle.assert_sent_none();
+    }
+
+    thread::sleep(Duration::from_millis(30));
+    conn.send_ack_packet(&mut socket, &address, &mut owner, &mut handler);
+    socket_handle.assert_sent(vec![("255.1.1.2:5678", [
+        1, 2, 3, 4,
+        (conn.id().0 >> 24) as u8,
+        (conn.id().0 >> 16) as u8,
+        (conn.id().0 >> 8) as u8,
+         conn.id().0 as u8,
+        0, 2,
+        0, 0, 0, 3,
+
+        8, 0, 0, 1, 7
+
+    ].to_vec())]);
+
+}
+
+#[test]
+fn test_immediate_ack_rate_limited() {
+
+    struct SkippingRateLimiter;
+
+    impl RateLimiter for SkippingRateLimiter {
+        fn update(&mut self, _: u32, _: f32) {}
+        fn congested(&self) -> bool {
+            true
+        }
+        fn should_send(&self) -> bool {
+            false
+        }
+        fn reset(&mut self) {}
+    }
+
+    let config = Config {
+        immediate_acks: true,
+        ack_coalesce_ms: 20,
+        .. Config::default()
+    };
+
+    let (conn, mut owner, mut handler) = create_connection(Some(config));
+    let mut conn = Connection::new(
+        config, conn.local_addr(), conn.peer_addr(), Box::new(SkippingRateLimiter)
+    );
+    let mut socket = MockSocket::from_address(conn.local_addr());
+    let mut socket_handle = socket.handle();
+    let address = conn.peer_addr();
+
+    conn.receive_packet([
+        1, 2, 3, 4,
+        0, 0, 0, 0,
+        0,
+        0,
+        0, 0, 0, 0,
+
+        0, 0, 0, 3, 70, 111, 111
+
+    ].to_vec(), 0, &mut owner, &mut handler);
+
+    // Skipped ticks do not acknowledge the data before the time limit
+    conn.send_packet(&mut socket, &address, &mut owner, &mut handler);
+    socket_handle.assert_sent_none();
+
+    // But do so once it was exceeded
+    thread::sleep(Duration::from_millis(30));
+    conn.send_packet(&mut socket, &address, &mut owner, &mut handler);
+    socket_handle.assert_sent(vec![("255.1.1.2:5678", [
+        1, 2, 3, 4,
+        (conn.id().0 >> 24) as u8,
+        (conn.id().0 >> 16) as u8,
+        (conn.id().0 >> 8) as u8,
+         conn.id().0 as u8,
+        0, 0,
+        0, 0, 0, 0,
+
+        8, 0, 0, 1, 7
+
+    ].to_vec())]);
+
+}
+
+#[test]
+fn test_immediate_ack_rtt() {
+
+    let (mut conn, mut socket, mut socket_handle, mut owner, mut handler) = create_socket(None);
+    let address = conn.peer_addr();
+
+    conn.send_packet(&mut socket, &address, &mut owner, &mut handler);
+    socket_handle.sent();
+
+    thread::sleep(Duration::from_millis(500));
+
+    // Acknowledgments from immediate ack packets are not corrected by the
+    // tick delay
+    conn.receive_packet([
+        1, 2, 3, 4,
+        0, 0, 0, 0,
+        0,
+        0,
+        0, 0, 0, 0,
+
+        8, 0, 0, 1, 7
+
+    ].to_vec(), 500, &mut owner, &mut handler);
+
+    assert!(conn.rtt() >= 40);
+
+}
+
+#[test]
+fn test_message_delivered() {
+
+    struct DeliveryHandler {
+        delivered: Vec<MessageHandle>
+    }
+
+    impl Handler<MockOwner> for DeliveryHandler {
+        fn connection_message_delivered(
+            &mut self, _: &mut MockOwner, _: &mut Connection, handle: MessageHandle
+        ) {
+            self.delivered.push(handle);
+        }
+    }
+
+    let config = Config {
+        packet_drop_threshold: 10,
+        .. Config::default()
+    };
+
+    let (mut conn, mut socket, _, mut owner, _) = create_socket(Some(config));
+    let mut handler = DeliveryHandler {
+        delivered: Vec::new()
+    };
+    let address = conn.peer_addr();
+
+    let reliable = conn.send(MessageKind::Reliable, b"Foo".to_vec()).unwrap();
+    conn.send(MessageKind::Instant, b"Bar".to_vec());
+    let ordered = conn.send(MessageKind::Ordered, b"Baz".to_vec()).unwrap();
+    conn.send_packet(&mut socket, &address, &mut owner, &mut handler);
+
+    let lost = conn.send(MessageKind::Reliable, b"Lost".to_vec()).unwrap();
+    conn.send_packet(&mut socket, &address, &mut owner, &mut handler);
+
+    // Acknowledge the first packet
+    conn.receive_packet([
+        1, 2, 3, 4,
+        0, 0, 0, 0,
+        0, 0,
+        0, 0, 0, 0
+
+    ].to_vec(), 0, &mut owner, &mut handler);
+
+    assert_eq!(handler.delivered, vec![reliable, ordered]);
+
+    // Lose the second packet
+    thread::sleep(Duration::from_millis(20));
+    conn.receive_packet([
+        1, 2, 3, 4,
+        0, 0, 0, 0,
+        1, 0,
+        0, 0, 0, 0
+
+    ].to_vec(), 0, &mut owner, &mut handler);
+
+    assert_eq!(handler.delivered, vec![reliable, ordered]);
+
+    // Acknowledge the packet re-sending the lost message
+    conn.send_packet(&mut socket, &address, &mut owner, &mut handler);
+    conn.receive_packet([
+        1, 2, 3, 4,
+        0, 0, 0, 0,
+        2, 2,
+        0, 0, 0, 0
+
+    ].to_vec(), 0, &mut owner, &mut handler);
+
+    assert_eq!(handler.delivered, vec![reliable, ordered, lost]);
+
+}
+
+#[cfg(feature = "encryption")]
+#[test]
+fn test_encryption() {
+
+    let config = Config {
+        encryption_key: Some([7; 32]),
+        .. Config::default()
+    };
+
+    let (mut conn, mut socket, mut socket_handle, mut owner, mut handler) = create_socket(Some(config));
+    let (mut remote, _, _) = create_connection(Some(config));
+    remote.set_id(conn.id());
+    remote.set_accepted();
+    assert!(conn.encrypted());
+
+    let address = conn.peer_addr();
+    conn.send(MessageKind::Instant, b"Foo".to_vec());
+    conn.send_packet(&mut socket, &address, &mut owner, &mut handler);
+
+    // The packet header is send in plain text, while its body is encrypted
+    let packet = socket_handle.sent().remove(0).1;
+    assert_eq!(packet.len(), 14 + 28 + 7);
+    assert_eq!(packet[..14].to_vec(), [
+        1, 2, 3, 4,
+        (conn.id().0 >> 24) as u8,
+        (conn.id().0 >> 16) as u8,
+        (conn.id().0 >> 8) as u8,
+         conn.id().0 as u8,
+        0, 0,
+        0, 0, 0, 0
+    ].to_vec());
+
+    // Packets with a tampered header fail to authenticate
+    let mut tampered = packet.clone();
+    tampered[9] = 1;
+    remote.receive_packet(tampered, 0, &mut owner, &mut handler);
+    assert!(remote.state() == ConnectionState::Connecting);
+
+    // Packets with a tampered body fail to authenticate
+    let mut tampered = packet.clone();
+    tampered[20] ^= 1;
+    remote.receive_packet(tampered, 0, &mut owner, &mut handler);
+    assert!(remote.state() == ConnectionState::Connecting);
+
+    // Connections with a different id derive a different key
+    let (mut other, _, _) = create_connection(Some(config));
+    other.receive_packet(packet.clone(), 0, &mut owner, &mut handler);
+    assert!(other.state() == ConnectionState::Connecting);
+
+    remote.receive_packet(packet, 0, &mut owner, &mut handler);
+    assert!(remote.state() == ConnectionState::Connected);
+    assert_eq!(remote.received().collect::<Vec<Vec<u8>>>(), vec![b"Foo".to_vec()]);
+
+}
+
+#[cfg(feature = "encryption")]
+#[test]
+fn test_encryption_replay() {
+
+    let config = Config {
+        encryption_key: Some([7; 32]),
+        .. Config::default()
+    };
+
+    let (mut conn, mut socket, mut socket_handle, mut owner, mut handler) = create_socket(Some(config));
+    let (mut remote, _, _) = create_connection(Some(config));
+    remote.set_id(conn.id());
+    remote.set_accepted();
+
+    let address = conn.peer_addr();
+    let mut packets = Vec::new();
+    for data in [b"Foo", b"Bar"].iter() {
+        conn.send(MessageKind::Instant, data.to_vec());
+        conn.send_packet(&mut socket, &address, &mut owner, &mut handler);
+        packets.push(socket_handle.sent().remove(0).1);
+    }
+
+    remote.receive_packet(packets[0].clone(), 0, &mut owner, &mut handler);
+    remote.receive_packet(packets[1].clone(), 0, &mut owner, &mut handler);
+    assert_eq!(remote.received().collect::<Vec<Vec<u8>>>(), vec![
+        b"Foo".to_vec(),
+        b"Bar".to_vec()
+    ]);
+    assert_eq!(remote.replayed_packets(), 0);
+
+    // Replayed packets are dropped and counted
+    remote.receive_packet(packets[0].clone(), 0, &mut owner, &mut handler);
+    remote.receive_packet(packets[1].clone(), 0, &mut owner, &mut handler);
+    assert!(remote.received().next().is_none());
+    assert_eq!(remote.replayed_packets(), 2);
+    assert_eq!(remote.security_stats().replayed, 2);
+
+    // Resetting the connection starts a new window
+    remote.reset();
+    assert_eq!(remote.replayed_packets(), 0);
+
+}
+
+#[cfg(feature = "encryption")]
+#[test]
+fn test_encryption_reflection() {
+
+    let config = Config {
+        encryption_key: Some([7; 32]),
+        .. Config::default()
+    };
+
+    let (mut conn, mut socket, mut socket_handle, mut owner, mut handler) = create_socket(Some(config));
+    let (mut remote, mut remote_socket, mut remote_handle, _, _) = create_socket(Some(config));
+    remote.set_id(conn.id());
+    remote.set_accepted();
+
+    let address = conn.peer_addr();
+    let remote_address = remote.peer_addr();
+    conn.send(MessageKind::Instant, b"Foo".to_vec());
+    conn.send_packet(&mut socket, &address, &mut owner, &mut handler);
+    let packet = socket_handle.sent().remove(0).1;
+
+    remote.send(MessageKind::Instant, b"Bar".to_vec());
+    remote.send_packet(&mut remote_socket, &remote_address, &mut owner, &mut handler);
+    let remote_packet = remote_handle.sent().remove(0).1;
+
+    // Both ends use the same counter, but never the same nonce
+    assert_ne!(packet[14..26], remote_packet[14..26]);
+
+    // Packets reflected back at their sender are dropped
+    conn.receive_packet(packet.clone(), 0, &mut owner, &mut handler);
+    assert!(conn.received().next().is_none());
+    assert_eq!(conn.replayed_packets(), 1);
+
+    remote.receive_packet(remote_packet.clone(), 0, &mut owner, &mut handler);
+    assert!(remote.received().next().is_none());
+    assert_eq!(remote.replayed_packets(), 1);
+
+    // While packets of the other end are accepted
+    remote.receive_packet(packet, 0, &mut owner, &mut handler);
+    assert_eq!(remote.received().collect::<Vec<Vec<u8>>>(), vec![b"Foo".to_vec()]);
+
+    conn.receive_packet(remote_packet, 0, &mut owner, &mut handler);
+    assert_eq!(conn.received().collect::<Vec<Vec<u8>>>(), vec![b"Bar".to_vec()]);
+
+}
+
+#[cfg(feature = "encryption")]
+#[test]
+fn test_encryption_max_message_size() {
+
+    let (mut conn, _, _) = create_connection(None);
+    assert_eq!(conn.max_message_size(), 1400 - 14 - 4);
+
+    conn.set_encryption_key([1; 32]);
+    assert!(conn.encrypted());
+    assert_eq!(conn.max_message_size(), 1400 - 14 - 28 - 4);
+
+}
+
+#[cfg(feature = "encryption")]
+#[test]
+fn test_key_rotation() {
+
+    let config = Config {
+        encryption_key: Some([7; 32]),
+        key_rotation_packets: 3,
+        .. Config::default()
+    };
+
+    let (mut conn, mut socket, mut socket_handle, mut owner, mut handler) = create_socket(Some(config));
+    let (mut remote, mut remote_socket, mut remote_handle, _, _) = create_socket(Some(config));
+    remote.set_id(conn.id());
+    remote.set_accepted();
+    let address = conn.peer_addr();
+    let remote_address = remote.peer_addr();
+
+    // Exchange packets in both directions
+    let mut exchange = |conn: &mut Connection, remote: &mut Connection, data: &[u8]| {
+
+        conn.send(MessageKind::Instant, data.to_vec());
+        conn.send_packet(&mut socket, &address, &mut owner, &mut handler);
+        remote.receive_packet(socket_handle.sent().remove(0).1, 0, &mut owner, &mut handler);
+        assert_eq!(remote.received().collect::<Vec<Vec<u8>>>(), vec![data.to_vec()]);
+
+        remote.send(MessageKind::Instant, data.to_vec());
+        remote.send_packet(&mut remote_socket, &remote_address, &mut owner, &mut handler);
+        conn.receive_packet(remote_handle.sent().remove(0).1, 0, &mut owner, &mut handler);
+        assert_eq!(conn.received().collect::<Vec<Vec<u8>>>(), vec![data.to_vec()]);
+
+    };
+
+    exchange(&mut conn, &mut remote, b"Foo");
+    assert_eq!(conn.key_epoch(), 0);
+    assert_eq!(remote.key_epoch(), 0);
+
+    // Both ends rotate their key after the configured number of packets
+    for _ in 0..4 {
+        exchange(&mut conn, &mut remote, b"Bar");
+    }
+    assert_eq!(conn.key_epoch(), 1);
+    assert_eq!(remote.key_epoch(), 1);
+
+    // Keys can also be rotated manually
+    conn.rotate_key();
+    for _ in 0..3 {
+        exchange(&mut conn, &mut remote, b"Baz");
+    }
+    assert_eq!(conn.key_epoch(), 2);
+    assert_eq!(remote.key_epoch(), 2);
+
+    // Resetting restores the initial key
+    conn.reset();
+    assert_eq!(conn.key_epoch(), 0);
+
+    let (mut other, _, _) = create_connection(Some(config));
+    other.set_id(conn.id());
+    other.set_accepted();
+    conn.send(MessageKind::Instant, b"Qux".to_vec());
+    conn.send_packet(&mut socket, &address, &mut owner, &mut handler);
+    other.receive_packet(socket_handle.sent().remove(0).1, 0, &mut owner, &mut handler);
+    assert_eq!(other.received().collect::<Vec<Vec<u8>>>(), vec![b"Qux".to_vec()]);
+
+}
+
+#[cfg(feature = "key_exchange")]
+#[test]
+fn test_key_exchange() {
+
+    let config = Config {
+        key_exchange: true,
+        .. Config::default()
+    };
+
+    let (mut conn, mut socket, mut socket_handle, mut owner, mut handler) = create_socket(Some(config));
+    let (mut remote, mut remote_socket, mut remote_handle, _, _) = create_socket(Some(config));
+    remote.set_id(conn.id());
+    assert_eq!(conn.session_key(), None);
+
+    // Exchange the public keys of both ends
+    let address = conn.peer_addr();
+    conn.send_packet(&mut socket, &address, &mut owner, &mut handler);
+    remote.receive_packet(socket_handle.sent().remove(0).1, 0, &mut owner, &mut handler);
+
+    let address = remote.peer_addr();
+    remote.send_packet(&mut remote_socket, &address, &mut owner, &mut handler);
+    conn.receive_packet(remote_handle.sent().remove(0).1, 0, &mut owner, &mut handler);
+
+    // Both ends derive the same key
+    assert!(conn.session_key().is_some());
+    assert_eq!(conn.session_key(), remote.session_key());
+
+    // Resetting the connection starts a new exchange
+    conn.reset();
+    assert_eq!(conn.session_key(), None);
+
+}
+
+#[cfg(feature = "key_exchange")]
+#[test]
+fn test_key_exchange_disabled() {
+
+    let (mut conn, mut socket, mut socket_handle, mut owner, mut handler) = create_socket(None);
+    let (mut remote, _, _) = create_connection(None);
+
+    let address = conn.peer_addr();
+    conn.send_packet(&mut socket, &address, &mut owner, &mut handler);
+    remote.receive_packet(socket_handle.sent().remove(0).1, 0, &mut owner, &mut handler);
+
+    assert_eq!(conn.session_key(), None);
+    assert_eq!(remote.session_key(), None);
+
+}
+
+#[cfg(feature = "key_exchange")]
+#[test]
+fn test_key_exchange_encryption() {
+
+    let config = Config {
+        key_exchange: true,
+        key_exchange_encryption: true,
+        .. Config::default()
+    };
+
+    let (mut conn, mut socket, mut socket_handle, mut owner, mut handler) = create_socket(Some(config));
+    let (mut remote, mut remote_socket, mut remote_handle, _, _) = create_socket(Some(config));
+    remote.set_id(conn.id());
+    remote.set_accepted();
+    let address = conn.peer_addr();
+    let remote_address = remote.peer_addr();
+
+    // Public keys are send in plain text
+    conn.send_packet(&mut socket, &address, &mut owner, &mut handler);
+    let packet = socket_handle.sent().remove(0).1;
+    assert_eq!(packet.len(), 14 + 4 + 33);
+    remote.receive_packet(packet, 0, &mut owner, &mut handler);
+    assert!(!remote.encrypted());
+
+    remote.send_packet(&mut remote_socket, &remote_address, &mut owner, &mut handler);
+    let packet = remote_handle.sent().remove(0).1;
+    assert_eq!(packet.len(), 14 + 4 + 33);
+    conn.receive_packet(packet, 0, &mut owner, &mut handler);
+
+    // Packets are encrypted once the remote acknowledged the public key
+    assert!(conn.encrypted());
+    conn.send(MessageKind::Instant, b"Foo".to_vec());
+    conn.send_packet(&mut socket, &address, &mut owner, &mut handler);
+    let packet = socket_handle.sent().remove(0).1;
+    assert_eq!(packet.len(), 14 + 28 + 7);
+    remote.receive_packet(packet, 0, &mut owner, &mut handler);
+    assert!(remote.encrypted());
+    assert_eq!(remote.received().collect::<Vec<Vec<u8>>>(), vec![b"Foo".to_vec()]);
+
+    remote.send(MessageKind::Instant, b"Bar".to_vec());
+    remote.send_packet(&mut remote_socket, &remote_address, &mut owner, &mut handler);
+    let packet = remote_handle.sent().remove(0).1;
+    assert_eq!(packet.len(), 14 + 28 + 7);
+    conn.receive_packet(packet, 0, &mut owner, &mut handler);
+    assert_eq!(conn.received().collect::<Vec<Vec<u8>>>(), vec![b"Bar".to_vec()]);
+
+    // Both ends derived the same key
+    assert!(conn.session_key().is_some());
+    assert_eq!(conn.session_key(), remote.session_key());
+
+    // Plain text packets are no longer accepted
+    conn.receive_packet([
+        1, 2, 3, 4,
+        (conn.id().0 >> 24) as u8,
+        (conn.id().0 >> 16) as u8,
+        (conn.id().0 >> 8) as u8,
+         conn.id().0 as u8,
+        2, 0,
+        0, 0, 0, 0,
+        0, 0, 0, 3, 66, 97, 122
+    ].to_vec(), 0, &mut owner, &mut handler);
+    assert!(conn.received().next().is_none());
+
+}
+
+#[cfg(feature = "key_exchange")]
+#[test]
+fn test_resumption_ticket() {
+
+    let key = [5; 32];
+    let config = Config {
+        key_exchange: true,
+        key_exchange_encryption: true,
+        .. Config::default()
+    };
+
+    // Establish the session which is going to be resumed
+    let (mut conn, mut socket, mut socket_handle, mut owner, mut handler) = create_socket(Some(config));
+    let (mut remote, mut remote_socket, mut remote_handle, _, _) = create_socket(Some(config));
+    remote.set_id(conn.id());
+    remote.set_accepted();
+    let address = conn.peer_addr();
+    let remote_address = remote.peer_addr();
+
+    conn.send_packet(&mut socket, &address, &mut owner, &mut handler);
+    remote.receive_packet(socket_handle.sent().remove(0).1, 0, &mut owner, &mut handler);
+
+    let issued = remote.issue_resumption_ticket(key, Duration::from_secs(60)).unwrap();
+    assert_eq!(issued.session_key, remote.session_key());
+
+    remote.send_packet(&mut remote_socket, &remote_address, &mut owner, &mut handler);
+    conn.receive_packet(remote_handle.sent().remove(0).1, 0, &mut owner, &mut handler);
+
+    let ticket = conn.resumption_ticket().unwrap();
+    assert_eq!(ticket, issued);
+    assert!(!conn.resumed());
+
+    // Record a packet of the previous session
+    conn.send(MessageKind::Instant, b"Old".to_vec());
+    conn.send_packet(&mut socket, &address, &mut owner, &mut handler);
+    let recorded = socket_handle.sent().remove(0).1;
+
+    // Resume the session from a new connection
+    let (mut client, mut socket, mut socket_handle, mut owner, mut handler) = create_socket(Some(config));
+    let (mut server, mut server_socket, mut server_handle, _, _) = create_socket(Some(config));
+    client.resume_with_ticket(ticket.clone());
+    assert_eq!(client.id(), ticket.id);
+    assert_eq!(client.session_key(), None);
+
+    // The ticket is presented in plain text
+    client.send_packet(&mut socket, &address, &mut owner, &mut handler);
+    let packet = socket_handle.sent().remove(0).1;
+    let data = Connection::resumption_ticket_from_packet(&config, &packet).unwrap();
+    let verified = ResumptionTicket::verify(key, client.id(), &data).unwrap();
+
+    server.set_id(client.id());
+    server.set_accepted();
+    server.accept_resumption_ticket(verified);
+    server.receive_packet(packet, 0, &mut owner, &mut handler);
+    assert!(server.resumed());
+    assert!(server.issue_resumption_ticket(key, Duration::from_secs(60)).is_some());
+
+    // The resumed session uses a fresh key derived from the nonces of both
+    // ends, which the server only encrypts with once the client knows it
+    assert!(server.session_key().is_some());
+    assert!(server.session_key() != ticket.session_key);
+    assert!(!server.encrypted());
+
+    server.send_packet(&mut server_socket, &remote_address, &mut owner, &mut handler);
+    client.receive_packet(server_handle.sent().remove(0).1, 0, &mut owner, &mut handler);
+    assert!(client.state() == ConnectionState::Connected);
+    assert!(client.resumed());
+    assert!(client.encrypted());
+    assert_eq!(client.session_key(), server.session_key());
+    assert!(client.resumption_ticket() != Some(ticket.clone()));
+
+    client.send(MessageKind::Instant, b"Foo".to_vec());
+    client.send_packet(&mut socket, &address, &mut owner, &mut handler);
+    server.receive_packet(socket_handle.sent().remove(0).1, 0, &mut owner, &mut handler);
+    assert_eq!(server.received().collect::<Vec<Vec<u8>>>(), vec![b"Foo".to_vec()]);
+    assert!(server.encrypted());
+
+    // Packets of the previous session are rejected
+    server.receive_packet(recorded, 0, &mut owner, &mut handler);
+    assert!(server.received().next().is_none());
+
+    // Remotes which do not accept the ticket establish a new session
+    let (mut client, mut socket, mut socket_handle, mut owner, mut handler) = create_socket(Some(config));
+    let (mut server, mut server_socket, mut server_handle, _, _) = create_socket(Some(config));
+    client.resume_with_ticket(ticket.clone());
+    server.set_id(client.id());
+    server.set_accepted();
+
+    client.send_packet(&mut socket, &address, &mut owner, &mut handler);
+    server.receive_packet(socket_handle.sent().remove(0).1, 0, &mut owner, &mut handler);
+    server.send_packet(&mut server_socket, &remote_address, &mut owner, &mut handler);
+    client.receive_packet(server_handle.sent().remove(0).1, 0, &mut owner, &mut handler);
+    assert!(!client.resumed());
+    assert_eq!(client.resumption_ticket(), None);
+
+    client.send_packet(&mut socket, &address, &mut owner, &mut handler);
+    server.receive_packet(socket_handle.sent().remove(0).1, 0, &mut owner, &mut handler);
+    assert!(client.session_key().is_some());
+    assert!(client.session_key() != ticket.session_key);
+    assert_eq!(client.session_key(), server.session_key());
+
+}
+
+#[cfg(feature = "encryption")]
+#[test]
+fn test_resumption_ticket_encryption() {
+
+    let key = [5; 32];
+    let config = Config {
+        encryption_key: Some([7; 32]),
+        .. Config::default()
+    };
+
+    // Establish the session which is going to be resumed
+    let (mut conn, mut socket, mut socket_handle, mut owner, mut handler) = create_socket(Some(config));
+    let (mut remote, mut remote_socket, mut remote_handle, _, _) = create_socket(Some(config));
+    remote.set_id(conn.id());
+    remote.set_accepted();
+    let address = conn.peer_addr();
+    let remote_address = remote.peer_addr();
+
+    conn.send_packet(&mut socket, &address, &mut owner, &mut handler);
+    remote.receive_packet(socket_handle.sent().remove(0).1, 0, &mut owner, &mut handler);
+    remote.issue_resumption_ticket(key, Duration::from_secs(60)).unwrap();
+    remote.send_packet(&mut remote_socket, &remote_address, &mut owner, &mut handler);
+    conn.receive_packet(remote_handle.sent().remove(0).1, 0, &mut owner, &mut handler);
+
+    let ticket = conn.resumption_ticket().unwrap();
+    assert_eq!(ticket.session_key, None);
+
+    // Record a packet of the previous session
+    conn.send(MessageKind::Instant, b"Old".to_vec());
+    conn.send_packet(&mut socket, &address, &mut owner, &mut handler);
+    let recorded = socket_handle.sent().remove(0).1;
+
+    // Resume the session from a new connection
+    let (mut client, mut socket, mut socket_handle, mut owner, mut handler) = create_socket(Some(config));
+    let (mut server, mut server_socket, mut server_handle, _, _) = create_socket(Some(config));
+    client.resume_with_ticket(ticket);
+
+    client.send_packet(&mut socket, &address, &mut owner, &mut handler);
+    let packet = socket_handle.sent().remove(0).1;
+    let data = Connection::resumption_ticket_from_packet(&config, &packet).unwrap();
+
+    server.set_id(client.id());
+    server.set_accepted();
+    server.accept_resumption_ticket(ResumptionTicket::verify(key, client.id(), &data).unwrap());
+    server.receive_packet(packet, 0, &mut owner, &mut handler);
+    server.issue_resumption_ticket(key, Duration::from_secs(60)).unwrap();
+    server.send_packet(&mut server_socket, &remote_address, &mut owner, &mut handler);
+    client.receive_packet(server_handle.sent().remove(0).1, 0, &mut owner, &mut handler);
+    assert!(client.resumed());
+
+    // Both ends switch to a fresh key, which the previous session's packets
+    // fail to authenticate with
+    client.send(MessageKind::Instant, b"Foo".to_vec());
+    client.send_packet(&mut socket, &address, &mut owner, &mut handler);
+    server.receive_packet(socket_handle.sent().remove(0).1, 0, &mut owner, &mut handler);
+    assert_eq!(server.received().collect::<Vec<Vec<u8>>>(), vec![b"Foo".to_vec()]);
+
+    server.receive_packet(recorded, 0, &mut owner, &mut handler);
+    assert!(server.received().next().is_none());
+    assert_eq!(server.security_stats().authentication_failed, 1);
+
+}
+
+#[cfg(feature = "connect_token")]
+#[test]
+fn test_connect_token() {
+
+    let key = [5; 32];
+    let token = ConnectToken::new(
+        ConnectionID(1234), Duration::from_secs(30), b"user".to_vec()
+
+    ).seal(key);
+
+    let (mut conn, mut socket, mut socket_handle, mut owner, mut handler) = create_socket(None);
+    conn.present_connect_token(token.clone());
+    assert_eq!(conn.id(), ConnectionID(1234));
+
+    // The token is presented with every packet until the connection is
+    // established
+    let address = conn.peer_addr();
+    for _ in 0..2 {
+        conn.send_packet(&mut socket, &address, &mut owner, &mut handler);
+        let packet = socket_handle.sent().remove(0).1;
+        assert_eq!(Connection::connect_token_from_packet(&Config::default(), &packet), Some(token.clone()));
+        assert_eq!(
+            ConnectToken::verify(key, ConnectionID(1234), &token).map(|t| t.user_data),
+            Some(b"user".to_vec())
+        );
+    }
+
+    conn.receive_packet([
+        1, 2, 3, 4,
+        0, 0, 4, 210,
+        0, 0,
+        0, 0, 0, 0
+
+    ].to_vec(), 0, &mut owner, &mut handler);
+    assert!(conn.state() == ConnectionState::Connected);
+
+    conn.send_packet(&mut socket, &address, &mut owner, &mut handler);
+    let packet = socket_handle.sent().remove(0).1;
+    assert_eq!(Connection::connect_token_from_packet(&Config::default(), &packet), None);
+
+}
+
+#[cfg(feature = "authentication")]
+#[test]
+fn test_authentication() {
+
+    let config = Config {
+        authentication_key: Some([9; 32]),
+        .. Config::default()
+    };
+
+    let (mut conn, mut socket, mut socket_handle, mut owner, mut handler) = create_socket(Some(config));
+    assert_eq!(conn.max_message_size(), 1400 - 14 - 16 - 4);
+
+    let address = conn.peer_addr();
+    conn.send(MessageKind::Instant, b"Foo".to_vec());
+    conn.send_packet(&mut socket, &address, &mut owner, &mut handler);
+
+    // The packet is send in plain text along with its tag
+    let packet = socket_handle.sent().remove(0).1;
+    assert_eq!(packet.len(), 14 + 7 + 16);
+    assert_eq!(packet[14..21].to_vec(), [0, 0, 0, 3, 70, 111, 111].to_vec());
+
+    // Packets which were tampered with fail to authenticate
+    let mut tampered = packet.clone();
+    tampered[20] = 0;
+    assert_eq!(Connection::authenticate_packet(&config, tampered), None);
+
+    // Packets signed with a different key fail to authenticate
+    let other = Config {
+        authentication_key: Some([8; 32]),
+        .. Config::default()
+    };
+    assert_eq!(Connection::authenticate_packet(&other, packet.clone()), None);
+
+    // The tag is stripped from authentic packets
+    assert_eq!(Connection::authenticate_packet(&config, packet.clone()), Some(packet[..21].to_vec()));
 
 }
 
@@ -771,7 +3627,7 @@ fn test_packet_compression() {
             assert_eq!([
                 0, 0, 0, 3, 70, 111, 111, // Foo
                 0, 0, 0, 3, 66, 97, 114 // Bar
-            ].to_vec(), &data[..]);
+            ].to_vec(), data);
 
             // Return a empty compression result
             packet
@@ -879,7 +3735,7 @@ fn test_packet_compression_inflated() {
             // Expect actual packet data
             assert_eq!(data.len(), 0);
 
-            let mut buffer: Vec<u8> = iter::repeat(74).take(16).collect();
+            let mut buffer: Vec<u8> = vec![74; 16];
             packet.append(&mut buffer);
 
             // Return a compression result that is bigger than the input
@@ -0,0 +1,59 @@
+// Copyright (c) 2015-2016 Ivo Wetzel
+
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use super::super::{ConnectionID, ConnectionRng};
+
+#[test]
+fn test_sequence_deterministic() {
+
+    let mut a = ConnectionRng::new(ConnectionID(1));
+    let mut b = ConnectionRng::new(ConnectionID(1));
+    let mut c = ConnectionRng::new(ConnectionID(2));
+
+    let a: Vec<u64> = (0..8).map(|_| a.next_u64()).collect();
+    let b: Vec<u64> = (0..8).map(|_| b.next_u64()).collect();
+    let c: Vec<u64> = (0..8).map(|_| c.next_u64()).collect();
+
+    assert_eq!(a, b);
+    assert_ne!(a, c);
+
+}
+
+#[test]
+fn test_sequence_stable() {
+    let mut rng = ConnectionRng::new(ConnectionID(0));
+    assert_eq!(rng.next_u64(), 0x8e42_8a74_3c01_980d);
+}
+
+#[test]
+fn test_f32() {
+    let mut rng = ConnectionRng::new(ConnectionID(3));
+    for _ in 0..1000 {
+        let value = rng.next_f32();
+        assert!((0.0..1.0).contains(&value));
+    }
+}
+
+#[test]
+fn test_range() {
+
+    let mut rng = ConnectionRng::new(ConnectionID(4));
+    let mut seen = [false; 6];
+    for _ in 0..1000 {
+        let value = rng.range(10, 16);
+        assert!((10..16).contains(&value));
+        seen[(value - 10) as usize] = true;
+    }
+    assert_eq!(seen, [true; 6]);
+
+    // Empty ranges do not draw any values
+    let mut other = rng;
+    assert_eq!(rng.range(5, 5), 5);
+    assert_eq!(rng.range(7, 3), 7);
+    assert_eq!(rng.next_u32(), other.next_u32());
+
+}
@@ -0,0 +1,187 @@
+// Copyright (c) 2015-2016 Ivo Wetzel
+
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use super::mock::{create_connection, MockShutdownHandler, MockSocket};
+use super::super::{Config, ConnectionID, ConnectionMap, Handler, MessageKind, Server};
+use super::super::contrib::{ChatHandler, EchoHandler, RelayHandler};
+
+#[test]
+fn test_contrib_echo() {
+
+    let socket = MockSocket::from_address("127.0.0.1:0");
+    socket.receive(vec![
+        ("127.0.0.1:1234", [
+            1, 2, 3, 4,
+            0, 0, 0, 1,
+            0, 0,
+            0, 0, 0, 0,
+
+            0, 0, 0, 3, 70, 111, 111
+
+        ].to_vec())
+    ]);
+
+    let mut socket_handle = socket.handle();
+
+    let mut server = Server::new(Config::default());
+    let mut handler = MockShutdownHandler {
+        handler: EchoHandler::new(MessageKind::Reliable)
+    };
+    server.bind_to_socket(&mut handler, socket).unwrap();
+
+    socket_handle.assert_sent(vec![
+        ("127.0.0.1:1234", [
+            1, 2, 3, 4,
+            0, 0, 0, 0,
+            0, 0,
+            0, 0, 0, 0,
+
+            1, 0, 0, 3, 70, 111, 111
+
+        ].to_vec())
+    ]);
+
+}
+
+#[test]
+fn test_contrib_chat_channels() {
+
+    let mut connections = connections(3);
+    let mut server = Server::new(Config::default());
+    let mut handler = ChatHandler::new();
+
+    receive(&mut connections, 1, 0, b"JOIN lobby");
+    receive(&mut connections, 2, 0, b"JOIN lobby");
+    handler.tick_connections(&mut server, &mut connections);
+    assert_eq!(handler.channel_members("lobby"), vec![ConnectionID(1), ConnectionID(2)]);
+
+    // Only other members of the channel receive the text
+    receive(&mut connections, 1, 1, b"SAY lobby Hello World");
+    receive(&mut connections, 3, 0, b"SAY lobby Ignored");
+    handler.tick_connections(&mut server, &mut connections);
+    assert!(sent(&mut connections, 1).is_empty());
+    assert_eq!(sent(&mut connections, 2), vec![b"lobby 1 Hello World".to_vec()]);
+    assert!(sent(&mut connections, 3).is_empty());
+
+    // Members can leave the channel
+    receive(&mut connections, 1, 2, b"LEAVE lobby");
+    handler.tick_connections(&mut server, &mut connections);
+    assert_eq!(handler.channel_members("lobby"), vec![ConnectionID(2)]);
+
+    // Lost connections are removed from all channels
+    let mut conn = connections.remove(&ConnectionID(2)).unwrap();
+    handler.connection_lost(&mut server, &mut conn);
+    assert!(handler.channel_members("lobby").is_empty());
+
+}
+
+#[test]
+fn test_contrib_chat_groups() {
+
+    let mut connections = connections(3);
+    let mut server = Server::new(Config::default());
+    let mut handler = ChatHandler::new();
+
+    handler.add_to_group("team", ConnectionID(1));
+    handler.add_to_group("team", ConnectionID(3));
+    assert_eq!(handler.group_members("team"), vec![ConnectionID(1), ConnectionID(3)]);
+
+    handler.broadcast("team", b"Go".to_vec());
+    handler.tick_connections(&mut server, &mut connections);
+    assert_eq!(sent(&mut connections, 1), vec![b"Go".to_vec()]);
+    assert!(sent(&mut connections, 2).is_empty());
+    assert_eq!(sent(&mut connections, 3), vec![b"Go".to_vec()]);
+
+    handler.remove_from_group("team", ConnectionID(1));
+    assert_eq!(handler.group_members("team"), vec![ConnectionID(3)]);
+
+}
+
+#[test]
+fn test_contrib_relay() {
+
+    let mut connections = connections(3);
+    let mut server = Server::new(Config::default());
+    let mut handler = RelayHandler::new(MessageKind::Reliable);
+
+    // Relay to a single connection
+    receive(&mut connections, 1, 0, &[0, 0, 0, 2, 70, 111, 111]);
+    handler.tick_connections(&mut server, &mut connections);
+    assert!(sent(&mut connections, 1).is_empty());
+    assert_eq!(sent(&mut connections, 2), vec![vec![0, 0, 0, 1, 70, 111, 111]]);
+    assert!(sent(&mut connections, 3).is_empty());
+
+    // Relay to all other connections
+    receive(&mut connections, 3, 0, &[0, 0, 0, 0, 66, 97, 114]);
+    handler.tick_connections(&mut server, &mut connections);
+    assert_eq!(sent(&mut connections, 1), vec![vec![0, 0, 0, 3, 66, 97, 114]]);
+    assert_eq!(sent(&mut connections, 2), vec![vec![0, 0, 0, 3, 66, 97, 114]]);
+    assert!(sent(&mut connections, 3).is_empty());
+
+    // Drop messages for unknown connections and without a recipient
+    receive(&mut connections, 1, 1, &[0, 0, 0, 9, 66, 97, 122]);
+    receive(&mut connections, 2, 1, &[0, 0]);
+    handler.tick_connections(&mut server, &mut connections);
+    assert!(sent(&mut connections, 1).is_empty());
+    assert!(sent(&mut connections, 2).is_empty());
+    assert!(sent(&mut connections, 3).is_empty());
+
+}
+
+// Helpers --------------------------------------------------------------------
+fn connections(count: u32) -> ConnectionMap {
+    (1..count + 1).map(|id| {
+        let (mut conn, _, _) = create_connection(None);
+        conn.set_id(ConnectionID(id));
+        (ConnectionID(id), conn)
+
+    }).collect()
+}
+
+fn receive(
+    connections: &mut ConnectionMap,
+    id: u32, seq: u8, data: &[u8]
+) {
+    let (_, mut owner, mut handler) = create_connection(None);
+    let mut packet = vec![
+        1, 2, 3, 4,
+        (id >> 24) as u8, (id >> 16) as u8, (id >> 8) as u8, id as u8,
+        seq, 0,
+        0, 0, 0, 0,
+
+        0, 0, 0, data.len() as u8
+    ];
+    packet.extend_from_slice(data);
+
+    let conn = connections.get_mut(&ConnectionID(id)).unwrap();
+    conn.receive_packet(packet, 0, &mut owner, &mut handler);
+}
+
+fn sent(connections: &mut ConnectionMap, id: u32) -> Vec<Vec<u8>> {
+
+    let (_, mut owner, mut handler) = create_connection(None);
+    let mut socket = MockSocket::from_address("127.0.0.1:1234");
+    let mut socket_handle = socket.handle();
+
+    let conn = connections.get_mut(&ConnectionID(id)).unwrap();
+    let address = conn.peer_addr();
+    conn.send_packet(&mut socket, &address, &mut owner, &mut handler);
+
+    // Extract the messages from the packet
+    let packet = socket_handle.sent().remove(0).1;
+    let mut messages = Vec::new();
+    let mut index = 14;
+    while index + 4 <= packet.len() {
+        let size = (packet[index + 2] as usize) << 8 | packet[index + 3] as usize;
+        messages.push(packet[index + 4..index + 4 + size].to_vec());
+        index += 4 + size;
+    }
+
+    messages
+
+}
+
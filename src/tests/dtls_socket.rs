@@ -0,0 +1,151 @@
+// Copyright (c) 2015-2016 Ivo Wetzel
+
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+extern crate openssl;
+
+use std::net;
+use std::thread;
+use std::time::Duration;
+use self::openssl::asn1::Asn1Time;
+use self::openssl::ec::{EcGroup, EcKey};
+use self::openssl::hash::MessageDigest;
+use self::openssl::nid::Nid;
+use self::openssl::pkey::{PKey, Private};
+use self::openssl::ssl::{SslAcceptor, SslConnector, SslMethod};
+use self::openssl::x509::{X509, X509NameBuilder};
+use self::openssl::x509::extension::SubjectAlternativeName;
+use super::super::{DtlsSocket, Socket, UdpSocket};
+
+fn certificate() -> (PKey<Private>, X509) {
+
+    let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+    let key = PKey::from_ec_key(EcKey::generate(&group).unwrap()).unwrap();
+
+    let mut name = X509NameBuilder::new().unwrap();
+    name.append_entry_by_text("CN", "localhost").unwrap();
+    let name = name.build();
+
+    let mut cert = X509::builder().unwrap();
+    cert.set_version(2).unwrap();
+    cert.set_subject_name(&name).unwrap();
+    cert.set_issuer_name(&name).unwrap();
+    cert.set_pubkey(&key).unwrap();
+    cert.set_not_before(&Asn1Time::days_from_now(0).unwrap()).unwrap();
+    cert.set_not_after(&Asn1Time::days_from_now(1).unwrap()).unwrap();
+
+    let san = SubjectAlternativeName::new().dns("localhost").build(
+        &cert.x509v3_context(None, None)
+
+    ).unwrap();
+    cert.append_extension(san).unwrap();
+    cert.sign(&key, MessageDigest::sha256()).unwrap();
+
+    (key, cert.build())
+
+}
+
+fn create_sockets(domain: &str) -> (DtlsSocket<UdpSocket>, DtlsSocket<UdpSocket>) {
+
+    let (key, cert) = certificate();
+
+    let mut acceptor = SslAcceptor::mozilla_intermediate(SslMethod::dtls()).unwrap();
+    acceptor.set_private_key(&key).unwrap();
+    acceptor.set_certificate(&cert).unwrap();
+
+    let mut connector = SslConnector::builder(SslMethod::dtls()).unwrap();
+    connector.cert_store_mut().add_cert(cert).unwrap();
+
+    let server = DtlsSocket::server(
+        UdpSocket::new("127.0.0.1:0", 2048).unwrap(),
+        acceptor.build()
+    );
+
+    let client = DtlsSocket::client(
+        UdpSocket::new("127.0.0.1:0", 2048).unwrap(),
+        connector.build(),
+        domain
+    );
+
+    (server, client)
+
+}
+
+fn receive_all<S: Socket>(socket: &mut S) -> Vec<(net::SocketAddr, Vec<u8>)> {
+    let mut packets = Vec::new();
+    while let Ok(packet) = socket.try_recv() {
+        packets.push(packet);
+    }
+    packets
+}
+
+#[test]
+fn test_dtls_socket_exchange() {
+
+    let (mut server, mut client) = create_sockets("localhost");
+    let server_addr = server.local_addr().unwrap();
+    let client_addr = client.local_addr().unwrap();
+
+    // Packets are dropped until the handshake has completed
+    assert_eq!(client.send_to(b"Hello", server_addr).unwrap(), 0);
+    assert!(!client.established(server_addr));
+
+    for _ in 0..50 {
+        assert!(receive_all(&mut server).is_empty());
+        assert!(receive_all(&mut client).is_empty());
+        if client.established(server_addr) {
+            break;
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+
+    assert!(client.established(server_addr));
+
+    // Packets are exchanged in both directions
+    assert_eq!(client.send_to(b"Hello", server_addr).unwrap(), 5);
+    thread::sleep(Duration::from_millis(10));
+    assert_eq!(receive_all(&mut server), vec![(client_addr, b"Hello".to_vec())]);
+    assert!(server.established(client_addr));
+
+    assert_eq!(server.send_to(b"World", client_addr).unwrap(), 5);
+    thread::sleep(Duration::from_millis(10));
+    assert_eq!(receive_all(&mut client), vec![(server_addr, b"World".to_vec())]);
+
+}
+
+#[test]
+fn test_dtls_socket_verification() {
+
+    let (mut server, mut client) = create_sockets("example.com");
+    let server_addr = server.local_addr().unwrap();
+
+    // Certificates which do not match the domain are rejected
+    client.send_to(b"Hello", server_addr).unwrap();
+    for _ in 0..10 {
+        assert!(receive_all(&mut server).is_empty());
+        assert!(receive_all(&mut client).is_empty());
+        thread::sleep(Duration::from_millis(10));
+    }
+
+    assert!(!client.established(server_addr));
+    assert_eq!(client.send_to(b"Hello", server_addr).unwrap(), 0);
+
+}
+
+#[test]
+fn test_dtls_socket_unknown_address() {
+
+    let (mut server, mut client) = create_sockets("localhost");
+    let server_addr = server.local_addr().unwrap();
+    let client_addr = client.local_addr().unwrap();
+
+    // Servers never start sessions themselves
+    assert_eq!(server.send_to(b"Hello", client_addr).unwrap(), 0);
+    thread::sleep(Duration::from_millis(10));
+    assert!(receive_all(&mut client).is_empty());
+    assert!(!client.established(server_addr));
+
+}
@@ -0,0 +1,150 @@
+// Copyright (c) 2015-2016 Ivo Wetzel
+
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use std::io::ErrorKind;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use super::super::{ConnectionID, CryptoProvider, DefaultCryptoProvider, install_crypto_provider};
+#[cfg(feature = "key_exchange")]
+use super::super::KeyAgreement;
+use super::super::shared::crypto_provider::crypto_provider;
+use super::super::shared::encryption::{PacketCipher, ReplayWindow};
+
+struct CountingCryptoProvider {
+    sealed: AtomicUsize,
+    opened: AtomicUsize
+}
+
+impl CryptoProvider for CountingCryptoProvider {
+
+    fn seal(&self, key: &[u8; 32], nonce: &[u8; 12], aad: &[u8], data: &[u8]) -> Vec<u8> {
+        self.sealed.fetch_add(1, Ordering::SeqCst);
+        DefaultCryptoProvider.seal(key, nonce, aad, data)
+    }
+
+    fn open(&self, key: &[u8; 32], nonce: &[u8; 12], aad: &[u8], sealed: &[u8]) -> Option<Vec<u8>> {
+        self.opened.fetch_add(1, Ordering::SeqCst);
+        DefaultCryptoProvider.open(key, nonce, aad, sealed)
+    }
+
+    #[cfg(feature = "authentication")]
+    fn mac(&self, key: &[u8; 32], data: &[u8]) -> [u8; 32] {
+        DefaultCryptoProvider.mac(key, data)
+    }
+
+    #[cfg(feature = "authentication")]
+    fn signing_public_key(&self, key: &[u8; 32]) -> [u8; 32] {
+        DefaultCryptoProvider.signing_public_key(key)
+    }
+
+    #[cfg(feature = "authentication")]
+    fn sign(&self, key: &[u8; 32], data: &[u8]) -> [u8; 64] {
+        DefaultCryptoProvider.sign(key, data)
+    }
+
+    #[cfg(feature = "authentication")]
+    fn verify(&self, public_key: &[u8; 32], data: &[u8], signature: &[u8; 64]) -> bool {
+        DefaultCryptoProvider.verify(public_key, data, signature)
+    }
+
+    #[cfg(feature = "key_exchange")]
+    fn key_agreement(&self) -> Box<dyn KeyAgreement> {
+        DefaultCryptoProvider.key_agreement()
+    }
+
+}
+
+static COUNTING_PROVIDER: CountingCryptoProvider = CountingCryptoProvider {
+    sealed: AtomicUsize::new(0),
+    opened: AtomicUsize::new(0)
+};
+
+#[test]
+fn test_nonce_counter() {
+
+    let cipher = PacketCipher::new([1; 32]);
+    let packet = cipher.encrypt_counted(&[1, 2, 3], &[4, 5, 6], 0x0102_0304_0506_0708);
+    assert_eq!(PacketCipher::nonce_counter(&packet[3..]), 0x0102_0304_0506_0708);
+    assert_eq!(cipher.decrypt(&packet[..3], &packet[3..]), Some(vec![1, 2, 3, 4, 5, 6]));
+
+}
+
+#[test]
+fn test_derive() {
+
+    // Both ends of a connection derive the same key from its id
+    let cipher = PacketCipher::derive([1; 32], ConnectionID(1));
+    let packet = cipher.encrypt(&[1, 2, 3], &[4, 5, 6]);
+    assert!(PacketCipher::derive([1; 32], ConnectionID(1)).decrypt(&packet[..3], &packet[3..]).is_some());
+    assert!(PacketCipher::derive([1; 32], ConnectionID(2)).decrypt(&packet[..3], &packet[3..]).is_none());
+    assert!(PacketCipher::new([1; 32]).decrypt(&packet[..3], &packet[3..]).is_none());
+
+    // Rotated keys differ for each epoch
+    let packet = cipher.rotate(1).encrypt(&[1, 2, 3], &[4, 5, 6]);
+    assert!(cipher.decrypt(&packet[..3], &packet[3..]).is_none());
+    assert!(cipher.rotate(1).decrypt(&packet[..3], &packet[3..]).is_some());
+    assert!(cipher.rotate(2).decrypt(&packet[..3], &packet[3..]).is_none());
+
+}
+
+#[test]
+fn test_replay_window() {
+
+    let mut window = ReplayWindow::new();
+    assert!(!window.accept(0));
+
+    // Counters are accepted once
+    assert!(window.accept(1));
+    assert!(!window.accept(1));
+
+    // Reordered counters within the window are accepted once
+    assert!(window.accept(5));
+    assert!(window.accept(3));
+    assert!(!window.accept(3));
+    assert!(window.accept(2));
+    assert!(!window.accept(5));
+
+    // Counters which fell behind the window are rejected
+    assert!(window.accept(68));
+    assert!(window.accept(6));
+    assert!(!window.accept(4));
+
+    // Large jumps clear the window
+    assert!(window.accept(1000));
+    assert!(!window.accept(68));
+    assert!(window.accept(999));
+    assert!(!window.accept(1000));
+
+}
+
+#[test]
+fn test_crypto_provider() {
+
+    let cipher = PacketCipher::with_provider(&COUNTING_PROVIDER, [1; 32]);
+    let packet = cipher.encrypt(&[1, 2, 3], &[4, 5, 6]);
+    assert_eq!(COUNTING_PROVIDER.sealed.load(Ordering::SeqCst), 1);
+
+    // Packets are compatible with the default provider
+    assert_eq!(PacketCipher::new([1; 32]).decrypt(&packet[..3], &packet[3..]), Some(vec![1, 2, 3, 4, 5, 6]));
+    assert_eq!(cipher.decrypt(&packet[..3], &packet[3..]), Some(vec![1, 2, 3, 4, 5, 6]));
+    assert_eq!(COUNTING_PROVIDER.opened.load(Ordering::SeqCst), 1);
+
+    // Rotated ciphers keep the provider
+    let rotated = cipher.rotate(1);
+    rotated.encrypt(&[1], &[2]);
+    assert_eq!(COUNTING_PROVIDER.sealed.load(Ordering::SeqCst), 2);
+
+}
+
+#[test]
+fn test_install_crypto_provider_in_use() {
+
+    // Once a provider is in use it can no longer be replaced
+    crypto_provider();
+    let err = install_crypto_provider(Box::new(DefaultCryptoProvider)).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::AlreadyExists);
+
+}
@@ -0,0 +1,40 @@
+// Copyright (c) 2015-2016 Ivo Wetzel
+
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use std::hash::Hasher;
+use super::super::{ConnectionID, ConnectionMap, FnvBuildHasher, FnvHasher};
+use super::mock::create_connection;
+
+#[test]
+fn test_hash_stable() {
+
+    let hash = |data: &[u8]| {
+        let mut hasher = FnvHasher::default();
+        hasher.write(data);
+        hasher.finish()
+    };
+
+    assert_eq!(hash(b""), 0xcbf2_9ce4_8422_2325);
+    assert_eq!(hash(b"a"), 0xaf63_dc4c_8601_ec8c);
+    assert_eq!(hash(b"foobar"), 0x8594_4171_f739_67e8);
+
+}
+
+#[test]
+fn test_connection_map() {
+
+    let mut connections: ConnectionMap<FnvBuildHasher> = ConnectionMap::default();
+    for id in 0..64 {
+        let (conn, _, _) = create_connection(None);
+        connections.insert(ConnectionID(id), conn);
+    }
+
+    assert_eq!(connections.len(), 64);
+    assert!(connections.contains_key(&ConnectionID(63)));
+    assert!(!connections.contains_key(&ConnectionID(64)));
+
+}
@@ -0,0 +1,32 @@
+// Copyright (c) 2015-2016 Ivo Wetzel
+
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use std::net::SocketAddr;
+use super::super::ConnectionID;
+use super::super::shared::handshake_cookie::{self, COOKIE_BYTES};
+
+#[test]
+fn test_handshake_cookie() {
+
+    let secret = [5; 32];
+    let addr: SocketAddr = "127.0.0.1:1000".parse().unwrap();
+    let cookie = handshake_cookie::issue(&secret, addr, ConnectionID(1));
+    assert_eq!(cookie.len(), COOKIE_BYTES);
+    assert!(handshake_cookie::verify(&secret, addr, ConnectionID(1), &cookie));
+
+    // Cookies are bound to the secret, address and connection
+    assert!(!handshake_cookie::verify(&[6; 32], addr, ConnectionID(1), &cookie));
+    assert!(!handshake_cookie::verify(&secret, "127.0.0.1:1001".parse().unwrap(), ConnectionID(1), &cookie));
+    assert!(!handshake_cookie::verify(&secret, addr, ConnectionID(2), &cookie));
+    assert!(!handshake_cookie::verify(&secret, addr, ConnectionID(1), &cookie[1..]));
+
+    // Cookies with a modified issue time are rejected
+    let mut modified = cookie.clone();
+    modified[3] = modified[3].wrapping_sub(1);
+    assert!(!handshake_cookie::verify(&secret, addr, ConnectionID(1), &modified));
+
+}
@@ -5,6 +5,9 @@
 // <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
+use std::io::ErrorKind;
+use std::thread;
+use std::time::Duration;
 use super::super::Config;
 use super::super::shared::message_queue::{MessageKind, MessageQueue};
 
@@ -148,6 +151,141 @@ fn test_send_write_long() {
 
 }
 
+#[test]
+fn test_cancel() {
+
+    let mut q = MessageQueue::new(Config::default());
+
+    let instant = q.send(MessageKind::Instant, b"Foo".to_vec()).unwrap();
+    let reliable = q.send(MessageKind::Reliable, b"Foo".to_vec()).unwrap();
+    q.send(MessageKind::Ordered, b"Bar".to_vec()).unwrap();
+    let ordered = q.send(MessageKind::Ordered, b"Bar2".to_vec()).unwrap();
+    q.send(MessageKind::Ordered, b"Bar3".to_vec()).unwrap();
+
+    assert!(q.cancel(instant));
+    assert!(q.cancel(reliable));
+    assert!(q.cancel(ordered));
+
+    // Messages can only be cancelled once
+    assert!(!q.cancel(instant));
+
+    // Later ordered messages take over the order id of the cancelled one
+    let sent = q.send(MessageKind::Ordered, b"Bar4".to_vec()).unwrap();
+    let mut buffer = Vec::new();
+    let handles = q.send_packet(&mut buffer, 64);
+
+    assert_eq!(buffer, [
+        // Bar
+        2, 0, 0, 3, 66, 97, 114,
+        // Bar3
+        2, 1, 0, 4, 66, 97, 114, 51,
+        // Bar4
+        2, 2, 0, 4, 66, 97, 114, 52
+
+    ].to_vec());
+
+    // Messages which have already been send cannot be cancelled
+    assert!(!q.cancel(sent));
+
+    // Not even when they are going to be re-send
+    q.lost_packet(&buffer, &handles);
+    assert!(!q.cancel(sent));
+
+}
+
+#[test]
+fn test_send_after() {
+
+    let mut q = MessageQueue::new(Config::default());
+
+    q.send_after(MessageKind::Ordered, b"Later".to_vec(), Duration::from_millis(30)).unwrap();
+    let cancelled = q.send_after(
+        MessageKind::Reliable, b"Never".to_vec(), Duration::from_millis(10)
+
+    ).unwrap();
+    q.send(MessageKind::Ordered, b"Now".to_vec()).unwrap();
+
+    assert!(q.cancel(cancelled));
+
+    // Delayed messages are not send before their delay elapsed
+    let mut buffer = Vec::new();
+    q.send_packet(&mut buffer, 64);
+    assert_eq!(buffer, [
+        // Now
+        2, 0, 0, 3, 78, 111, 119
+
+    ].to_vec());
+
+    thread::sleep(Duration::from_millis(40));
+
+    // And are ordered by the time they became eligible for sending
+    let mut buffer = Vec::new();
+    q.send_packet(&mut buffer, 64);
+    assert_eq!(buffer, [
+        // Later
+        2, 1, 0, 5, 76, 97, 116, 101, 114
+
+    ].to_vec());
+
+}
+
+#[test]
+fn test_try_send_queue_limit() {
+
+    let mut q = MessageQueue::new(Config {
+        message_queue_limit: 2,
+        .. Config::default()
+    });
+
+    assert!(q.try_send(MessageKind::Reliable, b"Foo".to_vec()).is_ok());
+    assert!(q.try_send(MessageKind::Reliable, b"Bar".to_vec()).is_ok());
+
+    // Queue for reliable messages is full
+    assert_eq!(
+        q.try_send(MessageKind::Reliable, b"Baz".to_vec()).unwrap_err().kind(),
+        ErrorKind::WouldBlock
+    );
+
+    // Other queues are not affected
+    assert!(q.try_send(MessageKind::Instant, b"Foo".to_vec()).is_ok());
+    assert!(q.try_send(MessageKind::Ordered, b"Foo".to_vec()).is_ok());
+
+    // Invalid messages are always rejected
+    assert_eq!(
+        q.try_send(MessageKind::Invalid, b"Foo".to_vec()).unwrap_err().kind(),
+        ErrorKind::InvalidInput
+    );
+
+    // Sending a packet drains the queues again
+    let mut buffer = Vec::new();
+    q.send_packet(&mut buffer, 64);
+    assert!(q.try_send(MessageKind::Reliable, b"Baz".to_vec()).is_ok());
+
+}
+
+#[test]
+fn test_send_packet_handles() {
+
+    let mut q = MessageQueue::new(Config::default());
+
+    q.send_control(MessageKind::Reliable, b"Control".to_vec());
+    q.send(MessageKind::Instant, b"Foo".to_vec());
+    let reliable = q.send(MessageKind::Reliable, b"Bar".to_vec());
+    let ordered = q.send(MessageKind::Ordered, b"Baz".to_vec());
+
+    // Only reliable and ordered messages are tracked for their delivery
+    let mut buffer = Vec::new();
+    let handles = q.send_packet(&mut buffer, 64);
+    assert_eq!(handles, vec![None, None, reliable, ordered]);
+
+    // Handles are restored for re-send messages
+    q.lost_packet(&buffer, &handles);
+
+    let mut buffer = Vec::new();
+    assert_eq!(q.send_packet(&mut buffer, 64), vec![None, reliable, ordered]);
+
+}
+
 #[test]
 fn test_packet_lost_write() {
 
@@ -164,7 +302,7 @@ fn test_packet_lost_write() {
         2, 1, 0, 4, 66, 97, 114, 50,
         // Foo More
         1, 0, 0, 8, 70, 111, 111, 32, 77, 111, 114, 101
-    ]);
+    ], &[]);
 
     // Send some more messages
     q.send(MessageKind::Instant, b"Hello World".to_vec());
@@ -214,7 +352,7 @@ fn test_receive_read() {
         0, 0, 0, 11, 72, 101, 108, 108, 111, 32, 87, 111, 114, 108, 100
     ].to_vec();
 
-    q.receive_packet(&packet[..]);
+    q.receive_packet(&packet[..], None);
 
     assert_eq!(messages(&mut q), [
         b"Hello World".to_vec(),
@@ -265,7 +403,7 @@ fn test_receive_read_long() {
         101, 115, 116, 32, 108, 97, 98, 111, 114, 117, 109
     ].to_vec();
 
-    q.receive_packet(&packet[..]);
+    q.receive_packet(&packet[..], None);
 
     let msg = b"Lorem ipsum dolor sit amet, consectetur adipiscing elit, sed do \
         eiusmod tempor incididunt ut labore et dolore magna aliqua. \
@@ -290,7 +428,7 @@ fn test_receive_read_out_of_order() {
     // Receive one out of order(#1) "World" message
     q.receive_packet(&[
         2, 1, 0, 5, 87, 111, 114, 108, 100
-    ]);
+    ], None);
 
     // We expect no message yet
     assert!(messages(&mut q).is_empty());
@@ -298,7 +436,7 @@ fn test_receive_read_out_of_order() {
     // Receive one out of order(#3) "order!" message
     q.receive_packet(&[
         2, 3, 0, 6, 111, 114, 100, 101, 114, 33
-    ]);
+    ], None);
 
     // We still expect no message yet
     assert!(messages(&mut q).is_empty());
@@ -306,7 +444,7 @@ fn test_receive_read_out_of_order() {
     // Receive the actual first "Hello" message
     q.receive_packet(&[
         2, 0, 0, 5, 72, 101, 108, 108, 111
-    ]);
+    ], None);
 
     // We now expect both "Hello" and "World"
     assert_eq!(messages(&mut q), [b"Hello", b"World"]);
@@ -314,7 +452,7 @@ fn test_receive_read_out_of_order() {
     // Receive the order(#2) "out of" message
     q.receive_packet(&[
         2, 2, 0, 6, 111, 117, 116, 32, 111, 102
-    ]);
+    ], None);
 
     // We now expect both "out of" and "order!"
     assert_eq!(messages(&mut q), [b"out of", b"order!"]);
@@ -329,12 +467,33 @@ fn test_receive_empty() {
     q.receive_packet(&[
         0, 0, 0, 0,
         0, 0, 0, 0
-    ]);
+    ], None);
 
     assert_eq!(messages(&mut q), [b"", b""]);
 
 }
 
+#[test]
+fn test_write_padding() {
+
+    let mut packet = vec![0, 0, 0, 1, 42];
+    MessageQueue::write_padding(&mut packet, 12);
+    assert_eq!(packet, [0, 0, 0, 1, 42, 3, 0, 0, 3, 0, 0, 0]);
+
+    // Padding which is too short for a message header
+    let mut packet = vec![0, 0, 0, 1, 42];
+    MessageQueue::write_padding(&mut packet, 7);
+    assert_eq!(packet, [0, 0, 0, 1, 42, 0, 0]);
+
+    // Padding is ignored by the receiving end
+    let mut q = MessageQueue::new(Config::default());
+    let mut packet = vec![0, 0, 0, 3, 70, 111, 111];
+    MessageQueue::write_padding(&mut packet, 64);
+    q.receive_packet(&packet, None);
+    assert_eq!(messages(&mut q), [b"Foo"]);
+
+}
+
 #[test]
 fn test_receive_invalid() {
 
@@ -343,23 +502,23 @@ fn test_receive_invalid() {
     // Receive a message with a invalid kind
     q.receive_packet(&[
         255, 0, 0, 0
-    ]);
+    ], None);
 
     assert!(messages(&mut q).is_empty());
 
     // Receive a message with incomplete header
     q.receive_packet(&[
         0, 0
-    ]);
+    ], None);
 
     q.receive_packet(&[
         0, 0, 0
-    ]);
+    ], None);
 
     // Receive a message with incomplete data
     q.receive_packet(&[
         0, 0, 0, 15, 72, 101, 108, 108, 111 // 15 bytes but only 5 in buffer
-    ]);
+    ], None);
 
     assert_eq!(messages(&mut q), [b"Hello"]);
 
@@ -373,7 +532,7 @@ fn test_receive_ordered_decoding_wrap_around() {
 
         q.receive_packet(&[
             2 | ((i & 0x0F00) >> 4) as u8, (i as u8), 0, 2, (i >> 8) as u8, i as u8
-        ]);
+        ], None);
 
         assert_eq!(messages(&mut q), [[(i >> 8) as u8, i as u8]]);
 
@@ -382,7 +541,7 @@ fn test_receive_ordered_decoding_wrap_around() {
     // Should now expect order=0 again
     q.receive_packet(&[
         2, 0, 0, 2, 0, 0
-    ]);
+    ], None);
     assert_eq!(messages(&mut q), [[0, 0]]);
 
 }
@@ -428,7 +587,7 @@ fn test_reset() {
     // Check that nothing gets serialized
     let mut buffer = Vec::new();
     q.send_packet(&mut buffer, 64);
-    assert_eq!(buffer, [].to_vec());
+    assert_eq!(buffer, Vec::<u8>::new());
 
     // Check that local_order_id has been reset
     q.send(MessageKind::Ordered, b"".to_vec());
@@ -449,7 +608,7 @@ fn test_in_order_duplicates() {
         2, 2, 0, 1, 57, // Expected #3
         2, 2, 0, 1, 58,
         2, 3, 0, 1, 59  // Expected #4
-    ]);
+    ], None);
 
     assert_eq!(messages(&mut q), [[53], [54], [57], [59]]);
 
@@ -468,7 +627,7 @@ fn test_out_of_order_duplicates() {
         2, 1, 0, 1, 57, // Expected #2
         2, 4, 0, 1, 58, // Expected #5
         2, 3, 0, 1, 59  // Expected #4
-    ]);
+    ], None);
 
     assert_eq!(messages(&mut q), [[53], [57], [54], [59], [58]]);
 
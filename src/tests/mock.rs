@@ -9,20 +9,23 @@
 use std::cmp;
 use std::net;
 use std::thread;
-use std::io::Error;
+use std::io::{Error, ErrorKind};
 use std::time::{Duration, Instant};
-use std::net::ToSocketAddrs;
-use std::collections::HashMap;
+use std::net::{IpAddr, ToSocketAddrs};
 
 use std::sync::{Arc, Mutex};
 use std::sync::mpsc::{channel, Sender, Receiver, TryRecvError};
 
 use super::super::{
-    BinaryRateLimiter, Config, Connection, ConnectionID,
-    Handler, MessageKind, Socket,
-    Server, Client
+    Admission, BanTarget, BinaryRateLimiter, Config, Connection, ConnectionFailure,
+    ConnectionID, ConnectionMap, Handler, IdGenerator, MessageKind,
+    PacketDropReason, Socket, Server, ServerStateDump, Stats, Client
 };
 
+#[cfg(feature = "authentication")]
+use super::super::IdCollisionPolicy;
+
+/// Asserts that `$value` lies within `$difference` of `$target`.
 #[macro_export]
 macro_rules! assert_epsilon {
     ($value:expr, $target:expr, $difference:expr) => {
@@ -30,7 +33,7 @@ macro_rules! assert_epsilon {
             let min = $target - $difference;
             let max = $target + $difference;
             if $value < min || $value > max {
-                panic!(format!("Value {} not in range {} - {}", $value, min, max));
+                panic!("Value {} not in range {} - {}", $value, min, max);
             }
         }
     }
@@ -43,8 +46,8 @@ impl Handler<MockOwner> for MockOwnerHandler {}
 
 
 // Mock Packet Data Abstraction -----------------------------------------------
-#[derive(Clone, Eq, PartialEq)]
-pub struct MockPacket(net::SocketAddr, Vec<u8>);
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct MockPacket(pub net::SocketAddr, pub Vec<u8>);
 
 impl Ord for MockPacket {
 
@@ -81,9 +84,9 @@ impl MockSocket {
     ) -> Self {
         MockSocket {
             addr: to_socket_addr(address),
-            incoming: incoming,
-            incoming_sender: incoming_sender,
-            outgoing: outgoing,
+            incoming,
+            incoming_sender,
+            outgoing,
             sent_packets: Arc::new(Mutex::new(Vec::new())),
             received_packets: Arc::new(Mutex::new(Vec::new()))
         }
@@ -106,6 +109,7 @@ impl MockSocket {
 
     pub fn handle(&self) -> MockSocketHandle {
         MockSocketHandle {
+            incoming_sender: self.incoming_sender.clone(),
             sent_index: 0,
             sent_packets: self.sent_packets.clone(),
             //received_index: 0,
@@ -152,7 +156,47 @@ impl Socket for MockSocket {
 
 }
 
+pub struct MockUnreachableSocket {
+    pub addr: net::SocketAddr,
+    pub error: Option<ErrorKind>,
+    pub sent: u32
+}
+
+impl Socket for MockUnreachableSocket {
+
+    fn try_recv(&mut self) -> Result<(net::SocketAddr, Vec<u8>), TryRecvError> {
+        Err(TryRecvError::Empty)
+    }
+
+    fn send_to(&mut self, data: &[u8], _: net::SocketAddr) -> Result<usize, Error> {
+        if let Some(kind) = self.error {
+            Err(Error::new(kind, ""))
+
+        } else {
+            self.sent += 1;
+            Ok(data.len())
+        }
+    }
+
+    fn local_addr(&self) -> Result<net::SocketAddr, Error> {
+        Ok(self.addr)
+    }
+
+    fn rebind(&mut self, addr: net::SocketAddr) -> Result<net::SocketAddr, Error> {
+        self.addr = if addr.port() == 0 {
+            net::SocketAddr::new(addr.ip(), self.addr.port() + 1)
+
+        } else {
+            addr
+        };
+        self.error = None;
+        Ok(self.addr)
+    }
+
+}
+
 pub struct MockSocketHandle {
+    incoming_sender: Option<Sender<MockPacket>>,
     sent_index: usize,
     sent_packets: Arc<Mutex<Vec<MockPacket>>>,
     //received_index: usize,
@@ -161,13 +205,18 @@ pub struct MockSocketHandle {
 
 impl MockSocketHandle {
 
+    pub fn receive<T: ToSocketAddrs>(&self, packets: Vec<(T, Vec<u8>)>)  {
+        if let Some(ref incoming_sender) = self.incoming_sender {
+            for (addr, data) in packets.into_iter() {
+                incoming_sender.send(MockPacket(to_socket_addr(addr), data)).ok();
+            }
+        }
+    }
+
     pub fn sent(&mut self) -> Vec<MockPacket> {
 
         let sent_packets = self.sent_packets.lock().unwrap();
-        let packets: Vec<MockPacket> = sent_packets.iter().skip(self.sent_index).map(|packet| {
-            packet.clone()
-
-        }).collect();
+        let packets: Vec<MockPacket> = sent_packets.iter().skip(self.sent_index).cloned().collect();
 
         self.sent_index += packets.len();
         packets
@@ -190,7 +239,7 @@ impl MockSocketHandle {
     pub fn assert_sent_none(&mut self) {
         let sent = self.sent();
         if !sent.is_empty() {
-            panic!(format!("Expected no more packet(s) to be sent, but {} additional ones.", sent.len()));
+            panic!("Expected no more packet(s) to be sent, but {} additional ones.", sent.len());
         }
     }
 
@@ -221,41 +270,41 @@ impl MockSocketHandle {
         let sent_total = sent.len();
         let min_total = cmp::min(expected_total, sent_total);
 
-        for (i, (expected, sent)) in expected.into_iter().zip(sent.into_iter()).enumerate() {
+        for (i, (expected, sent)) in expected.into_iter().zip(sent).enumerate() {
 
             // Compare addresses
             let sent_addr = to_socket_addr(sent.0);
             let expected_addr = to_socket_addr(expected.0);
             if sent_addr != expected_addr {
-                panic!(format!(
+                panic!(
                     "{}) Packet destination address ({:?}) does not match expected one: {:?}.",
                     i, sent_addr, expected_addr
-                ));
+                );
             }
 
             // Verify packet data. We specifically ignore the connection ID here
             // since it is random and cannot be accessed by the mocks
             if sent.1[0..4] != expected.1[0..4] {
-                panic!(format!(
+                panic!(
                     "{}) Packet protocol header of sent packet ({:?}) does not match expected one: {:?}.",
                     i, sent.1[0..4].to_vec(), expected.1[0..4].to_vec()
-                ));
+                );
             }
 
             if sent.1[8..] != expected.1[8..] {
-                panic!(format!(
+                panic!(
                     "{}) Packet body data of sent packet ({:?}) does not match expected one: {:?}.",
                     i, sent.1[8..].to_vec(), expected.1[8..].to_vec()
-                ));
+                );
             }
 
         }
 
         if expected_total > min_total {
-            panic!(format!("Expected at least {} packet(s) to be sent, but only got a total of {}.", expected_total, sent_total));
+            panic!("Expected at least {} packet(s) to be sent, but only got a total of {}.", expected_total, sent_total);
 
         } else if sent_total > min_total {
-            panic!(format!("Expected no more than {} packet(s) to be sent, but got a total of {}.", expected_total, sent_total));
+            panic!("Expected no more than {} packet(s) to be sent, but got a total of {}.", expected_total, sent_total);
         }
 
     }
@@ -279,12 +328,12 @@ impl MockTickRecorder {
 
     pub fn new(max_ticks: u32, load_ticks: u32, send_rate: u32, expected_time: u32) -> MockTickRecorder {
         MockTickRecorder {
-            max_ticks: max_ticks,
-            load_ticks: load_ticks,
+            max_ticks,
+            load_ticks,
             tick_delay: 1000 / send_rate,
             tick_count: 0,
             last_tick_time: Instant::now(),
-            expected_time: expected_time,
+            expected_time,
             last_sleep_duration: 0,
             accumulated: 0
         }
@@ -298,7 +347,7 @@ impl MockTickRecorder {
 
         if self.tick_count > 1 {
             let elapsed = self.last_tick_time.elapsed();
-            let delay = elapsed.as_secs() as i32 * 1000 + elapsed.subsec_nanos() as i32 / 1000_000 -
+            let delay = elapsed.as_secs() as i32 * 1000 + elapsed.subsec_nanos() as i32 / 1_000_000 -
                 (self.last_sleep_duration as i32 - self.tick_delay as i32 * 2);
             self.accumulated += delay;
         }
@@ -319,7 +368,7 @@ impl MockTickRecorder {
             let before = Instant::now();
             thread::sleep(Duration::from_millis((self.tick_delay * 2) as u64));
             let elapsed = before.elapsed();
-            self.last_sleep_duration = elapsed.as_secs() as u32 * 1000 + elapsed.subsec_nanos() / 1000_000;
+            self.last_sleep_duration = elapsed.as_secs() as u32 * 1000 + elapsed.subsec_millis();
             false
 
         } else {
@@ -349,6 +398,59 @@ impl Handler<Client> for MockTickDelayClientHandler {
 
 }
 
+pub struct MockIdGenerator;
+
+impl IdGenerator for MockIdGenerator {
+    fn generate(&mut self) -> ConnectionID {
+        ConnectionID(0x01020304)
+    }
+}
+
+pub struct MockEvenIdGenerator;
+
+impl IdGenerator for MockEvenIdGenerator {
+
+    fn generate(&mut self) -> ConnectionID {
+        ConnectionID(2)
+    }
+
+    fn validate(&mut self, id: ConnectionID) -> bool {
+        id.0.is_multiple_of(2)
+    }
+
+}
+
+pub struct MockIdValidationServerHandler {
+    pub connections: Vec<ConnectionID>,
+    pub failed: Vec<(net::SocketAddr, ConnectionFailure)>
+}
+
+impl Handler<Server> for MockIdValidationServerHandler {
+
+    fn id_generator(&self, _: &Config) -> Box<dyn IdGenerator> {
+        Box::new(MockEvenIdGenerator)
+    }
+
+    fn connection(&mut self, _: &mut Server, conn: &mut Connection) {
+        self.connections.push(conn.id());
+    }
+
+    fn connection_attempt_failed(
+        &mut self, _: &mut Server, addr: net::SocketAddr, failure: ConnectionFailure
+    ) {
+        self.failed.push((addr, failure));
+    }
+
+}
+
+pub struct MockIdGeneratorClientHandler;
+
+impl Handler<Client> for MockIdGeneratorClientHandler {
+    fn id_generator(&self, _: &Config) -> Box<dyn IdGenerator> {
+        Box::new(MockIdGenerator)
+    }
+}
+
 pub struct MockSyncClientHandler {
     pub connect_count: u32,
     pub tick_count: u32,
@@ -371,6 +473,561 @@ impl Handler<Client> for MockSyncClientHandler {
 
 }
 
+pub struct MockAddressSelectedClientHandler {
+    pub connections: u32,
+    pub failures: u32,
+    pub selected: Vec<net::SocketAddr>
+}
+
+impl Handler<Client> for MockAddressSelectedClientHandler {
+
+    fn connection(&mut self, _: &mut Client, _: &mut Connection) {
+        self.connections += 1;
+    }
+
+    fn connection_address_selected(&mut self, _: &mut Client, _: &mut Connection, addr: net::SocketAddr) {
+        self.selected.push(addr);
+    }
+
+    fn connection_failed(&mut self, _: &mut Client, _: &mut Connection) {
+        self.failures += 1;
+    }
+
+}
+
+pub struct MockRebindClientHandler {
+    pub rebound: Vec<(net::SocketAddr, net::SocketAddr)>
+}
+
+impl Handler<Client> for MockRebindClientHandler {
+    fn connection_rebound(&mut self, _: &mut Client, conn: &mut Connection, previous: net::SocketAddr) {
+        self.rebound.push((previous, conn.local_addr()));
+    }
+}
+
+pub struct MockMultiClientHandler {
+    pub connections: Vec<ConnectionID>,
+    pub ticks: Vec<ConnectionID>,
+    pub messages: Vec<(ConnectionID, Vec<u8>)>
+}
+
+impl Handler<Client> for MockMultiClientHandler {
+
+    fn connection(&mut self, _: &mut Client, conn: &mut Connection) {
+        self.connections.push(conn.id());
+    }
+
+    fn tick_connection(&mut self, _: &mut Client, conn: &mut Connection) {
+        let id = conn.id();
+        self.ticks.push(id);
+        for msg in conn.received() {
+            self.messages.push((id, msg));
+        }
+    }
+
+}
+
+#[cfg(feature = "connect_token")]
+pub struct MockConnectTokenServerHandler {
+    pub connections: Vec<(ConnectionID, Vec<u8>)>
+}
+
+#[cfg(feature = "connect_token")]
+impl Handler<Server> for MockConnectTokenServerHandler {
+
+    fn connection(&mut self, _: &mut Server, conn: &mut Connection) {
+        self.connections.push((
+            conn.id(),
+            conn.connect_token().map_or_else(Vec::new, |t| t.user_data.clone())
+        ));
+    }
+
+    fn tick_connections(
+        &mut self, server: &mut Server,
+        _: &mut ConnectionMap
+    ) {
+        server.shutdown().unwrap();
+    }
+
+}
+
+#[cfg(feature = "authentication")]
+pub struct MockHandshakeCookieServerHandler {
+    pub socket_handle: MockSocketHandle,
+    pub cookies: Vec<MockPacket>,
+    pub connections: Vec<ConnectionID>
+}
+
+#[cfg(feature = "authentication")]
+impl Handler<Server> for MockHandshakeCookieServerHandler {
+
+    fn connection(&mut self, _: &mut Server, conn: &mut Connection) {
+        self.connections.push(conn.id());
+    }
+
+    fn tick_connections(
+        &mut self, server: &mut Server,
+        _: &mut ConnectionMap
+    ) {
+        if self.cookies.is_empty() {
+
+            // Echo all issued cookies, tampering with the one for the
+            // second connection
+            self.cookies = self.socket_handle.sent();
+            for cookie in &self.cookies {
+                let mut echo = vec![
+                    1, 2, 3, 4,
+                    0, 0, 0, cookie.1[11],
+                    0, 0,
+                    0, 0, 0, 0,
+                    8, 0, 0, cookie.1.len() as u8 - 11, 14
+                ];
+                echo.extend_from_slice(&cookie.1[12..]);
+
+                if cookie.1[11] == 2 {
+                    let last = echo.len() - 1;
+                    echo[last] ^= 1;
+                }
+
+                self.socket_handle.receive(vec![(cookie.0, echo)]);
+            }
+
+        } else {
+            server.shutdown().unwrap();
+        }
+    }
+
+}
+
+pub struct MockAddressConflictServerHandler {
+    pub conflicts: Vec<net::SocketAddr>,
+    pub connection_calls: u32,
+    pub closed_calls: u32
+}
+
+impl Handler<Server> for MockAddressConflictServerHandler {
+
+    fn connection(&mut self, _: &mut Server, _: &mut Connection) {
+        self.connection_calls += 1;
+    }
+
+    fn connection_address_conflict(
+        &mut self, _: &mut Server, _: &mut Connection, addr: net::SocketAddr
+    ) {
+        self.conflicts.push(addr);
+    }
+
+    fn connection_closed(&mut self, _: &mut Server, _: &mut Connection, _: bool) {
+        self.closed_calls += 1;
+    }
+
+    fn tick_connections(
+        &mut self, server: &mut Server,
+        _: &mut ConnectionMap
+    ) {
+        server.shutdown().unwrap();
+    }
+
+}
+
+pub struct MockBanServerHandler {
+    pub connections: Vec<ConnectionID>,
+    pub banned: Vec<ConnectionID>,
+    pub closed: Vec<ConnectionID>,
+    pub ticks: u32
+}
+
+impl Handler<Server> for MockBanServerHandler {
+
+    fn connection(&mut self, _: &mut Server, conn: &mut Connection) {
+        self.connections.push(conn.id());
+    }
+
+    fn connection_banned(&mut self, _: &mut Server, conn: &mut Connection) {
+        self.banned.push(conn.id());
+    }
+
+    fn connection_closed(&mut self, _: &mut Server, conn: &mut Connection, _: bool) {
+        self.closed.push(conn.id());
+    }
+
+    fn tick_connections(
+        &mut self, server: &mut Server,
+        _: &mut ConnectionMap
+    ) {
+        self.ticks += 1;
+        if self.ticks == 1 {
+            server.ban_address("127.0.0.2".parse().unwrap(), None);
+            server.ban_connection(ConnectionID(3), Some(Duration::from_secs(60)));
+
+        } else if self.closed.len() == 2 {
+            server.shutdown().unwrap();
+        }
+    }
+
+}
+
+pub struct MockStateDumpServerHandler {
+    pub dump: Option<ServerStateDump>
+}
+
+impl Handler<Server> for MockStateDumpServerHandler {
+
+    fn tick_connections(
+        &mut self, server: &mut Server,
+        connections: &mut ConnectionMap
+    ) {
+        self.dump = Some(server.dump_state(connections));
+        server.shutdown().unwrap();
+    }
+
+}
+
+pub struct MockConnectionLimitServerHandler {
+    pub connections: Vec<ConnectionID>,
+    pub exceeded: Vec<(net::SocketAddr, ConnectionID)>,
+    pub full: Vec<(net::SocketAddr, ConnectionID)>
+}
+
+impl Handler<Server> for MockConnectionLimitServerHandler {
+
+    fn connection(&mut self, _: &mut Server, conn: &mut Connection) {
+        self.connections.push(conn.id());
+    }
+
+    fn connection_limit_exceeded(
+        &mut self, _: &mut Server, addr: net::SocketAddr, id: ConnectionID
+    ) {
+        self.exceeded.push((addr, id));
+    }
+
+    fn server_full(
+        &mut self, _: &mut Server, addr: net::SocketAddr, id: ConnectionID
+    ) {
+        self.full.push((addr, id));
+    }
+
+    fn tick_connections(
+        &mut self, server: &mut Server,
+        _: &mut ConnectionMap
+    ) {
+        server.shutdown().unwrap();
+    }
+
+}
+
+pub struct MockFloodServerHandler {
+    pub connections: Vec<ConnectionID>,
+    pub throttled: Vec<(IpAddr, u32)>
+}
+
+impl Handler<Server> for MockFloodServerHandler {
+
+    fn connection(&mut self, _: &mut Server, conn: &mut Connection) {
+        self.connections.push(conn.id());
+    }
+
+    fn source_throttled(&mut self, _: &mut Server, ip: IpAddr, dropped: u32) {
+        self.throttled.push((ip, dropped));
+    }
+
+    fn tick_connections(
+        &mut self, server: &mut Server,
+        _: &mut ConnectionMap
+    ) {
+        server.shutdown().unwrap();
+    }
+
+}
+
+pub struct MockPacketDropServerHandler {
+    pub connections: Vec<ConnectionID>,
+    pub dropped: Vec<(net::SocketAddr, PacketDropReason)>
+}
+
+impl Handler<Server> for MockPacketDropServerHandler {
+
+    fn connection(&mut self, _: &mut Server, conn: &mut Connection) {
+        self.connections.push(conn.id());
+    }
+
+    fn packet_dropped(&mut self, _: &mut Server, addr: net::SocketAddr, reason: PacketDropReason) {
+        self.dropped.push((addr, reason));
+    }
+
+    fn tick_connections(
+        &mut self, server: &mut Server,
+        _: &mut ConnectionMap
+    ) {
+        server.shutdown().unwrap();
+    }
+
+}
+
+pub struct MockAdmissionServerHandler {
+    pub connections: Vec<ConnectionID>,
+    pub requests: Vec<(net::SocketAddr, ConnectionID, usize)>
+}
+
+impl Handler<Server> for MockAdmissionServerHandler {
+
+    fn connection_request(
+        &mut self, _: &mut Server, addr: net::SocketAddr, id: ConnectionID, packet: &[u8]
+
+    ) -> Admission {
+        self.requests.push((addr, id, packet.len()));
+        if addr.ip() == IpAddr::V4(net::Ipv4Addr::new(127, 0, 0, 2)) {
+            Admission::Reject
+
+        } else {
+            Admission::Accept
+        }
+    }
+
+    fn connection(&mut self, _: &mut Server, conn: &mut Connection) {
+        self.connections.push(conn.id());
+    }
+
+    fn tick_connections(
+        &mut self, server: &mut Server,
+        _: &mut ConnectionMap
+    ) {
+        server.shutdown().unwrap();
+    }
+
+}
+
+pub struct MockPendingServerHandler {
+    pub connections: Vec<ConnectionID>,
+    pub failed: Vec<ConnectionID>,
+    pub dropped: Vec<(net::SocketAddr, PacketDropReason)>
+}
+
+impl Handler<Server> for MockPendingServerHandler {
+
+    fn connection(&mut self, _: &mut Server, conn: &mut Connection) {
+        self.connections.push(conn.id());
+    }
+
+    fn connection_failed(&mut self, _: &mut Server, conn: &mut Connection) {
+        self.failed.push(conn.id());
+    }
+
+    fn packet_dropped(&mut self, _: &mut Server, addr: net::SocketAddr, reason: PacketDropReason) {
+        self.dropped.push((addr, reason));
+    }
+
+}
+
+pub struct MockAttemptFailedServerHandler {
+    pub failed: Vec<(net::SocketAddr, ConnectionFailure)>,
+    pub stats: Vec<Stats>
+}
+
+impl Handler<Server> for MockAttemptFailedServerHandler {
+
+    fn connection_request(
+        &mut self, _: &mut Server, _: net::SocketAddr, id: ConnectionID, _: &[u8]
+
+    ) -> Admission {
+        if id == ConnectionID(3) {
+            Admission::Reject
+
+        } else {
+            Admission::Accept
+        }
+    }
+
+    fn connection_attempt_failed(
+        &mut self, _: &mut Server, addr: net::SocketAddr, failure: ConnectionFailure
+    ) {
+        self.failed.push((addr, failure));
+    }
+
+    fn stats_tick(&mut self, _: &mut Server, stats: Stats) {
+        self.stats.push(stats);
+    }
+
+}
+
+pub struct MockJoinNotifyServerHandler;
+
+impl Handler<Server> for MockJoinNotifyServerHandler {
+
+    fn connection(&mut self, server: &mut Server, conn: &mut Connection) {
+        if conn.id() != ConnectionID(1) {
+            server.send(ConnectionID(1), MessageKind::Instant, vec![conn.id().0 as u8]);
+        }
+    }
+
+}
+
+pub struct MockBanEventServerHandler {
+    pub applied: Vec<(BanTarget, Option<Duration>)>,
+    pub lifted: Vec<BanTarget>
+}
+
+impl Handler<Server> for MockBanEventServerHandler {
+
+    fn ban_applied(&mut self, _: &mut Server, target: BanTarget, duration: Option<Duration>) {
+        self.applied.push((target, duration));
+    }
+
+    fn ban_lifted(&mut self, _: &mut Server, target: BanTarget) {
+        self.lifted.push(target);
+    }
+
+}
+
+#[cfg(feature = "authentication")]
+pub struct MockIdCollisionServerHandler {
+    pub collisions: Vec<(ConnectionID, net::SocketAddr)>
+}
+
+#[cfg(feature = "authentication")]
+impl Handler<Server> for MockIdCollisionServerHandler {
+
+    fn connection_id_collision(
+        &mut self, _: &mut Server, conn: &mut Connection, addr: net::SocketAddr
+
+    ) -> IdCollisionPolicy {
+        self.collisions.push((conn.id(), addr));
+        if conn.id() == ConnectionID(1) {
+            IdCollisionPolicy::CloseBoth
+
+        } else {
+            IdCollisionPolicy::RejectNew
+        }
+    }
+
+}
+
+pub struct MockServerInfoHandler {
+    pub queries: u32
+}
+
+impl Handler<Server> for MockServerInfoHandler {
+
+    fn server_info(&mut self, _: &mut Server) -> Option<Vec<u8>> {
+        self.queries += 1;
+        Some(b"Info".to_vec())
+    }
+
+    fn tick_connections(
+        &mut self, server: &mut Server,
+        _: &mut ConnectionMap
+    ) {
+        server.shutdown().unwrap();
+    }
+
+}
+
+pub struct MockControlledServerHandler {
+    pub connections: Vec<ConnectionID>,
+    pub kicked: Vec<(ConnectionID, u8)>
+}
+
+impl Handler<Server> for MockControlledServerHandler {
+
+    fn connection(&mut self, _: &mut Server, conn: &mut Connection) {
+        self.connections.push(conn.id());
+    }
+
+    fn connection_kicked(&mut self, _: &mut Server, conn: &mut Connection, reason: u8) {
+        self.kicked.push((conn.id(), reason));
+    }
+
+}
+
+pub struct MockKeepAliveServerHandler {
+    pub keep_alive: Vec<(ConnectionID, u32)>
+}
+
+impl Handler<Server> for MockKeepAliveServerHandler {
+
+    fn connection_keep_alive(&mut self, _: &mut Server, conn: &mut Connection, duration: u32) {
+        self.keep_alive.push((conn.id(), duration));
+    }
+
+}
+
+pub struct MockConnectionCountServerHandler {
+    pub connections: Vec<ConnectionID>
+}
+
+impl Handler<Server> for MockConnectionCountServerHandler {
+
+    fn connection(&mut self, _: &mut Server, conn: &mut Connection) {
+        self.connections.push(conn.id());
+    }
+
+    fn tick_connections(
+        &mut self, server: &mut Server,
+        _: &mut ConnectionMap
+    ) {
+        server.shutdown().unwrap();
+    }
+
+}
+
+pub struct MockShutdownHandler<H> {
+    pub handler: H
+}
+
+impl<H: Handler<Server>> Handler<Server> for MockShutdownHandler<H> {
+
+    fn tick_connections(
+        &mut self, server: &mut Server,
+        connections: &mut ConnectionMap
+    ) {
+        self.handler.tick_connections(server, connections);
+        server.shutdown().unwrap();
+    }
+
+}
+
+pub struct MockTickPhaseHandler {
+    pub phases: Vec<&'static str>
+}
+
+impl Handler<Client> for MockTickPhaseHandler {
+
+    fn after_receive(&mut self, _: &mut Client) {
+        self.phases.push("after_receive");
+    }
+
+    fn tick_connection(&mut self, _: &mut Client, _: &mut Connection) {
+        self.phases.push("tick");
+    }
+
+    fn before_send(&mut self, _: &mut Client) {
+        self.phases.push("before_send");
+    }
+
+}
+
+impl Handler<Server> for MockTickPhaseHandler {
+
+    fn after_receive(&mut self, _: &mut Server) {
+        self.phases.push("after_receive");
+    }
+
+    fn tick_connections(
+        &mut self, _: &mut Server,
+        _: &mut ConnectionMap
+    ) {
+        self.phases.push("tick");
+    }
+
+    fn before_send(&mut self, server: &mut Server) {
+        self.phases.push("before_send");
+        if self.phases.len() == 6 {
+            server.shutdown().unwrap();
+        }
+    }
+
+}
+
 pub struct MockClientStatsHandler {
     pub tick_count: u32,
 }
@@ -409,7 +1066,7 @@ impl Handler<Server> for MockTickDelayServerHandler {
 
     fn tick_connections(
         &mut self, server: &mut Server,
-        _: &mut HashMap<ConnectionID, Connection>
+        _: &mut ConnectionMap
     ) {
         if self.tick_recorder.tick() {
             server.shutdown().unwrap();
@@ -430,13 +1087,13 @@ impl Handler<Server> for MockConnectionServerHandler {
 
     fn tick_connections(
         &mut self, server: &mut Server,
-        connections: &mut HashMap<ConnectionID, Connection>
+        connections: &mut ConnectionMap
     ) {
 
         // expect 1 message from each connection
         for (id, conn) in connections.iter_mut() {
             match *id {
-                ConnectionID(1...2) => check_server_messages(conn),
+                ConnectionID(1..=2) => check_server_messages(conn),
                 _ => unreachable!("Invalid connection ID")
             }
         }
@@ -462,7 +1119,7 @@ impl Handler<Server> for MockConnectionRemapServerHandler {
 
     fn tick_connections(
         &mut self, server: &mut Server,
-        connections: &mut HashMap<ConnectionID, Connection>
+        connections: &mut ConnectionMap
     ) {
 
         // expect 1 message from the connection
@@ -480,6 +1137,52 @@ impl Handler<Server> for MockConnectionRemapServerHandler {
 
 }
 
+pub struct MockHostMigrationServerHandler {
+    pub tick_count: u32
+}
+
+impl Handler<Server> for MockHostMigrationServerHandler {
+
+    fn session_state(&mut self, _: &mut Server) -> Option<Vec<u8>> {
+        Some(b"State".to_vec())
+    }
+
+    fn tick_connections(
+        &mut self, server: &mut Server,
+        _: &mut ConnectionMap
+    ) {
+
+        self.tick_count += 1;
+
+        if self.tick_count == 2 {
+            server.shutdown().unwrap();
+        }
+
+    }
+
+}
+
+pub struct MockSendOrderServerHandler {
+    pub tick_count: u32
+}
+
+impl Handler<Server> for MockSendOrderServerHandler {
+
+    fn tick_connections(
+        &mut self, server: &mut Server,
+        _: &mut ConnectionMap
+    ) {
+
+        self.tick_count += 1;
+
+        if self.tick_count == 3 {
+            server.shutdown().unwrap();
+        }
+
+    }
+
+}
+
 pub struct MockServerStatsHandler {
     pub tick_count: u32,
 }
@@ -491,7 +1194,7 @@ impl Handler<Server> for MockServerStatsHandler {
 
     fn tick_connections(
         &mut self, server: &mut Server,
-        _: &mut HashMap<ConnectionID, Connection>
+        _: &mut ConnectionMap
     ) {
 
         //conn.send(MessageKind::Instant, b"Hello World".to_vec());
@@ -505,6 +1208,80 @@ impl Handler<Server> for MockServerStatsHandler {
 
 }
 
+pub struct MockResumptionServerHandler {
+    pub socket_handle: MockSocketHandle,
+    pub tick_count: u32,
+    pub secret: Option<u64>,
+    pub connection_calls: u32,
+    pub connection_lost_calls: u32,
+    pub connection_resumed_calls: u32,
+    pub received: Vec<Vec<u8>>
+}
+
+impl Handler<Server> for MockResumptionServerHandler {
+
+    fn connection(&mut self, _: &mut Server, _: &mut Connection) {
+        self.connection_calls += 1;
+    }
+
+    fn connection_lost(&mut self, _: &mut Server, _: &mut Connection) {
+
+        self.connection_lost_calls += 1;
+
+        // Resume the lost connection with the issued token
+        let s = self.secret.expect("No resumption token was issued");
+        self.socket_handle.receive(vec![("127.0.0.1:5678", [
+            1, 2, 3, 4,
+            0, 0, 0, 1,
+            0, 0,
+            0, 0, 0, 0,
+
+            // Resume
+            8, 0, 0, 9, 1,
+            (s >> 56) as u8, (s >> 48) as u8, (s >> 40) as u8, (s >> 32) as u8,
+            (s >> 24) as u8, (s >> 16) as u8, (s >> 8) as u8, s as u8,
+
+            // Hello World
+            0, 0, 0, 11, 72, 101, 108, 108, 111, 32, 87, 111, 114, 108, 100
+
+        ].to_vec())]);
+
+    }
+
+    fn connection_resumed(&mut self, _: &mut Server, conn: &mut Connection) {
+        self.connection_resumed_calls += 1;
+        assert_eq!(conn.id(), ConnectionID(1));
+        assert!(conn.resumed());
+    }
+
+    fn tick_connections(
+        &mut self, server: &mut Server,
+        connections: &mut ConnectionMap
+    ) {
+
+        // Extract the issued resumption token from the sent packets
+        for packet in self.socket_handle.sent() {
+            if packet.1.len() == 28 && packet.1[14] == 9 {
+                self.secret = Some(packet.1[19..27].iter().fold(0, |s, b| s << 8 | *b as u64));
+            }
+        }
+
+        for (_, conn) in connections.iter_mut() {
+            for msg in conn.received() {
+                self.received.push(msg);
+            }
+        }
+
+        self.tick_count += 1;
+
+        if self.connection_resumed_calls > 0 || self.tick_count == 60 {
+            server.shutdown().unwrap();
+        }
+
+    }
+
+}
+
 pub struct MockServerHandler {
     send_count: u8,
     pub received: Vec<Vec<u8>>
@@ -523,7 +1300,7 @@ impl Handler<Server> for MockServerHandler {
 
     fn tick_connections(
         &mut self, server: &mut Server,
-        connections: &mut HashMap<ConnectionID, Connection>
+        connections: &mut ConnectionMap
     ) {
 
         // Ensure hashmap and connection object have the same id
@@ -565,11 +1342,11 @@ fn check_server_messages(conn: &mut Connection) {
 }
 
 fn to_socket_addr<T: ToSocketAddrs>(address: T) -> net::SocketAddr {
-    address.to_socket_addrs().unwrap().nth(0).unwrap()
+    address.to_socket_addrs().unwrap().next().unwrap()
 }
 
 pub fn create_connection(config: Option<Config>) -> (Connection, MockOwner, MockOwnerHandler) {
-    let config = config.unwrap_or_else(||Config::default());
+    let config = config.unwrap_or_default();
     let local_address: net::SocketAddr = "127.0.0.1:1234".parse().unwrap();
     let peer_address: net::SocketAddr = "255.1.1.2:5678".parse().unwrap();
     let limiter = BinaryRateLimiter::new(&config);
@@ -0,0 +1,94 @@
+// Copyright (c) 2015-2016 Ivo Wetzel
+
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use std::io::ErrorKind;
+use std::net::SocketAddr;
+
+use super::mock::{MockSocket, MockMultiClientHandler};
+use super::super::{Config, ConnectionID, MessageKind, MultiClient};
+
+fn message_packet(id: ConnectionID, payload: &[u8]) -> Vec<u8> {
+    let mut packet = vec![
+        1, 2, 3, 4,
+        (id.0 >> 24) as u8, (id.0 >> 16) as u8, (id.0 >> 8) as u8, id.0 as u8,
+        0,
+        0,
+        0, 0, 0, 0,
+
+        0, 0, 0, payload.len() as u8
+    ];
+    packet.extend_from_slice(payload);
+    packet
+}
+
+#[test]
+fn test_multi_client_connections() {
+
+    let socket = MockSocket::from_address("127.0.0.1:0");
+    let mut socket_handle = socket.handle();
+
+    let mut handler = MockMultiClientHandler {
+        connections: Vec::new(),
+        ticks: Vec::new(),
+        messages: Vec::new()
+    };
+
+    let mut client = MultiClient::from_socket(Config::default(), socket);
+    let game = client.connect_sync(&mut handler, "127.0.0.1:1000").unwrap();
+    let chat = client.connect_sync(&mut handler, "127.0.0.1:1001").unwrap();
+    assert_ne!(game, chat);
+
+    // Each server can only be connected to once
+    assert_eq!(
+        client.connect_sync(&mut handler, "127.0.0.1:1000").unwrap_err().kind(),
+        ErrorKind::AlreadyExists
+    );
+
+    let mut ids = vec![game, chat];
+    ids.sort_by_key(|id| id.0);
+    assert_eq!(client.connections(), ids);
+
+    // Packets are dispatched by the address of their server
+    socket_handle.receive(vec![
+        ("127.0.0.1:1001", message_packet(chat, b"Foo")),
+        ("127.0.0.1:1002", message_packet(game, b"Bar"))
+    ]);
+
+    client.tick_sync(&mut handler);
+
+    assert_eq!(handler.connections, vec![chat]);
+    assert_eq!(handler.messages, vec![(chat, b"Foo".to_vec())]);
+
+    handler.ticks.sort_by_key(|id| id.0);
+    assert_eq!(handler.ticks, ids);
+
+    // All connections send via the shared socket
+    let game_addr: SocketAddr = "127.0.0.1:1000".parse().unwrap();
+    let chat_addr: SocketAddr = "127.0.0.1:1001".parse().unwrap();
+    let sent = socket_handle.sent();
+    assert!(sent.iter().any(|packet| packet.0 == game_addr));
+    assert!(sent.iter().any(|packet| packet.0 == chat_addr));
+    for packet in sent {
+        let id = if packet.0 == game_addr { game } else { chat };
+        assert_eq!(packet.1[4..8].to_vec(), vec![
+            (id.0 >> 24) as u8, (id.0 >> 16) as u8, (id.0 >> 8) as u8, id.0 as u8
+        ]);
+    }
+
+    // Connections are closed individually
+    client.close_sync(&mut handler, game).unwrap();
+    assert_eq!(client.connections(), vec![chat]);
+    assert_eq!(
+        client.send(game, MessageKind::Instant, b"Foo".to_vec()).unwrap_err().kind(),
+        ErrorKind::NotConnected
+    );
+    assert!(client.send(chat, MessageKind::Instant, b"Foo".to_vec()).is_ok());
+
+    client.shutdown_sync(&mut handler);
+    assert!(client.connections().is_empty());
+
+}
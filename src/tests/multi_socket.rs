@@ -0,0 +1,65 @@
+// Copyright (c) 2015-2016 Ivo Wetzel
+
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use std::io::ErrorKind;
+use std::net::SocketAddr;
+use std::sync::mpsc::TryRecvError;
+use super::mock::{MockPacket, MockSocket};
+use super::super::{MultiSocket, Socket};
+
+#[test]
+fn test_multi_socket_empty() {
+    let sockets: Vec<MockSocket> = Vec::new();
+    assert_eq!(MultiSocket::new(sockets).err().unwrap().kind(), ErrorKind::InvalidInput);
+}
+
+#[test]
+fn test_multi_socket_routes() {
+
+    let lan = MockSocket::from_address("127.0.0.1:4000");
+    let public = MockSocket::from_address("127.0.0.1:5000");
+    lan.receive(vec![("127.0.0.1:1000", vec![1])]);
+    public.receive(vec![
+        ("127.0.0.1:2000", vec![2]),
+        ("127.0.0.1:2001", vec![3])
+    ]);
+
+    let mut lan_handle = lan.handle();
+    let mut public_handle = public.handle();
+    let mut socket = MultiSocket::new(vec![lan, public]).unwrap();
+
+    let lan_addr: SocketAddr = "127.0.0.1:4000".parse().unwrap();
+    let public_addr: SocketAddr = "127.0.0.1:5000".parse().unwrap();
+    assert_eq!(socket.local_addr().unwrap(), lan_addr);
+    assert_eq!(socket.local_addrs().unwrap(), vec![lan_addr, public_addr]);
+
+    // Sockets take turns when receiving
+    let mut received = Vec::new();
+    while let Ok((addr, packet)) = socket.try_recv() {
+        received.push((addr.port(), packet));
+    }
+    assert_eq!(received, vec![(1000, vec![1]), (2000, vec![2]), (2001, vec![3])]);
+    assert_eq!(socket.try_recv(), Err(TryRecvError::Empty));
+
+    let remote: SocketAddr = "127.0.0.1:2000".parse().unwrap();
+    assert_eq!(socket.local_addr_for(remote), Some(public_addr));
+
+    // Replies go out through the socket the remote was seen on
+    socket.send_to(&[4], "127.0.0.1:1000".parse().unwrap()).unwrap();
+    socket.send_to(&[5], remote).unwrap();
+    assert_eq!(lan_handle.sent(), vec![MockPacket("127.0.0.1:1000".parse().unwrap(), vec![4])]);
+    assert_eq!(public_handle.sent(), vec![MockPacket(remote, vec![5])]);
+
+    // Unknown remotes are served by the first socket
+    assert!(socket.forget(remote));
+    assert!(!socket.forget(remote));
+    assert_eq!(socket.local_addr_for(remote), None);
+    socket.send_to(&[6], remote).unwrap();
+    assert_eq!(lan_handle.sent(), vec![MockPacket(remote, vec![6])]);
+    public_handle.assert_sent_none();
+
+}
@@ -0,0 +1,61 @@
+// Copyright (c) 2015-2016 Ivo Wetzel
+
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use std::time::Duration;
+use super::super::{ConnectionID, ResumptionTicket};
+
+#[test]
+fn test_resumption_ticket() {
+
+    let key = [5; 32];
+    let ticket = ResumptionTicket::issue(
+        key, ConnectionID(1234), Duration::from_secs(60), Some([9; 32])
+    );
+    assert_eq!(ticket.id, ConnectionID(1234));
+    assert_eq!(ResumptionTicket::id_from_sealed(&ticket.data), Some(ConnectionID(1234)));
+
+    // The session is only restored with the right key and id
+    assert_eq!(ResumptionTicket::verify(key, ConnectionID(1234), &ticket.data), Some(ticket.clone()));
+    assert_eq!(ResumptionTicket::verify([6; 32], ConnectionID(1234), &ticket.data), None);
+    assert_eq!(ResumptionTicket::verify(key, ConnectionID(1235), &ticket.data), None);
+
+    // Tampered tickets are rejected
+    let mut tampered = ticket.data.clone();
+    tampered[10] ^= 1;
+    assert_eq!(ResumptionTicket::verify(key, ConnectionID(1234), &tampered), None);
+
+    // Expired tickets are rejected
+    let expired = ResumptionTicket::issue(key, ConnectionID(1234), Duration::from_secs(0), None);
+    assert_eq!(ResumptionTicket::verify(key, ConnectionID(1234), &expired.data), None);
+
+}
+
+#[test]
+fn test_resumption_ticket_bytes() {
+
+    let ticket = ResumptionTicket::issue(
+        [5; 32], ConnectionID(1234), Duration::from_secs(60), Some([9; 32])
+    );
+    assert_eq!(ResumptionTicket::from_bytes(&ticket.to_bytes()), Some(ticket));
+
+    let ticket = ResumptionTicket::issue(
+        [5; 32], ConnectionID(1234), Duration::from_secs(60), None
+    );
+    assert_eq!(ResumptionTicket::from_bytes(&ticket.to_bytes()), Some(ticket.clone()));
+
+    // Malformed tickets
+    assert_eq!(ResumptionTicket::from_bytes(&[]), None);
+    assert_eq!(ResumptionTicket::from_bytes(&[1, 0, 0, 4, 210]), None);
+    assert_eq!(ResumptionTicket::from_bytes(&[1, 0, 0, 4, 210, 1, 9, 9]), None);
+    assert_eq!(ResumptionTicket::from_bytes(&[1, 0, 0, 4, 210, 2]), None);
+
+    // Different format version
+    let mut bytes = ticket.to_bytes();
+    bytes[0] = 2;
+    assert_eq!(ResumptionTicket::from_bytes(&bytes), None);
+
+}
@@ -0,0 +1,72 @@
+// Copyright (c) 2015-2016 Ivo Wetzel
+
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use super::super::shared::rtt_histogram::RttHistogram;
+
+#[test]
+fn test_percentile_empty() {
+    let h = RttHistogram::new();
+    assert_eq!(h.percentile(50.0), 0);
+    assert_eq!(h.percentile(99.0), 0);
+}
+
+#[test]
+fn test_percentile_exact() {
+
+    let mut h = RttHistogram::new();
+    for rtt in 1..=50 {
+        h.record(rtt);
+    }
+
+    assert_eq!(h.percentile(0.0), 1);
+    assert_eq!(h.percentile(50.0), 25);
+    assert_eq!(h.percentile(90.0), 45);
+    assert_eq!(h.percentile(100.0), 50);
+
+    // Out of range percentiles are clamped
+    assert_eq!(h.percentile(-10.0), 1);
+    assert_eq!(h.percentile(150.0), 50);
+
+}
+
+#[test]
+fn test_percentile_tail() {
+
+    let mut h = RttHistogram::new();
+    for _ in 0..95 {
+        h.record(20);
+    }
+
+    for _ in 0..5 {
+        h.record(1000);
+    }
+
+    assert_eq!(h.percentile(50.0), 20);
+    assert_eq!(h.percentile(95.0), 20);
+    assert_eq!(h.percentile(99.0), 1000);
+
+}
+
+#[test]
+fn test_percentile_precision() {
+
+    let mut h = RttHistogram::new();
+    h.record(1000);
+    h.record(5000);
+
+    // Bigger samples share buckets, whose upper bound is reported
+    assert_eq!(h.percentile(50.0), 1007);
+    assert_eq!(h.percentile(100.0), 5000);
+
+    // Samples exceeding the recordable range are capped
+    h.record(u32::MAX);
+    assert_eq!(h.percentile(100.0), u16::MAX as u32);
+
+    h.clear();
+    assert_eq!(h.percentile(100.0), 0);
+
+}
@@ -6,15 +6,73 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
+#[cfg(feature = "serialization")]
+extern crate serde_json;
+
+use std::io::{self, ErrorKind};
+use std::net::{self, SocketAddr};
+use std::sync::mpsc::TryRecvError;
+use std::thread;
+use std::time::{Duration, Instant};
 use super::mock::{
     MockSocket,
+    MockAddressConflictServerHandler,
+    MockAdmissionServerHandler,
+    MockBanServerHandler,
+    MockConnectionCountServerHandler,
+    MockConnectionLimitServerHandler,
     MockTickDelayServerHandler,
     MockConnectionServerHandler,
+    MockFloodServerHandler,
+    MockPacketDropServerHandler,
+    MockPendingServerHandler,
+    MockAttemptFailedServerHandler,
+    MockJoinNotifyServerHandler,
+    MockBanEventServerHandler,
+    MockIdValidationServerHandler,
     MockConnectionRemapServerHandler,
+    MockHostMigrationServerHandler,
+    MockKeepAliveServerHandler,
+    MockResumptionServerHandler,
+    MockSendOrderServerHandler,
+    MockControlledServerHandler,
+    MockServerInfoHandler,
+    MockStateDumpServerHandler,
     MockServerStatsHandler,
+    MockTickPhaseHandler,
     MockTickRecorder
 };
-use super::super::{Config, Server, Stats};
+use super::super::{
+    AddressChangePolicy, BanTarget, Config, Connection, ConnectionFailure, ConnectionState,
+    MessageKind, PacketDropReason, SecurityStats, SendOrder, Server, ServerStats, Stats,
+    Socket, UdpSocket
+};
+
+#[cfg(feature = "serialization")]
+use super::super::ServerStateDump;
+
+#[cfg(feature = "connect_token")]
+use super::mock::MockConnectTokenServerHandler;
+
+#[cfg(feature = "connect_token")]
+use super::super::ConnectToken;
+
+use super::super::ConnectionID;
+
+#[cfg(feature = "authentication")]
+use super::mock::create_socket;
+
+#[cfg(feature = "authentication")]
+use super::mock::MockHandshakeCookieServerHandler;
+
+#[cfg(feature = "authentication")]
+use super::super::shared::authentication;
+
+#[cfg(feature = "authentication")]
+use super::mock::MockIdCollisionServerHandler;
+
+#[cfg(feature = "authentication")]
+use super::super::IdCollisionPolicy;
 
 #[test]
 fn test_server_tick_delay_no_overflow() {
@@ -205,6 +263,228 @@ fn test_server_connection_remapping() {
 
 }
 
+#[test]
+fn test_server_host_migration() {
+
+    let socket = MockSocket::from_address("127.0.0.1:0");
+    socket.receive(vec![
+        ("127.0.0.1:1234", [
+            1, 2, 3, 4, // Protocol Header
+            0, 0, 0, 1, // Connection ID
+            0, 0,
+            0, 0, 0, 0
+
+        ].to_vec())
+    ]);
+
+    let mut socket_handle = socket.handle();
+
+    // Server
+    let config = Config {
+        host_migration: true,
+        .. Config::default()
+    };
+
+    let mut server = Server::new(config);
+    let mut handler = MockHostMigrationServerHandler {
+        tick_count: 0
+    };
+    server.bind_to_socket(&mut handler, socket).unwrap();
+
+    // Expect the peers and the unchanged session state to be shared once
+    socket_handle.assert_sent(vec![
+        ("127.0.0.1:1234", [
+            1, 2, 3, 4,
+            0, 0, 0, 0,
+            0, 0,
+            0, 0, 0, 0,
+
+            // Peers
+            9, 0, 0, 12, 5, 0, 0, 0, 1, 4, 127, 0, 0, 1, 4, 210,
+
+            // Session State
+            9, 0, 0, 6, 6, 83, 116, 97, 116, 101
+
+        ].to_vec()),
+        ("127.0.0.1:1234", [
+            1, 2, 3, 4,
+            0, 0, 0, 0,
+            1, 0,
+            0, 0, 0, 0
+
+        ].to_vec())
+    ]);
+
+}
+
+#[test]
+fn test_server_send_order_rotate() {
+
+    let socket = MockSocket::from_address("127.0.0.1:0");
+    socket.receive(vec![
+        ("127.0.0.1:1001", [1, 2, 3, 4, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0].to_vec()),
+        ("127.0.0.1:1002", [1, 2, 3, 4, 0, 0, 0, 2, 0, 0, 0, 0, 0, 0].to_vec()),
+        ("127.0.0.1:1003", [1, 2, 3, 4, 0, 0, 0, 3, 0, 0, 0, 0, 0, 0].to_vec())
+    ]);
+
+    let mut socket_handle = socket.handle();
+
+    // Server
+    let config = Config {
+        send_order: SendOrder::Rotate,
+        .. Config::default()
+    };
+
+    let mut server = Server::new(config);
+    let mut handler = MockSendOrderServerHandler {
+        tick_count: 0
+    };
+    server.bind_to_socket(&mut handler, socket).unwrap();
+
+    // Expect the first connection to move by one position each tick
+    let ports: Vec<u16> = socket_handle.sent().iter().map(|p| p.0.port()).collect();
+    assert_eq!(ports, vec![
+        1001, 1002, 1003,
+        1002, 1003, 1001,
+        1003, 1001, 1002
+    ]);
+
+}
+
+#[test]
+fn test_server_connection_resumption() {
+
+    let socket = MockSocket::from_address("127.0.0.1:0");
+    socket.receive(vec![
+
+        // create a new connection
+        ("127.0.0.1:1234", [
+            1, 2, 3, 4, // Protocol Header
+            0, 0, 0, 1, // Connection ID
+            0, 0,
+            0, 0, 0, 0
+
+        ].to_vec())
+
+    ]);
+
+    // Server
+    let config = Config {
+        connection_drop_threshold: 100,
+        resumption_threshold: 1000,
+        .. Config::default()
+    };
+
+    let mut server = Server::new(config);
+    let mut handler = MockResumptionServerHandler {
+        socket_handle: socket.handle(),
+        tick_count: 0,
+        secret: None,
+        connection_calls: 0,
+        connection_lost_calls: 0,
+        connection_resumed_calls: 0,
+        received: Vec::new()
+    };
+    server.bind_to_socket(&mut handler, socket).unwrap();
+
+    // Expect the lost connection to have been resumed from another address
+    assert_eq!(handler.connection_calls, 1);
+    assert_eq!(handler.connection_lost_calls, 1);
+    assert_eq!(handler.connection_resumed_calls, 1);
+
+    // Expect the message from the first packet to be delivered
+    assert_eq!(handler.received, vec![b"Hello World".to_vec()]);
+
+}
+
+#[test]
+fn test_server_address_conflict() {
+
+    // Connections follow the new address by default
+    let (handler, sent) = address_conflict(AddressChangePolicy::Migrate);
+    assert_eq!(handler.conflicts, vec!["127.0.0.1:5678".parse().unwrap()]);
+    assert_eq!(handler.connection_calls, 1);
+    assert_eq!(handler.closed_calls, 0);
+    assert_eq!(sent, vec!["127.0.0.1:5678".parse().unwrap()]);
+
+    // The new address is challenged while packets keep being send to the
+    // previous one
+    let (handler, sent) = address_conflict(AddressChangePolicy::Challenge);
+    assert_eq!(handler.conflicts, vec!["127.0.0.1:5678".parse().unwrap()]);
+    assert_eq!(handler.connection_calls, 1);
+    assert_eq!(sent, vec![
+        "127.0.0.1:1234".parse().unwrap(),
+        "127.0.0.1:5678".parse().unwrap()
+    ]);
+
+    // A new connection replaces the existing one, which is closed
+    let (handler, sent) = address_conflict(AddressChangePolicy::Reconnect);
+    assert_eq!(handler.conflicts, vec!["127.0.0.1:5678".parse().unwrap()]);
+    assert_eq!(handler.connection_calls, 2);
+    assert_eq!(handler.closed_calls, 1);
+    assert_eq!(sent, vec!["127.0.0.1:5678".parse().unwrap()]);
+
+}
+
+fn address_conflict(policy: AddressChangePolicy) -> (MockAddressConflictServerHandler, Vec<SocketAddr>) {
+
+    let socket = MockSocket::from_address("127.0.0.1:0");
+    socket.receive(vec![
+        ("127.0.0.1:1234", [
+            1, 2, 3, 4,
+            0, 0, 0, 1,
+            0, 0,
+            0, 0, 0, 0
+
+        ].to_vec()),
+
+        // A different peer reusing the connection id
+        ("127.0.0.1:5678", [
+            1, 2, 3, 4,
+            0, 0, 0, 1,
+            1, 0,
+            0, 0, 0, 0
+
+        ].to_vec())
+    ]);
+
+    let mut socket_handle = socket.handle();
+
+    let config = Config {
+        address_change_policy: policy,
+        .. Config::default()
+    };
+
+    let mut server = Server::new(config);
+    let mut handler = MockAddressConflictServerHandler {
+        conflicts: Vec::new(),
+        connection_calls: 0,
+        closed_calls: 0
+    };
+    server.bind_to_socket(&mut handler, socket).unwrap();
+
+    let sent = socket_handle.sent().into_iter().map(|p| p.0).collect();
+    (handler, sent)
+
+}
+
+#[test]
+fn test_server_tick_phases() {
+
+    let mut handler = MockTickPhaseHandler {
+        phases: Vec::new()
+    };
+
+    let mut server = Server::new(Config::default());
+    server.bind_to_socket(&mut handler, MockSocket::from_address("127.0.0.1:0")).unwrap();
+
+    assert_eq!(handler.phases, vec![
+        "after_receive", "tick", "before_send",
+        "after_receive", "tick", "before_send"
+    ]);
+
+}
+
 #[test]
 fn test_server_stats() {
 
@@ -220,10 +500,1919 @@ fn test_server_stats() {
     let mut server = Server::new(config);
     server.bind(&mut handler, "127.0.0.1:0").unwrap();
 
-    assert_eq!(server.stats(), Stats {
+    let stats = server.stats();
+    assert_eq!(stats, Stats {
         bytes_sent: 0,
-        bytes_received: 0
+        bytes_received: 0,
+        .. stats
+    });
+
+    // Ticks should not deviate from the send rate by more than their duration
+    assert!(stats.tick_jitter() < 50_000);
+
+}
+
+#[test]
+fn test_server_tick_spin_threshold() {
+
+    let config = Config {
+        send_rate: 120,
+        tick_spin_threshold: 20,
+        .. Config::default()
+    };
+
+    let mut handler = MockServerStatsHandler {
+        tick_count: 0
+    };
+
+    let mut server = Server::new(config);
+    let start = Instant::now();
+    server.bind(&mut handler, "127.0.0.1:0").unwrap();
+
+    // Ticks should keep the send rate even below the sleep timer's granularity
+    assert!(start.elapsed() >= Duration::from_millis(150));
+    assert!(server.stats().tick_jitter() < 1_000_000 / 120);
+
+}
+
+#[cfg(feature = "connect_token")]
+#[test]
+fn test_server_connect_token() {
+
+    let key = [3; 32];
+    let token = ConnectToken::new(
+        ConnectionID(2), Duration::from_secs(30), b"user".to_vec()
+
+    ).seal(key);
+
+    let expired = ConnectToken {
+        id: ConnectionID(3),
+        expires: 0,
+        user_data: Vec::new()
+
+    }.seal(key);
+
+    let forged = ConnectToken::new(
+        ConnectionID(4), Duration::from_secs(30), Vec::new()
+
+    ).seal([4; 32]);
+
+    let socket = MockSocket::from_address("127.0.0.1:0");
+    socket.receive(vec![
+        ("127.0.0.1:1000", token_packet(1, &[])),
+        ("127.0.0.1:1001", token_packet(2, &token)),
+        ("127.0.0.1:1002", token_packet(3, &expired)),
+        ("127.0.0.1:1003", token_packet(4, &forged)),
+
+        // Tokens issued for another connection are rejected
+        ("127.0.0.1:1004", token_packet(5, &token))
+    ]);
+
+    let config = Config {
+        connect_token_key: Some(key),
+        .. Config::default()
+    };
+
+    let mut server = Server::new(config);
+    let mut handler = MockConnectTokenServerHandler {
+        connections: Vec::new()
+    };
+    server.bind_to_socket(&mut handler, socket).unwrap();
+
+    // Only the connection with a valid token is created
+    assert_eq!(handler.connections, vec![(ConnectionID(2), b"user".to_vec())]);
+
+}
+
+#[cfg(feature = "connect_token")]
+fn token_packet(id: u8, token: &[u8]) -> Vec<u8> {
+
+    let mut packet = vec![
+        1, 2, 3, 4,
+        0, 0, 0, id,
+        0, 0,
+        0, 0, 0, 0
+    ];
+
+    if !token.is_empty() {
+        packet.extend_from_slice(&[8, 0, 0, token.len() as u8 + 1, 9]);
+        packet.extend_from_slice(token);
+    }
+
+    packet
+
+}
+
+#[cfg(feature = "authentication")]
+#[test]
+fn test_server_authentication() {
+
+    let config = Config {
+        authentication_key: Some([9; 32]),
+        .. Config::default()
+    };
+
+    // Sign a packet using a connection with the same key
+    let (mut conn, mut socket, mut socket_handle, mut owner, mut handler) = create_socket(Some(config));
+    conn.set_id(ConnectionID(1));
+    let address = conn.peer_addr();
+    conn.send_packet(&mut socket, &address, &mut owner, &mut handler);
+    let signed = socket_handle.sent().remove(0).1;
+
+    let mut forged = signed.clone();
+    forged[7] = 2;
+
+    let socket = MockSocket::from_address("127.0.0.1:0");
+    socket.receive(vec![
+        ("127.0.0.1:1000", signed),
+        ("127.0.0.1:1001", forged),
+        ("127.0.0.1:1002", [
+            1, 2, 3, 4,
+            0, 0, 0, 3,
+            0, 0,
+            0, 0, 0, 0
+
+        ].to_vec())
+    ]);
+
+    let mut server = Server::new(config);
+    let mut handler = MockConnectionCountServerHandler {
+        connections: Vec::new()
+    };
+    server.bind_to_socket(&mut handler, socket).unwrap();
+
+    // Only the authentic packet creates a connection
+    assert_eq!(handler.connections, vec![ConnectionID(1)]);
+
+}
+
+#[test]
+fn test_server_unknown_address_packet_limit() {
+
+    let socket = MockSocket::from_address("127.0.0.1:0");
+    socket.receive(vec![
+        ("127.0.0.1:1000", id_packet(1)),
+
+        // Packets of established connections are not limited
+        ("127.0.0.1:1000", id_packet(1)),
+        ("127.0.0.1:1000", id_packet(1)),
+
+        // The limit applies to all ports of the same address
+        ("127.0.0.1:1001", id_packet(2)),
+        ("127.0.0.1:1002", id_packet(3)),
+        ("127.0.0.2:1000", id_packet(4))
+    ]);
+
+    let mut server = Server::new(Config {
+        unknown_address_packet_limit: 2,
+        .. Config::default()
     });
 
+    let mut handler = MockConnectionCountServerHandler {
+        connections: Vec::new()
+    };
+    server.bind_to_socket(&mut handler, socket).unwrap();
+
+    assert_eq!(handler.connections, vec![
+        ConnectionID(1), ConnectionID(2), ConnectionID(4)
+    ]);
+
 }
 
+#[test]
+fn test_server_max_connections_per_ip() {
+
+    let socket = MockSocket::from_address("127.0.0.1:0");
+    socket.receive(vec![
+        ("127.0.0.1:1000", id_packet(1)),
+        ("127.0.0.1:1001", id_packet(2)),
+        ("127.0.0.1:1002", id_packet(3)),
+
+        // Existing connections are not affected
+        ("127.0.0.1:1000", id_packet(1)),
+
+        // Other addresses have limits of their own
+        ("127.0.0.2:1000", id_packet(4))
+    ]);
+
+    let mut server = Server::new(Config {
+        max_connections_per_ip: 2,
+        .. Config::default()
+    });
+
+    let mut handler = MockConnectionLimitServerHandler {
+        connections: Vec::new(),
+        exceeded: Vec::new(),
+        full: Vec::new()
+    };
+    server.bind_to_socket(&mut handler, socket).unwrap();
+
+    assert_eq!(handler.connections, vec![
+        ConnectionID(1), ConnectionID(2), ConnectionID(4)
+    ]);
+
+    assert_eq!(handler.exceeded, vec![
+        ("127.0.0.1:1002".parse::<SocketAddr>().unwrap(), ConnectionID(3))
+    ]);
+
+}
+
+#[test]
+fn test_server_max_connections() {
+
+    let socket = MockSocket::from_address("127.0.0.1:0");
+    socket.receive(vec![
+        ("127.0.0.1:1000", id_packet(1)),
+        ("127.0.0.2:1000", id_packet(2)),
+        ("127.0.0.3:1000", id_packet(3)),
+
+        // Existing connections are not affected
+        ("127.0.0.1:1000", id_packet(1))
+    ]);
+
+    let mut socket_handle = socket.handle();
+    let mut server = Server::new(Config {
+        max_connections: 2,
+        .. Config::default()
+    });
+
+    let mut handler = MockConnectionLimitServerHandler {
+        connections: Vec::new(),
+        exceeded: Vec::new(),
+        full: Vec::new()
+    };
+    server.bind_to_socket(&mut handler, socket).unwrap();
+
+    assert_eq!(handler.connections, vec![ConnectionID(1), ConnectionID(2)]);
+    assert_eq!(handler.full, vec![
+        ("127.0.0.3:1000".parse::<SocketAddr>().unwrap(), ConnectionID(3))
+    ]);
+
+    // The denied client is notified
+    let denials: Vec<_> = socket_handle.sent().into_iter().filter(|p| {
+        Connection::server_full_from_packet(&Config::default(), &p.1).is_some()
+
+    }).map(|p| (p.0, p.1)).collect();
+
+    assert_eq!(denials, vec![(
+        "127.0.0.3:1000".parse::<SocketAddr>().unwrap(),
+        vec![1, 2, 3, 4, 255, 255, 255, 252, 0, 0, 0, 3]
+    )]);
+
+}
+
+#[test]
+fn test_server_handshake_limit() {
+
+    let socket = MockSocket::from_address("127.0.0.1:0");
+    socket.receive(vec![
+        ("127.0.0.1:1000", id_packet(1)),
+        ("127.0.0.2:1000", id_packet(2)),
+        ("127.0.0.3:1000", id_packet(3)),
+
+        // Packets of existing connections do not count against the limit
+        ("127.0.0.1:1000", id_packet(1)),
+        ("127.0.0.4:1000", id_packet(4))
+    ]);
+
+    let mut server = Server::new(Config {
+        handshake_limit: 2,
+        .. Config::default()
+    });
+
+    let mut handler = MockConnectionCountServerHandler {
+        connections: Vec::new()
+    };
+    server.bind_to_socket(&mut handler, socket).unwrap();
+
+    assert_eq!(handler.connections, vec![ConnectionID(1), ConnectionID(2)]);
+
+}
+
+#[test]
+fn test_server_handshake_min_size() {
+
+    let mut padded = id_packet(2);
+    padded.resize(32, 0);
+
+    let socket = MockSocket::from_address("127.0.0.1:0");
+    socket.receive(vec![
+        ("127.0.0.1:1000", id_packet(1)),
+        ("127.0.0.1:1001", padded),
+
+        // Packets of existing connections do not need to be padded
+        ("127.0.0.1:1001", id_packet(2))
+    ]);
+
+    let mut server = Server::new(Config {
+        handshake_min_size: 32,
+        .. Config::default()
+    });
+
+    let mut handler = MockConnectionCountServerHandler {
+        connections: Vec::new()
+    };
+    server.bind_to_socket(&mut handler, socket).unwrap();
+
+    assert_eq!(handler.connections, vec![ConnectionID(2)]);
+
+}
+
+#[test]
+fn test_server_protocol_mismatch_response() {
+
+    let mut mismatched = id_packet(1);
+    mismatched[0..4].copy_from_slice(&[5, 6, 7, 8]);
+
+    let socket = MockSocket::from_address("127.0.0.1:0");
+    socket.receive(vec![
+        ("127.0.0.1:1000", mismatched.clone()),
+
+        // Responses are limited per address
+        ("127.0.0.1:1001", mismatched.clone()),
+        ("127.0.0.2:1000", mismatched),
+
+        // Matching packets still create connections
+        ("127.0.0.1:1002", id_packet(2))
+    ]);
+
+    let mut socket_handle = socket.handle();
+
+    let mut server = Server::new(Config {
+        protocol_mismatch_response: true,
+        .. Config::default()
+    });
+
+    let mut handler = MockConnectionCountServerHandler {
+        connections: Vec::new()
+    };
+    server.bind_to_socket(&mut handler, socket).unwrap();
+
+    assert_eq!(handler.connections, vec![ConnectionID(2)]);
+
+    let rejections: Vec<_> = socket_handle.sent().into_iter().filter(|p| {
+        p.1.len() == 12
+
+    }).map(|p| (p.0, p.1)).collect();
+
+    let response = vec![
+        5, 6, 7, 8,
+        255, 255, 255, 255,
+        1, 2, 3, 4
+    ];
+
+    assert_eq!(rejections, vec![
+        ("127.0.0.1:1000".parse::<SocketAddr>().unwrap(), response.clone()),
+        ("127.0.0.2:1000".parse::<SocketAddr>().unwrap(), response)
+    ]);
+
+}
+
+#[test]
+fn test_server_sync() {
+
+    let socket = MockSocket::from_address("127.0.0.1:0");
+    socket.receive(vec![
+        ("127.0.0.1:1000", id_packet(1))
+    ]);
+
+    let mut socket_handle = socket.handle();
+    let mut handler = MockConnectionCountServerHandler {
+        connections: Vec::new()
+    };
+
+    let mut server = Server::new(Config::default());
+    let mut state = server.bind_to_socket_sync(&mut handler, socket).unwrap();
+    assert_eq!(server.local_addr().unwrap(), "127.0.0.1:0".parse::<SocketAddr>().unwrap());
+    assert!(state.connections().is_empty());
+
+    // Each tick receives, ticks and sends exactly once
+    server.tick_sync(&mut handler, &mut state);
+    assert_eq!(handler.connections, vec![ConnectionID(1)]);
+    assert_eq!(state.connections().len(), 1);
+    assert_eq!(server.tick_number(), 1);
+    assert_eq!(socket_handle.sent().len(), 1);
+
+    // Shutting down resets the server, after which ticks do nothing
+    server.shutdown_sync(&mut handler, &mut state).unwrap();
+    assert!(server.local_addr().is_err());
+    assert!(server.shutdown_sync(&mut handler, &mut state).is_err());
+
+    server.tick_sync(&mut handler, &mut state);
+    assert_eq!(server.tick_number(), 1);
+
+}
+
+#[test]
+fn test_server_handle() {
+
+    let socket = MockSocket::from_address("127.0.0.1:0");
+    socket.receive(vec![
+        ("127.0.0.1:1000", id_packet(1)),
+        ("127.0.0.1:1001", id_packet(2))
+    ]);
+
+    let mut socket_handle = socket.handle();
+    let mut handler = MockControlledServerHandler {
+        connections: Vec::new(),
+        kicked: Vec::new()
+    };
+
+    let mut server = Server::new(Config::default());
+    let handle = server.handle();
+    let mut state = server.bind_to_socket_sync(&mut handler, socket).unwrap();
+    server.tick_sync(&mut handler, &mut state);
+    assert_eq!(state.connections().len(), 2);
+    socket_handle.sent();
+
+    // Commands from other threads are applied with the next tick
+    let remote = handle.clone();
+    thread::spawn(move || {
+        remote.kick(ConnectionID(2), 1).unwrap();
+        remote.broadcast(MessageKind::Instant, b"Hello".to_vec()).unwrap();
+
+    }).join().unwrap();
+
+    server.tick_sync(&mut handler, &mut state);
+    assert_eq!(state.connections()[&ConnectionID(1)].state(), ConnectionState::Connected);
+    assert_eq!(state.connections()[&ConnectionID(2)].state(), ConnectionState::Closing);
+
+    let sent = socket_handle.sent();
+    assert!(sent.iter().any(|p| {
+        p.0 == "127.0.0.1:1000".parse::<SocketAddr>().unwrap() && p.1.ends_with(b"Hello")
+    }));
+    assert!(!sent.iter().any(|p| {
+        p.0 == "127.0.0.1:1001".parse::<SocketAddr>().unwrap() && p.1.ends_with(b"Hello")
+    }));
+
+    // Kicked connections send their reason along with the closure
+    assert!(sent.iter().any(|p| {
+        p.0 == "127.0.0.1:1001".parse::<SocketAddr>().unwrap() && p.1[8..] == [0, 128, 85, 85, 85, 85, 1]
+    }));
+    assert_eq!(handler.kicked, vec![(ConnectionID(2), 1)]);
+
+    // Messages are queued by connection id
+    server.send(ConnectionID(1), MessageKind::Instant, b"Foo".to_vec());
+    handle.send(ConnectionID(1), MessageKind::Instant, b"Bar".to_vec()).unwrap();
+    server.send(ConnectionID(3), MessageKind::Instant, b"Unknown".to_vec());
+    server.tick_sync(&mut handler, &mut state);
+
+    let sent = socket_handle.sent();
+    assert!(sent.iter().any(|p| {
+        p.0 == "127.0.0.1:1000".parse::<SocketAddr>().unwrap() && p.1.ends_with(b"Bar")
+    }));
+    assert!(sent.iter().any(|p| p.1.windows(3).any(|w| w == b"Foo")));
+    assert!(!sent.iter().any(|p| p.1.ends_with(b"Unknown")));
+
+    // Shutdown requests exit the tick loop
+    handle.shutdown().unwrap();
+    server.tick_sync(&mut handler, &mut state);
+    assert_eq!(server.tick_number(), 3);
+    server.shutdown_sync(&mut handler, &mut state).unwrap();
+
+    // Handles fail once the server is gone
+    drop(server);
+    assert_eq!(handle.shutdown().unwrap_err().kind(), ErrorKind::BrokenPipe);
+
+}
+
+#[test]
+fn test_server_broadcast() {
+
+    let socket = MockSocket::from_address("127.0.0.1:0");
+    socket.receive(vec![
+        ("127.0.0.1:1000", id_packet(1)),
+        ("127.0.0.1:1001", id_packet(2)),
+        ("127.0.0.1:1002", id_packet(3))
+    ]);
+
+    let mut socket_handle = socket.handle();
+    let mut handler = MockControlledServerHandler {
+        connections: Vec::new(),
+        kicked: Vec::new()
+    };
+
+    let mut server = Server::new(Config::default());
+    let mut state = server.bind_to_socket_sync(&mut handler, socket).unwrap();
+    server.tick_sync(&mut handler, &mut state);
+    socket_handle.sent();
+
+    server.broadcast(MessageKind::Instant, b"All".to_vec());
+    server.broadcast_except(ConnectionID(2), MessageKind::Instant, b"Others".to_vec());
+    server.tick_sync(&mut handler, &mut state);
+
+    let mut sent: Vec<_> = socket_handle.sent().into_iter().map(|p| {
+        (p.0.port(), p.1.windows(3).any(|w| w == b"All"), p.1.ends_with(b"Others"))
+
+    }).collect();
+
+    sent.sort();
+    assert_eq!(sent, vec![
+        (1000, true, true),
+        (1001, true, false),
+        (1002, true, true)
+    ]);
+
+}
+
+#[test]
+fn test_server_connections_with_tag() {
+
+    let socket = MockSocket::from_address("127.0.0.1:0");
+    socket.receive(vec![
+        ("127.0.0.1:1000", id_packet(1)),
+        ("127.0.0.1:1001", id_packet(2)),
+        ("127.0.0.1:1002", id_packet(3))
+    ]);
+
+    let mut handler = MockControlledServerHandler {
+        connections: Vec::new(),
+        kicked: Vec::new()
+    };
+
+    let mut server = Server::new(Config::default());
+    let mut state = server.bind_to_socket_sync(&mut handler, socket).unwrap();
+    server.tick_sync(&mut handler, &mut state);
+
+    {
+        let connections = state.connections_mut();
+        connections.get_mut(&ConnectionID(3)).unwrap().tag("admin");
+        connections.get_mut(&ConnectionID(1)).unwrap().tag("admin");
+        connections.get_mut(&ConnectionID(2)).unwrap().tag("observer");
+    }
+
+    assert_eq!(
+        server.connections_with_tag(state.connections(), "admin"),
+        vec![ConnectionID(1), ConnectionID(3)]
+    );
+    assert_eq!(
+        server.connections_with_tag(state.connections(), "observer"),
+        vec![ConnectionID(2)]
+    );
+    assert!(server.connections_with_tag(state.connections(), "unauthenticated").is_empty());
+
+}
+
+#[test]
+fn test_server_connection_info() {
+
+    let socket = MockSocket::from_address("127.0.0.1:0");
+    socket.receive(vec![
+        ("127.0.0.1:1002", id_packet(2)),
+        ("127.0.0.1:1001", id_packet(1))
+    ]);
+
+    let mut handler = MockControlledServerHandler {
+        connections: Vec::new(),
+        kicked: Vec::new()
+    };
+
+    let mut server = Server::new(Config::default());
+    let mut state = server.bind_to_socket_sync(&mut handler, socket).unwrap();
+    assert_eq!(server.connections().count(), 0);
+
+    // Connections are available in between ticks
+    server.tick_sync(&mut handler, &mut state);
+
+    let ids: Vec<ConnectionID> = server.connections().map(|(id, _)| id).collect();
+    assert_eq!(ids, vec![ConnectionID(1), ConnectionID(2)]);
+
+    let info = server.connection_info(ConnectionID(1)).unwrap();
+    assert_eq!(info.state, ConnectionState::Connected);
+    assert_eq!(info.peer_addr, "127.0.0.1:1001".parse::<SocketAddr>().unwrap());
+    assert_eq!(info.packets_sent, 1);
+    assert!(server.connection_info(ConnectionID(3)).is_none());
+
+    // The information is cleared once the server shuts down
+    server.shutdown_sync(&mut handler, &mut state).unwrap();
+    assert_eq!(server.connections().count(), 0);
+
+}
+
+#[test]
+fn test_server_groups() {
+
+    let socket = MockSocket::from_address("127.0.0.1:0");
+    socket.receive(vec![
+        ("127.0.0.1:1000", id_packet(1)),
+        ("127.0.0.1:1001", id_packet(2)),
+        ("127.0.0.1:1002", id_packet(3))
+    ]);
+
+    let mut socket_handle = socket.handle();
+    let mut handler = MockControlledServerHandler {
+        connections: Vec::new(),
+        kicked: Vec::new()
+    };
+
+    let mut server = Server::new(Config::default());
+    let mut state = server.bind_to_socket_sync(&mut handler, socket).unwrap();
+    server.tick_sync(&mut handler, &mut state);
+    socket_handle.sent();
+
+    server.join_group("lobby", ConnectionID(3));
+    server.join_group("lobby", ConnectionID(1));
+    server.join_group("match", ConnectionID(2));
+    server.join_group("match", ConnectionID(3));
+    assert_eq!(server.group_members("lobby"), vec![ConnectionID(1), ConnectionID(3)]);
+    assert_eq!(server.groups_of(ConnectionID(3)), vec!["lobby".to_string(), "match".to_string()]);
+    assert!(server.group_members("spectators").is_empty());
+
+    // Messages are only queued for the members of the group
+    server.broadcast_group("lobby", MessageKind::Instant, b"Lobby".to_vec());
+    server.tick_sync(&mut handler, &mut state);
+
+    let mut ports: Vec<u16> = socket_handle.sent().into_iter().filter(|p| {
+        p.1.ends_with(b"Lobby")
+
+    }).map(|p| p.0.port()).collect();
+    ports.sort();
+    assert_eq!(ports, vec![1000, 1002]);
+
+    // Empty groups are removed
+    assert!(server.leave_group("match", ConnectionID(2)));
+    assert!(!server.leave_group("match", ConnectionID(2)));
+    assert_eq!(server.groups_of(ConnectionID(2)), Vec::<String>::new());
+
+    // Dropped connections leave all of their groups
+    server.kick(ConnectionID(3), 0);
+    server.tick_sync(&mut handler, &mut state);
+    thread::sleep(Duration::from_millis(30));
+    server.tick_sync(&mut handler, &mut state);
+    assert!(!state.connections().contains_key(&ConnectionID(3)));
+    assert_eq!(server.group_members("lobby"), vec![ConnectionID(1)]);
+    assert!(server.group_members("match").is_empty());
+
+}
+
+#[test]
+fn test_server_relay() {
+
+    let socket = MockSocket::from_address("127.0.0.1:0");
+    socket.receive(vec![
+        ("127.0.0.1:1000", id_packet(1)),
+        ("127.0.0.1:1001", id_packet(2)),
+        ("127.0.0.1:1002", id_packet(3))
+    ]);
+
+    let mut socket_handle = socket.handle();
+    let mut handler = MockControlledServerHandler {
+        connections: Vec::new(),
+        kicked: Vec::new()
+    };
+
+    let mut server = Server::new(Config::default());
+    let mut state = server.bind_to_socket_sync(&mut handler, socket).unwrap();
+    server.tick_sync(&mut handler, &mut state);
+    socket_handle.sent();
+
+    server.pair(ConnectionID(1), ConnectionID(2));
+    assert_eq!(server.paired(ConnectionID(1)), Some(ConnectionID(2)));
+    assert_eq!(server.paired(ConnectionID(2)), Some(ConnectionID(1)));
+    assert_eq!(server.paired(ConnectionID(3)), None);
+
+    // Messages are forwarded to the partner instead of the handler
+    socket_handle.receive(vec![("127.0.0.1:1000", vec![
+        1, 2, 3, 4,
+        0, 0, 0, 1,
+        1, 0,
+        0, 0, 0, 0,
+
+        // Hello World
+        0, 0, 0, 11, 72, 101, 108, 108, 111, 32, 87, 111, 114, 108, 100
+    ])]);
+    server.tick_sync(&mut handler, &mut state);
+    assert_eq!(state.connections_mut().get_mut(&ConnectionID(1)).unwrap().received().count(), 0);
+
+    let sent = socket_handle.sent();
+    let relayed: Vec<SocketAddr> = sent.iter().filter(|p| {
+        p.1.ends_with(b"Hello World")
+
+    }).map(|p| p.0).collect();
+    assert_eq!(relayed, vec!["127.0.0.1:1001".parse::<SocketAddr>().unwrap()]);
+
+    // Pairing with another connection dissolves the previous pairing
+    server.pair(ConnectionID(1), ConnectionID(3));
+    assert_eq!(server.paired(ConnectionID(2)), None);
+    assert_eq!(server.unpair(ConnectionID(3)), Some(ConnectionID(1)));
+    assert_eq!(server.paired(ConnectionID(1)), None);
+
+}
+
+#[test]
+fn test_server_broadcast_filtered() {
+
+    let socket = MockSocket::from_address("127.0.0.1:0");
+    socket.receive(vec![
+        ("127.0.0.1:1000", id_packet(1)),
+        ("127.0.0.1:1001", id_packet(2)),
+        ("127.0.0.1:1002", id_packet(3))
+    ]);
+
+    let mut socket_handle = socket.handle();
+    let mut handler = MockControlledServerHandler {
+        connections: Vec::new(),
+        kicked: Vec::new()
+    };
+
+    let mut server = Server::new(Config::default());
+    let handle = server.handle();
+    let mut state = server.bind_to_socket_sync(&mut handler, socket).unwrap();
+    server.tick_sync(&mut handler, &mut state);
+    socket_handle.sent();
+
+    // Skip the first connection and trim the message for the second one
+    server.broadcast_filtered(MessageKind::Instant, b"FooBar".to_vec(), |conn, payload| {
+        match conn.id() {
+            ConnectionID(1) => None,
+            ConnectionID(2) => Some(payload[..3].to_vec()),
+            _ => Some(payload.to_vec())
+        }
+    });
+
+    handle.broadcast_filtered(MessageKind::Instant, b"Baz".to_vec(), |conn, payload| {
+        if conn.id() == ConnectionID(1) {
+            Some(payload.to_vec())
+
+        } else {
+            None
+        }
+
+    }).unwrap();
+
+    server.tick_sync(&mut handler, &mut state);
+
+    let mut sent: Vec<(SocketAddr, Vec<u8>)> = socket_handle.sent().into_iter().map(|p| {
+        (p.0, p.1[14..].to_vec())
+
+    }).collect();
+    sent.sort_by_key(|p| p.0);
+
+    assert_eq!(sent, vec![
+        ("127.0.0.1:1000".parse().unwrap(), vec![0, 0, 0, 3, 66, 97, 122]),
+        ("127.0.0.1:1001".parse().unwrap(), vec![0, 0, 0, 3, 70, 111, 111]),
+        ("127.0.0.1:1002".parse().unwrap(), vec![0, 0, 0, 6, 70, 111, 111, 66, 97, 114])
+    ]);
+
+}
+
+#[test]
+fn test_server_handler_context_send() {
+
+    let socket = MockSocket::from_address("127.0.0.1:0");
+    socket.receive(vec![("127.0.0.1:1000", id_packet(1))]);
+
+    let mut socket_handle = socket.handle();
+    let mut handler = MockJoinNotifyServerHandler;
+    let mut server = Server::new(Config::default());
+    let mut state = server.bind_to_socket_sync(&mut handler, socket).unwrap();
+    server.tick_sync(&mut handler, &mut state);
+    socket_handle.sent();
+
+    // Messages queued for other connections from within callbacks are
+    // send with the same tick
+    socket_handle.receive(vec![("127.0.0.1:1001", id_packet(2))]);
+    server.tick_sync(&mut handler, &mut state);
+
+    let sent = socket_handle.sent();
+    let notified: Vec<&[u8]> = sent.iter().filter(|p| {
+        p.0 == "127.0.0.1:1000".parse::<SocketAddr>().unwrap()
+
+    }).map(|p| &p.1[14..]).collect();
+    assert_eq!(notified, vec![&[0, 0, 0, 1, 2][..]]);
+
+}
+
+#[test]
+fn test_server_aggregate_stats() {
+
+    let socket = MockSocket::from_address("127.0.0.1:0");
+    socket.receive(vec![
+        ("127.0.0.1:1000", id_packet(1)),
+        ("127.0.0.1:1001", id_packet(2))
+    ]);
+
+    let mut socket_handle = socket.handle();
+    let mut handler = MockControlledServerHandler {
+        connections: Vec::new(),
+        kicked: Vec::new()
+    };
+
+    let mut server = Server::new(Config::default());
+    let mut state = server.bind_to_socket_sync(&mut handler, socket).unwrap();
+    assert_eq!(server.aggregate_stats(state.connections()), ServerStats::default());
+
+    server.tick_sync(&mut handler, &mut state);
+
+    let sent = socket_handle.sent().len() as u64;
+    let stats = server.aggregate_stats(state.connections());
+    assert_eq!(stats.packets_received, 2);
+    assert_eq!(stats.bytes_received, 28);
+    assert_eq!(stats.packets_sent, sent);
+    assert!(stats.bytes_sent >= sent * 14);
+    assert_eq!(stats.active_connections, 2);
+    assert_eq!(stats.pending_connections, 0);
+    assert_eq!(stats.dropped_connections, 0);
+    assert_eq!(stats.packet_loss, 0.0);
+
+    // Dropped connections are counted once they are removed
+    server.kick(ConnectionID(2), 0);
+    server.tick_sync(&mut handler, &mut state);
+    thread::sleep(Duration::from_millis(30));
+    server.tick_sync(&mut handler, &mut state);
+
+    let stats = server.aggregate_stats(state.connections());
+    assert_eq!(stats.active_connections, 1);
+    assert_eq!(stats.dropped_connections, 1);
+
+}
+
+#[test]
+fn test_server_bind_multiple() {
+
+    let mut handler = MockControlledServerHandler {
+        connections: Vec::new(),
+        kicked: Vec::new()
+    };
+
+    let mut server = Server::new(Config::default());
+    let mut state = server.bind_multiple_sync(
+        &mut handler, &["127.0.0.1:0", "127.0.0.1:0"]
+
+    ).unwrap();
+
+    let addrs = state.socket().local_addrs().unwrap();
+    assert_eq!(server.local_addr().unwrap(), addrs[0]);
+
+    // Replies are send from the address the connection arrived on
+    let client = net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    client.set_read_timeout(Some(Duration::from_millis(500))).unwrap();
+    client.send_to(&id_packet(1), addrs[1]).unwrap();
+    thread::sleep(Duration::from_millis(20));
+    server.tick_sync(&mut handler, &mut state);
+
+    let mut buffer = [0; 64];
+    let (_, from) = client.recv_from(&mut buffer).unwrap();
+    assert_eq!(from, addrs[1]);
+    assert_eq!(state.connections().len(), 1);
+
+}
+
+#[test]
+fn test_server_bind_dual_stack() {
+
+    let mut handler = MockControlledServerHandler {
+        connections: Vec::new(),
+        kicked: Vec::new()
+    };
+
+    let mut server = Server::new(Config::default());
+    let mut state = server.bind_dual_stack_sync(&mut handler, 0).unwrap();
+    let port = server.local_addr().unwrap().port();
+
+    let v4 = net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    let v6 = net::UdpSocket::bind("[::1]:0").unwrap();
+    v4.set_read_timeout(Some(Duration::from_millis(500))).unwrap();
+    v6.set_read_timeout(Some(Duration::from_millis(500))).unwrap();
+    v4.send_to(&id_packet(1), ("127.0.0.1", port)).unwrap();
+    v6.send_to(&id_packet(2), ("::1", port)).unwrap();
+    thread::sleep(Duration::from_millis(20));
+    server.tick_sync(&mut handler, &mut state);
+
+    // IPv4 clients are known by their plain IPv4 address
+    let mut peers: Vec<SocketAddr> = state.connections().values().map(|conn| {
+        conn.peer_addr()
+
+    }).collect();
+    peers.sort();
+    assert_eq!(peers, vec![v4.local_addr().unwrap(), v6.local_addr().unwrap()]);
+
+    // Both families receive replies
+    let mut buffer = [0; 64];
+    assert!(v4.recv_from(&mut buffer).is_ok());
+    assert!(v6.recv_from(&mut buffer).is_ok());
+
+}
+
+#[test]
+fn test_server_set_config() {
+
+    let socket = MockSocket::from_address("127.0.0.1:0");
+    socket.receive(vec![("127.0.0.1:1000", id_packet(1))]);
+
+    let mut handler = MockControlledServerHandler {
+        connections: Vec::new(),
+        kicked: Vec::new()
+    };
+
+    let mut server = Server::new(Config::default());
+    let handle = server.handle();
+    let mut state = server.bind_to_socket_sync(&mut handler, socket).unwrap();
+    server.tick_sync(&mut handler, &mut state);
+
+    let config = Config {
+        send_rate: 60,
+        connection_drop_threshold: 5000,
+        .. Config::default()
+    };
+
+    // The server's own configuration is replaced right away
+    server.set_config(config);
+    assert_eq!(server.config(), config);
+    assert_eq!(state.connections()[&ConnectionID(1)].config(), Config::default());
+
+    // Connections are updated with the next tick, without being reset
+    server.tick_sync(&mut handler, &mut state);
+    let conn = &state.connections()[&ConnectionID(1)];
+    assert_eq!(conn.config(), config);
+    assert_eq!(conn.state(), ConnectionState::Connected);
+
+    // Handles can update the configuration from other threads
+    let config = Config {
+        send_rate: 20,
+        .. config
+    };
+    handle.set_config(config).unwrap();
+    server.tick_sync(&mut handler, &mut state);
+    assert_eq!(server.config(), config);
+    assert_eq!(state.connections()[&ConnectionID(1)].config(), config);
+
+}
+
+#[test]
+fn test_server_bandwidth_limit() {
+
+    let socket = MockSocket::from_address("127.0.0.1:0");
+    socket.receive(vec![
+        ("127.0.0.1:1000", id_packet(1)),
+        ("127.0.0.1:1001", id_packet(2))
+    ]);
+
+    let mut handler = MockControlledServerHandler {
+        connections: Vec::new(),
+        kicked: Vec::new()
+    };
+
+    let mut server = Server::new(Config::default());
+    let handle = server.handle();
+    let mut state = server.bind_to_socket_sync(&mut handler, socket).unwrap();
+    server.tick_sync(&mut handler, &mut state);
+
+    server.set_bandwidth_limit(ConnectionID(1), 100);
+    handle.set_bandwidth_limit(ConnectionID(3), 100).unwrap();
+    for _ in 0..4 {
+        server.broadcast(MessageKind::Reliable, vec![0; 500]);
+    }
+
+    // Messages exceeding the cap remain queued on the capped connection
+    server.tick_sync(&mut handler, &mut state);
+    server.tick_sync(&mut handler, &mut state);
+    let capped = &state.connections()[&ConnectionID(1)];
+    assert_eq!(capped.bandwidth_limit(), 100);
+    assert_eq!(capped.dump_state().queued_reliable, 2);
+
+    let uncapped = &state.connections()[&ConnectionID(2)];
+    assert_eq!(uncapped.bandwidth_limit(), 0);
+    assert_eq!(uncapped.dump_state().queued_reliable, 0);
+
+}
+
+#[test]
+fn test_server_outgoing_bandwidth() {
+
+    let socket = MockSocket::from_address("127.0.0.1:0");
+    socket.receive(vec![
+        ("127.0.0.1:1000", id_packet(1)),
+        ("127.0.0.1:1001", id_packet(2))
+    ]);
+
+    let mut socket_handle = socket.handle();
+    let mut handler = MockControlledServerHandler {
+        connections: Vec::new(),
+        kicked: Vec::new()
+    };
+
+    // 400 bytes per tick
+    let mut server = Server::new(Config {
+        send_rate: 10,
+        max_outgoing_bandwidth: 4000,
+        .. Config::default()
+    });
+
+    let mut state = server.bind_to_socket_sync(&mut handler, socket).unwrap();
+    server.tick_sync(&mut handler, &mut state);
+    socket_handle.sent();
+
+    for _ in 0..4 {
+        server.broadcast(MessageKind::Reliable, vec![0; 100]);
+    }
+
+    // The budget is shared between all connections
+    server.tick_sync(&mut handler, &mut state);
+    let sent = socket_handle.sent();
+    assert_eq!(sent.len(), 2);
+    assert!(sent.iter().map(|packet| packet.1.len()).sum::<usize>() <= 400);
+
+    let queued: Vec<usize> = [ConnectionID(1), ConnectionID(2)].iter().map(|id| {
+        state.connections()[id].dump_state().queued_reliable
+
+    }).collect();
+    assert!(queued.iter().all(|queued| *queued > 0 && *queued < 4));
+
+    // Without a budget all messages are send right away
+    server.set_config(Config {
+        send_rate: 10,
+        .. Config::default()
+    });
+    server.tick_sync(&mut handler, &mut state);
+    assert!(state.connections().values().all(|conn| {
+        conn.dump_state().queued_reliable == 0
+    }));
+
+}
+
+#[test]
+fn test_server_connection_request() {
+
+    let socket = MockSocket::from_address("127.0.0.1:0");
+    socket.receive(vec![
+        ("127.0.0.1:1000", id_packet(1)),
+        ("127.0.0.2:1000", id_packet(2)),
+        ("127.0.0.1:1000", id_packet(1))
+    ]);
+
+    let mut handler = MockAdmissionServerHandler {
+        connections: Vec::new(),
+        requests: Vec::new()
+    };
+
+    let mut server = Server::new(Config::default());
+    server.bind_to_socket(&mut handler, socket).unwrap();
+
+    // Only packets for unknown connection ids are passed to the handler
+    assert_eq!(handler.requests, vec![
+        ("127.0.0.1:1000".parse().unwrap(), ConnectionID(1), 14),
+        ("127.0.0.2:1000".parse().unwrap(), ConnectionID(2), 14)
+    ]);
+
+    assert_eq!(handler.connections, vec![ConnectionID(1)]);
+
+}
+
+#[test]
+fn test_server_worker_threads() {
+
+    let mut packets = Vec::new();
+    for id in 1..6 {
+        packets.push((format!("127.0.0.1:100{}", id), id_packet(id)));
+        packets.push((format!("127.0.0.1:100{}", id), vec![
+            1, 2, 3, 4,
+            0, 0, 0, id,
+            1, 0,
+            0, 0, 0, 0,
+            0, 0, 0, 2, 72, id
+        ]));
+    }
+
+    let socket = MockSocket::from_address("127.0.0.1:0");
+    socket.receive(packets);
+
+    let mut socket_handle = socket.handle();
+    let mut handler = MockControlledServerHandler {
+        connections: Vec::new(),
+        kicked: Vec::new()
+    };
+
+    let mut server = Server::new(Config {
+        worker_threads: 3,
+        send_order: SendOrder::Rotate,
+        .. Config::default()
+    });
+
+    let mut state = server.bind_to_socket_sync(&mut handler, socket).unwrap();
+    server.tick_sync(&mut handler, &mut state);
+
+    // Handler callbacks are invoked once the workers are done
+    handler.connections.sort_by_key(|id| id.0);
+    assert_eq!(handler.connections, (1..6).map(ConnectionID).collect::<Vec<_>>());
+
+    // Each connection parsed its own packets
+    for id in 1..6 {
+        let dump = state.connections()[&ConnectionID(id)].dump_state();
+        assert_eq!(dump.packets_received, 2);
+    }
+
+    // Packets are still send in the configured order
+    let ports: Vec<u16> = socket_handle.sent().into_iter().map(|p| p.0.port()).collect();
+    assert_eq!(ports, vec![1001, 1002, 1003, 1004, 1005]);
+
+    server.send(ConnectionID(3), MessageKind::Instant, b"Work".to_vec());
+    server.tick_sync(&mut handler, &mut state);
+
+    let sent = socket_handle.sent();
+    let ports: Vec<u16> = sent.iter().map(|p| p.0.port()).collect();
+    assert_eq!(ports, vec![1002, 1003, 1004, 1005, 1001]);
+    assert!(sent[1].1.ends_with(b"Work"));
+
+}
+
+#[test]
+fn test_server_worker_threads_keep_alive() {
+
+    let mut packets = Vec::new();
+    for id in 1..4 {
+        packets.push((format!("127.0.0.1:100{}", id), id_packet(id)));
+        packets.push((format!("127.0.0.1:100{}", id), vec![
+            1, 2, 3, 4,
+            0, 0, 0, id,
+            1, 0,
+            0, 0, 0, 0,
+
+            // Keep alive request for 30 seconds
+            8, 0, 0, 5, 17, 0, 0, 117, 48
+        ]));
+    }
+
+    let socket = MockSocket::from_address("127.0.0.1:0");
+    socket.receive(packets);
+
+    let mut handler = MockKeepAliveServerHandler {
+        keep_alive: Vec::new()
+    };
+
+    let mut server = Server::new(Config {
+        worker_threads: 2,
+        max_keep_alive_extension: 10000,
+        .. Config::default()
+    });
+
+    let mut state = server.bind_to_socket_sync(&mut handler, socket).unwrap();
+    server.tick_sync(&mut handler, &mut state);
+
+    // Grants made on the workers are reported to the handler
+    handler.keep_alive.sort_by_key(|k| (k.0).0);
+    assert_eq!(handler.keep_alive, vec![
+        (ConnectionID(1), 10000),
+        (ConnectionID(2), 10000),
+        (ConnectionID(3), 10000)
+    ]);
+
+}
+
+#[test]
+fn test_server_worker_threads_send_error() {
+
+    struct FailingSocket(MockSocket);
+
+    impl Socket for FailingSocket {
+
+        fn try_recv(&mut self) -> Result<(SocketAddr, Vec<u8>), TryRecvError> {
+            self.0.try_recv()
+        }
+
+        fn send_to(&mut self, _: &[u8], _: SocketAddr) -> Result<usize, io::Error> {
+            Err(io::Error::new(ErrorKind::AddrNotAvailable, ""))
+        }
+
+        fn local_addr(&self) -> Result<SocketAddr, io::Error> {
+            self.0.local_addr()
+        }
+
+    }
+
+    let socket = MockSocket::from_address("127.0.0.1:0");
+    socket.receive(vec![
+        ("127.0.0.1:1001", id_packet(1)),
+        ("127.0.0.1:1002", id_packet(2))
+    ]);
+
+    let mut handler = MockControlledServerHandler {
+        connections: Vec::new(),
+        kicked: Vec::new()
+    };
+
+    let mut server = Server::new(Config {
+        worker_threads: 2,
+        .. Config::default()
+    });
+
+    // Send errors are recorded on the connections instead of panicking
+    let mut state = server.bind_to_socket_sync(&mut handler, FailingSocket(socket)).unwrap();
+    server.tick_sync(&mut handler, &mut state);
+
+    for id in 1..3 {
+        let conn = state.connections_mut().get_mut(&ConnectionID(id)).unwrap();
+        assert_eq!(conn.take_send_error(), Some(ErrorKind::AddrNotAvailable));
+    }
+
+}
+
+#[test]
+fn test_server_std_socket() {
+
+    let mut handler = MockControlledServerHandler {
+        connections: Vec::new(),
+        kicked: Vec::new()
+    };
+
+    // Sockets configured outside of the library can drive a server
+    let config = Config::default();
+    let socket = net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    let addr = socket.local_addr().unwrap();
+    let socket = UdpSocket::from_std(socket, config.packet_max_size).unwrap();
+
+    let mut server = Server::new(config);
+    let mut state = server.bind_to_socket_sync(&mut handler, socket).unwrap();
+    assert_eq!(server.local_addr().unwrap(), addr);
+
+    // The socket is switched into non-blocking mode
+    server.tick_sync(&mut handler, &mut state);
+
+    let client = net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    client.send_to(&id_packet(1), addr).unwrap();
+    thread::sleep(Duration::from_millis(20));
+    server.tick_sync(&mut handler, &mut state);
+    assert_eq!(handler.connections, vec![ConnectionID(1)]);
+
+}
+
+#[test]
+fn test_server_pending_connections() {
+
+    let socket = MockSocket::from_address("127.0.0.1:0");
+    socket.receive(vec![
+        ("127.0.0.1:1000", id_packet(1)),
+        ("127.0.0.1:1001", id_packet(2)),
+        ("127.0.0.1:1002", id_packet(3))
+    ]);
+
+    let socket_handle = socket.handle();
+    let mut handler = MockPendingServerHandler {
+        connections: Vec::new(),
+        failed: Vec::new(),
+        dropped: Vec::new()
+    };
+
+    let mut server = Server::new(Config {
+        pending_connection_timeout: 20,
+        max_pending_connections: 2,
+        .. Config::default()
+    });
+
+    let mut state = server.bind_to_socket_sync(&mut handler, socket).unwrap();
+    server.tick_sync(&mut handler, &mut state);
+
+    // Further unacknowledged connections are refused
+    assert_eq!(handler.connections, vec![ConnectionID(1), ConnectionID(2)]);
+    assert_eq!(handler.dropped, vec![
+        ("127.0.0.1:1002".parse().unwrap(), PacketDropReason::RateLimited)
+    ]);
+
+    // Acknowledged connections are no longer pending
+    socket_handle.receive(vec![("127.0.0.1:1001", vec![
+        1, 2, 3, 4,
+        0, 0, 0, 2,
+        1, 0,
+        0, 0, 0, 0
+    ])]);
+    server.tick_sync(&mut handler, &mut state);
+    assert!(!state.connections()[&ConnectionID(1)].acknowledged());
+    assert!(state.connections()[&ConnectionID(2)].acknowledged());
+
+    // Unacknowledged connections expire
+    thread::sleep(Duration::from_millis(30));
+    server.tick_sync(&mut handler, &mut state);
+    assert!(!state.connections().contains_key(&ConnectionID(1)));
+    assert!(state.connections().contains_key(&ConnectionID(2)));
+    assert_eq!(handler.failed, vec![ConnectionID(1)]);
+    assert_eq!(server.aggregate_stats(state.connections()).dropped_connections, 1);
+
+}
+
+#[test]
+fn test_server_connection_attempt_failed() {
+
+    let socket = MockSocket::from_address("127.0.0.1:0");
+    socket.receive(vec![
+        ("127.0.0.1:1000", id_packet(1)),
+        ("127.0.0.1:1001", id_packet(3)),
+        ("127.0.0.1:1003", id_packet(2)),
+        ("127.0.0.1:1004", id_packet(4))
+    ]);
+
+    let mut handler = MockAttemptFailedServerHandler {
+        failed: Vec::new(),
+        stats: Vec::new()
+    };
+
+    let mut server = Server::new(Config {
+        pending_connection_timeout: 20,
+        max_connections: 2,
+        .. Config::default()
+    });
+
+    let mut state = server.bind_to_socket_sync(&mut handler, socket).unwrap();
+    server.tick_sync(&mut handler, &mut state);
+
+    // Refused attempts are reported right away
+    assert_eq!(handler.failed, vec![
+        ("127.0.0.1:1001".parse().unwrap(), ConnectionFailure::Denied),
+        ("127.0.0.1:1004".parse().unwrap(), ConnectionFailure::ServerFull)
+    ]);
+
+    // Connections which never complete their handshake are reported once
+    // they expire
+    handler.failed.clear();
+    thread::sleep(Duration::from_millis(30));
+    server.tick_sync(&mut handler, &mut state);
+    handler.failed.sort_by_key(|failed| failed.0);
+    assert_eq!(handler.failed, vec![
+        ("127.0.0.1:1000".parse().unwrap(), ConnectionFailure::HandshakeTimeout),
+        ("127.0.0.1:1003".parse().unwrap(), ConnectionFailure::HandshakeTimeout)
+    ]);
+
+}
+
+#[test]
+fn test_server_connection_churn() {
+
+    let socket = MockSocket::from_address("127.0.0.1:0");
+    socket.receive(vec![
+        ("127.0.0.1:1000", id_packet(1)),
+        ("127.0.0.1:1001", id_packet(3)),
+        ("127.0.0.1:1003", id_packet(2)),
+        ("127.0.0.1:1004", id_packet(4))
+    ]);
+
+    let mut handler = MockAttemptFailedServerHandler {
+        failed: Vec::new(),
+        stats: Vec::new()
+    };
+
+    let mut server = Server::new(Config {
+        pending_connection_timeout: 20,
+        max_connections: 2,
+        .. Config::default()
+    });
+
+    let mut state = server.bind_to_socket_sync(&mut handler, socket).unwrap();
+    server.tick_sync(&mut handler, &mut state);
+
+    let stats = server.stats();
+    assert_eq!(stats.connects(), 2);
+    assert_eq!(stats.rejections(), 2);
+    assert_eq!(stats.timeouts(), 0);
+    assert_eq!(stats.disconnects(), 0);
+
+    // Expired connections count as timeouts
+    thread::sleep(Duration::from_millis(30));
+    server.tick_sync(&mut handler, &mut state);
+    assert_eq!(server.stats().timeouts(), 2);
+    assert!(handler.stats.is_empty());
+
+    // The statistics are passed to the handler once per second
+    thread::sleep(Duration::from_secs(1));
+    server.tick_sync(&mut handler, &mut state);
+    assert_eq!(handler.stats.len(), 1);
+    assert_eq!(handler.stats[0].connects(), 2);
+    assert_eq!(handler.stats[0].timeouts(), 2);
+
+}
+
+#[test]
+fn test_server_id_validation() {
+
+    let socket = MockSocket::from_address("127.0.0.1:0");
+    socket.receive(vec![
+        ("127.0.0.1:1000", id_packet(1)),
+        ("127.0.0.1:1001", id_packet(2)),
+        ("127.0.0.1:1002", id_packet(3)),
+        ("127.0.0.1:1003", id_packet(4))
+    ]);
+
+    let mut handler = MockIdValidationServerHandler {
+        connections: Vec::new(),
+        failed: Vec::new()
+    };
+
+    let mut server = Server::new(Config::default());
+    let mut state = server.bind_to_socket_sync(&mut handler, socket).unwrap();
+    server.tick_sync(&mut handler, &mut state);
+
+    // Only ids accepted by the handler's generator create connections
+    handler.connections.sort_by_key(|id| id.0);
+    assert_eq!(handler.connections, vec![ConnectionID(2), ConnectionID(4)]);
+    assert_eq!(handler.failed, vec![
+        ("127.0.0.1:1000".parse().unwrap(), ConnectionFailure::Denied),
+        ("127.0.0.1:1002".parse().unwrap(), ConnectionFailure::Denied)
+    ]);
+
+}
+
+#[cfg(feature = "authentication")]
+#[test]
+fn test_server_id_collision() {
+
+    fn signed(mut packet: Vec<u8>) -> Vec<u8> {
+        authentication::sign(&[9; 32], &mut packet);
+        packet
+    }
+
+    fn forged_response(id: u8) -> Vec<u8> {
+        signed(vec![
+            1, 2, 3, 4,
+            0, 0, 0, id,
+            1, 0,
+            0, 0, 0, 0,
+
+            8, 0, 0, 17, 4, 1, 2, 3, 4, 5, 6, 7, 8, 1, 2, 3, 4, 5, 6, 7, 8
+        ])
+    }
+
+    let socket = MockSocket::from_address("127.0.0.1:0");
+    socket.receive(vec![
+        ("127.0.0.1:1000", signed(id_packet(1))),
+        ("127.0.0.1:1002", signed(id_packet(2)))
+    ]);
+
+    let socket_handle = socket.handle();
+    let mut handler = MockIdCollisionServerHandler {
+        collisions: Vec::new()
+    };
+
+    let mut server = Server::new(Config {
+        migration_validation: true,
+        authentication_key: Some([9; 32]),
+        id_collision_policy: IdCollisionPolicy::Handler,
+        .. Config::default()
+    });
+
+    let mut state = server.bind_to_socket_sync(&mut handler, socket).unwrap();
+    server.tick_sync(&mut handler, &mut state);
+
+    // Peers which fail to prove that they own the ids are reported
+    socket_handle.receive(vec![
+        ("127.0.0.1:1001", forged_response(1)),
+        ("127.0.0.1:1003", forged_response(2))
+    ]);
+    server.tick_sync(&mut handler, &mut state);
+
+    handler.collisions.sort_by_key(|collision| collision.0 .0);
+    assert_eq!(handler.collisions, vec![
+        (ConnectionID(1), "127.0.0.1:1001".parse().unwrap()),
+        (ConnectionID(2), "127.0.0.1:1003".parse().unwrap())
+    ]);
+
+    // And handled according to the policy returned by the handler
+    let closed = &state.connections()[&ConnectionID(1)];
+    assert_eq!(closed.state(), ConnectionState::Closing);
+    assert_eq!(closed.peer_addr(), "127.0.0.1:1000".parse().unwrap());
+
+    let kept = &state.connections()[&ConnectionID(2)];
+    assert_eq!(kept.state(), ConnectionState::Connected);
+    assert_eq!(kept.peer_addr(), "127.0.0.1:1002".parse().unwrap());
+
+}
+
+#[test]
+fn test_server_receive_sync() {
+
+    let socket = MockSocket::from_address("127.0.0.1:0");
+    let mut socket_handle = socket.handle();
+    let mut handler = MockPendingServerHandler {
+        connections: Vec::new(),
+        failed: Vec::new(),
+        dropped: Vec::new()
+    };
+
+    let mut server = Server::new(Config::default());
+    let mut state = server.bind_to_socket_sync(&mut handler, socket).unwrap();
+
+    // Packets are received and parsed in between ticks
+    socket_handle.receive(vec![("127.0.0.1:1000", id_packet(1))]);
+    server.receive_sync(&mut handler, &mut state);
+    assert_eq!(handler.connections, vec![ConnectionID(1)]);
+    assert!(state.connections().contains_key(&ConnectionID(1)));
+
+    // But nothing is send until the next tick
+    assert!(socket_handle.sent().is_empty());
+    assert_eq!(server.stats().bytes_received, 0);
+
+    server.tick_sync(&mut handler, &mut state);
+    assert_eq!(socket_handle.sent().len(), 1);
+    assert_eq!(server.stats().bytes_received, 14);
+
+}
+
+#[test]
+fn test_server_run_once() {
+
+    let socket = MockSocket::from_address("127.0.0.1:0");
+    socket.receive(vec![("127.0.0.1:1000", id_packet(1))]);
+
+    let mut socket_handle = socket.handle();
+    let mut handler = MockPendingServerHandler {
+        connections: Vec::new(),
+        failed: Vec::new(),
+        dropped: Vec::new()
+    };
+
+    let mut server = Server::new(Config::default());
+    let handle = server.handle();
+    let mut state = server.bind_to_socket_sync(&mut handler, socket).unwrap();
+
+    // Each iteration receives, ticks and sends
+    assert!(server.run_once(&mut handler, &mut state));
+    assert_eq!(handler.connections, vec![ConnectionID(1)]);
+    assert_eq!(socket_handle.sent().len(), 1);
+
+    assert!(server.run_once(&mut handler, &mut state));
+    assert_eq!(socket_handle.sent().len(), 1);
+
+    // Shutdown requests are honored like with the internal loop
+    handle.shutdown().unwrap();
+    assert!(!server.run_once(&mut handler, &mut state));
+    assert!(server.local_addr().is_err());
+    assert!(socket_handle.sent().is_empty());
+
+    assert!(!server.run_once(&mut handler, &mut state));
+
+}
+
+#[test]
+fn test_udp_socket_wait() {
+
+    let mut socket = UdpSocket::new("127.0.0.1:0", 1400).unwrap();
+    let addr = socket.local_addr().unwrap();
+
+    // Waiting blocks for most of the timeout without any packets
+    let start = Instant::now();
+    socket.wait(Duration::from_millis(30));
+    assert!(start.elapsed() >= Duration::from_millis(20));
+
+    // But returns as soon as a packet can be received
+    let client = net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    client.send_to(&id_packet(1), addr).unwrap();
+
+    let start = Instant::now();
+    socket.wait(Duration::from_millis(1000));
+    assert!(start.elapsed() < Duration::from_millis(500));
+
+    // The socket stays in non-blocking mode
+    assert_eq!(socket.try_recv().unwrap().1, id_packet(1));
+    assert!(socket.try_recv().is_err());
+
+}
+
+#[test]
+fn test_server_info_query() {
+
+    let config = Config::default();
+    let query = Connection::server_info_query_packet(&config, 7);
+
+    let socket = MockSocket::from_address("127.0.0.1:0");
+    socket.receive(vec![
+        ("127.0.0.1:1000", query.clone()),
+
+        // Responses are limited per address
+        ("127.0.0.1:1001", query.clone()),
+        ("127.0.0.2:1000", query)
+    ]);
+
+    let mut socket_handle = socket.handle();
+    let mut server = Server::new(config);
+    let mut handler = MockServerInfoHandler {
+        queries: 0
+    };
+    server.bind_to_socket(&mut handler, socket).unwrap();
+    assert_eq!(handler.queries, 2);
+
+    // Queries are answered without creating any connections
+    let responses: Vec<_> = socket_handle.sent().into_iter().map(|p| {
+        (p.0, Connection::server_info_from_packet(&config, 7, &p.1))
+
+    }).collect();
+
+    assert_eq!(responses, vec![
+        ("127.0.0.1:1000".parse::<SocketAddr>().unwrap(), Some(b"Info".to_vec())),
+        ("127.0.0.2:1000".parse::<SocketAddr>().unwrap(), Some(b"Info".to_vec()))
+    ]);
+
+}
+
+#[cfg(feature = "authentication")]
+#[test]
+fn test_server_handshake_cookies() {
+
+    let socket = MockSocket::from_address("127.0.0.1:0");
+    socket.receive(vec![
+        ("127.0.0.1:1000", id_packet(1)),
+        ("127.0.0.1:1001", id_packet(2))
+    ]);
+
+    let mut handler = MockHandshakeCookieServerHandler {
+        socket_handle: socket.handle(),
+        cookies: Vec::new(),
+        connections: Vec::new()
+    };
+
+    let mut server = Server::new(Config {
+        handshake_cookies: true,
+        .. Config::default()
+    });
+    server.bind_to_socket(&mut handler, socket).unwrap();
+
+    // First packets are answered with a cookie instead of a connection
+    assert_eq!(handler.cookies.len(), 2);
+    for (cookie, id) in handler.cookies.iter().zip([1, 2].iter()) {
+        assert_eq!(cookie.1.len(), 32);
+        assert_eq!(cookie.1[..12].to_vec(), vec![
+            1, 2, 3, 4,
+            255, 255, 255, 254,
+            0, 0, 0, *id
+        ]);
+    }
+
+    // Only echoing a valid cookie creates a connection
+    assert_eq!(handler.connections, vec![ConnectionID(1)]);
+
+    // Tampered cookies are answered with a new cookie
+    let resent: Vec<u8> = handler.socket_handle.sent().into_iter().filter(|p| {
+        p.1.len() == 32 && p.1[4..8] == [255, 255, 255, 254]
+
+    }).map(|p| p.1[11]).collect();
+    assert_eq!(resent, vec![2]);
+
+}
+
+#[test]
+fn test_server_ban_list() {
+
+    let socket = MockSocket::from_address("127.0.0.1:0");
+    socket.receive(vec![
+        ("127.0.0.1:1000", id_packet(1)),
+        ("127.0.0.2:1000", id_packet(2)),
+        ("127.0.0.3:1000", id_packet(3)),
+        ("127.0.0.4:1000", id_packet(4)),
+        ("127.0.0.5:1000", id_packet(5)),
+        ("127.0.0.6:1000", id_packet(6))
+    ]);
+
+    let mut server = Server::new(Config::default());
+
+    // Packets from banned sources never create a connection
+    server.ban_address("127.0.0.4".parse().unwrap(), None);
+    server.ban_connection(ConnectionID(5), None);
+
+    // Expired and lifted bans have no effect
+    server.ban_connection(ConnectionID(1), Some(Duration::from_secs(0)));
+    server.ban_address("127.0.0.6".parse().unwrap(), None);
+    assert!(server.unban_address("127.0.0.6".parse().unwrap()));
+    assert!(!server.unban_address("127.0.0.6".parse().unwrap()));
+
+    assert!(server.is_address_banned("127.0.0.4".parse().unwrap()));
+    assert!(server.is_connection_banned(ConnectionID(5)));
+    assert!(!server.is_connection_banned(ConnectionID(1)));
+
+    let mut handler = MockBanServerHandler {
+        connections: Vec::new(),
+        banned: Vec::new(),
+        closed: Vec::new(),
+        ticks: 0
+    };
+    server.bind_to_socket(&mut handler, socket).unwrap();
+
+    handler.connections.sort_by_key(|id| id.0);
+    assert_eq!(handler.connections, vec![
+        ConnectionID(1), ConnectionID(2), ConnectionID(3), ConnectionID(6)
+    ]);
+
+    // Existing connections are closed once they get banned
+    handler.banned.sort_by_key(|id| id.0);
+    handler.closed.sort_by_key(|id| id.0);
+    assert_eq!(handler.banned, vec![ConnectionID(2), ConnectionID(3)]);
+    assert_eq!(handler.closed, vec![ConnectionID(2), ConnectionID(3)]);
+
+    assert!(server.unban_connection(ConnectionID(3)));
+    assert!(!server.is_connection_banned(ConnectionID(3)));
+
+}
+
+
+#[test]
+fn test_server_ban_events() {
+
+    let socket = MockSocket::from_address("127.0.0.1:0");
+    let mut handler = MockBanEventServerHandler {
+        applied: Vec::new(),
+        lifted: Vec::new()
+    };
+
+    let mut server = Server::new(Config::default());
+    let mut state = server.bind_to_socket_sync(&mut handler, socket).unwrap();
+
+    let ip: net::IpAddr = "127.0.0.4".parse().unwrap();
+    server.ban_address(ip, None);
+    server.ban_connection(ConnectionID(5), Some(Duration::from_millis(20)));
+    assert!(!server.unban_connection(ConnectionID(6)));
+
+    // Bans are reported with the next tick
+    assert!(handler.applied.is_empty());
+    server.tick_sync(&mut handler, &mut state);
+    assert_eq!(handler.applied, vec![
+        (BanTarget::Address(ip), None),
+        (BanTarget::Connection(ConnectionID(5)), Some(Duration::from_millis(20)))
+    ]);
+    assert!(handler.lifted.is_empty());
+
+    // Temporary bans are lifted once they expire
+    thread::sleep(Duration::from_millis(30));
+    server.tick_sync(&mut handler, &mut state);
+    assert!(!server.is_connection_banned(ConnectionID(5)));
+    assert_eq!(handler.lifted, vec![BanTarget::Connection(ConnectionID(5))]);
+
+    // Explicitly lifted bans are reported as well
+    assert!(server.unban_address(ip));
+    server.tick_sync(&mut handler, &mut state);
+    assert_eq!(handler.lifted, vec![
+        BanTarget::Connection(ConnectionID(5)),
+        BanTarget::Address(ip)
+    ]);
+    assert_eq!(handler.applied.len(), 2);
+
+}
+#[test]
+fn test_server_whitelist_only() {
+
+    let socket = MockSocket::from_address("127.0.0.1:0");
+    socket.receive(vec![
+        ("127.0.0.1:1000", id_packet(1)),
+        ("127.0.0.2:1000", id_packet(2)),
+        ("127.0.0.3:1000", id_packet(3)),
+        ("127.0.0.4:1000", id_packet(4))
+    ]);
+
+    let mut server = Server::new(Config {
+        whitelist_only: true,
+        .. Config::default()
+    });
+
+    // Sources are allowed by either their address or their connection id
+    server.allow_address("127.0.0.1".parse().unwrap());
+    server.allow_connection(ConnectionID(3));
+
+    // Removed sources are no longer allowed
+    server.allow_address("127.0.0.4".parse().unwrap());
+    assert!(server.disallow_address("127.0.0.4".parse().unwrap()));
+    assert!(!server.disallow_address("127.0.0.4".parse().unwrap()));
+
+    assert!(server.is_whitelisted("127.0.0.1".parse().unwrap(), ConnectionID(2)));
+    assert!(server.is_whitelisted("127.0.0.2".parse().unwrap(), ConnectionID(3)));
+    assert!(!server.is_whitelisted("127.0.0.2".parse().unwrap(), ConnectionID(2)));
+
+    let mut handler = MockConnectionCountServerHandler {
+        connections: Vec::new()
+    };
+    server.bind_to_socket(&mut handler, socket).unwrap();
+
+    handler.connections.sort_by_key(|id| id.0);
+    assert_eq!(handler.connections, vec![ConnectionID(1), ConnectionID(3)]);
+
+    assert!(server.disallow_connection(ConnectionID(3)));
+    assert!(!server.is_whitelisted("127.0.0.3".parse().unwrap(), ConnectionID(3)));
+
+    // Without the whitelist mode all sources are allowed
+    let server = Server::new(Config::default());
+    assert!(server.is_whitelisted("127.0.0.2".parse().unwrap(), ConnectionID(2)));
+
+}
+
+#[test]
+fn test_server_flood_limit() {
+
+    let socket = MockSocket::from_address("127.0.0.1:0");
+    socket.receive(vec![
+        ("127.0.0.1:1000", id_packet(1)),
+        ("127.0.0.1:1001", id_packet(2)),
+        ("127.0.0.1:1002", id_packet(3)),
+        ("127.0.0.1:1003", id_packet(4)),
+        ("127.0.0.1:1004", id_packet(5)),
+        ("127.0.0.2:1000", id_packet(6))
+    ]);
+
+    let mut handler = MockFloodServerHandler {
+        connections: Vec::new(),
+        throttled: Vec::new()
+    };
+
+    let mut server = Server::new(Config {
+        flood_packet_rate: 1,
+        flood_packet_burst: 2,
+        .. Config::default()
+    });
+    server.bind_to_socket(&mut handler, socket).unwrap();
+
+    // Only the burst of packets from each address is accepted
+    handler.connections.sort_by_key(|id| id.0);
+    assert_eq!(handler.connections, vec![
+        ConnectionID(1), ConnectionID(2), ConnectionID(6)
+    ]);
+
+    // Throttled addresses are reported along with their dropped packets
+    assert_eq!(handler.throttled, vec![("127.0.0.1".parse().unwrap(), 3)]);
+
+}
+
+#[test]
+fn test_server_packet_drops() {
+
+    let socket = MockSocket::from_address("127.0.0.1:0");
+    socket.receive(vec![
+        ("127.0.0.1:1000", id_packet(1)),
+        ("127.0.0.2:1000", id_packet(2)),
+        ("127.0.0.1:1001", vec![
+            9, 9, 9, 9,
+            0, 0, 0, 3,
+            0, 0,
+            0, 0, 0, 0
+        ]),
+        ("127.0.0.1:1002", id_packet(4)),
+        ("127.0.0.1:1003", id_packet(5))
+    ]);
+
+    let mut handler = MockPacketDropServerHandler {
+        connections: Vec::new(),
+        dropped: Vec::new()
+    };
+
+    let mut server = Server::new(Config::default());
+    server.ban_address("127.0.0.2".parse().unwrap(), None);
+    server.ban_connection(ConnectionID(4), None);
+    server.bind_to_socket(&mut handler, socket).unwrap();
+
+    assert_eq!(handler.connections, vec![ConnectionID(1), ConnectionID(5)]);
+
+    // Dropped packets are reported with their cause
+    assert_eq!(handler.dropped, vec![
+        ("127.0.0.2:1000".parse().unwrap(), PacketDropReason::Banned),
+        ("127.0.0.1:1001".parse().unwrap(), PacketDropReason::ProtocolMismatch),
+        ("127.0.0.1:1002".parse().unwrap(), PacketDropReason::Banned)
+    ]);
+
+    assert_eq!(server.security_stats(), SecurityStats {
+        protocol_mismatch: 1,
+        banned: 2,
+        .. SecurityStats::default()
+    });
+    assert_eq!(server.security_stats().total(), 3);
+
+}
+
+#[test]
+fn test_server_dump_state() {
+
+    let socket = MockSocket::from_address("127.0.0.1:0");
+    socket.receive(vec![
+        ("127.0.0.1:1000", id_packet(2)),
+        ("127.0.0.1:1001", id_packet(1))
+    ]);
+
+    let mut server = Server::new(Config::default());
+    server.ban_address("127.0.0.9".parse().unwrap(), None);
+
+    let mut handler = MockStateDumpServerHandler {
+        dump: None
+    };
+    server.bind_to_socket(&mut handler, socket).unwrap();
+
+    let dump = handler.dump.unwrap();
+    assert_eq!(dump.local_addr, Some("127.0.0.1:0".parse().unwrap()));
+    assert_eq!(dump.config, Config::default());
+    assert_eq!(dump.banned_addresses, 1);
+    assert_eq!(dump.banned_connections, 0);
+
+    // Connections are ordered by their id
+    assert_eq!(dump.connections.len(), 2);
+    assert_eq!(dump.connections[0].id, ConnectionID(1));
+    assert_eq!(dump.connections[0].peer_addr, "127.0.0.1:1001".parse().unwrap());
+    assert_eq!(dump.connections[1].id, ConnectionID(2));
+    assert_eq!(dump.connections[1].state, ConnectionState::Connected);
+    assert_eq!(dump.connections[1].packets_received, 1);
+
+    // Dumps can be attached to bug reports
+    #[cfg(feature = "serialization")]
+    {
+        let json = serde_json::to_string(&dump).unwrap();
+        assert_eq!(serde_json::from_str::<ServerStateDump>(&json).unwrap(), dump);
+    }
+
+}
+
+fn id_packet(id: u8) -> Vec<u8> {
+    vec![
+        1, 2, 3, 4,
+        0, 0, 0, id,
+        0, 0,
+        0, 0, 0, 0
+    ]
+}
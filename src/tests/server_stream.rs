@@ -0,0 +1,122 @@
+// Copyright (c) 2015-2016 Ivo Wetzel
+
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use std::thread;
+use std::time::Duration;
+use std::io::ErrorKind;
+use std::sync::mpsc::TryRecvError;
+
+use super::super::{
+    ClientEvent, ClientStream, Config, ConnectionID, MessageKind, Server,
+    ServerEvent, ServerStats, ServerStream
+};
+
+#[test]
+fn test_server_stream() {
+
+    let mut stream = ServerStream::new(Config::default());
+
+    // Check unbound defaults and errors
+    assert_eq!(stream.local_addr().unwrap_err().kind(), ErrorKind::AddrNotAvailable);
+    assert_eq!(stream.aggregate_stats(), ServerStats::default());
+    assert!(stream.connection(ConnectionID(1)).is_none());
+    assert_eq!(stream.receive(), Err(TryRecvError::Disconnected));
+    assert_eq!(stream.flush().unwrap_err().kind(), ErrorKind::NotConnected);
+    assert_eq!(stream.shutdown().unwrap_err().kind(), ErrorKind::NotConnected);
+    assert_eq!(
+        stream.send(ConnectionID(1), MessageKind::Instant, Vec::new()).unwrap_err().kind(),
+        ErrorKind::NotConnected
+    );
+
+    stream.bind("127.0.0.1:0").unwrap();
+    assert_eq!(stream.bind("127.0.0.1:0").unwrap_err().kind(), ErrorKind::AlreadyExists);
+    assert_eq!(stream.receive(), Ok(ServerEvent::Bind));
+    assert_eq!(stream.receive(), Err(TryRecvError::Empty));
+
+    stream.flush().unwrap();
+    assert_eq!(stream.receive(), Ok(ServerEvent::Tick));
+
+    stream.shutdown().unwrap();
+    assert_eq!(stream.receive(), Ok(ServerEvent::Shutdown));
+    assert_eq!(stream.receive(), Err(TryRecvError::Disconnected));
+
+}
+
+#[test]
+fn test_server_stream_from_server() {
+    let mut stream = ServerStream::from_server(Server::new(Config::default()));
+    stream.bind("127.0.0.1:0").unwrap();
+    assert!(stream.local_addr().is_ok());
+}
+
+#[test]
+fn test_server_stream_connection() {
+
+    let config = Config {
+        send_rate: 30,
+        .. Default::default()
+    };
+
+    let mut server = ServerStream::new(config);
+    server.bind("127.0.0.1:0").unwrap();
+    assert_eq!(server.receive(), Ok(ServerEvent::Bind));
+
+    let mut client = ClientStream::new(config);
+    client.connect(server.local_addr().unwrap()).unwrap();
+
+    let mut events = Vec::new();
+    let mut received = Vec::new();
+    for _ in 0..50 {
+
+        while let Ok(event) = server.receive() {
+            match event {
+                ServerEvent::Connection(id) => {
+                    server.send(id, MessageKind::Reliable, b"Welcome".to_vec()).unwrap();
+                    events.push(event);
+                },
+                ServerEvent::Message(id, _) => {
+                    server.kick(id, 7).unwrap();
+                    events.push(event);
+                },
+                ServerEvent::Tick | ServerEvent::MessageDelivered(_, _) => {},
+                _ => events.push(event)
+            }
+        }
+
+        while let Ok(event) = client.receive() {
+            if let ClientEvent::Message(payload) = event {
+                client.send(MessageKind::Instant, b"Thanks".to_vec()).unwrap();
+                received.push(payload);
+            }
+        }
+
+        client.flush().unwrap();
+        server.flush().unwrap();
+        thread::sleep(Duration::from_millis(10));
+
+        if events.len() >= 4 {
+            break;
+        }
+
+    }
+
+    assert_eq!(received, vec![b"Welcome".to_vec()]);
+    assert_eq!(events.len(), 4);
+
+    let id = match events[0] {
+        ServerEvent::Connection(id) => id,
+        _ => unreachable!()
+    };
+
+    assert_eq!(events, vec![
+        ServerEvent::Connection(id),
+        ServerEvent::Message(id, b"Thanks".to_vec()),
+        ServerEvent::ConnectionKicked(id, 7),
+        ServerEvent::ConnectionClosed(id, false)
+    ]);
+
+}
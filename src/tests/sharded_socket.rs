@@ -0,0 +1,96 @@
+// Copyright (c) 2015-2016 Ivo Wetzel
+
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use std::net;
+use std::thread;
+use std::time::Duration;
+use super::super::{Config, ConnectionID, ShardedSocket, Socket};
+
+fn id_packet(id: u8) -> Vec<u8> {
+    vec![
+        1, 2, 3, 4,
+        0, 0, 0, id,
+        0, 0,
+        0, 0, 0, 0
+    ]
+}
+
+fn receive_all(shards: &mut [ShardedSocket]) -> Vec<(usize, net::SocketAddr, Vec<u8>)> {
+    thread::sleep(Duration::from_millis(20));
+    let mut received = Vec::new();
+    for _ in 0..2 {
+        for shard in shards.iter_mut() {
+            while let Ok((addr, packet)) = shard.try_recv() {
+                received.push((shard.shard(), addr, packet));
+            }
+        }
+    }
+    received
+}
+
+#[test]
+fn test_sharded_socket_bind() {
+
+    let shards = ShardedSocket::bind("127.0.0.1:0", 4, Config::default()).unwrap();
+    assert_eq!(shards.len(), 4);
+
+    // All shards share the same port
+    let addr = shards[0].local_addr().unwrap();
+    assert_ne!(addr.port(), 0);
+    for (index, shard) in shards.iter().enumerate() {
+        assert_eq!(shard.shard(), index);
+        assert_eq!(shard.local_addr().unwrap(), addr);
+    }
+
+}
+
+#[test]
+fn test_sharded_socket_directory() {
+
+    let mut shards = ShardedSocket::bind("127.0.0.1:0", 4, Config::default()).unwrap();
+    let addr = shards[0].local_addr().unwrap();
+
+    // Packets of unknown connections are handled by the receiving shard
+    // without entering their id into the directory
+    let client = net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    client.send_to(&id_packet(1), addr).unwrap();
+
+    let received = receive_all(&mut shards);
+    assert_eq!(received.len(), 1);
+    assert_eq!(shards[0].owner(ConnectionID(1)), None);
+
+    // The shard which accepts the connection serves it
+    let owner = received[0].0;
+    shards[owner].register_connection(ConnectionID(1));
+    assert_eq!(shards[0].owner(ConnectionID(1)), Some(owner));
+    assert_eq!(shards[0].owner(ConnectionID(2)), None);
+
+    // Other shards can not take over connections which are still served
+    let other = (owner + 1) % shards.len();
+    shards[other].register_connection(ConnectionID(1));
+    assert_eq!(shards[0].owner(ConnectionID(1)), Some(owner));
+
+    // Packets from other addresses are forwarded to the serving shard,
+    // regardless of which shard the kernel picked
+    let migrated: Vec<net::UdpSocket> = (0..16).map(|_| {
+        net::UdpSocket::bind("127.0.0.1:0").unwrap()
+
+    }).collect();
+
+    for socket in &migrated {
+        socket.send_to(&id_packet(1), addr).unwrap();
+    }
+
+    let received = receive_all(&mut shards);
+    assert_eq!(received.len(), 16);
+    for (shard, from, packet) in received {
+        assert_eq!(shard, owner);
+        assert!(migrated.iter().any(|s| s.local_addr().unwrap() == from));
+        assert_eq!(packet, id_packet(1));
+    }
+
+}
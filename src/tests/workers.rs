@@ -0,0 +1,59 @@
+// Copyright (c) 2015-2016 Ivo Wetzel
+
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use std::net::SocketAddr;
+use super::super::ConnectionID;
+use super::super::shared::workers::WorkerPool;
+use super::mock::create_connection;
+
+#[test]
+fn test_worker_pool_reuses_threads() {
+
+    let pool = WorkerPool::new(3).unwrap();
+    assert_eq!(pool.threads(), 3);
+
+    let threads = pool.thread_ids();
+    let addr: SocketAddr = "127.0.0.1:5678".parse().unwrap();
+    let mut connections: Vec<_> = (0..4).map(|id| {
+        let (mut conn, _, _) = create_connection(None);
+        conn.set_id(ConnectionID(id));
+        conn
+
+    }).collect();
+
+    for _ in 0..5 {
+
+        let results = pool.send(connections.into_iter().map(|conn| (conn, addr)).collect());
+
+        // Connections are returned in order along with their packets
+        let ids: Vec<u32> = results.iter().map(|r| (r.1).id.0).collect();
+        assert_eq!(ids, vec![0, 1, 2, 3]);
+        assert!(results.iter().all(|r| r.1.packets.len() == 1));
+
+        connections = results.into_iter().map(|r| r.0).collect();
+
+    }
+
+    // The same threads process every tick
+    assert_eq!(pool.thread_ids(), threads);
+
+}
+
+#[test]
+fn test_worker_pool_without_threads() {
+
+    let pool = WorkerPool::new(1).unwrap();
+    assert_eq!(pool.threads(), 0);
+
+    // Connections are processed on the calling thread
+    let (conn, _, _) = create_connection(None);
+    let addr: SocketAddr = "127.0.0.1:5678".parse().unwrap();
+    let results = pool.send(vec![(conn, addr)]);
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].1.packets.len(), 1);
+
+}
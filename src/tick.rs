@@ -21,6 +21,47 @@ pub fn end(
     overflow: &mut u32,
     config: &Config
 ) {
+    wait(
+        remaining(tick_delay, tick_start, overflow, config),
+        Duration::from_millis(config.tick_spin_threshold as u64)
+    );
+}
+
+pub fn end_receiving(
+    tick_delay: u32,
+    tick_start: Instant,
+    overflow: &mut u32,
+    config: &Config,
+    receive: &mut dyn FnMut(Duration)
+) {
+
+    let delay = remaining(tick_delay, tick_start, overflow, config);
+    let spin = Duration::from_millis(config.tick_spin_threshold as u64);
+    let deadline = Instant::now() + delay;
+
+    // Wait for and receive packets for most of the remaining time
+    loop {
+        let left = deadline.saturating_duration_since(Instant::now());
+        if left <= spin {
+            break;
+        }
+        receive(left - spin);
+    }
+
+    // Spin for the remainder in order to wake up right on time
+    while Instant::now() < deadline {
+        thread::yield_now();
+    }
+
+}
+
+fn remaining(
+    tick_delay: u32,
+    tick_start: Instant,
+    overflow: &mut u32,
+    config: &Config
+
+) -> Duration {
 
     // Actual time taken by the tick
     let elapsed = tick_start.elapsed();
@@ -55,7 +96,36 @@ pub fn end(
 
     }
 
-    thread::sleep(Duration::new(0, tick_delay - reduction));
+    Duration::new(0, tick_delay - reduction)
+
+}
+
+pub fn jitter(tick_delay: u32, previous_start: Instant, tick_start: Instant) -> u32 {
+
+    // Deviation of the actual tick duration from the desired one
+    let deviation = tick_start.duration_since(previous_start).abs_diff(
+        Duration::new(0, tick_delay)
+    );
+
+    (deviation.as_secs() as u32).saturating_mul(1_000_000).saturating_add(
+        deviation.subsec_micros()
+    )
+
+}
+
+fn wait(delay: Duration, spin: Duration) {
+
+    let deadline = Instant::now() + delay;
+
+    // Sleep for most of the interval, where the coarse timer is precise enough
+    if delay > spin {
+        thread::sleep(delay - spin);
+    }
+
+    // Spin for the remainder in order to wake up right on time
+    while Instant::now() < deadline {
+        thread::yield_now();
+    }
 
 }
 
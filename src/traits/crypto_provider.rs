@@ -0,0 +1,117 @@
+// Copyright (c) 2015-2016 Ivo Wetzel
+
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use std::fmt;
+
+/// Trait for implementation of the cryptographic primitives which are used
+/// for encrypting, authenticating and key exchanging the packets of all
+/// connections.
+///
+/// A provider is installed process wide via `install_crypto_provider()`,
+/// replacing the `DefaultCryptoProvider`. Keys are only ever handed to the
+/// provider, so implementations are free to treat them as handles to keys
+/// which are stored outside of the process.
+///
+/// Both ends of a connection must use compatible providers.
+pub trait CryptoProvider: Send + Sync {
+
+    /// Method that encrypts `data` with the 256-bit `key` and the 96-bit
+    /// `nonce`, authenticating the additional data `aad` along with it.
+    ///
+    /// The returned ciphertext must be exactly 16 bytes longer than `data`
+    /// and end with the authentication tag.
+    ///
+    /// > Note: This method is feature-gated and will only be included when
+    /// > the `encryption` feature is enabled.
+    #[cfg(feature = "encryption")]
+    fn seal(&self, key: &[u8; 32], nonce: &[u8; 12], aad: &[u8], data: &[u8]) -> Vec<u8>;
+
+    /// Method that decrypts `sealed` data previously returned from
+    /// `CryptoProvider::seal()`, returning `None` in case either the data or
+    /// `aad` fail to authenticate.
+    ///
+    /// > Note: This method is feature-gated and will only be included when
+    /// > the `encryption` feature is enabled.
+    #[cfg(feature = "encryption")]
+    fn open(&self, key: &[u8; 32], nonce: &[u8; 12], aad: &[u8], sealed: &[u8]) -> Option<Vec<u8>>;
+
+    /// Method that returns the 256-bit message authentication code of `data`
+    /// with the specified `key`.
+    ///
+    /// > Note: This method is feature-gated and will only be included when
+    /// > the `authentication` feature is enabled.
+    #[cfg(feature = "authentication")]
+    fn mac(&self, key: &[u8; 32], data: &[u8]) -> [u8; 32];
+
+    /// Method that returns the 256-bit public key which belongs to the
+    /// private signing `key`.
+    ///
+    /// > Note: This method is feature-gated and will only be included when
+    /// > the `authentication` feature is enabled.
+    #[cfg(feature = "authentication")]
+    fn signing_public_key(&self, key: &[u8; 32]) -> [u8; 32];
+
+    /// Method that returns the 512-bit signature of `data` with the private
+    /// signing `key`.
+    ///
+    /// > Note: This method is feature-gated and will only be included when
+    /// > the `authentication` feature is enabled.
+    #[cfg(feature = "authentication")]
+    fn sign(&self, key: &[u8; 32], data: &[u8]) -> [u8; 64];
+
+    /// Method that verifies the `signature` of `data` with the specified
+    /// `public_key`.
+    ///
+    /// > Note: This method is feature-gated and will only be included when
+    /// > the `authentication` feature is enabled.
+    #[cfg(feature = "authentication")]
+    fn verify(&self, public_key: &[u8; 32], data: &[u8], signature: &[u8; 64]) -> bool;
+
+    /// Method that returns a fresh, ephemeral key agreement for a single
+    /// connection.
+    ///
+    /// > Note: This method is feature-gated and will only be included when
+    /// > the `key_exchange` feature is enabled.
+    #[cfg(feature = "key_exchange")]
+    fn key_agreement(&self) -> Box<dyn KeyAgreement>;
+
+}
+
+impl fmt::Debug for dyn CryptoProvider {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "CryptoProvider")
+    }
+}
+
+/// Trait for implementation of an ephemeral Diffie-Hellman key agreement
+/// which is returned from `CryptoProvider::key_agreement()`.
+///
+/// > Note: This trait is feature-gated and will only be included when the
+/// > `key_exchange` feature is enabled.
+#[cfg(feature = "key_exchange")]
+pub trait KeyAgreement: Send {
+
+    /// Method that returns the 32-byte public key which is send to the
+    /// remote.
+    fn public_key(&self) -> [u8; 32];
+
+    /// Method that consumes the agreement with the public key of the
+    /// remote, returning the raw shared secret.
+    ///
+    /// Should return `None` in case the remote's public key is invalid or
+    /// does not contribute to the secret.
+    fn agree(self: Box<Self>, remote: [u8; 32]) -> Option<[u8; 32]>;
+
+}
+
+#[cfg(feature = "key_exchange")]
+impl fmt::Debug for dyn KeyAgreement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "KeyAgreement")
+    }
+}
+
@@ -5,22 +5,88 @@
 // <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
-use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
 use super::super::{
-    BinaryRateLimiter, Connection, ConnectionID, Config, RateLimiter
+    BinaryRateLimiter, Connection, ConnectionID, ConnectionMap, Config, HostMigration,
+    IdCollisionPolicy, IdGenerator, MessageHandle, OsIdGenerator, PacketDropReason, RateLimiter, Stats
 };
 
+/// Decision of a `Handler` about a packet which would create a new connection
+/// on a `Server`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Admission {
+    /// A new connection is created for the packet.
+    Accept,
+    /// The packet is dropped without creating a connection.
+    Reject
+}
+
+/// Cause for which a connection attempt from a remote address failed on a
+/// `Server`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ConnectionFailure {
+    /// The connection was created but its remote did not acknowledge any of
+    /// its packets within `Config::pending_connection_timeout`.
+    HandshakeTimeout,
+    /// The attempt was rejected by `Config::whitelist_only`,
+    /// `Config::max_connections_per_ip`, `IdGenerator::validate` or
+    /// `Handler::connection_request`.
+    Denied,
+    /// The attempt used a different protocol header, i.e. from an
+    /// incompatible version.
+    ProtocolMismatch,
+    /// The server already reached `Config::max_connections`.
+    ServerFull
+}
+
+/// Subject of a ban on a `Server`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum BanTarget {
+    /// All packets from the IP address are dropped.
+    Address(IpAddr),
+    /// All packets for the connection id are dropped.
+    Connection(ConnectionID)
+}
+
 /// Trait for implementation of a client / server event proxy.
+///
+/// Every callback is passed the `Client` or `Server` which invokes it as its
+/// context. Within server callbacks which are only passed a single
+/// `Connection`, messages for any other connection can be queued by id via
+/// `Server::send`, `Server::broadcast` and friends. Messages queued during a
+/// tick are send with the packets of that same tick.
 pub trait Handler<T> {
 
     // Factories
 
     /// Method that returns a new `RateLimiter` instance for use with a
     /// freshly instantiated `Connection`.
-    fn rate_limiter(&self, config: &Config) -> Box<RateLimiter> {
+    fn rate_limiter(&self, config: &Config) -> Box<dyn RateLimiter> {
         BinaryRateLimiter::new(config)
     }
 
+    /// Method that returns a new `IdGenerator` instance which provides the
+    /// id of a freshly instantiated client `Connection`, or validates the ids
+    /// of new connections when binding a `Server`.
+    fn id_generator(&self, _: &Config) -> Box<dyn IdGenerator> {
+        OsIdGenerator::new()
+    }
+
+    // Tick phases
+
+    /// Method that is called each tick once all incoming packets have been
+    /// received and before `tick_connections` / `tick_connection` is
+    /// invoked.
+    fn after_receive(&mut self, _: &mut T) {
+    }
+
+    /// Method that is called each tick after `tick_connections` /
+    /// `tick_connection` was invoked and before any outgoing packets are
+    /// send.
+    fn before_send(&mut self, _: &mut T) {
+    }
+
     // Server only
 
     /// Method that is called once a `Server` has successfully bound itself
@@ -31,10 +97,87 @@ pub trait Handler<T> {
     /// Method that is called each time a `Server` "ticks". A "tick" occurs
     /// in-between the receiving and sending data from / to connections.
     fn tick_connections(
-        &mut self, _: &mut T, _: &mut HashMap<ConnectionID, Connection>
+        &mut self, _: &mut T, _: &mut ConnectionMap
     ) {
     }
 
+    /// Method that is called each tick by a `Server` with `host_migration`
+    /// enabled, returning the session state which should be shared with all
+    /// connected peers, if any.
+    ///
+    /// The state is only send when it differs from the one returned during
+    /// the previous tick, and to newly connected peers. The latest state is
+    /// handed over to the new host once the server's connections are lost.
+    fn session_state(&mut self, _: &mut T) -> Option<Vec<u8>> {
+        None
+    }
+
+    /// Method that is called each time a `Server` receives a packet which
+    /// would create a new connection with the passed in id, right before the
+    /// connection is created, i.e. in order to check the address against an
+    /// external service.
+    ///
+    /// The packet is passed in unparsed. Rejected packets are dropped without
+    /// a response.
+    fn connection_request(
+        &mut self, _: &mut T, _: SocketAddr, _: ConnectionID, _: &[u8]
+
+    ) -> Admission {
+        Admission::Accept
+    }
+
+    /// Method that is called each time a `Server` drops a packet which would
+    /// create a new connection with the passed in id, since its address
+    /// already reached `Config::max_connections_per_ip`.
+    fn connection_limit_exceeded(&mut self, _: &mut T, _: SocketAddr, _: ConnectionID) {
+    }
+
+    /// Method that is called each time a `Server` denies a packet which would
+    /// create a new connection with the passed in id, since it already
+    /// reached `Config::max_connections`.
+    fn server_full(&mut self, _: &mut T, _: SocketAddr, _: ConnectionID) {
+    }
+
+    /// Method that is called each time a `Server` fails an attempt of the
+    /// passed in address to establish a new connection, i.e. in order to log
+    /// failed handshakes.
+    ///
+    /// Attempts which are refused outright are reported once for each of
+    /// their packets, after any more specific method like
+    /// `Handler::server_full` was invoked. Connections which time out are
+    /// reported right after `Handler::connection_failed`.
+    fn connection_attempt_failed(&mut self, _: &mut T, _: SocketAddr, _: ConnectionFailure) {
+    }
+
+    /// Method that is called by a `Server` once per tick for each IP address
+    /// which exceeded `Config::flood_packet_rate`, with the number of its
+    /// packets which were dropped during the tick.
+    fn source_throttled(&mut self, _: &mut T, _: IpAddr, _: u32) {
+    }
+
+    /// Method that is called by a `Server` once per second with its
+    /// statistics over the last second, i.e. in order to spot mass
+    /// disconnects via the connection churn counters.
+    fn stats_tick(&mut self, _: &mut T, _: Stats) {
+    }
+
+    /// Method that is called each time a received packet from the passed in
+    /// address is dropped for the specified reason, either by a `Server` or
+    /// by a connection.
+    fn packet_dropped(&mut self, _: &mut T, _: SocketAddr, _: PacketDropReason) {
+    }
+
+    /// Method that is called each time a `Server` answers an unconnected
+    /// server info query, returning the info which is send back, i.e. the
+    /// name, map and player count of a game server.
+    ///
+    /// Queries are ignored in case `None` is returned or the info exceeds the
+    /// size limit of `Connection::server_info_packet`. At most one query per
+    /// IP address is answered each second.
+    fn server_info(&mut self, _: &mut T) -> Option<Vec<u8>> {
+        None
+    }
+
     /// Method that is called once a `Server` is going to shutdown.
     fn shutdown(&mut self, _: &mut T) {
     }
@@ -55,12 +198,86 @@ pub trait Handler<T> {
     fn close(&mut self, _: &mut T) {
     }
 
+    /// Method that is called by a `Client` each time it bound a fresh local
+    /// socket for its connection after sending failed with
+    /// `ErrorKind::AddrNotAvailable` because its local address went away,
+    /// with the previous local address.
+    ///
+    /// The socket is bound the same way as when connecting, honoring
+    /// `Client::set_bind_addr` and `Client::set_bind_ports`.
+    ///
+    /// The server migrates the connection to the new address once it receives
+    /// the next packet, validating it first with `Config::migration_validation`.
+    fn connection_rebound(&mut self, _: &mut T, _: &mut Connection, _: SocketAddr) {
+    }
+
+    /// Method that is called by a `Client` once one of its connection
+    /// attempts to the several addresses of a host name completed its
+    /// handshake, with the address which was selected.
+    ///
+    /// This is called right after `connection` respectively
+    /// `connection_resumed`.
+    fn connection_address_selected(&mut self, _: &mut T, _: &mut Connection, _: SocketAddr) {
+    }
+
     // Connection specific
 
     /// Method that is called each time a new connection is established.
     fn connection(&mut self, _: &mut T, _: &mut Connection) {
     }
 
+    /// Method that is called each time a previously lost connection is
+    /// resumed, instead of a new connection being established.
+    fn connection_resumed(&mut self, _: &mut T, _: &mut Connection) {
+    }
+
+    /// Method that is called each time a connection has been validated to
+    /// have migrated from its previous remote address, which is passed in as
+    /// well.
+    fn connection_migrated(&mut self, _: &mut T, _: &mut Connection, _: SocketAddr) {
+    }
+
+    /// Method that is called by a `Server` each time a packet for an
+    /// established connection arrives from a different address, which is
+    /// passed in as well, before the `Config::address_change_policy` is
+    /// applied.
+    ///
+    /// With `AddressChangePolicy::Reconnect`, the connection is closed via
+    /// `connection_closed` and dropped once this method returns.
+    fn connection_address_conflict(&mut self, _: &mut T, _: &mut Connection, _: SocketAddr) {
+    }
+
+    /// Method that is called by a `Server` each time a peer at the passed in
+    /// address answered the migration challenge of a connection with an
+    /// invalid proof, i.e. since it reuses the connection's id.
+    ///
+    /// The returned policy is applied with `IdCollisionPolicy::Handler`,
+    /// where returning `IdCollisionPolicy::Handler` again is treated as
+    /// `IdCollisionPolicy::RejectNew`.
+    fn connection_id_collision(
+        &mut self, _: &mut T, _: &mut Connection, _: SocketAddr
+
+    ) -> IdCollisionPolicy {
+        IdCollisionPolicy::RejectNew
+    }
+
+    /// Method that is called by a `Server` each time a connection to a banned
+    /// address or with a banned `ConnectionID` is closed, right before its
+    /// closure is initiated.
+    fn connection_banned(&mut self, _: &mut T, _: &mut Connection) {
+    }
+
+    /// Method that is called by a `Server` with its next tick each time a
+    /// ban was applied via `Server::ban_address` or `Server::ban_connection`,
+    /// along with its duration, `None` for permanent bans.
+    fn ban_applied(&mut self, _: &mut T, _: BanTarget, _: Option<Duration>) {
+    }
+
+    /// Method that is called by a `Server` each time a ban was lifted, either
+    /// explicitly or since its duration expired.
+    fn ban_lifted(&mut self, _: &mut T, _: BanTarget) {
+    }
+
     /// Method that is called each time a connection fails to establish.
     fn connection_failed(&mut self, _: &mut T, _: &mut Connection) {
     }
@@ -70,20 +287,60 @@ pub trait Handler<T> {
     fn connection_congestion_state(&mut self, _: &mut T, _: &mut Connection, _: bool) {
     }
 
+    /// Method that is called each time the unconsumed data received over a
+    /// connection exceeds or falls back below its receive watermark, see
+    /// `Connection::set_receive_watermark()`.
+    fn connection_receive_pressure(&mut self, _: &mut T, _: &mut Connection, _: bool) {
+    }
+
+    /// Method that is called each time a request to keep a connection alive
+    /// without receiving any packets was answered, see
+    /// `Connection::request_keep_alive()`, with the number of milliseconds
+    /// which were granted.
+    ///
+    /// This is called on both ends of the connection, with `0` in case the
+    /// request was declined.
+    fn connection_keep_alive(&mut self, _: &mut T, _: &mut Connection, _: u32) {
+    }
+
+    /// Method that is called each time a connection to the host of a session
+    /// is lost, right before `connection_lost` is invoked, with the outcome
+    /// of the host election among the session's remaining peers.
+    fn connection_host_migration(
+        &mut self, _: &mut T, _: &mut Connection, _: HostMigration
+    ) {
+    }
+
     /// Method that is called each time a connection is lost and dropped.
     fn connection_lost(&mut self, _: &mut T, _: &mut Connection) {
     }
 
+    /// Method that is called each time a connection is closed with a reason
+    /// code, either locally via `Server::kick` or by the remote via
+    /// `Connection::close_with_reason`.
+    fn connection_kicked(&mut self, _: &mut T, _: &mut Connection, _: u8) {
+    }
+
     /// Method that is called each time a connection is programmatically closed.
     fn connection_closed(&mut self, _: &mut T, _: &mut Connection, _: bool) {
     }
 
+    // Message specific
+
+    /// Method that is called each time a `Reliable` or `Ordered` message
+    /// send over a connection has been acknowledged by the remote, with the
+    /// handle that was returned when the message was send.
+    fn connection_message_delivered(
+        &mut self, _: &mut T, _: &mut Connection, _: MessageHandle
+    ) {
+    }
+
     // Packet specific
 
     /// Method that is called each time a packet send by a connection is lost.
     ///
     /// > Note: This method is feature-gated and will only be included when the
-    /// `packet_handler_lost` feature is enabled.
+    /// > `packet_handler_lost` feature is enabled.
     fn connection_packet_lost(
         &mut self, _: &mut T, _: &mut Connection, _: &[u8]
     ) {
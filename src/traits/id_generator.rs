@@ -0,0 +1,43 @@
+// Copyright (c) 2015-2016 Ivo Wetzel
+
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use std::fmt;
+use super::super::ConnectionID;
+
+/// Trait for implementation of a generator for the ids of new connections.
+///
+/// Clients use the generator returned by `Handler::id_generator` to pick the
+/// id of their connection, while servers use theirs to validate the ids of
+/// incoming connection attempts. Implementing both methods allows for ids
+/// which are derived from i.e. account identifiers or tokens, so a client
+/// which reconnects ends up with the same logical session.
+pub trait IdGenerator: Send {
+
+    /// Method that returns the id for a freshly instantiated `Connection`.
+    ///
+    /// Ids should be unpredictable, since any packet carrying the id of a
+    /// connection is considered to belong to it.
+    fn generate(&mut self) -> ConnectionID;
+
+    /// Method that is called by a `Server` for each packet which would create
+    /// a new connection with the passed in id, returning whether the id is
+    /// acceptable.
+    ///
+    /// Packets with rejected ids are dropped without a response and
+    /// reported as `ConnectionFailure::Denied`. Accepts all ids by default.
+    fn validate(&mut self, _: ConnectionID) -> bool {
+        true
+    }
+
+}
+
+impl fmt::Debug for dyn IdGenerator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "IdGenerator")
+    }
+}
+
@@ -8,7 +8,10 @@
 use std::fmt;
 
 /// Trait for implementation of a network congestion avoidance algorithm.
-pub trait RateLimiter {
+///
+/// Rate limiters must be `Send`, since a server may process its connections
+/// on worker threads.
+pub trait RateLimiter: Send {
 
     /// Method implementing a congestion avoidance algorithm based on round
     /// trip time and packet loss.
@@ -28,7 +31,7 @@ pub trait RateLimiter {
 
 }
 
-impl fmt::Debug for RateLimiter {
+impl fmt::Debug for dyn RateLimiter {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "RateLimiter")
     }
@@ -5,9 +5,13 @@
 // <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
+use std::cmp;
 use std::net;
-use std::io::Error;
+use std::thread;
+use std::io::{Error, ErrorKind};
 use std::sync::mpsc::TryRecvError;
+use std::time::Duration;
+use super::super::ConnectionID;
 
 /// Trait for implementation of a non-blocking UDP socket.
 pub trait Socket {
@@ -26,5 +30,32 @@ pub trait Socket {
     /// Method returning the address of the actual, underlying socket.
     fn local_addr(&self) -> Result<net::SocketAddr, Error>;
 
+    /// Method blocking the calling thread until a packet can be received or
+    /// the `timeout` elapsed, i.e. for receiving packets in between ticks.
+    ///
+    /// The default implementation sleeps for at most one millisecond, which
+    /// makes the caller poll the socket in short intervals.
+    fn wait(&mut self, timeout: Duration) {
+        thread::sleep(cmp::min(timeout, Duration::from_millis(1)));
+    }
+
+    /// Method replacing the underlying socket with one freshly bound to the
+    /// specified local address, i.e. after the local network changed,
+    /// returning its new local address.
+    ///
+    /// Sockets which cannot be rebound return an error of kind
+    /// `Unsupported`.
+    fn rebind(&mut self, _: net::SocketAddr) -> Result<net::SocketAddr, Error> {
+        Err(Error::new(ErrorKind::Unsupported, ""))
+    }
+
+    /// Method that is called by a `Server` each time it accepted a new
+    /// connection with the specified id, i.e. for sockets which route the
+    /// packets of each connection to the server serving it.
+    ///
+    /// The default implementation does nothing.
+    fn register_connection(&mut self, _: ConnectionID) {
+    }
+
 }
 
@@ -1,5 +1,6 @@
 extern crate cobalt;
 
+use std::io::ErrorKind;
 use std::net;
 use std::thread;
 use std::time::Duration;
@@ -37,6 +38,7 @@ fn test_server_bind_and_shutdown() {
     server.bind(&mut handler, "127.0.0.1:0").unwrap();
 
     assert_eq!(handler.bind_calls, 1);
+    assert!(handler.local_addr.unwrap().port() != 0);
     assert!(handler.tick_connections_calls > 0);
     assert_eq!(handler.shutdown_calls, 1);
 
@@ -48,6 +50,28 @@ fn test_server_bind_and_shutdown() {
 
 }
 
+#[test]
+fn test_server_bind_range() {
+
+    // Occupy the first port of the range
+    let occupied = net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    let port = occupied.local_addr().unwrap().port();
+
+    let mut handler = MockServerHandler::new(1, false);
+    let mut server = Server::new(Config::default());
+    server.bind_range(&mut handler, [127, 0, 0, 1], port..=port.saturating_add(10)).unwrap();
+
+    let bound = handler.local_addr.unwrap().port();
+    assert!(bound > port && bound <= port.saturating_add(10));
+
+    // Fail if no port in the range is available
+    let mut handler = MockServerHandler::new(1, false);
+    let err = server.bind_range(&mut handler, [127, 0, 0, 1], port..=port).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::AddrInUse);
+    assert_eq!(handler.bind_calls, 0);
+
+}
+
 #[test]
 fn test_server_client_connection_lost() {
 
@@ -59,7 +83,7 @@ fn test_server_client_connection_lost() {
     };
 
     // Setup Test Server
-    let server_address = address.clone();
+    let server_address = address;
     let server_thread = thread::spawn(move|| {
 
         let config = Config::default();
@@ -112,7 +136,7 @@ fn test_server_client_connection_close() {
     };
 
     // Setup Test Server
-    let server_address = address.clone();
+    let server_address = address;
     let server_thread = thread::spawn(move|| {
 
         let config = Config {
@@ -127,7 +151,7 @@ fn test_server_client_connection_close() {
         assert_eq!(server_handler.connection_lost_calls, 0);
         assert_eq!(server_handler.connection_calls, 1);
         assert_eq!(server_handler.connection_closed_calls, 1);
-        assert_eq!(server_handler.closed_by_remote, false);
+        assert!(!server_handler.closed_by_remote);
 
     });
 
@@ -153,7 +177,7 @@ fn test_server_client_connection_close() {
     assert_eq!(client_handler.connection_lost_calls, 0);
     assert_eq!(client_handler.connection_calls, 1);
     assert_eq!(client_handler.connection_closed_calls, 1);
-    assert_eq!(client_handler.closed_by_remote, true);
+    assert!(client_handler.closed_by_remote);
 
 }
 
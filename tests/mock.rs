@@ -1,5 +1,6 @@
 extern crate cobalt;
 use std::collections::HashMap;
+use std::net::SocketAddr;
 use cobalt::{Client, Connection, ConnectionID, Handler, Server};
 
 
@@ -18,6 +19,12 @@ pub struct MockClientHandler {
     pub closed_by_remote: bool
 }
 
+impl Default for MockClientHandler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl MockClientHandler {
     pub fn new() -> MockClientHandler {
         MockClientHandler {
@@ -92,6 +99,7 @@ pub struct MockServerHandler {
     pub shutdown_calls: u32,
     pub tick_connections_calls: u32,
     pub bind_calls: u32,
+    pub local_addr: Option<SocketAddr>,
 
     pub connection_calls: u32,
     pub connection_failed_calls: u32,
@@ -105,12 +113,13 @@ pub struct MockServerHandler {
 impl MockServerHandler {
     pub fn new(shutdown_ticks: u32, close_connection: bool) -> MockServerHandler {
         MockServerHandler {
-            shutdown_ticks: shutdown_ticks,
-            close_connection: close_connection,
+            shutdown_ticks,
+            close_connection,
 
             shutdown_calls: 0,
             tick_connections_calls: 0,
             bind_calls: 0,
+            local_addr: None,
 
             connection_calls: 0,
             connection_failed_calls: 0,
@@ -125,8 +134,9 @@ impl MockServerHandler {
 
 impl Handler<Server> for MockServerHandler {
 
-    fn bind(&mut self, _: &mut Server) {
+    fn bind(&mut self, server: &mut Server) {
         self.bind_calls += 1;
+        self.local_addr = server.local_addr().ok();
     }
 
     fn tick_connections(
@@ -141,7 +151,7 @@ impl Handler<Server> for MockServerHandler {
 
         // only advance until initial connection close
         // when there is a connection
-        if connections.len() == 0 && self.close_connection {
+        if connections.is_empty() && self.close_connection {
             self.tick_connections_calls = 0;
         }
 